@@ -0,0 +1,147 @@
+//! benchmark for serving documents: blob lookup, HTML rewriting and versions queries
+#![allow(clippy::self_named_module_files)]
+#![allow(clippy::implicit_return)]
+#![allow(clippy::expect_used)]
+#![allow(missing_docs)]
+
+use actix_web::rt::System;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use stelae::db::{self, models::document_change::Manager as _, DatabaseConnection};
+use stelae::utils::git::Repo;
+use stelae::utils::html::prefix_root_relative_urls;
+
+/// Number of document elements seeded into the versions benchmark database.
+const ELEMENTS: usize = 1_000;
+
+/// Number of publication versions seeded into the versions benchmark database.
+const VERSIONS: usize = 20;
+
+/// get the path to the test archive at `$REPO_ROOT/tests/fixtures/basic/archive`.
+fn get_test_archive_path() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/basic/archive");
+    let repo_path = path.join("test/law-html");
+    create_dir_all(repo_path.join("refs/heads"))
+        .expect("Something went wrong creating the refs/heads folder");
+    create_dir_all(repo_path.join("refs/tags"))
+        .expect("Something went wrong creating the refs/tags folder");
+    path
+}
+
+/// A synthetic HTML document with many root-relative links.
+fn large_document() -> Vec<u8> {
+    let mut html = String::from("<html><head><link href=\"/styles.css\"></head><body>");
+    for section in 0..2_000 {
+        html.push_str(&format!(
+            "<p id=\"s{section}\">See <a href=\"/us/ca/codes/{section}/\">section {section}</a> \
+             and <a href=\"https://example.com/{section}\">elsewhere</a>.</p>"
+        ));
+    }
+    html.push_str("</body></html>");
+    html.into_bytes()
+}
+
+/// Seed a database with one document of `ELEMENTS` elements, each changed in every
+/// one of `VERSIONS` publication versions.
+async fn seed_database(db: &DatabaseConnection) {
+    let statements = [
+        "INSERT INTO stele(name) VALUES ('test/law')".to_owned(),
+        "INSERT INTO publication(id, name, date, stele, revoked) VALUES ('p1', 'p1', '2024-01-01', 'test/law', 0)".to_owned(),
+        "INSERT INTO document(doc_id) VALUES ('d1')".to_owned(),
+    ];
+    for statement in statements {
+        sqlx::query(&statement)
+            .execute(&db.pool)
+            .await
+            .expect("Something went wrong seeding the database");
+    }
+    for version in 0..VERSIONS {
+        let date = format!("2023-01-{:02}", version + 1);
+        for statement in [
+            "INSERT INTO version(codified_date) VALUES ($1)",
+            "INSERT INTO publication_version(id, version, publication_id) VALUES ($1, $1, 'p1')",
+            "INSERT INTO publication_has_publication_versions(publication_id, publication_version_id) VALUES ('p1', $1)",
+        ] {
+            sqlx::query(statement)
+                .bind(&date)
+                .execute(&db.pool)
+                .await
+                .expect("Something went wrong seeding the database");
+        }
+    }
+    for element in 0..ELEMENTS {
+        let mpath = format!("|d1|s{element}|");
+        sqlx::query("INSERT INTO document_element(doc_mpath, url, doc_id, stele) VALUES ($1, $2, 'd1', 'test/law')")
+            .bind(&mpath)
+            .bind(format!("/us/ca/codes/{element}/"))
+            .execute(&db.pool)
+            .await
+            .expect("Something went wrong seeding the database");
+        for version in 0..VERSIONS {
+            let date = format!("2023-01-{:02}", version + 1);
+            sqlx::query("INSERT INTO document_change(id, status, publication_version_id, doc_mpath) VALUES ($1, $2, $3, $4)")
+                .bind(format!("{mpath}{date}"))
+                .bind(i64::from(version == 0))
+                .bind(date)
+                .bind(&mpath)
+                .execute(&db.pool)
+                .await
+                .expect("Something went wrong seeding the database");
+        }
+    }
+}
+
+/// Measure the speed of looking up a blob, including the `index.html` fallback.
+fn bench_find_blob(c: &mut Criterion) {
+    let archive_path = get_test_archive_path();
+    c.bench_function("find_blob", |b| {
+        b.iter(|| {
+            Repo::find_blob(&archive_path, "test", "law-html", "a/b/", "HEAD")
+                .expect("Something went wrong calling `find_blob`")
+        });
+    });
+}
+
+/// Measure the speed of rewriting root-relative URLs of a large document.
+fn bench_prefix_root_relative_urls(c: &mut Criterion) {
+    let html = large_document();
+    c.bench_function("prefix_root_relative_urls", |b| {
+        b.iter(|| {
+            prefix_root_relative_urls(&html, "/_archive/2023-01-01")
+                .expect("Something went wrong calling `prefix_root_relative_urls`")
+        });
+    });
+}
+
+/// Measure the speed of the versions query of a document element.
+fn bench_versions_query(c: &mut Criterion) {
+    let dir = tempfile::tempdir().expect("Something went wrong creating a temporary directory");
+    create_dir_all(dir.path().join(".taf")).expect("Something went wrong creating .taf");
+    let system = System::new();
+    let db = system.block_on(async {
+        let connection = db::init::connect(dir.path())
+            .await
+            .expect("Something went wrong connecting to the database");
+        seed_database(&connection).await;
+        connection
+    });
+    let mpath = format!("|d1|s{}|", ELEMENTS / 2);
+    c.bench_function("find_all_document_versions_by_mpath_and_publication", |b| {
+        b.iter(|| {
+            system
+                .block_on(db.find_all_document_versions_by_mpath_and_publication(&mpath, "p1"))
+                .expect("Something went wrong querying versions")
+        });
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_find_blob,
+    bench_prefix_root_relative_urls,
+    bench_versions_query
+);
+
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Fuzz targets for the parsing of untrusted request input.
+//!
+//! Every target takes arbitrary bytes and must never panic. The targets are run by
+//! `cargo fuzz`, and the corpus in `fuzz/corpus/{target}` is replayed by the test suite.
+use std::str;
+
+use stelae::history::links::resolve_href;
+use stelae::server::api::versions::response::{messages, Version, VersionList};
+use stelae::utils::date;
+use stelae::utils::html::{find_link_hrefs, prefix_root_relative_urls};
+use stelae::utils::locale::Locale;
+use stelae::utils::{http, paths};
+
+/// A fuzz target, run for each input.
+pub type Target = fn(&[u8]);
+
+/// Names of the targets, with the function run for each input.
+pub const TARGETS: [(&str, Target); 4] = [
+    ("clean_path", clean_path),
+    ("get_contenttype", get_contenttype),
+    ("version_query", version_query),
+    ("html_rewriter", html_rewriter),
+];
+
+/// Clean and normalize an arbitrary request path.
+pub fn clean_path(data: &[u8]) {
+    let Ok(path) = str::from_utf8(data) else {
+        return;
+    };
+    let cleaned = paths::clean_path(path);
+    assert!(!cleaned.starts_with('/') && !cleaned.ends_with('/'));
+    if let Ok(normalized) = paths::normalize_path(path) {
+        assert!(normalized.len() <= paths::MAX_PATH_LENGTH);
+        assert!(!normalized.split('/').any(|segment| segment == ".."));
+    }
+}
+
+/// Guess the content type of an arbitrary request path.
+pub fn get_contenttype(data: &[u8]) {
+    let Ok(path) = str::from_utf8(data) else {
+        return;
+    };
+    let _content_type = http::get_contenttype(path);
+}
+
+/// Build the historical messages of the versions endpoint from arbitrary dates.
+///
+/// The first line is the requested date, the second line the date to compare to,
+/// and the remaining lines the dates of the document's versions.
+pub fn version_query(data: &[u8]) {
+    let Ok(input) = str::from_utf8(data) else {
+        return;
+    };
+    let mut parsed = input.lines().map(|line| date::parse(line).ok());
+    let version_date = parsed.next().flatten();
+    let compare_to_date = parsed.next().flatten();
+    let mut versions: VersionList = parsed
+        .flatten()
+        .map(|found| Version::new(found.into(), date::format(found), 0))
+        .collect();
+    for inserted_date in [version_date, compare_to_date].into_iter().flatten() {
+        versions.insert_date(inserted_date);
+    }
+    let _messages = messages::historical(
+        &versions,
+        "current",
+        "active",
+        version_date,
+        compare_to_date,
+        "/a/b/c",
+        Locale::En,
+    );
+}
+
+/// Rewrite the urls of, and check the links in, an arbitrary html document.
+pub fn html_rewriter(data: &[u8]) {
+    let _rewritten = prefix_root_relative_urls(data, "/_date/2023-10-22");
+    if let Ok(hrefs) = find_link_hrefs(data) {
+        for href in hrefs {
+            let _path = resolve_href("us/ca/codes/1.01/index.html", &href);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+DROP TABLE IF EXISTS ingest_errors;
+
+PRAGMA optimize;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE ingest_errors (
+    stele TEXT,
+    publication TEXT,
+    file_path TEXT,
+    context TEXT,
+    message TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE,
+    PRIMARY KEY (stele, publication, file_path)
+);
+
+PRAGMA optimize;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+DROP TABLE IF EXISTS snapshot_commits;
+DROP TABLE IF EXISTS snapshots;
+
+PRAGMA optimize;
@@ -0,0 +1,44 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE snapshots (
+    name TEXT PRIMARY KEY,
+    stele TEXT,
+    publication TEXT,
+    date TEXT,
+    auth_commit_hash TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE snapshot_commits (
+    snapshot TEXT,
+    repository TEXT,
+    repo_type TEXT,
+    commit_hash TEXT,
+    CONSTRAINT fk_snapshot
+        FOREIGN KEY (snapshot)
+        REFERENCES snapshots(name),
+    PRIMARY KEY (snapshot, repository)
+);
+
+CREATE TRIGGER snapshots_no_update BEFORE UPDATE ON snapshots
+BEGIN
+    SELECT RAISE(ABORT, 'snapshots are immutable');
+END;
+
+CREATE TRIGGER snapshots_no_delete BEFORE DELETE ON snapshots
+BEGIN
+    SELECT RAISE(ABORT, 'snapshots are immutable');
+END;
+
+CREATE TRIGGER snapshot_commits_no_update BEFORE UPDATE ON snapshot_commits
+BEGIN
+    SELECT RAISE(ABORT, 'snapshots are immutable');
+END;
+
+CREATE TRIGGER snapshot_commits_no_delete BEFORE DELETE ON snapshot_commits
+BEGIN
+    SELECT RAISE(ABORT, 'snapshots are immutable');
+END;
+
+PRAGMA optimize;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE broken_links (
+    stele TEXT,
+    publication TEXT,
+    commit_hash TEXT,
+    source_path TEXT,
+    href TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE,
+    PRIMARY KEY (stele, publication, source_path, href)
+);
+
+PRAGMA optimize;
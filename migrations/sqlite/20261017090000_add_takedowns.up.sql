@@ -0,0 +1,12 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE takedowns (
+    stele TEXT,
+    path TEXT,
+    reason TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (stele, path)
+);
+
+PRAGMA optimize;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+DROP INDEX IF EXISTS identifiers_path_idx;
+DROP TABLE IF EXISTS identifiers;
+
+PRAGMA optimize;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE identifiers (
+    identifier TEXT NOT NULL,
+    stele TEXT NOT NULL,
+    path TEXT NOT NULL,
+    PRIMARY KEY (stele, identifier),
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE
+);
+CREATE INDEX identifiers_path_idx ON identifiers(path);
+
+PRAGMA optimize;
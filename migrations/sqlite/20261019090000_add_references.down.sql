@@ -0,0 +1,7 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+DROP INDEX IF EXISTS references_target_idx;
+DROP TABLE IF EXISTS "references";
+
+PRAGMA optimize;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE "references" (
+    stele TEXT,
+    source_path TEXT,
+    target_path TEXT,
+    href TEXT,
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE,
+    PRIMARY KEY (stele, source_path, target_path, href)
+);
+CREATE INDEX references_target_idx ON "references"(stele, target_path);
+
+PRAGMA optimize;
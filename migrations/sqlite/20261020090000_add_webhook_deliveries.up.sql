@@ -0,0 +1,18 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE webhook_deliveries (
+    stele TEXT,
+    publication TEXT,
+    url TEXT,
+    attempts INTEGER,
+    status INTEGER,
+    error TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE
+);
+
+PRAGMA optimize;
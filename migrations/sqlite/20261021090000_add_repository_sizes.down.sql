@@ -0,0 +1,7 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+DROP INDEX IF EXISTS repository_sizes_stele_repository_idx;
+DROP TABLE IF EXISTS repository_sizes;
+
+PRAGMA optimize;
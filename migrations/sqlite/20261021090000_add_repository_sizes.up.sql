@@ -0,0 +1,13 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE repository_sizes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    stele TEXT,
+    repository TEXT,
+    bytes INTEGER,
+    recorded_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX repository_sizes_stele_repository_idx ON repository_sizes(stele, repository);
+
+PRAGMA optimize;
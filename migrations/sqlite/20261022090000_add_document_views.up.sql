@@ -0,0 +1,13 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE document_views (
+    stele TEXT,
+    path TEXT,
+    date TEXT,
+    views INTEGER,
+    PRIMARY KEY (stele, path, date)
+);
+CREATE INDEX document_views_stele_date_idx ON document_views(stele, date);
+
+PRAGMA optimize;
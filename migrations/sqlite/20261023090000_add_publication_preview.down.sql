@@ -0,0 +1,6 @@
+-- Add down migration script here
+PRAGMA foreign_keys = OFF;
+
+ALTER TABLE publication DROP COLUMN preview;
+
+PRAGMA optimize;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+ALTER TABLE publication ADD COLUMN preview INTEGER NOT NULL DEFAULT 0;
+
+PRAGMA optimize;
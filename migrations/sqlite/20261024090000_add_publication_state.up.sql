@@ -0,0 +1,7 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+ALTER TABLE publication ADD COLUMN state TEXT;
+UPDATE publication SET state = 'live';
+
+PRAGMA optimize;
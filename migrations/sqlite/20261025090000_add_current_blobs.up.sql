@@ -0,0 +1,18 @@
+-- Add up migration script here
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE current_blobs (
+    stele TEXT,
+    repository TEXT,
+    commit_hash TEXT,
+    path TEXT,
+    blob_id TEXT,
+    CONSTRAINT fk_stele
+        FOREIGN KEY (stele)
+        REFERENCES stele(name)
+        ON DELETE CASCADE,
+    PRIMARY KEY (repository, path)
+);
+CREATE INDEX current_blobs_stele_idx ON current_blobs(stele);
+
+PRAGMA optimize;
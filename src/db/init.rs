@@ -1,5 +1,10 @@
-use crate::db::{DatabaseConnection, DatabaseKind, Db as _};
+use crate::db::{DatabaseConnection, DatabaseKind, Databases, Db as _};
+use crate::stelae::archive::read_config;
+use anyhow::Context as _;
+use sqlx::migrate::Migrator;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 /// Connects to a database and applies migrations.
 /// We use `SQLite` by default, but we can override this by setting the `DATABASE_URL` environment variable.
@@ -8,18 +13,138 @@ use std::path::{Path, PathBuf};
 /// Errors if connection to database fails.
 /// Connections can fail if the database is not running, or if the database URL is invalid.
 pub async fn connect(archive_path: &Path) -> anyhow::Result<DatabaseConnection> {
-    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| {
-        let sqlite_db_path = &archive_path.join(PathBuf::from(".taf/db.sqlite3"));
-        format!("sqlite:///{}?mode=rwc", sqlite_db_path.to_string_lossy())
-    });
-    let connection = DatabaseConnection::connect(&db_url).await?;
+    let connection = connect_url(&database_url(archive_path)).await?;
     tracing::info!("Connected to database");
+    Ok(connection)
+}
+
+/// Connects to the databases of the `stelae` of the archive that store their change data in
+/// their own database, next to the `shared` database of the archive.
+///
+/// # Errors
+/// Errors if connection to the database of an isolated stele fails.
+pub async fn connect_stelae<'stele, I>(
+    archive_path: &Path,
+    stelae: I,
+    shared: DatabaseConnection,
+) -> anyhow::Result<Databases>
+where
+    I: IntoIterator<Item = &'stele String>,
+{
+    let mut isolated = HashMap::new();
+    for stele in stelae {
+        if let Some(connection) = connect_stele(archive_path, stele).await? {
+            isolated.insert(stele.clone(), connection);
+        }
+    }
+    if !isolated.is_empty() {
+        tracing::info!("Connected to the databases of {} stele(s)", isolated.len());
+    }
+    Ok(Databases::new(shared, isolated))
+}
+
+/// Connects to the database of the stele `stele`, if it stores its change data in its own
+/// database.
+///
+/// # Errors
+/// Errors if connection to the database of the stele fails.
+pub async fn connect_stele(
+    archive_path: &Path,
+    stele: &str,
+) -> anyhow::Result<Option<DatabaseConnection>> {
+    let database = read_config(archive_path)
+        .ok()
+        .and_then(|config| config.database)
+        .unwrap_or_default();
+    let Some(db_url) = database.url_for_stele(archive_path, stele) else {
+        return Ok(None);
+    };
+    if let Some(dir) = sqlite_path(&db_url).as_deref().and_then(Path::parent) {
+        fs::create_dir_all(dir)?;
+    }
+    let connection = connect_url(&db_url)
+        .await
+        .with_context(|| format!("could not connect to the database of stele {stele}"))?;
+    Ok(Some(connection))
+}
+
+/// Connects to the database at `db_url` and applies migrations.
+///
+/// `SQLite` databases are switched to write-ahead logging, so the read transactions of the server
+/// see a consistent snapshot without blocking a concurrent `stelae update`.
+///
+/// # Errors
+/// Errors if connection to the database fails.
+async fn connect_url(db_url: &str) -> anyhow::Result<DatabaseConnection> {
+    let connection = DatabaseConnection::connect(db_url).await?;
     match connection.kind {
         DatabaseKind::Sqlite => {
-            sqlx::migrate!("./migrations/sqlite")
-                .run(&connection.pool)
+            sqlx::query("PRAGMA journal_mode = WAL")
+                .execute(&connection.pool)
                 .await?;
+            migrator(&connection.kind).run(&connection.pool).await?;
         }
     }
     Ok(connection)
 }
+
+/// Url of the database of the archive, from the `DATABASE_URL` environment variable,
+/// defaulting to `db.sqlite3` in the `.taf` dir.
+#[must_use]
+pub fn database_url(archive_path: &Path) -> String {
+    env::var("DATABASE_URL").unwrap_or_else(|_| {
+        let sqlite_db_path = &archive_path.join(PathBuf::from(".taf/db.sqlite3"));
+        format!("sqlite://{}?mode=rwc", sqlite_db_path.to_string_lossy())
+    })
+}
+
+/// Migrations of the schema of the databases of `kind`, applied once they are connected to.
+#[must_use]
+pub fn migrator(kind: &DatabaseKind) -> Migrator {
+    match *kind {
+        DatabaseKind::Sqlite => sqlx::migrate!("./migrations/sqlite"),
+    }
+}
+
+/// Path of the database file of the `SQLite` database url `db_url`, if it is one.
+#[must_use]
+pub fn sqlite_path(db_url: &str) -> Option<PathBuf> {
+    let location = db_url.strip_prefix("sqlite://")?;
+    let path = location.split('?').next().unwrap_or(location);
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::init::{connect, sqlite_path};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[actix_web::test]
+    async fn test_connect_when_sqlite_expect_write_ahead_log() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(archive_dir.path().join(".taf")).unwrap();
+        let cut = connect;
+        let conn = cut(archive_dir.path()).await.unwrap();
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&conn.pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_sqlite_path_when_sqlite_url_expect_file_path() {
+        let cut = sqlite_path;
+        assert_eq!(
+            cut("sqlite:///archive/.taf/db.sqlite3?mode=rwc"),
+            Some(PathBuf::from("/archive/.taf/db.sqlite3"))
+        );
+        assert_eq!(
+            cut("sqlite:///data/db.sqlite3"),
+            Some(PathBuf::from("/data/db.sqlite3"))
+        );
+        assert_eq!(cut("postgres://localhost/stelae"), None);
+    }
+}
@@ -1,6 +1,8 @@
 //! Database related module.
 use async_trait::async_trait;
 use sqlx::Transaction;
+use std::collections::HashMap;
+use std::iter;
 use std::str::FromStr as _;
 
 use sqlx::any::{self, AnyPoolOptions};
@@ -50,6 +52,59 @@ pub struct DatabaseConnection {
     pub kind: DatabaseKind,
 }
 
+/// Database connections of an archive.
+///
+/// Stelae that store their change data in their own database, see
+/// [`crate::stelae::archive::Database`], are queried through their own connection. All other
+/// stelae, and the data of the archive as a whole, are queried through the shared connection.
+#[derive(Debug, Clone)]
+pub struct Databases {
+    /// Connection to the database of the archive.
+    shared: DatabaseConnection,
+    /// Connections to the databases of the isolated stelae, keyed by qualified name.
+    stelae: HashMap<String, DatabaseConnection>,
+}
+
+impl Databases {
+    /// Create the connections of an archive from the `shared` connection and the connections of
+    /// the isolated `stelae`.
+    #[must_use]
+    pub const fn new(
+        shared: DatabaseConnection,
+        stelae: HashMap<String, DatabaseConnection>,
+    ) -> Self {
+        Self { shared, stelae }
+    }
+
+    /// Connection to the database of the archive.
+    #[must_use]
+    pub const fn shared(&self) -> &DatabaseConnection {
+        &self.shared
+    }
+
+    /// Connection to the database holding the change data of the stele `stele`.
+    #[must_use]
+    pub fn for_stele(&self, stele: &str) -> &DatabaseConnection {
+        self.stelae.get(stele).unwrap_or(&self.shared)
+    }
+
+    /// Connections to the databases of the isolated stelae, with their qualified names.
+    pub fn isolated(&self) -> impl Iterator<Item = (&String, &DatabaseConnection)> {
+        self.stelae.iter()
+    }
+
+    /// All connections, the shared connection first.
+    pub fn all(&self) -> impl Iterator<Item = &DatabaseConnection> {
+        iter::once(&self.shared).chain(self.stelae.values())
+    }
+}
+
+impl From<DatabaseConnection> for Databases {
+    fn from(shared: DatabaseConnection) -> Self {
+        Self::new(shared, HashMap::new())
+    }
+}
+
 /// Database transaction.
 pub struct DatabaseTransaction {
     /// Database transaction.
@@ -101,3 +156,35 @@ impl Tx for DatabaseTransaction {
         Ok(())
     }
 }
+
+/// Escape the `LIKE` wildcards in `value`, so it is matched literally by a pattern with
+/// `ESCAPE '\'`.
+#[must_use]
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('%', r"\%")
+        .replace('_', r"\_")
+}
+
+/// Whether `err` is a query expecting a row that found none.
+#[must_use]
+pub fn is_row_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<sqlx::Error>(),
+        Some(sqlx::Error::RowNotFound)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::escape_like;
+
+    #[test]
+    fn test_escape_like_when_wildcards_expect_escaped() {
+        let cut = escape_like;
+        let actual = cut(r"1.0_1%\");
+        let expected = r"1.0\_1\%\\";
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,79 @@
+//! Manager for the broken link model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::BrokenLink;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find all broken links of a stele, optionally only those of one publication.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele_and_publication(
+        &self,
+        stele: &str,
+        publication: Option<&str>,
+    ) -> anyhow::Result<Vec<BrokenLink>> {
+        let statement = "
+            SELECT stele, publication, commit_hash, source_path, href
+            FROM broken_links
+            WHERE stele = $1 AND ($2 IS NULL OR publication = $2)
+            ORDER BY publication DESC, source_path, href
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, BrokenLink>(statement)
+                    .bind(stele)
+                    .bind(publication)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Replace the broken links recorded for a publication with `broken_links`.
+    ///
+    /// # Errors
+    /// Errors if the broken links cannot be deleted or inserted.
+    async fn replace_all_by_stele_and_publication(
+        &mut self,
+        stele: &str,
+        publication: &str,
+        broken_links: Vec<BrokenLink>,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            DELETE FROM broken_links
+            WHERE stele = $1 AND publication = $2
+        ";
+        sqlx::query(statement)
+            .bind(stele)
+            .bind(publication)
+            .execute(&mut *self.tx)
+            .await?;
+        let mut query_builder = QueryBuilder::new(
+            "INSERT OR IGNORE INTO broken_links ( stele, publication, commit_hash, source_path, href ) ",
+        );
+        for chunk in broken_links.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, bl| {
+                bindings
+                    .push_bind(&bl.stele)
+                    .push_bind(&bl.publication)
+                    .push_bind(&bl.commit_hash)
+                    .push_bind(&bl.source_path)
+                    .push_bind(&bl.href);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
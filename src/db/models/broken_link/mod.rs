@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing broken links.
+#[async_trait]
+pub trait Manager {
+    /// Find all broken links of a stele, optionally only those of one publication.
+    async fn find_all_by_stele_and_publication(
+        &self,
+        stele: &str,
+        publication: Option<&str>,
+    ) -> anyhow::Result<Vec<BrokenLink>>;
+}
+
+/// Trait for managing transactional broken links.
+#[async_trait]
+pub trait TxManager {
+    /// Replace the broken links recorded for a publication.
+    async fn replace_all_by_stele_and_publication(
+        &mut self,
+        stele: &str,
+        publication: &str,
+        broken_links: Vec<BrokenLink>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Model for an internal link in a served html document that does not resolve within its publication.
+pub struct BrokenLink {
+    /// Foreign key reference to the stele the document belongs to.
+    pub stele: String,
+    /// Name of the publication the document was checked in, e.g. `2023-10-22`.
+    pub publication: String,
+    /// Commit of the html data repository the document was checked at.
+    pub commit_hash: String,
+    /// Path of the document containing the link, relative to the data repository.
+    pub source_path: String,
+    /// The link, exactly as found in the document.
+    pub href: String,
+}
+
+impl BrokenLink {
+    /// Create a new broken link.
+    #[must_use]
+    pub const fn new(
+        stele: String,
+        publication: String,
+        commit_hash: String,
+        source_path: String,
+        href: String,
+    ) -> Self {
+        Self {
+            stele,
+            publication,
+            commit_hash,
+            source_path,
+            href,
+        }
+    }
+}
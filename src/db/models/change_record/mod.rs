@@ -0,0 +1,38 @@
+//! Flattened view of a document or library change, used for exporting changes.
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow, Row as _};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// A single document or library change event, joined with its publication and version.
+pub struct ChangeRecord {
+    /// Kind of the changed element, either `document` or `library`.
+    pub kind: String,
+    /// Materialized path to the document or library.
+    pub mpath: String,
+    /// Url of the document or library.
+    pub url: Option<String>,
+    /// Change status of the element, as stored in the database.
+    /// See [`crate::db::models::status::Status`].
+    pub status: i64,
+    /// Optional reason for the change event. Always empty for libraries.
+    pub change_reason: Option<String>,
+    /// Codified date of the version in which the change occurred.
+    /// Used in the form %YYYY-%MM-%DD.
+    pub codified_date: String,
+    /// Name of the publication which introduced the change.
+    pub publication: String,
+}
+
+impl FromRow<'_, AnyRow> for ChangeRecord {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            kind: row.try_get("kind")?,
+            mpath: row.try_get("mpath")?,
+            url: row.try_get("url").ok(),
+            status: row.try_get("status")?,
+            change_reason: row.try_get("change_reason").ok(),
+            codified_date: row.try_get("codified_date")?,
+            publication: row.try_get("publication")?,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+//! Manager for the current blob model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::CurrentBlob;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find all current blobs of the data repositories of a stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele(&self, stele: &str) -> anyhow::Result<Vec<CurrentBlob>> {
+        let statement = "
+            SELECT stele, repository, commit_hash, path, blob_id
+            FROM current_blobs
+            WHERE stele = $1
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, CurrentBlob>(statement)
+                    .bind(stele)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Replace the current blobs recorded for a data repository with `current_blobs`.
+    ///
+    /// # Errors
+    /// Errors if the current blobs cannot be deleted or inserted.
+    async fn replace_all_by_repository(
+        &mut self,
+        repository: &str,
+        current_blobs: Vec<CurrentBlob>,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            DELETE FROM current_blobs
+            WHERE repository = $1
+        ";
+        sqlx::query(statement)
+            .bind(repository)
+            .execute(&mut *self.tx)
+            .await?;
+        let mut query_builder = QueryBuilder::new(
+            "INSERT OR IGNORE INTO current_blobs ( stele, repository, commit_hash, path, blob_id ) ",
+        );
+        for chunk in current_blobs.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, cb| {
+                bindings
+                    .push_bind(&cb.stele)
+                    .push_bind(&cb.repository)
+                    .push_bind(&cb.commit_hash)
+                    .push_bind(&cb.path)
+                    .push_bind(&cb.blob_id);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
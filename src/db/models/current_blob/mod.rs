@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing current blobs.
+#[async_trait]
+pub trait Manager {
+    /// Find all current blobs of the data repositories of a stele.
+    async fn find_all_by_stele(&self, stele: &str) -> anyhow::Result<Vec<CurrentBlob>>;
+}
+
+/// Trait for managing transactional current blobs.
+#[async_trait]
+pub trait TxManager {
+    /// Replace the current blobs recorded for a data repository.
+    async fn replace_all_by_repository(
+        &mut self,
+        repository: &str,
+        current_blobs: Vec<CurrentBlob>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for a blob of the `HEAD` commit of a data repository that current documents are served from.
+pub struct CurrentBlob {
+    /// Foreign key reference to the stele the data repository belongs to.
+    pub stele: String,
+    /// Name of the data repository, as `org/name`.
+    pub repository: String,
+    /// `HEAD` commit of the data repository the blob was indexed at.
+    pub commit_hash: String,
+    /// Path of the blob, relative to the data repository, e.g. `a/b/c/index.html`.
+    pub path: String,
+    /// Id of the blob in the data repository.
+    pub blob_id: String,
+}
+
+impl CurrentBlob {
+    /// Create a new current blob.
+    #[must_use]
+    pub const fn new(
+        stele: String,
+        repository: String,
+        commit_hash: String,
+        path: String,
+        blob_id: String,
+    ) -> Self {
+        Self {
+            stele,
+            repository,
+            commit_hash,
+            path,
+            blob_id,
+        }
+    }
+}
@@ -1,34 +1,296 @@
 //! Manager for the data repo commit model.
 use async_trait::async_trait;
+use chrono::NaiveDate;
 use sqlx::QueryBuilder;
 
-use crate::db::{models::BATCH_SIZE, DatabaseTransaction};
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+use crate::utils::date;
 
 use super::DataRepoCommits;
 
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find the latest data repository commit of `repo_type` for a stele on or before `date`,
+    /// in a preview or approved publication only if `previews` are allowed, and never in a
+    /// publication awaiting approval.
+    ///
+    /// Commits from the most recent non-revoked publication take precedence.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_by_stele_and_repo_type_on_or_before_date(
+        &self,
+        stele: &str,
+        repo_type: &str,
+        date: &NaiveDate,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            INNER JOIN publication p ON dc.publication_id = p.id
+            WHERE p.stele = $1 AND p.revoked = 0 AND dc.repo_type = $2 AND dc.date <= $3
+                AND p.state IS NOT 'ingested'
+                AND ((p.preview = 0 AND p.state IS NOT 'approved') OR $4 = 1)
+            ORDER BY p.date DESC, dc.date DESC, dc.auth_commit_timestamp DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(stele)
+                    .bind(repo_type)
+                    .bind(date::format(*date))
+                    .bind(i64::from(previews))
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+
+    /// Find up to `limit` data repository commits of `repo_type` in a publication on or before
+    /// `date`, latest first.
+    ///
+    /// Commits of the same date and authentication commit are ordered by hash, so the order is
+    /// deterministic.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_publication_and_repo_type_on_or_before_date(
+        &self,
+        publication_id: &str,
+        repo_type: &str,
+        date: &NaiveDate,
+        limit: i64,
+    ) -> anyhow::Result<Vec<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            WHERE dc.publication_id = $1 AND dc.repo_type = $2 AND dc.date <= $3
+            ORDER BY dc.date DESC, dc.auth_commit_timestamp DESC, dc.commit_hash DESC
+            LIMIT $4
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(publication_id)
+                    .bind(repo_type)
+                    .bind(date::format(*date))
+                    .bind(limit)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Find the data repository commit `commit_hash` recorded for a stele in a non-revoked
+    /// publication, in a preview or approved publication only if `previews` are allowed, and
+    /// never in a publication awaiting approval.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_by_stele_and_commit_hash(
+        &self,
+        stele: &str,
+        commit_hash: &str,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            INNER JOIN publication p ON dc.publication_id = p.id
+            WHERE p.stele = $1 AND dc.commit_hash = $2 AND p.revoked = 0
+                AND p.state IS NOT 'ingested'
+                AND ((p.preview = 0 AND p.state IS NOT 'approved') OR $3 = 1)
+            ORDER BY p.date DESC, dc.auth_commit_timestamp DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(stele)
+                    .bind(commit_hash)
+                    .bind(i64::from(previews))
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+
+    /// Find the data repository commit of `repo_type` for a stele recorded from the latest
+    /// authentication commit.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_by_stele_and_repo_type(
+        &self,
+        stele: &str,
+        repo_type: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            INNER JOIN publication p ON dc.publication_id = p.id
+            WHERE p.stele = $1 AND dc.repo_type = $2
+            ORDER BY dc.auth_commit_timestamp DESC, dc.date DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(stele)
+                    .bind(repo_type)
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+
+    /// Find the latest data repository commit of `repo_type` for a stele recorded in the latest
+    /// non-revoked publication that is served, in a preview or approved publication only if
+    /// `previews` are allowed, and never in a publication awaiting approval.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_served_by_stele_and_repo_type(
+        &self,
+        stele: &str,
+        repo_type: &str,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            INNER JOIN publication p ON dc.publication_id = p.id
+            WHERE p.stele = $1 AND dc.repo_type = $2 AND p.revoked = 0
+                AND p.state IS NOT 'ingested'
+                AND ((p.preview = 0 AND p.state IS NOT 'approved') OR $3 = 1)
+            ORDER BY p.date DESC, dc.auth_commit_timestamp DESC, dc.date DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(stele)
+                    .bind(repo_type)
+                    .bind(i64::from(previews))
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+    /// Find the data repository commit `commit_hash` recorded for a stele in a non-revoked
+    /// publication, whatever its state.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_recorded_by_stele_and_commit_hash(
+        &self,
+        stele: &str,
+        commit_hash: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let statement = "
+            SELECT dc.*
+            FROM data_repo_commits dc
+            INNER JOIN publication p ON dc.publication_id = p.id
+            WHERE p.stele = $1 AND dc.commit_hash = $2 AND p.revoked = 0
+            ORDER BY p.date DESC, dc.auth_commit_timestamp DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DataRepoCommits>(statement)
+                    .bind(stele)
+                    .bind(commit_hash)
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+}
+
 #[async_trait]
 impl super::TxManager for DatabaseTransaction {
-    /// Find all authentication commits for a given stele.
+    /// Find all commits of data repositories of `repo_type` recorded for a given stele.
     ///
     /// # Errors
     /// Errors if the commits cannot be found.
-    async fn find_all_auth_commits_for_stele(
+    async fn find_all_auth_commits_for_stele_and_repo_type(
         &mut self,
         stele_id: &str,
+        repo_type: &str,
     ) -> anyhow::Result<Vec<DataRepoCommits>> {
         let query = "
             SELECT dc.*
             FROM data_repo_commits dc
             LEFT JOIN publication p ON dc.publication_id = p.id
             LEFT JOIN stele s ON p.stele = s.name
-            WHERE s.name = $1
+            WHERE s.name = $1 AND dc.repo_type = $2
         ";
         let data_repo_commits = sqlx::query_as::<_, DataRepoCommits>(query)
             .bind(stele_id)
+            .bind(repo_type)
             .fetch_all(&mut *self.tx)
             .await?;
         Ok(data_repo_commits)
     }
+    /// Find the latest data repository commit of `repo_type` in a publication.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_by_publication_and_repo_type(
+        &mut self,
+        publication_id: &str,
+        repo_type: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let query = "
+            SELECT *
+            FROM data_repo_commits
+            WHERE publication_id = $1 AND repo_type = $2
+            ORDER BY date DESC, auth_commit_timestamp DESC
+            LIMIT 1
+        ";
+        let data_repo_commit = sqlx::query_as::<_, DataRepoCommits>(query)
+            .bind(publication_id)
+            .bind(repo_type)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+        Ok(data_repo_commit)
+    }
+    /// Find the data repository commit of a publication recorded from its latest authentication
+    /// commit.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_by_publication(
+        &mut self,
+        publication_id: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>> {
+        let query = "
+            SELECT *
+            FROM data_repo_commits
+            WHERE publication_id = $1
+            ORDER BY auth_commit_timestamp DESC, date DESC
+            LIMIT 1
+        ";
+        let data_repo_commit = sqlx::query_as::<_, DataRepoCommits>(query)
+            .bind(publication_id)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+        Ok(data_repo_commit)
+    }
     /// Upsert a bulk of data repository commits into the database.
     ///
     /// # Errors
@@ -39,7 +301,7 @@ impl super::TxManager for DatabaseTransaction {
             query_builder.push_values(chunk, |mut bindings, dc| {
                 bindings
                     .push_bind(&dc.commit_hash)
-                    .push_bind(&dc.date)
+                    .push_bind(date::format(dc.date))
                     .push_bind(&dc.repo_type)
                     .push_bind(&dc.auth_commit_hash)
                     .push_bind(&dc.auth_commit_timestamp)
@@ -1,27 +1,101 @@
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow, Row as _};
+
+use crate::utils::date;
 
 pub mod manager;
 
+/// Format of the timestamps of authentication commits, e.g. `2023-10-22 12:00:00 UTC`.
+pub const AUTH_COMMIT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+/// Trait for managing data repo commits.
+#[async_trait]
+pub trait Manager {
+    /// Find the latest data repository commit of `repo_type` for a stele on or before `date`,
+    /// in a preview or approved publication only if `previews` are allowed.
+    async fn find_latest_by_stele_and_repo_type_on_or_before_date(
+        &self,
+        stele: &str,
+        repo_type: &str,
+        date: &NaiveDate,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+    /// Find up to `limit` data repository commits of `repo_type` in a publication on or before
+    /// `date`, latest first.
+    async fn find_all_by_publication_and_repo_type_on_or_before_date(
+        &self,
+        publication_id: &str,
+        repo_type: &str,
+        date: &NaiveDate,
+        limit: i64,
+    ) -> anyhow::Result<Vec<DataRepoCommits>>;
+    /// Find the data repository commit `commit_hash` recorded for a stele in a non-revoked
+    /// publication, in a preview or approved publication only if `previews` are allowed.
+    async fn find_by_stele_and_commit_hash(
+        &self,
+        stele: &str,
+        commit_hash: &str,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+    /// Find the data repository commit of `repo_type` for a stele recorded from the latest
+    /// authentication commit.
+    async fn find_latest_by_stele_and_repo_type(
+        &self,
+        stele: &str,
+        repo_type: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+    /// Find the latest data repository commit of `repo_type` for a stele recorded in the latest
+    /// served publication, in a preview or approved publication only if `previews` are allowed.
+    async fn find_latest_served_by_stele_and_repo_type(
+        &self,
+        stele: &str,
+        repo_type: &str,
+        previews: bool,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+    /// Find the data repository commit `commit_hash` recorded for a stele in a non-revoked
+    /// publication, whatever its state.
+    async fn find_recorded_by_stele_and_commit_hash(
+        &self,
+        stele: &str,
+        commit_hash: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+}
+
 /// Trait for managing transactional data repo commits.
 #[async_trait]
 pub trait TxManager {
-    /// Find all authentication commits for a given stele.
-    async fn find_all_auth_commits_for_stele(
+    /// Find all commits of data repositories of `repo_type` recorded for a given stele.
+    async fn find_all_auth_commits_for_stele_and_repo_type(
         &mut self,
         stele_id: &str,
+        repo_type: &str,
     ) -> anyhow::Result<Vec<DataRepoCommits>>;
+    /// Find the latest data repository commit of `repo_type` in a publication.
+    async fn find_latest_by_publication_and_repo_type(
+        &mut self,
+        publication_id: &str,
+        repo_type: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
+    /// Find the data repository commit of a publication recorded from its latest authentication
+    /// commit.
+    async fn find_latest_by_publication(
+        &mut self,
+        publication_id: &str,
+    ) -> anyhow::Result<Option<DataRepoCommits>>;
     /// Insert a bulk of data repo commits.
     async fn insert_bulk(&mut self, data_repo_commits: Vec<DataRepoCommits>) -> anyhow::Result<()>;
 }
 
-#[derive(sqlx::FromRow, Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 /// Model for the commits within the data repository.
 pub struct DataRepoCommits {
     /// Unique commit hash of the authentication repository.
     pub commit_hash: String,
     /// Either codified date or date on which the commit was built on (build-date).
-    pub date: String,
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
     /// Type of the data repository. E.g. `html`.
     pub repo_type: String,
     /// Foreign key reference to the authentication commit hash.
@@ -32,12 +106,25 @@ pub struct DataRepoCommits {
     pub publication_id: String,
 }
 
+impl FromRow<'_, AnyRow> for DataRepoCommits {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            commit_hash: row.try_get("commit_hash")?,
+            date: date::from_row(row, "date")?,
+            repo_type: row.try_get("repo_type")?,
+            auth_commit_hash: row.try_get("auth_commit_hash")?,
+            auth_commit_timestamp: row.try_get("auth_commit_timestamp")?,
+            publication_id: row.try_get("publication_id")?,
+        })
+    }
+}
+
 impl DataRepoCommits {
     /// Create a new data commit.
     #[must_use]
     pub const fn new(
         commit_hash: String,
-        date: String,
+        date: NaiveDate,
         repo_type: String,
         auth_commit_hash: String,
         auth_commit_timestamp: String,
@@ -52,4 +139,12 @@ impl DataRepoCommits {
             publication_id,
         }
     }
+
+    /// Time of the authentication commit, or `None` if its timestamp is malformed.
+    #[must_use]
+    pub fn auth_commit_time(&self) -> Option<DateTime<Utc>> {
+        NaiveDateTime::parse_from_str(&self.auth_commit_timestamp, AUTH_COMMIT_TIMESTAMP_FORMAT)
+            .ok()
+            .map(|time| time.and_utc())
+    }
 }
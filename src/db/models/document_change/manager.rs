@@ -1,12 +1,16 @@
 //! Manager for the document change model.
-use super::DocumentChange;
+use super::{DocumentChange, StatusEvent};
 use crate::db::{
-    models::{status::Status, version::Version, BATCH_SIZE},
+    escape_like,
+    models::{
+        change_record::ChangeRecord, document_delta::DocumentDelta, status::Status,
+        version::Version, BATCH_SIZE,
+    },
     DatabaseConnection, DatabaseKind, DatabaseTransaction,
 };
 use async_trait::async_trait;
-use chrono::NaiveDate;
-use sqlx::QueryBuilder;
+use sqlx::{AnyConnection, QueryBuilder};
+use std::cmp::Reverse;
 
 #[async_trait]
 impl super::Manager for DatabaseConnection {
@@ -19,86 +23,157 @@ impl super::Manager for DatabaseConnection {
         mpath: &str,
         publication_id: &str,
     ) -> anyhow::Result<Vec<Version>> {
-        let mut statement = "
-            SELECT DISTINCT pv.version AS codified_date
+        match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                find_document_versions(&mut connection, mpath, publication_id).await
+            }
+        }
+    }
+
+    /// All document changes of a stele, optionally between two codified dates (inclusive).
+    ///
+    /// Changes from revoked publications are excluded.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_change_records_by_stele_and_date_range(
+        &self,
+        stele: &str,
+        start_date: Option<&str>,
+        end_date: Option<&str>,
+    ) -> anyhow::Result<Vec<ChangeRecord>> {
+        let statement = "
+            SELECT 'document' AS kind, dc.doc_mpath AS mpath, el.url AS url, dc.status AS status,
+                dc.change_reason AS change_reason, pv.version AS codified_date, p.name AS publication
             FROM document_change dc
-            LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
-            LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
-            WHERE dc.doc_mpath LIKE $1 AND phpv.publication_id = $2
+            INNER JOIN publication_version pv ON dc.publication_version_id = pv.id
+            INNER JOIN publication p ON pv.publication_id = p.id
+            LEFT JOIN document_element el ON dc.doc_mpath = el.doc_mpath
+            WHERE p.stele = $1 AND p.revoked = 0
+                AND ($2 IS NULL OR pv.version >= $2)
+                AND ($3 IS NULL OR pv.version <= $3)
+            ORDER BY pv.version, mpath
         ";
-        let mut rows = match self.kind {
+        let rows = match self.kind {
             DatabaseKind::Sqlite => {
                 let mut connection = self.pool.acquire().await?;
-                sqlx::query_as::<_, Version>(statement)
-                    .bind(format!("{mpath}%"))
-                    .bind(publication_id)
+                sqlx::query_as::<_, ChangeRecord>(statement)
+                    .bind(stele)
+                    .bind(start_date)
+                    .bind(end_date)
                     .fetch_all(&mut *connection)
                     .await?
             }
         };
-        statement = "
-            SELECT pv.version AS codified_date
-            FROM document_change dc
-            LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
-            LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
-            WHERE dc.doc_mpath = $1 AND phpv.publication_id = $2 AND dc.status = $3
-            LIMIT 1
+        Ok(rows)
+    }
+
+    /// Net change of every document changed in a publication since the previous publication.
+    ///
+    /// Only versions of the publication whose codified date is not a version of the previous
+    /// publication are considered. A document added in these versions is `new`, one whose latest
+    /// change removed it is `removed`, and any other changed document is `changed`.
+    /// Documents both added and removed in these versions are left out.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_document_deltas_by_publication(
+        &self,
+        publication_id: &str,
+        previous_publication_id: Option<&str>,
+    ) -> anyhow::Result<Vec<DocumentDelta>> {
+        let statement = "
+            WITH previous_versions AS (
+                SELECT pv.version
+                FROM publication_has_publication_versions phpv
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                WHERE phpv.publication_id = $2
+            ),
+            new_versions AS (
+                SELECT pv.id, pv.version
+                FROM publication_has_publication_versions phpv
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                WHERE phpv.publication_id = $1
+                    AND pv.version NOT IN (SELECT version FROM previous_versions)
+            ),
+            changes AS (
+                SELECT dc.doc_mpath, CAST(dc.status AS INTEGER) AS status, nv.version
+                FROM document_change dc
+                INNER JOIN new_versions nv ON dc.publication_version_id = nv.id
+                WHERE CAST(dc.status AS INTEGER) IN (0, 2, 3)
+            ),
+            documents AS (
+                SELECT c.doc_mpath,
+                    MAX(c.status = 0) AS added,
+                    (
+                        SELECT latest.status
+                        FROM changes latest
+                        WHERE latest.doc_mpath = c.doc_mpath
+                        ORDER BY latest.version DESC, latest.status DESC
+                        LIMIT 1
+                    ) AS latest_status
+                FROM changes c
+                GROUP BY c.doc_mpath
+            )
+            SELECT d.doc_mpath, el.url,
+                CASE
+                    WHEN d.latest_status = 3 THEN 'removed'
+                    WHEN d.added = 1 THEN 'new'
+                    ELSE 'changed'
+                END AS change
+            FROM documents d
+            LEFT JOIN document_element el ON d.doc_mpath = el.doc_mpath
+            WHERE NOT (d.added = 1 AND d.latest_status = 3)
+            ORDER BY d.doc_mpath
         ";
-        let element_added = match self.kind {
+        let rows = match self.kind {
             DatabaseKind::Sqlite => {
                 let mut connection = self.pool.acquire().await?;
-                sqlx::query_as::<_, Version>(statement)
-                    .bind(mpath)
+                sqlx::query_as::<_, DocumentDelta>(statement)
                     .bind(publication_id)
-                    .bind(Status::ElementAdded.to_int())
-                    .fetch_one(&mut *connection)
-                    .await
-                    .ok()
+                    .bind(previous_publication_id)
+                    .fetch_all(&mut *connection)
+                    .await?
             }
         };
+        Ok(rows)
+    }
 
-        if element_added.is_none() {
-            // When element doesn't have date added, it means we're looking
-            // at an old publication and this element doesn't yet exist in it
-            rows.sort_by(|v1, v2| v2.codified_date.cmp(&v1.codified_date));
-            return Ok(rows);
-        }
-
-        statement = "
-            SELECT pv.version AS codified_date
+    /// All status changes of a document and its elements in a publication, in codified date order.
+    ///
+    /// Includes the `Element effective` changes of the document the element belongs to.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_status_events_by_mpath_and_publication(
+        &self,
+        mpath: &str,
+        publication_id: &str,
+    ) -> anyhow::Result<Vec<StatusEvent>> {
+        let statement = r"
+            SELECT DISTINCT dc.doc_mpath, pv.version AS codified_date, CAST(dc.status AS INTEGER) AS status
             FROM document_change dc
-            LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
-            LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
-            WHERE dc.doc_mpath = $1 AND phpv.publication_id = $2 AND dc.status = $3
-            LIMIT 1
+            INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+            INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+            WHERE phpv.publication_id = $2
+                AND (dc.doc_mpath LIKE $1 ESCAPE '\' OR (dc.doc_mpath = $3 AND CAST(dc.status AS INTEGER) = $4))
+            ORDER BY pv.version, status
         ";
         let mut doc = mpath.split('|').next().unwrap_or("").to_owned();
         doc.push('|');
-
-        let document_effective = match self.kind {
+        let rows = match self.kind {
             DatabaseKind::Sqlite => {
                 let mut connection = self.pool.acquire().await?;
-                sqlx::query_as::<_, Version>(statement)
-                    .bind(doc)
+                sqlx::query_as::<_, StatusEvent>(statement)
+                    .bind(format!("{}%", escape_like(mpath)))
                     .bind(publication_id)
+                    .bind(doc)
                     .bind(Status::ElementEffective.to_int())
-                    .fetch_one(&mut *connection)
-                    .await
-                    .ok()
+                    .fetch_all(&mut *connection)
+                    .await?
             }
         };
-
-        if let (Some(doc_effective), Some(el_added)) = (document_effective, element_added) {
-            if !rows.contains(&doc_effective)
-                && NaiveDate::parse_from_str(&doc_effective.codified_date, "%Y-%m-%d")
-                    .unwrap_or_default()
-                    > NaiveDate::parse_from_str(&el_added.codified_date, "%Y-%m-%d")
-                        .unwrap_or_default()
-            {
-                rows.push(doc_effective);
-            }
-        }
-        rows.sort_by(|v1, v2| v2.codified_date.cmp(&v1.codified_date));
         Ok(rows)
     }
 }
@@ -127,3 +202,108 @@ impl super::TxManager for DatabaseTransaction {
         Ok(())
     }
 }
+
+/// All dates on which given document changed, on `connection`.
+async fn find_document_versions(
+    connection: &mut AnyConnection,
+    mpath: &str,
+    publication_id: &str,
+) -> anyhow::Result<Vec<Version>> {
+    let mut statement = "
+        SELECT DISTINCT pv.version AS codified_date
+        FROM document_change dc
+        LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+        LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
+        WHERE dc.doc_mpath LIKE $1 AND phpv.publication_id = $2
+    ";
+    let mut rows = sqlx::query_as::<_, Version>(statement)
+        .bind(format!("{mpath}%"))
+        .bind(publication_id)
+        .fetch_all(&mut *connection)
+        .await?;
+    statement = "
+        SELECT pv.version AS codified_date
+        FROM document_change dc
+        LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+        LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
+        WHERE dc.doc_mpath = $1 AND phpv.publication_id = $2 AND dc.status = $3
+        LIMIT 1
+    ";
+    let element_added = sqlx::query_as::<_, Version>(statement)
+        .bind(mpath)
+        .bind(publication_id)
+        .bind(Status::ElementAdded.to_int())
+        .fetch_one(&mut *connection)
+        .await
+        .ok();
+
+    if element_added.is_none() {
+        // When element doesn't have date added, it means we're looking
+        // at an old publication and this element doesn't yet exist in it
+        rows.sort_by_key(|version| Reverse(version.codified_date));
+        return Ok(rows);
+    }
+
+    statement = "
+        SELECT pv.version AS codified_date
+        FROM document_change dc
+        LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+        LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
+        WHERE dc.doc_mpath = $1 AND phpv.publication_id = $2 AND dc.status = $3
+        LIMIT 1
+    ";
+    let mut doc = mpath.split('|').next().unwrap_or("").to_owned();
+    doc.push('|');
+
+    let document_effective = sqlx::query_as::<_, Version>(statement)
+        .bind(doc)
+        .bind(publication_id)
+        .bind(Status::ElementEffective.to_int())
+        .fetch_one(&mut *connection)
+        .await
+        .ok();
+
+    if let (Some(doc_effective), Some(el_added)) = (document_effective, element_added) {
+        if !rows.contains(&doc_effective) && doc_effective.codified_date > el_added.codified_date {
+            rows.push(doc_effective);
+        }
+    }
+    rows.sort_by_key(|version| Reverse(version.codified_date));
+    Ok(rows)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::document_change::Manager as _;
+    use crate::db::models::fixture::{self, STELE};
+    use crate::db::Tx as _;
+
+    /// Statements inserting the documents `a_` and `ab`, whose materialized paths only differ
+    /// where `a_` has a `LIKE` wildcard, added in a version of the publication `pb`.
+    const FIXTURE: &[&str] = &[
+        "INSERT INTO version (codified_date) VALUES ('2020-01-01')",
+        "INSERT INTO publication_version (id, version, publication_id) VALUES ('v1', '2020-01-01', 'pb')",
+        "INSERT INTO publication_has_publication_versions (publication_id, publication_version_id) VALUES ('pb', 'v1')",
+        "INSERT INTO document (doc_id) VALUES ('a_'), ('ab')",
+        "INSERT INTO document_element (doc_mpath, url, doc_id, stele) VALUES ('a_|', '/a_', 'a_', 'org/law'), ('ab|', '/ab', 'ab', 'org/law')",
+        "INSERT INTO document_change (id, status, publication_version_id, doc_mpath) VALUES ('c1', 0, 'v1', 'a_|'), ('c2', 0, 'v1', 'ab|')",
+    ];
+
+    #[actix_web::test]
+    async fn test_find_all_status_events_by_mpath_and_publication_when_wildcard_expect_literal_match(
+    ) {
+        let fixture = fixture::begin(STELE, &[("pb", "2020-01-01")], FIXTURE).await;
+        let db = fixture.db;
+        fixture.tx.commit().await.unwrap();
+
+        let actual = db
+            .find_all_status_events_by_mpath_and_publication("a_|", "pb")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.doc_mpath)
+            .collect::<Vec<_>>();
+        assert_eq!(actual, vec!["a_|".to_owned()]);
+    }
+}
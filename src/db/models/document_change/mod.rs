@@ -1,3 +1,5 @@
+use super::change_record::ChangeRecord;
+use super::document_delta::DocumentDelta;
 use super::version::Version;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,25 @@ pub trait Manager {
         mpath: &str,
         publication: &str,
     ) -> anyhow::Result<Vec<Version>>;
+    /// All document changes of a stele, optionally between two codified dates (inclusive).
+    async fn find_all_change_records_by_stele_and_date_range(
+        &self,
+        stele: &str,
+        start_date: Option<&str>,
+        end_date: Option<&str>,
+    ) -> anyhow::Result<Vec<ChangeRecord>>;
+    /// Net change of every document changed in a publication since the previous publication.
+    async fn find_all_document_deltas_by_publication(
+        &self,
+        publication_id: &str,
+        previous_publication_id: Option<&str>,
+    ) -> anyhow::Result<Vec<DocumentDelta>>;
+    /// All status changes of a document and its elements in a publication, in codified date order.
+    async fn find_all_status_events_by_mpath_and_publication(
+        &self,
+        mpath: &str,
+        publication_id: &str,
+    ) -> anyhow::Result<Vec<StatusEvent>>;
 }
 
 /// Trait for managing transactional document changes.
@@ -39,6 +60,19 @@ pub struct DocumentChange {
     pub doc_mpath: String,
 }
 
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// A status change of a document element on a codified date.
+pub struct StatusEvent {
+    /// Materialized path to the changed document element.
+    pub doc_mpath: String,
+    /// Codified date of the version in which the status changed.
+    /// Used in the form %YYYY-%MM-%DD.
+    pub codified_date: String,
+    /// Change status of the document element.
+    /// See [`crate::db::models::status::Status`].
+    pub status: i64,
+}
+
 impl DocumentChange {
     /// Create a new document change.
     #[must_use]
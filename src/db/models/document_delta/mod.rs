@@ -0,0 +1,31 @@
+//! Net change of a document between a publication and the previous one.
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow, Row as _};
+
+/// Document was added since the previous publication.
+pub const NEW: &str = "new";
+/// Document was changed since the previous publication.
+pub const CHANGED: &str = "changed";
+/// Document was removed since the previous publication.
+pub const REMOVED: &str = "removed";
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// A document that is new, changed or removed in a publication.
+pub struct DocumentDelta {
+    /// Materialized path to the document.
+    pub doc_mpath: String,
+    /// Url of the document.
+    pub url: Option<String>,
+    /// Net change of the document, one of `new`, `changed` or `removed`.
+    pub change: String,
+}
+
+impl FromRow<'_, AnyRow> for DocumentDelta {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            doc_mpath: row.try_get("doc_mpath")?,
+            url: row.try_get("url").ok(),
+            change: row.try_get("change")?,
+        })
+    }
+}
@@ -0,0 +1,64 @@
+//! Manager for the document view model.
+use async_trait::async_trait;
+
+use crate::db::{DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::{DocumentView, TopDocument};
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find the `limit` most viewed documents of a stele since `since`, ordered by views.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_top_by_stele(
+        &self,
+        stele: &str,
+        since: &str,
+        limit: i64,
+    ) -> anyhow::Result<Vec<TopDocument>> {
+        let statement = "
+            SELECT dv.path, SUM(dv.views) AS views
+            FROM document_views dv
+            WHERE dv.stele = $1 AND dv.date >= $2
+            GROUP BY dv.path
+            ORDER BY views DESC, dv.path
+            LIMIT $3
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, TopDocument>(statement)
+                    .bind(stele)
+                    .bind(since)
+                    .bind(limit)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Add the views of a document on a day to the views counted so far.
+    ///
+    /// # Errors
+    /// Errors if the views cannot be inserted or updated.
+    async fn add(&mut self, view: &DocumentView) -> anyhow::Result<()> {
+        let statement = "
+            INSERT INTO document_views ( stele, path, date, views )
+            VALUES ( $1, $2, $3, $4 )
+            ON CONFLICT ( stele, path, date ) DO UPDATE SET views = views + excluded.views
+        ";
+        sqlx::query(statement)
+            .bind(&view.stele)
+            .bind(&view.path)
+            .bind(&view.date)
+            .bind(view.views)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+}
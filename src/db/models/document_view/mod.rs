@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing the counted views of documents.
+#[async_trait]
+pub trait Manager {
+    /// Find the `limit` most viewed documents of a stele since the `%Y-%m-%d` date `since`.
+    async fn find_top_by_stele(
+        &self,
+        stele: &str,
+        since: &str,
+        limit: i64,
+    ) -> anyhow::Result<Vec<TopDocument>>;
+}
+
+/// Trait for managing transactional counted views of documents.
+#[async_trait]
+pub trait TxManager {
+    /// Add the views of a document on a day to the views counted so far.
+    async fn add(&mut self, view: &DocumentView) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for the number of views of a document on a day.
+pub struct DocumentView {
+    /// Qualified name of the stele the document was served from.
+    pub stele: String,
+    /// Path of the document, as in its url, without leading or trailing `/`.
+    pub path: String,
+    /// Day of the views, as `YYYY-MM-DD` in UTC.
+    pub date: String,
+    /// Number of views.
+    pub views: i64,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for the total number of views of a document over a period.
+pub struct TopDocument {
+    /// Path of the document, as in its url, without leading or trailing `/`.
+    pub path: String,
+    /// Number of views.
+    pub views: i64,
+}
@@ -0,0 +1,61 @@
+//! Databases of a stele and its publications, set up for the tests of the managers and handlers.
+#![expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+use crate::db::models::{publication, stele};
+use crate::db::{init, DatabaseConnection, DatabaseTransaction, Tx as _};
+use chrono::NaiveDate;
+use std::fs;
+use tempfile::TempDir;
+
+/// Qualified name of the stele most fixtures are set up for.
+pub const STELE: &str = "org/law";
+
+/// A database set up by [`begin`].
+pub struct Fixture {
+    /// Connection to the database.
+    pub db: DatabaseConnection,
+    /// The temporary archive of the database, removed with it when dropped.
+    pub dir: TempDir,
+    /// The transaction the database was set up in, left open.
+    pub tx: DatabaseTransaction,
+}
+
+/// Connect to a new database, in the `.taf` dir of a temporary archive.
+///
+/// # Panics
+/// Panics if the database can't be created.
+pub async fn database() -> (TempDir, DatabaseConnection) {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join(".taf")).unwrap();
+    let db = init::connect(dir.path()).await.unwrap();
+    (dir, db)
+}
+
+/// Record the `stele`, and its `publications` as pairs of id and name, dated by their name, e.g.
+/// `("pb", "2020-01-01")`.
+///
+/// # Panics
+/// Panics if a name is not a date, or the rows can't be inserted.
+pub async fn publish(tx: &mut DatabaseTransaction, stele: &str, publications: &[(&str, &str)]) {
+    stele::TxManager::create(tx, stele).await.unwrap();
+    for &(id, name) in publications {
+        let date = NaiveDate::parse_from_str(name, "%Y-%m-%d").unwrap();
+        publication::TxManager::create(tx, id, name, &date, stele, None, None)
+            .await
+            .unwrap();
+    }
+}
+
+/// Set up a new database with the `stele`, its `publications`, see [`publish`], and the rows
+/// inserted by `statements`, in a transaction left open.
+///
+/// # Panics
+/// Panics if the database can't be set up.
+pub async fn begin(stele: &str, publications: &[(&str, &str)], statements: &[&str]) -> Fixture {
+    let (dir, db) = database().await;
+    let mut tx = DatabaseTransaction::begin(db.pool.clone()).await.unwrap();
+    publish(&mut tx, stele, publications).await;
+    for statement in statements {
+        sqlx::query(statement).execute(&mut *tx.tx).await.unwrap();
+    }
+    Fixture { db, dir, tx }
+}
@@ -0,0 +1,67 @@
+//! Manager for the identifier model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::Identifier;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find all persistent identifiers of documents.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all(&self) -> anyhow::Result<Vec<Identifier>> {
+        let statement = "
+            SELECT identifier, stele, path
+            FROM identifiers
+            ORDER BY stele, identifier
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Identifier>(statement)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Replace the persistent identifiers of a stele with `identifiers`.
+    ///
+    /// # Errors
+    /// Errors if the identifiers cannot be deleted or inserted.
+    async fn replace_all_by_stele(
+        &mut self,
+        stele: &str,
+        identifiers: Vec<Identifier>,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            DELETE FROM identifiers
+            WHERE stele = $1
+        ";
+        sqlx::query(statement)
+            .bind(stele)
+            .execute(&mut *self.tx)
+            .await?;
+        let mut query_builder =
+            QueryBuilder::new("INSERT INTO identifiers ( identifier, stele, path ) ");
+        for chunk in identifiers.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, id| {
+                bindings
+                    .push_bind(&id.identifier)
+                    .push_bind(&id.stele)
+                    .push_bind(&id.path);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing persistent identifiers.
+#[async_trait]
+pub trait Manager {
+    /// Find all persistent identifiers of documents.
+    async fn find_all(&self) -> anyhow::Result<Vec<Identifier>>;
+}
+
+/// Trait for managing transactional persistent identifiers.
+#[async_trait]
+pub trait TxManager {
+    /// Replace the persistent identifiers of a stele.
+    async fn replace_all_by_stele(
+        &mut self,
+        stele: &str,
+        identifiers: Vec<Identifier>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for a persistent identifier of a document, e.g. an ELI or a URN:LEX.
+pub struct Identifier {
+    /// The identifier, e.g. `eli/us/ca/code/title/1` or `urn:lex:us-ca:code:title.1`.
+    pub identifier: String,
+    /// Qualified name of the stele the document belongs to.
+    pub stele: String,
+    /// Path of the document, as in its url, without leading or trailing `/`.
+    pub path: String,
+}
@@ -0,0 +1,36 @@
+//! Manager for the ingest error model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseTransaction};
+
+use super::IngestError;
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Upsert a bulk of ingest errors into the database.
+    ///
+    /// A file that fails again on a later update replaces its previous error.
+    ///
+    /// # Errors
+    /// Errors if the ingest errors cannot be inserted.
+    async fn insert_bulk(&mut self, ingest_errors: Vec<IngestError>) -> anyhow::Result<()> {
+        let mut query_builder = QueryBuilder::new(
+            "INSERT OR REPLACE INTO ingest_errors ( stele, publication, file_path, context, message ) ",
+        );
+        for chunk in ingest_errors.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, ie| {
+                bindings
+                    .push_bind(&ie.stele)
+                    .push_bind(&ie.publication)
+                    .push_bind(&ie.file_path)
+                    .push_bind(&ie.context)
+                    .push_bind(&ie.message);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing transactional ingest errors.
+#[async_trait]
+pub trait TxManager {
+    /// Insert a bulk of ingest errors.
+    async fn insert_bulk(&mut self, ingest_errors: Vec<IngestError>) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for a malformed RDF file encountered while updating a stele.
+pub struct IngestError {
+    /// Foreign key reference to the stele the file belongs to.
+    pub stele: String,
+    /// Name of the publication directory the file was found in, e.g. `2023-10-22`.
+    pub publication: String,
+    /// Path of the file relative to the publication directory.
+    pub file_path: String,
+    /// Where in the file loading stopped, if known.
+    /// Example: `12 triples loaded before the error`.
+    pub context: Option<String>,
+    /// The error reported by the RDF parser.
+    pub message: String,
+}
+
+impl IngestError {
+    /// Create a new ingest error.
+    #[must_use]
+    pub const fn new(
+        stele: String,
+        publication: String,
+        file_path: String,
+        context: Option<String>,
+        message: String,
+    ) -> Self {
+        Self {
+            stele,
+            publication,
+            file_path,
+            context,
+            message,
+        }
+    }
+}
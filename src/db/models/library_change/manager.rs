@@ -1,10 +1,15 @@
 //! Manager for the library change model.
 use crate::db::{
-    models::{status::Status, version::Version, BATCH_SIZE},
+    escape_like,
+    models::{
+        change_record::ChangeRecord, document_delta::DocumentDelta, status::Status,
+        version::Version, BATCH_SIZE,
+    },
     DatabaseConnection, DatabaseKind, DatabaseTransaction,
 };
 use async_trait::async_trait;
-use sqlx::QueryBuilder;
+use sqlx::{AnyConnection, QueryBuilder};
+use std::cmp::Reverse;
 
 use super::LibraryChange;
 
@@ -19,51 +24,182 @@ impl super::Manager for DatabaseConnection {
         mpath: &str,
         publication_id: &str,
     ) -> anyhow::Result<Vec<Version>> {
-        let mut statement = "
-            SELECT DISTINCT pv.version AS codified_date
-            FROM changed_library_document cld
-            LEFT JOIN document_change dc on cld.document_change_id = dc.id
-            LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
-            LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
-            WHERE cld.library_mpath LIKE $1 AND phpv.publication_id = $2
+        match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                find_collection_versions(&mut connection, mpath, publication_id).await
+            }
+        }
+    }
+
+    /// All library changes of a stele, optionally between two codified dates (inclusive).
+    ///
+    /// Changes from revoked publications are excluded.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_change_records_by_stele_and_date_range(
+        &self,
+        stele: &str,
+        start_date: Option<&str>,
+        end_date: Option<&str>,
+    ) -> anyhow::Result<Vec<ChangeRecord>> {
+        let statement = "
+            SELECT 'library' AS kind, lc.library_mpath AS mpath, el.url AS url, CAST(lc.status AS INTEGER) AS status,
+                NULL AS change_reason, pv.version AS codified_date, p.name AS publication
+            FROM library_change lc
+            INNER JOIN publication_version pv ON lc.publication_version_id = pv.id
+            INNER JOIN publication p ON pv.publication_id = p.id
+            LEFT JOIN library el ON lc.library_mpath = el.mpath
+            WHERE p.stele = $1 AND p.revoked = 0
+                AND ($2 IS NULL OR pv.version >= $2)
+                AND ($3 IS NULL OR pv.version <= $3)
+            ORDER BY pv.version, mpath
         ";
-        let mut rows = match self.kind {
+        let rows = match self.kind {
             DatabaseKind::Sqlite => {
                 let mut connection = self.pool.acquire().await?;
-                sqlx::query_as::<_, Version>(statement)
-                    .bind(format!("{mpath}%"))
-                    .bind(publication_id)
+                sqlx::query_as::<_, ChangeRecord>(statement)
+                    .bind(stele)
+                    .bind(start_date)
+                    .bind(end_date)
                     .fetch_all(&mut *connection)
                     .await?
             }
         };
-        statement = "
-            SELECT DISTINCT pv.version AS codified_date
-            FROM library_change lc
-            LEFT JOIN publication_has_publication_versions phpv ON lc.publication_version_id = phpv.publication_version_id
-            LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
-            WHERE lc.library_mpath LIKE $1 AND lc.status = $2 AND phpv.publication_id = $3
-            LIMIT 1
+        Ok(rows)
+    }
+
+    /// Net changes of the member documents of a collection in a publication, after `from_date`
+    /// up to and including `to_date`.
+    ///
+    /// A document added and removed within the dates is left out.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_member_deltas_by_mpath_and_publication_between_dates(
+        &self,
+        mpath: &str,
+        publication_id: &str,
+        from_date: &str,
+        to_date: &str,
+    ) -> anyhow::Result<Vec<DocumentDelta>> {
+        let statement = r"
+            WITH changes AS (
+                SELECT DISTINCT dc.doc_mpath, CAST(dc.status AS INTEGER) AS status, pv.version
+                FROM changed_library_document cld
+                INNER JOIN document_change dc ON cld.document_change_id = dc.id
+                INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                WHERE cld.library_mpath LIKE $1 ESCAPE '\' AND phpv.publication_id = $2
+                    AND pv.version > $3 AND pv.version <= $4
+                    AND CAST(dc.status AS INTEGER) IN (0, 2, 3)
+            ),
+            documents AS (
+                SELECT c.doc_mpath,
+                    MAX(c.status = 0) AS added,
+                    (
+                        SELECT latest.status
+                        FROM changes latest
+                        WHERE latest.doc_mpath = c.doc_mpath
+                        ORDER BY latest.version DESC, latest.status DESC
+                        LIMIT 1
+                    ) AS latest_status
+                FROM changes c
+                GROUP BY c.doc_mpath
+            )
+            SELECT d.doc_mpath, el.url,
+                CASE
+                    WHEN d.latest_status = 3 THEN 'removed'
+                    WHEN d.added = 1 THEN 'new'
+                    ELSE 'changed'
+                END AS change
+            FROM documents d
+            LEFT JOIN document_element el ON d.doc_mpath = el.doc_mpath
+            WHERE NOT (d.added = 1 AND d.latest_status = 3)
+            ORDER BY d.doc_mpath
         ";
-        let element_added = match self.kind {
+        let rows = match self.kind {
             DatabaseKind::Sqlite => {
                 let mut connection = self.pool.acquire().await?;
-                sqlx::query_as::<_, Version>(statement)
-                    .bind(format!("{mpath}%"))
-                    .bind(Status::ElementAdded.to_int())
+                sqlx::query_as::<_, DocumentDelta>(statement)
+                    .bind(format!("{}%", escape_like(mpath)))
                     .bind(publication_id)
-                    .fetch_one(&mut *connection)
-                    .await
-                    .ok()
+                    .bind(from_date)
+                    .bind(to_date)
+                    .fetch_all(&mut *connection)
+                    .await?
             }
         };
+        Ok(rows)
+    }
 
-        if let Some(el_added) = element_added {
-            if !rows.contains(&el_added) {
-                rows.push(el_added);
+    /// Net change of every collection changed in a publication since the previous publication.
+    ///
+    /// Only versions of the publication that are not in the previous publication are considered.
+    /// A collection added and removed since the previous publication is left out.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_library_deltas_by_publication(
+        &self,
+        publication_id: &str,
+        previous_publication_id: Option<&str>,
+    ) -> anyhow::Result<Vec<DocumentDelta>> {
+        let statement = "
+            WITH previous_versions AS (
+                SELECT pv.version
+                FROM publication_has_publication_versions phpv
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                WHERE phpv.publication_id = $2
+            ),
+            new_versions AS (
+                SELECT pv.id, pv.version
+                FROM publication_has_publication_versions phpv
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                WHERE phpv.publication_id = $1
+                    AND pv.version NOT IN (SELECT version FROM previous_versions)
+            ),
+            changes AS (
+                SELECT lc.library_mpath, CAST(lc.status AS INTEGER) AS status, nv.version
+                FROM library_change lc
+                INNER JOIN new_versions nv ON lc.publication_version_id = nv.id
+                WHERE CAST(lc.status AS INTEGER) IN (0, 2, 3)
+            ),
+            collections AS (
+                SELECT c.library_mpath,
+                    MAX(c.status = 0) AS added,
+                    (
+                        SELECT latest.status
+                        FROM changes latest
+                        WHERE latest.library_mpath = c.library_mpath
+                        ORDER BY latest.version DESC, latest.status DESC
+                        LIMIT 1
+                    ) AS latest_status
+                FROM changes c
+                GROUP BY c.library_mpath
+            )
+            SELECT c.library_mpath AS doc_mpath, l.url,
+                CASE
+                    WHEN c.latest_status = 3 THEN 'removed'
+                    WHEN c.added = 1 THEN 'new'
+                    ELSE 'changed'
+                END AS change
+            FROM collections c
+            LEFT JOIN library l ON c.library_mpath = l.mpath
+            WHERE NOT (c.added = 1 AND c.latest_status = 3)
+            ORDER BY c.library_mpath
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, DocumentDelta>(statement)
+                    .bind(publication_id)
+                    .bind(previous_publication_id)
+                    .fetch_all(&mut *connection)
+                    .await?
             }
-        }
-        rows.sort_by(|v1, v2| v2.codified_date.cmp(&v1.codified_date));
+        };
         Ok(rows)
     }
 }
@@ -90,3 +226,142 @@ impl super::TxManager for DatabaseTransaction {
         Ok(())
     }
 }
+
+/// All dates on which documents from this collection changed, on `connection`.
+async fn find_collection_versions(
+    connection: &mut AnyConnection,
+    mpath: &str,
+    publication_id: &str,
+) -> anyhow::Result<Vec<Version>> {
+    let mut statement = r"
+        SELECT DISTINCT pv.version AS codified_date
+        FROM changed_library_document cld
+        LEFT JOIN document_change dc on cld.document_change_id = dc.id
+        LEFT JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+        LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
+        WHERE cld.library_mpath LIKE $1 ESCAPE '\' AND phpv.publication_id = $2
+    ";
+    let mut rows = sqlx::query_as::<_, Version>(statement)
+        .bind(format!("{}%", escape_like(mpath)))
+        .bind(publication_id)
+        .fetch_all(&mut *connection)
+        .await?;
+    statement = r"
+        SELECT DISTINCT pv.version AS codified_date
+        FROM library_change lc
+        LEFT JOIN publication_has_publication_versions phpv ON lc.publication_version_id = phpv.publication_version_id
+        LEFT JOIN publication_version pv ON phpv.publication_version_id = pv.id
+        WHERE lc.library_mpath LIKE $1 ESCAPE '\' AND lc.status = $2 AND phpv.publication_id = $3
+        LIMIT 1
+    ";
+    let element_added = sqlx::query_as::<_, Version>(statement)
+        .bind(format!("{}%", escape_like(mpath)))
+        .bind(Status::ElementAdded.to_int())
+        .bind(publication_id)
+        .fetch_one(&mut *connection)
+        .await
+        .ok();
+
+    if let Some(el_added) = element_added {
+        if !rows.contains(&el_added) {
+            rows.push(el_added);
+        }
+    }
+    rows.sort_by_key(|version| Reverse(version.codified_date));
+    Ok(rows)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::fixture::{self, STELE};
+    use crate::db::models::library_change::Manager as _;
+    use crate::db::Tx as _;
+
+    /// Statements inserting the collections `c_` and `cb`, whose materialized paths only differ
+    /// where `c_` has a `LIKE` wildcard, and the documents `d1` and `d2` of `c_` and `d3` of `cb`,
+    /// changed in the versions of the publication `pb`.
+    const FIXTURE: &[&str] = &[
+        "INSERT INTO version (codified_date) VALUES ('2020-01-01'), ('2020-02-01')",
+        "INSERT INTO publication_version (id, version, publication_id) VALUES ('v1', '2020-01-01', 'pb'), ('v2', '2020-02-01', 'pb')",
+        "INSERT INTO publication_has_publication_versions (publication_id, publication_version_id) VALUES ('pb', 'v1'), ('pb', 'v2')",
+        "INSERT INTO document (doc_id) VALUES ('d1'), ('d2'), ('d3')",
+        "INSERT INTO document_element (doc_mpath, url, doc_id, stele) VALUES ('c_|d1|', '/c_/d1', 'd1', 'org/law'), ('c_|d2|', '/c_/d2', 'd2', 'org/law'), ('cb|d3|', '/cb/d3', 'd3', 'org/law')",
+        "INSERT INTO document_change (id, status, publication_version_id, doc_mpath) VALUES ('c1', 0, 'v1', 'c_|d1|'), ('c2', 2, 'v2', 'c_|d1|'), ('c3', 0, 'v2', 'c_|d2|'), ('c4', 0, 'v2', 'cb|d3|')",
+        "INSERT INTO changed_library_document (library_mpath, document_change_id) VALUES ('c_|', 'c1'), ('c_|', 'c2'), ('c_|', 'c3'), ('cb|', 'c4')",
+    ];
+
+    #[actix_web::test]
+    async fn test_find_all_member_deltas_by_mpath_and_publication_between_dates_when_wildcard_expect_net_changes_of_members(
+    ) {
+        let fixture = fixture::begin(STELE, &[("pb", "2020-02-01")], FIXTURE).await;
+        let db = fixture.db;
+        fixture.tx.commit().await.unwrap();
+
+        for (from_date, expected) in [
+            ("2019-12-31", vec![("c_|d1|", "new"), ("c_|d2|", "new")]),
+            ("2020-01-01", vec![("c_|d1|", "changed"), ("c_|d2|", "new")]),
+            ("2020-02-01", vec![]),
+        ] {
+            let actual = db
+                .find_all_member_deltas_by_mpath_and_publication_between_dates(
+                    "c_|",
+                    "pb",
+                    from_date,
+                    "2020-02-01",
+                )
+                .await
+                .unwrap();
+            let changes: Vec<(&str, &str)> = actual
+                .iter()
+                .map(|delta| (delta.doc_mpath.as_str(), delta.change.as_str()))
+                .collect();
+            assert_eq!(changes, expected, "{from_date}");
+        }
+    }
+
+    /// Statements inserting the versions of the publication `pa`, and of the later publication
+    /// `pb` that keeps the version of `pa`, with collections added, changed and removed in them.
+    const DELTA_FIXTURE: &[&str] = &[
+        "INSERT INTO version (codified_date) VALUES ('2020-01-01'), ('2020-02-01'), ('2020-03-01')",
+        "INSERT INTO publication_version (id, version, publication_id) VALUES ('a1', '2020-01-01', 'pa'), ('b1', '2020-01-01', 'pb'), ('b2', '2020-02-01', 'pb'), ('b3', '2020-03-01', 'pb')",
+        "INSERT INTO publication_has_publication_versions (publication_id, publication_version_id) VALUES ('pa', 'a1'), ('pb', 'b1'), ('pb', 'b2'), ('pb', 'b3')",
+        "INSERT INTO library (mpath, url, stele) VALUES ('c1|', '/c1', 'org/law'), ('c2|', '/c2', 'org/law'), ('c4|', '/c4', 'org/law'), ('c5|', '/c5', 'org/law')",
+        "INSERT INTO library_change (publication_version_id, status, library_mpath) VALUES ('a1', '0', 'c1|'), ('b1', '0', 'c1|'), ('b1', '0', 'c5|'), ('b2', '2', 'c1|'), ('b2', '0', 'c2|'), ('b2', '0', 'c3|'), ('b3', '3', 'c3|'), ('b3', '3', 'c4|')",
+    ];
+
+    #[actix_web::test]
+    async fn test_find_all_library_deltas_by_publication_when_previous_publication_expect_net_changes_since(
+    ) {
+        let publications = [("pa", "2020-01-01"), ("pb", "2020-03-01")];
+        let fixture = fixture::begin(STELE, &publications, DELTA_FIXTURE).await;
+        let db = fixture.db;
+        fixture.tx.commit().await.unwrap();
+
+        for (previous, expected) in [
+            (
+                Some("pa"),
+                vec![("c1|", "changed"), ("c2|", "new"), ("c4|", "removed")],
+            ),
+            (
+                None,
+                vec![
+                    ("c1|", "new"),
+                    ("c2|", "new"),
+                    ("c4|", "removed"),
+                    ("c5|", "new"),
+                ],
+            ),
+        ] {
+            let actual = db
+                .find_all_library_deltas_by_publication("pb", previous)
+                .await
+                .unwrap();
+            let changes: Vec<(&str, &str)> = actual
+                .iter()
+                .map(|delta| (delta.doc_mpath.as_str(), delta.change.as_str()))
+                .collect();
+            assert_eq!(changes, expected, "{previous:?}");
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use super::change_record::ChangeRecord;
+use super::document_delta::DocumentDelta;
 use super::version::Version;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,28 @@ pub trait Manager {
         mpath: &str,
         publication: &str,
     ) -> anyhow::Result<Vec<Version>>;
+    /// All collection changes of a stele, optionally between two codified dates (inclusive).
+    async fn find_all_change_records_by_stele_and_date_range(
+        &self,
+        stele: &str,
+        start_date: Option<&str>,
+        end_date: Option<&str>,
+    ) -> anyhow::Result<Vec<ChangeRecord>>;
+    /// Net changes of the member documents of a collection in a publication, after `from_date`
+    /// up to and including `to_date`.
+    async fn find_all_member_deltas_by_mpath_and_publication_between_dates(
+        &self,
+        mpath: &str,
+        publication_id: &str,
+        from_date: &str,
+        to_date: &str,
+    ) -> anyhow::Result<Vec<DocumentDelta>>;
+    /// Net change of every collection changed in a publication since the previous publication.
+    async fn find_all_library_deltas_by_publication(
+        &self,
+        publication_id: &str,
+        previous_publication_id: Option<&str>,
+    ) -> anyhow::Result<Vec<DocumentDelta>>;
 }
 
 /// Trait for managing transactional collection changes.
@@ -3,16 +3,33 @@
 /// Size of the batch for bulk inserts.
 const BATCH_SIZE: usize = 1000;
 
+/// module for interacting with the `broken_links` table.
+pub mod broken_link;
+/// module for the change records joined from the `document_change` and `library_change` tables.
+pub mod change_record;
 /// module for interacting with the `changed_library_document` table.
 pub mod changed_library_document;
+/// module for interacting with the `current_blobs` table.
+pub mod current_blob;
 /// module for interacting with the `data_repos` table.
 pub mod data_repo_commits;
 /// module for interacting with the `document` table.
 pub mod document;
 /// module for interacting with the `document_change` table.
 pub mod document_change;
+/// module for the net document changes between publications, joined from the `document_change` table.
+pub mod document_delta;
 /// module for interacting with the `document_element` table.
 pub mod document_element;
+/// module for interacting with the `document_views` table.
+pub mod document_view;
+/// module setting up databases for tests.
+#[cfg(test)]
+pub mod fixture;
+/// module for interacting with the `identifiers` table.
+pub mod identifier;
+/// module for interacting with the `ingest_errors` table.
+pub mod ingest_error;
 /// module for interacting with the `library` table.
 pub mod library;
 /// module for interacting with the `library_change` table.
@@ -23,9 +40,23 @@ pub mod publication;
 pub mod publication_has_publication_versions;
 /// module for interacting with the `publication_version` table
 pub mod publication_version;
+/// module for interacting with the `references` table.
+pub mod reference;
+/// module for interacting with the `repository_sizes` table.
+pub mod repository_size;
+/// module for interacting with the `snapshots` and `snapshot_commits` tables.
+pub mod snapshot;
 /// module for the document or library status utility.
 pub mod status;
 /// module for interacting with the `stele` table.
 pub mod stele;
+/// module for the statistics of a stele, aggregated from the document, library and publication tables.
+pub mod stele_stats;
+/// module for the suggestions found in the `document_element` and `library` tables.
+pub mod suggestion;
+/// module for interacting with the `takedowns` table.
+pub mod takedown;
 /// module for interacting with the `version` table.
 pub mod version;
+/// module for interacting with the `webhook_deliveries` table.
+pub mod webhook_delivery;
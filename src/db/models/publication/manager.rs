@@ -1,24 +1,27 @@
 //! Manager for the publication model.
 use crate::db::{DatabaseConnection, DatabaseKind, DatabaseTransaction};
+use crate::utils::date;
 use async_trait::async_trait;
 use chrono::NaiveDate;
+use sqlx::AnyConnection;
 
 use super::Publication;
 
 #[async_trait]
 impl super::Manager for DatabaseConnection {
-    /// Find all publications which are not revoked for a given stele.
+    /// Find all approved or live publications which are neither revoked nor previews for a given
+    /// stele.
     ///
     /// # Errors
     /// Errors if can't establish a connection to the database.
-    async fn find_all_non_revoked_publications(
+    async fn find_all_approved_publications(
         &self,
         stele: &str,
     ) -> anyhow::Result<Vec<Publication>> {
         let statement = "
             SELECT *
             FROM publication
-            WHERE revoked = 0 AND stele = $1
+            WHERE revoked = 0 AND preview = 0 AND state IS NOT 'ingested' AND stele = $1
             ORDER BY name DESC
         ";
         let rows = match self.kind {
@@ -32,6 +35,46 @@ impl super::Manager for DatabaseConnection {
         };
         Ok(rows)
     }
+    /// Find all publications which are neither revoked, previews nor awaiting approval or release
+    /// for a given stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_non_revoked_publications(
+        &self,
+        stele: &str,
+    ) -> anyhow::Result<Vec<Publication>> {
+        match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                find_all_non_revoked(&mut connection, stele).await
+            }
+        }
+    }
+    /// Find the latest publication ingested for a given stele, whether it is revoked, a preview or
+    /// awaiting approval, like the latest publication of its RDF repository.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_latest_ingested(&self, stele: &str) -> anyhow::Result<Option<Publication>> {
+        let statement = "
+            SELECT *
+            FROM publication
+            WHERE stele = $1
+            ORDER BY date DESC, name DESC
+            LIMIT 1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Publication>(statement)
+                    .bind(stele)
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
 }
 
 #[async_trait]
@@ -56,7 +99,7 @@ impl super::TxManager for DatabaseTransaction {
         let id = sqlx::query(statement)
             .bind(hash_id)
             .bind(name)
-            .bind(date.to_string())
+            .bind(date::format(*date))
             .bind(stele)
             .bind(last_valid_publication_id)
             .bind(last_valid_version)
@@ -134,8 +177,8 @@ impl super::TxManager for DatabaseTransaction {
     /// Errors if can't establish a connection to the database.
     async fn find_all_by_date_and_stele_order_by_name_desc(
         &mut self,
-        date: String,
-        stele: String,
+        date: &NaiveDate,
+        stele: &str,
     ) -> anyhow::Result<Vec<Publication>> {
         let statement = "
             SELECT *
@@ -144,10 +187,133 @@ impl super::TxManager for DatabaseTransaction {
             ORDER BY name DESC
         ";
         let rows = sqlx::query_as::<_, Publication>(statement)
-            .bind(date)
+            .bind(date::format(*date))
+            .bind(stele)
+            .fetch_all(&mut *self.tx)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Find all publications which are neither revoked, previews nor awaiting approval or release
+    /// for a given stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_non_revoked_by_stele(
+        &mut self,
+        stele: &str,
+    ) -> anyhow::Result<Vec<Publication>> {
+        find_all_non_revoked(&mut self.tx, stele).await
+    }
+
+    /// Find all publications which are not revoked for a given stele, including previews.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_non_revoked_with_previews_by_stele(
+        &mut self,
+        stele: &str,
+    ) -> anyhow::Result<Vec<Publication>> {
+        let statement = "
+            SELECT *
+            FROM publication
+            WHERE revoked = 0 AND stele = $1
+            ORDER BY name DESC
+        ";
+        let rows = sqlx::query_as::<_, Publication>(statement)
             .bind(stele)
             .fetch_all(&mut *self.tx)
             .await?;
         Ok(rows)
     }
+
+    /// Update a publication by name and stele to be a preview, or to be live.
+    ///
+    /// # Errors
+    /// Errors if the publication cannot be updated.
+    async fn update_by_name_and_stele_set_preview(
+        &mut self,
+        name: &str,
+        stele: &str,
+        preview: bool,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            UPDATE publication
+            SET preview = $1
+            WHERE name = $2 AND stele = $3
+        ";
+        sqlx::query(statement)
+            .bind(i64::from(preview))
+            .bind(name)
+            .bind(stele)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Update the state of a publication by name and stele.
+    ///
+    /// # Errors
+    /// Errors if the publication cannot be updated.
+    async fn update_by_name_and_stele_set_state(
+        &mut self,
+        name: &str,
+        stele: &str,
+        state: &str,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            UPDATE publication
+            SET state = $1
+            WHERE name = $2 AND stele = $3
+        ";
+        sqlx::query(statement)
+            .bind(state)
+            .bind(name)
+            .bind(stele)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Set the state of the publications of a stele that have none.
+    ///
+    /// # Errors
+    /// Errors if the publications cannot be updated.
+    async fn update_by_stele_set_unset_state(
+        &mut self,
+        stele: &str,
+        state: &str,
+    ) -> anyhow::Result<()> {
+        let statement = "
+            UPDATE publication
+            SET state = $1
+            WHERE stele = $2 AND state IS NULL
+        ";
+        sqlx::query(statement)
+            .bind(state)
+            .bind(stele)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Find all publications which are neither revoked, previews nor awaiting approval or release,
+/// i.e. the live publications, for a given stele, on `connection`.
+async fn find_all_non_revoked(
+    connection: &mut AnyConnection,
+    stele: &str,
+) -> anyhow::Result<Vec<Publication>> {
+    let statement = "
+        SELECT *
+        FROM publication
+        WHERE revoked = 0 AND preview = 0 AND state IS NOT 'ingested' AND state IS NOT 'approved'
+            AND stele = $1
+        ORDER BY name DESC
+    ";
+    let rows = sqlx::query_as::<_, Publication>(statement)
+        .bind(stele)
+        .fetch_all(&mut *connection)
+        .await?;
+    Ok(rows)
 }
@@ -3,16 +3,43 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use sqlx::{any::AnyRow, FromRow, Row as _};
 
+use crate::utils::date;
+
 pub mod manager;
 
+/// State of a publication that awaits approval. Never served.
+pub const INGESTED: &str = "ingested";
+/// State of an approved publication awaiting release. Served like a preview, only to requests
+/// that send the preview token, so it can be reviewed as the current publication before release.
+pub const APPROVED: &str = "approved";
+/// State of a released publication, served to every request, and of every publication ingested
+/// without approval.
+pub const LIVE: &str = "live";
+
+/// The state a publication in `state` moves to next, or `None` if it is [`LIVE`].
+#[must_use]
+pub fn next_state(state: &str) -> Option<&'static str> {
+    match state {
+        INGESTED => Some(APPROVED),
+        APPROVED => Some(LIVE),
+        _ => None,
+    }
+}
+
 /// Trait for managing publications.
 #[async_trait]
 pub trait Manager {
+    /// Find all approved or live publications which are neither revoked nor previews for a given
+    /// stele.
+    async fn find_all_approved_publications(&self, stele: &str)
+        -> anyhow::Result<Vec<Publication>>;
     /// Find all publications which are not revoked for a given stele.
     async fn find_all_non_revoked_publications(
         &self,
         stele: &str,
     ) -> anyhow::Result<Vec<Publication>>;
+    /// Find the latest publication ingested for a given stele, whatever its state.
+    async fn find_latest_ingested(&self, stele: &str) -> anyhow::Result<Option<Publication>>;
 }
 
 /// Trait for managing transactions on publications.
@@ -46,12 +73,42 @@ pub trait TxManager {
     /// Used in revocation logic to find the latest publication.
     async fn find_all_by_date_and_stele_order_by_name_desc(
         &mut self,
-        date: String,
-        stele: String,
+        date: &NaiveDate,
+        stele: &str,
     ) -> anyhow::Result<Vec<Publication>>;
+    /// Find all publications which are not revoked for a given stele.
+    async fn find_all_non_revoked_by_stele(
+        &mut self,
+        stele: &str,
+    ) -> anyhow::Result<Vec<Publication>>;
+    /// Find all publications which are not revoked for a given stele, including previews.
+    async fn find_all_non_revoked_with_previews_by_stele(
+        &mut self,
+        stele: &str,
+    ) -> anyhow::Result<Vec<Publication>>;
+    /// Update a publication by name and set whether it is a preview.
+    async fn update_by_name_and_stele_set_preview(
+        &mut self,
+        name: &str,
+        stele: &str,
+        preview: bool,
+    ) -> anyhow::Result<()>;
+    /// Update a publication by name and set its state.
+    async fn update_by_name_and_stele_set_state(
+        &mut self,
+        name: &str,
+        stele: &str,
+        state: &str,
+    ) -> anyhow::Result<()>;
+    /// Set the state of the publications of a stele that have none, i.e. were just inserted.
+    async fn update_by_stele_set_unset_state(
+        &mut self,
+        stele: &str,
+        state: &str,
+    ) -> anyhow::Result<()>;
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 /// Model for a Stele.
 pub struct Publication {
     /// A hashed identifier for the publication.
@@ -62,7 +119,8 @@ pub struct Publication {
     /// when two publications exist on same date.
     pub name: String,
     /// Date of the publication.
-    pub date: String,
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
     /// Foreign key reference to stele by name.
     pub stele: String,
     /// Whether the publication has been revoked.
@@ -76,6 +134,13 @@ pub struct Publication {
     /// represents the last publication version (codified date) from the previous publication
     /// that the current publication is derived from.
     pub last_valid_version: Option<String>,
+    /// Whether the publication is a preview.
+    /// Previews are only served to requests that carry the preview token.
+    pub preview: i64,
+    /// State of the publication, one of [`INGESTED`], [`APPROVED`] or [`LIVE`].
+    /// Publications ingested before approval was required are [`LIVE`]. A publication has no
+    /// state until `stelae update` finishes inserting the changes of its stele.
+    pub state: String,
 }
 
 impl FromRow<'_, AnyRow> for Publication {
@@ -83,11 +148,13 @@ impl FromRow<'_, AnyRow> for Publication {
         Ok(Self {
             id: row.try_get("id")?,
             name: row.try_get("name")?,
-            date: row.try_get("date")?,
+            date: date::from_row(row, "date")?,
             stele: row.try_get("stele")?,
             revoked: row.try_get("revoked")?,
             last_valid_publication_id: row.try_get("last_valid_publication_id").ok(),
             last_valid_version: row.try_get("last_valid_version").ok(),
+            preview: row.try_get("preview").unwrap_or_default(),
+            state: row.try_get("state").unwrap_or_else(|_| LIVE.to_owned()),
         })
     }
 }
@@ -95,7 +162,7 @@ impl FromRow<'_, AnyRow> for Publication {
 impl Publication {
     /// Create a new publication.
     #[must_use]
-    pub const fn new(id: String, name: String, date: String, stele: String) -> Self {
+    pub fn new(id: String, name: String, date: NaiveDate, stele: String) -> Self {
         Self {
             id,
             name,
@@ -104,6 +171,8 @@ impl Publication {
             revoked: 0,
             last_valid_publication_id: None,
             last_valid_version: None,
+            preview: 0,
+            state: LIVE.to_owned(),
         }
     }
 }
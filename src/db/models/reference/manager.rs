@@ -0,0 +1,104 @@
+//! Manager for the reference model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::Reference;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find all references made by the document at `source_path` of a stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele_and_source(
+        &self,
+        stele: &str,
+        source_path: &str,
+    ) -> anyhow::Result<Vec<Reference>> {
+        let statement = r#"
+            SELECT stele, source_path, target_path, href
+            FROM "references"
+            WHERE stele = $1 AND source_path = $2
+            ORDER BY target_path, href
+        "#;
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Reference>(statement)
+                    .bind(stele)
+                    .bind(source_path)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Find all references to the document at `target_path` of a stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele_and_target(
+        &self,
+        stele: &str,
+        target_path: &str,
+    ) -> anyhow::Result<Vec<Reference>> {
+        let statement = r#"
+            SELECT stele, source_path, target_path, href
+            FROM "references"
+            WHERE stele = $1 AND target_path = $2
+            ORDER BY source_path, href
+        "#;
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Reference>(statement)
+                    .bind(stele)
+                    .bind(target_path)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Replace the references recorded for a stele with `references`.
+    ///
+    /// # Errors
+    /// Errors if the references cannot be deleted or inserted.
+    async fn replace_all_by_stele(
+        &mut self,
+        stele: &str,
+        references: Vec<Reference>,
+    ) -> anyhow::Result<()> {
+        let statement = r#"
+            DELETE FROM "references"
+            WHERE stele = $1
+        "#;
+        sqlx::query(statement)
+            .bind(stele)
+            .execute(&mut *self.tx)
+            .await?;
+        let mut query_builder = QueryBuilder::new(
+            r#"INSERT OR IGNORE INTO "references" ( stele, source_path, target_path, href ) "#,
+        );
+        for chunk in references.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, reference| {
+                bindings
+                    .push_bind(&reference.stele)
+                    .push_bind(&reference.source_path)
+                    .push_bind(&reference.target_path)
+                    .push_bind(&reference.href);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing references between documents.
+#[async_trait]
+pub trait Manager {
+    /// Find all references made by a document of a stele.
+    async fn find_all_by_stele_and_source(
+        &self,
+        stele: &str,
+        source_path: &str,
+    ) -> anyhow::Result<Vec<Reference>>;
+    /// Find all references to a document of a stele.
+    async fn find_all_by_stele_and_target(
+        &self,
+        stele: &str,
+        target_path: &str,
+    ) -> anyhow::Result<Vec<Reference>>;
+}
+
+/// Trait for managing transactional references between documents.
+#[async_trait]
+pub trait TxManager {
+    /// Replace the references recorded for a stele.
+    async fn replace_all_by_stele(
+        &mut self,
+        stele: &str,
+        references: Vec<Reference>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Model for a citation in a served html document of another document of the same stele.
+pub struct Reference {
+    /// Foreign key reference to the stele the documents belong to.
+    pub stele: String,
+    /// Path of the citing document, without file extension or index file, e.g. `a/b/c`.
+    pub source_path: String,
+    /// Path of the cited document, without file extension or index file, e.g. `a/b/d`.
+    pub target_path: String,
+    /// The link, exactly as found in the citing document.
+    pub href: String,
+}
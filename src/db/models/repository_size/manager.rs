@@ -0,0 +1,58 @@
+//! Manager for the recorded repository size model.
+use async_trait::async_trait;
+
+use crate::db::{DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::RecordedSize;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find the latest recorded size of every repository of a stele, ordered by repository.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_latest_by_stele(&self, stele: &str) -> anyhow::Result<Vec<RecordedSize>> {
+        let statement = "
+            SELECT rs.stele, rs.repository, rs.bytes, rs.recorded_at
+            FROM repository_sizes rs
+            WHERE rs.stele = $1 AND rs.id = (
+                SELECT MAX(latest.id)
+                FROM repository_sizes latest
+                WHERE latest.stele = rs.stele AND latest.repository = rs.repository
+            )
+            ORDER BY rs.repository
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, RecordedSize>(statement)
+                    .bind(stele)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Insert a new recorded repository size into the database.
+    ///
+    /// # Errors
+    /// Errors if the size cannot be inserted.
+    async fn create(&mut self, recorded: &RecordedSize) -> anyhow::Result<()> {
+        let statement = "
+            INSERT INTO repository_sizes ( stele, repository, bytes, recorded_at )
+            VALUES ( $1, $2, $3, $4 )
+        ";
+        sqlx::query(statement)
+            .bind(&recorded.stele)
+            .bind(&recorded.repository)
+            .bind(recorded.bytes)
+            .bind(&recorded.recorded_at)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing recorded repository sizes.
+#[async_trait]
+pub trait Manager {
+    /// Find the latest recorded size of every repository of a stele.
+    async fn find_all_latest_by_stele(&self, stele: &str) -> anyhow::Result<Vec<RecordedSize>>;
+}
+
+/// Trait for managing transactional recorded repository sizes.
+#[async_trait]
+pub trait TxManager {
+    /// Insert a new recorded repository size.
+    async fn create(&mut self, recorded: &RecordedSize) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Model for the size on disk of a repository, recorded by `stelae disk-usage`.
+pub struct RecordedSize {
+    /// Qualified name of the stele the repository belongs to.
+    pub stele: String,
+    /// Qualified name of the repository, e.g. `org-name/law-html`.
+    pub repository: String,
+    /// Size of all files of the repository, in bytes.
+    pub bytes: i64,
+    /// Time the size was recorded at, as `YYYY-MM-DD HH:MM:SS` in UTC.
+    pub recorded_at: String,
+}
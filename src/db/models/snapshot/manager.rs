@@ -0,0 +1,107 @@
+//! Manager for the snapshot model.
+use async_trait::async_trait;
+use sqlx::QueryBuilder;
+
+use crate::db::{models::BATCH_SIZE, DatabaseConnection, DatabaseKind, DatabaseTransaction};
+use crate::utils::date;
+
+use super::{PinnedCommit, Snapshot};
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find a snapshot by its name.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_by_name(&self, name: &str) -> anyhow::Result<Option<Snapshot>> {
+        let statement = "
+            SELECT name, stele, publication, date, auth_commit_hash
+            FROM snapshots
+            WHERE name = $1
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Snapshot>(statement)
+                    .bind(name)
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+
+    /// Find all data repository commits pinned by a snapshot.
+    ///
+    /// Commits of `html` repositories come last, as they usually act as the fallback.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_commits_by_snapshot(&self, name: &str) -> anyhow::Result<Vec<PinnedCommit>> {
+        let statement = "
+            SELECT *
+            FROM snapshot_commits
+            WHERE snapshot = $1
+            ORDER BY repo_type = 'html', repository
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, PinnedCommit>(statement)
+                    .bind(name)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Insert a new snapshot into the database.
+    ///
+    /// # Errors
+    /// Errors if the snapshot cannot be inserted, e.g. if a snapshot with the same name exists.
+    async fn create(&mut self, snapshot: &Snapshot) -> anyhow::Result<()> {
+        let statement = "
+            INSERT INTO snapshots ( name, stele, publication, date, auth_commit_hash )
+            VALUES ( $1, $2, $3, $4, $5 )
+        ";
+        sqlx::query(statement)
+            .bind(&snapshot.name)
+            .bind(&snapshot.stele)
+            .bind(&snapshot.publication)
+            .bind(date::format(snapshot.date))
+            .bind(&snapshot.auth_commit_hash)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Insert a bulk of snapshot commits into the database.
+    ///
+    /// # Errors
+    /// Errors if the snapshot commits cannot be inserted.
+    async fn insert_commits_bulk(
+        &mut self,
+        snapshot_commits: Vec<PinnedCommit>,
+    ) -> anyhow::Result<()> {
+        let mut query_builder = QueryBuilder::new(
+            "INSERT INTO snapshot_commits ( snapshot, repository, repo_type, commit_hash ) ",
+        );
+        for chunk in snapshot_commits.chunks(BATCH_SIZE) {
+            query_builder.push_values(chunk, |mut bindings, sc| {
+                bindings
+                    .push_bind(&sc.snapshot)
+                    .push_bind(&sc.repository)
+                    .push_bind(&sc.repo_type)
+                    .push_bind(&sc.commit_hash);
+            });
+            let query = query_builder.build();
+            query.execute(&mut *self.tx).await?;
+            query_builder.reset();
+        }
+        Ok(())
+    }
+}
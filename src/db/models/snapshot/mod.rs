@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow, Row as _};
+
+use crate::utils::date;
+
+pub mod manager;
+
+/// Trait for managing snapshots.
+#[async_trait]
+pub trait Manager {
+    /// Find a snapshot by its name.
+    async fn find_by_name(&self, name: &str) -> anyhow::Result<Option<Snapshot>>;
+    /// Find all data repository commits pinned by a snapshot.
+    async fn find_all_commits_by_snapshot(&self, name: &str) -> anyhow::Result<Vec<PinnedCommit>>;
+}
+
+/// Trait for managing transactional snapshots.
+#[async_trait]
+pub trait TxManager {
+    /// Insert a new snapshot.
+    async fn create(&mut self, snapshot: &Snapshot) -> anyhow::Result<()>;
+    /// Insert a bulk of snapshot commits.
+    async fn insert_commits_bulk(
+        &mut self,
+        snapshot_commits: Vec<PinnedCommit>,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for a named, immutable snapshot of a stele's data repositories.
+pub struct Snapshot {
+    /// Unique name of the snapshot, e.g. `smith-v-jones-2023`.
+    pub name: String,
+    /// Qualified name of the stele the snapshot was pinned for.
+    pub stele: String,
+    /// Id of the publication the pinned commits belong to.
+    pub publication: String,
+    /// Date of the pinned documents.
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
+    /// Authentication commit that authenticated the pinned commits.
+    pub auth_commit_hash: String,
+}
+
+impl FromRow<'_, AnyRow> for Snapshot {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            name: row.try_get("name")?,
+            stele: row.try_get("stele")?,
+            publication: row.try_get("publication")?,
+            date: date::from_row(row, "date")?,
+            auth_commit_hash: row.try_get("auth_commit_hash")?,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for one data repository commit pinned by a snapshot.
+pub struct PinnedCommit {
+    /// Foreign key reference to the snapshot name.
+    pub snapshot: String,
+    /// Qualified name of the data repository, e.g. `org-name/law-html`.
+    pub repository: String,
+    /// Type of the data repository. E.g. `html`.
+    pub repo_type: String,
+    /// Pinned commit hash of the data repository.
+    pub commit_hash: String,
+}
@@ -26,6 +26,30 @@ impl Status {
         }
     }
 
+    /// Convert an integer to a `Status` enum.
+    /// # Errors
+    /// Returns an error if the integer is not a valid status value.
+    pub fn from_int(status: i64) -> anyhow::Result<Self> {
+        match status {
+            0 => Ok(Self::ElementAdded),
+            1 => Ok(Self::ElementEffective),
+            2 => Ok(Self::ElementChanged),
+            3 => Ok(Self::ElementRemoved),
+            _ => Err(anyhow::anyhow!("Invalid status value")),
+        }
+    }
+
+    /// Convert a `Status` enum to its string representation.
+    #[must_use]
+    pub const fn to_str(&self) -> &'static str {
+        match *self {
+            Self::ElementAdded => "Element added",
+            Self::ElementChanged => "Element changed",
+            Self::ElementRemoved => "Element removed",
+            Self::ElementEffective => "Element effective",
+        }
+    }
+
     /// Convert a `Status` enum to an integer.
     #[must_use]
     pub const fn to_int(&self) -> i64 {
@@ -1,7 +1,24 @@
 //! Manager for the stele model.
+use super::Rename;
 use crate::db::DatabaseTransaction;
 use async_trait::async_trait;
 
+/// Tables with a `stele` column holding the qualified name of a stele.
+const STELE_TABLES: [&str; 12] = [
+    "document_element",
+    "library",
+    "publication",
+    "ingest_errors",
+    "snapshots",
+    "broken_links",
+    "identifiers",
+    "\"references\"",
+    "webhook_deliveries",
+    "repository_sizes",
+    "document_views",
+    "current_blobs",
+];
+
 #[async_trait]
 impl super::TxManager for DatabaseTransaction {
     /// Upsert a new stele into the database.
@@ -20,4 +37,96 @@ impl super::TxManager for DatabaseTransaction {
             .last_insert_id();
         Ok(id)
     }
+
+    /// Rename the stele `from` to `to`, moving the rows of every table from the former name to
+    /// the current name.
+    ///
+    /// Nothing is renamed if `from` does not exist, or if `to` already exists, as rows of both
+    /// names could not be merged without conflicts, e.g. publications of the same name.
+    ///
+    /// # Errors
+    /// Errors if the rows cannot be moved.
+    async fn rename(&mut self, from: &str, to: &str) -> anyhow::Result<Rename> {
+        let statement = "
+            SELECT name
+            FROM stele
+            WHERE name IN ( $1, $2 )
+        ";
+        let existing: Vec<(String,)> = sqlx::query_as(statement)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&mut *self.tx)
+            .await?;
+        if existing.iter().all(|found| found.0 != from) {
+            return Ok(Rename::Missing);
+        }
+        if existing.len() > 1 {
+            return Ok(Rename::Conflict);
+        }
+        self.create(to).await?;
+        for table in STELE_TABLES {
+            let update = format!("UPDATE {table} SET stele = $1 WHERE stele = $2");
+            sqlx::query(&update)
+                .bind(to)
+                .bind(from)
+                .execute(&mut *self.tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM stele WHERE name = $1")
+            .bind(from)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(Rename::Renamed)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::{
+        fixture, publication,
+        stele::{self, Rename},
+    };
+    use crate::db::Tx as _;
+
+    #[actix_web::test]
+    async fn test_rename_when_former_name_expect_rows_moved_once() {
+        let fixture = fixture::begin("city-of-x/law", &[("pb", "2020-01-01")], &[]).await;
+        let mut tx = fixture.tx;
+
+        let renamed = stele::TxManager::rename(&mut tx, "city-of-x/law", "x-city/law")
+            .await
+            .unwrap();
+        let renamed_again = stele::TxManager::rename(&mut tx, "city-of-x/law", "x-city/law")
+            .await
+            .unwrap();
+
+        assert_eq!(renamed, Rename::Renamed);
+        assert_eq!(renamed_again, Rename::Missing);
+        let found =
+            publication::TxManager::find_by_name_and_stele(&mut tx, "2020-01-01", "x-city/law")
+                .await
+                .unwrap();
+        assert_eq!(found.id, "pb");
+        tx.rollback().await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_rename_when_both_names_exist_expect_conflict_and_rows_left() {
+        let fixture = fixture::begin("city-of-x/law", &[("pa", "2020-01-01")], &[]).await;
+        let mut tx = fixture.tx;
+        fixture::publish(&mut tx, "x-city/law", &[("pb", "2020-01-01")]).await;
+
+        let actual = stele::TxManager::rename(&mut tx, "city-of-x/law", "x-city/law")
+            .await
+            .unwrap();
+
+        assert_eq!(actual, Rename::Conflict);
+        let former =
+            publication::TxManager::find_by_name_and_stele(&mut tx, "2020-01-01", "city-of-x/law")
+                .await
+                .unwrap();
+        assert_eq!(former.id, "pa");
+        tx.rollback().await.unwrap();
+    }
 }
@@ -3,11 +3,24 @@ use serde::{Deserialize, Serialize};
 
 pub mod manager;
 
+/// Outcome of renaming a stele.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rename {
+    /// Both names are in the database, so the rows of the former name were left in place.
+    Conflict,
+    /// The former name is not in the database, e.g. it was renamed before.
+    Missing,
+    /// The rows of the former name were moved to the current name.
+    Renamed,
+}
+
 /// Trait for managing transactional stele.
 #[async_trait]
 pub trait TxManager {
     /// Create a stele.
     async fn create(&mut self, stele: &str) -> anyhow::Result<Option<i64>>;
+    /// Rename the stele `from` to `to`, unless `to` already exists.
+    async fn rename(&mut self, from: &str, to: &str) -> anyhow::Result<Rename>;
 }
 
 #[derive(sqlx::FromRow, Deserialize, Serialize)]
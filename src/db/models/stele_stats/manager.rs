@@ -0,0 +1,50 @@
+//! Manager for the stele statistics.
+use async_trait::async_trait;
+
+use crate::db::{DatabaseConnection, DatabaseKind};
+
+use super::SteleStats;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find the statistics of the non-revoked publications of a stele.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_by_stele(&self, stele: &str) -> anyhow::Result<SteleStats> {
+        let statement = "
+            WITH versions AS (
+                SELECT pv.version
+                FROM publication_has_publication_versions phpv
+                INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                INNER JOIN publication p ON phpv.publication_id = p.id
+                WHERE p.stele = $1 AND p.revoked = 0
+            ),
+            auth_commits AS (
+                SELECT dc.auth_commit_timestamp
+                FROM data_repo_commits dc
+                INNER JOIN publication p ON dc.publication_id = p.id
+                WHERE p.stele = $1 AND p.revoked = 0
+            )
+            SELECT
+                (SELECT COUNT(DISTINCT de.doc_id) FROM document_element de WHERE de.stele = $1) AS documents,
+                (SELECT COUNT(*) FROM library l WHERE l.stele = $1) AS collections,
+                (SELECT COUNT(*) FROM publication p WHERE p.stele = $1 AND p.revoked = 0) AS publications,
+                (SELECT COUNT(DISTINCT v.version) FROM versions v) AS versions,
+                (SELECT MIN(v.version) FROM versions v) AS earliest_codified_date,
+                (SELECT MAX(v.version) FROM versions v) AS latest_codified_date,
+                (SELECT MIN(ac.auth_commit_timestamp) FROM auth_commits ac) AS first_auth_commit_at,
+                (SELECT MAX(ac.auth_commit_timestamp) FROM auth_commits ac) AS last_auth_commit_at
+        ";
+        let row = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, SteleStats>(statement)
+                    .bind(stele)
+                    .fetch_one(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(row)
+    }
+}
@@ -0,0 +1,50 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow, Row as _};
+
+pub mod manager;
+
+/// Trait for finding the statistics of a stele.
+#[async_trait]
+pub trait Manager {
+    /// Find the statistics of the non-revoked publications of a stele.
+    async fn find_by_stele(&self, stele: &str) -> anyhow::Result<SteleStats>;
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Statistics of a stele, aggregated from the `document_element`, `library`, `publication`,
+/// `publication_version` and `data_repo_commits` tables.
+pub struct SteleStats {
+    /// Number of documents.
+    pub documents: i64,
+    /// Number of collections.
+    pub collections: i64,
+    /// Number of non-revoked publications.
+    pub publications: i64,
+    /// Number of distinct codified dates of the non-revoked publications.
+    pub versions: i64,
+    /// Earliest codified date, if any.
+    pub earliest_codified_date: Option<String>,
+    /// Latest codified date, if any.
+    pub latest_codified_date: Option<String>,
+    /// Timestamp of the earliest ingested authentication commit, if any.
+    pub first_auth_commit_at: Option<String>,
+    /// Timestamp of the latest ingested authentication commit, if any.
+    pub last_auth_commit_at: Option<String>,
+}
+
+impl FromRow<'_, AnyRow> for SteleStats {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            documents: row.try_get("documents")?,
+            collections: row.try_get("collections")?,
+            publications: row.try_get("publications")?,
+            versions: row.try_get("versions")?,
+            earliest_codified_date: row.try_get("earliest_codified_date").ok(),
+            latest_codified_date: row.try_get("latest_codified_date").ok(),
+            first_auth_commit_at: row.try_get("first_auth_commit_at").ok(),
+            last_auth_commit_at: row.try_get("last_auth_commit_at").ok(),
+        })
+    }
+}
@@ -0,0 +1,50 @@
+//! Manager for the suggestion model.
+use async_trait::async_trait;
+
+use crate::db::{escape_like, DatabaseConnection, DatabaseKind};
+
+use super::Suggestion;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find documents and collections of a stele with a url segment starting with `prefix`.
+    ///
+    /// Matching is case-insensitive, and shorter urls are ranked first.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele_and_url_segment_prefix(
+        &self,
+        stele: &str,
+        prefix: &str,
+        limit: u32,
+    ) -> anyhow::Result<Vec<Suggestion>> {
+        let statement = r"
+            SELECT url, kind
+            FROM (
+                SELECT de.url AS url, 'document' AS kind
+                FROM document_element de
+                WHERE de.stele = $1 AND de.url LIKE $2 ESCAPE '\'
+                UNION
+                SELECT l.url AS url, 'collection' AS kind
+                FROM library l
+                WHERE l.stele = $1 AND l.url LIKE $2 ESCAPE '\'
+            )
+            ORDER BY length(url), url
+            LIMIT $3
+        ";
+        let pattern = format!("%/{}%", escape_like(prefix));
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Suggestion>(statement)
+                    .bind(stele)
+                    .bind(pattern)
+                    .bind(i64::from(limit))
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
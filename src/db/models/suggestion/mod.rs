@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for finding suggestions.
+#[async_trait]
+pub trait Manager {
+    /// Find documents and collections of a stele with a url segment starting with `prefix`.
+    async fn find_all_by_stele_and_url_segment_prefix(
+        &self,
+        stele: &str,
+        prefix: &str,
+        limit: u32,
+    ) -> anyhow::Result<Vec<Suggestion>>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// A document or collection suggested for a partially typed query.
+pub struct Suggestion {
+    /// Url of the document or collection.
+    pub url: String,
+    /// Kind of the suggested element, either `document` or `collection`.
+    pub kind: String,
+}
@@ -0,0 +1,92 @@
+//! Manager for the takedown model.
+use async_trait::async_trait;
+
+use crate::db::{DatabaseConnection, DatabaseKind, DatabaseTransaction};
+
+use super::Takedown;
+
+#[async_trait]
+impl super::Manager for DatabaseConnection {
+    /// Find all taken down documents.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all(&self) -> anyhow::Result<Vec<Takedown>> {
+        let statement = "
+            SELECT stele, path, reason
+            FROM takedowns
+            ORDER BY stele, path
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Takedown>(statement)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Find the taken down documents of the `stele`.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_all_by_stele(&self, stele: &str) -> anyhow::Result<Vec<Takedown>> {
+        let statement = "
+            SELECT stele, path, reason
+            FROM takedowns
+            WHERE stele = $1
+            ORDER BY path
+        ";
+        let rows = match self.kind {
+            DatabaseKind::Sqlite => {
+                let mut connection = self.pool.acquire().await?;
+                sqlx::query_as::<_, Takedown>(statement)
+                    .bind(stele)
+                    .fetch_all(&mut *connection)
+                    .await?
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Insert a new takedown into the database.
+    ///
+    /// # Errors
+    /// Errors if the takedown cannot be inserted, e.g. if the document is already taken down in
+    /// the stele.
+    async fn create(&mut self, takedown: &Takedown) -> anyhow::Result<()> {
+        let statement = "
+            INSERT INTO takedowns ( stele, path, reason )
+            VALUES ( $1, $2, $3 )
+        ";
+        sqlx::query(statement)
+            .bind(&takedown.stele)
+            .bind(&takedown.path)
+            .bind(&takedown.reason)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the takedown of a document of the `stele` from the database.
+    ///
+    /// # Errors
+    /// Errors if the takedown cannot be deleted.
+    async fn delete_by_stele_and_path(&mut self, stele: &str, path: &str) -> anyhow::Result<bool> {
+        let statement = "
+            DELETE FROM takedowns
+            WHERE stele = $1 AND path = $2
+        ";
+        let result = sqlx::query(statement)
+            .bind(stele)
+            .bind(path)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
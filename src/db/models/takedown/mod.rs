@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing takedowns.
+#[async_trait]
+pub trait Manager {
+    /// Find all taken down documents.
+    async fn find_all(&self) -> anyhow::Result<Vec<Takedown>>;
+    /// Find the taken down documents of a stele.
+    async fn find_all_by_stele(&self, stele: &str) -> anyhow::Result<Vec<Takedown>>;
+}
+
+/// Trait for managing transactional takedowns.
+#[async_trait]
+pub trait TxManager {
+    /// Insert a new takedown.
+    async fn create(&mut self, takedown: &Takedown) -> anyhow::Result<()>;
+    /// Delete the takedown of a document of a stele. Returns whether the document was taken down.
+    async fn delete_by_stele_and_path(&mut self, stele: &str, path: &str) -> anyhow::Result<bool>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for a document withheld from serving, e.g. by court order.
+pub struct Takedown {
+    /// Path of the document, as in its url, without leading or trailing `/`.
+    pub path: String,
+    /// Explanation shown to readers requesting the document.
+    pub reason: String,
+    /// Qualified name of the stele the document is taken down in.
+    pub stele: String,
+}
@@ -1,6 +1,11 @@
 //! Manager for the version model.
-use crate::db::DatabaseTransaction;
+use super::Version;
+use crate::db::{models::status::Status, DatabaseTransaction};
+use crate::server::cache::Mpath;
+use crate::utils::date;
 use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::cmp::Reverse;
 
 #[async_trait]
 impl super::TxManager for DatabaseTransaction {
@@ -8,16 +13,223 @@ impl super::TxManager for DatabaseTransaction {
     ///
     /// # Errors
     /// Errors if the version cannot be inserted into the database.
-    async fn create(&mut self, codified_date: &str) -> anyhow::Result<Option<i64>> {
+    async fn create(&mut self, codified_date: &NaiveDate) -> anyhow::Result<Option<i64>> {
         let statement = "
             INSERT OR IGNORE INTO version ( codified_date )
             VALUES ( $1 )
         ";
         let id = sqlx::query(statement)
-            .bind(codified_date)
+            .bind(date::format(*codified_date))
             .execute(&mut *self.tx)
             .await?
             .last_insert_id();
         Ok(id)
     }
+
+    /// Find the materialized path of the document, or else the collection, at `url`.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    async fn find_mpath_by_url(&mut self, url: &str, stele: &str) -> anyhow::Result<Option<Mpath>> {
+        let statement = "
+            SELECT kind, mpath FROM (
+                SELECT 0 AS precedence, 'document' AS kind, de.doc_mpath AS mpath
+                FROM document_element de
+                WHERE de.url = $1 AND de.stele = $2
+                UNION ALL
+                SELECT 1 AS precedence, 'collection' AS kind, l.mpath AS mpath
+                FROM library l
+                WHERE l.url = $1 AND l.stele = $2
+            )
+            ORDER BY precedence
+            LIMIT 1
+        ";
+        let row = sqlx::query_as::<_, (String, String)>(statement)
+            .bind(url)
+            .bind(stele)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+        Ok(row.map(|(kind, mpath)| {
+            if kind == "document" {
+                Mpath::Document(mpath)
+            } else {
+                Mpath::Collection(mpath)
+            }
+        }))
+    }
+
+    /// All dates on which the document or collection at `mpath` changed in a publication,
+    /// latest first, in a single query.
+    ///
+    /// The versions of a document are the dates it or any of its elements changed, and the date
+    /// the whole document became effective if that is after the element was added. The versions
+    /// of a collection are the dates any of its documents changed, and the date it was added.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database, or a date is malformed.
+    async fn find_all_by_mpath_and_publication(
+        &mut self,
+        mpath: Mpath,
+        publication_id: &str,
+    ) -> anyhow::Result<Vec<Version>> {
+        let is_document = matches!(mpath, Mpath::Document(_));
+        let rows = match mpath {
+            Mpath::Document(doc_mpath) => {
+                let statement = "
+                    SELECT 'changed' AS role, pv.version AS codified_date
+                    FROM document_change dc
+                    INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+                    INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                    WHERE dc.doc_mpath LIKE $1 AND phpv.publication_id = $2
+                    UNION
+                    SELECT 'added' AS role, codified_date FROM (
+                        SELECT pv.version AS codified_date
+                        FROM document_change dc
+                        INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+                        INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                        WHERE dc.doc_mpath = $3 AND phpv.publication_id = $2 AND dc.status = $4
+                        LIMIT 1
+                    )
+                    UNION
+                    SELECT 'effective' AS role, codified_date FROM (
+                        SELECT pv.version AS codified_date
+                        FROM document_change dc
+                        INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+                        INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                        WHERE dc.doc_mpath = $5 AND phpv.publication_id = $2 AND dc.status = $6
+                        LIMIT 1
+                    )
+                ";
+                let mut doc = doc_mpath.split('|').next().unwrap_or("").to_owned();
+                doc.push('|');
+                sqlx::query_as::<_, (String, String)>(statement)
+                    .bind(format!("{doc_mpath}%"))
+                    .bind(publication_id)
+                    .bind(&doc_mpath)
+                    .bind(Status::ElementAdded.to_int())
+                    .bind(doc)
+                    .bind(Status::ElementEffective.to_int())
+                    .fetch_all(&mut *self.tx)
+                    .await?
+            }
+            Mpath::Collection(lib_mpath) => {
+                let statement = "
+                    SELECT 'changed' AS role, pv.version AS codified_date
+                    FROM changed_library_document cld
+                    INNER JOIN document_change dc ON cld.document_change_id = dc.id
+                    INNER JOIN publication_has_publication_versions phpv ON dc.publication_version_id = phpv.publication_version_id
+                    INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                    WHERE cld.library_mpath LIKE $1 AND phpv.publication_id = $2
+                    UNION
+                    SELECT 'added' AS role, codified_date FROM (
+                        SELECT pv.version AS codified_date
+                        FROM library_change lc
+                        INNER JOIN publication_has_publication_versions phpv ON lc.publication_version_id = phpv.publication_version_id
+                        INNER JOIN publication_version pv ON phpv.publication_version_id = pv.id
+                        WHERE lc.library_mpath LIKE $1 AND phpv.publication_id = $2 AND lc.status = $3
+                        LIMIT 1
+                    )
+                ";
+                sqlx::query_as::<_, (String, String)>(statement)
+                    .bind(format!("{lib_mpath}%"))
+                    .bind(publication_id)
+                    .bind(Status::ElementAdded.to_int())
+                    .fetch_all(&mut *self.tx)
+                    .await?
+            }
+        };
+        let mut versions = Vec::new();
+        let mut added = None;
+        let mut effective = None;
+        for (role, codified_date) in rows {
+            let version = Version {
+                codified_date: date::parse(&codified_date)?,
+            };
+            match role.as_str() {
+                "added" => added = Some(version),
+                "effective" => effective = Some(version),
+                _ => versions.push(version),
+            }
+        }
+        let extra = if is_document {
+            // When element doesn't have date added, it means we're looking
+            // at an old publication and this element doesn't yet exist in it
+            effective.filter(|doc_effective| {
+                added.is_some_and(|el_added| doc_effective.codified_date > el_added.codified_date)
+            })
+        } else {
+            added
+        };
+        if let Some(found) = extra {
+            if !versions.contains(&found) {
+                versions.push(found);
+            }
+        }
+        versions.sort_by_key(|version| Reverse(version.codified_date));
+        Ok(versions)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::fixture::{self, Fixture, STELE};
+    use crate::db::models::version::{TxManager as _, Version};
+    use crate::server::cache::Mpath;
+
+    /// Statements inserting a document at `/doc` and a collection of it at `/lib`, changed in
+    /// versions of the publication `pb`, with the collection added before the document changed.
+    const FIXTURE: &[&str] = &[
+        "INSERT INTO version (codified_date) VALUES ('2019-12-01'), ('2020-01-01'), ('2020-03-01')",
+        "INSERT INTO publication_version (id, version, publication_id) VALUES ('v1', '2019-12-01', 'pb'), ('v2', '2020-01-01', 'pb'), ('v3', '2020-03-01', 'pb')",
+        "INSERT INTO publication_has_publication_versions (publication_id, publication_version_id) VALUES ('pb', 'v1'), ('pb', 'v2'), ('pb', 'v3')",
+        "INSERT INTO document (doc_id) VALUES ('doc')",
+        "INSERT INTO document_element (doc_mpath, url, doc_id, stele) VALUES ('doc|', '/doc', 'doc', 'org/law'), ('doc|sec|', '/both', 'doc', 'org/law')",
+        "INSERT INTO library (mpath, url, stele) VALUES ('lib|', '/lib', 'org/law'), ('both|', '/both', 'org/law')",
+        "INSERT INTO document_change (id, status, publication_version_id, doc_mpath) VALUES ('c1', 0, 'v2', 'doc|'), ('c2', 2, 'v3', 'doc|')",
+        "INSERT INTO library_change (publication_version_id, status, library_mpath) VALUES ('v1', 0, 'lib|')",
+        "INSERT INTO changed_library_document (library_mpath, document_change_id) VALUES ('lib|', 'c1'), ('lib|', 'c2')",
+    ];
+
+    async fn begin() -> Fixture {
+        fixture::begin(STELE, &[("pb", "2020-03-01")], FIXTURE).await
+    }
+
+    fn dates(versions: &[Version]) -> Vec<String> {
+        versions
+            .iter()
+            .map(|version| version.codified_date.to_string())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_find_mpath_by_url_when_document_and_collection_expect_document() {
+        let Fixture { mut tx, .. } = begin().await;
+        for (url, expected) in [
+            ("/both", Some(Mpath::Document("doc|sec|".to_owned()))),
+            ("/lib", Some(Mpath::Collection("lib|".to_owned()))),
+            ("/missing", None),
+        ] {
+            let actual = tx.find_mpath_by_url(url, STELE).await.unwrap();
+            assert_eq!(actual, expected, "{url}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_find_all_by_mpath_and_publication_expect_versions_latest_first() {
+        let Fixture { mut tx, .. } = begin().await;
+        let document = tx
+            .find_all_by_mpath_and_publication(Mpath::Document("doc|".to_owned()), "pb")
+            .await
+            .unwrap();
+        assert_eq!(dates(&document), vec!["2020-03-01", "2020-01-01"]);
+        let collection = tx
+            .find_all_by_mpath_and_publication(Mpath::Collection("lib|".to_owned()), "pb")
+            .await
+            .unwrap();
+        assert_eq!(
+            dates(&collection),
+            vec!["2020-03-01", "2020-01-01", "2019-12-01"]
+        );
+    }
 }
@@ -1,18 +1,41 @@
 use async_trait::async_trait;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, FromRow};
+
+use crate::server::cache::Mpath;
+use crate::utils::date;
 
 pub mod manager;
 /// Trait for managing versions.
 #[async_trait]
 pub trait TxManager {
     /// Create a new version.
-    async fn create(&mut self, codified_date: &str) -> anyhow::Result<Option<i64>>;
+    async fn create(&mut self, codified_date: &NaiveDate) -> anyhow::Result<Option<i64>>;
+    /// Find the materialized path of the document, or else the collection, at `url`.
+    async fn find_mpath_by_url(&mut self, url: &str, stele: &str) -> anyhow::Result<Option<Mpath>>;
+    /// All dates on which the document or collection at `mpath` changed in a publication,
+    /// latest first.
+    async fn find_all_by_mpath_and_publication(
+        &mut self,
+        mpath: Mpath,
+        publication_id: &str,
+    ) -> anyhow::Result<Vec<Version>>;
 }
 
-#[derive(sqlx::FromRow, Deserialize, Serialize, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
 /// Model for a version.
 pub struct Version {
     /// Significant codified date of any publication.
     /// Used in the form %YYYY-%MM-%DD.
-    pub codified_date: String,
+    #[serde(with = "date::ymd")]
+    pub codified_date: NaiveDate,
+}
+
+impl FromRow<'_, AnyRow> for Version {
+    fn from_row(row: &AnyRow) -> anyhow::Result<Self, sqlx::Error> {
+        Ok(Self {
+            codified_date: date::from_row(row, "codified_date")?,
+        })
+    }
 }
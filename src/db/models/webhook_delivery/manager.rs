@@ -0,0 +1,30 @@
+//! Manager for the webhook delivery model.
+use async_trait::async_trait;
+
+use crate::db::DatabaseTransaction;
+
+use super::WebhookDelivery;
+
+#[async_trait]
+impl super::TxManager for DatabaseTransaction {
+    /// Insert a new webhook delivery into the database.
+    ///
+    /// # Errors
+    /// Errors if the delivery cannot be inserted.
+    async fn create(&mut self, delivery: &WebhookDelivery) -> anyhow::Result<()> {
+        let statement = "
+            INSERT INTO webhook_deliveries ( stele, publication, url, attempts, status, error )
+            VALUES ( $1, $2, $3, $4, $5, $6 )
+        ";
+        sqlx::query(statement)
+            .bind(&delivery.stele)
+            .bind(&delivery.publication)
+            .bind(&delivery.url)
+            .bind(delivery.attempts)
+            .bind(delivery.status)
+            .bind(&delivery.error)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+}
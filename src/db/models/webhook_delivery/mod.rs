@@ -0,0 +1,28 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub mod manager;
+
+/// Trait for managing transactional webhook deliveries.
+#[async_trait]
+pub trait TxManager {
+    /// Insert a new webhook delivery.
+    async fn create(&mut self, delivery: &WebhookDelivery) -> anyhow::Result<()>;
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+/// Model for the delivery of an ingested publication to a webhook.
+pub struct WebhookDelivery {
+    /// Foreign key reference to the stele the publication belongs to.
+    pub stele: String,
+    /// Name of the delivered publication, e.g. `2023-10-22`.
+    pub publication: String,
+    /// Endpoint the publication was delivered to.
+    pub url: String,
+    /// Number of attempts made to deliver the publication.
+    pub attempts: i64,
+    /// HTTP status of the last response, if the endpoint responded.
+    pub status: Option<i64>,
+    /// Error of the last attempt, if the delivery failed.
+    pub error: Option<String>,
+}
@@ -0,0 +1,469 @@
+//! Back up and restore the database and the configuration of an archive.
+//!
+//! `stelae backup` writes a consistent snapshot of the database into a single `SQLite` file with
+//! `VACUUM INTO`, which does not block the running server. The snapshot also keeps the contents of
+//! `.taf/config.toml` in its `stelae_backup` table. `stelae restore` replaces the database of the
+//! archive with a snapshot and restores the configuration, then applies any newer migrations.
+//! Stop `stelae serve` before restoring. The databases of stelae isolated under `[database]` in
+//! `.taf/config.toml` are snapshotted the same way and kept in the `stelae_backup_databases` table,
+//! and restored to the locations configured for them.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::init::{self, database_url, sqlite_path};
+use crate::db::{DatabaseConnection, DatabaseKind, Databases, Db as _};
+use crate::server::errors::CliError;
+use crate::stelae::archive::{read_config, Archive};
+use anyhow::Context as _;
+use chrono::Utc;
+use sqlx::Row as _;
+use std::fs;
+use std::io;
+use std::path::{self, Path, PathBuf};
+
+/// Table of the snapshot holding the metadata of the backup.
+const METADATA_TABLE: &str = "stelae_backup";
+/// Table of the snapshot holding the databases of the isolated stelae.
+const DATABASES_TABLE: &str = "stelae_backup_databases";
+
+/// Metadata stored in a snapshot next to the database.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// Contents of `.taf/config.toml` at the time of the backup, if it existed.
+    pub config: Option<String>,
+    /// Time the backup was created at.
+    pub created_at: Option<String>,
+    /// Version of stelae that created the backup.
+    pub stelae_version: Option<String>,
+}
+
+/// Back up the database and the configuration of the archive to the file `out`.
+///
+/// # Errors
+/// Errors if the database cannot be reached, or the snapshot cannot be written.
+#[tracing::instrument(name = "Stelae backup", skip(raw_archive_path, archive_path))]
+pub async fn backup(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    out: &Path,
+) -> Result<(), CliError> {
+    let conn = match init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let result = async {
+        let archive = Archive::parse(
+            archive_path.clone(),
+            &PathBuf::from(raw_archive_path),
+            false,
+        )?;
+        let databases = init::connect_stelae(&archive_path, archive.stelae.keys(), conn).await?;
+        snapshot(&databases, &archive_path, out).await
+    };
+    match result.await {
+        Ok(()) => {
+            tracing::info!("Backed up the archive to {}", out.display());
+            Ok(())
+        }
+        Err(err) => {
+            tracing::error!("Failed to back up the archive");
+            tracing::error!("{err:?}");
+            Err(CliError::GenericError)
+        }
+    }
+}
+
+/// Restore the database, and unless `skip_config` the configuration, of the archive from the
+/// backup file `from`.
+///
+/// # Errors
+/// Errors if the file is not a backup, or the database cannot be replaced.
+#[tracing::instrument(name = "Stelae restore", skip(archive_path))]
+pub async fn restore(
+    archive_path: PathBuf,
+    from: &Path,
+    skip_config: bool,
+) -> Result<(), CliError> {
+    match load(&archive_path, from, skip_config).await {
+        Ok(metadata) => {
+            tracing::info!(
+                "Restored the archive from the backup created at {}",
+                metadata.created_at.as_deref().unwrap_or("an unknown time")
+            );
+            Ok(())
+        }
+        Err(err) => {
+            tracing::error!("Failed to restore the archive from {}", from.display());
+            tracing::error!("{err:?}");
+            Err(CliError::GenericError)
+        }
+    }
+}
+
+/// Write a snapshot of the `databases` and the configuration of the archive to `out`.
+///
+/// # Errors
+/// Errors if `out` already exists, or the snapshot cannot be written.
+pub async fn snapshot(
+    databases: &Databases,
+    archive_path: &Path,
+    out: &Path,
+) -> anyhow::Result<()> {
+    let target = path::absolute(out)?;
+    if target.exists() {
+        anyhow::bail!("{} already exists", target.display());
+    }
+    vacuum_into(databases.shared(), &target).await?;
+    let config = match fs::read_to_string(archive_path.join(".taf/config.toml")) {
+        Ok(config) => Some(config),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    let snapshot_conn = open(&target, "rw").await?;
+    let metadata = [
+        ("config", config),
+        ("created_at", Some(Utc::now().to_rfc3339())),
+        ("stelae_version", Some(env!("CARGO_PKG_VERSION").to_owned())),
+    ];
+    sqlx::query(&format!(
+        "CREATE TABLE {METADATA_TABLE} ( key TEXT PRIMARY KEY, value TEXT )"
+    ))
+    .execute(&snapshot_conn.pool)
+    .await?;
+    for (key, value) in metadata {
+        sqlx::query(&format!(
+            "INSERT INTO {METADATA_TABLE} ( key, value ) VALUES ( $1, $2 )"
+        ))
+        .bind(key)
+        .bind(value)
+        .execute(&snapshot_conn.pool)
+        .await?;
+    }
+    sqlx::query(&format!(
+        "CREATE TABLE {DATABASES_TABLE} ( stele TEXT PRIMARY KEY, content BLOB NOT NULL )"
+    ))
+    .execute(&snapshot_conn.pool)
+    .await?;
+    for (stele, conn) in databases.isolated() {
+        let stele_target =
+            PathBuf::from(format!("{}.{}", target.display(), stele.replace('/', "_")));
+        vacuum_into(conn, &stele_target).await?;
+        let content = fs::read(&stele_target);
+        fs::remove_file(&stele_target)?;
+        sqlx::query(&format!(
+            "INSERT INTO {DATABASES_TABLE} ( stele, content ) VALUES ( $1, $2 )"
+        ))
+        .bind(stele)
+        .bind(content?)
+        .execute(&snapshot_conn.pool)
+        .await?;
+    }
+    snapshot_conn.pool.close().await;
+    Ok(())
+}
+
+/// Write a consistent copy of the database `conn` to the new file `target`.
+///
+/// # Errors
+/// Errors if the copy cannot be written.
+async fn vacuum_into(conn: &DatabaseConnection, target: &Path) -> anyhow::Result<()> {
+    match conn.kind {
+        DatabaseKind::Sqlite => {
+            sqlx::query("VACUUM INTO $1")
+                .bind(target.to_string_lossy().into_owned())
+                .execute(&conn.pool)
+                .await
+                .context("could not write the database snapshot")?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the database of the archive with the snapshot `from`, and restore the configuration
+/// unless `skip_config`.
+///
+/// Returns the metadata of the snapshot.
+///
+/// # Errors
+/// Errors if the database is not `SQLite`, `from` is not a backup or was created by a newer
+/// stelae, or the database cannot be replaced.
+pub async fn load(archive_path: &Path, from: &Path, skip_config: bool) -> anyhow::Result<Metadata> {
+    let db_url = database_url(archive_path);
+    let Some(db_path) = sqlite_path(&db_url) else {
+        anyhow::bail!("Only SQLite databases can be restored, found {db_url}");
+    };
+    let source = path::absolute(from)?;
+    let snapshot_conn = open(&source, "ro").await?;
+    let metadata = read_metadata(&snapshot_conn)
+        .await
+        .with_context(|| format!("{} is not a stelae backup", source.display()))?;
+    let snapshot_version: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+            .fetch_one(&snapshot_conn.pool)
+            .await
+            .unwrap_or_default();
+    let stele_databases = read_databases(&snapshot_conn).await?;
+    snapshot_conn.pool.close().await;
+    let latest_version = sqlx::migrate!("./migrations/sqlite")
+        .iter()
+        .map(|migration| migration.version)
+        .max();
+    if snapshot_version > latest_version {
+        anyhow::bail!(
+            "The backup was created by stelae {}, which is newer than this stelae",
+            metadata.stelae_version.as_deref().unwrap_or("unknown")
+        );
+    }
+
+    let staging = PathBuf::from(format!("{}.restore", db_path.display()));
+    fs::copy(&source, &staging)
+        .with_context(|| format!("could not copy the backup to {}", staging.display()))?;
+    let staging_conn = open(&staging, "rw").await?;
+    for table in [METADATA_TABLE, DATABASES_TABLE] {
+        sqlx::query(&format!("DROP TABLE IF EXISTS {table}"))
+            .execute(&staging_conn.pool)
+            .await?;
+    }
+    staging_conn.pool.close().await;
+    replace_database(&staging, &db_path)?;
+
+    if let (false, Some(config)) = (skip_config, metadata.config.as_deref()) {
+        fs::write(archive_path.join(".taf/config.toml"), config)?;
+    }
+    let database = read_config(archive_path)
+        .ok()
+        .and_then(|config| config.database)
+        .unwrap_or_default();
+    for (stele, content) in stele_databases {
+        let Some(stele_path) = database
+            .url_for_stele(archive_path, &stele)
+            .as_deref()
+            .and_then(sqlite_path)
+        else {
+            tracing::warn!("Skipping the database of stele {stele}, it is no longer isolated");
+            continue;
+        };
+        if let Some(dir) = stele_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let stele_staging = PathBuf::from(format!("{}.restore", stele_path.display()));
+        fs::write(&stele_staging, content)?;
+        replace_database(&stele_staging, &stele_path)?;
+        if let Some(conn) = init::connect_stele(archive_path, &stele).await? {
+            conn.pool.close().await;
+        }
+    }
+    init::connect(archive_path).await?.pool.close().await;
+    Ok(metadata)
+}
+
+/// Replace the `SQLite` database file at `db_path` with the file at `staging`, dropping the
+/// write-ahead log of the replaced database.
+///
+/// # Errors
+/// Errors if the file cannot be replaced.
+fn replace_database(staging: &Path, db_path: &Path) -> anyhow::Result<()> {
+    fs::rename(staging, db_path)?;
+    for suffix in ["-wal", "-shm"] {
+        match fs::remove_file(format!("{}{suffix}", db_path.display())) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Read the databases of the isolated stelae from the snapshot `conn`, keyed by stele.
+///
+/// Snapshots created before isolated stelae were backed up have none.
+///
+/// # Errors
+/// Errors if the databases cannot be read.
+async fn read_databases(conn: &DatabaseConnection) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let exists: Option<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = $1")
+            .bind(DATABASES_TABLE)
+            .fetch_optional(&conn.pool)
+            .await?;
+    if exists.is_none() {
+        return Ok(vec![]);
+    }
+    let rows = sqlx::query(&format!("SELECT stele, content FROM {DATABASES_TABLE}"))
+        .fetch_all(&conn.pool)
+        .await?;
+    rows.into_iter()
+        .map(|row| Ok((row.try_get("stele")?, row.try_get("content")?)))
+        .collect()
+}
+
+/// Read the metadata of the snapshot `conn`.
+///
+/// # Errors
+/// Errors if the snapshot has no metadata table.
+async fn read_metadata(conn: &DatabaseConnection) -> anyhow::Result<Metadata> {
+    let rows = sqlx::query(&format!("SELECT key, value FROM {METADATA_TABLE}"))
+        .fetch_all(&conn.pool)
+        .await?;
+    let mut metadata = Metadata::default();
+    for row in rows {
+        let key: String = row.try_get("key")?;
+        let value: Option<String> = row.try_get("value").ok();
+        match key.as_str() {
+            "config" => metadata.config = value,
+            "created_at" => metadata.created_at = value,
+            "stelae_version" => metadata.stelae_version = value,
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+/// Open the `SQLite` database file at `path` in `mode`.
+///
+/// # Errors
+/// Errors if the database cannot be opened.
+async fn open(path: &Path, mode: &str) -> anyhow::Result<DatabaseConnection> {
+    DatabaseConnection::connect(&format!("sqlite://{}?mode={mode}", path.display()))
+        .await
+        .with_context(|| format!("could not open {}", path.display()))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::fixture;
+    use crate::db::{init, Databases};
+    use crate::history::backup::{load, snapshot};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[actix_web::test]
+    async fn test_load_when_snapshot_expect_database_and_config_restored() {
+        let (archive_dir, shared) = fixture::database().await;
+        let archive_path = archive_dir.path();
+        let config = "shallow = false\n[root]\norg = \"test_org\"\nname = \"law\"\n";
+        fs::write(archive_path.join(".taf/config.toml"), config).unwrap();
+        sqlx::query("INSERT INTO stele ( name ) VALUES ( 'test_org/law' )")
+            .execute(&shared.pool)
+            .await
+            .unwrap();
+        let databases = Databases::new(shared.clone(), HashMap::new());
+        let backup_file = archive_path.join("backup.sqlite3");
+        snapshot(&databases, archive_path, &backup_file)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM stele")
+            .execute(&shared.pool)
+            .await
+            .unwrap();
+        shared.pool.close().await;
+        fs::write(archive_path.join(".taf/config.toml"), "shallow = true\n").unwrap();
+
+        let cut = load;
+        let metadata = cut(archive_path, &backup_file, false).await.unwrap();
+
+        assert_eq!(metadata.config.as_deref(), Some(config));
+        assert_eq!(
+            metadata.stelae_version.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(
+            fs::read_to_string(archive_path.join(".taf/config.toml")).unwrap(),
+            config
+        );
+        let restored = init::connect(archive_path).await.unwrap();
+        let stelae: Vec<String> = sqlx::query_scalar("SELECT name FROM stele")
+            .fetch_all(&restored.pool)
+            .await
+            .unwrap();
+        assert_eq!(stelae, vec!["test_org/law".to_owned()]);
+        let leftover: Option<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'stelae_backup'",
+        )
+        .fetch_optional(&restored.pool)
+        .await
+        .unwrap();
+        assert_eq!(leftover, None);
+    }
+
+    #[actix_web::test]
+    async fn test_load_when_skip_config_expect_config_kept() {
+        let (archive_dir, shared) = fixture::database().await;
+        let archive_path = archive_dir.path();
+        fs::write(archive_path.join(".taf/config.toml"), "shallow = false\n").unwrap();
+        let databases = Databases::new(shared.clone(), HashMap::new());
+        let backup_file = archive_path.join("backup.sqlite3");
+        snapshot(&databases, archive_path, &backup_file)
+            .await
+            .unwrap();
+        shared.pool.close().await;
+        fs::write(archive_path.join(".taf/config.toml"), "shallow = true\n").unwrap();
+
+        let cut = load;
+        cut(archive_path, &backup_file, true).await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(archive_path.join(".taf/config.toml")).unwrap(),
+            "shallow = true\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_snapshot_when_isolated_stele_expect_database_restored() {
+        let (archive_dir, shared) = fixture::database().await;
+        let archive_path = archive_dir.path();
+        fs::write(
+            archive_path.join(".taf/config.toml"),
+            "shallow = false\n[root]\norg = \"test_org\"\nname = \"law\"\n[database]\nper_stele = true\n",
+        )
+        .unwrap();
+        let isolated = init::connect_stele(archive_path, "test_org/law")
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query("INSERT INTO stele ( name ) VALUES ( 'test_org/law' )")
+            .execute(&isolated.pool)
+            .await
+            .unwrap();
+        let databases = Databases::new(
+            shared.clone(),
+            HashMap::from([("test_org/law".to_owned(), isolated.clone())]),
+        );
+        let backup_file = archive_dir.path().join("backup.sqlite3");
+        snapshot(&databases, archive_path, &backup_file)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM stele")
+            .execute(&isolated.pool)
+            .await
+            .unwrap();
+        shared.pool.close().await;
+        isolated.pool.close().await;
+
+        load(archive_path, &backup_file, false).await.unwrap();
+
+        let restored = init::connect_stele(archive_path, "test_org/law")
+            .await
+            .unwrap()
+            .unwrap();
+        let stelae: Vec<String> = sqlx::query_scalar("SELECT name FROM stele")
+            .fetch_all(&restored.pool)
+            .await
+            .unwrap();
+        assert_eq!(stelae, vec!["test_org/law".to_owned()]);
+        let restored_shared = init::connect(archive_path).await.unwrap();
+        let shared_stelae: Vec<String> = sqlx::query_scalar("SELECT name FROM stele")
+            .fetch_all(&restored_shared.pool)
+            .await
+            .unwrap();
+        assert!(shared_stelae.is_empty());
+    }
+}
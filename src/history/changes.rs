@@ -7,11 +7,19 @@
     clippy::future_not_send,
     reason = "We don't worry about git2-rs not implementing `Send` trait"
 )]
+use super::digest::Collector;
+use super::export::find_commit_blobs;
+use super::links;
+use super::plugins::{Document, IngestPlugin, Registry};
 use super::rdf::graph::Bag;
+use super::webhooks::{load_secret, Notifier};
 use crate::db::models::changed_library_document::{self, ChangedLibraryDocument};
+use crate::db::models::current_blob::{self, CurrentBlob};
 use crate::db::models::data_repo_commits::{self, DataRepoCommits};
 use crate::db::models::document_change::{self, DocumentChange};
 use crate::db::models::document_element::DocumentElement;
+use crate::db::models::identifier::{self, Identifier};
+use crate::db::models::ingest_error::{self, IngestError};
 use crate::db::models::library::{self, Library};
 use crate::db::models::library_change::{self, LibraryChange};
 use crate::db::models::publication::{self, Publication};
@@ -21,41 +29,128 @@ use crate::db::models::publication_has_publication_versions::{
 use crate::db::models::publication_version;
 use crate::db::models::status::Status;
 use crate::db::models::{document, document_element};
-use crate::db::models::{stele, version};
+use crate::db::models::{
+    stele::{self, Rename},
+    version,
+};
 use crate::db::{DatabaseTransaction, Tx as _};
 use crate::history::rdf::graph::StelaeGraph;
 use crate::history::rdf::namespaces::{dcterms, oll};
+use crate::history::rdf::serialization::RdfFormat;
 use crate::server::errors::CliError;
 use crate::stelae::stele::Stele;
-use crate::stelae::types::repositories::Repository;
+use crate::stelae::types::repositories::{Repositories, Repository, RepositoryType, ServeType};
 use crate::utils::archive::get_name_parts;
+use crate::utils::date;
 use crate::utils::git::Repo;
 use crate::utils::md5;
+use crate::utils::output::{write_json, Output};
+use crate::utils::paths::normalize_path;
 use crate::{
     db::{self, DatabaseConnection},
-    stelae::archive::Archive,
+    stelae::archive::{Approval, Archive, Ingest},
 };
 use anyhow::Context as _;
 use chrono::DateTime;
 use git2::{TreeWalkMode, TreeWalkResult};
+use serde::Serialize;
 use sophia::api::ns::rdfs;
-use sophia::api::{prelude::*, term::SimpleTerm};
-use sophia::xml::parser;
+use sophia::api::term::SimpleTerm;
 use sqlx::types::chrono::NaiveDate;
 use std::{
     borrow::ToOwned,
-    io::{self, BufReader},
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
     result::Result,
 };
 
+/// Results of `stelae update`, written with `--output json`.
+#[derive(Debug, Default, Serialize)]
+pub struct Update {
+    /// Whether every stele was updated without errors.
+    pub ok: bool,
+    /// Error that stopped the update before the stelae were updated, if any.
+    pub error: Option<String>,
+    /// Results of every stele of the archive.
+    pub stelae: Vec<SteleUpdate>,
+}
+
+/// Result of updating a single stele.
+#[derive(Debug, Serialize)]
+pub struct SteleUpdate {
+    /// Qualified name of the stele.
+    pub stele: String,
+    /// Whether the stele was updated without errors.
+    pub ok: bool,
+    /// Errors of the update of the stele, and of the plugins run after it was committed.
+    pub errors: Vec<String>,
+}
+
+impl SteleUpdate {
+    /// Result of updating the stele `stele` with `errors`.
+    fn new(stele: String, errors: Vec<String>) -> Self {
+        Self {
+            stele,
+            ok: errors.is_empty(),
+            errors,
+        }
+    }
+}
+
 /// Inserts changes from the archive into the database
 ///
+/// Malformed RDF files are recorded in the `ingest_errors` table and skipped.
+/// If `strict` is set, a malformed RDF file fails the update of its stele instead.
+/// If `check_links` is set, internal links of the html documents of every publication
+/// are checked, and links that do not resolve are recorded in the `broken_links` table.
+/// The plugins configured in the `[ingest]` table of the archive config are run, see
+/// [`insert_with_plugins`]. With `output` JSON, the results of every stele are written to stdout,
+/// see [`Update`].
+///
+/// Runs on the runtime of the caller, and blocks its worker with the git calls that read the
+/// repositories. Servers run it on a thread of their own, as the updates scheduled by
+/// `stelae serve --update-schedule` are, see [`crate::server::scheduler`].
+///
 /// # Errors
 /// Errors if the changes cannot be inserted into the archive
-#[actix_web::main]
-#[tracing::instrument(name = "Stelae update", skip(raw_archive_path, archive_path))]
-pub async fn insert(raw_archive_path: &str, archive_path: PathBuf) -> Result<(), CliError> {
+pub async fn insert(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    strict: bool,
+    check_links: bool,
+    output: Output,
+) -> Result<(), CliError> {
+    insert_with_plugins(
+        raw_archive_path,
+        archive_path,
+        strict,
+        check_links,
+        &Registry::default(),
+        output,
+    )
+    .await
+}
+
+/// Inserts changes from the archive into the database, running the configured plugins of `registry`.
+///
+/// Deployments register their own plugins in `registry` to extend what is extracted from the
+/// archive, next to the built-in plugins.
+/// Runs on the runtime of the caller, as [`insert`] does.
+///
+/// # Errors
+/// Errors if the changes cannot be inserted into the archive. The archive cannot be parsed with
+/// [`CliError::ArchiveParseError`], its config is invalid, e.g. a configured plugin is not
+/// registered, with [`CliError::ConfigError`], and the database cannot be connected to with
+/// [`CliError::DatabaseConnectionError`].
+#[tracing::instrument(name = "Stelae update", skip(raw_archive_path, archive_path, registry))]
+pub async fn insert_with_plugins(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    strict: bool,
+    check_links: bool,
+    registry: &Registry,
+    output: Output,
+) -> Result<(), CliError> {
     let conn = match db::init::connect(&archive_path).await {
         Ok(conn) => conn,
         Err(err) => {
@@ -64,70 +159,385 @@ pub async fn insert(raw_archive_path: &str, archive_path: PathBuf) -> Result<(),
                 Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
             );
             tracing::error!("Error: {err:?}");
+            if output.is_json() {
+                let update = Update {
+                    error: Some(format!("could not connect to database: {err:#}")),
+                    ..Update::default()
+                };
+                write_json(&update).map_err(|_err| CliError::GenericError)?;
+            }
             return Err(CliError::DatabaseConnectionError);
         }
     };
-    insert_changes_archive(&conn, raw_archive_path, &archive_path)
-        .await
-        .map_err(|err| {
+    let mut failure = CliError::GenericError;
+    let update = match insert_changes_archive(
+        &conn,
+        raw_archive_path,
+        &archive_path,
+        strict,
+        check_links,
+        registry,
+    )
+    .await
+    {
+        Ok(stelae) => Update {
+            ok: stelae.iter().all(|updated| updated.ok),
+            error: None,
+            stelae,
+        },
+        Err(err) => {
             tracing::error!("Failed to update stele in the archive");
             tracing::error!("{err:?}");
-            CliError::GenericError
+            if let Some(classified) = err.downcast_ref::<CliError>() {
+                failure = classified.clone();
+            }
+            Update {
+                error: Some(format!("{err:#}")),
+                ..Update::default()
+            }
+        }
+    };
+    let errors: Vec<String> = update
+        .stelae
+        .iter()
+        .flat_map(|updated| {
+            updated
+                .errors
+                .iter()
+                .map(|err| format!("{}: {err}", updated.stele))
         })
+        .collect();
+    if !errors.is_empty() {
+        tracing::error!("Failed to update stele in the archive");
+        tracing::error!(
+            "Errors occurred while inserting changes:\n{}",
+            errors.join("\n")
+        );
+    }
+    if output.is_json() {
+        write_json(&update).map_err(|err| {
+            tracing::error!("Unable to write the results of the update: {err:?}");
+            CliError::GenericError
+        })?;
+    }
+    if update.ok {
+        Ok(())
+    } else if update.error.is_none() && update.stelae.iter().any(|updated| updated.ok) {
+        Err(CliError::PartialSuccess)
+    } else {
+        Err(failure)
+    }
 }
 
 /// Insert changes from the archive into the database
+///
+/// Returns the result of every stele. A stele that fails is rolled back, and the other stelae are
+/// still updated.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed, or the update of the stelae cannot be started. Errors
+/// of the archive, its config and the databases are classified with the [`CliError`] context.
 async fn insert_changes_archive(
     conn: &DatabaseConnection,
     raw_archive_path: &str,
     archive_path: &Path,
-) -> anyhow::Result<()> {
+    strict: bool,
+    check_links: bool,
+    registry: &Registry,
+) -> anyhow::Result<Vec<SteleUpdate>> {
     tracing::debug!("Inserting history into archive");
 
     let archive = Archive::parse(
         archive_path.to_path_buf(),
         &PathBuf::from(raw_archive_path),
         false,
-    )?;
+    )
+    .context(CliError::ArchiveParseError)?;
+    let config = archive.get_config().context(CliError::ConfigError)?;
+    let ingest = config.ingest.unwrap_or_default();
+    validate_plugins(registry, &ingest, &archive).context(CliError::ConfigError)?;
+    let webhooks = config.webhooks.unwrap_or_default();
+    let secret = load_secret(&webhooks).context(CliError::ConfigError)?;
+    let digest = config.digest.unwrap_or_default();
+    let approval = config.approval.unwrap_or_default();
+    let indexed = config.current_index.unwrap_or_default().is_enabled();
 
-    let mut errors = Vec::new();
+    let databases = db::init::connect_stelae(archive_path, archive.stelae.keys(), conn.clone())
+        .await
+        .context(CliError::DatabaseConnectionError)?;
+
+    let mut updated = Vec::new();
     for (name, mut stele) in archive.get_stelae() {
+        let stele_conn = databases.for_stele(&name);
+        let mut plugins = registry.create(&ingest.for_stele(&name))?;
+        if let Some(notifier) = Notifier::for_stele(&webhooks, secret.as_deref(), &name) {
+            plugins.push(Box::new(notifier));
+        }
+        if let Some(collector) = Collector::for_stele(&digest, &name) {
+            plugins.push(Box::new(collector));
+        }
         let mut tx = DatabaseTransaction {
-            tx: conn.pool.begin().await?,
+            tx: stele_conn.pool.begin().await?,
         };
-        match process_stele(&mut tx, &name, &mut stele, archive_path).await {
+        rename_former_names(&mut tx, &archive.aliases, &name).await?;
+        let processed = async {
+            process_stele(
+                &mut tx,
+                &name,
+                &mut stele,
+                archive_path,
+                strict,
+                check_links,
+                &mut plugins,
+            )
+            .await?;
+            finish_stele(&mut tx, &name, &mut stele, archive_path, &approval, indexed).await?;
+            run_before_commit(&mut tx, &name, &mut plugins).await
+        }
+        .await;
+        match processed {
             Ok(()) => {
                 tracing::debug!("Applying transaction for stele: {name}");
                 tx.commit().await?;
+                let errors = run_after_commit(stele_conn, &name, &mut plugins).await;
+                updated.push(SteleUpdate::new(name, errors));
             }
             Err(err) => {
                 tracing::error!("Rolling back transaction for stele: {name} due to error: {err:?}");
                 tx.rollback().await?;
-                errors.push(format!("{name}: {err}"));
+                updated.push(SteleUpdate::new(name, vec![err.to_string()]));
             }
         }
     }
-    if !errors.is_empty() {
-        let error_msg = errors.into_iter().collect::<Vec<_>>().join("\n");
-        return Err(anyhow::anyhow!(
-            "Errors occurred while inserting changes:\n{error_msg}"
-        ));
+    Ok(updated)
+}
+
+/// Check that every plugin configured for a stele of the `archive` in `ingest` is registered in
+/// `registry`, before any stele is updated.
+///
+/// # Errors
+/// Errors with the unknown plugins of the first stele configured with any.
+fn validate_plugins(registry: &Registry, ingest: &Ingest, archive: &Archive) -> anyhow::Result<()> {
+    let mut names: Vec<&String> = archive.stelae.keys().collect();
+    names.sort();
+    for name in names {
+        registry
+            .validate(&ingest.for_stele(name))
+            .with_context(|| format!("Invalid [ingest] plugins of stele {name}"))?;
+    }
+    Ok(())
+}
+
+/// Finish inserting the changes of the stele `name`: set the state of its new publications, see
+/// [`Approval`], and record the blobs its current documents are served from if `indexed`.
+///
+/// # Errors
+/// Errors if the publications cannot be updated, or the current blobs cannot be recorded.
+async fn finish_stele(
+    tx: &mut DatabaseTransaction,
+    name: &str,
+    stele: &mut Stele,
+    archive_path: &Path,
+    approval: &Approval,
+    indexed: bool,
+) -> anyhow::Result<()> {
+    let state = if approval.is_required(name) {
+        publication::INGESTED
+    } else {
+        publication::LIVE
+    };
+    publication::TxManager::update_by_stele_set_unset_state(tx, name, state).await?;
+    if indexed {
+        index_current_blobs(tx, name, stele, archive_path).await?;
+    }
+    Ok(())
+}
+
+/// Record the path and id of every blob of the `HEAD` commit of the served data repositories of
+/// the stele `name`, see [`crate::stelae::archive::CurrentIndex`].
+///
+/// # Errors
+/// Errors if a data repository cannot be read, or its blobs cannot be inserted.
+async fn index_current_blobs(
+    tx: &mut DatabaseTransaction,
+    name: &str,
+    stele: &mut Stele,
+    archive_path: &Path,
+) -> anyhow::Result<()> {
+    let Some(repositories) = stele.get_repositories()? else {
+        return Ok(());
+    };
+    for repository in repositories
+        .get_sorted()
+        .into_iter()
+        .filter(|repository| repository.is_served())
+    {
+        let data_repo = Repo::new(archive_path, &repository.get_org(), &repository.get_name())?;
+        let commit_hash = data_repo.head_commit_id()?;
+        let current_blobs = find_commit_blobs(&data_repo, &commit_hash)?
+            .into_iter()
+            .map(|(path, oid)| {
+                CurrentBlob::new(
+                    name.to_owned(),
+                    repository.name.clone(),
+                    commit_hash.clone(),
+                    path.to_string_lossy().into_owned(),
+                    oid.to_string(),
+                )
+            })
+            .collect();
+        current_blob::TxManager::replace_all_by_repository(tx, &repository.name, current_blobs)
+            .await?;
+        tracing::info!(
+            "[{name}] | Indexed the current blobs of {}",
+            repository.name
+        );
+    }
+    Ok(())
+}
+
+/// Rename the former names of the stele `name` in its database to `name`, see [`Archive::aliases`].
+///
+/// # Errors
+/// Errors if a stele cannot be renamed.
+async fn rename_former_names(
+    tx: &mut DatabaseTransaction,
+    aliases: &HashMap<String, String>,
+    name: &str,
+) -> anyhow::Result<()> {
+    for (former, _) in aliases.iter().filter(|&(_, current)| current == name) {
+        match stele::TxManager::rename(tx, former, name).await? {
+            Rename::Renamed => tracing::info!("Renamed stele {former} to {name}"),
+            Rename::Missing => {}
+            Rename::Conflict => tracing::warn!(
+                "Stele {former} was not renamed to {name}, as both are in the database. \
+                 The history of {former} stays under its former name; remove either stele from \
+                 the database and run `stelae update` again to rename it"
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Run the pre-commit hook of the `plugins` of the stele `name`, in its transaction.
+///
+/// # Errors
+/// Errors if a plugin fails.
+async fn run_before_commit(
+    tx: &mut DatabaseTransaction,
+    name: &str,
+    plugins: &mut [Box<dyn IngestPlugin>],
+) -> anyhow::Result<()> {
+    for plugin in plugins {
+        plugin.before_commit(tx, name).await?;
+    }
+    Ok(())
+}
+
+/// Run the post-commit hook of the `plugins` of the stele `name`.
+///
+/// Returns the errors of the plugins that failed.
+async fn run_after_commit(
+    conn: &DatabaseConnection,
+    name: &str,
+    plugins: &mut [Box<dyn IngestPlugin>],
+) -> Vec<String> {
+    let mut errors = vec![];
+    for plugin in plugins {
+        if let Err(err) = plugin.after_commit(conn, name).await {
+            tracing::error!("Ingest plugin failed after committing stele: {name}: {err:?}");
+            errors.push(err.to_string());
+        }
+    }
+    errors
+}
+
+/// Run the per-publication hook of the `plugins` for the inserted `publication`.
+///
+/// # Errors
+/// Errors if a plugin fails.
+async fn run_on_publication(
+    tx: &mut DatabaseTransaction,
+    name: &str,
+    publication: &Publication,
+    plugins: &mut [Box<dyn IngestPlugin>],
+) -> anyhow::Result<()> {
+    for plugin in plugins {
+        plugin.on_publication(tx, name, publication).await?;
     }
     Ok(())
 }
 
+/// Run the per-document hook of the `plugins` for every html document of the current commit
+/// of `html_repo`.
+///
+/// # Errors
+/// Errors if the html data repository cannot be read, or if a plugin fails.
+async fn run_on_documents(
+    tx: &mut DatabaseTransaction,
+    name: &str,
+    html_repo: &Repo,
+    plugins: &mut [Box<dyn IngestPlugin>],
+) -> anyhow::Result<()> {
+    if plugins.is_empty() {
+        return Ok(());
+    }
+    let commit_hash = html_repo.repo.head()?.peel_to_commit()?.id().to_string();
+    for (path, oid) in find_commit_blobs(html_repo, &commit_hash)? {
+        let is_html = path
+            .extension()
+            .is_some_and(|ext| ext == "html" || ext == "htm");
+        if !is_html {
+            continue;
+        }
+        let blob = html_repo.repo.find_blob(oid)?;
+        let document_path = path.to_string_lossy();
+        let document = Document {
+            repo: html_repo,
+            commit_hash: &commit_hash,
+            path: &document_path,
+            content: blob.content(),
+        };
+        for plugin in plugins.iter_mut() {
+            plugin.on_document(tx, name, document).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Types of historical data repositories whose commits are recorded in `data_repo_commits`.
+const RECORDED_REPO_TYPES: [RepositoryType; 3] = [
+    RepositoryType::Html,
+    RepositoryType::Pdf,
+    RepositoryType::Xml,
+];
+
+/// Warn about the custom fields of the data repositories of the stele `name` with deprecated values.
+fn warn_deprecations(name: &str, repositories: &Repositories) {
+    for deprecation in repositories.deprecations() {
+        tracing::warn!("targets/repositories.json of stele {name}: {deprecation}");
+    }
+}
+
 /// Process the stele and insert changes into the database
 async fn process_stele(
     tx: &mut DatabaseTransaction,
     name: &str,
     stele: &mut Stele,
     archive_path: &Path,
+    strict: bool,
+    check_links: bool,
+    plugins: &mut [Box<dyn IngestPlugin>],
 ) -> anyhow::Result<()> {
     let Some(repositories) = stele.get_repositories()? else {
         tracing::warn!("No repositories found for stele: {name}");
         return Ok(());
     };
-    let Some(rdf_repo) = repositories.get_one_by_custom_type("rdf") else {
+    repositories
+        .validate_injections()
+        .with_context(|| format!("Invalid targets/repositories.json of stele {name}"))?;
+    warn_deprecations(name, &repositories);
+    let Some(rdf_repo) = repositories.get_one_by_custom_type(&RepositoryType::Rdf) else {
         tracing::warn!("No RDF repository found for stele: {name}");
         return Ok(());
     };
@@ -140,28 +550,90 @@ async fn process_stele(
     }
     let (rdf_org, rdf_name) = get_name_parts(&rdf_repo.name)?;
     let rdf = Repo::new(archive_path, &rdf_org, &rdf_name)?;
-    insert_changes_from_rdf_repository(tx, rdf, name).await?;
+    insert_changes_from_rdf_repository(tx, rdf, name, strict, plugins).await?;
+    insert_identifiers(tx, name, stele).await?;
     // Insert commit hashes for data repositories with serve type 'historical'
-    let data_repos = repositories.get_all_by_serve_type("historical");
+    let data_repos = repositories.get_all_by_serve_type(&ServeType::Historical);
     for data_repo in data_repos {
-        // For now insert commit hashes only for repositories with repository type 'html'
-        if data_repo.custom.repository_type.as_deref() != Some("html") {
+        let Some(repo_type) = data_repo
+            .custom
+            .repository_type
+            .as_ref()
+            .filter(|repo_type| RECORDED_REPO_TYPES.contains(repo_type))
+        else {
             continue;
-        }
+        };
         insert_commit_hashes_from_auth_repository(tx, stele, data_repo).await?;
+        // Documents and links are only read from html repositories
+        if *repo_type != RepositoryType::Html {
+            continue;
+        }
+        let html_repo = Repo::new(archive_path, &data_repo.get_org(), &data_repo.get_name())?;
+        run_on_documents(tx, name, &html_repo, plugins).await?;
+        if check_links {
+            links::check_publications(tx, name, &html_repo).await?;
+        }
     }
     Ok(())
 }
 
+/// Replace the persistent identifiers of the stele with those of its `targets/identifiers.json` file.
+///
+/// Identifiers of documents with an invalid path are skipped. Identifiers that differ only by
+/// leading or trailing `/` are the same identifier; if they identify different documents, the
+/// conflict is reported and the first of them is kept.
+async fn insert_identifiers(
+    tx: &mut DatabaseTransaction,
+    stele_id: &str,
+    stele: &Stele,
+) -> anyhow::Result<()> {
+    let Some(mapping) = stele.get_identifiers()? else {
+        return Ok(());
+    };
+    let mut identifiers: BTreeMap<String, Identifier> = BTreeMap::new();
+    for (id, path) in mapping.identifiers {
+        let Ok(normalized) = normalize_path(&path) else {
+            tracing::warn!("[{stele_id}] | Skipping identifier {id} of invalid path {path}");
+            continue;
+        };
+        let trimmed = id.trim_matches('/').to_owned();
+        if let Some(kept) = identifiers.get(&trimmed) {
+            if kept.path != normalized {
+                tracing::warn!(
+                    "[{stele_id}] | Skipping identifier {id} of {normalized}, it conflicts with {trimmed} of {}",
+                    kept.path
+                );
+            }
+            continue;
+        }
+        identifiers.insert(
+            trimmed.clone(),
+            Identifier {
+                identifier: trimmed,
+                stele: stele_id.to_owned(),
+                path: normalized,
+            },
+        );
+    }
+    tracing::info!(
+        "[{stele_id}] | Inserting {} persistent identifier(s)",
+        identifiers.len()
+    );
+    identifier::TxManager::replace_all_by_stele(tx, stele_id, identifiers.into_values().collect())
+        .await
+}
+
 /// Insert changes from the RDF repository into the database
 async fn insert_changes_from_rdf_repository(
     tx: &mut DatabaseTransaction,
     rdf_repo: Repo,
     stele_id: &str,
+    strict: bool,
+    plugins: &mut [Box<dyn IngestPlugin>],
 ) -> anyhow::Result<()> {
     tracing::debug!("Inserting changes from RDF repository: {}", stele_id);
     tracing::debug!("RDF repository path: {}", rdf_repo.path.display());
-    load_delta_for_stele(tx, &rdf_repo, stele_id).await?;
+    load_delta_for_stele(tx, &rdf_repo, stele_id, strict, plugins).await?;
     Ok(())
 }
 
@@ -170,27 +642,37 @@ async fn load_delta_for_stele(
     tx: &mut DatabaseTransaction,
     rdf_repo: &Repo,
     stele: &str,
+    strict: bool,
+    plugins: &mut [Box<dyn IngestPlugin>],
 ) -> anyhow::Result<()> {
     stele::TxManager::create(tx, stele).await?;
     if let Some(publication) = publication::TxManager::find_last_inserted(tx, stele).await? {
         tracing::info!("[{stele}] | Inserting RDF changes from last inserted publication");
-        load_delta_from_publications(tx, rdf_repo, stele, Some(publication)).await?;
+        load_delta_from_publications(tx, rdf_repo, stele, Some(publication), strict, plugins)
+            .await?;
     } else {
         tracing::info!("[{stele}] | Inserting RDF changes from beginning...");
-        load_delta_from_publications(tx, rdf_repo, stele, None).await?;
+        load_delta_from_publications(tx, rdf_repo, stele, None, strict, plugins).await?;
     }
     Ok(())
 }
 
 /// Iterate and load delta from all publications in the `_publication` directory
 ///
+/// Malformed RDF files are collected per publication and inserted into the `ingest_errors` table.
+/// A publication whose index file is malformed is skipped entirely.
+/// The per-publication hook of the `plugins` is run for every inserted publication.
+///
 /// # Errors
-/// Errors if the delta cannot be loaded from the publications
+/// Errors if the delta cannot be loaded from the publications,
+/// or if `strict` is set and a publication contains malformed RDF files
 async fn load_delta_from_publications(
     tx: &mut DatabaseTransaction,
     rdf_repo: &Repo,
     stele: &str,
     last_inserted_publication: Option<Publication>,
+    strict: bool,
+    plugins: &mut [Box<dyn IngestPlugin>],
 ) -> anyhow::Result<()> {
     let head_commit = rdf_repo.repo.head()?.peel_to_commit()?;
     let tree = head_commit.tree()?;
@@ -209,24 +691,33 @@ async fn load_delta_from_publications(
                 NaiveDate::parse_from_str(&pv.version, "%Y-%m-%d").context("Could not parse date")
             })
             .and_then(Result::ok);
-        Some(NaiveDate::parse_from_str(
-            &last_inserted_pub.date,
-            "%Y-%m-%d",
-        )?)
+        Some(last_inserted_pub.date)
     } else {
         None
     };
     for publication_entry in &publications_subtree {
         let mut pub_graph = StelaeGraph::new();
+        let publication_dir = publication_entry.name().unwrap_or_default();
         let object = publication_entry.to_object(&rdf_repo.repo)?;
         let publication_tree = object
             .as_tree()
             .context("Expected a tree but got something else")?;
-        let index_rdf = publication_tree.get_path(&PathBuf::from("index.rdf"))?;
-        let blob = rdf_repo.repo.find_blob(index_rdf.id())?;
-        let data = blob.content();
-        let reader = io::BufReader::new(data);
-        parser::parse_bufread(reader).add_to_graph(&mut pub_graph.fast_graph)?;
+        let (index_entry, index_format) = find_publication_index(publication_tree)?;
+        let blob = rdf_repo.repo.find_blob(index_entry.id())?;
+        if let Err(ingest_error) = add_to_publication_graph(
+            &mut pub_graph,
+            blob.content(),
+            index_format,
+            stele,
+            publication_dir,
+            index_format.index_file_name(),
+        ) {
+            report_ingest_errors(tx, vec![ingest_error], strict).await?;
+            tracing::warn!(
+                "[{stele}] | Skipping publication {publication_dir} with malformed index file"
+            );
+            continue;
+        }
         let pub_label = pub_graph.literal_from_triple_matching(None, Some(rdfs::label), None)?;
         let pub_name = pub_label
             .strip_prefix("Publication ")
@@ -244,27 +735,14 @@ async fn load_delta_from_publications(
             }
         }
         tracing::info!("[{stele}] | Publication: {pub_name}");
-        publication_tree.walk(TreeWalkMode::PreOrder, |_, entry| {
-            let path_name = entry.name().unwrap_or_default();
-            if path_name.contains(".rdf") {
-                match rdf_repo.repo.find_blob(entry.id()) {
-                    Ok(current_blob) => {
-                        let current_content = current_blob.content();
-                        if let Err(err) = parser::parse_bufread(BufReader::new(current_content))
-                            .add_to_graph(&mut pub_graph.fast_graph)
-                        {
-                            tracing::error!(
-                                "Error adding content to graph for entry {path_name}: {err:?}"
-                            );
-                        }
-                    }
-                    Err(err) => {
-                        tracing::error!("Error finding blob for entry {path_name}: {err:?}");
-                    }
-                }
-            }
-            TreeWalkResult::Ok
-        })?;
+        let ingest_errors = load_publication_files(
+            rdf_repo,
+            publication_tree,
+            &mut pub_graph,
+            stele,
+            publication_dir,
+        )?;
+        report_ingest_errors(tx, ingest_errors, strict).await?;
         let (last_valid_pub_name, last_valid_codified_date) =
             referenced_publication_information(&pub_graph);
         let publication_hash = md5::compute(format!("{}{}", pub_name.clone(), stele));
@@ -285,15 +763,193 @@ async fn load_delta_from_publications(
             last_valid_codified_date,
         )
         .await?;
+        update_preview(tx, &pub_graph, &pub_name, stele).await?;
         let publication =
             publication::TxManager::find_by_name_and_stele(tx, &pub_name, stele).await?;
-        load_delta_for_publication(tx, publication, &pub_graph, last_inserted_date).await?;
+        load_delta_for_publication(tx, publication.clone(), &pub_graph, last_inserted_date).await?;
+        run_on_publication(tx, stele, &publication, plugins).await?;
         // reset last inserted date for next publication
         last_inserted_date = None;
     }
     Ok(())
 }
 
+/// Find the index file of a publication tree.
+///
+/// Looks for `index.rdf`, `index.ttl` and `index.nt`, in that order, and returns the first one found
+/// together with its serialization format.
+///
+/// # Errors
+/// Errors if the publication tree contains none of the supported index files.
+fn find_publication_index<'tree>(
+    publication_tree: &'tree git2::Tree,
+) -> anyhow::Result<(git2::TreeEntry<'tree>, RdfFormat)> {
+    RdfFormat::ALL
+        .into_iter()
+        .find_map(|format| {
+            publication_tree
+                .get_path(&PathBuf::from(format.index_file_name()))
+                .ok()
+                .map(|entry| (entry, format))
+        })
+        .context("Publication does not contain an index.rdf, index.ttl or index.nt file")
+}
+
+/// Name and date of the latest publication in the `_publication` directory of the RDF repository
+/// of the `stele`.
+///
+/// Publications whose index file is missing or malformed are skipped, like by the update.
+///
+/// # Errors
+/// Errors if the `HEAD` of the RDF repository cannot be read.
+pub fn find_latest_rdf_publication(
+    rdf_repo: &Repo,
+    stele: &str,
+) -> anyhow::Result<Option<(String, NaiveDate)>> {
+    let tree = rdf_repo.repo.head()?.peel_to_commit()?.tree()?;
+    let Ok(publications_dir_entry) = tree.get_path(Path::new("_publication")) else {
+        return Ok(None);
+    };
+    let publications_subtree = rdf_repo.repo.find_tree(publications_dir_entry.id())?;
+    let latest = publications_subtree
+        .iter()
+        .filter_map(|publication_entry| read_publication_index(rdf_repo, &publication_entry, stele))
+        .max_by(|current, next| (current.1, &current.0).cmp(&(next.1, &next.0)));
+    Ok(latest)
+}
+
+/// Name and date of the publication at `publication_entry`, read from its index file.
+fn read_publication_index(
+    rdf_repo: &Repo,
+    publication_entry: &git2::TreeEntry,
+    stele: &str,
+) -> Option<(String, NaiveDate)> {
+    let object = publication_entry.to_object(&rdf_repo.repo).ok()?;
+    let (index_entry, index_format) = find_publication_index(object.as_tree()?).ok()?;
+    let blob = rdf_repo.repo.find_blob(index_entry.id()).ok()?;
+    let mut pub_graph = StelaeGraph::new();
+    add_to_publication_graph(
+        &mut pub_graph,
+        blob.content(),
+        index_format,
+        stele,
+        publication_entry.name().unwrap_or_default(),
+        index_format.index_file_name(),
+    )
+    .ok()?;
+    let pub_label = pub_graph
+        .literal_from_triple_matching(None, Some(rdfs::label), None)
+        .ok()?;
+    let pub_date = pub_graph
+        .literal_from_triple_matching(None, Some(dcterms::available), None)
+        .ok()?;
+    Some((
+        pub_label.strip_prefix("Publication ")?.to_owned(),
+        NaiveDate::parse_from_str(&pub_date, "%Y-%m-%d").ok()?,
+    ))
+}
+
+/// Load all RDF files of a publication into the publication graph.
+///
+/// # Errors
+/// Errors if the publication tree cannot be walked.
+/// Malformed RDF files do not error, but are returned as `IngestError`s.
+fn load_publication_files(
+    rdf_repo: &Repo,
+    publication_tree: &git2::Tree,
+    pub_graph: &mut StelaeGraph,
+    stele: &str,
+    publication_dir: &str,
+) -> anyhow::Result<Vec<IngestError>> {
+    let mut ingest_errors: Vec<IngestError> = vec![];
+    publication_tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path_name = entry.name().unwrap_or_default();
+        if let Some(format) = RdfFormat::from_path(path_name) {
+            match rdf_repo.repo.find_blob(entry.id()) {
+                Ok(current_blob) => {
+                    if let Err(ingest_error) = add_to_publication_graph(
+                        pub_graph,
+                        current_blob.content(),
+                        format,
+                        stele,
+                        publication_dir,
+                        format!("{root}{path_name}"),
+                    ) {
+                        ingest_errors.push(ingest_error);
+                    }
+                }
+                Err(err) => {
+                    tracing::error!("Error finding blob for entry {path_name}: {err:?}");
+                }
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(ingest_errors)
+}
+
+/// Parse `data` into the publication graph.
+///
+/// # Errors
+/// Errors with an `IngestError` describing the parser error and the number of triples
+/// parsed from `data` before it, if `data` is malformed. None of the triples of a malformed
+/// file are added to the publication graph.
+fn add_to_publication_graph(
+    pub_graph: &mut StelaeGraph,
+    data: &[u8],
+    format: RdfFormat,
+    stele: &str,
+    publication_dir: &str,
+    file_path: String,
+) -> Result<(), IngestError> {
+    pub_graph.add_from_bytes(data, format).map_err(|err| {
+        let triples_loaded = StelaeGraph::count_parsed_triples(data, format);
+        IngestError::new(
+            stele.to_owned(),
+            publication_dir.to_owned(),
+            file_path,
+            Some(format!("{triples_loaded} triples loaded before the error")),
+            format!("{err:#}"),
+        )
+    })
+}
+
+/// Log and record the malformed RDF files of a publication.
+///
+/// # Errors
+/// Errors if `strict` is set and `ingest_errors` is not empty, or if the errors cannot be inserted
+async fn report_ingest_errors(
+    tx: &mut DatabaseTransaction,
+    ingest_errors: Vec<IngestError>,
+    strict: bool,
+) -> anyhow::Result<()> {
+    if ingest_errors.is_empty() {
+        return Ok(());
+    }
+    let report = ingest_errors
+        .iter()
+        .map(|ie| {
+            format!(
+                "{}/{}: {} ({})",
+                ie.publication,
+                ie.file_path,
+                ie.message,
+                ie.context.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if strict {
+        anyhow::bail!("Malformed RDF files in publication:\n{report}");
+    }
+    tracing::warn!(
+        "Recorded {} malformed RDF file(s) in `ingest_errors`:\n{report}",
+        ingest_errors.len()
+    );
+    ingest_error::TxManager::insert_bulk(tx, ingest_errors).await?;
+    Ok(())
+}
+
 /// Load all deltas for the publication given a stele
 ///
 /// # Errors
@@ -345,16 +1001,17 @@ async fn insert_document_changes(
     let mut document_elements_bulk: Vec<DocumentElement> = vec![];
     let mut document_changes_bulk: Vec<DocumentChange> = vec![];
     for version in pub_document_versions {
-        let codified_date =
-            pub_graph.literal_from_triple_matching(Some(version), Some(oll::codifiedDate), None)?;
-        if let Some(last_inserted_date) = last_inserted_date.as_ref() {
-            let codified_date = NaiveDate::parse_from_str(codified_date.as_str(), "%Y-%m-%d")?;
-            if &codified_date <= last_inserted_date {
-                // Date already inserted
-                continue;
-            }
+        let codified_date = date::parse(&pub_graph.literal_from_triple_matching(
+            Some(version),
+            Some(oll::codifiedDate),
+            None,
+        )?)?;
+        if last_inserted_date.is_some_and(|last_inserted| &codified_date <= last_inserted) {
+            // Date already inserted
+            continue;
         }
         version::TxManager::create(tx, &codified_date).await?;
+        let codified_date = date::format(codified_date);
         let pub_version_hash = md5::compute(format!(
             "{}{}{}",
             publication.name.clone(),
@@ -557,6 +1214,22 @@ fn referenced_publication_information(pub_graph: &StelaeGraph) -> (Option<String
     (last_valid_pub, last_valid_version)
 }
 
+/// Mark the publication as a preview if its graph flags it with `oll:preview "true"`, and as live otherwise.
+///
+/// # Errors
+/// Errors if the publication cannot be updated.
+async fn update_preview(
+    tx: &mut DatabaseTransaction,
+    pub_graph: &StelaeGraph,
+    pub_name: &str,
+    stele: &str,
+) -> anyhow::Result<()> {
+    let preview = pub_graph
+        .literal_from_triple_matching(None, Some(oll::preview), None)
+        .is_ok_and(|flag| flag == "true");
+    publication::TxManager::update_by_name_and_stele_set_preview(tx, pub_name, stele, preview).await
+}
+
 /// Revoke publications that have the same date as the current publication
 ///
 /// # Errors
@@ -568,8 +1241,8 @@ async fn revoke_same_date_publications(
     let duplicate_publications =
         publication::TxManager::find_all_by_date_and_stele_order_by_name_desc(
             tx,
-            publication.date,
-            publication.stele,
+            &publication.date,
+            &publication.stele,
         )
         .await?;
     if let Some(duplicate_publications_slice) = duplicate_publications.get(1..) {
@@ -600,7 +1273,12 @@ async fn insert_commit_hashes_from_auth_repository(
     let mut data_repo_commits_bulk: Vec<DataRepoCommits> = vec![];
 
     let loaded_auth_commits =
-        data_repo_commits::TxManager::find_all_auth_commits_for_stele(tx, &stele_name).await?;
+        data_repo_commits::TxManager::find_all_auth_commits_for_stele_and_repo_type(
+            tx,
+            &stele_name,
+            data_repo.get_type().unwrap_or_default().as_str(),
+        )
+        .await?;
 
     if loaded_auth_commits.is_empty() {
         tracing::info!("[{stele_name}] | Inserting commit hashes from the beginning...");
@@ -695,6 +1373,14 @@ async fn process_commit<'commit>(
         );
         return Ok(());
     };
+    let Ok(data_repo_commit_date) = date::parse(&data_repo_commit_date) else {
+        tracing::warn!(
+            "[{stele_name}] | Skipping commit {} with date '{data_repo_commit_date}' not in {} format",
+            &auth_commit_hash,
+            date::FORMAT
+        );
+        return Ok(());
+    };
     let auth_commit_timestamp = DateTime::from_timestamp(commit.time().seconds(), 0)
         .unwrap_or_default()
         .to_string();
@@ -720,3 +1406,195 @@ fn is_commit_in_loaded_auth_commits(
         .iter()
         .any(|ac| ac.auth_commit_hash == commit_hash)
 }
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::changes::add_to_publication_graph;
+    use crate::history::rdf::graph::StelaeGraph;
+    use crate::history::rdf::serialization::RdfFormat;
+    use sophia::api::graph::Graph as _;
+
+    #[test]
+    fn test_add_to_publication_graph_when_valid_turtle_expect_ok() {
+        let mut pub_graph = StelaeGraph::new();
+        let data = b"<http://example.org/a> <http://example.org/b> \"c\" .";
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::Turtle,
+            "test_org/law",
+            "2023-10-22",
+            "index.ttl".to_owned(),
+        );
+
+        assert_eq!(actual, Ok(()));
+    }
+
+    #[test]
+    fn test_add_to_publication_graph_when_malformed_turtle_expect_ingest_error() {
+        let mut pub_graph = StelaeGraph::new();
+        let data = b"<http://example.org/a> <http://example.org/b> \"c\" .\n<http://example.org/d> <http://example.org/e>";
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::Turtle,
+            "test_org/law",
+            "2023-10-22",
+            "a/b/index.ttl".to_owned(),
+        )
+        .unwrap_err();
+
+        assert_eq!(actual.stele, "test_org/law");
+        assert_eq!(actual.publication, "2023-10-22");
+        assert_eq!(actual.file_path, "a/b/index.ttl");
+        assert_eq!(
+            actual.context.as_deref(),
+            Some("1 triples loaded before the error")
+        );
+        assert!(!actual.message.is_empty());
+        assert_eq!(pub_graph.fast_graph.triples().count(), 0);
+    }
+
+    #[test]
+    fn test_add_to_publication_graph_when_valid_n_triples_expect_triples_added() {
+        let mut pub_graph = StelaeGraph::new();
+        let data = b"<http://example.org/a> <http://example.org/b> \"c\" .\n<http://example.org/a> <http://example.org/d> <http://example.org/e> .\n";
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::NTriples,
+            "test_org/law",
+            "2023-10-22",
+            "index.nt".to_owned(),
+        );
+
+        assert_eq!(actual, Ok(()));
+        assert_eq!(pub_graph.fast_graph.triples().count(), 2);
+    }
+
+    #[test]
+    fn test_add_to_publication_graph_when_malformed_n_triples_expect_graph_unchanged() {
+        let mut pub_graph = StelaeGraph::new();
+        let valid = b"<http://example.org/a> <http://example.org/b> \"c\" .\n";
+        pub_graph
+            .add_from_bytes(valid, RdfFormat::NTriples)
+            .unwrap();
+        let data = b"<http://example.org/d> <http://example.org/e> \"f\" .\n<http://example.org/g> \"h\" .\n";
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::NTriples,
+            "test_org/law",
+            "2023-10-22",
+            "a/index.nt".to_owned(),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            actual.context.as_deref(),
+            Some("1 triples loaded before the error")
+        );
+        assert_eq!(pub_graph.fast_graph.triples().count(), 1);
+    }
+
+    #[test]
+    fn test_add_to_publication_graph_when_valid_rdf_xml_expect_triples_added() {
+        let mut pub_graph = StelaeGraph::new();
+        let data = br#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:ex="http://example.org/">
+  <rdf:Description rdf:about="http://example.org/a">
+    <ex:b>c</ex:b>
+  </rdf:Description>
+</rdf:RDF>"#;
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::RdfXml,
+            "test_org/law",
+            "2023-10-22",
+            "index.rdf".to_owned(),
+        );
+
+        assert_eq!(actual, Ok(()));
+        assert_eq!(pub_graph.fast_graph.triples().count(), 1);
+    }
+
+    #[test]
+    fn test_add_to_publication_graph_when_malformed_rdf_xml_expect_graph_unchanged() {
+        let mut pub_graph = StelaeGraph::new();
+        let data = br#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:ex="http://example.org/">
+  <rdf:Description rdf:about="http://example.org/a">
+    <ex:b>c</ex:b>
+  </rdf:Description>
+  <rdf:Description rdf:about="http://example.org/d">
+    <ex:e>f</ex:g>
+</rdf:RDF>"#;
+
+        let cut = add_to_publication_graph;
+        let actual = cut(
+            &mut pub_graph,
+            data,
+            RdfFormat::RdfXml,
+            "test_org/law",
+            "2023-10-22",
+            "a/index.rdf".to_owned(),
+        );
+
+        assert!(actual.is_err());
+        assert_eq!(pub_graph.fast_graph.triples().count(), 0);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_insert_when_historical_pdf_and_xml_repositories_expect_their_commits_recorded() {
+        use crate::db::init;
+        use crate::history::changes::insert;
+        use crate::testing::generate;
+        use crate::utils::output::Output;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive_path = archive_dir.path();
+        let size = generate::Size {
+            documents: 1,
+            versions: 2,
+        };
+        generate::generate(archive_path, size).unwrap();
+        let raw_archive_path = archive_path.to_string_lossy();
+        let cut = || {
+            insert(
+                &raw_archive_path,
+                archive_path.to_path_buf(),
+                true,
+                false,
+                Output::Text,
+            )
+        };
+        cut().await.unwrap();
+        // A later update records no commit twice
+        cut().await.unwrap();
+
+        let db = init::connect(archive_path).await.unwrap();
+        let actual: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT repo_type, COUNT(*) FROM data_repo_commits GROUP BY repo_type ORDER BY repo_type",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        let expected = [("html", 2), ("pdf", 2), ("xml", 2)]
+            .map(|(repo_type, count)| (repo_type.to_owned(), count))
+            .to_vec();
+        assert_eq!(actual, expected);
+    }
+}
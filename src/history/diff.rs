@@ -0,0 +1,274 @@
+//! Compare two publications of a stele, for editorial review before a publication is announced.
+//!
+//! The documents are compared with the same queries as the `/_api/publications/{name}/delta`
+//! endpoint, and the collections are compared the same way.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{
+    self,
+    models::{document_change, document_delta::DocumentDelta, library_change},
+    DatabaseConnection,
+};
+use crate::server::api::publications::{count, find_compared, Counts, DeltaDocument};
+use crate::server::errors::CliError;
+use crate::stelae::archive::Archive;
+use anyhow::Context as _;
+use serde::Serialize;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// New, changed and removed documents and collections of a publication relative to an earlier one.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+    pub stele: String,
+    /// Name of the earlier publication, if the stele has one.
+    pub from: Option<String>,
+    /// Name of the compared publication.
+    pub to: String,
+    /// Number of documents for each change.
+    pub document_counts: Counts,
+    /// Number of collections for each change.
+    pub collection_counts: Counts,
+    /// New, changed and removed documents.
+    pub documents: Vec<DeltaDocument>,
+    /// New, changed and removed collections.
+    pub collections: Vec<DeltaDocument>,
+}
+
+/// Compare the publication `to` of the `stele` with the publication `from`, or with the
+/// publication preceding it if `from` is not given.
+///
+/// # Errors
+/// Errors if a publication is not found, if `from` is not earlier than `to`, or if the changes
+/// cannot be read from the database.
+pub async fn compare(
+    db: &DatabaseConnection,
+    stele: &str,
+    from: Option<&str>,
+    to: &str,
+) -> anyhow::Result<Comparison> {
+    let (publication, previous) = find_compared(db, stele, to, from)
+        .await?
+        .with_context(|| {
+            from.map_or_else(
+                || format!("Publication {to} not found in stele {stele}"),
+                |from_name| {
+                    format!("Publications {from_name} and {to} not found in stele {stele}, or {from_name} is not earlier than {to}")
+                },
+            )
+        })?;
+    let previous_id = previous.as_ref().map(|pb| pb.id.as_str());
+    let documents = document_change::Manager::find_all_document_deltas_by_publication(
+        db,
+        &publication.id,
+        previous_id,
+    )
+    .await?;
+    let collections = library_change::Manager::find_all_library_deltas_by_publication(
+        db,
+        &publication.id,
+        previous_id,
+    )
+    .await?;
+    let listed = |deltas: Vec<DocumentDelta>| deltas.into_iter().map(DeltaDocument::from).collect();
+    Ok(Comparison {
+        stele: stele.to_owned(),
+        from: previous.map(|pb| pb.name),
+        to: publication.name,
+        document_counts: count(&documents),
+        collection_counts: count(&collections),
+        documents: listed(documents),
+        collections: listed(collections),
+    })
+}
+
+/// Render the differences between the publications as a plain text report.
+#[must_use]
+pub fn render(diff: &Comparison) -> String {
+    let mut report = String::new();
+    let _infallible = write_diff(&mut report, diff);
+    report
+}
+
+/// Write the plain text report of the differences to `out`.
+fn write_diff(out: &mut String, diff: &Comparison) -> fmt::Result {
+    let from = diff.from.as_deref().unwrap_or("nothing");
+    writeln!(out, "{}: {} compared to {from}", diff.stele, diff.to)?;
+    let sections = [
+        ("documents", &diff.document_counts, &diff.documents),
+        ("collections", &diff.collection_counts, &diff.collections),
+    ];
+    for (name, counts, listed) in sections {
+        writeln!(
+            out,
+            "  {name}: {} new, {} changed, {} removed",
+            counts.new, counts.changed, counts.removed
+        )?;
+        for delta in listed {
+            let url = delta.url.as_deref().unwrap_or("-");
+            writeln!(out, "    {:<8} {url} ({})", delta.change, delta.mpath)?;
+        }
+    }
+    Ok(())
+}
+
+/// Report the differences between two publications of a stele, as plain text or JSON.
+///
+/// The report is written to `out_file`, or to stdout.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed, the database cannot be reached, or the publications
+/// cannot be compared.
+#[tracing::instrument(
+    name = "Stelae diff-publications",
+    skip(raw_archive_path, archive_path)
+)]
+pub async fn report(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    stele: Option<&str>,
+    from: Option<&str>,
+    to: &str,
+    json: bool,
+    out_file: Option<&Path>,
+) -> Result<(), CliError> {
+    let archive = Archive::parse(
+        archive_path.clone(),
+        &PathBuf::from(raw_archive_path),
+        false,
+    )
+    .map_err(|err| {
+        tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+        tracing::error!("Error: {err:?}");
+        CliError::ArchiveParseError
+    })?;
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let result = async {
+        let stele_name = match stele {
+            Some(name) => archive
+                .stelae
+                .get(name)
+                .with_context(|| format!("Stele {name} not found in the archive"))?
+                .get_qualified_name(),
+            None => archive.get_root()?.get_qualified_name(),
+        };
+        let db = db::init::connect_stelae(&archive.path, archive.stelae.keys(), conn).await?;
+        let diff = compare(db.for_stele(&stele_name), &stele_name, from, to).await?;
+        let output = if json {
+            serde_json::to_string_pretty(&diff)?
+        } else {
+            render(&diff)
+        };
+        match out_file {
+            Some(path) => fs::write(path, output)?,
+            None => writeln!(io::stdout().lock(), "{}", output.trim_end())?,
+        }
+        anyhow::Ok(())
+    };
+    result.await.map_err(|err| {
+        tracing::error!("Failed to compare publication {to}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::history::diff::{render, Comparison};
+    use crate::server::api::publications::{Counts, DeltaDocument};
+
+    #[test]
+    fn test_render_when_documents_and_collections_changed_expect_report() {
+        let cut = render;
+        let diff = Comparison {
+            stele: "org/law".to_owned(),
+            from: Some("2023-09-01".to_owned()),
+            to: "2023-10-22".to_owned(),
+            document_counts: Counts {
+                new: 1,
+                ..Counts::default()
+            },
+            collection_counts: Counts::default(),
+            documents: vec![DeltaDocument {
+                mpath: "|a|b|".to_owned(),
+                url: Some("/a/b/".to_owned()),
+                change: "new".to_owned(),
+            }],
+            collections: vec![],
+        };
+        let actual = cut(&diff);
+        let expected = "org/law: 2023-10-22 compared to 2023-09-01\n  \
+                        documents: 1 new, 0 changed, 0 removed\n    \
+                        new      /a/b/ (|a|b|)\n  \
+                        collections: 0 new, 0 changed, 0 removed\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_compare_when_from_given_or_defaulted_expect_compared_with_earlier_publication() {
+        use crate::db::init;
+        use crate::history::{changes, diff::compare};
+        use crate::testing::generate;
+        use crate::utils::output::Output;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive_path = archive_dir.path();
+        let size = generate::Size {
+            documents: 2,
+            versions: 2,
+        };
+        generate::generate(archive_path, size).unwrap();
+        changes::insert(
+            &archive_path.to_string_lossy(),
+            archive_path.to_path_buf(),
+            true,
+            false,
+            Output::Text,
+        )
+        .await
+        .unwrap();
+        let db = init::connect(archive_path).await.unwrap();
+        let cut = |from, to| compare(&db, "generated/law", from, to);
+
+        let first = cut(None, "2020-01-01").await.unwrap();
+        assert_eq!(first.from, None);
+        assert_eq!(first.document_counts.new, first.documents.len());
+        assert!(!first.documents.is_empty());
+
+        let defaulted = cut(None, "2020-01-31").await.unwrap();
+        let given = cut(Some("2020-01-01"), "2020-01-31").await.unwrap();
+        assert_eq!(defaulted.from.as_deref(), Some("2020-01-01"));
+        assert_eq!(given.from.as_deref(), Some("2020-01-01"));
+        assert_eq!(given.to, "2020-01-31");
+        let mpaths = |diff: &Comparison| -> Vec<(String, String)> {
+            diff.documents
+                .iter()
+                .map(|doc| (doc.mpath.clone(), doc.change.clone()))
+                .collect()
+        };
+        assert_eq!(mpaths(&given), mpaths(&defaulted));
+        assert!(given.documents.iter().all(|doc| doc.change != "removed"));
+
+        let later = cut(Some("2020-01-31"), "2020-01-01").await.unwrap_err();
+        assert!(later.to_string().contains("is not earlier than"), "{later}");
+        let missing = cut(None, "2021-01-01").await.unwrap_err();
+        assert!(missing.to_string().contains("not found"), "{missing}");
+    }
+}
@@ -0,0 +1,751 @@
+//! Send a digest of the documents changed by `stelae update`, per stele.
+//!
+//! The digest lists the title, path, net change and reason of every document changed by the
+//! publications ingested for a stele. It is mailed to the configured recipients through an SMTP
+//! relay, and posted as JSON to the configured webhook. Publications awaiting approval are left
+//! out of the digest of their update, and digested once they are live, see
+//! [`Collector::on_release`].
+//!
+//! The connection to the relay is upgraded with STARTTLS whenever the relay offers it. Credentials
+//! are only sent over an upgraded connection, so authenticating requires STARTTLS.
+use crate::db::models::document_change;
+use crate::db::models::publication::{self, Publication};
+use crate::db::{DatabaseConnection, DatabaseTransaction};
+use crate::history::plugins::{Document, IngestPlugin};
+use crate::server::api::formats::document_stem;
+use crate::stelae::archive::{Digest, DigestDestinations};
+use crate::utils::html::find_first_heading;
+use crate::utils::http;
+use actix_web::rt;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use chrono::Utc;
+use rustls::crypto::ring::default_provider;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Write as _};
+use std::io::{BufRead as _, BufReader, Read, Write};
+use std::mem;
+use std::net::{TcpStream, ToSocketAddrs as _};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Time to wait for connecting to, writing to and reading a reply of the SMTP relay.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest line of a header folded by [`encode_subject`], as recommended by RFC 5322.
+const HEADER_LINE_LENGTH: usize = 78;
+
+/// Most bytes of text in one RFC 2047 encoded word, so that the line of the first word, after
+/// `Subject: `, stays within [`HEADER_LINE_LENGTH`].
+const ENCODED_WORD_BYTES: usize = 42;
+
+/// Reasons of the changes of documents, keyed by publication name and document mpath.
+type Reasons = HashMap<(String, String), String>;
+
+/// Digest of the documents changed in a stele, as posted to the webhook.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    /// Qualified name of the stele.
+    pub stele: String,
+    /// Names of the ingested publications.
+    pub publications: Vec<String>,
+    /// Documents changed by the ingested publications.
+    pub documents: Vec<ChangedDocument>,
+}
+
+/// A document listed in the digest.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedDocument {
+    /// Name of the publication that changed the document.
+    pub publication: String,
+    /// Title of the document, i.e. its first heading, if it is still served.
+    pub title: Option<String>,
+    /// Url path of the document.
+    pub path: String,
+    /// Net change of the document, one of `new`, `changed` or `removed`.
+    pub status: String,
+    /// Reason of the latest change of the document, if recorded.
+    pub reason: Option<String>,
+}
+
+/// Ingest plugin collecting the documents changed in a stele into a digest.
+///
+/// Created by `stelae update` for every stele with configured digest destinations.
+#[derive(Debug)]
+pub struct Collector {
+    /// Destinations of the digest of the stele.
+    destinations: DigestDestinations,
+    /// SMTP relay the digest is mailed through, if configured.
+    relay: Option<Relay>,
+    /// Sender address of the mailed digest.
+    sender: String,
+    /// Names of the publications ingested so far.
+    publications: Vec<String>,
+    /// Titles of the current html documents, keyed by document path.
+    titles: HashMap<String, String>,
+}
+
+impl Collector {
+    /// Create a collector for the stele `stele_name`, or `None` if no digest is sent for it.
+    #[must_use]
+    pub fn for_stele(digest: &Digest, stele_name: &str) -> Option<Self> {
+        let destinations = digest.for_stele(stele_name);
+        let has_recipients = destinations
+            .recipients
+            .as_ref()
+            .is_some_and(|recipients| !recipients.is_empty());
+        (has_recipients || destinations.webhook.is_some()).then(|| Self {
+            destinations,
+            relay: digest.smtp.clone().map(|server| Relay {
+                password_env: digest.password_env.clone(),
+                require_tls: digest.require_tls.unwrap_or(false),
+                server,
+                username: digest.username.clone(),
+            }),
+            sender: digest.sender().to_owned(),
+            publications: Vec::new(),
+            titles: HashMap::new(),
+        })
+    }
+
+    /// Find the documents changed by the ingested publication `name`, with their `reasons`.
+    ///
+    /// Returns no documents if the publication was revoked by a later publication of the same update.
+    async fn changed_documents(
+        &self,
+        conn: &DatabaseConnection,
+        publications: &[Publication],
+        reasons: &Reasons,
+        name: &str,
+    ) -> anyhow::Result<Vec<ChangedDocument>> {
+        let mut remaining = publications.iter().skip_while(|pb| pb.name != name);
+        let Some(ingested) = remaining.next() else {
+            return Ok(vec![]);
+        };
+        let previous = remaining.next();
+        let deltas = document_change::Manager::find_all_document_deltas_by_publication(
+            conn,
+            &ingested.id,
+            previous.map(|pb| pb.id.as_str()),
+        )
+        .await?;
+        Ok(deltas
+            .into_iter()
+            .map(|delta| {
+                let path = delta.url.unwrap_or_else(|| delta.doc_mpath.clone());
+                ChangedDocument {
+                    publication: name.to_owned(),
+                    title: self
+                        .titles
+                        .get(document_stem(path.trim_matches('/')))
+                        .cloned(),
+                    reason: reasons
+                        .get(&(name.to_owned(), delta.doc_mpath.clone()))
+                        .cloned(),
+                    path,
+                    status: delta.change,
+                }
+            })
+            .collect())
+    }
+
+    /// Send the digest of the documents changed by the live publications of `names`.
+    ///
+    /// # Errors
+    /// Errors if the changes cannot be read from the database, or the digest cannot be sent.
+    async fn digest(
+        &self,
+        conn: &DatabaseConnection,
+        stele: &str,
+        names: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let publications =
+            publication::Manager::find_all_non_revoked_publications(conn, stele).await?;
+        let reasons: Reasons =
+            document_change::Manager::find_all_change_records_by_stele_and_date_range(
+                conn, stele, None, None,
+            )
+            .await?
+            .into_iter()
+            .filter(|record| record.kind == "document" && names.contains(&record.publication))
+            .filter_map(|record| Some(((record.publication, record.mpath), record.change_reason?)))
+            .collect();
+        let mut documents = vec![];
+        for name in &names {
+            documents.extend(
+                self.changed_documents(conn, &publications, &reasons, name)
+                    .await?,
+            );
+        }
+        if documents.is_empty() {
+            tracing::info!("[{stele}] | No changed documents, skipping digest");
+            return Ok(());
+        }
+        let changes = Changes {
+            stele: stele.to_owned(),
+            publications: names,
+            documents,
+        };
+        self.send(&changes).await?;
+        tracing::info!(
+            "[{stele}] | Sent digest of {} changed document(s)",
+            changes.documents.len()
+        );
+        Ok(())
+    }
+
+    /// Send the digest of the documents changed by the publication `name` of the stele, released
+    /// through `POST /_admin/publications/{name}/state`.
+    ///
+    /// Titles of the documents are only known while they are ingested, so they are left out.
+    ///
+    /// # Errors
+    /// Errors if the changes cannot be read from the database, or the digest cannot be sent.
+    pub async fn on_release(
+        &self,
+        conn: &DatabaseConnection,
+        stele: &str,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        self.digest(conn, stele, vec![name.to_owned()]).await
+    }
+    /// Mail and post the digest `changes` to the destinations of the stele.
+    async fn send(&self, changes: &Changes) -> anyhow::Result<()> {
+        let mut errors = vec![];
+        let recipients = self.destinations.recipients.clone().unwrap_or_default();
+        if !recipients.is_empty() {
+            let relay = self
+                .relay
+                .clone()
+                .context("Digest recipients are configured, but no `smtp` relay")?;
+            let mail = message(
+                &self.sender,
+                &recipients,
+                &subject(changes),
+                &render(changes),
+            );
+            let sender = self.sender.clone();
+            let mailed =
+                rt::task::spawn_blocking(move || send_mail(&relay, &sender, &recipients, &mail))
+                    .await?;
+            if let Err(err) = mailed {
+                errors.push(format!("Unable to mail digest: {err}"));
+            }
+        }
+        if let Some(webhook) = self.destinations.webhook.clone() {
+            let body = serde_json::to_string(changes)?;
+            let posted = rt::task::spawn_blocking(move || {
+                http::client()
+                    .post(&webhook)
+                    .set("Content-Type", "application/json")
+                    .send_string(&body)
+                    .map(|_response| ())
+                    .map_err(|err| err.to_string())
+            })
+            .await?;
+            if let Err(err) = posted {
+                errors.push(format!("Unable to post digest: {err}"));
+            }
+        }
+        if !errors.is_empty() {
+            anyhow::bail!(errors.join("; "));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+#[expect(
+    clippy::missing_trait_methods,
+    reason = "The digest is mailed once the changes are committed"
+)]
+impl IngestPlugin for Collector {
+    async fn on_publication(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        publication: &Publication,
+    ) -> anyhow::Result<()> {
+        self.publications.push(publication.name.clone());
+        Ok(())
+    }
+
+    async fn on_document(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        document: Document<'_>,
+    ) -> anyhow::Result<()> {
+        if let Some(title) = find_first_heading(document.content)? {
+            self.titles
+                .insert(document_stem(document.path).to_owned(), title);
+        }
+        Ok(())
+    }
+
+    async fn after_commit(&mut self, conn: &DatabaseConnection, stele: &str) -> anyhow::Result<()> {
+        let ingested = mem::take(&mut self.publications);
+        if ingested.is_empty() {
+            return Ok(());
+        }
+        self.digest(conn, stele, ingested).await
+    }
+}
+
+/// SMTP relay digests are mailed through.
+#[derive(Debug, Clone)]
+struct Relay {
+    /// Name of the environment variable holding the password of `username`.
+    password_env: Option<String>,
+    /// Whether to refuse to mail if the relay does not offer STARTTLS.
+    require_tls: bool,
+    /// Address of the relay, as `host:port`.
+    server: String,
+    /// User name to authenticate as, if any.
+    username: Option<String>,
+}
+
+impl Relay {
+    /// Read the user name and password to authenticate with, if a user name is configured.
+    fn credentials(&self) -> anyhow::Result<Option<(String, String)>> {
+        let Some(username) = self.username.clone() else {
+            return Ok(None);
+        };
+        let variable = self
+            .password_env
+            .as_deref()
+            .context("SMTP `username` is configured, but no `password_env`")?;
+        let password = env::var(variable)
+            .with_context(|| format!("Unable to read the SMTP password from `{variable}`"))?;
+        Ok(Some((username, password)))
+    }
+}
+
+/// Commands and replies exchanged with the SMTP relay over `S`.
+struct Session<S> {
+    /// Buffered connection to the relay.
+    reader: BufReader<S>,
+}
+
+impl<S: Read + Write> Session<S> {
+    /// Send the command `line`, and read its reply, see [`Session::reply`].
+    fn command(&mut self, line: &str, class: char) -> anyhow::Result<Vec<String>> {
+        let writer = self.reader.get_mut();
+        writer.write_all(format!("{line}\r\n").as_bytes())?;
+        writer.flush()?;
+        self.reply(class)
+    }
+
+    /// End the session, returning the connection to the relay.
+    fn into_inner(self) -> S {
+        self.reader.into_inner()
+    }
+
+    /// Start a session over the connection `stream`.
+    fn new(stream: S) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+        }
+    }
+
+    /// Read a reply, and check its code is of the class `class`, e.g. `'2'` for `250`.
+    ///
+    /// Returns the text of every line of the reply.
+    fn reply(&mut self, class: char) -> anyhow::Result<Vec<String>> {
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                anyhow::bail!("SMTP relay closed the connection");
+            }
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            if last && !line.starts_with(class) {
+                anyhow::bail!("SMTP relay replied {}", line.trim_end());
+            }
+            lines.push(line.get(4..).unwrap_or_default().trim_end().to_owned());
+            // Lines of multiline replies continue with a `-` after the code.
+            if last {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+/// Subject of the mailed digest `changes`.
+#[must_use]
+pub fn subject(changes: &Changes) -> String {
+    format!(
+        "[{}] {} document(s) changed in publication(s) {}",
+        changes.stele,
+        changes.documents.len(),
+        changes.publications.join(", ")
+    )
+}
+
+/// Render the digest `changes` as plain text, one document per line, grouped by publication.
+#[must_use]
+pub fn render(changes: &Changes) -> String {
+    let mut text = String::new();
+    // Writing to a `String` cannot fail.
+    let _infallible = write_changes(&mut text, changes);
+    text
+}
+
+/// Write the digest `changes` as plain text to `text`.
+fn write_changes(text: &mut String, changes: &Changes) -> fmt::Result {
+    writeln!(text, "Documents changed in {}", changes.stele)?;
+    for name in &changes.publications {
+        let mut documents = changes
+            .documents
+            .iter()
+            .filter(|document| &document.publication == name)
+            .peekable();
+        if documents.peek().is_none() {
+            continue;
+        }
+        write!(text, "\nPublication {name}\n")?;
+        for document in documents {
+            let title = document.title.as_deref().unwrap_or("Untitled");
+            writeln!(text, "- [{}] {title} ({})", document.status, document.path)?;
+            if let Some(reason) = document.reason.as_deref() {
+                writeln!(text, "  Reason: {reason}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encode the `subject` header of a mail, folded so that no line exceeds the line length limit.
+///
+/// A subject of printable ASCII is folded between words; any other subject is encoded as
+/// RFC 2047 base64 words of UTF-8, one per folded line.
+fn encode_subject(subject: &str) -> String {
+    let mut folded = String::from("Subject:");
+    let mut line_length = folded.len();
+    if subject
+        .chars()
+        .all(|ch| ch.is_ascii() && !ch.is_ascii_control())
+    {
+        for word in subject.split(' ') {
+            if line_length + 1 + word.len() > HEADER_LINE_LENGTH {
+                folded.push_str("\r\n");
+                line_length = 0;
+            }
+            folded.push(' ');
+            folded.push_str(word);
+            line_length += 1 + word.len();
+        }
+        return folded;
+    }
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for ch in subject.chars() {
+        if chunk.len() + ch.len_utf8() > ENCODED_WORD_BYTES {
+            words.push(mem::take(&mut chunk));
+        }
+        chunk.push(ch);
+    }
+    words.push(chunk);
+    let encoded: Vec<String> = words
+        .iter()
+        .map(|word| format!("=?utf-8?B?{}?=", STANDARD.encode(word)))
+        .collect();
+    folded.push(' ');
+    folded.push_str(&encoded.join("\r\n "));
+    folded
+}
+
+/// Compose a plain text mail of `body`, with an encoded subject, CRLF line endings and
+/// dot-stuffed lines.
+#[must_use]
+pub fn message(from: &str, recipients: &[String], subject: &str, body: &str) -> String {
+    let headers = [
+        format!("From: <{from}>"),
+        format!("To: {}", recipients.join(", ")),
+        encode_subject(subject),
+        format!("Date: {}", Utc::now().to_rfc2822()),
+        "MIME-Version: 1.0".to_owned(),
+        "Content-Type: text/plain; charset=utf-8".to_owned(),
+        "Content-Transfer-Encoding: 8bit".to_owned(),
+    ];
+    // Bare CRs are line breaks too, so that none is left in the CRLF-terminated lines.
+    let text = body.replace("\r\n", "\n").replace('\r', "\n");
+    let lines = text.lines().map(|line| {
+        if line.starts_with('.') {
+            format!(".{line}")
+        } else {
+            line.to_owned()
+        }
+    });
+    let mut mail = headers.join("\r\n");
+    mail.push_str("\r\n\r\n");
+    for line in lines {
+        mail.push_str(&line);
+        mail.push_str("\r\n");
+    }
+    mail
+}
+
+/// Deliver the composed `mail` to `recipients` through the SMTP relay `relay`.
+///
+/// # Errors
+/// Errors if the relay cannot be reached, does not offer STARTTLS when required, or rejects a command.
+fn send_mail(relay: &Relay, from: &str, recipients: &[String], mail: &str) -> anyhow::Result<()> {
+    let mut session = Session::new(connect(&relay.server)?);
+    session.reply('2')?;
+    let extensions = session.command("EHLO stelae", '2')?;
+    let offers_tls = extensions
+        .iter()
+        .any(|extension| extension.eq_ignore_ascii_case("STARTTLS"));
+    if !offers_tls {
+        if relay.require_tls || relay.username.is_some() {
+            anyhow::bail!("SMTP relay {} does not offer STARTTLS", relay.server);
+        }
+        return deliver(&mut session, from, recipients, mail);
+    }
+    session.command("STARTTLS", '2')?;
+    let mut secure = Session::new(upgrade(&relay.server, session.into_inner())?);
+    secure.command("EHLO stelae", '2')?;
+    if let Some((username, password)) = relay.credentials()? {
+        let token = STANDARD.encode(format!("\0{username}\0{password}"));
+        secure.command(&format!("AUTH PLAIN {token}"), '2')?;
+    }
+    deliver(&mut secure, from, recipients, mail)
+}
+
+/// Connect to the SMTP relay at `server`, giving up on every address after [`SMTP_TIMEOUT`].
+fn connect(server: &str) -> anyhow::Result<TcpStream> {
+    let addresses = server
+        .to_socket_addrs()
+        .with_context(|| format!("Unable to resolve SMTP relay {server}"))?;
+    let mut stream = None;
+    for address in addresses {
+        if let Ok(connected) = TcpStream::connect_timeout(&address, SMTP_TIMEOUT) {
+            stream = Some(connected);
+            break;
+        }
+    }
+    let connected = stream.with_context(|| format!("Unable to connect to SMTP relay {server}"))?;
+    connected.set_read_timeout(Some(SMTP_TIMEOUT))?;
+    connected.set_write_timeout(Some(SMTP_TIMEOUT))?;
+    Ok(connected)
+}
+
+/// Upgrade the connection `stream` to the SMTP relay at `server` to TLS.
+///
+/// The certificate of the relay is verified against the Mozilla root certificates.
+fn upgrade(
+    server: &str,
+    stream: TcpStream,
+) -> anyhow::Result<StreamOwned<ClientConnection, TcpStream>> {
+    let host = server
+        .rsplit_once(':')
+        .map_or(server, |(host, _port)| host)
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let name = ServerName::try_from(host.to_owned())
+        .with_context(|| format!("Invalid SMTP relay host {host}"))?;
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connection = ClientConnection::new(Arc::new(config), name)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+/// Send the composed `mail` from `from` to `recipients` in the established `session`.
+fn deliver<S: Read + Write>(
+    session: &mut Session<S>,
+    from: &str,
+    recipients: &[String],
+    mail: &str,
+) -> anyhow::Result<()> {
+    session.command(&format!("MAIL FROM:<{from}>"), '2')?;
+    for recipient in recipients {
+        session.command(&format!("RCPT TO:<{recipient}>"), '2')?;
+    }
+    session.command("DATA", '3')?;
+    session.command(&format!("{mail}."), '2')?;
+    session.command("QUIT", '2')?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::digest::{
+        message, render, send_mail, subject, ChangedDocument, Changes, Relay,
+    };
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine as _;
+    use std::io::{BufRead as _, BufReader, Write as _};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Serve one session of an SMTP relay answering with `replies`, the first being the greeting.
+    ///
+    /// Returns the address of the relay, and a handle joining to the received commands.
+    fn relay(replies: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _peer) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut commands = vec![];
+            let mut rest = replies.into_iter();
+            let mut last = rest.next().unwrap();
+            write!(writer, "{last}\r\n").unwrap();
+            for reply in rest {
+                let mut command = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 {
+                        return commands;
+                    }
+                    command.push_str(&line);
+                    // The data of the mail ends with a line of a single dot.
+                    if !last.starts_with("354") || line == ".\r\n" {
+                        break;
+                    }
+                }
+                commands.push(command.trim_end().to_owned());
+                write!(writer, "{reply}\r\n").unwrap();
+                last = reply;
+            }
+            commands
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn test_send_mail_when_relay_accepts_expect_mail_delivered() {
+        let cut = send_mail;
+        let (server, handle) = relay(vec![
+            "220 relay",
+            "250-relay\r\n250 8BITMIME",
+            "250 ok",
+            "250 ok",
+            "354 go",
+            "250 ok",
+            "221 bye",
+        ]);
+        let relay = Relay {
+            password_env: None,
+            require_tls: false,
+            server,
+            username: None,
+        };
+        let recipients = vec!["editors@example.com".to_owned()];
+        cut(
+            &relay,
+            "stelae@example.com",
+            &recipients,
+            "Subject: Digest\r\n\r\nbody\r\n",
+        )
+        .unwrap();
+        let expected = vec![
+            "EHLO stelae",
+            "MAIL FROM:<stelae@example.com>",
+            "RCPT TO:<editors@example.com>",
+            "DATA",
+            "Subject: Digest\r\n\r\nbody\r\n.",
+            "QUIT",
+        ];
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_send_mail_when_credentials_and_no_starttls_expect_error_before_mail() {
+        let cut = send_mail;
+        let (server, handle) = relay(vec!["220 relay", "250 relay"]);
+        let relay = Relay {
+            password_env: Some("STELAE_SMTP_PASSWORD".to_owned()),
+            require_tls: false,
+            server,
+            username: Some("stelae".to_owned()),
+        };
+        let actual = cut(&relay, "stelae@example.com", &[], "").unwrap_err();
+        assert!(actual.to_string().contains("does not offer STARTTLS"));
+        assert_eq!(handle.join().unwrap(), vec!["EHLO stelae"]);
+    }
+
+    #[test]
+    fn test_render_when_documents_changed_expect_grouped_by_publication() {
+        let cut = render;
+        let changes = Changes {
+            stele: "test_org/law".to_owned(),
+            publications: vec!["2023-10-22".to_owned(), "2023-11-01".to_owned()],
+            documents: vec![ChangedDocument {
+                publication: "2023-11-01".to_owned(),
+                title: Some("Section 1".to_owned()),
+                path: "/a/b/c/".to_owned(),
+                status: "changed".to_owned(),
+                reason: Some("Amended by Act 12".to_owned()),
+            }],
+        };
+        let expected = "Documents changed in test_org/law\n\nPublication 2023-11-01\n- [changed] Section 1 (/a/b/c/)\n  Reason: Amended by Act 12\n";
+        assert_eq!(cut(&changes), expected);
+    }
+
+    #[test]
+    fn test_message_when_line_starts_with_dot_expect_crlf_and_dot_stuffed() {
+        let cut = message;
+        let recipients = vec!["a@example.com".to_owned(), "b@example.com".to_owned()];
+        let actual = cut("stelae@example.com", &recipients, "Digest", "one\n.two\n");
+        assert!(actual.starts_with(
+            "From: <stelae@example.com>\r\nTo: a@example.com, b@example.com\r\nSubject: Digest\r\n"
+        ));
+        assert!(actual.ends_with("\r\n\r\none\r\n..two\r\n"));
+    }
+
+    #[test]
+    fn test_message_when_bare_carriage_return_expect_crlf() {
+        let cut = message;
+        let recipients = vec!["a@example.com".to_owned()];
+        let actual = cut("stelae@example.com", &recipients, "Digest", "one\rtwo\r\n");
+        assert!(actual.ends_with("\r\n\r\none\r\ntwo\r\n"));
+    }
+
+    #[test]
+    fn test_message_when_non_ascii_publication_expect_encoded_folded_subject() {
+        let cut = message;
+        let changes = Changes {
+            stele: "test_org/law".to_owned(),
+            publications: vec!["\u{c9}dition sp\u{e9}ciale \u{2014} 2023-11-01, r\u{e9}vis\u{e9}e et corrig\u{e9}e".to_owned()],
+            documents: vec![],
+        };
+        let recipients = vec!["a@example.com".to_owned()];
+        let actual = cut("stelae@example.com", &recipients, &subject(&changes), "");
+        let (headers, _) = actual.split_once("\r\n\r\n").unwrap();
+        let lines: Vec<&str> = headers.split("\r\n").collect();
+        assert!(lines.iter().all(|line| line.is_ascii() && line.len() <= 78));
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("Subject: "))
+            .unwrap();
+        let mut decoded = Vec::new();
+        for line in lines.iter().skip(start) {
+            let Some(word) = line
+                .trim_start_matches("Subject:")
+                .trim_start()
+                .strip_prefix("=?utf-8?B?")
+            else {
+                break;
+            };
+            decoded.extend(STANDARD.decode(word.strip_suffix("?=").unwrap()).unwrap());
+        }
+        assert_eq!(String::from_utf8(decoded).unwrap(), subject(&changes));
+    }
+}
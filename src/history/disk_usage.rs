@@ -0,0 +1,236 @@
+//! Record the sizes on disk of the repositories of an archive over time.
+//!
+//! Every round of `stelae disk-usage` records the size of every repository of every stele in
+//! the `repository_sizes` table. A warning is logged when a repository grew by more than the
+//! configured percentage since its previous recorded size, or when less than the configured
+//! number of bytes is free on the disk of the archive.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::models::repository_size::{self, RecordedSize};
+use crate::db::{self, DatabaseConnection, DatabaseTransaction, Tx as _};
+use crate::history::stats::{repository_sizes, RepositorySize};
+use crate::server::errors::CliError;
+use crate::stelae::archive::{Archive, DiskUsage};
+use crate::stelae::stele::Stele;
+use actix_web::rt::time;
+use anyhow::Context as _;
+use chrono::Utc;
+#[cfg(unix)]
+use nix::sys::statvfs::statvfs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Record the sizes of the repositories of the archive, and warn about crossed thresholds.
+///
+/// Keeps recording every `interval` seconds when an interval is given.
+/// Errors of a single round are logged, and the next round is attempted.
+///
+/// # Errors
+/// Errors if the round fails and no interval is given.
+pub async fn monitor(
+    raw_archive_path: &str,
+    archive_path: &Path,
+    interval: Option<u64>,
+) -> Result<(), CliError> {
+    loop {
+        let result = record_round(raw_archive_path, archive_path.to_path_buf()).await;
+        let Some(seconds) = interval else {
+            return result;
+        };
+        time::sleep(Duration::from_secs(seconds)).await;
+    }
+}
+
+/// Record the sizes of the repositories of the archive once.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed, the database cannot be reached or a size cannot be recorded.
+#[tracing::instrument(name = "Stelae disk usage", skip(raw_archive_path, archive_path))]
+async fn record_round(raw_archive_path: &str, archive_path: PathBuf) -> Result<(), CliError> {
+    let archive = Archive::parse(
+        archive_path.clone(),
+        &PathBuf::from(raw_archive_path),
+        false,
+    )
+    .map_err(|err| {
+        tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+        tracing::error!("Error: {err:?}");
+        CliError::ArchiveParseError
+    })?;
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let thresholds = archive
+        .get_config()
+        .ok()
+        .and_then(|config| config.disk_usage)
+        .unwrap_or_default();
+    match record(&archive, &conn, &thresholds).await {
+        Ok(warnings) => {
+            tracing::info!("Recorded disk usage with {warnings} warning(s)");
+            Ok(())
+        }
+        Err(err) => {
+            tracing::error!("Failed to record disk usage");
+            tracing::error!("{err:?}");
+            Err(CliError::GenericError)
+        }
+    }
+}
+
+/// Record the size of every repository of every stele in the `archive`.
+///
+/// Returns the number of warnings logged for crossed `thresholds`.
+///
+/// # Errors
+/// Errors if the sizes cannot be recorded in the database.
+pub async fn record(
+    archive: &Archive,
+    conn: &DatabaseConnection,
+    thresholds: &DiskUsage,
+) -> anyhow::Result<usize> {
+    let recorded_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut stelae: Vec<(&String, &Stele)> = archive.stelae.iter().collect();
+    stelae.sort_by_key(|&(stele_name, _)| stele_name);
+    let mut warnings: usize = 0;
+    for (stele_name, stele) in stelae {
+        let sizes = repository_sizes(&archive.path, stele);
+        let found = record_stele(conn, stele_name, sizes, thresholds, &recorded_at).await?;
+        warnings = warnings.saturating_add(found);
+    }
+    if let Some(min_free) = thresholds.min_free_bytes {
+        match free_bytes(&archive.path) {
+            Ok(free) if free < min_free => {
+                tracing::warn!(
+                    "Only {free} bytes are free on the disk of the archive, less than {min_free}"
+                );
+                warnings = warnings.saturating_add(1);
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Unable to find the free disk space: {err:?}"),
+        }
+    }
+    Ok(warnings)
+}
+
+/// Record the `sizes` of the repositories of the stele `stele_name` at `recorded_at`.
+///
+/// Returns the number of repositories that grew beyond the threshold of `thresholds`.
+///
+/// # Errors
+/// Errors if the sizes cannot be recorded in the database.
+async fn record_stele(
+    conn: &DatabaseConnection,
+    stele_name: &str,
+    sizes: Vec<RepositorySize>,
+    thresholds: &DiskUsage,
+    recorded_at: &str,
+) -> anyhow::Result<usize> {
+    let previous = repository_size::Manager::find_all_latest_by_stele(conn, stele_name).await?;
+    let mut tx = DatabaseTransaction {
+        tx: conn.pool.begin().await?,
+    };
+    let mut warnings: usize = 0;
+    for size in sizes {
+        let Some(bytes) = size.bytes else {
+            tracing::debug!("[{stele_name}] | Skipping {}, not cloned", size.repository);
+            continue;
+        };
+        let current = i64::try_from(bytes).unwrap_or(i64::MAX);
+        let before = previous
+            .iter()
+            .find(|recorded| recorded.repository == size.repository);
+        if let (Some(max_percent), Some(last)) = (thresholds.max_growth_percent, before) {
+            if grew_beyond(last.bytes, current, max_percent) {
+                tracing::warn!(
+                    "[{stele_name}] | {} grew from {} to {current} bytes since {}, more than {max_percent}%",
+                    size.repository,
+                    last.bytes,
+                    last.recorded_at
+                );
+                warnings = warnings.saturating_add(1);
+            }
+        }
+        repository_size::TxManager::create(
+            &mut tx,
+            &RecordedSize {
+                stele: stele_name.to_owned(),
+                repository: size.repository,
+                bytes: current,
+                recorded_at: recorded_at.to_owned(),
+            },
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(warnings)
+}
+
+/// Whether a repository grew from `before` to `current` bytes by more than `max_percent`.
+#[must_use]
+pub fn grew_beyond(before: i64, current: i64, max_percent: u64) -> bool {
+    let growth = current.saturating_sub(before);
+    let allowed = i64::try_from(max_percent).unwrap_or(i64::MAX);
+    growth > 0 && growth.saturating_mul(100) > before.saturating_mul(allowed)
+}
+
+/// Number of bytes free on the disk of `path`, as available to unprivileged users.
+///
+/// # Errors
+/// Errors if the file system of `path` cannot be queried.
+#[cfg(unix)]
+#[cfg_attr(
+    target_os = "linux",
+    expect(
+        clippy::useless_conversion,
+        reason = "Block counts and sizes are narrower than u64 on other unix platforms"
+    )
+)]
+pub fn free_bytes(path: &Path) -> anyhow::Result<u64> {
+    let stat = statvfs(path).with_context(|| format!("could not query {}", path.display()))?;
+    u64::from(stat.blocks_available())
+        .checked_mul(u64::from(stat.fragment_size()))
+        .context("free bytes overflow")
+}
+
+/// Number of bytes free on the disk of `path`.
+///
+/// # Errors
+/// Always errors, as the free space cannot be queried on this platform.
+#[cfg(not(unix))]
+pub fn free_bytes(path: &Path) -> anyhow::Result<u64> {
+    anyhow::bail!("could not query {}: unsupported platform", path.display())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::disk_usage::{free_bytes, grew_beyond};
+
+    #[test]
+    fn test_grew_beyond_when_growth_above_threshold_expect_true() {
+        let cut = grew_beyond;
+        assert!(cut(100, 121, 20));
+        assert!(!cut(100, 120, 20));
+        assert!(!cut(100, 50, 20));
+        assert!(cut(0, 1, 20));
+    }
+
+    #[test]
+    fn test_free_bytes_when_existing_path_expect_available_bytes() {
+        let cut = free_bytes;
+        let dir = tempfile::tempdir().unwrap();
+        assert!(cut(dir.path()).unwrap() > 0);
+        cut(&dir.path().join("missing")).unwrap_err();
+    }
+}
@@ -0,0 +1,627 @@
+//! Diagnose the environment of an archive with `stelae doctor`.
+//!
+//! Every check is reported as passed or failed, together with a hint to fix a failure, so an
+//! installation can be checked in one go before it serves or updates an archive. The checks cover
+//! the built-in git and XML support, the git binary `stelae git` serves clones with, the layout and configuration of the archive, the database
+//! and its schema, the permissions of the `.taf` dir, and the skew of the clock.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{self, DatabaseConnection, DatabaseKind, Db as _};
+use crate::history::rdf::graph::StelaeGraph;
+use crate::history::rdf::serialization::RdfFormat;
+use crate::server::errors::CliError;
+use crate::server::startup::{self, ProblemKind, Report};
+use crate::stelae::archive::read_config;
+use crate::utils::git::Repo;
+use crate::utils::http;
+use crate::utils::output::{write_json, Output};
+use actix_web::http::header::HttpDate;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use sqlx::Row as _;
+use std::fmt::{self, Write as _};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// Largest difference between the clock and a reference time that passes, in seconds.
+const CLOCK_SKEW_TOLERANCE: i64 = 300;
+
+/// File created in the `.taf` dir to check that it is writable.
+const PROBE_FILE: &str = ".taf/.stelae-doctor";
+
+/// A minimal RDF/XML document, parsed to check the XML support.
+const RDF_XML_PROBE: &[u8] = br#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:dc="http://purl.org/dc/terms/">
+  <rdf:Description rdf:about="https://example.org/doc"><dc:title>Doctor</dc:title></rdf:Description>
+</rdf:RDF>"#;
+
+/// Hint for git support missing from the build.
+const GIT_HINT: &str = "Build stelae with the default features of the `git2` crate, which bundle libgit2 with https support.";
+/// Hint for a git binary missing from the `PATH`.
+const GIT_BINARY_HINT: &str =
+    "Install git and add it to the `PATH`, `stelae git` runs `git upload-pack` to serve clones.";
+/// Hint for XML support missing from the build.
+const XML_HINT: &str = "Build stelae with the `xml` feature of the `sophia` crate.";
+/// Hint for a database schema newer than the migrations of this build.
+const SCHEMA_HINT: &str = "Upgrade stelae to the version that migrated the database, or restore a backup with `stelae restore`.";
+/// Hint for a `.taf` dir or database that cannot be written.
+const PERMISSIONS_HINT: &str =
+    "Make the `.taf` dir and the database file writable by the user running stelae.";
+/// Hint for a skewed clock.
+const CLOCK_HINT: &str =
+    "Synchronize the system clock, e.g. with NTP, so dates of versions and caches are correct.";
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Outcome {
+    /// The check passed.
+    Pass,
+    /// The check failed.
+    Fail,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Pass => write!(formatter, "PASS"),
+            Self::Fail => write!(formatter, "FAIL"),
+        }
+    }
+}
+
+/// A check of the environment of the archive.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    /// What is checked, e.g. `database schema`.
+    pub name: &'static str,
+    /// Whether the check passed.
+    pub outcome: Outcome,
+    /// What was found.
+    pub detail: String,
+    /// How a failure can be fixed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+impl Check {
+    /// A passed check of `name`.
+    const fn pass(name: &'static str, detail: String) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass,
+            detail,
+            hint: None,
+        }
+    }
+
+    /// A failed check of `name`, fixed as told by `hint`.
+    const fn fail(name: &'static str, detail: String, hint: &'static str) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail,
+            detail,
+            hint: Some(hint),
+        }
+    }
+}
+
+/// The checks of `stelae doctor`.
+#[derive(Debug, Serialize)]
+pub struct Diagnosis {
+    /// Whether every check passed.
+    pub ok: bool,
+    /// The checks, in the order they ran in.
+    pub checks: Vec<Check>,
+}
+
+impl Diagnosis {
+    /// Diagnose the `checks`.
+    #[must_use]
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self {
+            ok: checks.iter().all(|check| check.outcome == Outcome::Pass),
+            checks,
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                formatter,
+                "{}  {}: {}",
+                check.outcome, check.name, check.detail
+            )?;
+            if let Some(hint) = check.hint {
+                writeln!(formatter, "      {hint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run every check on the archive at `archive_path`, and report them to stdout as plain text or
+/// JSON.
+///
+/// The clock is compared with the `Date` header of `clock_url` if given, and with the latest
+/// commit of the authentication repository of the root stele otherwise.
+///
+/// # Errors
+/// Errors if any check failed, or the report cannot be written.
+#[tracing::instrument(name = "Stelae doctor", skip(raw_archive_path, archive_path))]
+pub async fn run(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    clock_url: Option<&str>,
+    output: Output,
+) -> Result<(), CliError> {
+    let mut checks = vec![check_git(), check_git_binary(), check_xml()];
+    checks.push(check_schema(&archive_path).await);
+    checks.extend(check_archive(raw_archive_path, archive_path.clone()).await);
+    checks.push(check_permissions(&archive_path));
+    checks.push(check_clock(&archive_path, clock_url));
+    let diagnosis = Diagnosis::new(checks);
+    let written = if output.is_json() {
+        write_json(&diagnosis)
+    } else {
+        write!(io::stdout().lock(), "{diagnosis}").map_err(anyhow::Error::from)
+    };
+    written.map_err(|err| {
+        tracing::error!("Unable to write the diagnosis: {err:?}");
+        CliError::GenericError
+    })?;
+    if diagnosis.ok {
+        Ok(())
+    } else {
+        Err(CliError::GenericError)
+    }
+}
+
+/// Check that git repositories are read with the built-in libgit2, without a git binary.
+fn check_git() -> Check {
+    let version = git2::Version::get();
+    let (major, minor, rev) = version.libgit2_version();
+    let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+    let detail = format!(
+        "libgit2 {major}.{minor}.{rev} built in, no git binary needed to read repositories (https: {}, ssh: {}, threads: {})",
+        yes_no(version.https()),
+        yes_no(version.ssh()),
+        yes_no(version.threads())
+    );
+    if version.https() && version.threads() {
+        Check::pass("git", detail)
+    } else {
+        Check::fail("git", detail, GIT_HINT)
+    }
+}
+
+/// Check that the git binary `stelae git` serves clones and fetches with is on the `PATH`.
+fn check_git_binary() -> Check {
+    let name = "git binary";
+    match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => Check::pass(
+            name,
+            String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+        ),
+        Ok(output) => Check::fail(
+            name,
+            format!(
+                "`git --version` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            GIT_BINARY_HINT,
+        ),
+        Err(err) => Check::fail(name, format!("could not run git: {err}"), GIT_BINARY_HINT),
+    }
+}
+
+/// Check that RDF/XML is parsed with the built-in parser, without libxml.
+fn check_xml() -> Check {
+    let mut graph = StelaeGraph::new();
+    match graph.add_from_bytes(RDF_XML_PROBE, RdfFormat::RdfXml) {
+        Ok(()) => Check::pass(
+            "xml",
+            "RDF/XML parsed with the built-in parser, no libxml needed".to_owned(),
+        ),
+        Err(err) => Check::fail("xml", format!("could not parse RDF/XML: {err:#}"), XML_HINT),
+    }
+}
+
+/// Check that the schema of the database of the archive is not newer than this build.
+///
+/// The database is only read, the migrations are applied once it is connected to.
+async fn check_schema(archive_path: &Path) -> Check {
+    let name = "database schema";
+    let url = db::init::database_url(archive_path);
+    let (latest, applied) = match migrations(&url).await {
+        Ok(migrations) => migrations,
+        Err(err) => {
+            return Check::fail(
+                name,
+                format!("could not read the schema of {url}: {err:#}"),
+                startup::DATABASE_HINT,
+            )
+        }
+    };
+    match applied {
+        Some(version) if version > latest => Check::fail(
+            name,
+            format!("version {version} is newer than version {latest} of this stelae"),
+            SCHEMA_HINT,
+        ),
+        Some(version) if version == latest => {
+            Check::pass(name, format!("version {version} is up to date"))
+        }
+        Some(version) => Check::pass(
+            name,
+            format!("version {version}, migrated to version {latest} once connected to"),
+        ),
+        None => Check::pass(
+            name,
+            format!("no migrations applied yet, migrated to version {latest} once connected to"),
+        ),
+    }
+}
+
+/// Latest migration of this build for the database at `url`, and the latest migration applied to
+/// it, if any.
+///
+/// # Errors
+/// Errors if the database cannot be connected to, or a migration failed to apply.
+async fn migrations(url: &str) -> anyhow::Result<(i64, Option<i64>)> {
+    let conn = DatabaseConnection::connect(url).await?;
+    let latest = db::init::migrator(&conn.kind)
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default();
+    let tables_query = match conn.kind {
+        DatabaseKind::Sqlite => {
+            "SELECT COUNT(*) AS found FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'"
+        }
+    };
+    let tables: i64 = sqlx::query(tables_query)
+        .fetch_one(&conn.pool)
+        .await?
+        .try_get("found")?;
+    if tables == 0 {
+        return Ok((latest, None));
+    }
+    let failed: i64 =
+        sqlx::query("SELECT COUNT(*) AS failed FROM _sqlx_migrations WHERE success = 0")
+            .fetch_one(&conn.pool)
+            .await?
+            .try_get("failed")?;
+    if failed > 0 {
+        anyhow::bail!("{failed} migration(s) failed to apply");
+    }
+    // the version is read as text, as `sqlx::Any` truncates the integers of `SQLite` to 32 bits.
+    let applied: Option<String> = sqlx::query(
+        "SELECT CAST(version AS TEXT) AS version FROM _sqlx_migrations ORDER BY version DESC LIMIT 1",
+    )
+    .fetch_optional(&conn.pool)
+    .await?
+    .map(|row| row.try_get("version"))
+    .transpose()?;
+    Ok((latest, applied.map(|version| version.parse()).transpose()?))
+}
+
+/// Check the configuration, the layout and the databases of the archive like `stelae serve`
+/// does, see [`startup::validate`].
+async fn check_archive(raw_archive_path: &str, archive_path: PathBuf) -> Vec<Check> {
+    let report = match startup::validate(raw_archive_path, archive_path, false).await {
+        Ok(validated) => {
+            return vec![
+                Check::pass("configuration", "`.taf/config.toml` is valid".to_owned()),
+                Check::pass(
+                    "archive layout",
+                    format!("{} stele(s) can be served", validated.archive.stelae.len()),
+                ),
+                Check::pass(
+                    "database connection",
+                    "the databases can be connected to".to_owned(),
+                ),
+            ];
+        }
+        Err(report) => report,
+    };
+    [
+        ("configuration", ProblemKind::Config),
+        ("archive layout", ProblemKind::Archive),
+        ("database connection", ProblemKind::Database),
+    ]
+    .into_iter()
+    .map(|(name, kind)| problems_check(name, kind, &report))
+    .collect()
+}
+
+/// Check `name`, failed by the problems of `kind` in the `report`.
+fn problems_check(name: &'static str, kind: ProblemKind, report: &Report) -> Check {
+    let mut problems = report
+        .problems
+        .iter()
+        .filter(|problem| problem.kind == kind);
+    let Some(first) = problems.next() else {
+        return Check::pass(name, "no problems found".to_owned());
+    };
+    let mut detail = format!("{}: {}", first.subject, first.error);
+    for problem in problems {
+        let _infallible = write!(detail, "; {}: {}", problem.subject, problem.error);
+    }
+    Check::fail(name, detail, first.hint)
+}
+
+/// Check that the `.taf` dir and the `SQLite` database file of the archive are writable.
+fn check_permissions(archive_path: &Path) -> Check {
+    let name = "permissions";
+    let probe = archive_path.join(PROBE_FILE);
+    if let Err(err) = fs::write(&probe, b"").and_then(|()| fs::remove_file(&probe)) {
+        return Check::fail(
+            name,
+            format!("could not write to {}: {err}", probe.display()),
+            PERMISSIONS_HINT,
+        );
+    }
+    let url = db::init::database_url(archive_path);
+    let database = db::init::sqlite_path(&url).filter(|path| path.exists());
+    if let Some(path) = database.as_deref() {
+        if let Err(err) = OpenOptions::new().write(true).open(path) {
+            return Check::fail(
+                name,
+                format!("could not open {} for writing: {err}", path.display()),
+                PERMISSIONS_HINT,
+            );
+        }
+    }
+    Check::pass(
+        name,
+        "the `.taf` dir and the database are writable".to_owned(),
+    )
+}
+
+/// Check that the clock is within [`CLOCK_SKEW_TOLERANCE`] of the `Date` of `clock_url`, or not
+/// behind the latest commit of the root stele if no url is given.
+fn check_clock(archive_path: &Path, clock_url: Option<&str>) -> Check {
+    let name = "clock";
+    let now = Utc::now();
+    let reference = clock_url.map_or_else(
+        || {
+            latest_commit_time(archive_path)
+                .map(|time| (time, "the latest commit of the root stele".to_owned()))
+        },
+        |url| server_time(url).map(|time| (time, format!("the `Date` of {url}"))),
+    );
+    let (time, source) = match reference {
+        Ok(found) => found,
+        Err(err) => {
+            return Check::fail(
+                name,
+                format!("could not read a reference time: {err:#}"),
+                CLOCK_HINT,
+            )
+        }
+    };
+    let skew = now.signed_duration_since(time);
+    let tolerance = TimeDelta::seconds(CLOCK_SKEW_TOLERANCE);
+    let skewed = if clock_url.is_some() {
+        skew.abs() > tolerance
+    } else {
+        -skew > tolerance
+    };
+    let detail = format!(
+        "{} compared with {source} at {}",
+        now.format("%Y-%m-%d %H:%M:%S UTC"),
+        time.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if skewed {
+        Check::fail(
+            name,
+            format!("off by {}s, {detail}", skew.num_seconds()),
+            CLOCK_HINT,
+        )
+    } else {
+        Check::pass(name, detail)
+    }
+}
+
+/// Time of the `Date` header of a response of the server at `url`, given up on after
+/// [`http::CLIENT_TIMEOUT`].
+///
+/// # Errors
+/// Errors if the server cannot be reached, or responds without a valid `Date` header.
+fn server_time(url: &str) -> anyhow::Result<DateTime<Utc>> {
+    let response = http::client().head(url).call()?;
+    let date = response
+        .header("Date")
+        .ok_or_else(|| anyhow::anyhow!("{url} responded without a `Date` header"))?;
+    let parsed: HttpDate = date.parse()?;
+    Ok(DateTime::<Utc>::from(SystemTime::from(parsed)))
+}
+
+/// Time of the latest commit of the authentication repository of the root stele.
+///
+/// # Errors
+/// Errors if the configuration cannot be read, or the repository has no `HEAD` commit.
+fn latest_commit_time(archive_path: &Path) -> anyhow::Result<DateTime<Utc>> {
+    let config = read_config(archive_path)?;
+    let repo = Repo::new(archive_path, &config.root.org, &config.root.name)?;
+    let seconds = repo.repo.head()?.peel_to_commit()?.time().seconds();
+    DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| anyhow::anyhow!("the latest commit has an invalid time"))
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::history::doctor::{
+        check_clock, check_git, check_git_binary, check_permissions, check_xml, Check, Diagnosis,
+        Outcome,
+    };
+    use std::io::{Read as _, Write as _};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Generate an archive of one document in one version.
+    #[cfg(feature = "test-fixtures")]
+    fn generate_archive() -> tempfile::TempDir {
+        use crate::testing::generate;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let size = generate::Size {
+            documents: 1,
+            versions: 1,
+        };
+        generate::generate(archive_dir.path(), size).unwrap();
+        archive_dir
+    }
+
+    /// Url of a server answering one request with the `Date` header `date`.
+    fn dated_server(date: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _read = stream.read(&mut request).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nDate: {date}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_diagnosis_when_check_failed_expect_not_ok_with_hint() {
+        let cut = Diagnosis::new(vec![
+            Check::pass("git", "built in".to_owned()),
+            Check::fail("clock", "off by 600s".to_owned(), "Synchronize it."),
+        ]);
+        assert!(!cut.ok);
+        let actual = cut.to_string();
+        assert_eq!(
+            actual,
+            "PASS  git: built in\nFAIL  clock: off by 600s\n      Synchronize it.\n"
+        );
+        let json = serde_json::to_value(&cut).unwrap();
+        assert_eq!(json["checks"][1]["outcome"], "FAIL");
+        assert!(json["checks"][0].get("hint").is_none());
+    }
+
+    #[test]
+    fn test_builtin_checks_when_default_build_expect_pass() {
+        assert_eq!(check_git().outcome, Outcome::Pass);
+        assert_eq!(check_xml().outcome, Outcome::Pass);
+    }
+
+    #[test]
+    fn test_check_git_binary_when_git_installed_expect_version() {
+        let actual = check_git_binary();
+        assert_eq!(actual.outcome, Outcome::Pass);
+        assert!(
+            actual.detail.starts_with("git version"),
+            "{}",
+            actual.detail
+        );
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_check_schema_when_migrated_expect_up_to_date() {
+        use crate::db;
+        use crate::history::doctor::check_schema;
+
+        let archive_dir = generate_archive();
+        let cut = check_schema;
+        let actual = cut(archive_dir.path()).await;
+        assert_eq!(actual.outcome, Outcome::Pass);
+        assert!(actual.detail.starts_with("no migrations applied yet"));
+
+        let _conn = db::init::connect(archive_dir.path()).await.unwrap();
+        let migrated = cut(archive_dir.path()).await;
+        assert_eq!(migrated.outcome, Outcome::Pass);
+        assert!(
+            migrated.detail.ends_with("is up to date"),
+            "{}",
+            migrated.detail
+        );
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_check_schema_when_newer_migration_applied_expect_fail() {
+        use crate::db;
+        use crate::history::doctor::{check_schema, SCHEMA_HINT};
+
+        let archive_dir = generate_archive();
+        let conn = db::init::connect(archive_dir.path()).await.unwrap();
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (99990101000000, 'future', 1, x'00', 0)",
+        )
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+
+        let cut = check_schema;
+        let actual = cut(archive_dir.path()).await;
+        assert_eq!(actual.outcome, Outcome::Fail);
+        assert!(actual.detail.starts_with("version 99990101000000 is newer"));
+        assert_eq!(actual.hint, Some(SCHEMA_HINT));
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_check_permissions_when_taf_dir_writable_expect_pass() {
+        let archive_dir = generate_archive();
+        let cut = check_permissions;
+        assert_eq!(cut(archive_dir.path()).outcome, Outcome::Pass);
+    }
+
+    #[test]
+    fn test_check_permissions_when_taf_dir_missing_expect_fail() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let cut = check_permissions;
+        let actual = cut(archive_dir.path());
+        assert_eq!(actual.outcome, Outcome::Fail);
+        assert!(actual.detail.starts_with("could not write to"));
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_check_clock_when_after_latest_commit_expect_pass() {
+        let archive_dir = generate_archive();
+        let cut = check_clock;
+        let actual = cut(archive_dir.path(), None);
+        assert_eq!(actual.outcome, Outcome::Pass, "{}", actual.detail);
+    }
+
+    #[test]
+    fn test_check_clock_when_server_date_skewed_expect_fail() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let url = dated_server("Mon, 01 Jan 2001 00:00:00 GMT");
+        let cut = check_clock;
+        let actual = cut(archive_dir.path(), Some(&url));
+        assert_eq!(actual.outcome, Outcome::Fail);
+        assert!(actual.detail.starts_with("off by"), "{}", actual.detail);
+    }
+
+    #[test]
+    fn test_check_clock_when_server_unreachable_expect_fail() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let cut = check_clock;
+        let actual = cut(archive_dir.path(), Some("http://127.0.0.1:9"));
+        assert_eq!(actual.outcome, Outcome::Fail);
+        assert!(actual.detail.starts_with("could not read a reference time"));
+    }
+}
@@ -0,0 +1,760 @@
+//! Module for exporting changes and documents from the archive
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+
+/// Writer for WARC web archive files.
+pub mod warc;
+use crate::db::models::change_record::ChangeRecord;
+use crate::db::models::data_repo_commits::{self, DataRepoCommits};
+use crate::db::models::status::Status;
+use crate::db::models::{document_change, library_change};
+use crate::db::{self, DatabaseConnection, Databases};
+use crate::server::base_path::BasePath;
+use crate::server::errors::CliError;
+use crate::stelae::archive::Archive;
+use crate::stelae::stele::Stele;
+use crate::stelae::types::repositories::{Repository, RepositoryType, ServeType};
+use crate::utils::date;
+use crate::utils::git::Repo;
+use crate::utils::html::{mount_root_relative_urls, prefix_root_relative_urls};
+use crate::utils::http::get_contenttype;
+use anyhow::Context as _;
+use chrono::{NaiveDate, Utc};
+use git2::{ObjectType, Oid, TreeWalkMode, TreeWalkResult};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Output format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Comma-separated values, with a header row.
+    Csv,
+    /// JSON Lines, one JSON object per change.
+    Json,
+}
+
+/// A change as it is written to the export.
+#[derive(Serialize)]
+struct ExportedChange<'record> {
+    /// Kind of the changed element, either `document` or `library`.
+    kind: &'record str,
+    /// Materialized path to the document or library.
+    mpath: &'record str,
+    /// Url of the document or library.
+    url: Option<&'record str>,
+    /// Human readable change status, e.g. `Element added`.
+    status: &'static str,
+    /// Optional reason for the change event.
+    change_reason: Option<&'record str>,
+    /// Codified date of the version in which the change occurred.
+    codified_date: &'record str,
+    /// Name of the publication which introduced the change.
+    publication: &'record str,
+}
+
+impl<'record> TryFrom<&'record ChangeRecord> for ExportedChange<'record> {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &'record ChangeRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            kind: &record.kind,
+            mpath: &record.mpath,
+            url: record.url.as_deref(),
+            status: Status::from_int(record.status)?.to_str(),
+            change_reason: record.change_reason.as_deref(),
+            codified_date: &record.codified_date,
+            publication: &record.publication,
+        })
+    }
+}
+
+/// Data repository commit of a stele, resolved for a date.
+pub struct DataCommit {
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+    pub stele: String,
+    /// The data repository.
+    pub repo: Repo,
+    /// The data repository commit mapped to the date.
+    pub data_repo_commit: DataRepoCommits,
+}
+
+/// Export all document and library changes of `stele` to stdout.
+///
+/// Changes can be limited to the codified dates between `start_date` and `end_date` (inclusive).
+///
+/// # Errors
+/// Errors if the database cannot be reached or the changes cannot be written
+#[tracing::instrument(name = "Stelae export changes", skip(archive_path))]
+pub async fn changes(
+    archive_path: PathBuf,
+    stele: &str,
+    format: Format,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+) -> Result<(), CliError> {
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let records = async {
+        let isolated = db::init::connect_stele(&archive_path, stele).await?;
+        find_change_records(
+            isolated.as_ref().unwrap_or(&conn),
+            stele,
+            start_date.map(|date| date.to_string()).as_deref(),
+            end_date.map(|date| date.to_string()).as_deref(),
+        )
+        .await
+    }
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to load changes for stele: {stele}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })?;
+    tracing::info!("[{stele}] | Exporting {} changes", records.len());
+    write_changes(io::stdout().lock(), &records, format).map_err(|err| {
+        tracing::error!("Failed to export changes for stele: {stele}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+/// Urls of exported documents.
+#[derive(Debug, Clone, Default)]
+pub struct Urls {
+    /// Prefix urls with `/_date/{date}`, mirroring historical urls served by stelae.
+    pub dated: bool,
+    /// Path the documents are served under, e.g. `/laws`, prefixing all root-relative urls.
+    pub base_path: BasePath,
+}
+
+/// Export the html data repository of a stele, as it was on `date`, into a static site in `out_dir`.
+///
+/// The site is taken from the data repository commit mapped to `date` during `stelae update`.
+/// If `urls.dated` is set, the site is written to `{out_dir}/_date/{date}/` and root-relative urls
+/// in html documents are prefixed with `/_date/{date}`, mirroring historical urls served by stelae.
+/// Otherwise the site is written to `out_dir` as is. Root-relative urls in html documents are
+/// prefixed with `urls.base_path`, if any.
+///
+/// # Errors
+/// Errors if the database cannot be reached, no commit is mapped to `date` or the site cannot be written
+#[tracing::instrument(name = "Stelae export site", skip(raw_archive_path, archive_path))]
+pub async fn site(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    stele: Option<&str>,
+    date: NaiveDate,
+    out_dir: &Path,
+    urls: &Urls,
+) -> Result<(), CliError> {
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    export_site(
+        conn,
+        raw_archive_path,
+        &archive_path,
+        stele,
+        &date,
+        out_dir,
+        urls,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to export static site for {date}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+/// Find the html data repository commit for `date` and write its tree to `out_dir`.
+async fn export_site(
+    conn: DatabaseConnection,
+    raw_archive_path: &str,
+    archive_path: &Path,
+    requested_stele: Option<&str>,
+    date: &NaiveDate,
+    out_dir: &Path,
+    urls: &Urls,
+) -> anyhow::Result<()> {
+    let (archive, databases) = open_archive(conn, raw_archive_path, archive_path).await?;
+    let html_commit = find_html_commit(&databases, &archive, requested_stele, date).await?;
+    let (site_dir, url_prefix) = if urls.dated {
+        (
+            out_dir.join("_date").join(date::format(*date)),
+            Some(format!("/_date/{date}")),
+        )
+    } else {
+        (out_dir.to_path_buf(), None)
+    };
+    let written = write_commit_tree(
+        &html_commit.repo,
+        &html_commit.data_repo_commit.commit_hash,
+        &site_dir,
+        url_prefix.as_deref(),
+        &urls.base_path,
+    )?;
+    tracing::info!("Exported {written} files to {}", site_dir.display());
+    Ok(())
+}
+
+/// Export the html data repository of a stele, as it was on `date`, into a WARC file at `out_file`.
+///
+/// Every file is recorded as a response and request record for its url under `base_url`.
+/// If `urls.dated` is set, urls are prefixed with `/_date/{date}`, mirroring historical urls
+/// served by stelae, including root-relative urls in html documents. Urls are prefixed with
+/// `urls.base_path` after `base_url`, if any.
+///
+/// # Errors
+/// Errors if the database cannot be reached, no commit is mapped to `date` or the file cannot be written
+#[tracing::instrument(name = "Stelae export warc", skip(raw_archive_path, archive_path))]
+pub async fn warc(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    stele: Option<&str>,
+    date: NaiveDate,
+    out_file: &Path,
+    base_url: &str,
+    urls: &Urls,
+) -> Result<(), CliError> {
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let result = async {
+        let (archive, databases) = open_archive(conn, raw_archive_path, &archive_path).await?;
+        let html_commit = find_html_commit(&databases, &archive, stele, &date).await?;
+        let url_prefix = urls.dated.then(|| format!("/_date/{date}"));
+        let written = write_warc(
+            &html_commit.repo,
+            &html_commit.data_repo_commit.commit_hash,
+            out_file,
+            base_url,
+            url_prefix.as_deref(),
+            &urls.base_path,
+        )?;
+        tracing::info!("Exported {written} documents to {}", out_file.display());
+        anyhow::Ok(())
+    };
+    result.await.map_err(|err| {
+        tracing::error!("Failed to export WARC file for {date}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+/// Write all files of the tree at `commit_hash` to a WARC file at `out_file`, returning the number of files written.
+fn write_warc(
+    repo: &Repo,
+    commit_hash: &str,
+    out_file: &Path,
+    base_url: &str,
+    url_prefix: Option<&str>,
+    base_path: &BasePath,
+) -> anyhow::Result<usize> {
+    let file = io::BufWriter::new(fs::File::create(out_file)?);
+    let mut writer = warc::Writer::new(file, &Utc::now(), commit_hash);
+    let filename = out_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    writer.write_warcinfo(&filename)?;
+    let blobs = find_commit_blobs(repo, commit_hash)?;
+    let written = blobs.len();
+    for (path, oid) in blobs {
+        let content = read_blob(repo, &path, oid, url_prefix, base_path)?;
+        let target_uri = format!(
+            "{}{}{}{}",
+            base_url.trim_end_matches('/'),
+            base_path.as_str(),
+            url_prefix.unwrap_or_default(),
+            document_url(&path)
+        );
+        let content_type = get_contenttype(&path.to_string_lossy()).to_string();
+        writer.write_exchange(&target_uri, &content_type, &content)?;
+    }
+    writer.into_inner()?;
+    Ok(written)
+}
+
+/// Root-relative url under which the file at `path` is served.
+///
+/// `index.html` files are served at their directory, e.g. `a/b/index.html` at `/a/b/`.
+fn document_url(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    let url = format!("/{}", components.join("/"));
+    url.strip_suffix("index.html")
+        .map_or(url.clone(), ToOwned::to_owned)
+}
+
+/// Parse the archive at `archive_path` and connect to the databases of its isolated stelae, next
+/// to the `shared` database of the archive.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed or the database of an isolated stele cannot be reached.
+pub async fn open_archive(
+    shared: DatabaseConnection,
+    raw_archive_path: &str,
+    archive_path: &Path,
+) -> anyhow::Result<(Archive, Databases)> {
+    let archive = Archive::parse(
+        archive_path.to_path_buf(),
+        &PathBuf::from(raw_archive_path),
+        false,
+    )?;
+    let databases = db::init::connect_stelae(archive_path, archive.stelae.keys(), shared).await?;
+    Ok((archive, databases))
+}
+
+/// Find the html data repository of a stele and the commit of it mapped to `date`.
+///
+/// Defaults to the root stele of the `archive` if `requested_stele` is not given.
+/// The commit is looked up in the database of the stele in `databases`.
+///
+/// # Errors
+/// Errors if the stele has no historical html repository or no commit is mapped to `date`
+pub async fn find_html_commit(
+    databases: &Databases,
+    archive: &Archive,
+    requested_stele: Option<&str>,
+    date: &NaiveDate,
+) -> anyhow::Result<DataCommit> {
+    let mut stele = find_stele(archive, requested_stele)?;
+    let stele_name = stele.get_qualified_name();
+    let repositories = stele
+        .get_repositories()?
+        .with_context(|| format!("No repositories found for stele: {stele_name}"))?;
+    let html_repo = repositories
+        .get_all_by_serve_type(&ServeType::Historical)
+        .into_iter()
+        .find(|repository| repository.custom.repository_type == Some(RepositoryType::Html))
+        .with_context(|| format!("No historical html repository found for stele: {stele_name}"))?;
+    let data_commit = find_data_commit(databases, archive, &stele_name, html_repo, date)
+        .await?
+        .with_context(|| format!("No html commit found for stele {stele_name} on {date}"))?;
+    tracing::info!(
+        "[{stele_name}] | Exporting {} at commit {} for {date}",
+        html_repo.name,
+        data_commit.data_repo_commit.commit_hash
+    );
+    Ok(data_commit)
+}
+
+/// Find every historical data repository of a stele and the commit of it mapped to `date`.
+///
+/// Defaults to the root stele of the `archive` if `requested_stele` is not given. Repositories
+/// without a commit mapped to `date` are left out.
+///
+/// # Errors
+/// Errors if the stele has no repositories, or a repository or its commit cannot be read.
+pub async fn find_data_commits(
+    databases: &Databases,
+    archive: &Archive,
+    requested_stele: Option<&str>,
+    date: &NaiveDate,
+) -> anyhow::Result<Vec<DataCommit>> {
+    let mut stele = find_stele(archive, requested_stele)?;
+    let stele_name = stele.get_qualified_name();
+    let repositories = stele
+        .get_repositories()?
+        .with_context(|| format!("No repositories found for stele: {stele_name}"))?;
+    let mut data_commits = vec![];
+    for repository in repositories.get_all_by_serve_type(&ServeType::Historical) {
+        if let Some(data_commit) =
+            find_data_commit(databases, archive, &stele_name, repository, date).await?
+        {
+            data_commits.push(data_commit);
+        }
+    }
+    Ok(data_commits)
+}
+
+/// Find the commit of the data `repository` of the stele `stele_name` mapped to `date`, if any.
+///
+/// Commits of preview publications are not exported.
+///
+/// # Errors
+/// Errors if the database cannot be reached, or the repository cannot be opened.
+async fn find_data_commit(
+    databases: &Databases,
+    archive: &Archive,
+    stele_name: &str,
+    repository: &Repository,
+    date: &NaiveDate,
+) -> anyhow::Result<Option<DataCommit>> {
+    let Some(repo_type) = repository.custom.repository_type.as_ref() else {
+        return Ok(None);
+    };
+    let Some(data_repo_commit) =
+        data_repo_commits::Manager::find_latest_by_stele_and_repo_type_on_or_before_date(
+            databases.for_stele(stele_name),
+            stele_name,
+            repo_type.as_str(),
+            date,
+            false,
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    let repo = Repo::new(&archive.path, &repository.get_org(), &repository.get_name())?;
+    Ok(Some(DataCommit {
+        stele: stele_name.to_owned(),
+        repo,
+        data_repo_commit,
+    }))
+}
+
+/// The stele `requested_stele` of the `archive`, or its root stele if not given.
+///
+/// # Errors
+/// Errors if the stele is not in the archive.
+fn find_stele(archive: &Archive, requested_stele: Option<&str>) -> anyhow::Result<Stele> {
+    Ok(match requested_stele {
+        Some(name) => archive
+            .stelae
+            .get(name)
+            .with_context(|| format!("Stele {name} not found in the archive"))?
+            .clone(),
+        None => archive.get_root()?.clone(),
+    })
+}
+
+/// Write all files of the tree at `commit_hash` to `site_dir`, returning the number of files written.
+///
+/// If `url_prefix` is given, root-relative urls in html documents are prefixed with it, and then
+/// with `base_path`.
+fn write_commit_tree(
+    repo: &Repo,
+    commit_hash: &str,
+    site_dir: &Path,
+    url_prefix: Option<&str>,
+    base_path: &BasePath,
+) -> anyhow::Result<usize> {
+    let blobs = find_commit_blobs(repo, commit_hash)?;
+    let written = blobs.len();
+    for (path, oid) in blobs {
+        let content = read_blob(repo, &path, oid, url_prefix, base_path)?;
+        let file_path = site_dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&file_path, content)?;
+    }
+    Ok(written)
+}
+
+/// Find the paths and ids of all blobs in the tree at `commit_hash`.
+///
+/// # Errors
+/// Errors if the commit cannot be found in the repository
+pub fn find_commit_blobs(repo: &Repo, commit_hash: &str) -> anyhow::Result<Vec<(PathBuf, Oid)>> {
+    let commit = repo.repo.find_commit(Oid::from_str(commit_hash)?)?;
+    let tree = commit.tree()?;
+    let mut blobs: Vec<(PathBuf, Oid)> = vec![];
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            let name = entry.name().unwrap_or_default();
+            blobs.push((PathBuf::from(root).join(name), entry.id()));
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(blobs)
+}
+
+/// Read the content of the blob `oid` found at `path`.
+///
+/// If the blob is an html document, its root-relative urls are prefixed with `url_prefix`, if
+/// given, and then with `base_path`.
+fn read_blob(
+    repo: &Repo,
+    path: &Path,
+    oid: Oid,
+    url_prefix: Option<&str>,
+    base_path: &BasePath,
+) -> anyhow::Result<Vec<u8>> {
+    let blob = repo.repo.find_blob(oid)?;
+    let is_html = path
+        .extension()
+        .is_some_and(|ext| ext == "html" || ext == "htm");
+    if !is_html {
+        return Ok(blob.content().to_vec());
+    }
+    let dated = match url_prefix {
+        Some(prefix) => prefix_root_relative_urls(blob.content(), prefix)
+            .with_context(|| format!("Could not rewrite urls in {}", path.display()))?,
+        None => blob.content().to_vec(),
+    };
+    if base_path.is_root() {
+        return Ok(dated);
+    }
+    mount_root_relative_urls(&dated, base_path.as_str())
+        .with_context(|| format!("Could not rewrite urls in {}", path.display()))
+}
+
+/// Load both document and library changes of a stele, ordered by codified date.
+async fn find_change_records(
+    conn: &DatabaseConnection,
+    stele: &str,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> anyhow::Result<Vec<ChangeRecord>> {
+    let mut records = document_change::Manager::find_all_change_records_by_stele_and_date_range(
+        conn, stele, start_date, end_date,
+    )
+    .await?;
+    records.extend(
+        library_change::Manager::find_all_change_records_by_stele_and_date_range(
+            conn, stele, start_date, end_date,
+        )
+        .await?,
+    );
+    records.sort_by(|cr1, cr2| {
+        (&cr1.codified_date, &cr1.kind, &cr1.mpath).cmp(&(
+            &cr2.codified_date,
+            &cr2.kind,
+            &cr2.mpath,
+        ))
+    });
+    Ok(records)
+}
+
+/// Write `records` to `writer` in the given `format`.
+///
+/// # Errors
+/// Errors if a record has an unknown status or cannot be written
+pub fn write_changes<W: Write>(
+    mut writer: W,
+    records: &[ChangeRecord],
+    format: Format,
+) -> anyhow::Result<()> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for record in records {
+                csv_writer.serialize(ExportedChange::try_from(record)?)?;
+            }
+            csv_writer.flush()?;
+        }
+        Format::Json => {
+            for record in records {
+                serde_json::to_writer(&mut writer, &ExportedChange::try_from(record)?)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::db::models::change_record::ChangeRecord;
+    use crate::history::export::{document_url, write_changes, write_commit_tree, Format};
+    use crate::server::base_path::BasePath;
+    use crate::utils::git::Repo;
+    use std::fs;
+    use std::path::Path;
+
+    fn change_records() -> Vec<ChangeRecord> {
+        vec![
+            ChangeRecord {
+                kind: "document".to_owned(),
+                mpath: "|ord|chapter-1|".to_owned(),
+                url: Some("/ord/chapter-1/".to_owned()),
+                status: 0,
+                change_reason: Some("Added, by Ordinance 1".to_owned()),
+                codified_date: "2023-10-22".to_owned(),
+                publication: "2023-10-22".to_owned(),
+            },
+            ChangeRecord {
+                kind: "library".to_owned(),
+                mpath: "|ord|".to_owned(),
+                url: None,
+                status: 2,
+                change_reason: None,
+                codified_date: "2023-11-02".to_owned(),
+                publication: "2023-12-30".to_owned(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_changes_when_csv_expect_header_and_rows() {
+        let mut actual = Vec::new();
+
+        let cut = write_changes;
+        cut(&mut actual, &change_records(), Format::Csv).unwrap();
+
+        let expected = "kind,mpath,url,status,change_reason,codified_date,publication\n\
+            document,|ord|chapter-1|,/ord/chapter-1/,Element added,\"Added, by Ordinance 1\",2023-10-22,2023-10-22\n\
+            library,|ord|,,Element changed,,2023-11-02,2023-12-30\n";
+        assert_eq!(String::from_utf8(actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_changes_when_json_expect_one_object_per_line() {
+        let mut actual = Vec::new();
+
+        let cut = write_changes;
+        cut(&mut actual, &change_records(), Format::Json).unwrap();
+
+        let expected = "{\"kind\":\"document\",\"mpath\":\"|ord|chapter-1|\",\"url\":\"/ord/chapter-1/\",\"status\":\"Element added\",\"change_reason\":\"Added, by Ordinance 1\",\"codified_date\":\"2023-10-22\",\"publication\":\"2023-10-22\"}\n\
+            {\"kind\":\"library\",\"mpath\":\"|ord|\",\"url\":null,\"status\":\"Element changed\",\"change_reason\":null,\"codified_date\":\"2023-11-02\",\"publication\":\"2023-12-30\"}\n";
+        assert_eq!(String::from_utf8(actual).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_changes_when_unknown_status_expect_error() {
+        let mut records = change_records();
+        records[0].status = 42;
+        let mut output = Vec::new();
+
+        let cut = write_changes;
+        let actual = cut(&mut output, &records, Format::Csv);
+
+        assert!(actual.is_err());
+    }
+
+    fn commit_site(archive_dir: &Path) -> String {
+        let repo = git2::Repository::init(archive_dir.join("test_org/law-html")).unwrap();
+        let html = repo
+            .blob(br#"<a href="/a/b/">b</a><img src="logo.png">"#)
+            .unwrap();
+        let png = repo.blob(b"/not/a/url").unwrap();
+        let mut subtree = repo.treebuilder(None).unwrap();
+        subtree.insert("index.html", html, 0o100_644).unwrap();
+        let subtree_oid = subtree.write().unwrap();
+        let mut root = repo.treebuilder(None).unwrap();
+        root.insert("a", subtree_oid, 0o040_000).unwrap();
+        root.insert("logo.png", png, 0o100_644).unwrap();
+        let tree = repo.find_tree(root.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(None, &signature, &signature, "site", &tree, &[])
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_write_commit_tree_when_url_prefix_expect_html_urls_rewritten() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let site_dir = tempfile::tempdir().unwrap();
+        let commit_hash = commit_site(archive_dir.path());
+        let repo = Repo::new(archive_dir.path(), "test_org", "law-html").unwrap();
+
+        let cut = write_commit_tree;
+        let actual = cut(
+            &repo,
+            &commit_hash,
+            site_dir.path(),
+            Some("/_date/2023-10-22"),
+            &BasePath::default(),
+        )
+        .unwrap();
+
+        assert_eq!(actual, 2);
+        assert_eq!(
+            fs::read_to_string(site_dir.path().join("a/index.html")).unwrap(),
+            r#"<a href="/_date/2023-10-22/a/b/">b</a><img src="logo.png">"#
+        );
+        assert_eq!(
+            fs::read_to_string(site_dir.path().join("logo.png")).unwrap(),
+            "/not/a/url"
+        );
+    }
+
+    #[test]
+    fn test_write_commit_tree_when_base_path_expect_html_urls_mounted() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let site_dir = tempfile::tempdir().unwrap();
+        let commit_hash = commit_site(archive_dir.path());
+        let repo = Repo::new(archive_dir.path(), "test_org", "law-html").unwrap();
+
+        let cut = write_commit_tree;
+        cut(
+            &repo,
+            &commit_hash,
+            site_dir.path(),
+            Some("/_date/2023-10-22"),
+            &BasePath::parse("/laws").unwrap(),
+        )
+        .unwrap();
+
+        let actual = fs::read_to_string(site_dir.path().join("a/index.html")).unwrap();
+        let expected = r#"<a href="/laws/_date/2023-10-22/a/b/">b</a><img src="logo.png">"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_commit_tree_when_no_url_prefix_expect_files_unchanged() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let site_dir = tempfile::tempdir().unwrap();
+        let commit_hash = commit_site(archive_dir.path());
+        let repo = Repo::new(archive_dir.path(), "test_org", "law-html").unwrap();
+
+        let cut = write_commit_tree;
+        cut(
+            &repo,
+            &commit_hash,
+            site_dir.path(),
+            None,
+            &BasePath::default(),
+        )
+        .unwrap();
+
+        let actual = fs::read_to_string(site_dir.path().join("a/index.html")).unwrap();
+        let expected = r#"<a href="/a/b/">b</a><img src="logo.png">"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_document_url_when_index_html_expect_directory_url() {
+        let cut = document_url;
+        let actual = cut(Path::new("a/b/index.html"));
+        let expected = "/a/b/";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_document_url_when_other_file_expect_file_url() {
+        let cut = document_url;
+        let actual = cut(Path::new("static/logo.png"));
+        let expected = "/static/logo.png";
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,191 @@
+//! Writer for ISO 28500 (WARC 1.1) web archive files
+use chrono::{DateTime, SecondsFormat, Utc};
+use md5::{Digest as _, Md5};
+use std::io::{self, Write};
+
+/// Version line starting every WARC record.
+const WARC_VERSION: &str = "WARC/1.1";
+
+/// Writes WARC records of documents from a single data repository commit.
+pub struct Writer<W: Write> {
+    /// Underlying writer, usually a `.warc` file.
+    inner: W,
+    /// Capture date written to `WARC-Date` of every record.
+    warc_date: String,
+    /// Commit hash of the data repository the documents were read from.
+    commit_hash: String,
+}
+
+impl<W: Write> Writer<W> {
+    /// Create a new WARC writer for documents read from `commit_hash`, captured on `warc_date`.
+    pub fn new(inner: W, warc_date: &DateTime<Utc>, commit_hash: &str) -> Self {
+        Self {
+            inner,
+            warc_date: warc_date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            commit_hash: commit_hash.to_owned(),
+        }
+    }
+
+    /// Write the `warcinfo` record describing the WARC file named `filename`.
+    ///
+    /// # Errors
+    /// Errors if the record cannot be written
+    pub fn write_warcinfo(&mut self, filename: &str) -> io::Result<()> {
+        let fields = format!(
+            "software: stelae/{}\r\nformat: WARC File Format 1.1\r\nconformsTo: http://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        let record_id = self.record_id("warcinfo", filename);
+        self.write_record(
+            &[
+                ("WARC-Type", "warcinfo"),
+                ("WARC-Record-ID", &record_id),
+                ("WARC-Filename", filename),
+                ("Content-Type", "application/warc-fields"),
+            ],
+            fields.as_bytes(),
+        )
+    }
+
+    /// Write a `response` record for `body` served at `target_uri`, followed by its `request` record.
+    ///
+    /// # Errors
+    /// Errors if the records cannot be written
+    pub fn write_exchange(
+        &mut self,
+        target_uri: &str,
+        content_type: &str,
+        body: &[u8],
+    ) -> io::Result<()> {
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        let response_id = self.record_id("response", target_uri);
+        self.write_record(
+            &[
+                ("WARC-Type", "response"),
+                ("WARC-Record-ID", &response_id),
+                ("WARC-Target-URI", target_uri),
+                ("Content-Type", "application/http; msgtype=response"),
+            ],
+            &response,
+        )?;
+
+        let (host, path) = split_uri(target_uri);
+        let request = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n\r\n");
+        let request_id = self.record_id("request", target_uri);
+        self.write_record(
+            &[
+                ("WARC-Type", "request"),
+                ("WARC-Record-ID", &request_id),
+                ("WARC-Concurrent-To", &response_id),
+                ("WARC-Target-URI", target_uri),
+                ("Content-Type", "application/http; msgtype=request"),
+            ],
+            request.as_bytes(),
+        )
+    }
+
+    /// Flush and return the underlying writer.
+    ///
+    /// # Errors
+    /// Errors if the underlying writer cannot be flushed
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Write a single record with the common `WARC-Date`, commit hash and `Content-Length` headers.
+    fn write_record(&mut self, headers: &[(&str, &str)], block: &[u8]) -> io::Result<()> {
+        write!(self.inner, "{WARC_VERSION}\r\n")?;
+        for &(name, value) in headers {
+            write!(self.inner, "{name}: {value}\r\n")?;
+        }
+        write!(
+            self.inner,
+            "WARC-Date: {}\r\nWARC-Stelae-Commit-Hash: {}\r\nContent-Length: {}\r\n\r\n",
+            self.warc_date,
+            self.commit_hash,
+            block.len()
+        )?;
+        self.inner.write_all(block)?;
+        self.inner.write_all(b"\r\n\r\n")
+    }
+
+    /// Deterministic, name-based (version 3) record id for a record of `record_type` about `name`.
+    fn record_id(&self, record_type: &str, name: &str) -> String {
+        let mut bytes: [u8; 16] =
+            Md5::digest(format!("{}{record_type}{name}", self.commit_hash)).into();
+        bytes[6] = (bytes[6] & 0x0f) | 0x30;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let uuid: String = bytes
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| {
+                if matches!(idx, 4 | 6 | 8 | 10) {
+                    format!("-{byte:02x}")
+                } else {
+                    format!("{byte:02x}")
+                }
+            })
+            .collect();
+        format!("<urn:uuid:{uuid}>")
+    }
+}
+
+/// Split an absolute `uri` into its host and path.
+fn split_uri(uri: &str) -> (&str, &str) {
+    let without_scheme = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    without_scheme
+        .find('/')
+        .map_or((without_scheme, "/"), |idx| without_scheme.split_at(idx))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::export::warc::Writer;
+    use chrono::{TimeZone as _, Utc};
+
+    fn write_exchange() -> String {
+        let warc_date = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let mut cut = Writer::new(Vec::new(), &warc_date, "abc123");
+        cut.write_exchange("https://law.example.gov/a/b/", "text/html", b"<p>law</p>")
+            .unwrap();
+        String::from_utf8(cut.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_write_exchange_when_html_expect_response_record() {
+        let actual = write_exchange();
+
+        let response = actual.split("WARC/1.1\r\n").nth(1).unwrap();
+        assert!(response.starts_with("WARC-Type: response\r\n"));
+        assert!(response.contains("WARC-Target-URI: https://law.example.gov/a/b/\r\n"));
+        assert!(response.contains("WARC-Date: 2024-01-02T03:04:05Z\r\n"));
+        assert!(response.contains("WARC-Stelae-Commit-Hash: abc123\r\n"));
+        assert!(response.contains("Content-Length: 74\r\n"));
+        assert!(response.ends_with(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 10\r\n\r\n<p>law</p>\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_write_exchange_when_html_expect_concurrent_request_record() {
+        let actual = write_exchange();
+
+        let mut records = actual.split("WARC/1.1\r\n").skip(1);
+        let response = records.next().unwrap();
+        let request = records.next().unwrap();
+        let response_id = response
+            .lines()
+            .find_map(|line| line.strip_prefix("WARC-Record-ID: "))
+            .unwrap();
+        assert!(request.starts_with("WARC-Type: request\r\n"));
+        assert!(request.contains(&format!("WARC-Concurrent-To: {response_id}\r\n")));
+        assert!(request.ends_with("GET /a/b/ HTTP/1.1\r\nHost: law.example.gov\r\n\r\n\r\n\r\n"));
+    }
+}
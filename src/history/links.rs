@@ -0,0 +1,212 @@
+//! Check that the internal links of served html documents resolve within their publication.
+//!
+//! For every publication of a stele, the html documents of the latest html data repository
+//! commit in the publication are parsed for links. Links to other documents of the stele, i.e.
+//! root-relative and relative links, are looked up at the same commit. Links that do not
+//! resolve are recorded in the `broken_links` table, replacing the previous report.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::models::broken_link::{self, BrokenLink};
+use crate::db::models::{data_repo_commits, publication};
+use crate::db::DatabaseTransaction;
+use crate::history::export::find_commit_blobs;
+use crate::utils::git::Repo;
+use crate::utils::html::find_link_hrefs;
+use std::collections::HashMap;
+
+/// Check the internal links of the html documents in every publication of the stele.
+///
+/// # Errors
+/// Errors if the publications or their commits cannot be read from the database or the
+/// html data repository, or if the broken links cannot be inserted.
+pub async fn check_publications(
+    tx: &mut DatabaseTransaction,
+    stele: &str,
+    html_repo: &Repo,
+) -> anyhow::Result<()> {
+    for publication in
+        publication::TxManager::find_all_non_revoked_with_previews_by_stele(tx, stele).await?
+    {
+        let Some(data_repo_commit) =
+            data_repo_commits::TxManager::find_latest_by_publication_and_repo_type(
+                tx,
+                &publication.id,
+                "html",
+            )
+            .await?
+        else {
+            continue;
+        };
+        let commit_hash = data_repo_commit.commit_hash;
+        let broken_links: Vec<BrokenLink> = find_broken(html_repo, &commit_hash)?
+            .into_iter()
+            .map(|(source_path, href)| {
+                BrokenLink::new(
+                    stele.to_owned(),
+                    publication.name.clone(),
+                    commit_hash.clone(),
+                    source_path,
+                    href,
+                )
+            })
+            .collect();
+        if broken_links.is_empty() {
+            tracing::info!(
+                "[{stele}] | No broken links in publication {}",
+                publication.name
+            );
+        } else {
+            tracing::warn!(
+                "[{stele}] | Recorded {} broken link(s) in publication {} in `broken_links`",
+                broken_links.len(),
+                publication.name
+            );
+        }
+        broken_link::TxManager::replace_all_by_stele_and_publication(
+            tx,
+            stele,
+            &publication.name,
+            broken_links,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Find the internal links of the html documents at `commit_hash` that do not resolve.
+///
+/// Returns the path of the document and the link, for every broken link.
+///
+/// # Errors
+/// Errors if the commit or its html documents cannot be read.
+pub fn find_broken(repo: &Repo, commit_hash: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut resolved: HashMap<String, bool> = HashMap::new();
+    let mut broken = vec![];
+    for (path, oid) in find_commit_blobs(repo, commit_hash)? {
+        let is_html = path
+            .extension()
+            .is_some_and(|ext| ext == "html" || ext == "htm");
+        if !is_html {
+            continue;
+        }
+        let source_path = path.to_string_lossy().into_owned();
+        let blob = repo.repo.find_blob(oid)?;
+        for href in find_link_hrefs(blob.content())? {
+            let Some(target) = resolve_href(&source_path, &href) else {
+                continue;
+            };
+            let exists = *resolved.entry(target).or_insert_with_key(|target_path| {
+                repo.get_bytes_at_path(commit_hash, target_path).is_ok()
+            });
+            if !exists {
+                broken.push((source_path.clone(), href));
+            }
+        }
+    }
+    Ok(broken)
+}
+
+/// Resolve an internal link found in the document at `source_path` to a repository path.
+///
+/// Query strings and fragments are ignored. Returns `None` for links that are not internal,
+/// i.e. absolute and protocol-relative urls, links to stelae's own endpoints (starting
+/// with `/_`), and links to the document itself.
+#[must_use]
+pub fn resolve_href(source_path: &str, href: &str) -> Option<String> {
+    let url = href.split(['#', '?']).next().unwrap_or_default();
+    let has_scheme = url
+        .find(':')
+        .is_some_and(|colon| url.find('/').is_none_or(|slash| colon < slash));
+    if url.is_empty() || has_scheme || url.starts_with("//") || url.starts_with("/_") {
+        return None;
+    }
+    let mut segments: Vec<&str> = vec![];
+    if !url.starts_with('/') {
+        segments.extend(source_path.split('/'));
+        segments.pop();
+    }
+    for segment in url.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::links::{find_broken, resolve_href};
+    use crate::utils::git::Repo;
+    use git2::{Repository, Signature};
+
+    #[test]
+    fn test_resolve_href_when_internal_expect_repository_path() {
+        let cut = resolve_href;
+        let source = "us/ca/codes/1.01/index.html";
+        assert_eq!(
+            cut(source, "/us/ca/codes/1.02/").unwrap(),
+            "us/ca/codes/1.02"
+        );
+        assert_eq!(cut(source, "../1.02/#s1").unwrap(), "us/ca/codes/1.02");
+        assert_eq!(
+            cut(source, "./a.html?x=1").unwrap(),
+            "us/ca/codes/1.01/a.html"
+        );
+        assert_eq!(cut(source, "/").unwrap(), "");
+    }
+
+    #[test]
+    fn test_resolve_href_when_not_internal_expect_none() {
+        let cut = resolve_href;
+        let source = "us/ca/codes/1.01/index.html";
+        for href in [
+            "https://example.com/a",
+            "mailto:info@example.com",
+            "//cdn.example.com/b",
+            "/_api/versions/",
+            "#section-1",
+        ] {
+            assert_eq!(cut(source, href), None, "{href}");
+        }
+    }
+
+    #[test]
+    fn test_find_broken_when_links_do_not_resolve_expect_reported() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let repository = Repository::init(archive_dir.path().join("test_org/law-html")).unwrap();
+        let index = repository
+            .blob(br#"<a href="/a/">a</a><a href="/missing/">m</a><a href="https://example.com/">e</a>"#)
+            .unwrap();
+        let nested = repository
+            .blob(br#"<a href="../">up</a><a href="../b.html">b</a>"#)
+            .unwrap();
+        let mut a_builder = repository.treebuilder(None).unwrap();
+        a_builder.insert("index.html", nested, 0o100_644).unwrap();
+        let a_tree = a_builder.write().unwrap();
+        let mut root_builder = repository.treebuilder(None).unwrap();
+        root_builder.insert("index.html", index, 0o100_644).unwrap();
+        root_builder.insert("a", a_tree, 0o040_000).unwrap();
+        let tree = repository.find_tree(root_builder.write().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let commit = repository
+            .commit(Some("HEAD"), &signature, &signature, "v1", &tree, &[])
+            .unwrap();
+        let repo = Repo::new(archive_dir.path(), "test_org", "law-html").unwrap();
+
+        let cut = find_broken;
+        let mut actual = cut(&repo, &commit.to_string()).unwrap();
+        actual.sort();
+        let expected = vec![
+            ("a/index.html".to_owned(), "../b.html".to_owned()),
+            ("index.html".to_owned(), "/missing/".to_owned()),
+        ];
+        assert_eq!(actual, expected);
+    }
+}
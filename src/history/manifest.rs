@@ -0,0 +1,592 @@
+//! Module for creating and verifying signed checksum manifests of a publication.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db;
+use crate::history::export::{find_commit_blobs, find_data_commits, open_archive};
+use crate::server::errors::CliError;
+use crate::utils::archive::get_name_parts;
+use crate::utils::git::Repo;
+use crate::utils::output::{write_json, Output};
+use anyhow::Context as _;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use chrono::{NaiveDate, Utc};
+use git2::Oid;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Checksum manifest of all files of a publication, across its historical data repositories.
+///
+/// Every repository is bound to the authentication repository commit that authenticated its data
+/// repository commit. The manifest carries a digest over all of its entries and, if created with
+/// a key, an HMAC-SHA256 signature of the digest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+    pub stele: String,
+    /// Date the manifest was created for (YYYY-MM-DD).
+    pub date: String,
+    /// Time the manifest was generated at, in RFC 3339 format.
+    pub generated_at: String,
+    /// Files of every historical data repository of the publication, sorted by repository.
+    pub repositories: Vec<RepositoryChecksums>,
+    /// `sha256` over all `repositories`, see [`Manifest::compute_digest`].
+    pub digest: String,
+    /// Base64 encoded HMAC-SHA256 of `digest`, see [`Manifest::sign`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Checksums of all files in a data repository commit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepositoryChecksums {
+    /// Qualified name of the data repository, e.g. `org-name/repo-name-law-html`.
+    pub repository: String,
+    /// Data repository commit the files were read from.
+    pub commit: String,
+    /// Authentication repository commit which authenticated `commit`.
+    pub auth_commit: String,
+    /// Entries for every file in `commit`, sorted by path.
+    pub files: Vec<Entry>,
+}
+
+/// A single file in a checksum manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Entry {
+    /// Path of the file in the data repository.
+    pub path: String,
+    /// Hex encoded `sha256` of the file content.
+    pub sha256: String,
+    /// Git object id of the file.
+    pub oid: String,
+}
+
+impl Manifest {
+    /// Compute the digest of the manifest entries.
+    ///
+    /// The digest is the `sha256` of one `{repository}\t{commit}\t{auth_commit}\n` line per
+    /// repository, each followed by one `{path}\t{sha256}\t{oid}\n` line per entry.
+    #[must_use]
+    pub fn compute_digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for repository in &self.repositories {
+            hasher.update(format!(
+                "{}\t{}\t{}\n",
+                repository.repository, repository.commit, repository.auth_commit
+            ));
+            for entry in &repository.files {
+                hasher.update(format!("{}\t{}\t{}\n", entry.path, entry.sha256, entry.oid));
+            }
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Sign the digest of the manifest with HMAC-SHA256 under `key`.
+    pub fn sign(&mut self, key: &[u8]) {
+        let hmac_key = hmac::Key::new(hmac::HMAC_SHA256, key);
+        let tag = hmac::sign(&hmac_key, self.digest.as_bytes());
+        self.signature = Some(STANDARD.encode(tag.as_ref()));
+    }
+
+    /// Whether the manifest is signed, and its signature matches its digest under `key`.
+    #[must_use]
+    pub fn verify_signature(&self, key: &[u8]) -> bool {
+        let Some(tag) = self
+            .signature
+            .as_deref()
+            .and_then(|signature| STANDARD.decode(signature).ok())
+        else {
+            return false;
+        };
+        let hmac_key = hmac::Key::new(hmac::HMAC_SHA256, key);
+        hmac::verify(&hmac_key, self.digest.as_bytes(), &tag).is_ok()
+    }
+}
+
+/// Outcome of verifying an archive against a manifest.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Verification {
+    /// Number of files that matched the manifest.
+    pub matched: usize,
+    /// Paths of files in the manifest whose content no longer matches.
+    pub mismatched: Vec<String>,
+    /// Paths of files in the manifest that no longer exist in the commit.
+    pub missing: Vec<String>,
+    /// Paths of files in the commit that are not in the manifest.
+    pub unexpected: Vec<String>,
+    /// Whether the stored digest matches the manifest entries.
+    pub digest_valid: bool,
+    /// Whether the signature matches the digest, if verified with a key.
+    pub signature_valid: Option<bool>,
+}
+
+impl Verification {
+    /// Human readable description of every difference between the archive and the manifest.
+    #[must_use]
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = vec![];
+        if !self.digest_valid {
+            problems.push("Manifest digest does not match its entries".to_owned());
+        }
+        if self.signature_valid == Some(false) {
+            problems.push("Manifest signature does not match its digest".to_owned());
+        }
+        problems.extend(
+            self.mismatched
+                .iter()
+                .map(|path| format!("Checksum mismatch: {path}")),
+        );
+        problems.extend(
+            self.missing
+                .iter()
+                .map(|path| format!("Missing file: {path}")),
+        );
+        problems.extend(
+            self.unexpected
+                .iter()
+                .map(|path| format!("File not in manifest: {path}")),
+        );
+        problems
+    }
+}
+
+/// Create a checksum manifest of the historical data repositories of a stele, as they were on
+/// `date`.
+///
+/// The manifest is signed with the contents of `key_file`, if given, and written as JSON to
+/// `out_file`, or to stdout if not given.
+///
+/// # Errors
+/// Errors if the database cannot be reached, no commit is mapped to `date` or the manifest cannot be written
+#[tracing::instrument(name = "Stelae manifest", skip(raw_archive_path, archive_path))]
+pub async fn create(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    stele: Option<&str>,
+    date: NaiveDate,
+    out_file: Option<&Path>,
+    key_file: Option<&Path>,
+) -> Result<(), CliError> {
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let result = async {
+        let (archive, databases) = open_archive(conn, raw_archive_path, &archive_path).await?;
+        let data_commits = find_data_commits(&databases, &archive, stele, &date).await?;
+        let Some(first) = data_commits.first() else {
+            anyhow::bail!("No data repository commit found on {date}");
+        };
+        let mut manifest = Manifest {
+            stele: first.stele.clone(),
+            date: date.to_string(),
+            generated_at: Utc::now().to_rfc3339(),
+            repositories: data_commits
+                .iter()
+                .map(|data_commit| {
+                    build(
+                        &data_commit.repo,
+                        &data_commit.data_repo_commit.commit_hash,
+                        &data_commit.data_repo_commit.auth_commit_hash,
+                    )
+                })
+                .collect::<anyhow::Result<_>>()?,
+            digest: String::new(),
+            signature: None,
+        };
+        manifest.repositories.sort_by(|repository1, repository2| {
+            repository1.repository.cmp(&repository2.repository)
+        });
+        manifest.digest = manifest.compute_digest();
+        if let Some(path) = key_file {
+            manifest.sign(&read_key(path)?);
+        }
+        let json = serde_json::to_string_pretty(&manifest)?;
+        match out_file {
+            Some(path) => fs::write(path, json)?,
+            None => writeln!(io::stdout().lock(), "{json}")?,
+        }
+        tracing::info!(
+            "Created manifest of {} files in {} repositories with digest {}",
+            manifest
+                .repositories
+                .iter()
+                .map(|repository| repository.files.len())
+                .sum::<usize>(),
+            manifest.repositories.len(),
+            manifest.digest
+        );
+        anyhow::Ok(())
+    };
+    result.await.map_err(|err| {
+        tracing::error!("Failed to create manifest for {date}");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+/// Results of `stelae manifest --verify`, written with `--output json`.
+#[derive(Debug, Serialize)]
+pub struct Verified<'verification> {
+    /// Whether the archive matches the manifest.
+    pub ok: bool,
+    /// Path of the manifest file.
+    pub manifest: String,
+    /// Error that stopped the verification, if any.
+    pub error: Option<String>,
+    /// Outcome of the verification, if it ran.
+    #[serde(flatten)]
+    pub verification: Option<&'verification Verification>,
+    /// Human readable description of every difference between the archive and the manifest.
+    pub problems: Vec<String>,
+}
+
+impl<'verification> Verified<'verification> {
+    /// Results of verifying the archive against the manifest at `manifest_file`.
+    fn new(manifest_file: &Path, result: &'verification anyhow::Result<Verification>) -> Self {
+        let problems = result
+            .as_ref()
+            .map(Verification::problems)
+            .unwrap_or_default();
+        Self {
+            ok: result.is_ok() && problems.is_empty(),
+            manifest: manifest_file.display().to_string(),
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+            verification: result.as_ref().ok(),
+            problems,
+        }
+    }
+
+    /// Log the error and the problems of the verification.
+    fn log(&self) {
+        if let Some(err) = self.error.as_deref() {
+            tracing::error!("Failed to verify manifest {}: {err}", self.manifest);
+        }
+        for problem in &self.problems {
+            tracing::error!("{problem}");
+        }
+    }
+}
+
+/// Verify the archive against a previously created manifest at `manifest_file`.
+///
+/// If `key_file` is given, the manifest must also be signed with its contents.
+/// With `output` JSON, the outcome is written to stdout, see [`Verified`].
+///
+/// # Errors
+/// Errors with [`CliError::VerificationFailure`] if the archive does not match the manifest, or
+/// with [`CliError::GenericError`] if the manifest cannot be read.
+pub fn verify(
+    archive_path: &Path,
+    manifest_file: &Path,
+    key_file: Option<&Path>,
+    output: Output,
+) -> Result<(), CliError> {
+    let result = verify_manifest_file(archive_path, manifest_file, key_file);
+    let verified = Verified::new(manifest_file, &result);
+    verified.log();
+    if output.is_json() {
+        write_json(&verified).map_err(|_err| CliError::GenericError)?;
+    }
+    if let (true, Some(verification)) = (verified.ok, verified.verification) {
+        tracing::info!("Verified {} files against manifest", verification.matched);
+        Ok(())
+    } else if verified.error.is_some() {
+        Err(CliError::GenericError)
+    } else {
+        Err(CliError::VerificationFailure)
+    }
+}
+
+/// Verify the archive at `archive_path` against the manifest at `manifest_file`, and its
+/// signature with the key in `key_file`, if given.
+///
+/// # Errors
+/// Errors if the manifest or the key cannot be read, or a repository cannot be read.
+fn verify_manifest_file(
+    archive_path: &Path,
+    manifest_file: &Path,
+    key_file: Option<&Path>,
+) -> anyhow::Result<Verification> {
+    let json = fs::read_to_string(manifest_file).context("Could not read manifest")?;
+    let manifest: Manifest = serde_json::from_str(&json).context("Could not parse manifest")?;
+    let key = key_file.map(read_key).transpose()?;
+    check(archive_path, &manifest, key.as_deref())
+}
+
+/// Read the signing key of manifests from `path`, without surrounding whitespace.
+///
+/// # Errors
+/// Errors if the file cannot be read or is empty.
+fn read_key(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let content = fs::read(path)
+        .with_context(|| format!("Could not read manifest key {}", path.display()))?;
+    let key = content.trim_ascii();
+    if key.is_empty() {
+        anyhow::bail!("Manifest key {} is empty", path.display());
+    }
+    Ok(key.to_vec())
+}
+
+/// Build the manifest of all files in `commit_hash` of `repo`.
+///
+/// # Errors
+/// Errors if the commit or one of its blobs cannot be read.
+pub fn build(
+    repo: &Repo,
+    commit_hash: &str,
+    auth_commit_hash: &str,
+) -> anyhow::Result<RepositoryChecksums> {
+    let mut files = find_commit_blobs(repo, commit_hash)?
+        .into_iter()
+        .map(|(path, oid)| {
+            let blob = repo.repo.find_blob(oid)?;
+            anyhow::Ok(Entry {
+                path: path.to_string_lossy().into_owned(),
+                sha256: format!("{:x}", Sha256::digest(blob.content())),
+                oid: oid.to_string(),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    files.sort_by(|entry1, entry2| entry1.path.cmp(&entry2.path));
+    Ok(RepositoryChecksums {
+        repository: format!("{}/{}", repo.org, repo.name),
+        commit: commit_hash.to_owned(),
+        auth_commit: auth_commit_hash.to_owned(),
+        files,
+    })
+}
+
+/// Re-check the repositories of the archive at `archive_path` against `manifest`, and its
+/// signature under `key`, if given.
+///
+/// Paths of files in the outcome are prefixed with the qualified name of their repository.
+///
+/// # Errors
+/// Errors if a repository, or its commit in the manifest, cannot be found in the archive.
+pub fn check(
+    archive_path: &Path,
+    manifest: &Manifest,
+    key: Option<&[u8]>,
+) -> anyhow::Result<Verification> {
+    let mut verification = Verification {
+        digest_valid: manifest.compute_digest() == manifest.digest,
+        signature_valid: key.map(|signing_key| manifest.verify_signature(signing_key)),
+        ..Verification::default()
+    };
+    for repository in &manifest.repositories {
+        let (org, name) = get_name_parts(&repository.repository)?;
+        let repo = Repo::new(archive_path, &org, &name)?;
+        check_repository(&repo, repository, &mut verification)?;
+    }
+    Ok(verification)
+}
+
+/// Re-check the files of `repo` against its `manifest`, recording the outcome in `verification`.
+///
+/// # Errors
+/// Errors if the manifest commit cannot be found in `repo`.
+fn check_repository(
+    repo: &Repo,
+    manifest: &RepositoryChecksums,
+    verification: &mut Verification,
+) -> anyhow::Result<()> {
+    let mut blobs: BTreeMap<String, Oid> = find_commit_blobs(repo, &manifest.commit)?
+        .into_iter()
+        .map(|(path, oid)| (path.to_string_lossy().into_owned(), oid))
+        .collect();
+    let qualified = |path: &str| format!("{}/{path}", manifest.repository);
+    for entry in &manifest.files {
+        let Some(oid) = blobs.remove(&entry.path) else {
+            verification.missing.push(qualified(&entry.path));
+            continue;
+        };
+        let blob = repo.repo.find_blob(oid)?;
+        let sha256 = format!("{:x}", Sha256::digest(blob.content()));
+        if sha256 == entry.sha256 && oid.to_string() == entry.oid {
+            verification.matched += 1;
+        } else {
+            verification.mismatched.push(qualified(&entry.path));
+        }
+    }
+    verification
+        .unexpected
+        .extend(blobs.into_keys().map(|path| qualified(&path)));
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::history::manifest::{build, check, Manifest};
+    use crate::utils::git::Repo;
+    use std::path::Path;
+
+    fn commit_files(archive_dir: &Path, name: &str, files: &[(&str, &[u8])]) -> String {
+        let path = archive_dir.join("test_org").join(name);
+        let repo = git2::Repository::open(&path)
+            .or_else(|_| git2::Repository::init(&path))
+            .unwrap();
+        let mut root = repo.treebuilder(None).unwrap();
+        for &(file_name, content) in files {
+            let blob = repo.blob(content).unwrap();
+            root.insert(file_name, blob, 0o100_644).unwrap();
+        }
+        let tree = repo.find_tree(root.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(None, &signature, &signature, "files", &tree, &[])
+            .unwrap()
+            .to_string()
+    }
+
+    fn manifest_of(archive_dir: &Path, commits: &[(&str, &str)]) -> Manifest {
+        let mut manifest = Manifest {
+            stele: "test_org/law".to_owned(),
+            date: "2023-10-22".to_owned(),
+            generated_at: "2023-10-22T00:00:00+00:00".to_owned(),
+            repositories: commits
+                .iter()
+                .map(|&(name, commit)| {
+                    let repo = Repo::new(archive_dir, "test_org", name).unwrap();
+                    build(&repo, commit, "auth").unwrap()
+                })
+                .collect(),
+            digest: String::new(),
+            signature: None,
+        };
+        manifest.digest = manifest.compute_digest();
+        manifest
+    }
+
+    #[test]
+    fn test_check_when_archive_unchanged_expect_no_problems() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let commit = commit_files(
+            archive_dir.path(),
+            "law-html",
+            &[("index.html", b"<p>law</p>"), ("b.html", b"<p>b</p>")],
+        );
+        let manifest = manifest_of(archive_dir.path(), &[("law-html", &commit)]);
+
+        let cut = check;
+        let actual = cut(archive_dir.path(), &manifest, None).unwrap();
+
+        assert_eq!(actual.matched, 2);
+        assert!(actual.problems().is_empty());
+        let files = &manifest.repositories[0].files;
+        assert_eq!(files[0].path, "b.html");
+        assert_eq!(
+            files[1].sha256,
+            "0f10791dd55630f10b051e53d92772251fd09e25a1f32cad63f607ce2470daff"
+        );
+    }
+
+    #[test]
+    fn test_check_when_pdf_and_xml_repositories_expect_all_files_checked() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let html = commit_files(archive_dir.path(), "law-html", &[("index.html", b"law")]);
+        let pdf = commit_files(archive_dir.path(), "law-pdf", &[("a.pdf", b"%PDF-1.7")]);
+        let xml = commit_files(archive_dir.path(), "law-xml", &[("a.xml", b"<law/>")]);
+        let manifest = manifest_of(
+            archive_dir.path(),
+            &[("law-html", &html), ("law-pdf", &pdf), ("law-xml", &xml)],
+        );
+        commit_files(archive_dir.path(), "law-pdf", &[("a.pdf", b"%PDF-2.0")]);
+        let mut tampered = manifest.clone();
+        tampered.repositories[1].files[0].sha256 = "0".repeat(64);
+
+        let cut = check;
+        let actual = cut(archive_dir.path(), &manifest, None).unwrap();
+        let tampered_actual = cut(archive_dir.path(), &tampered, None).unwrap();
+
+        assert_eq!(actual.matched, 3);
+        assert!(actual.problems().is_empty());
+        assert_eq!(
+            tampered_actual.problems(),
+            vec![
+                "Manifest digest does not match its entries".to_owned(),
+                "Checksum mismatch: test_org/law-pdf/a.pdf".to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_when_manifest_tampered_expect_problems() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let commit = commit_files(
+            archive_dir.path(),
+            "law-html",
+            &[("index.html", b"<p>law</p>")],
+        );
+        let mut manifest = manifest_of(archive_dir.path(), &[("law-html", &commit)]);
+        manifest.repositories[0].files[0].sha256 = "0".repeat(64);
+
+        let cut = check;
+        let actual = cut(archive_dir.path(), &manifest, None).unwrap();
+
+        assert_eq!(actual.matched, 0);
+        assert_eq!(
+            actual.problems(),
+            vec![
+                "Manifest digest does not match its entries".to_owned(),
+                "Checksum mismatch: test_org/law-html/index.html".to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_when_different_commit_expect_missing_and_unexpected_files() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let first = commit_files(archive_dir.path(), "law-xml", &[("a.xml", b"a")]);
+        let second = commit_files(archive_dir.path(), "law-xml", &[("b.xml", b"b")]);
+        let mut manifest = manifest_of(archive_dir.path(), &[("law-xml", &first)]);
+        manifest.repositories[0].commit = second;
+        manifest.digest = manifest.compute_digest();
+
+        let cut = check;
+        let actual = cut(archive_dir.path(), &manifest, None).unwrap();
+
+        assert_eq!(actual.missing, vec!["test_org/law-xml/a.xml".to_owned()]);
+        assert_eq!(actual.unexpected, vec!["test_org/law-xml/b.xml".to_owned()]);
+    }
+
+    #[test]
+    fn test_check_when_signed_expect_signature_verified_with_key_only() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let commit = commit_files(archive_dir.path(), "law-html", &[("index.html", b"law")]);
+        let mut manifest = manifest_of(archive_dir.path(), &[("law-html", &commit)]);
+        manifest.sign(b"secret");
+
+        let cut = check;
+        let signed = cut(archive_dir.path(), &manifest, Some(b"secret")).unwrap();
+        let wrong_key = cut(archive_dir.path(), &manifest, Some(b"other")).unwrap();
+        manifest.signature = None;
+        let unsigned = cut(archive_dir.path(), &manifest, Some(b"secret")).unwrap();
+
+        assert_eq!(signed.signature_valid, Some(true));
+        assert!(signed.problems().is_empty());
+        assert_eq!(
+            wrong_key.problems(),
+            vec!["Manifest signature does not match its digest".to_owned()]
+        );
+        assert_eq!(unsigned.signature_valid, Some(false));
+    }
+}
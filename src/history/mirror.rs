@@ -0,0 +1,476 @@
+//! Mirror a Stelae archive from an upstream Stelae git server.
+//!
+//! The upstream git server (`stelae git`) lists the repositories of its archive at `/_sync`,
+//! together with the commit every branch points to. The mirror fetches each repository
+//! over the git smart HTTP protocol and moves its branches to the listed commits.
+//! Branches are only ever fast-forwarded, so a mirror refuses upstream history rewrites.
+//!
+//! Change data is not copied from the upstream database. Instead, it is inserted into the
+//! mirror's own database from the fetched repositories, exactly as `stelae update` does.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::history::changes;
+use crate::server::errors::CliError;
+use crate::stelae::archive::{Archive, Config};
+use crate::stelae::stele;
+use crate::utils::lock;
+use crate::utils::output::Output;
+use crate::utils::paths::is_archive_component;
+use actix_web::rt::{task, time};
+use anyhow::Context as _;
+use git2::{BranchType, FetchOptions, Oid, Repository};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml_edit::ser;
+
+/// Path of the sync endpoint on the upstream git server.
+pub const SYNC_PATH: &str = "/_sync";
+
+/// Refspec used to fetch all branches of an upstream repository.
+const FETCH_REFSPEC: &str = "+refs/heads/*:refs/remotes/upstream/*";
+
+/// The repositories of an archive, as listed by the sync endpoint.
+#[derive(Deserialize, Serialize)]
+pub struct SyncManifest {
+    /// The root stele of the archive.
+    pub root: stele::Config,
+    /// Every repository of every stele in the archive.
+    pub repositories: Vec<SyncRepository>,
+}
+
+/// One repository of an archive, as listed by the sync endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SyncRepository {
+    /// Organization (directory) of the repository.
+    pub org: String,
+    /// Name of the repository.
+    pub name: String,
+    /// Reference the repository's `HEAD` points to, e.g. `refs/heads/main`.
+    pub head: Option<String>,
+    /// Map of branch reference names to the commit hash they point to.
+    pub branches: BTreeMap<String, String>,
+}
+
+/// Upstream Stelae git server an archive is mirrored from.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    /// Token of the `[mirror]` configuration of the upstream archive.
+    pub token: String,
+    /// Url of the server, without a trailing slash, e.g. `https://git.law.example.gov`.
+    pub url: String,
+}
+
+impl Upstream {
+    /// Upstream at `url`, authenticated with the token in the file `token_file`.
+    ///
+    /// # Errors
+    /// Errors if the token file cannot be read or is empty.
+    pub fn new(url: &str, token_file: &Path) -> anyhow::Result<Self> {
+        let token = read_to_string(token_file)
+            .with_context(|| format!("could not read the mirror token {}", token_file.display()))?
+            .trim()
+            .to_owned();
+        if token.is_empty() {
+            anyhow::bail!("the mirror token {} is empty", token_file.display());
+        }
+        Ok(Self {
+            token,
+            url: url.trim_end_matches('/').to_owned(),
+        })
+    }
+}
+
+/// Mirror the archive served by the `upstream` Stelae git server.
+///
+/// Keeps mirroring every `interval` seconds when an interval is given.
+/// Errors of a single round are logged, and the next round is attempted.
+/// The repositories are fetched on a blocking thread, so other tasks of the runtime keep running.
+/// Every round holds the [`lock`] of the archive, waiting for it if `wait` is set.
+///
+/// # Errors
+/// Errors if the mirror round fails and no interval is given.
+pub async fn mirror(
+    raw_archive_path: &str,
+    archive_path: &Path,
+    upstream: &Upstream,
+    interval: Option<u64>,
+    wait: bool,
+) -> Result<(), CliError> {
+    loop {
+        tracing::info!("Mirroring archive from '{}'", upstream.url);
+        let (path, from) = (archive_path.to_path_buf(), upstream.clone());
+        let synced = task::spawn_blocking(move || {
+            let locked = lock::Guard::acquire(&path, "mirror", wait)?;
+            sync(&path, &from).map(|()| locked)
+        })
+        .await
+        .unwrap_or_else(|err| Err(err.into()));
+        let result = match synced {
+            Ok(_locked) => {
+                changes::insert(
+                    raw_archive_path,
+                    archive_path.to_path_buf(),
+                    false,
+                    false,
+                    Output::Text,
+                )
+                .await
+            }
+            Err(err) => {
+                tracing::error!("Unable to mirror archive from '{}'.", upstream.url);
+                tracing::error!("Error: {err:?}");
+                Err(CliError::GenericError)
+            }
+        };
+        let Some(seconds) = interval else {
+            return result;
+        };
+        time::sleep(Duration::from_secs(seconds)).await;
+    }
+}
+
+/// Fetch every repository listed by the sync endpoint of the `upstream` into the archive.
+///
+/// # Errors
+/// Errors if the sync endpoint cannot be read, or if any repository cannot be fetched.
+pub fn sync(archive_path: &Path, upstream: &Upstream) -> anyhow::Result<()> {
+    let authorization = format!("Bearer {}", upstream.token);
+    let manifest: SyncManifest = ureq::get(&format!("{}{SYNC_PATH}", upstream.url))
+        .set("Authorization", &authorization)
+        .call()
+        .context("could not request the upstream sync endpoint")?
+        .into_json()
+        .context("could not parse the upstream sync endpoint response")?;
+    validate_manifest(&manifest)?;
+    write_config(archive_path, manifest.root)?;
+    for repository in &manifest.repositories {
+        let path = archive_path.join(&repository.org).join(&repository.name);
+        let url = format!(
+            "{upstream}/{org}/{name}",
+            upstream = upstream.url,
+            org = repository.org,
+            name = repository.name
+        );
+        tracing::info!("Fetching {}/{}", repository.org, repository.name);
+        fetch_repository(&path, &url, &authorization, repository)
+            .with_context(|| format!("could not fetch {}/{}", repository.org, repository.name))?;
+    }
+    Ok(())
+}
+
+/// Check that every organization and name listed in the `manifest` is inside of the archive.
+///
+/// Each must be a single path component, see [`is_archive_component`], so an upstream cannot
+/// create or fetch repositories outside of the mirrored archive.
+///
+/// # Errors
+/// Errors if the root stele or any repository has an organization or name that is not.
+pub fn validate_manifest(manifest: &SyncManifest) -> anyhow::Result<()> {
+    let names = iter::once((&manifest.root.org, &manifest.root.name)).chain(
+        manifest
+            .repositories
+            .iter()
+            .map(|repository| (&repository.org, &repository.name)),
+    );
+    for (org, name) in names {
+        if !is_archive_component(org) || !is_archive_component(name) {
+            anyhow::bail!("refusing to mirror '{org}/{name}', which is outside of the archive");
+        }
+    }
+    Ok(())
+}
+
+/// Fetch a repository from `url`, sending the `authorization` header, and move its branches to
+/// the commits listed for it.
+///
+/// A bare repository is created at `path` if no repository exists yet.
+///
+/// # Errors
+/// Errors if the fetch fails, if a listed commit was not fetched, or if moving a branch
+/// to the listed commit would not be a fast-forward.
+pub fn fetch_repository(
+    path: &Path,
+    url: &str,
+    authorization: &str,
+    repository: &SyncRepository,
+) -> anyhow::Result<()> {
+    let repo = Repository::open(path).or_else(|_| Repository::init_bare(path))?;
+    let header = format!("Authorization: {authorization}");
+    let mut options = FetchOptions::new();
+    options.custom_headers(&[&header]);
+    repo.remote_anonymous(url)?
+        .fetch(&[FETCH_REFSPEC], Some(&mut options), None)?;
+    for (branch, commit) in &repository.branches {
+        if !branch.starts_with("refs/heads/") {
+            anyhow::bail!("refusing to update '{branch}', which is not a branch");
+        }
+        let oid = Oid::from_str(commit)?;
+        repo.find_commit(oid)
+            .with_context(|| format!("commit {oid} of '{branch}' was not fetched"))?;
+        let current = repo
+            .find_reference(branch)
+            .ok()
+            .and_then(|reference| reference.target());
+        if let Some(current_oid) = current {
+            if current_oid != oid && !repo.graph_descendant_of(oid, current_oid)? {
+                anyhow::bail!(
+                    "refusing to update '{branch}' from {current_oid} to {oid}, which is not a fast-forward"
+                );
+            }
+        }
+        repo.reference(branch, oid, true, "stelae mirror")?;
+    }
+    if let Some(head) = repository.head.as_deref() {
+        repo.set_head(head)?;
+    }
+    Ok(())
+}
+
+/// List the repositories of the archive for the sync endpoint.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed, or if any of its repositories cannot be opened.
+pub fn build_manifest(archive_path: &Path) -> anyhow::Result<SyncManifest> {
+    let archive = Archive::parse(archive_path.to_path_buf(), archive_path, false)?;
+    let root = archive.get_config()?.root;
+    let mut names = BTreeMap::new();
+    for (_, stele) in archive.get_stelae() {
+        names.insert(
+            stele.get_qualified_name(),
+            (stele.auth_repo.org.clone(), stele.auth_repo.name.clone()),
+        );
+        for repository in stele
+            .repositories
+            .iter()
+            .flat_map(|repositories| repositories.repositories.values())
+        {
+            names.insert(
+                repository.name.clone(),
+                (repository.get_org(), repository.get_name()),
+            );
+        }
+    }
+    let mut repositories = vec![];
+    for (org, name) in names.into_values() {
+        let path: PathBuf = archive_path.join(&org).join(&name);
+        if !path.exists() {
+            continue;
+        }
+        let repo = Repository::open(&path)?;
+        repositories.push(list_repository(&repo, org, name)?);
+    }
+    Ok(SyncManifest { root, repositories })
+}
+
+/// List the `HEAD` and branches of a repository.
+fn list_repository(repo: &Repository, org: String, name: String) -> anyhow::Result<SyncRepository> {
+    let head = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|reference| reference.symbolic_target().map(ToOwned::to_owned));
+    let mut branches = BTreeMap::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let reference = branch?.0.into_reference();
+        if let (Some(branch_name), Some(oid)) = (reference.name(), reference.target()) {
+            branches.insert(branch_name.to_owned(), oid.to_string());
+        }
+    }
+    Ok(SyncRepository {
+        org,
+        name,
+        head,
+        branches,
+    })
+}
+
+/// Point the archive's config at the upstream root stele, keeping any other local settings.
+fn write_config(archive_path: &Path, root: stele::Config) -> anyhow::Result<()> {
+    let config_path = archive_path.join(".taf/config.toml");
+    let conf = match read_to_string(&config_path) {
+        Ok(config_str) => Config {
+            root,
+            ..toml::from_str(&config_str)?
+        },
+        Err(_) => Config {
+            root,
+            shallow: false,
+            headers: None,
+            security_headers: None,
+            limits: None,
+            concurrency: None,
+            timeouts: None,
+            locales: None,
+            auth: None,
+            access_log: None,
+            proxy: None,
+            document_views: None,
+            watermarks: None,
+            structured_data: None,
+            ingest: None,
+            webhooks: None,
+            digest: None,
+            disk_usage: None,
+            mirror: None,
+            warmup: None,
+            database: None,
+            aliases: None,
+            preview: None,
+            approval: None,
+            current_index: None,
+            server_timing: None,
+        },
+    };
+    write(config_path, ser::to_string_pretty(&conf)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::mirror::*;
+    use git2::Signature;
+
+    fn commit_file(repo: &Repository, content: &str, parent: Option<Oid>) -> Oid {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        let mut tree_builder = repo.treebuilder(None).unwrap();
+        tree_builder.insert("index.html", blob, 0o100_644).unwrap();
+        let tree = repo.find_tree(tree_builder.write().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let parents: Vec<_> = parent
+            .map(|oid| repo.find_commit(oid).unwrap())
+            .into_iter()
+            .collect();
+        let parent_refs: Vec<_> = parents.iter().collect();
+        repo.commit(None, &signature, &signature, content, &tree, &parent_refs)
+            .unwrap()
+    }
+
+    fn sync_repository(branches: &[(&str, Oid)]) -> SyncRepository {
+        SyncRepository {
+            org: "test_org".to_owned(),
+            name: "law-html".to_owned(),
+            head: Some("refs/heads/main".to_owned()),
+            branches: branches
+                .iter()
+                .map(|&(branch, oid)| (branch.to_owned(), oid.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_fetch_repository_when_new_commits_expect_branch_fast_forwarded() {
+        let upstream_dir = tempfile::tempdir().unwrap();
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let upstream = Repository::init(upstream_dir.path()).unwrap();
+        let first = commit_file(&upstream, "first", None);
+        upstream
+            .reference("refs/heads/main", first, true, "")
+            .unwrap();
+        let url = upstream_dir.path().to_string_lossy();
+        let mirror_path = mirror_dir.path().join("test_org/law-html");
+        fetch_repository(
+            &mirror_path,
+            &url,
+            "Bearer token",
+            &sync_repository(&[("refs/heads/main", first)]),
+        )
+        .unwrap();
+
+        let second = commit_file(&upstream, "second", Some(first));
+        upstream
+            .reference("refs/heads/main", second, true, "")
+            .unwrap();
+        let cut = fetch_repository;
+        cut(
+            &mirror_path,
+            &url,
+            "Bearer token",
+            &sync_repository(&[("refs/heads/main", second)]),
+        )
+        .unwrap();
+
+        let mirror = Repository::open(&mirror_path).unwrap();
+        let actual = mirror.head().unwrap().target().unwrap();
+        let expected = second;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_fetch_repository_when_history_rewritten_expect_error() {
+        let upstream_dir = tempfile::tempdir().unwrap();
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let upstream = Repository::init(upstream_dir.path()).unwrap();
+        let first = commit_file(&upstream, "first", None);
+        upstream
+            .reference("refs/heads/main", first, true, "")
+            .unwrap();
+        let url = upstream_dir.path().to_string_lossy();
+        let mirror_path = mirror_dir.path().join("test_org/law-html");
+        fetch_repository(
+            &mirror_path,
+            &url,
+            "Bearer token",
+            &sync_repository(&[("refs/heads/main", first)]),
+        )
+        .unwrap();
+
+        let rewritten = commit_file(&upstream, "rewritten", None);
+        upstream
+            .reference("refs/heads/main", rewritten, true, "")
+            .unwrap();
+        let cut = fetch_repository;
+        let actual = cut(
+            &mirror_path,
+            &url,
+            "Bearer token",
+            &sync_repository(&[("refs/heads/main", rewritten)]),
+        )
+        .unwrap_err();
+
+        assert!(actual.to_string().contains("not a fast-forward"));
+        let mirror = Repository::open(&mirror_path).unwrap();
+        let actual_head = mirror.head().unwrap().target().unwrap();
+        assert_eq!(actual_head, first);
+    }
+
+    #[test]
+    fn test_validate_manifest_when_outside_of_archive_expect_error() {
+        let cut = validate_manifest;
+        let root = |name: &str| stele::Config {
+            org: "test_org".to_owned(),
+            name: name.to_owned(),
+            hash: None,
+        };
+        let inside = SyncManifest {
+            root: root("law"),
+            repositories: vec![sync_repository(&[])],
+        };
+        cut(&inside).unwrap();
+
+        let traversal = SyncRepository {
+            org: "../outside".to_owned(),
+            ..sync_repository(&[])
+        };
+        let outside_repository = SyncManifest {
+            root: root("law"),
+            repositories: vec![sync_repository(&[]), traversal],
+        };
+        let actual = cut(&outside_repository).unwrap_err();
+        assert!(
+            actual.to_string().contains("../outside/law-html"),
+            "{actual}"
+        );
+
+        let outside_root = SyncManifest {
+            root: root("/etc"),
+            repositories: vec![],
+        };
+        assert!(cut(&outside_root).is_err());
+    }
+}
@@ -1,5 +1,35 @@
 //! The history module contains tools for interacting with the history of the Stele.
+// The backup module contains logic for backing up and restoring the database and configuration.
+pub mod backup;
 // The changes module contains logic for inserting change objects into the database.
 pub mod changes;
+// The diff module contains logic for comparing two publications of a stele.
+pub mod diff;
+// The doctor module contains logic for diagnosing the environment of an archive.
+pub mod doctor;
+// The digest module contains logic for sending a digest of the documents changed by an update.
+pub mod digest;
+// The disk_usage module contains logic for recording the sizes on disk of the repositories of an archive.
+pub mod disk_usage;
+// The export module contains logic for exporting change objects from the database.
+pub mod export;
+// The links module contains logic for checking the internal links of served html documents.
+pub mod links;
+// The manifest module contains logic for creating and verifying checksum manifests of a publication.
+pub mod manifest;
+// The mirror module contains logic for mirroring an archive from an upstream Stelae git server.
+pub mod mirror;
+// The plugins module contains the extension points of the ingestion of changes.
+pub mod plugins;
 // The rdf module contains helper functions that work with loading, parsing and querying the RDF graph using `sophia`.
 pub mod rdf;
+// The references module contains logic for extracting the citations between served html documents.
+pub mod references;
+// The stats module contains logic for reporting statistics of the stelae in an archive.
+pub mod stats;
+// The status module contains logic for reporting the drift of the database and a running server from an archive.
+pub mod status;
+// The views module contains logic for counting the views of current documents and writing them to the database.
+pub mod views;
+// The webhooks module contains logic for notifying webhooks of ingested publications.
+pub mod webhooks;
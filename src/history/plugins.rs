@@ -0,0 +1,175 @@
+//! Plugins extending what `stelae update` extracts from an archive.
+//!
+//! Plugins are registered by name in a [`Registry`], and the plugins run for a stele are chosen
+//! by name in the `[ingest]` table of `.taf/config.toml`. A fresh set of plugins is created for
+//! every stele, so plugins can collect state across the hooks of one stele.
+use crate::db::models::publication::Publication;
+use crate::db::{DatabaseConnection, DatabaseTransaction};
+use crate::history::references::Citations;
+use crate::utils::git::Repo;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// A served document of the current commit of an html data repository.
+#[derive(Clone, Copy)]
+pub struct Document<'doc> {
+    /// The html data repository the document is found in.
+    pub repo: &'doc Repo,
+    /// Hash of the current commit of the data repository.
+    pub commit_hash: &'doc str,
+    /// Path of the document, relative to the data repository, e.g. `a/b/c/index.html`.
+    pub path: &'doc str,
+    /// Content of the document.
+    pub content: &'doc [u8],
+}
+
+/// Hooks invoked by `stelae update` while inserting the changes of a stele.
+///
+/// Every hook defaults to doing nothing. Errors of the hooks run in the transaction of the stele
+/// roll back the update of the stele.
+#[async_trait(?Send)]
+pub trait IngestPlugin {
+    /// Invoked after the documents of the stele are processed, in the transaction of the stele,
+    /// so what the plugin collected is committed together with the changes of the stele.
+    ///
+    /// # Errors
+    /// Errors if the plugin cannot complete.
+    async fn before_commit(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked for every publication inserted into the database, in the transaction of the stele.
+    ///
+    /// # Errors
+    /// Errors if the publication cannot be processed.
+    async fn on_publication(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        _publication: &Publication,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked for every html document of the current commit of the html data repositories of
+    /// the stele, in the transaction of the stele.
+    ///
+    /// # Errors
+    /// Errors if the document cannot be processed.
+    async fn on_document(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        _document: Document<'_>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked after the changes of the stele are committed to the database.
+    ///
+    /// # Errors
+    /// Errors if the plugin cannot complete. The committed changes are kept.
+    async fn after_commit(
+        &mut self,
+        _conn: &DatabaseConnection,
+        _stele: &str,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates a fresh instance of a plugin.
+pub type Factory = fn() -> Box<dyn IngestPlugin>;
+
+/// Plugins available to `stelae update`, by the name they are configured with.
+#[derive(Clone)]
+pub struct Registry {
+    /// Factories of the plugins, keyed by name.
+    factories: HashMap<String, Factory>,
+}
+
+impl Default for Registry {
+    /// A registry of the built-in plugins: `references`.
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        registry.register("references", || Box::new(Citations::default()));
+        registry
+    }
+}
+
+impl Registry {
+    /// Register the plugin created by `factory` under `name`, replacing any plugin of that name.
+    pub fn register(&mut self, name: &str, factory: Factory) {
+        self.factories.insert(name.to_owned(), factory);
+    }
+
+    /// Create the plugins named `names`, in order.
+    ///
+    /// # Errors
+    /// Errors if no plugin is registered under one of the names.
+    pub fn create(&self, names: &[String]) -> anyhow::Result<Vec<Box<dyn IngestPlugin>>> {
+        names
+            .iter()
+            .map(|name| {
+                self.factories
+                    .get(name)
+                    .map(|factory| factory())
+                    .ok_or_else(|| anyhow::anyhow!("Unknown ingest plugin {name}"))
+            })
+            .collect()
+    }
+
+    /// Check that a plugin is registered under every name of `names`.
+    ///
+    /// # Errors
+    /// Errors with the names no plugin is registered under.
+    pub fn validate(&self, names: &[String]) -> anyhow::Result<()> {
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|name| !self.factories.contains_key(name.as_str()))
+            .map(String::as_str)
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        anyhow::bail!("Unknown ingest plugin(s): {}", unknown.join(", "))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::plugins::{IngestPlugin, Registry};
+
+    struct Noop;
+
+    impl IngestPlugin for Noop {}
+
+    #[test]
+    fn test_create_when_registered_expect_plugins_and_unknown_error() {
+        let mut cut = Registry::default();
+        cut.register("noop", || Box::new(Noop));
+        let names = vec!["references".to_owned(), "noop".to_owned()];
+        assert_eq!(cut.create(&names).unwrap().len(), 2);
+        assert!(cut.create(&["search".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_validate_when_unknown_names_expect_error_listing_them() {
+        let cut = Registry::default();
+        cut.validate(&["references".to_owned()]).unwrap();
+        let names = [
+            "search".to_owned(),
+            "references".to_owned(),
+            "index".to_owned(),
+        ];
+        let actual = cut.validate(&names).unwrap_err().to_string();
+        assert_eq!(actual, "Unknown ingest plugin(s): search, index");
+    }
+}
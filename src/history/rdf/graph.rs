@@ -11,12 +11,16 @@
     reason = "Bypass sophia internal & ref match on SimpleTerm"
 )]
 /// The helper methods for working with RDF in Stelae.
+use super::serialization::RdfFormat;
 use anyhow::Context as _;
 use sophia::api::graph::{GTripleSource, Graph as _};
 use sophia::api::ns::NsTerm;
 use sophia::api::MownStr;
 use sophia::api::{prelude::*, term::SimpleTerm};
 use sophia::inmem::graph::FastGraph;
+use sophia::turtle::parser::{nt, turtle};
+use sophia::xml::parser as xml;
+use std::io::BufReader;
 use std::iter;
 /// Stelae representation of an RDF graph.
 pub struct StelaeGraph {
@@ -38,6 +42,28 @@ impl StelaeGraph {
             fast_graph: FastGraph::new(),
         }
     }
+    /// Parse `data` serialized in `format` and add the resulting triples to the graph.
+    ///
+    /// `data` is parsed into a graph of its own first, so the graph is left unchanged if `data`
+    /// is malformed.
+    ///
+    /// # Errors
+    /// Errors if `data` is not valid for the given `format`.
+    pub fn add_from_bytes(&mut self, data: &[u8], format: RdfFormat) -> anyhow::Result<()> {
+        let mut parsed = FastGraph::new();
+        parse_into(&mut parsed, data, format)?;
+        self.fast_graph.insert_all(parsed.triples())?;
+        Ok(())
+    }
+
+    /// Number of triples of `data` serialized in `format` that parse before its first error.
+    #[must_use]
+    pub fn count_parsed_triples(data: &[u8], format: RdfFormat) -> usize {
+        let mut partial = FastGraph::new();
+        let _partial = parse_into(&mut partial, data, format);
+        partial.triples().count()
+    }
+
     /// Extract a literal from a triple matching.
     ///
     /// # Errors
@@ -217,3 +243,23 @@ impl Bag<'_> {
         Ok(items)
     }
 }
+
+/// Parse `data` serialized in `format` into `graph`, keeping the triples parsed before an error.
+///
+/// # Errors
+/// Errors if `data` is not valid for the given `format`.
+fn parse_into(graph: &mut FastGraph, data: &[u8], format: RdfFormat) -> anyhow::Result<()> {
+    let reader = BufReader::new(data);
+    match format {
+        RdfFormat::RdfXml => {
+            xml::parse_bufread(reader).add_to_graph(graph)?;
+        }
+        RdfFormat::Turtle => {
+            turtle::parse_bufread(reader).add_to_graph(graph)?;
+        }
+        RdfFormat::NTriples => {
+            nt::parse_bufread(reader).add_to_graph(graph)?;
+        }
+    }
+    Ok(())
+}
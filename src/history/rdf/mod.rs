@@ -3,5 +3,8 @@
 /// RDF namespaces for the Stele ontology.
 pub mod namespaces;
 
+/// Serialization formats of RDF files in a publication.
+pub mod serialization;
+
 /// The graph module contains the `Graph` struct which is used to interact with the RDF graph.
 pub mod graph;
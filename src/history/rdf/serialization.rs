@@ -0,0 +1,90 @@
+//! Serialization formats of the RDF files found in a publication.
+use std::path::Path;
+
+/// Serialization format of an RDF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdfFormat {
+    /// RDF/XML, stored in `.rdf` files.
+    RdfXml,
+    /// Turtle, stored in `.ttl` files.
+    Turtle,
+    /// N-Triples, stored in `.nt` files.
+    NTriples,
+}
+
+impl RdfFormat {
+    /// All supported formats, in order of preference when looking up a publication index file.
+    pub const ALL: [Self; 3] = [Self::RdfXml, Self::Turtle, Self::NTriples];
+
+    /// Determine the format of an RDF file from the extension of `path`.
+    ///
+    /// Returns `None` if the file is not an RDF file.
+    #[must_use]
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    /// File extension used by the format.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::RdfXml => "rdf",
+            Self::Turtle => "ttl",
+            Self::NTriples => "nt",
+        }
+    }
+
+    /// Name of the publication index file in this format, e.g. `index.ttl`.
+    #[must_use]
+    pub fn index_file_name(self) -> String {
+        format!("index.{}", self.extension())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::history::rdf::serialization::RdfFormat;
+
+    #[test]
+    fn test_from_path_when_rdf_ext_expect_rdf_xml() {
+        let cut = RdfFormat::from_path;
+        let actual = cut("a/b/index.rdf");
+        let expected = Some(RdfFormat::RdfXml);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_from_path_when_ttl_ext_expect_turtle() {
+        let cut = RdfFormat::from_path;
+        let actual = cut("a/b/index.ttl");
+        let expected = Some(RdfFormat::Turtle);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_from_path_when_nt_ext_expect_n_triples() {
+        let cut = RdfFormat::from_path;
+        let actual = cut("a/b/index.nt");
+        let expected = Some(RdfFormat::NTriples);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_from_path_when_non_rdf_ext_expect_none() {
+        let cut = RdfFormat::from_path;
+        let actual = cut("a/b/index.html");
+        let expected = None;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_from_path_when_rdf_in_name_but_not_ext_expect_none() {
+        let cut = RdfFormat::from_path;
+        let actual = cut("a/b/index.rdf.bak");
+        let expected = None;
+        assert_eq!(expected, actual);
+    }
+}
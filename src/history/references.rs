@@ -0,0 +1,169 @@
+//! Extract the citations between the served html documents of a stele.
+//!
+//! The html documents of the current commit of the html data repository are parsed for links.
+//! Links to other documents of the stele that resolve at the same commit are recorded in the
+//! `references` table in the transaction of the stele, replacing its previous references.
+use crate::db::models::reference::{self, Reference};
+use crate::db::DatabaseTransaction;
+use crate::history::export::find_commit_blobs;
+use crate::history::links::resolve_href;
+use crate::history::plugins::{Document, IngestPlugin};
+use crate::server::api::formats::document_stem;
+use crate::utils::git::Repo;
+use crate::utils::html::find_link_hrefs;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap};
+use std::mem;
+
+/// Ingest plugin recording the citations between the current html documents of a stele.
+///
+/// Registered as the built-in `references` plugin.
+#[derive(Debug, Default)]
+pub struct Citations {
+    /// Citations found so far, as citing document, cited document and link.
+    found: BTreeSet<(String, String, String)>,
+    /// Whether each looked up repository path resolves, keyed by commit and path.
+    resolved: HashMap<(String, String), bool>,
+}
+
+#[async_trait(?Send)]
+#[expect(
+    clippy::missing_trait_methods,
+    reason = "Citations are only found in documents"
+)]
+impl IngestPlugin for Citations {
+    async fn on_document(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        document: Document<'_>,
+    ) -> anyhow::Result<()> {
+        let citations = find_document_citations(&mut self.resolved, document)?;
+        self.found.extend(citations);
+        Ok(())
+    }
+
+    async fn before_commit(
+        &mut self,
+        tx: &mut DatabaseTransaction,
+        stele: &str,
+    ) -> anyhow::Result<()> {
+        let references: Vec<Reference> = mem::take(&mut self.found)
+            .into_iter()
+            .map(|(source_path, target_path, href)| Reference {
+                stele: stele.to_owned(),
+                source_path,
+                target_path,
+                href,
+            })
+            .collect();
+        tracing::info!(
+            "[{stele}] | Recorded {} reference(s) between documents in `references`",
+            references.len()
+        );
+        reference::TxManager::replace_all_by_stele(tx, stele, references).await
+    }
+}
+
+/// Find the links between the html documents at `commit_hash`.
+///
+/// Returns the paths of the citing and the cited document, without file extension or index
+/// file, and the link, for every link to another document that resolves.
+///
+/// # Errors
+/// Errors if the commit or its html documents cannot be read.
+pub fn find_citations(
+    repo: &Repo,
+    commit_hash: &str,
+) -> anyhow::Result<BTreeSet<(String, String, String)>> {
+    let mut resolved = HashMap::new();
+    let mut citations = BTreeSet::new();
+    for (path, oid) in find_commit_blobs(repo, commit_hash)? {
+        let is_html = path
+            .extension()
+            .is_some_and(|ext| ext == "html" || ext == "htm");
+        if !is_html {
+            continue;
+        }
+        let source_file = path.to_string_lossy().into_owned();
+        let blob = repo.repo.find_blob(oid)?;
+        let document = Document {
+            repo,
+            commit_hash,
+            path: &source_file,
+            content: blob.content(),
+        };
+        citations.extend(find_document_citations(&mut resolved, document)?);
+    }
+    Ok(citations)
+}
+
+/// Find the links of the html `document` to other documents that resolve at its commit.
+///
+/// Whether repository paths resolve is cached in `resolved`, keyed by commit and path.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+fn find_document_citations(
+    resolved: &mut HashMap<(String, String), bool>,
+    document: Document<'_>,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    let source_path = document_stem(document.path);
+    let mut citations = vec![];
+    for href in find_link_hrefs(document.content)? {
+        let Some(target) = resolve_href(document.path, &href) else {
+            continue;
+        };
+        let target_path = document_stem(&target).to_owned();
+        let exists = *resolved
+            .entry((document.commit_hash.to_owned(), target))
+            .or_insert_with_key(|key| document.repo.get_bytes_at_path(&key.0, &key.1).is_ok());
+        if exists && target_path != source_path {
+            citations.push((source_path.to_owned(), target_path, href));
+        }
+    }
+    Ok(citations)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::references::find_citations;
+    use crate::utils::git::Repo;
+    use git2::{Repository, Signature};
+
+    #[test]
+    fn test_find_citations_when_links_resolve_expect_cited_documents() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let repository = Repository::init(archive_dir.path().join("test_org/law-html")).unwrap();
+        let index = repository
+            .blob(br##"<a href="/a/">a</a><a href="/missing/">m</a><a href="#top">t</a>"##)
+            .unwrap();
+        let nested = repository
+            .blob(br#"<a href="../">up</a><a href="https://example.com/">e</a>"#)
+            .unwrap();
+        let mut a_builder = repository.treebuilder(None).unwrap();
+        a_builder.insert("index.html", nested, 0o100_644).unwrap();
+        let a_tree = a_builder.write().unwrap();
+        let mut root_builder = repository.treebuilder(None).unwrap();
+        root_builder.insert("index.html", index, 0o100_644).unwrap();
+        root_builder.insert("a", a_tree, 0o040_000).unwrap();
+        let tree = repository.find_tree(root_builder.write().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let commit = repository
+            .commit(Some("HEAD"), &signature, &signature, "v1", &tree, &[])
+            .unwrap();
+        let repo = Repo::new(archive_dir.path(), "test_org", "law-html").unwrap();
+
+        let cut = find_citations;
+        let actual: Vec<_> = cut(&repo, &commit.to_string())
+            .unwrap()
+            .into_iter()
+            .collect();
+        let expected = vec![
+            ("".to_owned(), "a".to_owned(), "/a/".to_owned()),
+            ("a".to_owned(), "".to_owned(), "../".to_owned()),
+        ];
+        assert_eq!(actual, expected);
+    }
+}
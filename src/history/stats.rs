@@ -0,0 +1,270 @@
+//! Module for reporting statistics of the stelae in an archive
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{
+    self,
+    models::{repository_size, stele_stats},
+    Databases,
+};
+use crate::server::errors::CliError;
+use crate::stelae::archive::Archive;
+use crate::stelae::stele::Stele;
+use crate::utils::output::{write_json, Output};
+use actix_web::rt::task;
+use serde::Serialize;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Statistics of a stele.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+    pub stele: String,
+    /// Counts and dates of the stele, read from the database.
+    #[serde(flatten)]
+    pub counts: stele_stats::SteleStats,
+    /// Sizes on disk of the repositories of the stele, authentication repository first.
+    pub repositories: Vec<RepositorySize>,
+}
+
+/// Size on disk of a repository.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RepositorySize {
+    /// Qualified name of the repository.
+    pub repository: String,
+    /// Size of all files of the repository, in bytes. `None` if the repository is not cloned.
+    pub bytes: Option<u64>,
+    /// Size last recorded by `stelae disk-usage`, in bytes, if any.
+    pub recorded_bytes: Option<i64>,
+    /// Time the size was last recorded at, if any.
+    pub recorded_at: Option<String>,
+}
+
+/// Collect the statistics of every stele in the `archive`, sorted by stele name.
+///
+/// # Errors
+/// Errors if the statistics cannot be read from the databases, or the repositories cannot be
+/// measured.
+pub async fn collect(archive: &Archive, db: &Databases) -> anyhow::Result<Vec<Stats>> {
+    let mut stelae: Vec<&Stele> = archive.stelae.values().collect();
+    stelae.sort_by_key(|stele| stele.get_qualified_name());
+    let mut stats = vec![];
+    for stele in stelae {
+        let name = stele.get_qualified_name();
+        let counts = stele_stats::Manager::find_by_stele(db.for_stele(&name), &name).await?;
+        let recorded =
+            repository_size::Manager::find_all_latest_by_stele(db.shared(), &name).await?;
+        // Walking the repositories blocks, so it is kept off the workers of the server.
+        let paths = repository_paths(&archive.path, stele);
+        let mut repositories = task::spawn_blocking(move || measure(paths)).await?;
+        for repository in &mut repositories {
+            if let Some(found) = recorded
+                .iter()
+                .find(|size| size.repository == repository.repository)
+            {
+                repository.recorded_bytes = Some(found.bytes);
+                repository.recorded_at = Some(found.recorded_at.clone());
+            }
+        }
+        stats.push(Stats {
+            stele: name,
+            counts,
+            repositories,
+        });
+    }
+    Ok(stats)
+}
+
+/// Sizes on disk of the authentication and data repositories of the `stele`.
+#[must_use]
+pub fn repository_sizes(archive_path: &Path, stele: &Stele) -> Vec<RepositorySize> {
+    measure(repository_paths(archive_path, stele))
+}
+
+/// Qualified names and paths of the authentication and data repositories of the `stele`.
+fn repository_paths(archive_path: &Path, stele: &Stele) -> Vec<(String, PathBuf)> {
+    let auth_name = stele.get_qualified_name();
+    let mut paths = vec![(auth_name.clone(), stele.auth_repo.path.clone())];
+    paths.extend(
+        stele
+            .repositories
+            .iter()
+            .flat_map(|repositories| repositories.get_sorted())
+            .filter(|repository| repository.name != auth_name)
+            .map(|repository| {
+                let path = archive_path
+                    .join(repository.get_org())
+                    .join(repository.get_name());
+                (repository.name.clone(), path)
+            }),
+    );
+    paths
+}
+
+/// Sizes on disk of the repositories at `paths`, keyed by qualified name.
+fn measure(paths: Vec<(String, PathBuf)>) -> Vec<RepositorySize> {
+    paths
+        .into_iter()
+        .map(|(repository, path)| RepositorySize {
+            repository,
+            bytes: disk_usage(&path).ok(),
+            recorded_bytes: None,
+            recorded_at: None,
+        })
+        .collect()
+}
+
+/// Size of all files under the directory at `path`, in bytes.
+///
+/// # Errors
+/// Errors if the directory, or one of its subdirectories, cannot be read.
+pub fn disk_usage(path: &Path) -> io::Result<u64> {
+    let mut total: u64 = 0;
+    for entry in fs::read_dir(path)? {
+        let found = entry?;
+        let file_type = found.file_type()?;
+        let size = if file_type.is_dir() {
+            disk_usage(&found.path())?
+        } else {
+            found.metadata()?.len()
+        };
+        total = total.saturating_add(size);
+    }
+    Ok(total)
+}
+
+/// Render the statistics of the stelae as a plain text report.
+#[must_use]
+pub fn render(stats: &[Stats]) -> String {
+    let mut report = String::new();
+    let _infallible = write_stats(&mut report, stats);
+    report
+}
+
+/// Write the plain text report of the statistics to `out`.
+fn write_stats(out: &mut String, stats: &[Stats]) -> fmt::Result {
+    for stele in stats {
+        let counts = &stele.counts;
+        let or_none = |value: Option<&str>| value.unwrap_or("-").to_owned();
+        writeln!(out, "{}", stele.stele)?;
+        writeln!(out, "  documents:     {}", counts.documents)?;
+        writeln!(out, "  collections:   {}", counts.collections)?;
+        writeln!(out, "  publications:  {}", counts.publications)?;
+        writeln!(out, "  versions:      {}", counts.versions)?;
+        writeln!(
+            out,
+            "  codified:      {} to {}",
+            or_none(counts.earliest_codified_date.as_deref()),
+            or_none(counts.latest_codified_date.as_deref())
+        )?;
+        writeln!(
+            out,
+            "  auth commits:  {} to {}",
+            or_none(counts.first_auth_commit_at.as_deref()),
+            or_none(counts.last_auth_commit_at.as_deref())
+        )?;
+        writeln!(out, "  repositories:")?;
+        for repository in &stele.repositories {
+            let size = repository
+                .bytes
+                .map_or_else(|| "not cloned".to_owned(), |bytes| format!("{bytes} bytes"));
+            writeln!(out, "    {}: {size}", repository.repository)?;
+        }
+    }
+    Ok(())
+}
+
+/// Report the statistics of every stele in the archive to stdout, as plain text or, with `output`
+/// JSON, as JSON.
+///
+/// # Errors
+/// Errors if the archive cannot be parsed or the database cannot be reached.
+#[tracing::instrument(name = "Stelae stats", skip(raw_archive_path, archive_path))]
+pub async fn report(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    output: Output,
+) -> Result<(), CliError> {
+    let archive = Archive::parse(
+        archive_path.clone(),
+        &PathBuf::from(raw_archive_path),
+        false,
+    )
+    .map_err(|err| {
+        tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+        tracing::error!("Error: {err:?}");
+        CliError::ArchiveParseError
+    })?;
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let result = async {
+        let db = db::init::connect_stelae(&archive.path, archive.stelae.keys(), conn).await?;
+        let stats = collect(&archive, &db).await?;
+        if output.is_json() {
+            write_json(&stats)
+        } else {
+            writeln!(io::stdout().lock(), "{}", render(&stats).trim_end())?;
+            anyhow::Ok(())
+        }
+    };
+    result.await.map_err(|err| {
+        tracing::error!("Failed to report statistics");
+        tracing::error!("{err:?}");
+        CliError::GenericError
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::models::stele_stats::SteleStats;
+    use crate::history::stats::{render, RepositorySize, Stats};
+
+    #[test]
+    fn test_render_when_stele_with_missing_repository_expect_report() {
+        let cut = render;
+        let stats = Stats {
+            stele: "org/law".to_owned(),
+            counts: SteleStats {
+                documents: 3,
+                publications: 1,
+                earliest_codified_date: Some("2023-01-01".to_owned()),
+                latest_codified_date: Some("2023-10-22".to_owned()),
+                ..SteleStats::default()
+            },
+            repositories: vec![
+                RepositorySize {
+                    repository: "org/law".to_owned(),
+                    bytes: Some(2048),
+                    recorded_bytes: None,
+                    recorded_at: None,
+                },
+                RepositorySize {
+                    repository: "org/law-html".to_owned(),
+                    bytes: None,
+                    recorded_bytes: None,
+                    recorded_at: None,
+                },
+            ],
+        };
+        let actual = cut(&[stats]);
+        assert!(actual.starts_with("org/law\n  documents:     3\n"));
+        assert!(actual.contains("  codified:      2023-01-01 to 2023-10-22\n"));
+        assert!(actual.contains("  auth commits:  - to -\n"));
+        assert!(actual.contains("    org/law-html: not cloned\n"));
+    }
+}
@@ -0,0 +1,532 @@
+//! Report the drift between the repositories of an archive, its database and a running server.
+//!
+//! For every stele, `stelae status` compares the latest publication in the RDF repository with
+//! the latest publication ingested into the database, and the `HEAD` of every historical html
+//! data repository with the commit last recorded in `data_repo_commits`. Both drift until
+//! `stelae update` is run. With `--server`, the stelae and repositories of the archive are also
+//! compared with those the running server parsed at start-up, exposed by the management route
+//! `/_admin/archive`, which drift until the server is restarted.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{
+    self,
+    models::{data_repo_commits, publication},
+    DatabaseConnection, Databases,
+};
+use crate::history::changes::find_latest_rdf_publication;
+use crate::server::errors::CliError;
+use crate::stelae::archive::Archive;
+use crate::stelae::stele::Stele;
+use crate::stelae::types::repositories::{RepositoryType, ServeType};
+use crate::utils::archive::get_name_parts;
+use crate::utils::date;
+use crate::utils::git::Repo;
+use crate::utils::http;
+use crate::utils::output::{write_json, Output};
+use anyhow::Context as _;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Path of the summary of the archive parsed by a running server.
+pub const ARCHIVE_PATH: &str = "/_admin/archive";
+
+/// Stelae of an archive, with the qualified names of their repositories.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Repositories of every stele, sorted by name, keyed by the qualified name of the stele.
+    pub stelae: BTreeMap<String, Vec<String>>,
+}
+
+impl Summary {
+    /// Summarize the stelae of the `archive`.
+    #[must_use]
+    pub fn of(archive: &Archive) -> Self {
+        let stelae = archive
+            .stelae
+            .iter()
+            .map(|(name, stele)| {
+                let mut repositories: Vec<String> = stele
+                    .repositories
+                    .iter()
+                    .flat_map(|repositories| repositories.get_sorted())
+                    .map(|repository| repository.name.clone())
+                    .collect();
+                repositories.sort();
+                (name.clone(), repositories)
+            })
+            .collect();
+        Self { stelae }
+    }
+
+    /// Qualified names of the stelae that were added, removed, or whose repositories changed in
+    /// `other`.
+    #[must_use]
+    pub fn changed(&self, other: &Self) -> Vec<String> {
+        let mut changed: Vec<String> = self
+            .stelae
+            .iter()
+            .filter(|&(name, repositories)| other.stelae.get(name) != Some(repositories))
+            .map(|(name, _)| name.clone())
+            .collect();
+        changed.extend(
+            other
+                .stelae
+                .keys()
+                .filter(|name| !self.stelae.contains_key(*name))
+                .cloned(),
+        );
+        changed.sort();
+        changed
+    }
+}
+
+/// Drift of the stelae of an archive.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// Whether the database and the server, if checked, are up to date with the archive.
+    pub ok: bool,
+    /// Drift of every stele, sorted by stele name.
+    pub stelae: Vec<SteleDrift>,
+    /// Drift of the running server, if checked.
+    pub server: Option<ServerDrift>,
+}
+
+/// Drift of the database from the repositories of a stele.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SteleDrift {
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+    pub stele: String,
+    /// Latest publication in the RDF repository, if any.
+    pub rdf_publication: Option<Published>,
+    /// Latest publication ingested into the database, if any, whether it is revoked, a preview or
+    /// awaiting approval.
+    pub db_publication: Option<Published>,
+    /// Historical html data repositories of the stele.
+    pub repositories: Vec<RepositoryDrift>,
+    /// Error that stopped checking the stele, if any.
+    pub error: Option<String>,
+}
+
+/// Name and date of a publication.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Published {
+    /// Name of the publication.
+    pub name: String,
+    /// Date of the publication.
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
+}
+
+/// Drift of the database from a data repository.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepositoryDrift {
+    /// Qualified name of the repository.
+    pub repository: String,
+    /// `HEAD` commit of the repository, if it is cloned.
+    pub head: Option<String>,
+    /// Commit of the repository last recorded in the database, if any.
+    pub recorded: Option<String>,
+}
+
+/// Drift of the archive parsed by a running server.
+#[derive(Debug, Serialize)]
+pub struct ServerDrift {
+    /// Url of the server.
+    pub url: String,
+    /// Stelae added, removed, or whose repositories changed since the server parsed the archive.
+    pub changed: Vec<String>,
+    /// Error that stopped checking the server, e.g. because it is not running.
+    pub error: Option<String>,
+}
+
+impl SteleDrift {
+    /// Whether the database is up to date with the repositories of the stele.
+    #[must_use]
+    pub fn is_up_to_date(&self) -> bool {
+        self.error.is_none()
+            && self.rdf_publication == self.db_publication
+            && self.repositories.iter().all(RepositoryDrift::is_up_to_date)
+    }
+}
+
+impl RepositoryDrift {
+    /// Whether the `HEAD` commit of the repository is the one last recorded in the database.
+    #[must_use]
+    pub fn is_up_to_date(&self) -> bool {
+        self.head.is_some() && self.head == self.recorded
+    }
+}
+
+impl ServerDrift {
+    /// Whether the server parsed the same stelae and repositories as the archive has.
+    #[must_use]
+    pub fn is_up_to_date(&self) -> bool {
+        self.error.is_none() && self.changed.is_empty()
+    }
+}
+
+/// Collect the drift of every stele in the `archive` from the databases, sorted by stele name.
+///
+/// # Errors
+/// Errors if the publications or commits cannot be read from the databases.
+pub async fn collect(archive: &Archive, db: &Databases) -> anyhow::Result<Vec<SteleDrift>> {
+    let mut statuses = vec![];
+    for (name, stele) in archive.get_stelae() {
+        let stele_db = db.for_stele(&name);
+        let db_publication = publication::Manager::find_latest_ingested(stele_db, &name)
+            .await?
+            .map(|found| Published {
+                name: found.name,
+                date: found.date,
+            });
+        let mut status = SteleDrift {
+            stele: name,
+            rdf_publication: None,
+            db_publication,
+            repositories: vec![],
+            error: None,
+        };
+        if let Err(err) = check_stele(&mut status, stele_db, &stele).await {
+            status.error = Some(format!("{err:#}"));
+        }
+        statuses.push(status);
+    }
+    Ok(statuses)
+}
+
+/// Check the latest publication of the RDF repository and the `HEAD` of the historical html
+/// data repositories of the `stele` into its `status`.
+///
+/// # Errors
+/// Errors if the RDF repository cannot be read, or the commits cannot be read from the database.
+async fn check_stele(
+    status: &mut SteleDrift,
+    db: &DatabaseConnection,
+    stele: &Stele,
+) -> anyhow::Result<()> {
+    let Some(repositories) = stele.repositories.as_ref() else {
+        return Ok(());
+    };
+    if let Some(rdf_repo) = repositories.get_one_by_custom_type(&RepositoryType::Rdf) {
+        let (org, name) = get_name_parts(&rdf_repo.name)?;
+        let rdf = Repo::new(&stele.archive_path, &org, &name)
+            .with_context(|| format!("could not open RDF repository {}", rdf_repo.name))?;
+        status.rdf_publication =
+            find_latest_rdf_publication(&rdf, &status.stele)?.map(|(pub_name, date)| Published {
+                name: pub_name,
+                date,
+            });
+    }
+    // Commits are only recorded for historical html data repositories, see `stelae update`.
+    let data_repos = repositories
+        .get_all_by_serve_type(&ServeType::Historical)
+        .into_iter()
+        .filter(|data_repo| data_repo.custom.repository_type == Some(RepositoryType::Html));
+    for data_repo in data_repos {
+        let recorded = data_repo_commits::Manager::find_latest_by_stele_and_repo_type(
+            db,
+            &status.stele,
+            &data_repo.get_type().unwrap_or_default(),
+        )
+        .await?;
+        let head = Repo::new(
+            &stele.archive_path,
+            &data_repo.get_org(),
+            &data_repo.get_name(),
+        )
+        .and_then(|repo| repo.head_commit_id())
+        .ok();
+        status.repositories.push(RepositoryDrift {
+            repository: data_repo.name.clone(),
+            head,
+            recorded: recorded.map(|commit| commit.commit_hash),
+        });
+    }
+    Ok(())
+}
+
+/// Compare the `archive` with the archive parsed by the server running at `server_url`, sending
+/// the bearer `token` if the management routes of the server are authenticated.
+#[must_use]
+pub fn check_server(archive: &Archive, server_url: &str, token: Option<&str>) -> ServerDrift {
+    let url = server_url.trim_end_matches('/').to_owned();
+    let mut request = http::client().get(&format!("{url}{ARCHIVE_PATH}"));
+    if let Some(bearer) = token {
+        request = request.set("Authorization", &format!("Bearer {bearer}"));
+    }
+    let served = request
+        .call()
+        .context("could not request the archive of the server")
+        .and_then(|response| {
+            response
+                .into_json::<Summary>()
+                .context("could not parse the archive of the server")
+        });
+    match served {
+        Ok(summary) => ServerDrift {
+            changed: summary.changed(&Summary::of(archive)),
+            url,
+            error: None,
+        },
+        Err(err) => ServerDrift {
+            url,
+            changed: vec![],
+            error: Some(format!("{err:#}")),
+        },
+    }
+}
+
+/// Render the drift of the archive as a plain text report.
+#[must_use]
+pub fn render(status: &Status) -> String {
+    let mut report = String::new();
+    let _infallible = write_status(&mut report, status);
+    report
+}
+
+/// Write the plain text report of the drift to `out`.
+fn write_status(out: &mut String, status: &Status) -> fmt::Result {
+    let or_none = |publication: Option<&Published>| {
+        publication.map_or_else(
+            || "-".to_owned(),
+            |found| format!("{} ({})", found.name, found.date),
+        )
+    };
+    let up_to_date = |ok: bool| if ok { "up to date" } else { "behind" };
+    for stele in &status.stelae {
+        writeln!(
+            out,
+            "{}: {}",
+            stele.stele,
+            up_to_date(stele.is_up_to_date())
+        )?;
+        if let Some(err) = stele.error.as_deref() {
+            writeln!(out, "  error:        {err}")?;
+        }
+        writeln!(
+            out,
+            "  publication:  {} in the RDF repository, {} in the database",
+            or_none(stele.rdf_publication.as_ref()),
+            or_none(stele.db_publication.as_ref())
+        )?;
+        for repository in &stele.repositories {
+            writeln!(
+                out,
+                "  {}: HEAD {}, recorded {}",
+                repository.repository,
+                repository.head.as_deref().unwrap_or("not cloned"),
+                repository.recorded.as_deref().unwrap_or("-")
+            )?;
+        }
+    }
+    if let Some(server) = status.server.as_ref() {
+        match server.error.as_deref() {
+            Some(err) => writeln!(out, "server {}: {err}", server.url)?,
+            None if server.changed.is_empty() => {
+                writeln!(out, "server {}: up to date", server.url)?;
+            }
+            None => writeln!(
+                out,
+                "server {}: stale, restart it to serve the changes of {}",
+                server.url,
+                server.changed.join(", ")
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Report the drift of every stele in the archive, and of the server running at `server` if
+/// given, to stdout as plain text or JSON. The bearer token in `token_file`, if given, is sent to
+/// the management routes of the server.
+///
+/// # Errors
+/// Errors with [`CliError::VerificationFailure`] if the database or the server is behind the
+/// archive, with [`CliError::ConfigError`] if the token cannot be read, or if the archive cannot
+/// be parsed or the database cannot be reached.
+#[tracing::instrument(name = "Stelae status", skip(raw_archive_path, archive_path))]
+pub async fn report(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    server: Option<&str>,
+    token_file: Option<&Path>,
+    output: Output,
+) -> Result<(), CliError> {
+    let token = token_file
+        .map(|path| {
+            fs::read_to_string(path)
+                .map(|token| token.trim().to_owned())
+                .map_err(|err| {
+                    tracing::error!("Unable to read the token at '{}': {err}", path.display());
+                    CliError::ConfigError
+                })
+        })
+        .transpose()?;
+    let archive = Archive::parse(
+        archive_path.clone(),
+        &PathBuf::from(raw_archive_path),
+        false,
+    )
+    .map_err(|err| {
+        tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+        tracing::error!("Error: {err:?}");
+        CliError::ArchiveParseError
+    })?;
+    let conn = match db::init::connect(&archive_path).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!(
+                "error: could not connect to database.
+                Confirm that `db.sqlite3` exists in `.taf` dir or that DATABASE_URL env var is set correctly."
+            );
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::DatabaseConnectionError);
+        }
+    };
+    let status = collect_status(&archive, &archive_path, conn, server, token.as_deref())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to report the status of the archive");
+            tracing::error!("{err:?}");
+            CliError::GenericError
+        })?;
+    write_report(&status, output).map_err(|err| {
+        tracing::error!("Unable to write the status of the archive: {err:?}");
+        CliError::GenericError
+    })?;
+    if status.ok {
+        Ok(())
+    } else {
+        Err(CliError::VerificationFailure)
+    }
+}
+
+/// Collect the drift of the `archive` from the database `conn`, and from the server running at
+/// `server` if given, authenticated with `token`.
+///
+/// # Errors
+/// Errors if the databases of the stelae cannot be connected to, or cannot be read.
+async fn collect_status(
+    archive: &Archive,
+    archive_path: &Path,
+    conn: DatabaseConnection,
+    server: Option<&str>,
+    token: Option<&str>,
+) -> anyhow::Result<Status> {
+    let db = db::init::connect_stelae(archive_path, archive.stelae.keys(), conn).await?;
+    let stelae = collect(archive, &db).await?;
+    let server_status = server.map(|url| check_server(archive, url, token));
+    Ok(Status {
+        ok: stelae.iter().all(SteleDrift::is_up_to_date)
+            && server_status
+                .as_ref()
+                .is_none_or(ServerDrift::is_up_to_date),
+        stelae,
+        server: server_status,
+    })
+}
+
+/// Write the `status` to stdout in the format of `output`.
+///
+/// # Errors
+/// Errors if the status cannot be written to stdout.
+fn write_report(status: &Status, output: Output) -> anyhow::Result<()> {
+    if output.is_json() {
+        return write_json(status);
+    }
+    writeln!(io::stdout().lock(), "{}", render(status).trim_end())?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::history::status::{
+        render, Published, RepositoryDrift, ServerDrift, Status, SteleDrift, Summary,
+    };
+    use crate::utils::date;
+    use std::collections::BTreeMap;
+
+    fn summary(stelae: &[(&str, &[&str])]) -> Summary {
+        Summary {
+            stelae: stelae
+                .iter()
+                .map(|&(name, repositories)| {
+                    (
+                        name.to_owned(),
+                        repositories.iter().map(|&repo| repo.to_owned()).collect(),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_changed_when_stele_added_removed_or_changed_expect_names() {
+        let served = summary(&[
+            ("org/law", &["org/law", "org/law-html"]),
+            ("org/old", &["org/old"]),
+            ("other/law", &["other/law"]),
+        ]);
+        let current = summary(&[
+            ("org/law", &["org/law", "org/law-html", "org/law-pdf"]),
+            ("new/law", &["new/law"]),
+            ("other/law", &["other/law"]),
+        ]);
+        let cut = Summary::changed;
+        assert_eq!(cut(&served, &current), ["new/law", "org/law", "org/old"]);
+        assert!(cut(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_render_when_database_and_server_behind_expect_report() {
+        let publication = |name: &str| Published {
+            name: name.to_owned(),
+            date: date::parse(name).unwrap(),
+        };
+        let status = Status {
+            ok: false,
+            stelae: vec![SteleDrift {
+                stele: "org/law".to_owned(),
+                rdf_publication: Some(publication("2023-10-22")),
+                db_publication: Some(publication("2023-01-01")),
+                repositories: vec![RepositoryDrift {
+                    repository: "org/law-html".to_owned(),
+                    head: Some("abc".to_owned()),
+                    recorded: Some("abc".to_owned()),
+                }],
+                error: None,
+            }],
+            server: Some(ServerDrift {
+                url: "http://localhost:8080".to_owned(),
+                changed: vec!["org/law".to_owned()],
+                error: None,
+            }),
+        };
+        assert!(!status.stelae[0].is_up_to_date());
+        assert!(status.stelae[0].repositories[0].is_up_to_date());
+        let actual = render(&status);
+        assert!(actual.starts_with("org/law: behind\n"));
+        assert!(actual.contains(
+            "  publication:  2023-10-22 (2023-10-22) in the RDF repository, 2023-01-01 (2023-01-01) in the database\n"
+        ));
+        assert!(actual.contains("  org/law-html: HEAD abc, recorded abc\n"));
+        assert!(actual.ends_with(
+            "server http://localhost:8080: stale, restart it to serve the changes of org/law\n"
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+//! Count the views of current documents served by `stelae serve`, and write them to the database.
+//!
+//! Views are counted in memory by every worker of the server, and added to the `document_views`
+//! table every `flush_interval` seconds of the `[document_views]` config, and when the server
+//! stops.
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{rt, rt::time};
+use chrono::{NaiveDate, Utc};
+
+use crate::db::{
+    models::document_view::{self, DocumentView},
+    DatabaseConnection, DatabaseTransaction, Tx as _,
+};
+use crate::utils::date;
+
+/// Number of views, keyed by stele, document path and day.
+type ViewCounts = HashMap<(String, String, NaiveDate), i64>;
+
+/// Views of current documents counted since they were last written to the database, keyed by
+/// stele, document path and day.
+///
+/// Views are only counted if enabled by the `[document_views]` config.
+#[derive(Debug, Clone, Default)]
+pub struct ViewCounter {
+    /// Whether views are counted.
+    enabled: bool,
+    /// Views counted since the last flush.
+    counts: Arc<Mutex<ViewCounts>>,
+}
+
+impl ViewCounter {
+    /// Create a counter of the views of current documents.
+    #[must_use]
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    /// Count a view of the document at the normalized `path` of the stele `stele` today.
+    pub fn record(&self, stele: &str, path: &str) {
+        if !self.enabled {
+            return;
+        }
+        let key = (stele.to_owned(), path.to_owned(), Utc::now().date_naive());
+        if let Ok(mut counts) = self.counts.lock() {
+            let views = counts.entry(key).or_default();
+            *views = views.saturating_add(1);
+        }
+    }
+
+    /// Take the views counted since the last flush, ordered by stele, path and day.
+    #[must_use]
+    pub fn take(&self) -> Vec<DocumentView> {
+        let taken = self
+            .counts
+            .lock()
+            .map(|mut counts| mem::take(&mut *counts))
+            .unwrap_or_default();
+        let mut views: Vec<_> = taken
+            .into_iter()
+            .map(|((stele, path, day), views)| DocumentView {
+                stele,
+                path,
+                date: date::format(day),
+                views,
+            })
+            .collect();
+        views.sort_by(|left, right| {
+            (&left.stele, &left.path, &left.date).cmp(&(&right.stele, &right.path, &right.date))
+        });
+        views
+    }
+
+    /// Add the views counted since the last flush to the views in the database.
+    ///
+    /// Returns the number of documents whose views were written.
+    ///
+    /// # Errors
+    /// Errors if the views cannot be written. They are counted again in the next flush.
+    pub async fn flush(&self, db: &DatabaseConnection) -> anyhow::Result<usize> {
+        let views = self.take();
+        if views.is_empty() {
+            return Ok(0);
+        }
+        match write_views(db, &views).await {
+            Ok(()) => Ok(views.len()),
+            Err(err) => {
+                self.restore(views);
+                Err(err)
+            }
+        }
+    }
+
+    /// Flush the counted views to `db` every `interval` in the background.
+    pub fn start_flushing(&self, db: DatabaseConnection, interval: Duration) {
+        if !self.enabled {
+            return;
+        }
+        let counter = self.clone();
+        rt::spawn(async move {
+            let mut ticks = time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(err) = counter.flush(&db).await {
+                    tracing::error!("Unable to write document views: {err:?}");
+                }
+            }
+        });
+    }
+
+    /// Count the `views` that could not be written again.
+    fn restore(&self, views: Vec<DocumentView>) {
+        let Ok(mut counts) = self.counts.lock() else {
+            return;
+        };
+        for view in views {
+            let Ok(day) = date::parse(&view.date) else {
+                continue;
+            };
+            let count = counts.entry((view.stele, view.path, day)).or_default();
+            *count = count.saturating_add(view.views);
+        }
+    }
+}
+
+/// Add the `views` to the views in the database, in a single transaction.
+async fn write_views(db: &DatabaseConnection, views: &[DocumentView]) -> anyhow::Result<()> {
+    let mut tx = DatabaseTransaction {
+        tx: db.pool.begin().await?,
+    };
+    for view in views {
+        document_view::TxManager::add(&mut tx, view).await?;
+    }
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::history::views::ViewCounter;
+
+    #[test]
+    fn test_take_when_views_recorded_expect_counted_per_document_then_reset() {
+        let cut = ViewCounter::enabled();
+        cut.record("test_org/law", "a/b/c");
+        cut.record("test_org/law", "a/b/c");
+        cut.record("test_org/law", "a/b");
+        let actual: Vec<_> = cut
+            .take()
+            .into_iter()
+            .map(|view| (view.path, view.views))
+            .collect();
+        assert_eq!(actual, vec![("a/b".to_owned(), 1), ("a/b/c".to_owned(), 2)]);
+        assert!(cut.take().is_empty());
+    }
+
+    #[test]
+    fn test_record_when_disabled_expect_no_views() {
+        let cut = ViewCounter::default();
+        cut.record("test_org/law", "a/b/c");
+        assert!(cut.take().is_empty());
+    }
+}
@@ -0,0 +1,606 @@
+//! Notify webhooks of the publications ingested by `stelae update`, and of their approval.
+//!
+//! Every configured endpoint receives a JSON `POST` per ingested publication, with the number of
+//! new, changed and removed documents. Publications awaiting approval are not notified when
+//! ingested, but once approved and once live, see [`Notifier::on_transition`]. Every attempt carries its Unix time in the
+//! `X-Stelae-Timestamp` header. If a secret is configured, `{timestamp}.{payload}` is signed with
+//! HMAC-SHA256 in the `X-Stelae-Signature` header, so endpoints can reject replayed deliveries.
+//! Failed deliveries are retried with exponential backoff, and the outcome of every delivery is
+//! recorded in the `webhook_deliveries` table.
+use crate::db::models::document_change;
+use crate::db::models::publication::{self, Publication};
+use crate::db::models::webhook_delivery::{self, WebhookDelivery};
+use crate::db::{DatabaseConnection, DatabaseTransaction, Tx as _};
+use crate::history::plugins::IngestPlugin;
+use crate::server::api::publications::{count, Counts};
+use crate::stelae::archive::Webhooks;
+use crate::utils::{date, http};
+use actix_web::rt;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use ring::hmac;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::time::Duration;
+use std::{env, mem};
+
+/// Event name of a publication approved through `POST /_admin/publications/{name}/state`.
+pub const PUBLICATION_APPROVED: &str = "publication.approved";
+/// Event name of an ingested publication.
+pub const PUBLICATION_INGESTED: &str = "publication.ingested";
+/// Event name of a publication released through `POST /_admin/publications/{name}/state`.
+pub const PUBLICATION_LIVE: &str = "publication.live";
+/// Header carrying the signature of the timestamp and the payload.
+pub const SIGNATURE_HEADER: &str = "X-Stelae-Signature";
+/// Header carrying the Unix time of the attempt to deliver the payload.
+pub const TIMESTAMP_HEADER: &str = "X-Stelae-Timestamp";
+/// Delay before the first retry of a failed delivery, doubled for every further retry.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Payload posted to the webhooks for an ingested, approved or released publication.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payload {
+    /// Name of the event, one of `publication.ingested`, `publication.approved` or
+    /// `publication.live`.
+    pub event: &'static str,
+    /// Qualified name of the stele the publication belongs to.
+    pub stele: String,
+    /// Name of the publication.
+    pub publication: String,
+    /// Name of the previous publication the counts are relative to, if any.
+    pub previous_publication: Option<String>,
+    /// Date of the publication.
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
+    /// Number of new, changed and removed documents of the publication.
+    pub counts: Counts,
+}
+
+/// Outcome of an attempt to deliver a payload.
+enum Attempt {
+    /// The endpoint accepted the payload with a success status.
+    Delivered(u16),
+    /// The endpoint responded with an error status.
+    Rejected(u16),
+    /// The endpoint could not be reached.
+    Failed(String),
+}
+
+/// Ingest plugin notifying webhooks of the publications ingested for a stele.
+///
+/// Created by `stelae update` for every stele with configured webhooks.
+#[derive(Debug)]
+pub struct Notifier {
+    /// Delay before the first retry of a failed delivery.
+    backoff: Duration,
+    /// Endpoints notified of the publications of the stele.
+    urls: Vec<String>,
+    /// Secret payloads are signed with, if any.
+    secret: Option<Vec<u8>>,
+    /// Maximum number of attempts to deliver a payload to an endpoint.
+    max_attempts: u32,
+    /// Names of the publications ingested so far.
+    publications: Vec<String>,
+}
+
+impl Notifier {
+    /// Create a notifier delivering to `urls`, signing payloads with `secret` if given.
+    #[must_use]
+    pub const fn new(urls: Vec<String>, secret: Option<Vec<u8>>, max_attempts: u32) -> Self {
+        Self {
+            backoff: INITIAL_BACKOFF,
+            urls,
+            secret,
+            max_attempts,
+            publications: Vec::new(),
+        }
+    }
+
+    /// Notify the endpoints of the `event` of every publication of `names` found in the
+    /// `publications` of the stele, recording every delivery.
+    ///
+    /// # Errors
+    /// Errors if the deliveries cannot be recorded, or if a delivery failed.
+    async fn notify(
+        &self,
+        conn: &DatabaseConnection,
+        stele: &str,
+        publications: &[Publication],
+        names: &[String],
+        event: &'static str,
+    ) -> anyhow::Result<()> {
+        let mut failed: usize = 0;
+        for name in names {
+            let Some(payload) = Self::payload(conn, stele, publications, name, event).await? else {
+                continue;
+            };
+            let body = serde_json::to_string(&payload)?;
+            for url in &self.urls {
+                let delivery = self.deliver(stele, name, url, &body).await;
+                if delivery.error.is_some() {
+                    tracing::error!(
+                        "[{stele}] | Giving up delivering publication {name} to {url} after {} attempt(s)",
+                        delivery.attempts
+                    );
+                    failed = failed.saturating_add(1);
+                }
+                let mut tx = DatabaseTransaction {
+                    tx: conn.pool.begin().await?,
+                };
+                webhook_delivery::TxManager::create(&mut tx, &delivery).await?;
+                tx.commit().await?;
+            }
+        }
+        if failed > 0 {
+            anyhow::bail!("{failed} webhook delivery(ies) failed, see `webhook_deliveries`");
+        }
+        Ok(())
+    }
+
+    /// Notify the endpoints that the publication `name` of the stele moved to `state`, approved
+    /// or live.
+    ///
+    /// # Errors
+    /// Errors if the publications cannot be read from the database, the deliveries cannot be
+    /// recorded, or if a delivery failed.
+    pub async fn on_transition(
+        &self,
+        conn: &DatabaseConnection,
+        stele: &str,
+        name: &str,
+        state: &str,
+    ) -> anyhow::Result<()> {
+        let event = if state == publication::APPROVED {
+            PUBLICATION_APPROVED
+        } else {
+            PUBLICATION_LIVE
+        };
+        let publications =
+            publication::Manager::find_all_approved_publications(conn, stele).await?;
+        self.notify(conn, stele, &publications, &[name.to_owned()], event)
+            .await
+    }
+
+    /// Create a notifier for the stele `stele_name`, or `None` if no webhooks are configured for it.
+    #[must_use]
+    pub fn for_stele(webhooks: &Webhooks, secret: Option<&[u8]>, stele_name: &str) -> Option<Self> {
+        let urls = webhooks.urls_for_stele(stele_name);
+        (!urls.is_empty())
+            .then(|| Self::new(urls, secret.map(<[u8]>::to_vec), webhooks.max_attempts()))
+    }
+
+    /// Build the payload of the `event` of the publication `name` of the stele.
+    ///
+    /// Returns `None` if the publication is not one of the `publications`, e.g. it was revoked by
+    /// a later publication of the same update, or awaits approval.
+    async fn payload(
+        conn: &DatabaseConnection,
+        stele: &str,
+        publications: &[Publication],
+        name: &str,
+        event: &'static str,
+    ) -> anyhow::Result<Option<Payload>> {
+        let mut remaining = publications.iter().skip_while(|pb| pb.name != name);
+        let Some(ingested) = remaining.next() else {
+            return Ok(None);
+        };
+        let previous = remaining.next();
+        let documents = document_change::Manager::find_all_document_deltas_by_publication(
+            conn,
+            &ingested.id,
+            previous.map(|pb| pb.id.as_str()),
+        )
+        .await?;
+        Ok(Some(Payload {
+            event,
+            stele: stele.to_owned(),
+            publication: ingested.name.clone(),
+            previous_publication: previous.map(|pb| pb.name.clone()),
+            date: ingested.date,
+            counts: count(&documents),
+        }))
+    }
+
+    /// Deliver `body` to `url`, retrying until delivered or out of attempts.
+    async fn deliver(
+        &self,
+        stele: &str,
+        publication: &str,
+        url: &str,
+        body: &str,
+    ) -> WebhookDelivery {
+        let mut delivery = WebhookDelivery {
+            stele: stele.to_owned(),
+            publication: publication.to_owned(),
+            url: url.to_owned(),
+            attempts: 0,
+            status: None,
+            error: None,
+        };
+        let mut backoff = self.backoff;
+        for attempt in 1..=self.max_attempts {
+            if attempt > 1 {
+                rt::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+            }
+            delivery.attempts = i64::from(attempt);
+            let timestamp = Utc::now().timestamp().to_string();
+            let signature = self
+                .secret
+                .as_deref()
+                .map(|secret| sign(secret, format!("{timestamp}.{body}").as_bytes()));
+            let (target, payload) = (url.to_owned(), body.to_owned());
+            let outcome = rt::task::spawn_blocking(move || {
+                post(&target, &payload, &timestamp, signature.as_deref())
+            })
+            .await
+            .unwrap_or_else(|err| Attempt::Failed(err.to_string()));
+            if record(&mut delivery, outcome) {
+                break;
+            }
+        }
+        delivery
+    }
+}
+
+/// Record the outcome of the last attempt of `delivery`.
+///
+/// Returns whether the payload was delivered.
+fn record(delivery: &mut WebhookDelivery, outcome: Attempt) -> bool {
+    let (stele, publication, url, attempts) = (
+        &delivery.stele,
+        &delivery.publication,
+        &delivery.url,
+        delivery.attempts,
+    );
+    match outcome {
+        Attempt::Delivered(status) => {
+            tracing::info!("[{stele}] | Delivered publication {publication} to {url}");
+            delivery.status = Some(i64::from(status));
+            delivery.error = None;
+            true
+        }
+        Attempt::Rejected(status) => {
+            tracing::warn!(
+                "[{stele}] | Attempt {attempts} to deliver publication {publication} to {url} was rejected with status {status}"
+            );
+            delivery.status = Some(i64::from(status));
+            delivery.error = Some(format!("Rejected with status {status}"));
+            false
+        }
+        Attempt::Failed(err) => {
+            tracing::warn!(
+                "[{stele}] | Attempt {attempts} to deliver publication {publication} to {url} failed: {err}"
+            );
+            delivery.status = None;
+            delivery.error = Some(err);
+            false
+        }
+    }
+}
+
+#[async_trait(?Send)]
+#[expect(
+    clippy::missing_trait_methods,
+    reason = "Webhooks are only notified of publications"
+)]
+impl IngestPlugin for Notifier {
+    async fn on_publication(
+        &mut self,
+        _tx: &mut DatabaseTransaction,
+        _stele: &str,
+        publication: &Publication,
+    ) -> anyhow::Result<()> {
+        self.publications.push(publication.name.clone());
+        Ok(())
+    }
+
+    async fn after_commit(&mut self, conn: &DatabaseConnection, stele: &str) -> anyhow::Result<()> {
+        let ingested = mem::take(&mut self.publications);
+        if ingested.is_empty() {
+            return Ok(());
+        }
+        let publications =
+            publication::Manager::find_all_non_revoked_publications(conn, stele).await?;
+        self.notify(conn, stele, &publications, &ingested, PUBLICATION_INGESTED)
+            .await
+    }
+}
+
+/// Read the secret payloads are signed with from the environment variable named in `webhooks`.
+///
+/// # Errors
+/// Errors if a secret is configured, but its environment variable is not set.
+pub fn load_secret(webhooks: &Webhooks) -> anyhow::Result<Option<Vec<u8>>> {
+    webhooks
+        .secret_env
+        .as_deref()
+        .map(|secret_env| {
+            env::var(secret_env)
+                .map(String::into_bytes)
+                .with_context(|| format!("Webhook secret {secret_env} is not set"))
+        })
+        .transpose()
+}
+
+/// Post the json `body` to `url` at `timestamp`, with the `signature` header if given.
+fn post(url: &str, body: &str, timestamp: &str, signature: Option<&str>) -> Attempt {
+    let mut request = http::client()
+        .post(url)
+        .set("Content-Type", "application/json")
+        .set(TIMESTAMP_HEADER, timestamp);
+    if let Some(signed) = signature {
+        request = request.set(SIGNATURE_HEADER, signed);
+    }
+    match request.send_string(body) {
+        Ok(response) => Attempt::Delivered(response.status()),
+        Err(ureq::Error::Status(status, _)) => Attempt::Rejected(status),
+        Err(err) => Attempt::Failed(err.to_string()),
+    }
+}
+
+/// Sign `payload` with HMAC-SHA256 under `secret`, as sent in the signature header.
+///
+/// Deliveries sign their timestamp and body, joined by a `.`. The signature is formatted as
+/// `sha256={hex digest}`.
+#[must_use]
+pub fn sign(secret: &[u8], payload: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::sign(&key, payload).as_ref().iter().fold(
+        String::from("sha256="),
+        |mut signature, byte| {
+            let _infallible = write!(signature, "{byte:02x}");
+            signature
+        },
+    )
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::history::webhooks::{sign, Notifier, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+    use std::io::{BufRead as _, BufReader, Read as _, Write as _};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    /// A received request, as its headers keyed by lowercase name, and its body.
+    type Received = (Vec<(String, String)>, String);
+
+    /// Serve one request per status of `statuses` on a local port, answering them in order.
+    ///
+    /// Returns the url served, and the handle of the thread returning the received requests.
+    fn serve(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            statuses
+                .into_iter()
+                .map(|status| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut headers = vec![];
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+                        }
+                    }
+                    let length = headers
+                        .iter()
+                        .find(|header| header.0 == "content-length")
+                        .map_or(0, |header| header.1.parse().unwrap());
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                    (headers, String::from_utf8(body).unwrap())
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    /// Value of the header `name` of the `received` request.
+    fn header<'req>(received: &'req Received, name: &str) -> &'req str {
+        received
+            .0
+            .iter()
+            .find(|header| header.0 == name.to_lowercase())
+            .map(|header| header.1.as_str())
+            .unwrap()
+    }
+
+    /// A notifier of `urls` signing with `secret`, retrying after a millisecond.
+    fn notifier(urls: Vec<String>, secret: &[u8], max_attempts: u32) -> Notifier {
+        Notifier {
+            backoff: Duration::from_millis(1),
+            ..Notifier::new(urls, Some(secret.to_vec()), max_attempts)
+        }
+    }
+
+    #[test]
+    fn test_sign_when_rfc_4231_vector_expect_known_digest() {
+        let cut = sign;
+        assert_eq!(
+            cut(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_deliver_when_rejected_then_accepted_expect_retried_with_signed_timestamp() {
+        let (url, server) = serve(vec![500, 204]);
+        let cut = notifier(vec![url.clone()], b"secret", 3);
+        let body = r#"{"event":"publication.ingested"}"#;
+        let actual = cut.deliver("org/law", "2023-10-22", &url, body).await;
+        assert_eq!(actual.attempts, 2);
+        assert_eq!(actual.status, Some(204));
+        assert_eq!(actual.error, None);
+        let received = server.join().unwrap();
+        assert_eq!(received.len(), 2);
+        for request in &received {
+            assert_eq!(request.1, body);
+            let timestamp = header(request, TIMESTAMP_HEADER);
+            let expected = sign(b"secret", format!("{timestamp}.{body}").as_bytes());
+            assert_eq!(header(request, SIGNATURE_HEADER), expected);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_deliver_when_always_rejected_expect_error_after_max_attempts() {
+        let (url, server) = serve(vec![503, 503]);
+        let cut = notifier(vec![url.clone()], b"secret", 2);
+        let actual = cut.deliver("org/law", "2023-10-22", &url, "{}").await;
+        assert_eq!(actual.attempts, 2);
+        assert_eq!(actual.status, Some(503));
+        assert_eq!(actual.error.as_deref(), Some("Rejected with status 503"));
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_insert_when_webhook_configured_expect_deliveries_recorded() {
+        use crate::db;
+        use crate::history::changes;
+        use crate::testing::generate;
+        use crate::utils::output::Output;
+        use std::fs::OpenOptions;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive_path = archive_dir.path();
+        let size = generate::Size {
+            documents: 1,
+            versions: 2,
+        };
+        generate::generate(archive_path, size).unwrap();
+        let (url, server) = serve(vec![200, 200]);
+        let mut config = OpenOptions::new()
+            .append(true)
+            .open(archive_path.join(".taf/config.toml"))
+            .unwrap();
+        writeln!(config, "\n[webhooks]\nurls = [\"{url}\"]").unwrap();
+        changes::insert(
+            &archive_path.to_string_lossy(),
+            archive_path.to_path_buf(),
+            true,
+            false,
+            Output::Text,
+        )
+        .await
+        .unwrap();
+
+        let received = server.join().unwrap();
+        let events: Vec<serde_json::Value> = received
+            .iter()
+            .map(|request| serde_json::from_str(&request.1).unwrap())
+            .collect();
+        assert!(events
+            .iter()
+            .all(|event| event["event"] == "publication.ingested"));
+        let conn = db::init::connect(archive_path).await.unwrap();
+        // The `Any` driver cannot decode a NULL into an `Option`, so the errors are checked in SQL.
+        let mut actual: Vec<(String, String, String, i64, i64, i64)> = sqlx::query_as(
+            "SELECT stele, publication, url, attempts, status, error IS NULL FROM webhook_deliveries",
+        )
+        .fetch_all(&conn.pool)
+        .await
+        .unwrap();
+        actual.sort();
+        let expected: Vec<(String, String, String, i64, i64, i64)> = ["2020-01-01", "2020-01-31"]
+            .into_iter()
+            .map(|publication| {
+                (
+                    "generated/law".to_owned(),
+                    publication.to_owned(),
+                    url.clone(),
+                    1,
+                    200,
+                    1,
+                )
+            })
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_on_transition_when_approval_required_expect_notified_once_approved_and_live() {
+        use crate::db;
+        use crate::db::models::publication;
+        use crate::history::changes;
+        use crate::server::api::publications::move_to_state;
+        use crate::testing::generate;
+        use crate::utils::output::Output;
+        use std::fs::OpenOptions;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive_path = archive_dir.path();
+        let size = generate::Size {
+            documents: 1,
+            versions: 2,
+        };
+        generate::generate(archive_path, size).unwrap();
+        let (unreachable, no_requests) = serve(vec![]);
+        no_requests.join().unwrap();
+        let mut config = OpenOptions::new()
+            .append(true)
+            .open(archive_path.join(".taf/config.toml"))
+            .unwrap();
+        writeln!(
+            config,
+            "\n[approval]\nrequired = true\n\n[webhooks]\nurls = [\"{unreachable}\"]"
+        )
+        .unwrap();
+        changes::insert(
+            &archive_path.to_string_lossy(),
+            archive_path.to_path_buf(),
+            true,
+            false,
+            Output::Text,
+        )
+        .await
+        .unwrap();
+        let conn = db::init::connect(archive_path).await.unwrap();
+        let (url, server) = serve(vec![200, 200]);
+        let cut = notifier(vec![url], b"secret", 1);
+
+        for state in [publication::APPROVED, publication::LIVE] {
+            move_to_state(&conn, "generated/law", "2020-01-01", state)
+                .await
+                .unwrap();
+            cut.on_transition(&conn, "generated/law", "2020-01-01", state)
+                .await
+                .unwrap();
+        }
+
+        let events: Vec<serde_json::Value> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_str(&request.1).unwrap())
+            .collect();
+        let names: Vec<&str> = events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["publication.approved", "publication.live"]);
+        assert!(events
+            .iter()
+            .all(|event| event["publication"] == "2020-01-01"));
+        let (deliveries,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM webhook_deliveries")
+            .fetch_one(&conn.pool)
+            .await
+            .unwrap();
+        assert_eq!(deliveries, 2);
+    }
+}
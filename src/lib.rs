@@ -127,4 +127,6 @@ pub mod db;
 pub mod history;
 pub mod server;
 pub mod stelae;
+#[cfg(feature = "test-fixtures")]
+pub mod testing;
 pub mod utils;
@@ -0,0 +1,184 @@
+//! Write a structured access log of the requests to an archive.
+//!
+//! The access log is written as JSON lines, one per request, separately from the tracing
+//! diagnostics so it can be retained and shipped under its own privacy policy.
+use crate::server::proxy::Forwarded;
+use crate::stelae::archive::{AccessLog, IpPrivacy};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::Error;
+use chrono::Utc;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A line of the access log.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    /// Time the request was received, in RFC 3339 format.
+    pub timestamp: String,
+    /// Client IP address, anonymized as configured.
+    pub ip: Option<String>,
+    /// HTTP method of the request.
+    pub method: String,
+    /// Path of the request, without the query string.
+    pub path: String,
+    /// Qualified name of the stele the request was routed to.
+    pub stele: String,
+    /// HTTP status of the response.
+    pub status: u16,
+    /// Time taken to respond, in milliseconds.
+    pub latency_ms: u128,
+    /// Size of the response body in bytes, if known before streaming.
+    pub bytes: Option<u64>,
+}
+
+/// Writes requests to the access log.
+#[derive(Clone)]
+pub struct AccessLogger {
+    /// Access log configuration of the archive.
+    config: Arc<AccessLog>,
+    /// Qualified name of the root stele, the stele of requests without a guard header.
+    root_stele: String,
+    /// Header selecting the stele of current documents, if the archive guards them by header.
+    guard_header: Option<String>,
+    /// Destination of the access log.
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl AccessLogger {
+    /// Create an access logger, opening the configured log file for appending.
+    ///
+    /// # Errors
+    /// Errors if the log file cannot be opened.
+    pub fn new(
+        config: AccessLog,
+        root_stele: String,
+        guard_header: Option<String>,
+    ) -> io::Result<Self> {
+        let writer: Box<dyn Write + Send> = match config.path.as_ref() {
+            Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Self {
+            config: Arc::new(config),
+            root_stele,
+            guard_header,
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// The qualified name of the stele a request is routed to.
+    #[must_use]
+    pub fn stele_of(&self, req: &ServiceRequest) -> String {
+        self.guard_header
+            .as_deref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or(&self.root_stele)
+            .to_owned()
+    }
+
+    /// Start an entry for the request `req`, or `None` if its route or stele is not logged.
+    #[must_use]
+    pub fn start(&self, req: &ServiceRequest) -> Option<Entry> {
+        let stele = self.stele_of(req);
+        if !self.config.is_enabled(req.path(), &stele) {
+            return None;
+        }
+        let ip = Forwarded::of(req.request())
+            .client
+            .and_then(|ip| anonymize(ip, self.config.ip.unwrap_or_default()));
+        Some(Entry {
+            timestamp: Utc::now().to_rfc3339(),
+            ip,
+            method: req.method().to_string(),
+            path: req.path().to_owned(),
+            stele,
+            status: 0,
+            latency_ms: 0,
+            bytes: None,
+        })
+    }
+
+    /// Complete `entry` with the outcome of the request, and write it to the access log.
+    pub fn finish<B: MessageBody>(
+        &self,
+        mut entry: Entry,
+        response: &Result<ServiceResponse<B>, Error>,
+        latency: Duration,
+    ) {
+        let (status, size) = match response.as_ref() {
+            Ok(res) => (res.status(), res.response().body().size()),
+            Err(err) => (err.as_response_error().status_code(), BodySize::None),
+        };
+        entry.status = status.as_u16();
+        entry.latency_ms = latency.as_millis();
+        entry.bytes = match size {
+            BodySize::Sized(bytes) => Some(bytes),
+            BodySize::None => Some(0),
+            BodySize::Stream => None,
+        };
+        self.write(&entry);
+    }
+
+    /// Write `entry` as a line of the access log.
+    fn write(&self, entry: &Entry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::error!("Unable to serialize access log entry: {err:?}");
+                return;
+            }
+        };
+        let written = self
+            .writer
+            .lock()
+            .map_err(|_poisoned| io::Error::other("access log writer is poisoned"))
+            .and_then(|mut writer| writeln!(writer, "{line}").and_then(|()| writer.flush()));
+        if let Err(err) = written {
+            tracing::error!("Unable to write access log: {err:?}");
+        }
+    }
+}
+
+/// Anonymize `ip` as required by `privacy`. Returns `None` if the address is not recorded.
+#[must_use]
+pub fn anonymize(ip: IpAddr, privacy: IpPrivacy) -> Option<String> {
+    match privacy {
+        IpPrivacy::Full => Some(ip.to_string()),
+        IpPrivacy::Omit => None,
+        IpPrivacy::Truncate => Some(match ip {
+            IpAddr::V4(v4) => {
+                let [first, second, third, _] = v4.octets();
+                Ipv4Addr::new(first, second, third, 0).to_string()
+            }
+            IpAddr::V6(v6) => {
+                let [first, second, third, ..] = v6.segments();
+                Ipv6Addr::new(first, second, third, 0, 0, 0, 0, 0).to_string()
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::access_log::anonymize;
+    use crate::stelae::archive::IpPrivacy;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_anonymize_when_truncate_expect_host_bits_zeroed() {
+        let cut = anonymize;
+        let v4: IpAddr = "203.0.113.7".parse().unwrap();
+        let v6: IpAddr = "2001:db8:85a3:8d3:1319:8a2e:370:7348".parse().unwrap();
+        assert_eq!(cut(v4, IpPrivacy::Truncate).unwrap(), "203.0.113.0");
+        assert_eq!(cut(v6, IpPrivacy::Truncate).unwrap(), "2001:db8:85a3::");
+        assert_eq!(cut(v4, IpPrivacy::Full).unwrap(), "203.0.113.7");
+        assert_eq!(cut(v4, IpPrivacy::Omit), None);
+    }
+}
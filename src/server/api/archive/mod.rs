@@ -0,0 +1,20 @@
+//! Handler summarizing the archive parsed by the server at start-up.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::history::status::Summary;
+
+use super::state::{App as AppState, Global as _};
+use crate::utils::http::respond_json;
+
+/// Report the stelae of the archive the server parsed at start-up, with their repositories.
+///
+/// `stelae status --server` compares the summary with the archive on disk, to find out whether
+/// the server needs a restart to serve changed stelae or repositories.
+#[tracing::instrument(skip(data))]
+pub async fn archive(data: web::Data<AppState>) -> impl Responder {
+    respond_json(HttpResponse::Ok(), &Summary::of(data.archive()))
+}
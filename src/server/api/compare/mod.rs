@@ -0,0 +1,155 @@
+//! Handler comparing the member documents of a collection between two dates.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use actix_web::{web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        is_row_not_found,
+        models::{library, library_change, publication},
+    },
+    server::errors::HTTPError,
+};
+
+use super::policy::AccessDecision;
+use super::publications::{count, Counts, DeltaDocument};
+use super::response::ApiVersion;
+use super::state::{App as AppState, Global as _};
+use super::versions::clean_url_path;
+
+/// Query string of the compare collection endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Url path of the collection, e.g. `a/b`.
+    pub path: String,
+    /// Codified date to compare from. Changes on this date are not included.
+    pub from: NaiveDate,
+    /// Codified date to compare to. Changes on this date are included.
+    pub to: NaiveDate,
+    /// Name of the publication to compare in. Defaults to the latest publication.
+    pub publication: Option<String>,
+}
+
+/// Response for the compare collection endpoint.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionComparison {
+    /// Url path of the collection.
+    pub path: String,
+    /// Name of the publication the collection was compared in.
+    pub publication: String,
+    /// Codified date compared from.
+    pub from: NaiveDate,
+    /// Codified date compared to.
+    pub to: NaiveDate,
+    /// Number of added, changed and removed member documents.
+    pub counts: Counts,
+    /// Member documents added, changed or removed between the dates.
+    pub documents: Vec<DeltaDocument>,
+}
+
+/// List the member documents of a collection added, changed or removed between two dates.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Responses are enveloped as negotiated by
+/// [`ApiVersion`].
+#[tracing::instrument(skip(data, access))]
+pub async fn compare_collection(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+    params: web::Query<Params>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    if params.from > params.to {
+        return api_version.respond_error(
+            HttpResponse::BadRequest(),
+            "invalid_query",
+            "Query parameter `from` must not be after `to`.",
+        );
+    }
+    let db = data.db().for_stele(&stele);
+    let publications =
+        match publication::Manager::find_all_non_revoked_publications(db, &stele).await {
+            Ok(publications) => publications,
+            Err(err) => {
+                tracing::error!("Error finding publications of {stele}: {err:?}");
+                return api_version.respond_error(
+                    HttpResponse::InternalServerError(),
+                    "database_error",
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        };
+    let active_publication = params.publication.as_deref().map_or_else(
+        || publications.first(),
+        |name| publications.iter().find(|pb| pb.name == name),
+    );
+    let Some(publication) = active_publication else {
+        return api_version.respond_error(
+            HttpResponse::NotFound(),
+            "publication_not_found",
+            "No publication found.",
+        );
+    };
+    let url = clean_url_path(&params.path);
+    let mpath = match library::Manager::find_lib_mpath_by_url(db, &url, &stele).await {
+        Ok(mpath) => mpath,
+        Err(err) if is_row_not_found(&err) => {
+            return api_version.respond_error(
+                HttpResponse::NotFound(),
+                "collection_not_found",
+                format!("No collection found at {url}."),
+            );
+        }
+        Err(err) => {
+            tracing::error!("Error finding collection {url}: {err:?}");
+            return api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let documents =
+        match library_change::Manager::find_all_member_deltas_by_mpath_and_publication_between_dates(
+            db,
+            &mpath,
+            &publication.id,
+            &params.from.to_string(),
+            &params.to.to_string(),
+        )
+        .await
+        {
+            Ok(documents) => documents,
+            Err(err) => {
+                tracing::error!("Error comparing collection {url}: {err:?}");
+                return api_version.respond_error(
+                    HttpResponse::InternalServerError(),
+                    "database_error",
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        };
+    api_version.respond(
+        HttpResponse::Ok(),
+        &CollectionComparison {
+            path: url,
+            publication: publication.name.clone(),
+            from: params.from,
+            to: params.to,
+            counts: count(&documents),
+            documents: documents.into_iter().map(Into::into).collect(),
+        },
+    )
+}
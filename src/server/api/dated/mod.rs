@@ -0,0 +1,378 @@
+//! Handler serving the documents of any typed data repository of a stele as they were on a date.
+//!
+//! Documents are served at `/_date/{date}/_repo/{type}/{path}`, e.g.
+//! `/_date/2023-10-22/_repo/pdf/a/b/c.pdf`, from the commit of the historical data repository of
+//! the type that `stelae update` mapped to the date. Documents are also served at
+//! `/_date/{date}/{path}`, from the historical data repository whose routes or scope match the
+//! path as they do for current documents, and from the html repository otherwise.
+//!
+//! Html documents of a date are marked with the banner of historical documents of their stele,
+//! get the elements their data repository declares injected, and have their root-relative urls
+//! prefixed with `/_date/{date}`, so links stay on the date, like `stelae export --dated`. Like
+//! snapshots, they link to the current document as their canonical url, and are described with
+//! the structured data of their stele.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
+use regex::Regex;
+use std::iter;
+use std::path::Path;
+
+use crate::{
+    db::{
+        models::data_repo_commits::{self, DataRepoCommits},
+        DatabaseConnection,
+    },
+    server::{
+        base_path::BasePath,
+        errors::HTTPError,
+        proxy::Forwarded,
+        timing::{Phase, Timings},
+    },
+    stelae::{
+        archive::{Archive, StructuredData},
+        types::repositories::{Repositories, Repository, RepositoryType, ServeType},
+    },
+    utils::{
+        date,
+        git::Repo,
+        html::prefix_root_relative_urls,
+        http::{get_contenttype, respond_blob, respond_text},
+        paths::normalize_path,
+        structured_data::Document,
+        template::{inject, watermark},
+    },
+};
+
+use super::formats::FALLBACK_COMMITS;
+use super::policy::AccessDecision;
+use super::snapshot::canonicalize;
+use super::state::{App as AppState, Global as _};
+use super::takedown::unavailable;
+use super::versions::request::DATE_SEGMENT_FORMAT;
+
+/// Serve the document at `path` of the historical data repository of `type` as it was on `date`.
+///
+/// Without `type`, the repository is the one serving `path`, see [`route_on_date`].
+/// The stele is selected by [`AccessDecision::stele`]. Commits of preview publications are only
+/// mapped to the date for requests that may see previews, see [`AccessDecision::previews`].
+/// A document missing from the commit mapped to the date is looked up in earlier commits of the
+/// same publication. The repository is the one listed at the authentication commit mapped to the
+/// date, so documents of repositories removed since are still served.
+///
+/// Html documents link to the current document at `/{path}` as their canonical url, in the
+/// document and in the `Link` header.
+#[tracing::instrument(skip(req, data, access, structured_data))]
+pub async fn serve_on_date(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    structured_data: Option<web::Data<StructuredData>>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let raw_date = req.match_info().get("date").unwrap_or_default();
+    let Ok(on_date) = date::parse(raw_date) else {
+        return respond_text(
+            HttpResponse::BadRequest(),
+            format!("Invalid date {raw_date}, expected {DATE_SEGMENT_FORMAT}."),
+        );
+    };
+    let requested_path = match normalize_path(req.match_info().get("path").unwrap_or_default()) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    if let Some(reason) = data.takedowns.find(&stele, &requested_path) {
+        return unavailable(&requested_path, &reason);
+    }
+    let (routed_type, path) = req.match_info().get("repo_type").map_or_else(
+        || {
+            let repositories = data
+                .archive()
+                .stelae
+                .get(&stele)
+                .and_then(|found| found.repositories.as_ref());
+            route_on_date(repositories, &requested_path)
+        },
+        |requested_type| (requested_type.to_owned(), requested_path.clone()),
+    );
+    let repo_type = routed_type.as_str();
+    let db = data.db().for_stele(&stele);
+    let timings = Timings::of(&req);
+    let found = timings.measure_async(
+        Phase::Db,
+        data_repo_commits::Manager::find_latest_by_stele_and_repo_type_on_or_before_date(
+            db,
+            &stele,
+            repo_type,
+            &on_date,
+            access.previews(),
+        ),
+    );
+    let mapped = match found.await {
+        Ok(Some(mapped)) => mapped,
+        Ok(None) => {
+            return respond_text(
+                HttpResponse::NotFound(),
+                format!("No {repo_type} commit of stele {stele} found on or before {on_date}."),
+            );
+        }
+        Err(err) => {
+            tracing::error!(
+                "Error finding the {repo_type} commit of {stele} on {on_date}: {err:?}"
+            );
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let Some(repository) =
+        find_historical_repository(data.archive(), &stele, &mapped.auth_commit_hash, repo_type)
+    else {
+        return respond_text(
+            HttpResponse::NotFound(),
+            format!("No historical {repo_type} repository found for stele {stele}."),
+        );
+    };
+    let archive_path = &data.archive().path;
+    let blob = find_blob_on_date(db, archive_path, &repository, &mapped, &on_date, &path);
+    match timings.measure_async(Phase::Git, blob).await {
+        Ok(Some(content)) => {
+            let contenttype = get_contenttype(&path);
+            if contenttype.0 != mime::TEXT_HTML {
+                return respond_blob(HttpResponse::Ok(), contenttype.0, content);
+            }
+            let base_path = BasePath::of(&req);
+            let forwarded = Forwarded::of(&req);
+            let canonical =
+                forwarded.stable_url(&format!("{}/{requested_path}", base_path.as_str()));
+            let served_url = forwarded.stable_url(&base_path.url(req.path()));
+            let document = Document {
+                path: &requested_path,
+                url: &canonical,
+                served_url: &served_url,
+                version_date: None,
+            };
+            let dated = Dated {
+                stele: &stele,
+                repository: &repository,
+                on_date,
+                document: &document,
+            };
+            let body = decorate(
+                &data,
+                &dated,
+                &base_path,
+                structured_data.as_ref().map(web::Data::get_ref),
+                content,
+            );
+            let mut response = HttpResponse::Ok();
+            response.insert_header(("Link", format!("<{canonical}>; rel=\"canonical\"")));
+            respond_blob(response, contenttype.0, body)
+        }
+        Ok(None) => {
+            tracing::debug!("{path}: not found in {} on {on_date}", repository.name);
+            respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string())
+        }
+        Err(err) => {
+            tracing::error!(
+                "Error finding {path} in {} on {on_date}: {err:?}",
+                repository.name
+            );
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Find the type of the historical data repository serving the `path` of a date, and the path
+/// of the document in it.
+///
+/// The repositories are matched as for current documents: by the scope the path starts with,
+/// then by their routes, longest first. Paths matching no historical repository are served from
+/// the html repository.
+fn route_on_date(repositories: Option<&Repositories>, path: &str) -> (String, String) {
+    let historical = repositories
+        .map(Repositories::get_sorted)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|repository| repository.custom.serve == ServeType::Historical);
+    let routed = historical.clone().find_map(|repository| {
+        let scope = normalize_path(repository.custom.scope.as_deref()?).ok()?;
+        let scoped = path.strip_prefix(scope.as_str())?;
+        let tail = match scoped.strip_prefix('/') {
+            Some(tail) => tail,
+            None if scoped.is_empty() => scoped,
+            None => return None,
+        };
+        Some((repository, tail.to_owned()))
+    });
+    let matched = routed.or_else(|| {
+        historical
+            .clone()
+            .find(|repository| {
+                repository.custom.routes.iter().flatten().any(|route| {
+                    Regex::new(&format!("^(?:{route})$")).is_ok_and(|re| re.is_match(path))
+                })
+            })
+            .map(|repository| (repository, path.to_owned()))
+    });
+    matched
+        .and_then(|(repository, tail)| Some((repository.get_type()?, tail)))
+        .unwrap_or_else(|| (RepositoryType::Html.as_str().to_owned(), path.to_owned()))
+}
+
+/// A document served as it was on a date.
+struct Dated<'doc> {
+    /// Qualified name of the stele of the document.
+    stele: &'doc str,
+    /// The historical data repository the document is served from.
+    repository: &'doc Repository,
+    /// The date the document is served as of.
+    on_date: NaiveDate,
+    /// The document, with the url of its current version.
+    document: &'doc Document<'doc>,
+}
+
+/// Mark the html `content` of the `dated` document with the banner of historical documents of
+/// its stele, inject the elements its repository declares, prefix its root-relative urls with
+/// `/_date/{date}`, mount them under the `base_path`, point its canonical link at the url of the
+/// current document, and describe it with `structured_data`, see [`canonicalize`].
+fn decorate(
+    data: &AppState,
+    dated: &Dated<'_>,
+    base_path: &BasePath,
+    structured_data: Option<&StructuredData>,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    let &Dated {
+        stele,
+        repository,
+        on_date,
+        document,
+    } = dated;
+    let path = document.path;
+    let version_date = date::format(on_date);
+    let marked = match data.watermarks.for_stele(stele) {
+        Some(text) => {
+            let display_date = data.locales.for_stele(stele).format_date(on_date);
+            watermark(&content, &text, &display_date).unwrap_or_else(|err| {
+                tracing::warn!("{path}: unable to mark historical document: {err}");
+                content
+            })
+        }
+        None => content,
+    };
+    let injected = match repository.custom.injections.as_deref() {
+        Some(injections) => inject(&marked, injections, &version_date).unwrap_or_else(|err| {
+            tracing::warn!("{path}: unable to inject elements: {err}");
+            marked
+        }),
+        None => marked,
+    };
+    let prefixed = prefix_root_relative_urls(&injected, &format!("/_date/{version_date}"))
+        .unwrap_or_else(|err| {
+            tracing::warn!("{path}: unable to prefix urls with the date: {err}");
+            injected
+        });
+    // The urls of the document are mounted already, unlike the urls inserted from here on.
+    let mounted = base_path.html(prefixed);
+    let versioned = Document {
+        version_date: Some(&version_date),
+        ..*document
+    };
+    canonicalize(stele, &versioned, structured_data, mounted)
+}
+
+/// Find the historical data repository of `repo_type` of the stele `stele_name`, as listed in
+/// `targets/repositories.json` at the authentication commit `auth_commit`, see
+/// [`find_repositories_at_commit`].
+#[must_use]
+pub fn find_historical_repository(
+    archive: &Archive,
+    stele_name: &str,
+    auth_commit: &str,
+    repo_type: &str,
+) -> Option<Repository> {
+    find_repositories_at_commit(archive, stele_name, auth_commit)?
+        .get_all_by_serve_type(&ServeType::Historical)
+        .into_iter()
+        .find(|repository| {
+            repository
+                .custom
+                .repository_type
+                .as_ref()
+                .map(RepositoryType::as_str)
+                == Some(repo_type)
+        })
+        .cloned()
+}
+
+/// The data repositories of the stele `stele_name`, as listed in `targets/repositories.json` at
+/// the authentication commit `auth_commit`.
+///
+/// Falls back to the repositories listed at `HEAD` if the commit has no readable list, so
+/// documents of publications whose repositories were removed since are still found.
+#[must_use]
+pub fn find_repositories_at_commit(
+    archive: &Archive,
+    stele_name: &str,
+    auth_commit: &str,
+) -> Option<Repositories> {
+    let stele = archive.stelae.get(stele_name)?;
+    stele
+        .get_repositories_at_commit(auth_commit)
+        .map_err(|err| {
+            tracing::warn!("Unable to read repositories of {stele_name} at {auth_commit}: {err}");
+        })
+        .ok()
+        .flatten()
+        .or_else(|| stele.repositories.clone())
+}
+
+/// Find the blob at `path` of the data `repository` at its commit `mapped` to `on_date`.
+///
+/// If the mapped commit lacks the blob, up to [`FALLBACK_COMMITS`] earlier commits of the same
+/// publication on or before the date are looked through, latest first.
+///
+/// # Errors
+/// Errors if can't establish a connection to the database, or the repository cannot be opened.
+async fn find_blob_on_date(
+    db: &DatabaseConnection,
+    archive_path: &Path,
+    repository: &Repository,
+    mapped: &DataRepoCommits,
+    on_date: &NaiveDate,
+    path: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let earlier =
+        data_repo_commits::Manager::find_all_by_publication_and_repo_type_on_or_before_date(
+            db,
+            &mapped.publication_id,
+            &mapped.repo_type,
+            on_date,
+            FALLBACK_COMMITS,
+        )
+        .await?;
+    let repo = Repo::new(archive_path, &repository.get_org(), &repository.get_name())?;
+    let commits = iter::once(&mapped.commit_hash).chain(
+        earlier
+            .iter()
+            .map(|earlier_commit| &earlier_commit.commit_hash)
+            .filter(|&commit_hash| *commit_hash != mapped.commit_hash),
+    );
+    Ok(commits
+        .into_iter()
+        .find_map(|commit_hash| repo.get_bytes_at_path(commit_hash, path).ok()))
+}
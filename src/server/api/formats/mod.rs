@@ -0,0 +1,550 @@
+//! Discovery of the formats a document is available in.
+//!
+//! A document is available in every typed data repository of its stele that has a blob for it,
+//! e.g. `a/b/c.html` in the html repository, `a/b/c.xml` in the xml repository and
+//! `a/b/c/index.rdf` in the rdf repository.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{
+    http::header::{self, Header as _},
+    web, HttpRequest, HttpResponse, Responder,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{models::data_repo_commits, DatabaseConnection},
+    server::{base_path::BasePath, errors::HTTPError},
+    stelae::{stele::Stele, types::repositories::Languages},
+    utils::{git::Repo, paths::normalize_path},
+};
+
+use super::links::{find_repository_commits, RepositoryCommit};
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use super::timeline::find_effective_periods;
+use super::versions::clean_url_path;
+use crate::utils::http::{respond_json, respond_text};
+
+/// Maximum number of commits of a publication looked through for a document on a date.
+pub const FALLBACK_COMMITS: i64 = 10;
+
+/// A typed data repository of a stele, that a document may be available in.
+#[derive(Debug, Clone)]
+pub struct Alternate {
+    /// Qualified name of the data repository, e.g. `org-name/law-xml`.
+    pub repository: String,
+    /// Type of the data repository, also the file extension of its documents, e.g. `xml`.
+    pub repo_type: String,
+    /// Media type of the documents of the data repository.
+    pub media_type: &'static str,
+    /// Url prefix the data repository is served under, e.g. `/_xml`. Empty if served at the root.
+    pub url_prefix: String,
+}
+
+/// A format a document is available in.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Format {
+    /// Type of the data repository the document is found in, e.g. `xml`.
+    pub repo_type: String,
+    /// Media type of the document.
+    pub media_type: String,
+    /// Url of the current document in this format.
+    pub url: String,
+    /// Qualified name of the data repository the document is found in.
+    pub repository: String,
+}
+
+impl Format {
+    /// The format, with its url under the `base_path` the archive is served under.
+    #[must_use]
+    pub fn mounted(self, base_path: &BasePath) -> Self {
+        Self {
+            url: base_path.url(&self.url),
+            ..self
+        }
+    }
+}
+
+/// Response of the formats endpoint.
+#[derive(Debug, Serialize)]
+pub struct Formats {
+    /// The requested document path.
+    pub path: String,
+    /// Date of the version the formats were looked up in, or `None` for the current version.
+    pub date: Option<NaiveDate>,
+    /// Formats the document is available in.
+    pub formats: Vec<Format>,
+    /// Persistent identifiers of the document, e.g. its ELI.
+    pub identifiers: Vec<String>,
+}
+
+/// Response of the formats endpoint for a document that does not exist on the requested date.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotFoundOnDate {
+    /// The requested document path.
+    pub path: String,
+    /// The requested date.
+    pub date: NaiveDate,
+    /// Codified date of the first version of the document, if it exists in any version.
+    pub first_date: Option<String>,
+    /// Explanation of why the document was not found.
+    pub message: String,
+}
+
+impl NotFoundOnDate {
+    /// Explain that the document at `path` does not exist on `date`.
+    #[must_use]
+    pub fn new(path: String, date: NaiveDate, first_date: Option<String>) -> Self {
+        let message = first_date.as_deref().map_or_else(
+            || format!("No version of {path} exists on or before {date}."),
+            |first| {
+                format!(
+                    "No version of {path} exists on {date}. The first version is dated {first}."
+                )
+            },
+        );
+        Self {
+            path,
+            date,
+            first_date,
+            message,
+        }
+    }
+}
+
+/// Query parameters of the formats endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Look up the formats of the version of this date. Defaults to the current version.
+    pub date: Option<NaiveDate>,
+}
+
+/// Representations of a current document that can be negotiated with the `Accept` header,
+/// or chosen with the `?format=` query parameter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// The html document, as stored.
+    #[default]
+    Html,
+    /// The document in the xml data repository of the stele, e.g. Akoma Ntoso.
+    Xml,
+    /// The document in the json data repository of the stele, or its metadata and text.
+    Json,
+}
+
+impl Representation {
+    /// Find the representation named `format`, i.e. `html`, `xml` or `json`.
+    #[must_use]
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "html" => Some(Self::Html),
+            "xml" => Some(Self::Xml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Find the representation of the media range `media_type`, e.g. `application/xml`.
+    ///
+    /// The wildcard range `*/*` is answered with html.
+    #[must_use]
+    pub fn from_media_type(media_type: &mime::Mime) -> Option<Self> {
+        match (media_type.type_(), media_type.subtype()) {
+            (mime::STAR, mime::STAR) | (mime::TEXT, mime::HTML | mime::STAR) => Some(Self::Html),
+            (mime::APPLICATION | mime::TEXT, mime::XML) => Some(Self::Xml),
+            (mime::APPLICATION, mime::JSON) => Some(Self::Json),
+            (mime::APPLICATION, subtype) if subtype.as_str() == "xhtml" => Some(Self::Html),
+            (mime::APPLICATION, _) if media_type.suffix() == Some(mime::XML) => Some(Self::Xml),
+            (mime::APPLICATION, _) if media_type.suffix() == Some(mime::JSON) => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Negotiate the representation of the document requested by `req`.
+    ///
+    /// The `?format=` query parameter takes precedence over the `Accept` header. Media ranges
+    /// are tried by preference, and requests accepting no known representation are answered
+    /// with html.
+    ///
+    /// # Errors
+    /// Errors with the requested format if `?format=` names an unknown representation.
+    pub fn negotiate(req: &HttpRequest) -> Result<Self, String> {
+        let format = web::Query::<NegotiationParams>::from_query(req.query_string())
+            .ok()
+            .and_then(|params| params.into_inner().format);
+        if let Some(requested) = format {
+            return Self::from_format(&requested).ok_or(requested);
+        }
+        Ok(header::Accept::parse(req)
+            .ok()
+            .and_then(|accept| accept.ranked().iter().find_map(Self::from_media_type))
+            .unwrap_or_default())
+    }
+
+    /// Type of the data repositories holding the representation, e.g. `xml`.
+    #[must_use]
+    pub const fn repo_type(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Xml => "xml",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Query parameters of current documents that select their representation.
+#[derive(Debug, Deserialize)]
+pub struct NegotiationParams {
+    /// Name of the representation, overriding the `Accept` header.
+    pub format: Option<String>,
+    /// Language of the document, overriding the `Accept-Language` header.
+    pub lang: Option<String>,
+}
+
+/// Negotiate the language of the document requested by `req`, among the declared `languages`.
+///
+/// The `?lang=` query parameter takes precedence over the `Accept-Language` header. Language
+/// ranges are tried by preference, and requests accepting no declared language are answered in
+/// the default language.
+///
+/// # Errors
+/// Errors with the requested language if `?lang=` names an undeclared language.
+pub fn negotiate_language(req: &HttpRequest, languages: &Languages) -> Result<String, String> {
+    let lang = web::Query::<NegotiationParams>::from_query(req.query_string())
+        .ok()
+        .and_then(|params| params.into_inner().lang);
+    if let Some(requested) = lang {
+        return languages
+            .find(&requested)
+            .map(str::to_owned)
+            .ok_or(requested);
+    }
+    let accepted = header::AcceptLanguage::parse(req).ok().and_then(|accept| {
+        accept
+            .ranked()
+            .into_iter()
+            .find_map(|preference| match preference {
+                header::Preference::Specific(tag) => languages.find(tag.as_str()),
+                header::Preference::Any => None,
+            })
+            .map(str::to_owned)
+    });
+    Ok(accepted.unwrap_or_else(|| languages.default.clone()))
+}
+
+/// Media type of the documents of data repositories of type `repo_type`, if it is a document format.
+#[must_use]
+pub fn media_type(repo_type: &str) -> Option<&'static str> {
+    match repo_type {
+        "html" => Some("text/html"),
+        "xml" => Some("application/xml"),
+        "rdf" => Some("application/rdf+xml"),
+        "pdf" => Some("application/pdf"),
+        "json" => Some("application/json"),
+        _ => None,
+    }
+}
+
+/// Find the data repositories of the `stele` that documents may be available in.
+#[must_use]
+pub fn alternates_of(stele: &Stele) -> Vec<Alternate> {
+    stele
+        .repositories
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+        .filter_map(|repository| {
+            let repo_type = repository.custom.repository_type.as_ref()?.as_str();
+            Some(Alternate {
+                repository: repository.name.clone(),
+                repo_type: repo_type.to_owned(),
+                media_type: media_type(repo_type)?,
+                url_prefix: repository
+                    .custom
+                    .scope
+                    .as_deref()
+                    .map(|scope| format!("/{}", scope.trim_matches('/')))
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// The path of the document at the normalized `path`, without its file extension or index file.
+///
+/// E.g. both `a/b/c.html` and `a/b/c/index.xml` are the document `a/b/c`.
+#[must_use]
+pub fn document_stem(path: &str) -> &str {
+    let (parent, file) = path.rsplit_once('/').unwrap_or(("", path));
+    match file.rsplit_once('.') {
+        Some(("index", _)) => parent,
+        Some((stem, extension)) if !stem.is_empty() => path
+            .strip_suffix(extension)
+            .and_then(|without_extension| without_extension.strip_suffix('.'))
+            .unwrap_or(path),
+        _ => path,
+    }
+}
+
+impl Alternate {
+    /// Find the document `stem` in `repo`, opened at `commitish`.
+    #[must_use]
+    pub fn find(&self, repo: &Repo, commitish: &str, stem: &str) -> Option<Format> {
+        self.locate(repo, commitish, stem)
+            .map(|blob_path| self.format(&blob_path))
+    }
+
+    /// Find the document `stem` at the mapped `commit` of a date.
+    ///
+    /// If the mapped commit lacks the document, up to [`FALLBACK_COMMITS`] earlier commits of
+    /// the same publication on or before `date` are looked through, latest first.
+    ///
+    /// # Errors
+    /// Errors if can't establish a connection to the database.
+    pub async fn find_on_date(
+        &self,
+        db: &DatabaseConnection,
+        commit: &RepositoryCommit,
+        stem: &str,
+        date: &NaiveDate,
+    ) -> anyhow::Result<Option<Format>> {
+        if let Some(found) = self.find(&commit.repo, &commit.commitish, stem) {
+            return Ok(Some(found));
+        }
+        let Some(publication_id) = commit.publication_id.as_deref() else {
+            return Ok(None);
+        };
+        let earlier =
+            data_repo_commits::Manager::find_all_by_publication_and_repo_type_on_or_before_date(
+                db,
+                publication_id,
+                &self.repo_type,
+                date,
+                FALLBACK_COMMITS,
+            )
+            .await?;
+        Ok(earlier
+            .iter()
+            .filter(|earlier_commit| earlier_commit.commit_hash != commit.commitish)
+            .find_map(|earlier_commit| self.find(&commit.repo, &earlier_commit.commit_hash, stem)))
+    }
+
+    /// Find the path of the blob of the document `stem` in `repo`, opened at `commitish`.
+    ///
+    /// The document is looked up as `{stem}.{type}`, then as `{stem}/index.{type}`.
+    #[must_use]
+    pub fn locate(&self, repo: &Repo, commitish: &str, stem: &str) -> Option<String> {
+        let extension = &self.repo_type;
+        let candidates = if stem.is_empty() {
+            vec![format!("index.{extension}")]
+        } else {
+            vec![
+                format!("{stem}.{extension}"),
+                format!("{stem}/index.{extension}"),
+            ]
+        };
+        candidates
+            .into_iter()
+            .find(|candidate| repo.has_blob(commitish, candidate))
+    }
+
+    /// The format of the document found in the data repository at `blob_path`.
+    #[must_use]
+    pub fn format(&self, blob_path: &str) -> Format {
+        Format {
+            repo_type: self.repo_type.clone(),
+            media_type: self.media_type.to_owned(),
+            url: format!("{}/{blob_path}", self.url_prefix),
+            repository: self.repository.clone(),
+        }
+    }
+}
+
+/// List the formats the document at `/_api/formats/{path}` is available in.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+/// With a `date`, a document missing from the commit mapped to the date is looked up in earlier
+/// commits of the same publication. If it is found in no format, `404 Not Found` is answered
+/// with a JSON explanation giving the date of the first version of the document.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn formats(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    params: web::Query<Params>,
+) -> impl Responder {
+    let stele_name = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let Some(stele) = data.archive().stelae.get(&stele_name) else {
+        return respond_text(
+            HttpResponse::NotFound(),
+            format!("Stele {stele_name} not found."),
+        );
+    };
+    let path = match normalize_path(req.match_info().get("path").unwrap_or_default()) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    let commits = match find_repository_commits(
+        data.archive(),
+        data.db().for_stele(&stele_name),
+        &stele_name,
+        params.date.as_ref(),
+    )
+    .await
+    {
+        Ok(commits) => commits,
+        Err(err) => {
+            tracing::error!("Error finding data repositories of stele {stele_name}: {err:?}");
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let stem = document_stem(&path);
+    let alternates = alternates_of(stele);
+    let mut found: Vec<Format> = vec![];
+    for commit in &commits {
+        let Some(alternate) = alternates
+            .iter()
+            .find(|alternate| alternate.repository == commit.name)
+        else {
+            continue;
+        };
+        let lookup = match params.date.as_ref() {
+            None => Ok(alternate.find(&commit.repo, &commit.commitish, stem)),
+            Some(on_date) => {
+                alternate
+                    .find_on_date(data.db().for_stele(&stele_name), commit, stem, on_date)
+                    .await
+            }
+        };
+        match lookup {
+            Ok(format) => found.extend(format),
+            Err(err) => {
+                tracing::error!("Error finding {path} in {}: {err:?}", commit.name);
+                return respond_text(
+                    HttpResponse::InternalServerError(),
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        }
+    }
+    if let (Some(on_date), true) = (params.date, found.is_empty()) {
+        let periods = find_effective_periods(
+            data.db().for_stele(&stele_name),
+            &stele_name,
+            &clean_url_path(stem),
+            None,
+        )
+        .await;
+        let first_date = match periods {
+            Ok(found_periods) => found_periods
+                .and_then(|(_, effective)| effective.into_iter().next())
+                .map(|period| period.codified_date),
+            Err(err) => {
+                tracing::error!("Error finding the effective periods of {path}: {err:?}");
+                return respond_text(
+                    HttpResponse::InternalServerError(),
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        };
+        return respond_json(
+            HttpResponse::NotFound(),
+            &NotFoundOnDate::new(path, on_date, first_date),
+        );
+    }
+    let base_path = BasePath::of(&req);
+    respond_json(
+        HttpResponse::Ok(),
+        &Formats {
+            identifiers: data.identifiers.of_document(&stele_name, &path),
+            path,
+            date: params.date,
+            formats: found
+                .into_iter()
+                .map(|format| format.mounted(&base_path))
+                .collect(),
+        },
+    )
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::api::formats::{
+        document_stem, negotiate_language, NotFoundOnDate, Representation,
+    };
+    use crate::stelae::types::repositories::{LanguageNaming, Languages};
+    use actix_web::test::TestRequest;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_document_stem_when_extension_or_index_expect_stripped() {
+        let cut = document_stem;
+        assert_eq!(cut("a/b/c.html"), "a/b/c");
+        assert_eq!(cut("a/b/c/index.xml"), "a/b/c");
+        assert_eq!(cut("index.html"), "");
+        assert_eq!(cut("a/b"), "a/b");
+        assert_eq!(cut(".well-known"), ".well-known");
+    }
+
+    #[test]
+    fn test_from_media_type_when_known_ranges_expect_representation() {
+        let cut = |media_type: &str| Representation::from_media_type(&media_type.parse().unwrap());
+        assert_eq!(cut("application/xml"), Some(Representation::Xml));
+        assert_eq!(cut("application/akn+xml"), Some(Representation::Xml));
+        assert_eq!(cut("application/json"), Some(Representation::Json));
+        assert_eq!(cut("text/html"), Some(Representation::Html));
+        assert_eq!(cut("*/*"), Some(Representation::Html));
+        assert_eq!(cut("image/png"), None);
+    }
+
+    #[test]
+    fn test_not_found_on_date_when_first_date_known_expect_it_in_message() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let cut = NotFoundOnDate::new("a/b/c".to_owned(), date, Some("2021-06-01".to_owned()));
+        assert_eq!(
+            cut.message,
+            "No version of a/b/c exists on 2020-01-01. The first version is dated 2021-06-01."
+        );
+        let cut = NotFoundOnDate::new("a/b/c".to_owned(), date, None);
+        assert_eq!(
+            cut.message,
+            "No version of a/b/c exists on or before 2020-01-01."
+        );
+    }
+
+    #[test]
+    fn test_negotiate_language_when_query_or_header_expect_declared_language() {
+        let languages = Languages {
+            default: "en".to_owned(),
+            variants: vec!["es".to_owned()],
+            naming: LanguageNaming::Suffix,
+        };
+        let cut = |req: TestRequest| negotiate_language(&req.to_http_request(), &languages);
+        let accept = |value| TestRequest::get().insert_header(("Accept-Language", value));
+        assert_eq!(
+            cut(accept("fr, es-MX;q=0.8, en;q=0.5")),
+            Ok("es".to_owned())
+        );
+        assert_eq!(cut(accept("fr")), Ok("en".to_owned()));
+        assert_eq!(cut(TestRequest::get()), Ok("en".to_owned()));
+        assert_eq!(cut(accept("es").uri("/a?lang=en")), Ok("en".to_owned()));
+        assert_eq!(
+            cut(TestRequest::get().uri("/a?lang=fr")),
+            Err("fr".to_owned())
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Resolution of persistent identifiers of documents, e.g. European Legislation Identifiers (ELI).
+//!
+//! Identifiers are mapped to document paths in the `targets/identifiers.json` file of a stele,
+//! and ingested into the `identifiers` table by `stelae update`. Steles assign identifiers
+//! independently, so an identifier is resolved in the stele of the request first.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+
+use crate::{
+    db::{
+        models::identifier::{self, Identifier},
+        Databases,
+    },
+    server::{base_path::BasePath, errors::HTTPError},
+    stelae::archive::Archive,
+};
+
+use super::formats::{alternates_of, document_stem};
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use crate::utils::http::respond_text;
+
+/// Persistent identifiers of documents, by identifier and by document.
+///
+/// Loaded from the database at start-up and shared by all workers, so identifiers are resolved
+/// without a database round trip per request.
+#[derive(Debug, Clone, Default)]
+pub struct Identifiers {
+    /// Identifiers, keyed by stele and document path without file extension or index file.
+    by_document: Arc<HashMap<(String, String), Vec<String>>>,
+    /// Identified documents, keyed by stele and identifier.
+    documents: Arc<HashMap<(String, String), Identifier>>,
+    /// Qualified names of the steles assigning each identifier, keyed by identifier.
+    steles: Arc<HashMap<String, Vec<String>>>,
+}
+
+impl Identifiers {
+    /// Load all identifiers recorded in the databases.
+    ///
+    /// # Errors
+    /// Errors if the identifiers cannot be read from a database.
+    pub async fn load(db: &Databases) -> anyhow::Result<Self> {
+        let mut identifiers = vec![];
+        for conn in db.all() {
+            identifiers.extend(identifier::Manager::find_all(conn).await?);
+        }
+        Ok(Self::from(identifiers))
+    }
+
+    /// The document identified by `id` in the stele `stele`, or in the only other stele that
+    /// assigns it, if any.
+    #[must_use]
+    pub fn resolve(&self, stele: &str, id: &str) -> Option<&Identifier> {
+        let trimmed = id.trim_matches('/');
+        let steles = self.steles.get(trimmed)?;
+        let assigning = steles
+            .first()
+            .filter(|_| steles.len() == 1)
+            .map_or(stele, String::as_str);
+        self.documents
+            .get(&(stele.to_owned(), trimmed.to_owned()))
+            .or_else(|| {
+                self.documents
+                    .get(&(assigning.to_owned(), trimmed.to_owned()))
+            })
+    }
+
+    /// The identifiers of the document at the normalized `path` of the stele `stele`, sorted.
+    #[must_use]
+    pub fn of_document(&self, stele: &str, path: &str) -> Vec<String> {
+        self.by_document
+            .get(&(stele.to_owned(), document_stem(path).to_owned()))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl From<Vec<Identifier>> for Identifiers {
+    fn from(mut identifiers: Vec<Identifier>) -> Self {
+        identifiers.sort_by(|first, second| {
+            (&first.stele, &first.identifier).cmp(&(&second.stele, &second.identifier))
+        });
+        let mut by_document: HashMap<(String, String), Vec<String>> = HashMap::new();
+        let mut steles: HashMap<String, Vec<String>> = HashMap::new();
+        for found in &identifiers {
+            by_document
+                .entry((found.stele.clone(), document_stem(&found.path).to_owned()))
+                .or_default()
+                .push(found.identifier.clone());
+            steles
+                .entry(found.identifier.clone())
+                .or_default()
+                .push(found.stele.clone());
+        }
+        let documents = identifiers
+            .into_iter()
+            .map(|found| ((found.stele.clone(), found.identifier.clone()), found))
+            .collect();
+        Self {
+            by_document: Arc::new(by_document),
+            documents: Arc::new(documents),
+            steles: Arc::new(steles),
+        }
+    }
+}
+
+/// The url of the identifier `id`, e.g. `/eli/us/ca/code/title/1` or `urn:lex:us-ca:code:title.1`.
+#[must_use]
+pub fn identifier_url(id: &str) -> String {
+    if id.starts_with("urn:") {
+        return id.to_owned();
+    }
+    format!("/{id}")
+}
+
+/// Resolve the persistent identifier at `/eli/{path}` or `/urn:lex:{name}`.
+///
+/// The identifier is resolved in the stele of the request, see [`AccessDecision`], or else in
+/// the only other stele that assigns it. Responds with `303 See Other` redirecting to the
+/// current document identified.
+#[tracing::instrument(skip(req, data, identifiers, access))]
+pub async fn resolve(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    identifiers: web::Data<Identifiers>,
+    access: AccessDecision,
+) -> impl Responder {
+    let id = req.match_info().get("identifier").unwrap_or_default();
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let Some(found) = identifiers.resolve(&stele, id) else {
+        tracing::debug!("{id}: unknown identifier");
+        return respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string());
+    };
+    HttpResponse::SeeOther()
+        .insert_header((
+            header::LOCATION,
+            BasePath::of(&req).url(&document_url(data.archive(), found)),
+        ))
+        .finish()
+}
+
+/// The url of the current html document identified by `found`, under the scope its stele serves
+/// html documents under, e.g. `/_html/a/b/c.html`.
+fn document_url(archive: &Archive, found: &Identifier) -> String {
+    let url_prefix = archive
+        .stelae
+        .get(&found.stele)
+        .map(alternates_of)
+        .and_then(|alternates| {
+            alternates
+                .into_iter()
+                .find(|alternate| alternate.repo_type == "html")
+        })
+        .map(|alternate| alternate.url_prefix)
+        .unwrap_or_default();
+    format!("{url_prefix}/{}", found.path)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::identifier::Identifier;
+    use crate::server::api::identifiers::Identifiers;
+
+    fn identifier(stele: &str, id: &str, path: &str) -> Identifier {
+        Identifier {
+            identifier: id.to_owned(),
+            stele: stele.to_owned(),
+            path: path.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_when_identified_expect_document_and_identifiers_of_document() {
+        let stele = "test_org/law";
+        let cut = Identifiers::from(vec![
+            identifier(stele, "urn:lex:us:act:1", "a/b/c.html"),
+            identifier(stele, "eli/us/act/1", "a/b/c.html"),
+        ]);
+        assert_eq!(
+            cut.resolve(stele, "/eli/us/act/1/").unwrap().path,
+            "a/b/c.html"
+        );
+        assert!(cut.resolve(stele, "eli/us/act/2").is_none());
+        assert_eq!(
+            cut.of_document("test_org/law", "a/b/c/index.html"),
+            vec!["eli/us/act/1", "urn:lex:us:act:1"]
+        );
+        assert!(cut.of_document("test_org/other", "a/b/c").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_when_assigned_by_several_steles_expect_document_of_request_stele() {
+        let cut = Identifiers::from(vec![
+            identifier("test_org/law", "eli/us/act/1", "a/b/c.html"),
+            identifier("test_org/other", "eli/us/act/1", "d/e/f.html"),
+            identifier("test_org/other", "eli/us/act/2", "d/e/g.html"),
+        ]);
+        let other = cut.resolve("test_org/other", "eli/us/act/1").unwrap();
+        assert_eq!(other.path, "d/e/f.html");
+        let law = cut.resolve("test_org/law", "eli/us/act/1").unwrap();
+        assert_eq!(law.path, "a/b/c.html");
+        let only = cut.resolve("test_org/law", "eli/us/act/2").unwrap();
+        assert_eq!(only.stele, "test_org/other");
+        assert!(cut.resolve("test_org/third", "eli/us/act/1").is_none());
+    }
+}
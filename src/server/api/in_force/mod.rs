@@ -0,0 +1,110 @@
+//! Handler resolving which text of a document was in force on a date.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::server::base_path::BasePath;
+use crate::server::errors::HTTPError;
+
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use super::timeline::{find_effective_periods, EffectivePeriod};
+use super::versions::clean_url_path;
+use crate::utils::http::{respond_json, respond_text};
+
+/// Query string of the in-force endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Date on which the text should have been in force.
+    pub on: NaiveDate,
+    /// Name of the publication to resolve the text from. Defaults to the latest publication.
+    pub publication: Option<String>,
+    /// Set to `json` to return the resolution instead of redirecting to it.
+    pub format: Option<String>,
+}
+
+/// Explanation of which text of a document was in force on a date.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InForce {
+    /// Url path of the document.
+    pub path: String,
+    /// Requested date.
+    pub on: String,
+    /// Name of the publication the text was resolved from.
+    pub publication: String,
+    /// Effective period of the text in force on the requested date.
+    pub period: EffectivePeriod,
+    /// Url of the text in force on the requested date.
+    pub url: String,
+}
+
+/// Redirect to the text of the document at `path` that was in force on the `on` date.
+///
+/// Unlike `/_date/{date}/...`, which serves the text codified on a date, the text is chosen
+/// by its effective period. With `format=json`, the resolution is returned instead.
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn in_force(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    path: web::Path<String>,
+    params: web::Query<Params>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let url = clean_url_path(&path);
+    let on = params.on.to_string();
+    let found = match find_effective_periods(
+        data.db().for_stele(&stele),
+        &stele,
+        &url,
+        params.publication.as_deref(),
+    )
+    .await
+    {
+        Ok(found) => found,
+        Err(err) => {
+            tracing::error!("Error finding timeline of {url}: {err:?}");
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let Some((active_publication, periods)) = found else {
+        return respond_text(
+            HttpResponse::NotFound(),
+            format!("No document found at {url}."),
+        );
+    };
+    let Some(period) = periods.into_iter().find(|period| period.contains(&on)) else {
+        return respond_text(
+            HttpResponse::NotFound(),
+            format!("No text of {url} was in force on {on}."),
+        );
+    };
+    let resolved = InForce {
+        url: BasePath::of(&req).url(&format!("/_date/{}{url}", period.codified_date)),
+        path: url,
+        on,
+        publication: active_publication.name,
+        period,
+    };
+    if params.format.as_deref() == Some("json") {
+        return respond_json(HttpResponse::Ok(), &resolved);
+    }
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, resolved.url))
+        .finish()
+}
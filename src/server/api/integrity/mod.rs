@@ -0,0 +1,220 @@
+//! Handler serving the Subresource Integrity manifest of the static assets of the current documents.
+//!
+//! `/_api/asset-integrity.json` maps the url of every script and stylesheet at the `HEAD` commit
+//! of the served data repositories of a stele to its `sha384` integrity value, so sites embedding
+//! the official assets can hot-link them with `<script integrity="...">`.
+//!
+//! The manifests are computed off the worker threads, and ahead of traffic by
+//! `stelae serve --warmup`, see [`warm`].
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use actix_web::http::header::{CacheControl, CacheDirective, VARY};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use git2::{ObjectType, Oid, TreeWalkMode, TreeWalkResult};
+use sha2::{Digest as _, Sha384};
+
+use crate::{
+    server::{base_path::BasePath, cache::Cache, errors::HTTPError},
+    stelae::{archive::Archive, types::repositories::Repository},
+    utils::{
+        git::Repo,
+        http::{respond_json, respond_text},
+    },
+};
+
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use super::versions::{STELE_HEADER, STELE_SCOPE_HEADER};
+
+/// Extensions of the static assets listed in the manifest.
+const ASSET_EXTENSIONS: [&str; 2] = ["js", "css"];
+
+/// Time shared caches may serve a manifest for, in seconds.
+const MANIFEST_MAX_AGE: u32 = 60;
+
+/// Serve the `sha384` integrity values of the scripts and stylesheets of the current documents,
+/// keyed by url.
+///
+/// The stele is selected by [`AccessDecision::stele`], so responses vary by the stele headers. The
+/// urls are mounted under the base path of the request. The manifest is cached until the `HEAD`
+/// commit of a served data repository moves.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn asset_integrity(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let lookup = web::block({
+        let (cache, archive_path) = (data.cache().clone(), data.archive().path.clone());
+        let (name, repositories) = (stele.clone(), served_repositories(data.archive(), &stele));
+        move || find_manifest(&cache, &archive_path, &name, &repositories)
+    });
+    let found = lookup.await.unwrap_or_else(|err| Err(err.into()));
+    match found {
+        Ok(manifest) => {
+            let base_path = BasePath::of(&req);
+            let mounted = manifest
+                .into_iter()
+                .map(|(url, value)| (base_path.url(&url), value))
+                .collect::<BTreeMap<_, _>>();
+            let mut response = HttpResponse::Ok();
+            response
+                .insert_header(CacheControl(vec![
+                    CacheDirective::Public,
+                    CacheDirective::MaxAge(MANIFEST_MAX_AGE),
+                ]))
+                .append_header((VARY, STELE_HEADER))
+                .append_header((VARY, STELE_SCOPE_HEADER));
+            respond_json(response, &mounted)
+        }
+        Err(err) => {
+            tracing::error!("Error computing the asset integrity of {stele}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Compute the integrity manifest of every stele of the `archive` into the `cache`, so the first
+/// requests do not hash every asset.
+///
+/// Returns the number of manifests computed.
+#[expect(
+    clippy::iter_over_hash_type,
+    reason = "Manifests are computed independently of each other"
+)]
+pub fn warm(cache: &Cache, archive: &Archive) -> usize {
+    let mut warmed: usize = 0;
+    for name in archive.stelae.keys() {
+        let repositories = served_repositories(archive, name);
+        match find_manifest(cache, &archive.path, name, &repositories) {
+            Ok(_) => warmed = warmed.saturating_add(1),
+            Err(err) => tracing::warn!("Unable to compute the asset integrity of {name}: {err:?}"),
+        }
+    }
+    warmed
+}
+
+/// Find the integrity manifest of the static assets of the served `repositories` of the `stele`,
+/// in the `cache` if computed at their current `HEAD` commits.
+///
+/// The urls of the manifest are not mounted under a base path.
+///
+/// # Errors
+/// Errors if a repository cannot be opened, or its `HEAD` commit cannot be read.
+fn find_manifest(
+    cache: &Cache,
+    archive_path: &Path,
+    stele: &str,
+    repositories: &[Repository],
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut opened = vec![];
+    for repository in repositories {
+        let repo = Repo::new(archive_path, &repository.get_org(), &repository.get_name())?;
+        let head = repo.repo.head()?.peel_to_commit()?.id();
+        opened.push((repository, repo, head));
+    }
+    let heads = opened
+        .iter()
+        .map(|&(_, _, head)| head.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    if let Some(manifest) = cache.asset_integrity(stele, &heads) {
+        return Ok(manifest);
+    }
+    let mut manifest = BTreeMap::new();
+    for (repository, repo, head) in opened {
+        let url_prefix = repository
+            .custom
+            .scope
+            .as_deref()
+            .map(|scope| format!("/{}", scope.trim_matches('/')))
+            .unwrap_or_default();
+        for (path, blob_id) in find_assets(&repo.repo, head)? {
+            let content = repo.repo.find_blob(blob_id)?;
+            manifest
+                .entry(format!("{url_prefix}/{path}"))
+                .or_insert_with(|| integrity(content.content()));
+        }
+    }
+    cache.insert_asset_integrity(stele.to_owned(), heads, manifest.clone());
+    Ok(manifest)
+}
+
+/// Paths and ids of the scripts and stylesheets of the tree of the `commit` of `repo`.
+///
+/// # Errors
+/// Errors if the commit is not found, or its tree cannot be walked.
+fn find_assets(repo: &git2::Repository, commit: Oid) -> anyhow::Result<Vec<(String, Oid)>> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut assets = vec![];
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path = format!("{root}{}", entry.name().unwrap_or_default());
+        if entry.kind() == Some(ObjectType::Blob) && is_asset(&path) {
+            assets.push((path, entry.id()));
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(assets)
+}
+
+/// The served data repositories of the `stele` of the `archive`, sorted.
+fn served_repositories(archive: &Archive, stele: &str) -> Vec<Repository> {
+    archive
+        .stelae
+        .get(stele)
+        .and_then(|found| found.repositories.as_ref())
+        .map(|found| {
+            found
+                .get_sorted()
+                .into_iter()
+                .filter(|repository| repository.is_served())
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the blob at `path` is a script or a stylesheet.
+fn is_asset(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ASSET_EXTENSIONS.contains(&extension))
+}
+
+/// Subresource Integrity value of `content`, e.g. `sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC`.
+fn integrity(content: &[u8]) -> String {
+    format!("sha384-{}", STANDARD.encode(Sha384::digest(content)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::integrity::{integrity, is_asset};
+
+    #[test]
+    fn test_integrity_when_script_expect_sha384_value() {
+        let cut = integrity;
+        let actual = cut(b"alert('Hello, world.');");
+        let expected = "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO";
+        assert_eq!(actual, expected);
+        assert!(is_asset("_static/app.js"));
+        assert!(!is_asset("a/b/index.html"));
+    }
+}
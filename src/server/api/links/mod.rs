@@ -0,0 +1,272 @@
+//! Handlers for checking whether document paths resolve, and for reporting broken links.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Context as _;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        models::{broken_link, data_repo_commits},
+        DatabaseConnection,
+    },
+    server::errors::HTTPError,
+    stelae::archive::Archive,
+    utils::{git::Repo, paths::normalize_path},
+};
+
+use super::policy::AccessDecision;
+use super::response::ApiVersion;
+use super::state::{App as AppState, Global as _};
+use super::takedown::Takedowns;
+use crate::utils::http::{respond_json, respond_text};
+
+/// Maximum number of paths checked in one request.
+const MAX_PATHS: usize = 1_000;
+
+/// Request body of the check links endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Body {
+    /// Paths to check, e.g. `/us/ca/cities/san-mateo/codes/1.01`.
+    pub paths: Vec<String>,
+    /// Check the documents as they were on this date. Defaults to the current documents.
+    pub date: Option<NaiveDate>,
+}
+
+/// Query parameters of the broken links endpoint.
+#[derive(Debug, Deserialize)]
+pub struct BrokenLinksParams {
+    /// Only report the broken links of this publication, e.g. `2023-10-22`.
+    pub publication: Option<String>,
+}
+
+/// Status of one checked path.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkStatus {
+    /// The checked path, as requested.
+    pub path: String,
+    /// Whether the path resolves to a document that is served.
+    pub exists: bool,
+    /// HTTP status a request for the path would get.
+    pub status: u16,
+    /// Qualified name of the data repository the path resolves in.
+    pub repository: Option<String>,
+}
+
+/// A data repository of a stele, at the commit documents are looked up in.
+pub struct RepositoryCommit {
+    /// Qualified name of the data repository.
+    pub name: String,
+    /// The opened data repository.
+    pub repo: Repo,
+    /// Commit hash, or `HEAD` for the current documents.
+    pub commitish: String,
+    /// Publication the commit was mapped from, or `None` for the current documents.
+    pub publication_id: Option<String>,
+}
+
+impl RepositoryCommit {
+    /// Whether `path` resolves to a blob at the commit. Invalid request paths never resolve.
+    #[must_use]
+    pub fn resolves(&self, path: &str) -> bool {
+        normalize_path(path).is_ok_and(|blob_path| {
+            self.repo
+                .get_bytes_at_path(&self.commitish, &blob_path)
+                .is_ok()
+        })
+    }
+}
+
+/// Check whether each of the requested paths resolves, for the current documents or on a date.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Paths of documents taken down are reported
+/// with the `451` they are answered with. The paths are looked up in git on the blocking thread
+/// pool. Responses are enveloped as negotiated by [`ApiVersion`].
+#[tracing::instrument(skip(data, body, access))]
+pub async fn check_links(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+    body: web::Json<Body>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    let Body { paths, date } = body.into_inner();
+    if paths.len() > MAX_PATHS {
+        return api_version.respond_error(
+            HttpResponse::BadRequest(),
+            "too_many_paths",
+            format!("At most {MAX_PATHS} paths can be checked at once."),
+        );
+    }
+    let commits = match find_repository_commits(
+        data.archive(),
+        data.db().for_stele(&stele),
+        &stele,
+        date.as_ref(),
+    )
+    .await
+    {
+        Ok(commits) => commits,
+        Err(err) => {
+            tracing::error!("Error finding data repositories of stele {stele}: {err:?}");
+            return api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "internal_error",
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let checked = web::block({
+        let takedowns = data.takedowns.clone();
+        move || {
+            paths
+                .into_iter()
+                .map(|path| check_link(&takedowns, &stele, &commits, path))
+                .collect::<Vec<LinkStatus>>()
+        }
+    });
+    match checked.await {
+        Ok(statuses) => api_version.respond(HttpResponse::Ok(), &statuses),
+        Err(err) => {
+            tracing::error!("Error checking links: {err}");
+            api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "internal_error",
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Status of the `path` of the `stele`, resolved in the first of the data repository `commits`
+/// it is found in unless it is in the `takedowns`.
+fn check_link(
+    takedowns: &Takedowns,
+    stele: &str,
+    commits: &[RepositoryCommit],
+    path: String,
+) -> LinkStatus {
+    let Ok(normalized) = normalize_path(&path) else {
+        return LinkStatus {
+            path,
+            exists: false,
+            status: 400,
+            repository: None,
+        };
+    };
+    if takedowns.find(stele, &normalized).is_some() {
+        return LinkStatus {
+            path,
+            exists: false,
+            status: 451,
+            repository: None,
+        };
+    }
+    let repository = commits
+        .iter()
+        .find(|commit| commit.resolves(&path))
+        .map(|commit| commit.name.clone());
+    LinkStatus {
+        exists: repository.is_some(),
+        status: if repository.is_some() { 200 } else { 404 },
+        path,
+        repository,
+    }
+}
+
+/// List the broken internal links recorded by `stelae update --check-links`.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(data, access))]
+pub async fn broken_links(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    params: web::Query<BrokenLinksParams>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    match broken_link::Manager::find_all_by_stele_and_publication(
+        data.db().for_stele(&stele),
+        &stele,
+        params.publication.as_deref(),
+    )
+    .await
+    {
+        Ok(links) => respond_json(HttpResponse::Ok(), &links),
+        Err(err) => {
+            tracing::error!("Error finding broken links of stele {stele}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Open the data repositories of a stele at the commits documents are looked up in.
+///
+/// Without a date all data repositories are opened at `HEAD`. With a date, every typed
+/// data repository is opened at its latest commit on or before the date that is not of a preview
+/// publication.
+///
+/// # Errors
+/// Errors if the stele is not in the archive, or if a data repository cannot be opened.
+pub async fn find_repository_commits(
+    archive: &Archive,
+    db: &DatabaseConnection,
+    stele_name: &str,
+    date: Option<&NaiveDate>,
+) -> anyhow::Result<Vec<RepositoryCommit>> {
+    let stele = archive
+        .stelae
+        .get(stele_name)
+        .with_context(|| format!("Stele {stele_name} not found in the archive"))?;
+    let mut commits = vec![];
+    for repository in stele
+        .repositories
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+    {
+        let (commitish, publication_id) = match (date, repository.get_type()) {
+            (None, _) => ("HEAD".to_owned(), None),
+            (Some(on_date), Some(repo_type)) => {
+                let Some(data_repo_commit) =
+                    data_repo_commits::Manager::find_latest_by_stele_and_repo_type_on_or_before_date(
+                        db, stele_name, &repo_type, on_date, false,
+                    )
+                    .await?
+                else {
+                    continue;
+                };
+                (
+                    data_repo_commit.commit_hash,
+                    Some(data_repo_commit.publication_id),
+                )
+            }
+            (Some(_), None) => continue,
+        };
+        commits.push(RepositoryCommit {
+            name: repository.name.clone(),
+            repo: Repo::new(&archive.path, &repository.get_org(), &repository.get_name())?,
+            commitish,
+            publication_id,
+        });
+    }
+    Ok(commits)
+}
@@ -0,0 +1,137 @@
+//! Handler exposing metrics of the archive in the Prometheus text format.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use std::fmt::{self, Write as _};
+
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::{
+    db::models::repository_size::{self, RecordedSize},
+    server::{cache::NotFoundHits, errors::HTTPError},
+    utils::http::respond_text,
+};
+
+use super::state::{App as AppState, Global as _};
+
+/// Expose the repository sizes last recorded by `stelae disk-usage` as Prometheus gauges, and
+/// the lookups of missing documents answered from the cache as a counter.
+#[tracing::instrument(skip(data))]
+pub async fn metrics(data: web::Data<AppState>) -> impl Responder {
+    let mut stelae: Vec<&String> = data.archive().stelae.keys().collect();
+    stelae.sort();
+    let mut sizes = vec![];
+    for stele in stelae {
+        match repository_size::Manager::find_all_latest_by_stele(data.db().shared(), stele).await {
+            Ok(found) => sizes.extend(found),
+            Err(err) => {
+                tracing::error!("Error finding repository sizes of stele {stele}: {err:?}");
+                return respond_text(
+                    HttpResponse::InternalServerError(),
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        }
+    }
+    respond_text(
+        HttpResponse::Ok(),
+        render(&sizes, data.cache().not_found_hits()),
+    )
+}
+
+/// Render the recorded repository sizes and the lookups that found nothing answered from the
+/// cache in the Prometheus text format.
+#[must_use]
+pub fn render(sizes: &[RecordedSize], not_found: NotFoundHits) -> String {
+    let mut exposition = String::new();
+    let _infallible = write_sizes(&mut exposition, sizes)
+        .and_then(|()| write_not_found(&mut exposition, not_found));
+    exposition
+}
+
+/// Write the recorded repository sizes as a Prometheus gauge to `out`.
+fn write_sizes(out: &mut String, sizes: &[RecordedSize]) -> fmt::Result {
+    writeln!(
+        out,
+        "# HELP stelae_repository_size_bytes Size on disk of a repository, as last recorded by `stelae disk-usage`."
+    )?;
+    writeln!(out, "# TYPE stelae_repository_size_bytes gauge")?;
+    for size in sizes {
+        writeln!(
+            out,
+            "stelae_repository_size_bytes{{stele=\"{}\",repository=\"{}\"}} {}",
+            escape_label(&size.stele),
+            escape_label(&size.repository),
+            size.bytes
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the lookups that found nothing answered from the cache as a Prometheus counter to `out`.
+fn write_not_found(out: &mut String, not_found: NotFoundHits) -> fmt::Result {
+    writeln!(
+        out,
+        "# HELP stelae_not_found_cache_hits_total Lookups of missing documents answered from the cache."
+    )?;
+    writeln!(out, "# TYPE stelae_not_found_cache_hits_total counter")?;
+    writeln!(
+        out,
+        "stelae_not_found_cache_hits_total{{lookup=\"blob\"}} {}",
+        not_found.blobs
+    )?;
+    writeln!(
+        out,
+        "stelae_not_found_cache_hits_total{{lookup=\"mpath\"}} {}",
+        not_found.mpaths
+    )
+}
+
+/// Escape a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::models::repository_size::RecordedSize;
+    use crate::server::api::metrics::render;
+    use crate::server::cache::NotFoundHits;
+
+    #[test]
+    fn test_render_when_recorded_sizes_expect_gauge_per_repository() {
+        let cut = render;
+        let actual = cut(
+            &[RecordedSize {
+                stele: "org/law".to_owned(),
+                repository: "org/law-html".to_owned(),
+                bytes: 2048,
+                recorded_at: "2026-10-21 09:00:00".to_owned(),
+            }],
+            NotFoundHits::default(),
+        );
+        assert!(actual.contains("# TYPE stelae_repository_size_bytes gauge\n"));
+        assert!(actual.contains(
+            "stelae_repository_size_bytes{stele=\"org/law\",repository=\"org/law-html\"} 2048\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_when_not_found_hits_expect_counter_per_lookup() {
+        let cut = render;
+        let actual = cut(
+            &[],
+            NotFoundHits {
+                blobs: 3,
+                mpaths: 1,
+            },
+        );
+        assert!(actual.contains("# TYPE stelae_not_found_cache_hits_total counter\n"));
+        assert!(actual.contains("stelae_not_found_cache_hits_total{lookup=\"blob\"} 3\n"));
+        assert!(actual.ends_with("stelae_not_found_cache_hits_total{lookup=\"mpath\"} 1\n"));
+    }
+}
@@ -1,5 +1,25 @@
 //! This module contains the API endpoints for the server.
+pub mod archive;
+pub mod compare;
+pub mod dated;
+pub mod formats;
+pub mod identifiers;
+pub mod in_force;
+pub mod integrity;
+pub mod links;
+pub mod metrics;
+pub mod pinned;
+pub mod policy;
+pub mod publications;
+pub mod references;
+pub mod response;
 pub mod routes;
 pub mod serve;
+pub mod snapshot;
 pub mod state;
+pub mod stats;
+pub mod status;
+pub mod suggest;
+pub mod takedown;
+pub mod timeline;
 pub mod versions;
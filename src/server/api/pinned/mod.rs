@@ -0,0 +1,174 @@
+//! Handler serving the documents of a stele at a commit of its historical data repositories.
+//!
+//! Documents are served at `/_commit/{sha}/{path}`, e.g.
+//! `/_commit/0f2f1ef9fa213dcf83e269bc832ab63435cbd4b1/a/b/c`, so they can be cited permanently,
+//! regardless of the commits `stelae update` maps to dates. Only commits `stelae update` recorded
+//! for a publication of the stele are served, so unpublished commits stay private, and commits of
+//! preview publications are served to requests that may see previews only.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+use std::path::Path;
+
+use crate::{
+    db::models::data_repo_commits::{self, DataRepoCommits},
+    server::{
+        base_path::BasePath,
+        errors::HTTPError,
+        timing::{Phase, Timings},
+    },
+    stelae::archive::Archive,
+    utils::{
+        git::Repo,
+        html::prefix_root_relative_urls,
+        http::{get_contenttype, respond_blob, respond_text},
+        paths::normalize_path,
+    },
+};
+
+use super::dated::find_historical_repository;
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use super::takedown::unavailable;
+
+/// Number of hexadecimal digits of a full commit SHA.
+const SHA_LENGTH: usize = 40;
+
+/// Serve the document at `path` of the historical data repository of the stele that has the commit `sha`.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Only full commit SHAs are accepted, so a url
+/// always names a single commit, and SHAs with uppercase digits are redirected permanently to
+/// their lowercase form, so every commit has a single url.
+/// Root-relative urls of html documents are prefixed with `/_commit/{sha}`.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn serve_at_commit(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let sha = req.match_info().get("sha").unwrap_or_default();
+    if !is_full_sha(sha) {
+        return respond_text(
+            HttpResponse::BadRequest(),
+            format!("Invalid commit {sha}, expected a full {SHA_LENGTH} character SHA."),
+        );
+    }
+    let path = match normalize_path(req.match_info().get("path").unwrap_or_default()) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    if sha.bytes().any(|digit| digit.is_ascii_uppercase()) {
+        let canonical = match req.query_string() {
+            "" => format!("/_commit/{}/{path}", sha.to_ascii_lowercase()),
+            query => format!("/_commit/{}/{path}?{query}", sha.to_ascii_lowercase()),
+        };
+        return HttpResponse::MovedPermanently()
+            .insert_header((header::LOCATION, BasePath::of(&req).url(&canonical)))
+            .finish();
+    }
+    if let Some(reason) = data.takedowns.find(&stele, &path) {
+        return unavailable(&path, &reason);
+    }
+    let timings = Timings::of(&req);
+    let recorded = timings.measure_async(
+        Phase::Db,
+        data_repo_commits::Manager::find_by_stele_and_commit_hash(
+            data.db().for_stele(&stele),
+            &stele,
+            sha,
+            access.previews(),
+        ),
+    );
+    let mapped = match recorded.await {
+        Ok(Some(mapped)) => mapped,
+        Ok(None) => {
+            tracing::debug!("{sha}: not a commit of a served publication of {stele}");
+            return respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string());
+        }
+        Err(err) => {
+            tracing::error!("Error finding commit {sha} of {stele}: {err:?}");
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let found = timings.measure(Phase::Git, || {
+        find_blob_at_commit(data.archive(), &stele, &mapped, &path)
+    });
+    let Some(content) = found else {
+        tracing::debug!("{path}: not found at commit {sha}");
+        return respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string());
+    };
+    let contenttype = get_contenttype(&path);
+    let body = if contenttype.0 == mime::TEXT_HTML {
+        timings.measure(Phase::Rewrite, || {
+            let pinned = prefix_root_relative_urls(&content, &format!("/_commit/{sha}"))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("{path}: unable to rewrite urls: {err}");
+                    content
+                });
+            BasePath::of(&req).html(pinned)
+        })
+    } else {
+        content
+    };
+    let mut response = HttpResponse::Ok();
+    response.insert_header(("Cache-Control", "public, max-age=31536000, immutable"));
+    respond_blob(response, contenttype.0, body)
+}
+
+/// Whether `sha` is a full hexadecimal commit SHA, in lowercase or uppercase digits.
+fn is_full_sha(sha: &str) -> bool {
+    sha.len() == SHA_LENGTH && sha.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+/// Find the blob at `path` at the data repository commit `mapped` of the stele `stele_name`, in
+/// the historical data repository of its type listed at its authentication commit, so commits of
+/// repositories removed since are still served.
+fn find_blob_at_commit(
+    archive: &Archive,
+    stele_name: &str,
+    mapped: &DataRepoCommits,
+    path: &str,
+) -> Option<Vec<u8>> {
+    let repository = find_historical_repository(
+        archive,
+        stele_name,
+        &mapped.auth_commit_hash,
+        &mapped.repo_type,
+    )?;
+    let repo = open(&archive.path, &repository.get_org(), &repository.get_name())?;
+    repo.get_bytes_at_path(&mapped.commit_hash, path).ok()
+}
+
+/// Open the data repository `org/name` of the archive, logging why it cannot be opened.
+fn open(archive_path: &Path, org: &str, name: &str) -> Option<Repo> {
+    Repo::new(archive_path, org, name)
+        .map_err(|err| tracing::warn!("Unable to open {org}/{name}: {err}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::pinned::is_full_sha;
+
+    #[test]
+    fn test_is_full_sha_when_abbreviated_or_not_hex_expect_false() {
+        let cut = is_full_sha;
+        assert!(cut("0f2f1ef9fa213dcf83e269bc832ab63435cbd4b1"));
+        assert!(cut("0F2F1EF9FA213DCF83E269BC832AB63435CBD4B1"));
+        assert!(!cut("0f2f1ef"));
+        assert!(!cut("HEAD"));
+        assert!(!cut("0f2f1ef9fa213dcf83e269bc832ab63435cbd4bz"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Access policy of requests, evaluated once per request.
+//!
+//! The stele a request is for, with former names resolved through the configured aliases, and
+//! whether it may see preview publications, are decided the first time a handler extracts an
+//! [`AccessDecision`]. The
+//! decision is kept in the extensions of the request, so every later extraction reuses it.
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload, error::ErrorInternalServerError, web, FromRequest, HttpMessage as _, HttpRequest,
+};
+
+use crate::server::errors::HTTPError;
+use crate::stelae::archive::{Archive, Preview};
+
+use super::state::{App as AppState, Global as _};
+use super::versions::{get_stele_from_request, SteleError, PREVIEW_HEADER};
+
+/// Access of a request to the stelae and publications of the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessDecision {
+    /// Qualified name of the stele the request is for, or why it cannot be selected.
+    stele: Result<String, SteleError>,
+    /// Whether the request carries the configured preview token.
+    previews: bool,
+}
+
+impl AccessDecision {
+    /// Decide the access of the request `req` to the `archive`.
+    #[must_use]
+    pub fn evaluate(req: &HttpRequest, archive: &Archive) -> Self {
+        Self {
+            stele: get_stele_from_request(req, archive),
+            previews: allows_previews(req),
+        }
+    }
+
+    /// Qualified name of the stele the request is for.
+    ///
+    /// The stele is taken from the `X-Stelae` header, or resolved from the `X-Stelae-Scope` header,
+    /// and defaults to the root stele.
+    ///
+    /// # Errors
+    /// Errors if the root stele cannot be found, or the stele headers of the request are not
+    /// valid, see [`get_stele_from_request`].
+    pub fn stele(&self) -> Result<String, SteleError> {
+        self.stele.clone()
+    }
+
+    /// Whether the request may see preview publications.
+    #[must_use]
+    pub const fn previews(&self) -> bool {
+        self.previews
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
+impl FromRequest for AccessDecision {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    /// The access decided for the request `req`, evaluated on first extraction.
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(decision) = req.extensions().get::<Self>() {
+            return ready(Ok(decision.clone()));
+        }
+        let Some(data) = req.app_data::<web::Data<AppState>>() else {
+            return ready(Err(ErrorInternalServerError(
+                HTTPError::InternalServerError,
+            )));
+        };
+        let decision = Self::evaluate(req, data.archive());
+        req.extensions_mut().insert(decision.clone());
+        ready(Ok(decision))
+    }
+}
+
+/// Whether the request carries the configured preview token in its `X-Stelae-Preview` header.
+fn allows_previews(req: &HttpRequest) -> bool {
+    req.app_data::<web::Data<Preview>>().is_some_and(|preview| {
+        let token = req
+            .headers()
+            .get(PREVIEW_HEADER)
+            .and_then(|value| value.to_str().ok());
+        preview.allows(token)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::policy::allows_previews;
+    use crate::stelae::archive::Preview;
+    use actix_web::{test::TestRequest, web};
+
+    #[test]
+    fn test_allows_previews_when_token_matches_expect_true() {
+        let cut = allows_previews;
+        let preview = web::Data::new(Preview {
+            token: "secret".to_owned(),
+        });
+        let req = TestRequest::default()
+            .app_data(preview.clone())
+            .insert_header(("X-Stelae-Preview", "secret"))
+            .to_http_request();
+        assert!(cut(&req));
+        let req = TestRequest::default()
+            .app_data(preview)
+            .insert_header(("X-Stelae-Preview", "guess"))
+            .to_http_request();
+        assert!(!cut(&req));
+        let req = TestRequest::default()
+            .insert_header(("X-Stelae-Preview", "secret"))
+            .to_http_request();
+        assert!(!cut(&req));
+    }
+}
@@ -0,0 +1,461 @@
+//! Handlers for summarizing publications, and for moving them through their approval.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use actix_web::{rt, web, HttpRequest, HttpResponse, Responder, ResponseError as _};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        models::{
+            document_change,
+            document_delta::{self, DocumentDelta},
+            publication::{self, Publication},
+        },
+        DatabaseConnection, DatabaseTransaction, Tx as _,
+    },
+    history::{
+        digest::Collector,
+        webhooks::{load_secret, Notifier},
+    },
+    server::{
+        auth::{self, AuthError},
+        base_path::BasePath,
+        errors::HTTPError,
+    },
+    stelae::archive::Archive,
+};
+
+use super::policy::AccessDecision;
+use super::response::ApiVersion;
+use super::state::{App as AppState, Global as _};
+use crate::utils::http::{respond_json, respond_text};
+
+/// Number of documents per page when no page size is requested.
+const DEFAULT_PER_PAGE: usize = 50;
+/// Maximum number of documents per page.
+const MAX_PER_PAGE: usize = 500;
+
+/// Query string of the delta endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaParams {
+    /// Only list documents with this change, one of `new`, `changed` or `removed`.
+    pub change: Option<String>,
+    /// Page of the listed documents, starting at 1.
+    pub page: Option<usize>,
+    /// Number of documents per page. Capped at 500.
+    pub per_page: Option<usize>,
+}
+
+/// Response for the delta endpoint.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delta {
+    /// Name of the publication.
+    pub publication: String,
+    /// Name of the previous publication the delta is relative to, if any.
+    pub previous_publication: Option<String>,
+    /// Number of documents for each change.
+    pub counts: Counts,
+    /// Current page of the listed documents.
+    pub page: usize,
+    /// Number of documents per page.
+    pub per_page: usize,
+    /// Documents on the current page.
+    pub documents: Vec<DeltaDocument>,
+}
+
+/// Request body of the publication state endpoint.
+#[derive(Debug, Deserialize)]
+pub struct StateRequest {
+    /// State to move the publication to, `approved` or `live`.
+    pub state: String,
+}
+
+/// Outcome of moving a publication to a state.
+#[derive(Debug)]
+pub enum Transition {
+    /// The publication was moved to the state.
+    Moved(Publication),
+    /// The publication is not found.
+    NotFound,
+    /// The publication is in the given state, which it cannot move from to the requested state.
+    Refused(String),
+}
+
+/// Number of new, changed and removed documents of a publication.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Counts {
+    /// Number of new documents.
+    pub new: usize,
+    /// Number of changed documents.
+    pub changed: usize,
+    /// Number of removed documents.
+    pub removed: usize,
+}
+
+/// A document listed in the delta endpoint response.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaDocument {
+    /// Materialized path to the document.
+    pub mpath: String,
+    /// Url of the document.
+    pub url: Option<String>,
+    /// Net change of the document, one of `new`, `changed` or `removed`.
+    pub change: String,
+}
+
+impl From<DocumentDelta> for DeltaDocument {
+    fn from(document: DocumentDelta) -> Self {
+        Self {
+            mpath: document.doc_mpath,
+            url: document.url,
+            change: document.change,
+        }
+    }
+}
+
+impl DeltaDocument {
+    /// The document, with its url under the `base_path` the archive is served under.
+    #[must_use]
+    pub fn mounted(self, base_path: &BasePath) -> Self {
+        Self {
+            url: self.url.map(|url| base_path.url(&url)),
+            ..self
+        }
+    }
+}
+
+/// Summarize the new, changed and removed documents of a publication relative to the previous publication.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Responses are enveloped as negotiated by
+/// [`ApiVersion`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn delta(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+    name: web::Path<String>,
+    params: web::Query<DeltaParams>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    if let Some(change) = params.change.as_deref() {
+        if ![
+            document_delta::NEW,
+            document_delta::CHANGED,
+            document_delta::REMOVED,
+        ]
+        .contains(&change)
+        {
+            return api_version.respond_error(
+                HttpResponse::BadRequest(),
+                "invalid_query",
+                "Query parameter `change` must be one of `new`, `changed` or `removed`.",
+            );
+        }
+    }
+    let db = data.db().for_stele(&stele);
+    let (active_publication, previous_publication) =
+        match find_compared(db, &stele, &name, None).await {
+            Ok(Some(compared)) => compared,
+            Ok(None) => {
+                return api_version.respond_error(
+                    HttpResponse::NotFound(),
+                    "publication_not_found",
+                    format!("Publication {name} not found."),
+                );
+            }
+            Err(err) => {
+                tracing::error!("Error finding publication {name}: {err:?}");
+                return api_version.respond_error(
+                    HttpResponse::InternalServerError(),
+                    "database_error",
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        };
+    let base_path = BasePath::of(&req);
+    let documents = match document_change::Manager::find_all_document_deltas_by_publication(
+        db,
+        &active_publication.id,
+        previous_publication.as_ref().map(|pb| pb.id.as_str()),
+    )
+    .await
+    {
+        Ok(documents) => documents,
+        Err(err) => {
+            tracing::error!("Error finding delta of publication {name}: {err:?}");
+            return api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    api_version.respond(
+        HttpResponse::Ok(),
+        &Delta {
+            publication: active_publication.name.clone(),
+            previous_publication: previous_publication.map(|pb| pb.name),
+            counts: count(&documents),
+            page,
+            per_page,
+            documents: documents
+                .into_iter()
+                .filter(|document| {
+                    params
+                        .change
+                        .as_deref()
+                        .is_none_or(|change| document.change == change)
+                })
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .map(|document| DeltaDocument::from(document).mounted(&base_path))
+                .collect(),
+        },
+    )
+}
+
+/// Move the publication `name` to the next state of its approval, `ingested` → `approved` → `live`.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Publications awaiting approval are never
+/// served. Approved publications are served like previews, only to requests that send the preview
+/// token, and live publications to every request; the latest publication served to a request is
+/// its current publication. The webhooks of the stele are notified of every transition, and the
+/// digest of the publication is sent once it is live.
+///
+/// Transitions are refused with `403 Forbidden` unless the request was authenticated as an admin
+/// of the stele, see [`auth::require_admin`], as they release publications to every reader.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn transition(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    name: web::Path<String>,
+    body: web::Json<StateRequest>,
+) -> impl Responder {
+    let admin = match auth::require_admin(&req) {
+        Ok(admin) => admin,
+        Err(err) => return err.error_response(),
+    };
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    if admin.stele != stele {
+        return AuthError::Forbidden.error_response();
+    }
+    let state = body.state.as_str();
+    if ![publication::APPROVED, publication::LIVE].contains(&state) {
+        return respond_text(
+            HttpResponse::BadRequest(),
+            "Field `state` must be one of `approved` or `live`.",
+        );
+    }
+    let db = data.db().for_stele(&stele);
+    match move_to_state(db, &stele, &name, state).await {
+        Ok(Transition::Moved(moved)) => {
+            data.cache().refresh();
+            match publication::Manager::find_all_non_revoked_publications(db, &stele).await {
+                Ok(publications) => data
+                    .cache()
+                    .insert_publications(stele.clone(), publications),
+                Err(err) => tracing::warn!("Error refreshing publications of {stele}: {err:?}"),
+            }
+            notify_transition(data.archive(), db, &stele, &moved.name, state);
+            respond_json(HttpResponse::Ok(), &moved)
+        }
+        Ok(Transition::NotFound) => respond_text(
+            HttpResponse::NotFound(),
+            format!("Publication {name} not found."),
+        ),
+        Ok(Transition::Refused(current)) => respond_text(
+            HttpResponse::Conflict(),
+            format!("Publication {name} is {current}, and cannot move to {state}."),
+        ),
+        Err(err) => {
+            tracing::error!("Error moving publication {name} to {state}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Notify the webhooks of the `stele` that the publication `name` moved to `state`, and send its
+/// digest if it is live, in the background.
+///
+/// Failures are logged only, as the transition is committed.
+fn notify_transition(
+    archive: &Archive,
+    db: &DatabaseConnection,
+    stele: &str,
+    name: &str,
+    state: &str,
+) {
+    let config = match archive.get_config() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("Unable to notify that {name} is {state}: {err:?}");
+            return;
+        }
+    };
+    let webhooks = config.webhooks.unwrap_or_default();
+    let notifier = match load_secret(&webhooks) {
+        Ok(secret) => Notifier::for_stele(&webhooks, secret.as_deref(), stele),
+        Err(err) => {
+            tracing::error!("Unable to notify the webhooks that {name} is {state}: {err:?}");
+            None
+        }
+    };
+    let collector = (state == publication::LIVE)
+        .then(|| Collector::for_stele(&config.digest.unwrap_or_default(), stele))
+        .flatten();
+    if notifier.is_none() && collector.is_none() {
+        return;
+    }
+    let (conn, stele_name, publication_name, moved_to) = (
+        db.clone(),
+        stele.to_owned(),
+        name.to_owned(),
+        state.to_owned(),
+    );
+    rt::spawn(async move {
+        if let Some(hooks) = notifier {
+            if let Err(err) = hooks
+                .on_transition(&conn, &stele_name, &publication_name, &moved_to)
+                .await
+            {
+                tracing::error!("[{stele_name}] | Error notifying webhooks: {err:?}");
+            }
+        }
+        if let Some(digest) = collector {
+            if let Err(err) = digest
+                .on_release(&conn, &stele_name, &publication_name)
+                .await
+            {
+                tracing::error!("[{stele_name}] | Error sending digest: {err:?}");
+            }
+        }
+    });
+}
+
+/// Move the non-revoked publication `name` of the `stele` to `state`, if it is the next state of
+/// the publication, see [`publication::next_state`].
+///
+/// # Errors
+/// Errors if the publication cannot be updated.
+pub async fn move_to_state(
+    db: &DatabaseConnection,
+    stele: &str,
+    name: &str,
+    state: &str,
+) -> anyhow::Result<Transition> {
+    let mut tx = DatabaseTransaction::begin(db.pool.clone()).await?;
+    let Ok(mut found) = publication::TxManager::find_by_name_and_stele(&mut tx, name, stele).await
+    else {
+        tx.rollback().await?;
+        return Ok(Transition::NotFound);
+    };
+    if publication::next_state(&found.state) != Some(state) {
+        tx.rollback().await?;
+        return Ok(Transition::Refused(found.state));
+    }
+    publication::TxManager::update_by_name_and_stele_set_state(&mut tx, name, stele, state).await?;
+    tx.commit().await?;
+    state.clone_into(&mut found.state);
+    Ok(Transition::Moved(found))
+}
+
+/// Find the non-revoked publication `name` of the `stele`, and the publication it is compared with:
+/// the publication `previous` if given, and the publication preceding it otherwise.
+///
+/// Returns `None` if `name` is not found, or if `previous` is not an earlier publication.
+///
+/// # Errors
+/// Errors if the publications cannot be read from the database.
+pub async fn find_compared(
+    db: &DatabaseConnection,
+    stele: &str,
+    name: &str,
+    previous: Option<&str>,
+) -> anyhow::Result<Option<(Publication, Option<Publication>)>> {
+    let publications = publication::Manager::find_all_non_revoked_publications(db, stele).await?;
+    let mut remaining = publications.into_iter().skip_while(|pb| pb.name != name);
+    let Some(active_publication) = remaining.next() else {
+        return Ok(None);
+    };
+    let compared = match previous {
+        Some(previous_name) => match remaining.find(|pb| pb.name == previous_name) {
+            Some(found) => Some(found),
+            None => return Ok(None),
+        },
+        None => remaining.next(),
+    };
+    Ok(Some((active_publication, compared)))
+}
+
+/// Count the new, changed and removed documents.
+#[must_use]
+pub fn count(documents: &[DocumentDelta]) -> Counts {
+    let mut counts = Counts::default();
+    for document in documents {
+        match document.change.as_str() {
+            document_delta::NEW => counts.new += 1,
+            document_delta::CHANGED => counts.changed += 1,
+            document_delta::REMOVED => counts.removed += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::publications::*;
+
+    fn document_delta(doc_mpath: &str, change: &str) -> DocumentDelta {
+        DocumentDelta {
+            doc_mpath: doc_mpath.to_owned(),
+            url: None,
+            change: change.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_count_when_mixed_changes_expect_counts_per_change() {
+        let cut = count;
+        let actual = cut(&[
+            document_delta("a", "new"),
+            document_delta("b", "changed"),
+            document_delta("c", "new"),
+            document_delta("d", "removed"),
+        ]);
+        let expected = Counts {
+            new: 2,
+            changed: 1,
+            removed: 1,
+        };
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,99 @@
+//! Handlers for the citation network of documents, as recorded by `stelae update`.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+
+use crate::{
+    db::models::reference::{self, Reference},
+    server::errors::HTTPError,
+    utils::paths::normalize_path,
+};
+
+use super::formats::document_stem;
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use crate::utils::http::{respond_json, respond_text};
+
+/// Direction of the references of a document.
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    /// References made by the document.
+    Outgoing,
+    /// References to the document.
+    Incoming,
+}
+
+/// List the documents cited by the document at `/_api/references/{path}`.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn references(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    find(&req, &data, &access, Direction::Outgoing).await
+}
+
+/// List the documents citing the document at `/_api/cited-by/{path}`.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn cited_by(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    find(&req, &data, &access, Direction::Incoming).await
+}
+
+/// Respond with the references of the requested document in the `direction`.
+async fn find(
+    req: &HttpRequest,
+    data: &AppState,
+    access: &AccessDecision,
+    direction: Direction,
+) -> HttpResponse {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let path = match normalize_path(req.match_info().get("path").unwrap_or_default()) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    let stem = document_stem(&path);
+    let found: anyhow::Result<Vec<Reference>> = match direction {
+        Direction::Outgoing => {
+            reference::Manager::find_all_by_stele_and_source(
+                data.db().for_stele(&stele),
+                &stele,
+                stem,
+            )
+            .await
+        }
+        Direction::Incoming => {
+            reference::Manager::find_all_by_stele_and_target(
+                data.db().for_stele(&stele),
+                &stele,
+                stem,
+            )
+            .await
+        }
+    };
+    match found {
+        Ok(references) => respond_json(HttpResponse::Ok(), &references),
+        Err(err) => {
+            tracing::error!("Error finding references of {path} in stele {stele}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Shared envelope and serialization conventions of `_api` JSON responses.
+//!
+//! Clients opt into the envelope by sending `Accept-Version: 2` to the JSON endpoints that
+//! negotiate it: `versions`, `adjacent`, `suggest`, `timeline`, `check-links`,
+//! `compare-collection` and the publication `delta`. Every enveloped response is an
+//! object with three members:
+//!
+//! - `data`: the payload of the endpoint, or `null` if there is none;
+//! - `meta`: information about the response itself, e.g. its `apiVersion`;
+//! - `errors`: the problems with the request, each with a stable snake case `code` and a
+//!   human-readable `message`, empty on success.
+//!
+//! Members are camelCase, and dates are `YYYY-MM-DD`. Requests without an `Accept-Version`
+//! header, or with `Accept-Version: 1`, keep receiving the payload unwrapped, as before the
+//! envelope was introduced; an unsupported version is answered `406 Not Acceptable`.
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload,
+    error::ErrorNotAcceptable,
+    http::header::{HeaderName, HeaderValue, VARY},
+    FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use serde::Serialize;
+
+use crate::utils::http::{respond_json, respond_text};
+
+/// Header selecting the version of the `_api` responses a client accepts.
+pub const ACCEPT_VERSION_HEADER: &str = "Accept-Version";
+/// Header carrying the version of an `_api` response.
+const CONTENT_VERSION: HeaderName = HeaderName::from_static("content-version");
+
+/// Version of the `_api` responses negotiated with a client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// Payloads unwrapped, and errors as plain text.
+    #[default]
+    Legacy,
+    /// Payloads and errors in an [`Envelope`].
+    Enveloped,
+}
+
+impl ApiVersion {
+    /// Versions in the order they were introduced.
+    pub const ALL: [Self; 2] = [Self::Legacy, Self::Enveloped];
+
+    /// Number of the version in the `Accept-Version` header.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Legacy => "1",
+            Self::Enveloped => "2",
+        }
+    }
+
+    /// Version accepted by the request `req`, the legacy version if it does not select one.
+    ///
+    /// # Errors
+    /// Errors if the `Accept-Version` header of the request is not a supported version.
+    pub fn negotiate(req: &HttpRequest) -> anyhow::Result<Self> {
+        let Some(value) = req.headers().get(ACCEPT_VERSION_HEADER) else {
+            return Ok(Self::default());
+        };
+        let requested = value.to_str().unwrap_or_default().trim();
+        Self::ALL
+            .into_iter()
+            .find(|version| version.as_str() == requested)
+            .ok_or_else(|| {
+                let supported = Self::ALL.map(Self::as_str).join(", ");
+                anyhow::anyhow!(
+                    "Unsupported {ACCEPT_VERSION_HEADER} '{requested}', expected one of: {supported}."
+                )
+            })
+    }
+
+    /// Complete `response` with the `data` of an endpoint, in an [`Envelope`] if negotiated.
+    pub fn respond<T: Serialize>(self, response: HttpResponseBuilder, data: &T) -> HttpResponse {
+        match self {
+            Self::Legacy => respond_json(self.negotiated(response), data),
+            Self::Enveloped => respond_json(self.negotiated(response), &Envelope::data(data)),
+        }
+    }
+
+    /// Complete `response` with the error `message`, as plain text or in an [`Envelope`] with
+    /// its `code` if negotiated.
+    pub fn respond_error<M: Into<String>>(
+        self,
+        response: HttpResponseBuilder,
+        code: &'static str,
+        message: M,
+    ) -> HttpResponse {
+        match self {
+            Self::Legacy => respond_text(self.negotiated(response), message),
+            Self::Enveloped => respond_json(
+                self.negotiated(response),
+                &Envelope::<()>::error(code, message.into()),
+            ),
+        }
+    }
+
+    /// Complete `response` with the `data` of a rejected request, with the problem `code` and
+    /// `message` in an [`Envelope`] if negotiated.
+    pub fn respond_rejected<T: Serialize>(
+        self,
+        response: HttpResponseBuilder,
+        code: &'static str,
+        message: String,
+        data: &T,
+    ) -> HttpResponse {
+        match self {
+            Self::Legacy => respond_json(self.negotiated(response), data),
+            Self::Enveloped => {
+                let mut envelope = Envelope::error(code, message);
+                envelope.data = Some(data);
+                respond_json(self.negotiated(response), &envelope)
+            }
+        }
+    }
+
+    /// Add the `Vary` and `Content-Version` headers of a negotiated response to `response`.
+    ///
+    /// Responses of versioned endpoints vary by `Accept-Version`, whichever version they are in,
+    /// so shared caches do not serve one version to a client asking for the other.
+    #[must_use]
+    pub fn negotiated(self, mut response: HttpResponseBuilder) -> HttpResponseBuilder {
+        response.append_header((VARY, ACCEPT_VERSION_HEADER));
+        response.insert_header((CONTENT_VERSION, HeaderValue::from_static(self.as_str())));
+        response
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
+impl FromRequest for ApiVersion {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    /// The version negotiated with the request `req`, or `406 Not Acceptable`.
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Self::negotiate(req).map_err(|err| ErrorNotAcceptable(err.to_string())))
+    }
+}
+
+/// Envelope of an `_api` response.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope<T> {
+    /// Payload of the endpoint.
+    pub data: Option<T>,
+    /// Information about the response.
+    pub meta: Meta,
+    /// Problems with the request.
+    pub errors: Vec<Problem>,
+}
+
+impl<T> Envelope<T> {
+    /// Envelope of the payload `data`.
+    #[must_use]
+    pub fn data(data: T) -> Self {
+        Self {
+            data: Some(data),
+            meta: Meta::default(),
+            errors: vec![],
+        }
+    }
+
+    /// Envelope of a single problem with the request, without a payload.
+    #[must_use]
+    pub fn error(code: &'static str, message: String) -> Self {
+        Self {
+            data: None,
+            meta: Meta::default(),
+            errors: vec![Problem { code, message }],
+        }
+    }
+}
+
+/// Information about an `_api` response.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    /// Version of the response, see [`ApiVersion`].
+    pub api_version: &'static str,
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Self {
+            api_version: ApiVersion::Enveloped.as_str(),
+        }
+    }
+}
+
+/// Problem with an `_api` request.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Problem {
+    /// Stable snake case identifier of the problem, e.g. `invalid_date`.
+    pub code: &'static str,
+    /// Human-readable explanation of the problem.
+    pub message: String,
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::api::response::{ApiVersion, Envelope};
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_negotiate_when_accept_version_expect_matching_version() {
+        let cut = ApiVersion::negotiate;
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(cut(&req).unwrap(), ApiVersion::Legacy);
+        let req = TestRequest::default()
+            .insert_header(("Accept-Version", "2"))
+            .to_http_request();
+        assert_eq!(cut(&req).unwrap(), ApiVersion::Enveloped);
+        let req = TestRequest::default()
+            .insert_header(("Accept-Version", "3"))
+            .to_http_request();
+        assert!(cut(&req).is_err());
+    }
+
+    #[test]
+    fn test_envelope_when_error_expect_null_data_and_problem() {
+        let cut = Envelope::<()>::error;
+        let actual = serde_json::to_value(cut("invalid_date", "Not a date.".to_owned())).unwrap();
+        let expected = serde_json::json!({
+            "data": null,
+            "meta": {"apiVersion": "2"},
+            "errors": [{"code": "invalid_date", "message": "Not a date."}],
+        });
+        assert_eq!(actual, expected);
+    }
+}
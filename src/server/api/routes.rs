@@ -3,27 +3,74 @@
     clippy::exit,
     reason = "We exit with 1 error code on any application errors"
 )]
-use std::{process, sync::OnceLock};
+use std::process;
 
+use crate::history::status::ARCHIVE_PATH;
 use crate::server::api::state;
-use crate::stelae::{stele::Stele, types::repositories::Repositories};
+use crate::stelae::{archive::SecurityHeaders, stele::Stele, types::repositories::Repositories};
 use actix_service::ServiceFactory;
 use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
-    guard, web, App, Error, Scope,
+    guard,
+    http::header::HeaderValue,
+    middleware::DefaultHeaders,
+    web, App, Error, Scope,
 };
+use anyhow::Context as _;
 
-use super::{serve::serve, state::Global, versions::versions};
+use super::{
+    archive::archive,
+    compare::compare_collection,
+    dated::serve_on_date,
+    formats::formats,
+    identifiers::resolve,
+    in_force::in_force,
+    integrity::asset_integrity,
+    links::{broken_links, check_links},
+    metrics::metrics,
+    pinned::serve_at_commit,
+    publications::{delta, transition},
+    references::{cited_by, references},
+    serve::serve,
+    snapshot::{pin, serve_snapshot},
+    state::Global,
+    stats::{stats, top_documents},
+    status::{health, status},
+    suggest::suggest,
+    takedown::{list_takedowns, restore, take_down},
+    timeline::timeline,
+    versions::{adjacent, versions},
+};
+
+/// Routes registered by [`register_app`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Routes {
+    /// Every route, served on a single listener.
+    All,
+    /// The documents and the APIs, without the management routes.
+    Public,
+    /// Only the management routes, `/_admin/*`, `/_metrics` and `/_health`.
+    Management,
+}
 
-/// Name of the header to guard current documents
-static HEADER_NAME: OnceLock<String> = OnceLock::new();
-/// Values of the header to guard current documents
-static HEADER_VALUES: OnceLock<Vec<String>> = OnceLock::new();
+impl Routes {
+    /// Whether the documents and the APIs are registered.
+    #[must_use]
+    pub const fn public(self) -> bool {
+        matches!(self, Self::All | Self::Public)
+    }
+
+    /// Whether the management routes are registered.
+    #[must_use]
+    pub const fn management(self) -> bool {
+        matches!(self, Self::All | Self::Management)
+    }
+}
 
 /// Central place to register all the App routing.
 ///
-/// Registers all routes for the given Archive
+/// Registers the `routes` for the given Archive
 /// Static routes should be registered first, followed by dynamic routes.
 ///
 /// # Errors
@@ -42,8 +89,54 @@ pub fn register_app<
 >(
     mut app: App<V>,
     state: &T,
+    routes: Routes,
 ) -> anyhow::Result<App<V>> {
+    if routes.management() {
+        app = app
+            .service(web::resource("/_metrics").route(web::get().to(metrics)))
+            .service(web::resource("/_health").route(web::get().to(health)))
+            .service(web::resource(ARCHIVE_PATH).route(web::get().to(archive)))
+            .service(
+                web::scope("/_admin")
+                    .service(web::resource("/pin").route(web::post().to(pin)))
+                    .service(web::resource("/broken-links").route(web::get().to(broken_links)))
+                    .service(web::resource("/stats").route(web::get().to(stats)))
+                    .service(web::resource("/status").route(web::get().to(status)))
+                    .service(
+                        web::resource("/publications/{name}/state")
+                            .route(web::post().to(transition)),
+                    )
+                    .service(
+                        web::resource("/takedowns")
+                            .route(web::get().to(list_takedowns))
+                            .route(web::post().to(take_down)),
+                    )
+                    .service(
+                        web::resource("/takedowns/{path:.*}").route(web::delete().to(restore)),
+                    ),
+            );
+    }
     app = app
+        .app_data(web::Data::new(state.clone()))
+        .app_data(web::Data::new(state.takedowns().clone()))
+        .app_data(web::Data::new(state.identifiers().clone()))
+        .app_data(web::Data::new(state.cache().clone()))
+        .app_data(web::Data::new(state.views().clone()));
+    if !routes.public() {
+        return Ok(app);
+    }
+    app = app
+        .service(web::resource("/_api/suggest").route(web::get().to(suggest)))
+        .service(web::resource("/_api/asset-integrity.json").route(web::get().to(asset_integrity)))
+        .service(web::resource("/_api/stats/top-documents").route(web::get().to(top_documents)))
+        .service(web::resource("/_api/publications/{name}/delta").route(web::get().to(delta)))
+        .service(web::resource("/_api/timeline/{path:.*}").route(web::get().to(timeline)))
+        .service(web::resource("/_api/in-force/{path:.*}").route(web::get().to(in_force)))
+        .service(web::resource("/_api/formats/{path:.*}").route(web::get().to(formats)))
+        .service(web::resource("/_api/references/{path:.*}").route(web::get().to(references)))
+        .service(web::resource("/_api/cited-by/{path:.*}").route(web::get().to(cited_by)))
+        .service(web::resource("/_api/check-links").route(web::post().to(check_links)))
+        .service(web::resource("/_api/compare-collection").route(web::get().to(compare_collection)))
         .service(
             web::scope("/_api").service(
                 web::scope("/versions")
@@ -66,11 +159,62 @@ pub fn register_app<
                     )
                     .service(web::resource("/_date/{date}").to(versions))
                     .service(web::resource("/_date/{date}/{path:.*}").to(versions))
+                    .service(web::resource("/adjacent").route(web::get().to(adjacent)))
+                    .service(web::resource("/{path:.*}/adjacent").route(web::get().to(adjacent)))
                     .service(web::resource("/{path:.*}").to(versions))
                     .service(web::resource("").to(versions)),
             ),
         )
-        .app_data(web::Data::new(state.clone()));
+        .service(
+            web::resource("/_date/{date}/_repo/{repo_type}/{path:.*}")
+                .route(web::get().to(serve_on_date))
+                .route(web::head().to(serve_on_date)),
+        )
+        .service(
+            web::resource("/_date/{date}")
+                .route(web::get().to(serve_on_date))
+                .route(web::head().to(serve_on_date)),
+        )
+        .service(
+            web::resource("/_date/{date}/{path:.*}")
+                .route(web::get().to(serve_on_date))
+                .route(web::head().to(serve_on_date)),
+        )
+        .service(
+            web::resource("/_commit/{sha}/{path:.*}")
+                .route(web::get().to(serve_at_commit))
+                .route(web::head().to(serve_at_commit)),
+        )
+        .service(
+            web::resource("/{identifier:eli/.+}")
+                .route(web::get().to(resolve))
+                .route(web::head().to(resolve)),
+        )
+        .service(
+            web::resource("/{identifier:urn:lex:.+}")
+                .route(web::get().to(resolve))
+                .route(web::head().to(resolve)),
+        )
+        .service(
+            web::scope("/_snapshot")
+                .service(
+                    web::resource("/{name}")
+                        .route(web::get().to(serve_snapshot))
+                        .route(web::head().to(serve_snapshot)),
+                )
+                .service(
+                    web::resource("/{name}/{path:.*}")
+                        .route(web::get().to(serve_snapshot))
+                        .route(web::head().to(serve_snapshot)),
+                ),
+        );
+    let config = state.archive().get_config()?;
+    if let Some(structured_data) = config.structured_data {
+        app = app.app_data(web::Data::new(structured_data));
+    }
+    if let Some(preview) = config.preview {
+        app = app.app_data(web::Data::new(preview));
+    }
 
     app = register_dynamic_routes(app, state)?;
     Ok(app)
@@ -100,11 +244,15 @@ fn register_dynamic_routes<
     let stelae_guard = config
         .headers
         .and_then(|headers| headers.current_documents_guard);
+    let security_headers = config.security_headers;
+    if let Some(headers) = security_headers.as_ref() {
+        headers.validate()?;
+    }
 
     if let Some(guard) = stelae_guard {
-        app = initialize_guarded_dynamic_routes(guard, app, state)?;
+        app = initialize_guarded_dynamic_routes(&guard, app, state, security_headers.as_ref())?;
     } else {
-        app = initialize_dynamic_routes(app, state)?;
+        app = initialize_dynamic_routes(app, state, security_headers.as_ref())?;
     };
     Ok(app)
 }
@@ -114,6 +262,10 @@ fn register_dynamic_routes<
 ///
 /// # Errors
 /// Errors if unable to register dynamic routes (e.g. if git repository cannot be opened)
+#[expect(
+    clippy::iter_over_hash_type,
+    reason = "Every stele is guarded by its own header value, so the order the scopes are registered in does not matter"
+)]
 fn initialize_guarded_dynamic_routes<
     T: MessageBody,
     U: ServiceFactory<
@@ -124,50 +276,39 @@ fn initialize_guarded_dynamic_routes<
         Error = Error,
     >,
 >(
-    guard: String,
+    guard: &str,
     mut app: App<U>,
     state: &impl Global,
+    security_headers: Option<&SecurityHeaders>,
 ) -> anyhow::Result<App<U>> {
     tracing::info!(
         "Initializing guarded current documents with header: {}",
         guard
     );
-    HEADER_NAME.get_or_init(|| guard);
-    HEADER_VALUES.get_or_init(|| {
-        state
-            .archive()
-            .stelae
-            .keys()
-            .map(ToString::to_string)
-            .collect()
-    });
-
-    if let (Some(guard_name), Some(guard_values)) = (HEADER_NAME.get(), HEADER_VALUES.get()) {
-        for guard_value in guard_values {
-            let stele = state.archive().stelae.get(guard_value);
-            if let Some(guarded_stele) = stele {
-                let shared_state = state::init_shared(guarded_stele)?;
-                let mut stelae_scope = web::scope("");
-                stelae_scope = stelae_scope.guard(guard::Header(guard_name, guard_value));
-                app = app.service(
-                    stelae_scope
-                        .app_data(web::Data::new(shared_state))
-                        .configure(|cfg| {
-                            register_root_routes(cfg, guarded_stele).unwrap_or_else(|_| {
-                                tracing::error!(
-                                    "Failed to initialize routes for Stele: {}",
-                                    guarded_stele.get_qualified_name()
-                                );
-                                process::exit(1);
-                            });
-                        }),
-                );
-            }
-        }
-    } else {
-        let err_msg = "Failed to initialize guarded routes. Header name or values not found.";
-        tracing::error!(err_msg);
-        anyhow::bail!(err_msg);
+    for (guard_value, guarded_stele) in &state.archive().stelae {
+        let shared_state = state::init_shared(guarded_stele)?;
+        let (header_name, header_value) = (guard.to_owned(), guard_value.clone());
+        app = app.service(
+            web::scope("")
+                .guard(guard::fn_guard(move |ctx| {
+                    ctx.head()
+                        .headers()
+                        .get(header_name.as_str())
+                        .is_some_and(|value| value == header_value.as_str())
+                }))
+                .app_data(web::Data::new(shared_state))
+                .configure(|cfg| {
+                    register_root_routes(cfg, guarded_stele, security_headers).unwrap_or_else(
+                        |_| {
+                            tracing::error!(
+                                "Failed to initialize routes for Stele: {}",
+                                guarded_stele.get_qualified_name()
+                            );
+                            process::exit(1);
+                        },
+                    );
+                }),
+        );
     }
     Ok(app)
 }
@@ -188,6 +329,7 @@ fn initialize_dynamic_routes<
 >(
     mut app: App<U>,
     state: &impl Global,
+    security_headers: Option<&SecurityHeaders>,
 ) -> anyhow::Result<App<U>> {
     tracing::info!("Initializing app");
     let root = state.archive().get_root()?;
@@ -196,7 +338,7 @@ fn initialize_dynamic_routes<
         web::scope("")
             .app_data(web::Data::new(shared_state))
             .configure(|cfg| {
-                register_routes(cfg, state).unwrap_or_else(|_| {
+                register_routes(cfg, state, security_headers).unwrap_or_else(|_| {
                     tracing::error!(
                         // TODO: error handling
                         "Failed to initialize routes for root Stele: {}",
@@ -216,23 +358,28 @@ fn initialize_dynamic_routes<
 /// # Arguments
 /// * `cfg` - The Actix `ServiceConfig`
 /// * `state` - The application state
+/// * `security_headers` - Security headers to send with served documents, if configured
 /// # Errors
 /// Will error if unable to register routes (e.g. if git repository cannot be opened)
 #[expect(
     clippy::iter_over_hash_type,
     reason = "List of repositories that are registered as routes are always sorted, even with iterating over hash type"
 )]
-fn register_routes<T: Global>(cfg: &mut web::ServiceConfig, state: &T) -> anyhow::Result<()> {
+fn register_routes<T: Global>(
+    cfg: &mut web::ServiceConfig,
+    state: &T,
+    security_headers: Option<&SecurityHeaders>,
+) -> anyhow::Result<()> {
     for stele in state.archive().stelae.values() {
         if let Some(repositories) = stele.repositories.as_ref() {
             if stele.is_root() {
                 continue;
             }
-            register_dependent_routes(cfg, stele, repositories)?;
+            register_dependent_routes(cfg, stele, repositories, security_headers)?;
         }
     }
     let root = state.archive().get_root()?;
-    register_root_routes(cfg, root)?;
+    register_root_routes(cfg, root, security_headers)?;
     Ok(())
 }
 
@@ -241,9 +388,14 @@ fn register_routes<T: Global>(cfg: &mut web::ServiceConfig, state: &T) -> anyhow
 /// # Arguments
 /// * `cfg` - The Actix `ServiceConfig`
 /// * `stele` - The root Stele
+/// * `security_headers` - Security headers to send with served documents, if configured
 /// # Errors
 /// Will error if unable to register routes (e.g. if git repository cannot be opened)
-fn register_root_routes(cfg: &mut web::ServiceConfig, stele: &Stele) -> anyhow::Result<()> {
+fn register_root_routes(
+    cfg: &mut web::ServiceConfig,
+    stele: &Stele,
+    security_headers: Option<&SecurityHeaders>,
+) -> anyhow::Result<()> {
     let mut root_scope: Scope = web::scope("");
     if let Some(repositories) = stele.repositories.as_ref() {
         let sorted_repositories = repositories.get_sorted();
@@ -268,10 +420,12 @@ fn register_root_routes(cfg: &mut web::ServiceConfig, stele: &Stele) -> anyhow::
                             .app_data(web::Data::new(repo_state.clone())),
                     ),
                 );
-                cfg.service(actix_underscore_scope);
+                cfg.service(
+                    actix_underscore_scope.wrap(init_security_headers(security_headers, stele)?),
+                );
             }
         }
-        cfg.service(root_scope);
+        cfg.service(root_scope.wrap(init_security_headers(security_headers, stele)?));
     }
     Ok(())
 }
@@ -282,12 +436,14 @@ fn register_root_routes(cfg: &mut web::ServiceConfig, stele: &Stele) -> anyhow::
 /// * `cfg` - The Actix `ServiceConfig`
 /// * `stele` - The root Stele
 /// * `repositories` - Data repositories of the dependent Stele
+/// * `security_headers` - Security headers to send with served documents, if configured
 /// # Errors
 /// Will error if unable to register routes (e.g. if git repository cannot be opened)
 fn register_dependent_routes(
     cfg: &mut web::ServiceConfig,
     stele: &Stele,
     repositories: &Repositories,
+    security_headers: Option<&SecurityHeaders>,
 ) -> anyhow::Result<()> {
     let sorted_repositories = repositories.get_sorted();
     for scope in repositories.scopes.iter().flat_map(|scopes| scopes.iter()) {
@@ -311,7 +467,29 @@ fn register_dependent_routes(
                 );
             }
         }
-        cfg.service(actix_scope);
+        cfg.service(actix_scope.wrap(init_security_headers(security_headers, stele)?));
     }
     Ok(())
 }
+
+/// Initialize the middleware sending the security headers of `stele` with served documents.
+///
+/// Headers already set by a handler are left untouched.
+///
+/// # Errors
+/// Errors if a configured header is not a valid header value, e.g. contains a newline.
+fn init_security_headers(
+    security_headers: Option<&SecurityHeaders>,
+    stele: &Stele,
+) -> anyhow::Result<DefaultHeaders> {
+    let stele_name = stele.get_qualified_name();
+    security_headers
+        .into_iter()
+        .flat_map(|headers| headers.for_stele(&stele_name))
+        .try_fold(DefaultHeaders::new(), |default_headers, (name, value)| {
+            let header_value = HeaderValue::from_str(&value).with_context(|| {
+                format!("Invalid {name} header of {stele_name} in [security_headers]: {value:?}")
+            })?;
+            Ok(default_headers.add((name, header_value)))
+        })
+}
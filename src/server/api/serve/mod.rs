@@ -1,16 +1,123 @@
 //! API endpoint for serving current documents from Stele repositories.
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::{
+    error::BlockingError,
+    http::{header, Method},
+    web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
+};
 
 use crate::{
-    server::errors::HTTPError,
-    utils::{git::Repo, http::get_contenttype, paths::clean_path},
+    db::models::data_repo_commits,
+    history::views::ViewCounter,
+    server::{
+        api::{
+            formats::{document_stem, negotiate_language, Alternate, Format, Representation},
+            identifiers::{identifier_url, Identifiers},
+            policy::AccessDecision,
+            takedown::unavailable,
+        },
+        base_path::BasePath,
+        cache::{Cache, WithheldHeads},
+        errors::HTTPError,
+        proxy::Forwarded,
+        timing::{Phase, Timings},
+    },
+    stelae::archive::{StructuredData, StructuredDataValues},
+    utils::{
+        archive::get_name_parts,
+        git::{Repo, GIT_REQUEST_NOT_FOUND},
+        html::{extract_text, find_first_heading},
+        http::{
+            get_contenttype, respond, respond_blob, respond_digested_blob, respond_json,
+            respond_text, Sha256Digest,
+        },
+        paths::{normalize_path, InvalidPath},
+        structured_data::{insert_legislation, Document},
+        template::{escape, wrap_fragment},
+    },
 };
 
-use super::state::{RepoData as RepoState, Shared as SharedState};
-/// Most-recent git commit
-const HEAD_COMMIT: &str = "HEAD";
+use anyhow::Context as _;
+use serde::Serialize;
+use std::future::Future;
+use std::path::Path;
+
+use super::state::{App as AppState, Global as _, RepoData as RepoState, Shared as SharedState};
+
+/// Metadata and text of a current html document, served as its json representation.
+#[derive(Debug, Serialize)]
+struct DocumentText {
+    /// Path of the document.
+    path: String,
+    /// Qualified name of the stele the document belongs to.
+    stele: String,
+    /// Text of the first heading of the document, if any.
+    title: Option<String>,
+    /// Formats the document is available in.
+    formats: Vec<Format>,
+    /// Persistent identifiers of the document, e.g. its ELI.
+    identifiers: Vec<String>,
+    /// Readable text of the document.
+    text: String,
+}
+
+/// A blob of the `HEAD` commit of a data repository.
+#[derive(Debug)]
+struct HeadBlob {
+    /// Content of the blob.
+    content: Vec<u8>,
+    /// SHA-256 of the content, if the blob was cached with it.
+    digest: Option<Sha256Digest>,
+}
+
+/// A current document, its language if the repository declares languages, and the formats it is
+/// available in, see [`look_up_current_document`].
+type CurrentDocument = (anyhow::Result<HeadBlob>, Option<String>, Vec<Format>);
+
+/// The `HEAD` commits the current documents of a data repository are looked up at.
+#[derive(Clone, Copy)]
+struct HeadLookup<'state> {
+    /// Cache of the current blobs, layouts and paths of alternate formats.
+    cache: &'state Cache,
+    /// The data repository the documents are served from.
+    repo: &'state RepoState,
+    /// Commits served instead of the withheld `HEAD` commits, see [`withheld_heads`].
+    withheld: &'state WithheldHeads,
+}
+
+/// Listing of a directory of a data repository, served as its json representation.
+#[derive(Debug, Serialize)]
+struct Listing {
+    /// Path of the directory in the data repository.
+    path: String,
+    /// Entries of the directory, directories first.
+    entries: Vec<ListingEntry>,
+}
+
+/// An entry of a directory listing.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListingEntry {
+    /// Name of the file or directory.
+    name: String,
+    /// Url the entry is served at.
+    url: String,
+    /// Whether the entry is a directory.
+    is_directory: bool,
+}
 
 /// Serve current document
+///
+/// Html documents are also served as xml or json if requested with the `Accept` header or the
+/// `?format=` query parameter, see [`Representation::negotiate`]. Documents of repositories that
+/// declare languages are served in the language requested with the `Accept-Language` header or
+/// the `?lang=` query parameter, see [`negotiate_language`].
+///
+/// Documents, the other formats they are available in, and directory listings are looked up in
+/// git on the blocking thread pool, so a request whose lookup exceeds its `[timeouts]` budget is answered
+/// `504 Gateway Timeout` instead of holding the worker.
+///
+/// Documents are served from the `HEAD` commits of the data repositories, unless withheld from
+/// the request because they are not published to it yet, see [`withheld_heads`].
 #[expect(
     clippy::future_not_send,
     reason = "We don't worry about git2-rs not implementing `Send` trait"
@@ -19,47 +126,609 @@ pub async fn serve(
     req: HttpRequest,
     shared: web::Data<SharedState>,
     data: web::Data<RepoState>,
+    app: web::Data<AppState>,
+    access: AccessDecision,
+    structured_data: Option<web::Data<StructuredData>>,
 ) -> impl Responder {
-    let prefix = req
-        .match_info()
-        .get("prefix")
-        .unwrap_or_default()
-        .to_owned();
-    let tail = req.match_info().get("tail").unwrap_or_default().to_owned();
-    let mut path = format!("{prefix}/{tail}");
-    path = clean_path(&path);
+    let path = match requested_path(&req) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    if let Some(reason) = app.takedowns().find(&data.stele, &path) {
+        return unavailable(&path, &reason);
+    }
+    let cache = app.cache();
+    let withheld = match withheld_heads(&app, &data.stele, access.previews()).await {
+        Ok(withheld) => withheld,
+        Err(err) => return lookup_error(&data.stele, &err),
+    };
     let contenttype = get_contenttype(&path);
-    let blob = find_current_blob(&data, &shared, &path);
+    let negotiable = contenttype.0 == mime::TEXT_HTML;
+    let (representation, language) = match negotiate(&req, &data, negotiable) {
+        Ok(negotiated) => negotiated,
+        Err(response) => return response,
+    };
+    let base_path = BasePath::of(&req);
+    let timings = Timings::of(&req);
+    if let Some(response) = respond_representation(
+        &data,
+        &withheld,
+        &path,
+        representation,
+        &base_path,
+        &timings,
+    )
+    .await
+    {
+        return response;
+    }
+    if data.directory_listing {
+        let base = base_path.url(&format!("{}/", req.path().trim_end_matches('/')));
+        let lookup = look_up_listing(&data, cache, &withheld, &path, base);
+        match timings.measure_async(Phase::Git, lookup).await {
+            Ok(Some(listing)) => return respond_listing(&listing, representation),
+            Ok(None) => {}
+            Err(err) => return blocking_error(&path, &err),
+        }
+    }
+    let lookup = look_up_current_document(&data, shared, cache, &withheld, &path, language);
+    let (blob, content_language, formats) = match timings.measure_async(Phase::Git, lookup).await {
+        Ok(found) => found,
+        Err(err) => return blocking_error(&path, &err),
+    };
+    if blob.is_ok() && negotiable {
+        count_view(&req, &data.stele, &path);
+    }
+    let head = HeadLookup {
+        cache,
+        repo: &data,
+        withheld: &withheld,
+    };
     match blob {
-        Ok(content) => HttpResponse::Ok().insert_header(contenttype).body(content),
+        Ok(HeadBlob { content, .. }) if representation == Representation::Json => {
+            let document = document_text(
+                &data,
+                app.identifiers(),
+                path,
+                &content,
+                formats,
+                &base_path,
+            );
+            let mut response = HttpResponse::Ok();
+            insert_negotiated_headers(&mut response, true, content_language.as_deref());
+            respond_json(response, &document)
+        }
+        Ok(HeadBlob { content, digest }) => {
+            let rewritten = is_rewritten(&path);
+            let mounted = timings.measure(Phase::Rewrite, || {
+                rewrite(&req, &head, structured_data.as_ref(), &path, content)
+            });
+            let mut response = HttpResponse::Ok();
+            insert_negotiated_headers(&mut response, negotiable, content_language.as_deref());
+            for link in alternate_links(&data, formats, &base_path) {
+                response.append_header((header::LINK, link));
+            }
+            for id in app.identifiers().of_document(&data.stele, &path) {
+                let link = format!("<{}>; rel=\"cite-as\"", base_path.url(&identifier_url(&id)));
+                response.append_header((header::LINK, link));
+            }
+            match digest.filter(|_| !rewritten) {
+                Some(cached) => respond_digested_blob(response, contenttype.0, mounted, &cached),
+                None => respond_blob(response, contenttype.0, mounted),
+            }
+        }
         Err(error) => {
             tracing::debug!("{path}: {error}",);
-            HttpResponse::NotFound().body(HTTPError::NotFound.to_string())
+            respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string())
+        }
+    }
+}
+
+/// Negotiate the representation and the language the current document is served in, if the
+/// document is `negotiable` and the repository `repo` declares languages respectively.
+///
+/// # Errors
+/// Responds `400 Bad Request` if the requested format or language is not supported.
+fn negotiate(
+    req: &HttpRequest,
+    repo: &RepoState,
+    negotiable: bool,
+) -> Result<(Representation, Option<String>), HttpResponse> {
+    let representation = match Representation::negotiate(req) {
+        Ok(representation) if negotiable => representation,
+        Ok(_) => Representation::Html,
+        Err(format) => {
+            return Err(respond_text(
+                HttpResponse::BadRequest(),
+                format!("Unsupported format {format}."),
+            ))
+        }
+    };
+    match repo
+        .languages
+        .as_ref()
+        .map(|declared| negotiate_language(req, declared))
+    {
+        Some(Err(lang)) => Err(respond_text(
+            HttpResponse::BadRequest(),
+            format!("Unsupported language {lang}."),
+        )),
+        negotiated => Ok((representation, negotiated.and_then(Result::ok))),
+    }
+}
+
+/// Respond with the current document at `path` in the `representation`, if it is not html and
+/// the document is available in it.
+#[expect(
+    clippy::future_not_send,
+    reason = "Timings are shared within the worker serving the request only"
+)]
+async fn respond_representation(
+    repo: &web::Data<RepoState>,
+    withheld: &WithheldHeads,
+    path: &str,
+    representation: Representation,
+    base_path: &BasePath,
+    timings: &Timings,
+) -> Option<HttpResponse> {
+    if representation == Representation::Html {
+        return None;
+    }
+    let lookup = web::block({
+        let (alternates, served, document_path) = (repo.clone(), withheld.clone(), path.to_owned());
+        move || find_representation(&alternates, &served, &document_path, representation)
+    });
+    let (format, content) = match timings.measure_async(Phase::Git, lookup).await {
+        Ok(found) => found?,
+        Err(err) => return Some(blocking_error(path, &err)),
+    };
+    let media_type = format
+        .media_type
+        .parse()
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((header::CONTENT_LOCATION, base_path.url(&format.url)))
+        .insert_header((header::VARY, "Accept"));
+    Some(respond_blob(response, media_type, content))
+}
+
+/// Describe the current html document `content` at `path` of the `repo` as its json
+/// representation, with the `formats` it is available in under the `base_path` and its
+/// persistent `identifiers`.
+fn document_text(
+    repo: &RepoState,
+    identifiers: &Identifiers,
+    path: String,
+    content: &[u8],
+    formats: Vec<Format>,
+    base_path: &BasePath,
+) -> DocumentText {
+    DocumentText {
+        title: find_first_heading(content).ok().flatten(),
+        text: extract_text(content).unwrap_or_default(),
+        formats: formats
+            .into_iter()
+            .map(|format| format.mounted(base_path))
+            .collect(),
+        identifiers: identifiers.of_document(&repo.stele, &path),
+        stele: repo.stele.clone(),
+        path,
+    }
+}
+
+/// Respond `500 Internal Server Error` to a request for a current document of the `stele` whose
+/// served commits could not be looked up in the database.
+fn lookup_error(stele: &str, err: &anyhow::Error) -> HttpResponse {
+    tracing::error!("Error finding the published commits of {stele}: {err:?}");
+    respond_text(
+        HttpResponse::InternalServerError(),
+        HTTPError::InternalServerError.to_string(),
+    )
+}
+
+/// Respond `500 Internal Server Error` to a request for `path` whose git lookup could not run
+/// on the blocking thread pool.
+fn blocking_error(path: &str, err: &BlockingError) -> HttpResponse {
+    tracing::error!("Error looking up {path}: {err}");
+    respond_text(
+        HttpResponse::InternalServerError(),
+        HTTPError::InternalServerError.to_string(),
+    )
+}
+
+/// Insert the `Vary` and `Content-Language` headers of a document negotiated by format if
+/// `negotiable`, and by language if it is served in a declared `language`.
+fn insert_negotiated_headers(
+    response: &mut HttpResponseBuilder,
+    negotiable: bool,
+    language: Option<&str>,
+) {
+    let vary = match (negotiable, language.is_some()) {
+        (true, true) => "Accept, Accept-Language",
+        (true, false) => "Accept",
+        (false, true) => "Accept-Language",
+        (false, false) => return,
+    };
+    response.insert_header((header::VARY, vary));
+    if let Some(tag) = language {
+        response.insert_header((header::CONTENT_LANGUAGE, tag));
+    }
+}
+
+/// Normalized path of the document requested by `req`, from its `{prefix}` and `{tail}` segments.
+///
+/// # Errors
+/// Errors if the path is not a valid url path, see [`normalize_path`].
+fn requested_path(req: &HttpRequest) -> Result<String, InvalidPath> {
+    let prefix = req.match_info().get("prefix").unwrap_or_default();
+    let tail = req.match_info().get("tail").unwrap_or_default();
+    normalize_path(&format!("{prefix}/{tail}"))
+}
+
+/// Count a view of the current html document at `path` of the stele `stele`, if views are counted.
+///
+/// Only `GET` requests are counted as views.
+fn count_view(req: &HttpRequest, stele: &str, path: &str) {
+    if req.method() != Method::GET {
+        return;
+    }
+    if let Some(views) = req.app_data::<web::Data<ViewCounter>>() {
+        views.record(stele, path);
+    }
+}
+
+/// Look up the listing of the directory at `path` of the `repo` on the blocking thread pool, see
+/// [`find_listing`].
+fn look_up_listing(
+    repo: &web::Data<RepoState>,
+    cache: &Cache,
+    withheld: &WithheldHeads,
+    path: &str,
+    base: String,
+) -> impl Future<Output = Result<Option<Listing>, BlockingError>> {
+    let (data, blobs, served, directory) = (
+        repo.clone(),
+        cache.clone(),
+        withheld.clone(),
+        path.to_owned(),
+    );
+    web::block(move || {
+        let head = HeadLookup {
+            cache: &blobs,
+            repo: &data,
+            withheld: &served,
+        };
+        find_listing(&head, &directory, &base)
+    })
+}
+
+/// List the directory at `path` of the data repository, if it is a directory without an index
+/// document, with the urls of its entries under the url `base` of the directory.
+///
+/// The `HEAD` commit is looked up in the `cache` like for documents, see [`find_head_blob`].
+fn find_listing(head: &HeadLookup, path: &str, base: &str) -> Option<Listing> {
+    let repo = head.repo;
+    let repository = format!("{}/{}", repo.org, repo.name);
+    let (head_commit, opened) =
+        resolve_head(head.cache, &repo.archive_path, &repo.org, &repo.name).ok()?;
+    let commit = served_commit(head.withheld, &repository, head_commit)?;
+    let git_repo = match opened {
+        Some(git_repo) => git_repo,
+        None => Repo::new(&repo.archive_path, &repo.org, &repo.name).ok()?,
+    };
+    if git_repo.get_bytes_at_path(&commit, path).is_ok() {
+        return None;
+    }
+    let entries = git_repo.list_tree(&commit, path).ok()?;
+    Some(Listing {
+        path: path.to_owned(),
+        entries: entries
+            .into_iter()
+            .map(|entry| ListingEntry {
+                url: format!(
+                    "{base}{}{}",
+                    entry.name,
+                    if entry.is_directory { "/" } else { "" }
+                ),
+                name: entry.name,
+                is_directory: entry.is_directory,
+            })
+            .collect(),
+    })
+}
+
+/// Respond with the directory `listing`, as json if requested, and as html otherwise.
+fn respond_listing(listing: &Listing, representation: Representation) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.insert_header((header::VARY, "Accept"));
+    if representation == Representation::Json {
+        respond_json(response, listing)
+    } else {
+        respond(
+            response,
+            mime::TEXT_HTML,
+            render_listing(listing).into_bytes(),
+        )
+    }
+}
+
+/// Render the directory `listing` as an html page.
+fn render_listing(listing: &Listing) -> String {
+    let title = escape(&format!("Index of /{}", listing.path));
+    let items: Vec<String> = listing
+        .entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<li><a href=\"{}\">{}{}</a></li>",
+                escape(&entry.url),
+                escape(&entry.name),
+                if entry.is_directory { "/" } else { "" }
+            )
+        })
+        .collect();
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+         <body><h1>{title}</h1><ul>{}</ul></body></html>",
+        items.concat()
+    )
+}
+
+/// Find the current document at `path` in the data repository of the `representation`.
+fn find_representation(
+    repo: &RepoState,
+    withheld: &WithheldHeads,
+    path: &str,
+    representation: Representation,
+) -> Option<(Format, Vec<u8>)> {
+    let stem = document_stem(path);
+    repo.alternates
+        .iter()
+        .filter(|alternate| alternate.repo_type == representation.repo_type())
+        .find_map(|alternate| {
+            let (org, name) = get_name_parts(&alternate.repository).ok()?;
+            let alternate_repo = Repo::new(&repo.archive_path, &org, &name).ok()?;
+            let head = alternate_repo.head_commit_id().ok()?;
+            let commit = served_commit(withheld, &alternate.repository, head)?;
+            let blob_path = alternate.locate(&alternate_repo, &commit, stem)?;
+            let content = alternate_repo.get_bytes_at_path(&commit, &blob_path).ok()?;
+            Some((alternate.format(&blob_path), content))
+        })
+}
+
+/// Find the formats the current document at `path` is available in.
+fn current_formats(head: &HeadLookup, path: &str) -> Vec<Format> {
+    let stem = document_stem(path);
+    head.repo
+        .alternates
+        .iter()
+        .filter_map(|alternate| find_current_format(head, alternate, stem))
+        .collect()
+}
+
+/// Find the current document `stem` in the `alternate` repository, in the `cache` if looked up
+/// at its `HEAD` commit before.
+///
+/// The `HEAD` commit is looked up in the `cache` like for documents, see [`find_head_blob`], so
+/// the repository is only opened when the path is not cached.
+fn find_current_format(head: &HeadLookup, alternate: &Alternate, stem: &str) -> Option<Format> {
+    let (org, name) = get_name_parts(&alternate.repository).ok()?;
+    let cache = head.cache;
+    let (head_commit, opened) = resolve_head(cache, &head.repo.archive_path, &org, &name).ok()?;
+    let commit = served_commit(head.withheld, &alternate.repository, head_commit)?;
+    if let Some(cached) = cache.alternate_path(&alternate.repository, &commit, stem) {
+        return cached.map(|found| alternate.format(&found));
+    }
+    let alternate_repo = match opened {
+        Some(alternate_repo) => alternate_repo,
+        None => Repo::new(&head.repo.archive_path, &org, &name).ok()?,
+    };
+    let located = alternate.locate(&alternate_repo, &commit, stem);
+    let format = located.as_deref().map(|found| alternate.format(found));
+    cache.insert_alternate_path(
+        alternate.repository.clone(),
+        commit,
+        stem.to_owned(),
+        located,
+    );
+    format
+}
+
+/// Build `Link` header values pointing to the current document served from the `repo` in the
+/// other `formats` it is available in, e.g.
+/// `</_xml/a/b/c.xml>; rel="alternate"; type="application/xml"`, under the `base_path` the
+/// archive is served under.
+fn alternate_links(repo: &RepoState, formats: Vec<Format>, base_path: &BasePath) -> Vec<String> {
+    let served = format!("{}/{}", repo.org, repo.name);
+    formats
+        .into_iter()
+        .filter(|format| format.repository != served)
+        .map(|format| format.mounted(base_path))
+        .map(|format| {
+            format!(
+                "<{}>; rel=\"alternate\"; type=\"{}\"",
+                format.url, format.media_type
+            )
+        })
+        .collect()
+}
+
+/// Whether the current document at `path` is rewritten for the request, see [`rewrite`].
+fn is_rewritten(path: &str) -> bool {
+    get_contenttype(path).0 == mime::TEXT_HTML
+}
+
+/// Rewrite the current html document `content` at `path` for the request `req`: wrap it in the
+/// repository's layout, describe it with the structured data `values` of the stele, and mount its
+/// root-relative urls under the base path. Other documents are returned unchanged.
+fn rewrite(
+    req: &HttpRequest,
+    head: &HeadLookup,
+    structured_data: Option<&web::Data<StructuredData>>,
+    path: &str,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    if !is_rewritten(path) {
+        return content;
+    }
+    let body = match head.repo.layout.as_deref() {
+        Some(layout) => wrap_in_layout(head, layout, path, content),
+        None => content,
+    };
+    let stele = &head.repo.stele;
+    let described = match structured_data.and_then(|configured| configured.for_stele(stele)) {
+        Some(stele_values) => describe(req, path, &stele_values, body),
+        None => body,
+    };
+    BasePath::of(req).html(described)
+}
+
+/// Wrap the html fragment `content` at `path` in the repository's current `layout` template.
+///
+/// Returns `content` unchanged if it is a complete document, or if the layout cannot be applied.
+fn wrap_in_layout(head: &HeadLookup, layout: &str, path: &str, content: Vec<u8>) -> Vec<u8> {
+    let wrapped = find_layout(head, layout)
+        .and_then(|template| wrap_fragment(&template, &content, path, "", ""));
+    wrapped.unwrap_or_else(|err| {
+        tracing::warn!("{path}: unable to apply layout {layout}: {err}");
+        content
+    })
+}
+
+/// Find the `layout` template at the `HEAD` commit of the repository, in the `cache` if it was
+/// read from that commit before.
+///
+/// # Errors
+/// Errors if the repository cannot be opened, its `HEAD` commit is withheld without a published
+/// commit, or the layout is not found at the commit.
+fn find_layout(head: &HeadLookup, layout: &str) -> anyhow::Result<String> {
+    let (repo, cache) = (head.repo, head.cache);
+    let repository = format!("{}/{}", repo.org, repo.name);
+    let (head_commit, opened) = resolve_head(cache, &repo.archive_path, &repo.org, &repo.name)?;
+    let commit =
+        served_commit(head.withheld, &repository, head_commit).context("No published commit")?;
+    if let Some(template) = cache.layout(&repository, &commit) {
+        return Ok(template);
+    }
+    let git_repo = match opened {
+        Some(git_repo) => git_repo,
+        None => Repo::new(&repo.archive_path, &repo.org, &repo.name)?,
+    };
+    let blob = git_repo.get_bytes_at_path(&commit, &normalize_path(layout)?)?;
+    let template = String::from_utf8_lossy(&blob).into_owned();
+    cache.insert_layout(repository, commit, template.clone());
+    Ok(template)
+}
+
+/// Describe the current html document `content` at `path` with its `Legislation` structured data.
+///
+/// Returns `content` unchanged if the structured data cannot be inserted.
+fn describe(
+    req: &HttpRequest,
+    path: &str,
+    values: &StructuredDataValues,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    let url = format!(
+        "{}{}/{path}",
+        Forwarded::of(req).origin(),
+        BasePath::of(req).as_str()
+    );
+    let document = Document {
+        path,
+        url: &url,
+        served_url: &url,
+        version_date: None,
+    };
+    insert_legislation(&content, &document, values).unwrap_or_else(|err| {
+        tracing::warn!("{path}: unable to insert structured data: {err}");
+        content
+    })
+}
+
+/// Find the current document at `path` in the negotiated `language`, falling back to the
+/// document in the default language of the repository.
+///
+/// Variants are looked up at the `HEAD` commit like documents in the default language, in the
+/// `cache` first, see [`find_head_blob`].
+///
+/// Returns the document, and its language if the repository declares languages.
+fn find_current_document(
+    head: &HeadLookup,
+    shared: &SharedState,
+    path: &str,
+    language: Option<&str>,
+) -> (anyhow::Result<HeadBlob>, Option<String>) {
+    let Some(languages) = head.repo.languages.as_ref() else {
+        return (find_current_blob(head, shared, path), None);
+    };
+    if let Some(lang) = language.filter(|&lang| lang != languages.default) {
+        let variant = languages
+            .variant_paths(path, lang)
+            .iter()
+            .find_map(|variant_path| find_head_blob(head, variant_path).ok());
+        if let Some(blob) = variant {
+            return (Ok(blob), Some(lang.to_owned()));
         }
     }
+    (
+        find_current_blob(head, shared, path),
+        Some(languages.default.clone()),
+    )
+}
+
+/// Look up the current document at `path` of the `repo` in the negotiated `language`, see
+/// [`find_current_document`], and the formats it is available in, see [`current_formats`], on
+/// the blocking thread pool.
+fn look_up_current_document(
+    repo: &web::Data<RepoState>,
+    shared: web::Data<SharedState>,
+    cache: &Cache,
+    withheld: &WithheldHeads,
+    path: &str,
+    language: Option<String>,
+) -> impl Future<Output = Result<CurrentDocument, BlockingError>> {
+    let (data, blobs, served, document_path) = (
+        repo.clone(),
+        cache.clone(),
+        withheld.clone(),
+        path.to_owned(),
+    );
+    web::block(move || {
+        let head = HeadLookup {
+            cache: &blobs,
+            repo: &data,
+            withheld: &served,
+        };
+        let (blob, content_language) =
+            find_current_document(&head, &shared, &document_path, language.as_deref());
+        let formats = if blob.is_ok() {
+            current_formats(&head, &document_path)
+        } else {
+            vec![]
+        };
+        (blob, content_language, formats)
+    })
 }
 
 /// Find the latest blob for the given path from the given repo
-/// Latest blob is found by looking at the HEAD commit
-#[tracing::instrument(name = "Finding document", skip(repo, shared))]
+/// Latest blob is found by looking at the HEAD commit, or in the warmed or indexed `cache`
+#[tracing::instrument(name = "Finding document", skip(head, shared))]
 fn find_current_blob(
-    repo: &RepoState,
+    head: &HeadLookup,
     shared: &SharedState,
     path: &str,
-) -> anyhow::Result<Vec<u8>> {
-    let blob = Repo::find_blob(&repo.archive_path, &repo.org, &repo.name, path, HEAD_COMMIT);
-    match blob {
-        Ok(content) => Ok(content),
+) -> anyhow::Result<HeadBlob> {
+    match find_head_blob(head, path) {
+        Ok(blob) => Ok(blob),
         Err(error) => {
             if let Some(fallback) = shared.fallback.as_ref() {
-                let fallback_blob = Repo::find_blob(
-                    &fallback.archive_path,
-                    &fallback.org,
-                    &fallback.name,
-                    path,
-                    HEAD_COMMIT,
-                );
-                return fallback_blob.map_or_else(
+                let fallback_head = HeadLookup {
+                    repo: fallback,
+                    ..*head
+                };
+                return find_head_blob(&fallback_head, path).map_or_else(
                     |err| anyhow::bail!("No fallback blob found - {}", err.to_string()),
                     Ok,
                 );
@@ -68,3 +737,149 @@ fn find_current_blob(
         }
     }
 }
+
+/// Find the blob at `path` of the `HEAD` commit of the repository, in the warmed `cache` or by
+/// its id in the index of current blobs if its `HEAD` did not move since.
+///
+/// The `HEAD` commit is remembered in the `cache` for [`crate::server::cache::HEAD_MAX_AGE`], so
+/// the repository is only opened when the blob is not cached.
+///
+/// A blob not found is remembered in the `cache` for the `HEAD` commit, so it is not looked up
+/// again until [`crate::server::cache::NOT_FOUND_MAX_AGE`] passed.
+///
+/// A withheld `HEAD` commit is replaced by the published commit served instead, see
+/// [`withheld_heads`].
+fn find_head_blob(head: &HeadLookup, path: &str) -> anyhow::Result<HeadBlob> {
+    let (repo, cache) = (head.repo, head.cache);
+    let repository = format!("{}/{}", repo.org, repo.name);
+    let (head_commit, opened) = resolve_head(cache, &repo.archive_path, &repo.org, &repo.name)?;
+    let Some(commit) = served_commit(head.withheld, &repository, head_commit) else {
+        anyhow::bail!(GIT_REQUEST_NOT_FOUND);
+    };
+    if let Some((content, digest)) = cache.blob(&repository, &commit, path) {
+        return Ok(HeadBlob {
+            content,
+            digest: Some(digest),
+        });
+    }
+    if cache.is_missing_blob(&repository, &commit, path) {
+        anyhow::bail!(GIT_REQUEST_NOT_FOUND);
+    }
+    let git_repo = match opened {
+        Some(git_repo) => git_repo,
+        None => Repo::new(&repo.archive_path, &repo.org, &repo.name)?,
+    };
+    let content = match cache
+        .blob_id(&repository, &commit, path)
+        .and_then(|blob_id| git_repo.get_bytes_by_id(&blob_id).ok())
+    {
+        Some(content) => content,
+        None => git_repo
+            .get_bytes_at_path(&commit, path)
+            .inspect_err(|err| {
+                if err.to_string() == GIT_REQUEST_NOT_FOUND {
+                    cache.insert_missing_blob(repository, commit, path.to_owned());
+                }
+            })?,
+    };
+    Ok(HeadBlob {
+        content,
+        digest: None,
+    })
+}
+
+/// Resolve the `HEAD` commit of the data repository `org`/`name` of the archive at
+/// `archive_path`, in the `cache` if resolved less than [`crate::server::cache::HEAD_MAX_AGE`]
+/// ago.
+///
+/// Returns the commit, and the repository if it had to be opened to resolve it.
+///
+/// # Errors
+/// Errors if the repository cannot be opened, or its `HEAD` cannot be resolved.
+fn resolve_head(
+    cache: &Cache,
+    archive_path: &Path,
+    org: &str,
+    name: &str,
+) -> anyhow::Result<(String, Option<Repo>)> {
+    let repository = format!("{org}/{name}");
+    if let Some(cached) = cache.head(&repository) {
+        return Ok((cached, None));
+    }
+    let git_repo = Repo::new(archive_path, org, name)?;
+    let resolved = git_repo.head_commit_id()?;
+    cache.insert_head(repository, resolved.clone());
+    Ok((resolved, Some(git_repo)))
+}
+
+/// The commit the current documents of the `repository` are served from: its `head` commit, or
+/// the published commit served instead if it is `withheld`, see [`withheld_heads`].
+///
+/// Returns `None` if the `head` commit is withheld and no commit of the repository is published.
+fn served_commit(withheld: &WithheldHeads, repository: &str, head: String) -> Option<String> {
+    withheld.get(repository).map_or(Some(head), Clone::clone)
+}
+
+/// Resolve the commits served instead of the withheld `HEAD` commits of the data repositories of
+/// the `stele`, in the cache if resolved less than [`crate::server::cache::HEAD_MAX_AGE`] ago.
+///
+/// The `HEAD` commit of a typed data repository is withheld if it is recorded, but not in a
+/// publication served to the request: publications awaiting approval are never served, and
+/// preview and approved publications only to requests that may see `previews`. The latest commit
+/// of its type recorded in a served publication is served instead. `HEAD` commits not recorded
+/// yet, e.g. of a publication being ingested, are served as is.
+///
+/// # Errors
+/// Errors if the database cannot be reached.
+#[expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+async fn withheld_heads(
+    app: &AppState,
+    stele: &str,
+    previews: bool,
+) -> anyhow::Result<WithheldHeads> {
+    let cache = app.cache();
+    if let Some(cached) = cache.withheld_heads(stele, previews) {
+        return Ok(cached);
+    }
+    let mut withheld = WithheldHeads::new();
+    let db = app.db().for_stele(stele);
+    let archive = app.archive();
+    for repository in archive
+        .stelae
+        .get(stele)
+        .and_then(|found| found.repositories.as_ref())
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+    {
+        let Some(repo_type) = repository.get_type() else {
+            continue;
+        };
+        let Ok(head) = Repo::new(&archive.path, &repository.get_org(), &repository.get_name())
+            .and_then(|git_repo| git_repo.head_commit_id())
+        else {
+            continue;
+        };
+        let served =
+            data_repo_commits::Manager::find_by_stele_and_commit_hash(db, stele, &head, previews);
+        if served.await?.is_some() {
+            continue;
+        }
+        let recorded =
+            data_repo_commits::Manager::find_recorded_by_stele_and_commit_hash(db, stele, &head);
+        if recorded.await?.is_none() {
+            continue;
+        }
+        let latest = data_repo_commits::Manager::find_latest_served_by_stele_and_repo_type(
+            db, stele, &repo_type, previews,
+        );
+        withheld.insert(
+            repository.name.clone(),
+            latest.await?.map(|commit| commit.commit_hash),
+        );
+    }
+    cache.insert_withheld_heads(stele.to_owned(), previews, withheld.clone());
+    Ok(withheld)
+}
@@ -0,0 +1,624 @@
+//! Handlers for pinning and serving named, immutable snapshots of a stele.
+//!
+//! A snapshot records the data repository commits of a stele on a date, so
+//! the exact documents stay retrievable under `/_snapshot/{name}/...`
+//! regardless of later publications, e.g. for litigation holds.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{error::ResponseError as _, web, HttpRequest, HttpResponse, Responder};
+use anyhow::Context as _;
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        models::{
+            data_repo_commits,
+            snapshot::{self, PinnedCommit, Snapshot},
+        },
+        DatabaseTransaction, Databases, Tx as _,
+    },
+    server::{
+        api::takedown::unavailable,
+        auth::{self, AuthError},
+        base_path::BasePath,
+        errors::HTTPError,
+        proxy::Forwarded,
+    },
+    stelae::{
+        archive::{Archive, StructuredData},
+        types::repositories::Custom,
+    },
+    utils::{
+        archive::get_name_parts,
+        date,
+        git::Repo,
+        html::{set_canonical_href, set_canonical_link},
+        http::{get_contenttype, respond_digested_blob, respond_json, respond_text, Sha256Digest},
+        paths::normalize_path,
+        structured_data::{insert_legislation, Document},
+        template::{inject, watermark, wrap_fragment},
+    },
+};
+
+use super::dated::find_repositories_at_commit;
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+
+/// Request body of the pin endpoint.
+#[derive(Debug, Deserialize)]
+pub struct PinRequest {
+    /// Unique name of the snapshot. May only contain ASCII letters, digits, `-`, `_` and `.`.
+    pub name: String,
+    /// Date of the documents to pin. Defaults to today.
+    pub date: Option<NaiveDate>,
+}
+
+/// Query parameters of the snapshot documents endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Serve html documents in canonical form, see [`serve_snapshot`].
+    #[serde(default)]
+    pub canonical: bool,
+}
+
+/// Pin a named snapshot of the stele's data repositories.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+/// Snapshots can never be changed or removed once pinned.
+///
+/// Pinning is refused with `403 Forbidden` unless the request was authenticated as an admin of
+/// the stele, see [`auth::require_admin`], as snapshots can never be removed.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn pin(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    body: web::Json<PinRequest>,
+) -> impl Responder {
+    let admin = match auth::require_admin(&req) {
+        Ok(admin) => admin,
+        Err(err) => return err.error_response(),
+    };
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    if admin.stele != stele {
+        return AuthError::Forbidden.error_response();
+    }
+    let PinRequest { name, date } = body.into_inner();
+    if !is_valid_name(&name) {
+        return respond_text(
+            HttpResponse::BadRequest(),
+            "Snapshot names may only contain ASCII letters, digits, `-`, `_` and `.`.",
+        );
+    }
+    let db = data.db();
+    match snapshot::Manager::find_by_name(db.shared(), &name).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return respond_text(
+                HttpResponse::Conflict(),
+                format!("Snapshot {name} already exists."),
+            );
+        }
+        Err(err) => {
+            tracing::error!("Error finding snapshot {name}: {err:?}");
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    }
+    let pin_date = date.unwrap_or_else(|| Utc::now().date_naive());
+    match pin_snapshot(data.archive(), db, &stele, &name, &pin_date).await {
+        Ok(Some(pinned)) => respond_json(HttpResponse::Created(), &pinned),
+        Ok(None) => respond_text(
+            HttpResponse::NotFound(),
+            format!("No data repository commits found for stele {stele} on {pin_date}."),
+        ),
+        Err(err) => {
+            tracing::error!("Error pinning snapshot {name}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Serve a document of a snapshot, at `/_snapshot/{name}/{path}`.
+///
+/// The document is looked up in each pinned data repository commit in turn. Its canonical
+/// url is the url of the current document, so search engines index the current version only.
+///
+/// With `canonical=true`, html documents are served in canonical form: the bytes pinned in the
+/// data repository, with only the `href` of an existing canonical link rewritten, and without
+/// the layout, banner and structured data, and root-relative urls prefixed with the base path the
+/// archive is served under, if any. Every response carries a `Repr-Digest` header of the served
+/// body, whose urls never depend on the host the client requested, so in canonical form it only
+/// changes with the pinned document and the configured origin, and can be hashed reproducibly.
+#[tracing::instrument(skip(req, data))]
+pub async fn serve_snapshot(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    params: web::Query<Params>,
+    structured_data: Option<web::Data<StructuredData>>,
+) -> impl Responder {
+    let name = req.match_info().get("name").unwrap_or_default().to_owned();
+    let path = match normalize_path(req.match_info().get("path").unwrap_or_default()) {
+        Ok(path) => path,
+        Err(err) => return respond_text(HttpResponse::BadRequest(), err.to_string()),
+    };
+    let pinned = match snapshot::Manager::find_by_name(data.db().shared(), &name).await {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            return respond_text(
+                HttpResponse::NotFound(),
+                format!("Snapshot {name} not found."),
+            )
+        }
+        Err(err) => {
+            tracing::error!("Error finding snapshot {name}: {err:?}");
+            return respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            );
+        }
+    };
+    if let Some(reason) = data.takedowns.find(&pinned.stele, &path) {
+        return unavailable(&path, &reason);
+    }
+    let commits =
+        match snapshot::Manager::find_all_commits_by_snapshot(data.db().shared(), &name).await {
+            Ok(commits) => commits,
+            Err(err) => {
+                tracing::error!("Error finding commits of snapshot {name}: {err:?}");
+                return respond_text(
+                    HttpResponse::InternalServerError(),
+                    HTTPError::InternalServerError.to_string(),
+                );
+            }
+        };
+    if commits.is_empty() {
+        return respond_text(
+            HttpResponse::NotFound(),
+            format!("Snapshot {name} not found."),
+        );
+    }
+    let base_path = BasePath::of(&req);
+    // Snapshots are cached as immutable, so their urls must not depend on the requested host.
+    let (canonical, served_url) = {
+        let forwarded = Forwarded::of(&req);
+        (
+            forwarded.stable_url(&format!("{}/{path}", base_path.as_str())),
+            forwarded.stable_url(&base_path.url(req.path())),
+        )
+    };
+    let document = Document {
+        path: &path,
+        url: &canonical,
+        served_url: &served_url,
+        version_date: None,
+    };
+    let archive_path = &data.archive().path;
+    for commit in &commits {
+        let Ok((org, repo_name)) = get_name_parts(&commit.repository) else {
+            continue;
+        };
+        if let Ok(content) =
+            Repo::find_blob(archive_path, &org, &repo_name, &path, &commit.commit_hash)
+        {
+            let contenttype = get_contenttype(&path);
+            let body = if contenttype.0 != mime::TEXT_HTML {
+                content
+            } else if params.canonical {
+                let mounted = base_path.html(content);
+                set_canonical_href(&mounted, &canonical).unwrap_or_else(|err| {
+                    tracing::warn!("{path}: unable to set canonical link: {err}");
+                    mounted
+                })
+            } else {
+                decorate(
+                    &data,
+                    &pinned,
+                    commit,
+                    &document,
+                    &base_path,
+                    structured_data.as_ref().map(web::Data::get_ref),
+                    content,
+                )
+            };
+            let digest = Sha256Digest::of(&body);
+            let mut response = HttpResponse::Ok();
+            response
+                .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+                .insert_header(("Link", format!("<{canonical}>; rel=\"canonical\"")))
+                .insert_header(("Repr-Digest", digest.repr_digest()));
+            return respond_digested_blob(response, contenttype.0, body, &digest);
+        }
+    }
+    tracing::debug!("{path}: not found in snapshot {name}");
+    respond_text(HttpResponse::NotFound(), HTTPError::NotFound.to_string())
+}
+
+/// Find the custom configuration of the data repository named `repository`, as listed at the
+/// authentication commit of the `pinned` snapshot, so snapshots of repositories removed since
+/// keep their layout and injections.
+fn find_custom(archive: &Archive, pinned: &Snapshot, repository: &str) -> Option<Custom> {
+    find_repositories_at_commit(archive, &pinned.stele, &pinned.auth_commit_hash)?
+        .repositories
+        .remove(repository)
+        .map(|listed| listed.custom)
+}
+
+/// Wrap the html `content` of the `document` of the `pinned` snapshot in the layout template of
+/// the pinned `commit`, mark it with the banner of historical documents of its stele, inject the
+/// elements its data repository declares, mount its root-relative urls under the `base_path`,
+/// point its canonical link at the url of the current document, and describe it with
+/// `structured_data`.
+///
+/// The date of the snapshot is displayed in the locale of its stele.
+fn decorate(
+    data: &AppState,
+    pinned: &Snapshot,
+    commit: &PinnedCommit,
+    document: &Document<'_>,
+    base_path: &BasePath,
+    structured_data: Option<&StructuredData>,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    let path = document.path;
+    let display_date = data
+        .locales
+        .for_stele(&pinned.stele)
+        .format_date(pinned.date);
+    let custom = find_custom(data.archive(), pinned, &commit.repository);
+    let wrapped = match custom.as_ref().and_then(|found| found.layout.as_deref()) {
+        Some(layout) => wrap_in_layout(
+            data,
+            &pinned.name,
+            commit,
+            layout,
+            path,
+            &display_date,
+            content,
+        ),
+        None => content,
+    };
+    let banner_text = data.watermarks.for_stele(&pinned.stele);
+    let marked = match banner_text {
+        Some(text) => watermark(&wrapped, &text, &display_date).unwrap_or_else(|err| {
+            tracing::warn!("{path}: unable to mark historical document: {err}");
+            wrapped
+        }),
+        None => wrapped,
+    };
+    let version_date = date::format(pinned.date);
+    let injected = match custom
+        .as_ref()
+        .and_then(|found| found.injections.as_deref())
+    {
+        Some(injections) => inject(&marked, injections, &version_date).unwrap_or_else(|err| {
+            tracing::warn!("{path}: unable to inject elements: {err}");
+            marked
+        }),
+        None => marked,
+    };
+    // The urls of the document are mounted already, unlike the urls inserted from here on.
+    let mounted = base_path.html(injected);
+    let versioned = Document {
+        version_date: Some(&version_date),
+        ..*document
+    };
+    canonicalize(&pinned.stele, &versioned, structured_data, mounted)
+}
+
+/// Point the canonical link of the mounted html `content` of the historical `document` of the
+/// `stele` at the url of its current version, and describe it with `structured_data`.
+///
+/// Returns `content` as far as it could be rewritten.
+#[must_use]
+pub fn canonicalize(
+    stele: &str,
+    document: &Document<'_>,
+    structured_data: Option<&StructuredData>,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    let path = document.path;
+    let linked = set_canonical_link(&content, document.url).unwrap_or_else(|err| {
+        tracing::warn!("{path}: unable to set canonical link: {err}");
+        content
+    });
+    match structured_data.and_then(|configured| configured.for_stele(stele)) {
+        Some(stele_values) => {
+            insert_legislation(&linked, document, &stele_values).unwrap_or_else(|err| {
+                tracing::warn!("{path}: unable to insert structured data: {err}");
+                linked
+            })
+        }
+        None => linked,
+    }
+}
+
+/// Wrap the html fragment `content` at `path` of the snapshot `name` in the `layout`
+/// template of the pinned `commit`, with the `display_date` of the snapshot.
+///
+/// Returns `content` unchanged if it is a complete document, or if the layout cannot be applied.
+fn wrap_in_layout(
+    data: &AppState,
+    name: &str,
+    commit: &PinnedCommit,
+    layout: &str,
+    path: &str,
+    display_date: &str,
+    content: Vec<u8>,
+) -> Vec<u8> {
+    let wrapped = get_name_parts(&commit.repository).and_then(|(org, repo_name)| {
+        let template = Repo::find_blob(
+            &data.archive().path,
+            &org,
+            &repo_name,
+            layout,
+            &commit.commit_hash,
+        )?;
+        wrap_fragment(
+            &String::from_utf8_lossy(&template),
+            &content,
+            path,
+            &format!("/_snapshot/{name}"),
+            display_date,
+        )
+    });
+    wrapped.unwrap_or_else(|err| {
+        tracing::warn!("{path}: unable to apply layout {layout}: {err}");
+        content
+    })
+}
+
+/// Record the commit of every typed data repository of the stele in the latest publication on
+/// or before `date`.
+///
+/// The authentication commit of the latest data repository commit of each repository type on or
+/// before `date` is looked up in the database of the stele, and the commit of each repository is
+/// read from its target at that authentication commit, so repositories of the same type are
+/// pinned at their own commits. Repositories without a target at that commit are skipped, and
+/// commits of preview publications are never pinned. The snapshot is recorded in the database of
+/// the archive.
+/// Returns `None` if no commits were found.
+async fn pin_snapshot(
+    archive: &Archive,
+    db: &Databases,
+    stele_name: &str,
+    name: &str,
+    date: &NaiveDate,
+) -> anyhow::Result<Option<Snapshot>> {
+    let stele = archive
+        .stelae
+        .get(stele_name)
+        .with_context(|| format!("Stele {stele_name} not found in the archive"))?;
+    let mut pinned = None;
+    let mut commits = vec![];
+    for repository in stele
+        .repositories
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+    {
+        let Some(repo_type) = repository.get_type() else {
+            continue;
+        };
+        let Some(data_repo_commit) =
+            data_repo_commits::Manager::find_latest_by_stele_and_repo_type_on_or_before_date(
+                db.for_stele(stele_name),
+                stele_name,
+                &repo_type,
+                date,
+                false,
+            )
+            .await?
+        else {
+            continue;
+        };
+        let Some(target) = stele.get_targets_metadata_at_commit_and_filename(
+            &data_repo_commit.auth_commit_hash,
+            &repository.get_name(),
+        )?
+        else {
+            continue;
+        };
+        pinned.get_or_insert_with(|| Snapshot {
+            name: name.to_owned(),
+            stele: stele_name.to_owned(),
+            publication: data_repo_commit.publication_id.clone(),
+            date: *date,
+            auth_commit_hash: data_repo_commit.auth_commit_hash.clone(),
+        });
+        commits.push(PinnedCommit {
+            snapshot: name.to_owned(),
+            repository: repository.name.clone(),
+            repo_type,
+            commit_hash: target.commit,
+        });
+    }
+    let Some(pinned_snapshot) = pinned else {
+        return Ok(None);
+    };
+    let mut tx = DatabaseTransaction {
+        tx: db.shared().pool.begin().await?,
+    };
+    snapshot::TxManager::create(&mut tx, &pinned_snapshot).await?;
+    snapshot::TxManager::insert_commits_bulk(&mut tx, commits).await?;
+    tx.commit().await?;
+    Ok(Some(pinned_snapshot))
+}
+
+/// Whether `name` is a valid snapshot name, usable as a single url path segment.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::api::snapshot::*;
+
+    #[test]
+    fn test_is_valid_name_when_url_safe_expect_true() {
+        let cut = is_valid_name;
+        let actual = cut("smith-v-jones_2023.1");
+        assert!(actual);
+    }
+
+    #[test]
+    fn test_is_valid_name_when_path_or_empty_expect_false() {
+        let cut = is_valid_name;
+        let actual: Vec<bool> = ["", "..", "a/b", "a b"].into_iter().map(cut).collect();
+        let expected = vec![false; 4];
+        assert_eq!(actual, expected);
+    }
+
+    /// Generate an archive with the history of one document in two versions, and connect to its
+    /// databases.
+    #[cfg(feature = "test-fixtures")]
+    async fn generate_archive() -> (tempfile::TempDir, Archive, Databases) {
+        use crate::{db, history::changes, testing::generate, utils::output::Output};
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive_path = archive_dir.path();
+        let size = generate::Size {
+            documents: 1,
+            versions: 2,
+        };
+        generate::generate(archive_path, size).unwrap();
+        changes::insert(
+            &archive_path.to_string_lossy(),
+            archive_path.to_path_buf(),
+            true,
+            false,
+            Output::Text,
+        )
+        .await
+        .unwrap();
+        let archive = Archive::parse(archive_path.to_path_buf(), archive_path, false).unwrap();
+        let shared = db::init::connect(archive_path).await.unwrap();
+        let databases = db::init::connect_stelae(archive_path, archive.stelae.keys(), shared)
+            .await
+            .unwrap();
+        (archive_dir, archive, databases)
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_pin_snapshot_when_earlier_date_expect_repository_commit_of_its_target() {
+        use std::collections::BTreeSet;
+
+        let (archive_dir, archive, databases) = generate_archive().await;
+        let first = |name: &str| {
+            let commit = Repo::new(archive_dir.path(), "generated", name)
+                .unwrap()
+                .repo
+                .revparse_single("HEAD~1")
+                .unwrap()
+                .id()
+                .to_string();
+            (format!("generated/{name}"), commit)
+        };
+        let cut = pin_snapshot;
+        let date = NaiveDate::from_ymd_opt(2020, 1, 15).unwrap();
+        let pinned = cut(&archive, &databases, "generated/law", "first", &date)
+            .await
+            .unwrap();
+        assert!(pinned.is_some());
+        let actual = snapshot::Manager::find_all_commits_by_snapshot(databases.shared(), "first")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|commit| (commit.repository, commit.commit_hash))
+            .collect::<BTreeSet<_>>();
+        let expected = ["law-html", "law-pdf", "law-xml"]
+            .map(first)
+            .into_iter()
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_serve_snapshot_when_requested_on_other_host_expect_same_response() {
+        use crate::history::views::ViewCounter;
+        use crate::server::{
+            api::{identifiers::Identifiers, takedown::Takedowns},
+            cache::Cache,
+            load_shedding::LoadShedder,
+            scheduler::Updates,
+        };
+        use crate::stelae::archive::{Locales, Watermarks};
+        use crate::utils::http::{content_sha256, repr_digest, CONTENT_SHA256_HEADER};
+        use actix_web::{test, App};
+
+        let (_archive_dir, archive, databases) = generate_archive().await;
+        let date = NaiveDate::from_ymd_opt(2020, 1, 15).unwrap();
+        pin_snapshot(&archive, &databases, "generated/law", "first", &date)
+            .await
+            .unwrap();
+        let state = AppState {
+            archive,
+            authenticator: None,
+            db: databases,
+            cache: Cache::default(),
+            identifiers: Identifiers::default(),
+            locales: Locales::default(),
+            takedowns: Takedowns::default(),
+            watermarks: Watermarks::default(),
+            updates: Updates::default(),
+            base_path: BasePath::default(),
+            views: ViewCounter::default(),
+            load_shedder: LoadShedder::default(),
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .route("/_snapshot/{name}/{path:.*}", web::get().to(serve_snapshot)),
+        )
+        .await;
+
+        for uri in [
+            "/_snapshot/first/doc-0",
+            "/_snapshot/first/doc-0?canonical=true",
+        ] {
+            let mut responses = vec![];
+            for host in ["laws.example.org", "attacker.example.com"] {
+                let req = test::TestRequest::get()
+                    .uri(uri)
+                    .insert_header(("Host", host))
+                    .insert_header(("X-Forwarded-Host", host))
+                    .to_request();
+                let resp = test::call_service(&app, req).await;
+                assert!(resp.status().is_success(), "{uri}");
+                let headers = ["Link", "Repr-Digest", CONTENT_SHA256_HEADER]
+                    .map(|name| resp.headers().get(name).unwrap().to_owned());
+                let body = test::read_body(resp).await;
+                assert_eq!(headers[1], repr_digest(&body).as_str(), "{uri}");
+                assert_eq!(headers[2], content_sha256(&body).as_str(), "{uri}");
+                responses.push((headers, body));
+            }
+            let first = responses.first().unwrap();
+            assert_eq!(first.0[0], "</doc-0>; rel=\"canonical\"", "{uri}");
+            assert!(
+                !String::from_utf8_lossy(&first.1).contains("example"),
+                "{uri}"
+            );
+            assert_eq!(responses.first(), responses.last(), "{uri}");
+        }
+    }
+}
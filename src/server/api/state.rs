@@ -3,16 +3,44 @@ use std::{fmt, path::PathBuf};
 
 use crate::{
     db,
-    stelae::{archive::Archive, stele::Stele, types::repositories::Repository},
+    history::views::ViewCounter,
+    server::{
+        auth::Authenticator, base_path::BasePath, cache::Cache, load_shedding::LoadShedder,
+        scheduler::Updates,
+    },
+    stelae::{
+        archive::{Archive, Locales, Watermarks},
+        stele::Stele,
+        types::repositories::{Languages, Repository, ServeType},
+    },
     utils::archive::get_name_parts,
 };
 
+use super::formats::{alternates_of, Alternate};
+use super::identifiers::Identifiers;
+use super::takedown::Takedowns;
+
 /// Global, read-only state
 pub trait Global {
     /// Fully initialized Stelae archive
     fn archive(&self) -> &Archive;
-    /// Database connection
-    fn db(&self) -> &db::DatabaseConnection;
+    /// Authenticator of the guarded routes, shared by all workers, if `[auth]` is configured.
+    fn authenticator(&self) -> Option<&Authenticator>;
+    /// Database connections, of the archive and of the stelae isolated in their own database
+    fn db(&self) -> &db::Databases;
+    /// Documents withheld from serving
+    fn takedowns(&self) -> &Takedowns;
+    /// Persistent identifiers of documents
+    fn identifiers(&self) -> &Identifiers;
+    /// Results resolved ahead of traffic by the warmup
+    fn cache(&self) -> &Cache;
+    /// Path the documents and the APIs are served under
+    fn base_path(&self) -> &BasePath;
+    /// Views of current documents counted since they were last written to the database
+    fn views(&self) -> &ViewCounter;
+    /// Counts of the requests in flight, shared by all workers, so the `[concurrency]` limits
+    /// hold server-wide.
+    fn load_shedder(&self) -> &LoadShedder;
 }
 
 /// Application state
@@ -20,8 +48,28 @@ pub trait Global {
 pub struct App {
     /// Fully initialized Stelae archive
     pub archive: Archive,
-    /// Database connection
-    pub db: db::DatabaseConnection,
+    /// Authenticator of the guarded routes, if enabled by the `[auth]` config.
+    pub authenticator: Option<Authenticator>,
+    /// Database connections, of the archive and of the stelae isolated in their own database
+    pub db: db::Databases,
+    /// Results resolved ahead of traffic by the warmup
+    pub cache: Cache,
+    /// Persistent identifiers of documents
+    pub identifiers: Identifiers,
+    /// Locales of display dates, per stele
+    pub locales: Locales,
+    /// Documents withheld from serving
+    pub takedowns: Takedowns,
+    /// Banners of historical documents, per stele
+    pub watermarks: Watermarks,
+    /// Status of the updates scheduled by `stelae serve --update-schedule`
+    pub updates: Updates,
+    /// Path the documents and the APIs are served under, see `stelae --base-path`
+    pub base_path: BasePath,
+    /// Views of current documents, counted if enabled by the `[document_views]` config
+    pub views: ViewCounter,
+    /// Requests in flight, capped by the `[concurrency]` config across all workers.
+    pub load_shedder: LoadShedder,
 }
 
 impl Global for App {
@@ -29,9 +77,37 @@ impl Global for App {
         &self.archive
     }
 
-    fn db(&self) -> &db::DatabaseConnection {
+    fn authenticator(&self) -> Option<&Authenticator> {
+        self.authenticator.as_ref()
+    }
+
+    fn db(&self) -> &db::Databases {
         &self.db
     }
+
+    fn takedowns(&self) -> &Takedowns {
+        &self.takedowns
+    }
+
+    fn identifiers(&self) -> &Identifiers {
+        &self.identifiers
+    }
+
+    fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    fn base_path(&self) -> &BasePath {
+        &self.base_path
+    }
+
+    fn views(&self) -> &ViewCounter {
+        &self.views
+    }
+
+    fn load_shedder(&self) -> &LoadShedder {
+        &self.load_shedder
+    }
 }
 
 /// Repository to serve
@@ -47,13 +123,23 @@ pub struct RepoData {
     // /// path to the git repository
     // pub repo_path: PathBuf;
     ///Latest or historical
-    pub serve: String,
+    pub serve: ServeType,
+    /// Path of the layout template that html fragments are wrapped in, if any
+    pub layout: Option<String>,
+    /// Qualified name of the stele the repository belongs to
+    pub stele: String,
+    /// Data repositories of the stele that documents may also be available in
+    pub alternates: Vec<Alternate>,
+    /// Whether directories without an index document are served as listings
+    pub directory_listing: bool,
+    /// Languages the documents are available in, if the repository declares them
+    pub languages: Option<Languages>,
 }
 
 impl RepoData {
     /// Create a new Repo state object
     #[must_use]
-    pub fn new(archive_path: &str, org: &str, name: &str, serve: &str) -> Self {
+    pub fn new(archive_path: &str, org: &str, name: &str, serve: &ServeType) -> Self {
         let mut repo_path = archive_path.to_owned();
         repo_path = format!("{repo_path}/{org}/{name}");
         Self {
@@ -61,7 +147,12 @@ impl RepoData {
             path: PathBuf::from(&repo_path),
             org: org.to_owned(),
             name: name.to_owned(),
-            serve: serve.to_owned(),
+            serve: serve.clone(),
+            layout: None,
+            stele: String::new(),
+            alternates: vec![],
+            directory_listing: false,
+            languages: None,
         }
     }
 }
@@ -110,6 +201,11 @@ impl Clone for RepoData {
             org: self.org.clone(),
             name: self.name.clone(),
             serve: self.serve.clone(),
+            layout: self.layout.clone(),
+            stele: self.stele.clone(),
+            alternates: self.alternates.clone(),
+            directory_listing: self.directory_listing,
+            languages: self.languages.clone(),
         }
     }
 }
@@ -134,12 +230,19 @@ impl Clone for Shared {
 pub fn init_repo(repo: &Repository, stele: &Stele) -> anyhow::Result<RepoData> {
     let custom = &repo.custom;
     let (org, name) = get_name_parts(&repo.name)?;
-    Ok(RepoData::new(
-        &stele.archive_path.to_string_lossy(),
-        &org,
-        &name,
-        &custom.serve,
-    ))
+    Ok(RepoData {
+        layout: custom.layout.clone(),
+        stele: stele.get_qualified_name(),
+        alternates: alternates_of(stele),
+        directory_listing: custom.directory_listing.unwrap_or(false),
+        languages: custom.languages.clone(),
+        ..RepoData::new(
+            &stele.archive_path.to_string_lossy(),
+            &org,
+            &name,
+            &custom.serve,
+        )
+    })
 }
 
 /// Initialize the shared application state
@@ -154,12 +257,19 @@ pub fn init_shared(stele: &Stele) -> anyhow::Result<Shared> {
         .get_fallback_repo()
         .map(|repo| {
             let (org, name) = get_name_parts(&repo.name)?;
-            Ok::<RepoData, anyhow::Error>(RepoData::new(
-                &stele.archive_path.to_string_lossy(),
-                &org,
-                &name,
-                &repo.custom.serve,
-            ))
+            Ok::<RepoData, anyhow::Error>(RepoData {
+                layout: repo.custom.layout.clone(),
+                stele: stele.get_qualified_name(),
+                alternates: alternates_of(stele),
+                directory_listing: repo.custom.directory_listing.unwrap_or(false),
+                languages: repo.custom.languages.clone(),
+                ..RepoData::new(
+                    &stele.archive_path.to_string_lossy(),
+                    &org,
+                    &name,
+                    &repo.custom.serve,
+                )
+            })
         })
         .transpose()?;
     Ok(Shared { fallback })
@@ -0,0 +1,108 @@
+//! Handlers reporting statistics of the stelae in the archive, and the views of their documents
+//! counted by [`crate::history::views`].
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::models::document_view::{self, TopDocument},
+    history::stats::collect,
+    server::errors::HTTPError,
+    utils::date,
+};
+
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use crate::utils::http::{respond_json, respond_text};
+
+/// Number of days of views listed when no `since` date is requested.
+const DEFAULT_SINCE_DAYS: u64 = 30;
+/// Number of documents listed when no limit is requested.
+const DEFAULT_LIMIT: i64 = 20;
+/// Maximum number of documents listed.
+const MAX_LIMIT: i64 = 100;
+
+/// Query string of the top documents endpoint.
+#[derive(Debug, Deserialize)]
+pub struct TopDocumentsParams {
+    /// Only count views on or after this date. Defaults to 30 days ago.
+    pub since: Option<NaiveDate>,
+    /// Number of documents listed. Defaults to 20, and is capped at 100.
+    pub limit: Option<i64>,
+}
+
+/// Response of the top documents endpoint.
+#[derive(Debug, Serialize)]
+pub struct TopDocuments {
+    /// Qualified name of the stele.
+    pub stele: String,
+    /// Date from which views are counted.
+    #[serde(with = "date::ymd")]
+    pub since: NaiveDate,
+    /// Most viewed documents, most viewed first.
+    pub documents: Vec<TopDocument>,
+}
+
+/// Report the number of documents, collections, publications and versions, the codified dates,
+/// the ingested authentication commits and the repository sizes on disk of every stele.
+#[tracing::instrument(skip(data))]
+pub async fn stats(data: web::Data<AppState>) -> impl Responder {
+    match collect(data.archive(), data.db()).await {
+        Ok(found) => respond_json(HttpResponse::Ok(), &found),
+        Err(err) => {
+            tracing::error!("Error collecting statistics: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// List the most viewed current documents of a stele since the `since` date.
+///
+/// Views are counted if enabled by the `[document_views]` config, and listed once written to the
+/// database. The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(data, access))]
+pub async fn top_documents(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    params: web::Query<TopDocumentsParams>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            return respond_text(HttpResponse::BadRequest(), format!("Error: {err}"));
+        }
+    };
+    let since = params.since.unwrap_or_else(|| {
+        let today = Utc::now().date_naive();
+        today
+            .checked_sub_days(Days::new(DEFAULT_SINCE_DAYS))
+            .unwrap_or(today)
+    });
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let db = data.db().shared();
+    match document_view::Manager::find_top_by_stele(db, &stele, &date::format(since), limit).await {
+        Ok(documents) => respond_json(
+            HttpResponse::Ok(),
+            &TopDocuments {
+                stele,
+                since,
+                documents,
+            },
+        ),
+        Err(err) => {
+            tracing::error!("Error finding the most viewed documents of {stele}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
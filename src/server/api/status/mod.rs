@@ -0,0 +1,31 @@
+//! Handlers exposing the status of the server, such as its scheduled updates.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError as _};
+
+use crate::server::auth;
+use crate::utils::http::{respond_json, respond_text};
+
+use super::state::App as AppState;
+
+/// Respond with the status of the updates scheduled by `stelae serve --update-schedule`, see
+/// [`crate::server::scheduler::Status`].
+///
+/// The status is refused with `403 Forbidden` unless the request was authenticated as an admin,
+/// see [`auth::require_admin`], as the errors of the updates may reveal the upstream and the
+/// layout of the archive.
+#[tracing::instrument(skip(req, data))]
+pub async fn status(req: HttpRequest, data: web::Data<AppState>) -> impl Responder {
+    if let Err(err) = auth::require_admin(&req) {
+        return err.error_response();
+    }
+    respond_json(HttpResponse::Ok(), &data.updates.status())
+}
+
+/// Respond with `200 OK` while the server is running, for liveness checks.
+#[tracing::instrument]
+pub async fn health() -> impl Responder {
+    respond_text(HttpResponse::Ok(), "OK")
+}
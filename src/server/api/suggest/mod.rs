@@ -0,0 +1,77 @@
+//! Handler for suggesting documents and collections while a query is typed.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::{db::models::suggestion, server::errors::HTTPError};
+
+use super::policy::AccessDecision;
+use super::response::ApiVersion;
+use super::state::{App as AppState, Global as _};
+
+/// Number of suggestions returned when no limit is requested.
+const DEFAULT_LIMIT: u32 = 10;
+/// Maximum number of suggestions returned.
+const MAX_LIMIT: u32 = 50;
+
+/// Query string of the suggest endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Partially typed query, e.g. a section number like `1.0`.
+    #[serde(rename = "q")]
+    pub query: String,
+    /// Maximum number of suggestions to return. Capped at 50.
+    pub limit: Option<u32>,
+}
+
+/// Suggest documents and collections with a url segment starting with the query.
+///
+/// Url segments carry the numbers of documents and collections, e.g. `/us/ca/cities/san-mateo/codes/1.01`.
+/// The stele is selected by [`AccessDecision::stele`]. Responses are enveloped as negotiated by
+/// [`ApiVersion`].
+#[tracing::instrument(skip(data, access))]
+pub async fn suggest(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+    params: web::Query<Params>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    let prefix = params.query.trim();
+    if prefix.is_empty() {
+        return api_version.respond_error(
+            HttpResponse::BadRequest(),
+            "invalid_query",
+            "Query parameter `q` must not be empty.",
+        );
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    match suggestion::Manager::find_all_by_stele_and_url_segment_prefix(
+        data.db().for_stele(&stele),
+        &stele,
+        prefix,
+        limit,
+    )
+    .await
+    {
+        Ok(suggestions) => api_version.respond(HttpResponse::Ok(), &suggestions),
+        Err(err) => {
+            tracing::error!("Error finding suggestions for {prefix}: {err:?}");
+            api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
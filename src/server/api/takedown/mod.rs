@@ -0,0 +1,334 @@
+//! Handlers for withholding individual documents from serving, e.g. by court order.
+//!
+//! Taken down documents are answered with `451 Unavailable For Legal Reasons` by the current
+//! document, `/_date`, `/_commit` and `/_snapshot` endpoints, at every path and in every format
+//! they are served at. Their git history is left untouched, so a takedown can be lifted at any
+//! time.
+//!
+//! A document is taken down in the stele selected by [`AccessDecision::stele`], the stele the
+//! admin was authorized in, so documents of other stelae at the same path are still served.
+//! All handlers are refused unless the request was authenticated as an admin of that stele, see
+//! [`admin_stele`].
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError as _};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        models::takedown::{self, Takedown},
+        DatabaseConnection, DatabaseTransaction, Tx as _,
+    },
+    server::{
+        auth::{self, AuthError},
+        errors::HTTPError,
+    },
+    utils::{paths::normalize_path, template::escape},
+};
+
+use super::policy::AccessDecision;
+use super::state::{App as AppState, Global as _};
+use super::versions::SteleError;
+use crate::utils::http::{respond, respond_json, respond_text};
+
+/// Reasons of taken down documents, keyed by stele and document, see [`document_key`].
+///
+/// Loaded from the database at start-up and shared by all workers, so documents are checked
+/// without a database round trip per request.
+#[derive(Debug, Clone, Default)]
+pub struct Takedowns(Arc<RwLock<HashMap<(String, String), String>>>);
+
+impl Takedowns {
+    /// Load all takedowns recorded in the database.
+    ///
+    /// # Errors
+    /// Errors if the takedowns cannot be read from the database.
+    pub async fn load(db: &DatabaseConnection) -> anyhow::Result<Self> {
+        let takedowns = takedown::Manager::find_all(db).await?;
+        let reasons = takedowns
+            .into_iter()
+            .map(|found| {
+                let key = document_key(&found.path).to_owned();
+                ((found.stele, key), found.reason)
+            })
+            .collect();
+        Ok(Self(Arc::new(RwLock::new(reasons))))
+    }
+
+    /// The reason the document at the normalized `path` of the `stele` was taken down, if it was.
+    ///
+    /// The document is found at any path it is served at, e.g. both `a/b/c.html` and
+    /// `a/b/c/index.pdf` of a document taken down as `a/b/c`.
+    #[must_use]
+    pub fn find(&self, stele: &str, path: &str) -> Option<String> {
+        self.0.read().map_or(None, |reasons| {
+            let key = (stele.to_owned(), document_key(path).to_owned());
+            reasons.get(&key).cloned()
+        })
+    }
+
+    /// Take down the document at the normalized `path` of the `stele`.
+    pub fn insert(&self, stele: String, path: &str, reason: String) {
+        if let Ok(mut reasons) = self.0.write() {
+            reasons.insert((stele, document_key(path).to_owned()), reason);
+        }
+    }
+
+    /// Lift the takedown of the document at the normalized `path` of the `stele`.
+    pub fn remove(&self, stele: &str, path: &str) {
+        if let Ok(mut reasons) = self.0.write() {
+            reasons.remove(&(stele.to_owned(), document_key(path).to_owned()));
+        }
+    }
+}
+
+/// The document served at the normalized `path`, without the file extension or index file its
+/// blob is resolved with, see [`BLOB_PATH_POSTFIXES`](crate::utils::git::BLOB_PATH_POSTFIXES) and
+/// [`Alternate::locate`](super::formats::Alternate::locate).
+///
+/// E.g. `a/b/c`, `a/b/c.html`, `a/b/c/index.html` and `a/b/c.pdf` are all the document `a/b/c`.
+/// Only alphabetic extensions are removed, so `a/1.2` is not the document `a/1`.
+fn document_key(path: &str) -> &str {
+    let (parent, file) = path.rsplit_once('/').unwrap_or(("", path));
+    match file.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty()
+                && !extension.is_empty()
+                && extension.bytes().all(|byte| byte.is_ascii_alphabetic()) =>
+        {
+            if stem == "index" {
+                return parent;
+            }
+            path.strip_suffix(extension)
+                .and_then(|without_extension| without_extension.strip_suffix('.'))
+                .unwrap_or(path)
+        }
+        _ => path,
+    }
+}
+
+/// Respond to a request for the taken down document at `path` with `451 Unavailable For Legal Reasons`.
+#[must_use]
+pub fn unavailable(path: &str, reason: &str) -> HttpResponse {
+    tracing::debug!("{path}: taken down");
+    let body = format!(
+        "<!DOCTYPE html><html><head><title>Unavailable For Legal Reasons</title></head>\
+         <body><h1>Unavailable For Legal Reasons</h1><p>{}</p></body></html>",
+        escape(reason)
+    );
+    respond(
+        HttpResponse::UnavailableForLegalReasons(),
+        mime::TEXT_HTML,
+        body.into_bytes(),
+    )
+}
+
+/// Request body of the take down endpoint.
+#[derive(Debug, Deserialize)]
+pub struct TakeDownRequest {
+    /// Path of the document, as in its url.
+    pub path: String,
+    /// Explanation shown to readers requesting the document.
+    pub reason: String,
+}
+
+/// List the taken down documents of the stele.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn list_takedowns(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    let stele = match admin_stele(&req, &access) {
+        Ok(stele) => stele,
+        Err(response) => return response,
+    };
+    match takedown::Manager::find_all_by_stele(data.db().shared(), &stele).await {
+        Ok(takedowns) => respond_json(HttpResponse::Ok(), &takedowns),
+        Err(err) => {
+            tracing::error!("Error finding takedowns: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Take down a document of the stele, in its current documents and every snapshot of the stele.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn take_down(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    body: web::Json<TakeDownRequest>,
+) -> impl Responder {
+    let stele = match admin_stele(&req, &access) {
+        Ok(stele) => stele,
+        Err(response) => return response,
+    };
+    let TakeDownRequest { path, reason } = body.into_inner();
+    let Ok(normalized) = normalize_path(&path) else {
+        return respond_text(
+            HttpResponse::BadRequest(),
+            format!("Invalid document path {path}."),
+        );
+    };
+    if data.takedowns.find(&stele, &normalized).is_some() {
+        return respond_text(
+            HttpResponse::Conflict(),
+            format!("Document {normalized} is already taken down."),
+        );
+    }
+    let created = Takedown {
+        stele,
+        path: normalized,
+        reason,
+    };
+    match record_takedown(data.db().shared(), &created).await {
+        Ok(()) => {
+            data.takedowns
+                .insert(created.stele.clone(), &created.path, created.reason.clone());
+            respond_json(HttpResponse::Created(), &created)
+        }
+        Err(err) => {
+            tracing::error!("Error taking down {}: {err:?}", created.path);
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Lift the takedown of the document of the stele at `/_admin/takedowns/{path}`.
+///
+/// The stele is selected by [`AccessDecision::stele`].
+#[tracing::instrument(skip(req, data, access))]
+pub async fn restore(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+) -> impl Responder {
+    let stele = match admin_stele(&req, &access) {
+        Ok(stele) => stele,
+        Err(response) => return response,
+    };
+    let Ok(path) = normalize_path(req.match_info().get("path").unwrap_or_default()) else {
+        return respond_text(HttpResponse::BadRequest(), "Invalid document path.");
+    };
+    match lift_takedown(data.db().shared(), &stele, &path).await {
+        Ok(true) => {
+            data.takedowns.remove(&stele, &path);
+            HttpResponse::NoContent().finish()
+        }
+        Ok(false) => respond_text(
+            HttpResponse::NotFound(),
+            format!("Document {path} is not taken down."),
+        ),
+        Err(err) => {
+            tracing::error!("Error restoring {path}: {err:?}");
+            respond_text(
+                HttpResponse::InternalServerError(),
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Record the takedown in the database.
+async fn record_takedown(db: &DatabaseConnection, created: &Takedown) -> anyhow::Result<()> {
+    let mut tx = DatabaseTransaction {
+        tx: db.pool.begin().await?,
+    };
+    takedown::TxManager::create(&mut tx, created).await?;
+    tx.commit().await
+}
+
+/// Delete the takedown of the document at `path` of the `stele` from the database.
+///
+/// Returns whether the document was taken down.
+async fn lift_takedown(db: &DatabaseConnection, stele: &str, path: &str) -> anyhow::Result<bool> {
+    let mut tx = DatabaseTransaction {
+        tx: db.pool.begin().await?,
+    };
+    let deleted = takedown::TxManager::delete_by_stele_and_path(&mut tx, stele, path).await?;
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+/// The stele of the request `req`, selected by [`AccessDecision::stele`], if the request was
+/// authenticated as an admin of the stele, see [`auth::require_admin`].
+///
+/// # Errors
+/// Responds `403 Forbidden` unless the request was authenticated as an admin of the stele, and
+/// `400 Bad Request` if the stele could not be selected.
+fn admin_stele(req: &HttpRequest, access: &AccessDecision) -> Result<String, HttpResponse> {
+    let admin = auth::require_admin(req).map_err(|err| err.error_response())?;
+    let stele = access.stele().map_err(|err| stele_error(&err))?;
+    if admin.stele != stele {
+        return Err(AuthError::Forbidden.error_response());
+    }
+    Ok(stele)
+}
+
+/// Respond `400 Bad Request` to a request whose stele could not be selected.
+fn stele_error(err: &SteleError) -> HttpResponse {
+    tracing::error!("Error getting stele from request: {err}");
+    respond_text(HttpResponse::BadRequest(), format!("Error: {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::takedown::Takedowns;
+
+    #[test]
+    fn test_find_when_inserted_and_removed_expect_reason_then_none() {
+        let cut = Takedowns::default();
+        let shared = cut.clone();
+        shared.insert(
+            "org/law".to_owned(),
+            "a/b/c.html",
+            "Court order 123".to_owned(),
+        );
+        assert_eq!(
+            cut.find("org/law", "a/b/c.html").as_deref(),
+            Some("Court order 123")
+        );
+        assert_eq!(cut.find("org/law", "a/b"), None);
+        assert_eq!(cut.find("org/other", "a/b/c.html"), None);
+        shared.remove("org/law", "a/b/c.html");
+        assert_eq!(cut.find("org/law", "a/b/c.html"), None);
+    }
+
+    #[test]
+    fn test_find_when_served_at_other_path_of_document_expect_reason() {
+        let cut = Takedowns::default();
+        cut.insert("org/law".to_owned(), "a/b/c", "Court order 123".to_owned());
+        for path in [
+            "a/b/c",
+            "a/b/c.html",
+            "a/b/c/index.html",
+            "a/b/c.xml",
+            "a/b/c/index.pdf",
+        ] {
+            assert_eq!(
+                cut.find("org/law", path).as_deref(),
+                Some("Court order 123"),
+                "{path}"
+            );
+        }
+        for path in ["a/b", "a/b/c/d.html", "a/b/c.1"] {
+            assert_eq!(cut.find("org/law", path), None, "{path}");
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Handler for the effective periods of a document.
+//!
+//! A document's text changes on codified dates, but a codified text may only
+//! come into force on a later effective date. The timeline lists the periods
+//! during which each codified text of the document was in force.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        is_row_not_found,
+        models::{
+            document_change::{self, StatusEvent},
+            document_element,
+            publication::{self, Publication},
+            status::Status,
+        },
+        DatabaseConnection,
+    },
+    server::errors::HTTPError,
+};
+
+use super::policy::AccessDecision;
+use super::response::ApiVersion;
+use super::state::{App as AppState, Global as _};
+use super::versions::clean_url_path;
+
+/// Query string of the timeline endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    /// Name of the publication to read the timeline from. Defaults to the latest publication.
+    pub publication: Option<String>,
+}
+
+/// Response for the timeline endpoint.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timeline {
+    /// Url path of the document.
+    pub path: String,
+    /// Name of the publication the timeline was read from.
+    pub publication: String,
+    /// Effective periods of the document, oldest first.
+    pub periods: Vec<EffectivePeriod>,
+}
+
+/// A period during which one codified text of a document was in force.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectivePeriod {
+    /// Codified date of the text, i.e. the `/_date/{date}` under which the text is served.
+    pub codified_date: String,
+    /// Date on which the text came into force (inclusive).
+    pub start: String,
+    /// Date on which the text stopped being in force (exclusive).
+    /// `None` if the text is still in force.
+    pub end: Option<String>,
+}
+
+impl EffectivePeriod {
+    /// Whether the text was in force on `date` (%Y-%m-%d).
+    #[must_use]
+    pub fn contains(&self, date: &str) -> bool {
+        self.start.as_str() <= date && self.end.as_deref().is_none_or(|end| date < end)
+    }
+}
+
+/// Return the effective periods of the document at `path`.
+///
+/// The stele is selected by [`AccessDecision::stele`]. Responses are enveloped as negotiated by
+/// [`ApiVersion`].
+#[tracing::instrument(skip(data, access))]
+pub async fn timeline(
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+    path: web::Path<String>,
+    params: web::Query<Params>,
+) -> impl Responder {
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    let url = clean_url_path(&path);
+    match find_effective_periods(
+        data.db().for_stele(&stele),
+        &stele,
+        &url,
+        params.publication.as_deref(),
+    )
+    .await
+    {
+        Ok(Some((active_publication, periods))) => api_version.respond(
+            HttpResponse::Ok(),
+            &Timeline {
+                path: url,
+                publication: active_publication.name,
+                periods,
+            },
+        ),
+        Ok(None) => api_version.respond_error(
+            HttpResponse::NotFound(),
+            "document_not_found",
+            format!("No document found at {url}."),
+        ),
+        Err(err) => {
+            tracing::error!("Error finding timeline of {url}: {err:?}");
+            api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                HTTPError::InternalServerError.to_string(),
+            )
+        }
+    }
+}
+
+/// Find the effective periods of the document at `url` in a publication of the stele.
+///
+/// Returns `None` if the publication or document do not exist.
+///
+/// # Errors
+/// Errors if can't establish a connection to the database.
+pub async fn find_effective_periods(
+    db: &DatabaseConnection,
+    stele: &str,
+    url: &str,
+    publication_name: Option<&str>,
+) -> anyhow::Result<Option<(Publication, Vec<EffectivePeriod>)>> {
+    let publications = publication::Manager::find_all_non_revoked_publications(db, stele).await?;
+    let active_publication = publications
+        .into_iter()
+        .find(|pb| publication_name.is_none_or(|name| pb.name == name));
+    let Some(found_publication) = active_publication else {
+        return Ok(None);
+    };
+    let mpath = match document_element::Manager::find_doc_mpath_by_url(db, url, stele).await {
+        Ok(mpath) => mpath,
+        Err(err) if is_row_not_found(&err) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let events = document_change::Manager::find_all_status_events_by_mpath_and_publication(
+        db,
+        &mpath,
+        &found_publication.id,
+    )
+    .await?;
+    Ok(Some((found_publication, effective_periods(&mpath, events))))
+}
+
+/// Derive the effective periods of the `mpath` document element from its status changes.
+///
+/// Every addition or change of the element (or any change of its sub-elements) codifies a new text.
+/// The text is in force from its codified date, or from the first `Element effective` date
+/// after it, until the next text comes into force or the element is removed.
+#[must_use]
+pub fn effective_periods(mpath: &str, mut events: Vec<StatusEvent>) -> Vec<EffectivePeriod> {
+    events.sort_by(|first, second| {
+        (&first.codified_date, Step::of(first, mpath))
+            .cmp(&(&second.codified_date, Step::of(second, mpath)))
+    });
+    let mut segments: Vec<Segment> = vec![];
+    for event in events {
+        let date = event.codified_date.clone();
+        let last = segments.last_mut();
+        match Step::of(&event, mpath) {
+            Some(Step::Codified) => {
+                if last.is_some_and(|segment| segment.codified_date.as_ref() == Some(&date)) {
+                    // Several changes on the same date codify a single text.
+                    continue;
+                }
+                segments.push(Segment {
+                    codified_date: Some(date.clone()),
+                    start: date,
+                    awaiting_effective: true,
+                });
+            }
+            Some(Step::Effective) => {
+                if let Some(segment) = last {
+                    if segment.awaiting_effective && segment.start < date {
+                        segment.start = date;
+                    }
+                    segment.awaiting_effective = false;
+                }
+            }
+            Some(Step::Removed) if last.is_some_and(|segment| segment.codified_date.is_some()) => {
+                segments.push(Segment {
+                    codified_date: None,
+                    start: date,
+                    awaiting_effective: false,
+                });
+            }
+            Some(Step::Removed) | None => {}
+        }
+    }
+    segments
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, segment)| {
+            Some(EffectivePeriod {
+                codified_date: segment.codified_date.clone()?,
+                start: segment.start.clone(),
+                end: segments.get(idx + 1).map(|next| next.start.clone()),
+            })
+        })
+        .collect()
+}
+
+/// What a status change means for the text of a document element.
+///
+/// Ordered by how status changes on the same date are applied.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    /// A new text was codified.
+    Codified,
+    /// The latest text came into force.
+    Effective,
+    /// The element was removed.
+    Removed,
+}
+
+impl Step {
+    /// Classify a status change of `mpath` or one of its sub-elements.
+    fn of(event: &StatusEvent, mpath: &str) -> Option<Self> {
+        match Status::from_int(event.status).ok()? {
+            Status::ElementEffective => Some(Self::Effective),
+            Status::ElementRemoved if event.doc_mpath == mpath => Some(Self::Removed),
+            Status::ElementAdded | Status::ElementChanged | Status::ElementRemoved => {
+                Some(Self::Codified)
+            }
+        }
+    }
+}
+
+/// A text of a document element, or its removal, in date order.
+struct Segment {
+    /// Codified date of the text. `None` for the removal of the element.
+    codified_date: Option<String>,
+    /// Date on which the text came into force, or on which the element was removed.
+    start: String,
+    /// Whether the text may still come into force on a later effective date.
+    awaiting_effective: bool,
+}
+
+#[cfg(test)]
+#[expect(clippy::indexing_slicing, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::api::timeline::*;
+
+    fn event(doc_mpath: &str, codified_date: &str, status: &Status) -> StatusEvent {
+        StatusEvent {
+            doc_mpath: doc_mpath.to_owned(),
+            codified_date: codified_date.to_owned(),
+            status: status.to_int(),
+        }
+    }
+
+    fn period(codified_date: &str, start: &str, end: Option<&str>) -> EffectivePeriod {
+        EffectivePeriod {
+            codified_date: codified_date.to_owned(),
+            start: start.to_owned(),
+            end: end.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_effective_periods_when_changes_without_effective_dates_expect_codified_periods() {
+        let cut = effective_periods;
+        let actual = cut(
+            "doc|sec",
+            vec![
+                event("doc|sec", "2023-01-01", &Status::ElementAdded),
+                event("doc|sec|a", "2023-03-01", &Status::ElementChanged),
+                event("doc|sec", "2023-03-01", &Status::ElementChanged),
+                event("doc|sec", "2023-06-01", &Status::ElementRemoved),
+            ],
+        );
+        let expected = vec![
+            period("2023-01-01", "2023-01-01", Some("2023-03-01")),
+            period("2023-03-01", "2023-03-01", Some("2023-06-01")),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_effective_periods_when_effective_after_codified_expect_previous_text_in_force_until_effective(
+    ) {
+        let cut = effective_periods;
+        let actual = cut(
+            "doc|sec",
+            vec![
+                event("doc|sec", "2023-01-01", &Status::ElementAdded),
+                event("doc|sec", "2023-03-01", &Status::ElementChanged),
+                event("doc|", "2023-04-15", &Status::ElementEffective),
+            ],
+        );
+        let expected = vec![
+            period("2023-01-01", "2023-01-01", Some("2023-04-15")),
+            period("2023-03-01", "2023-04-15", None),
+        ];
+        assert_eq!(actual, expected);
+        assert!(actual[0].contains("2023-04-14"));
+        assert!(!actual[0].contains("2023-04-15"));
+    }
+}
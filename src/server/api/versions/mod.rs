@@ -3,24 +3,39 @@
     clippy::future_not_send,
     reason = "We don't worry about git2-rs not implementing `Send` trait"
 )]
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
-use chrono::NaiveDate;
+use actix_web::{
+    http::header::{
+        CacheControl, CacheDirective, ETag, EntityTag, Header as _, HttpDate, IfModifiedSince,
+        IfNoneMatch, LastModified, VARY,
+    },
+    web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
+};
+use derive_more::{Display, Error};
 use std::convert::Into;
+use std::time::SystemTime;
 
 use crate::{
     db::{
         models::{
-            document_change, document_element, library, library_change,
+            data_repo_commits,
             publication::{self, Publication},
+            version,
         },
-        DatabaseConnection,
+        DatabaseConnection, DatabaseTransaction, Tx as _,
     },
-    stelae::archive::Archive,
-    utils::paths::clean_path,
+    server::{
+        base_path::BasePath,
+        cache::{Cache, Mpath},
+        timing::{Phase, Timings},
+    },
+    stelae::{archive::Archive, stele::Stele},
+    utils::{md5, paths::clean_path},
 };
 
-use self::response::messages;
+use self::response::{messages, VersionDate, VersionList};
 
+use super::policy::AccessDecision;
+use super::response::ApiVersion;
 use super::state::{App as AppState, Global as _};
 
 /// Name of the current publication.
@@ -29,6 +44,14 @@ pub const CURRENT_PUBLICATION_NAME: &str = "Current";
 pub const CURRENT_VERSION_NAME: &str = "Current";
 /// Date of the current version.
 pub const CURRENT_VERSION_DATE: &str = "current";
+/// Header selecting the stele of a request by its qualified name.
+pub const STELE_HEADER: &str = "X-Stelae";
+/// Header selecting the dependent stele of a request by a scope it serves.
+pub const STELE_SCOPE_HEADER: &str = "X-Stelae-Scope";
+/// Header carrying the token that grants a request access to preview publications.
+pub const PREVIEW_HEADER: &str = "X-Stelae-Preview";
+/// Seconds that clients and proxies may reuse a versions response without revalidating it.
+pub const VERSIONS_MAX_AGE: u32 = 60;
 
 /// Module that maps the HTTP web request body to structs.
 pub mod request;
@@ -36,29 +59,94 @@ pub mod request;
 /// Module that maps the HTTP web response to structs.
 pub mod response;
 
+/// Why the stele of a request cannot be selected, see [`get_stele_from_request`].
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq)]
+pub enum SteleError {
+    /// Both the `X-Stelae` and the `X-Stelae-Scope` headers are set.
+    #[display(fmt = "Only one of the {STELE_HEADER} and {STELE_SCOPE_HEADER} headers may be set")]
+    ConflictingHeaders,
+    /// The value of the header `name` is not valid.
+    #[display(fmt = "Invalid {name} header value")]
+    InvalidHeader {
+        /// Name of the header.
+        name: &'static str,
+    },
+    /// The archive has no root stele.
+    #[display(fmt = "No root Stele found in archive")]
+    NoRoot,
+    /// No single stele serves the scope of the `X-Stelae-Scope` header.
+    #[display(fmt = "{reason}")]
+    UnservedScope {
+        /// Why no single stele serves the scope.
+        reason: String,
+    },
+}
+
 /// Handler for the versions endpoint.
-#[tracing::instrument(skip(req, data))]
+///
+/// The publications and versions are read in a single transaction, so a concurrent
+/// `stelae update` cannot mix the results of two publications.
+///
+/// Responses only change when a publication is ingested, so they are tagged with the latest
+/// publication, its latest authentication commit, the stele and the negotiated api version, and
+/// last modified at the time of that commit. A request `If-None-Match` the tag, or else
+/// `If-Modified-Since` that time, is answered `304 Not Modified` without querying the versions.
+#[tracing::instrument(skip(req, data, access))]
 pub async fn versions(
     req: HttpRequest,
     data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
     params: web::Path<request::Version>,
 ) -> impl Responder {
-    let stele = match get_stele_from_request(&req, data.archive()) {
+    let stele = match access.stele() {
         Ok(stele) => stele,
         Err(err) => {
             tracing::error!("Error getting stele from request: {err}");
-            return HttpResponse::BadRequest().body(format!("Error: {err}"));
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    let locale = data.locales.for_stele(&stele);
+    let mut tx = match read_transaction(data.db().for_stele(&stele)).await {
+        Ok(tx) => tx,
+        Err(err) => {
+            tracing::error!("Error beginning a read transaction: {err:?}");
+            return api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                "Database error.",
+            );
         }
     };
-    let db = data.db();
-    let mut publications = publication::Manager::find_all_non_revoked_publications(db, &stele)
-        .await
-        .unwrap_or_default();
+    let previews = access.previews();
+    let timings = Timings::of(&req);
+    let found_publications = timings
+        .measure_async(
+            Phase::Db,
+            stele_publications(&mut tx, data.cache(), &stele, previews),
+        )
+        .await;
+    let mut publications = match found_publications {
+        Ok(publications) => publications,
+        Err(err) => return database_error(tx, api_version, &err).await,
+    };
 
     let Some(current_publication) = publications.first() else {
         tracing::warn!("No publications found for stele: {stele}");
-        return HttpResponse::NotFound().body("No publications found.");
+        end_read_transaction(tx).await;
+        return api_version.respond_error(
+            HttpResponse::NotFound(),
+            "publication_not_found",
+            "No publications found.",
+        );
     };
+    let validators = Validators::find(&mut tx, current_publication, &stele, api_version).await;
+    if is_not_modified(&req, &validators) {
+        end_read_transaction(tx).await;
+        let not_modified = cacheable(HttpResponse::NotModified(), &validators, previews);
+        return api_version.negotiated(not_modified).finish();
+    }
 
     let mut active_publication_name = params
         .publication
@@ -71,62 +159,83 @@ pub async fn versions(
 
     let url = clean_url_path(&params.path.clone().unwrap_or_default());
 
-    let mut versions = if let Some(publication) = active_publication {
-        publication_versions(db, publication, url.clone()).await
+    let found_versions = if let Some(publication) = active_publication {
+        let found = find_versions(&mut tx, data.cache(), publication, url.clone());
+        timings.measure_async(Phase::Db, found).await
     } else {
-        vec![]
+        Ok(VersionList::default())
+    };
+    let mut versions = match found_versions {
+        Ok(versions) => versions,
+        Err(err) => return database_error(tx, api_version, &err).await,
     };
+    end_read_transaction(tx).await;
 
+    let (version_date, active_compare_to) = match params.dates() {
+        Ok(dates) => dates,
+        Err(invalid) => {
+            let base_path = BasePath::of(&req);
+            let mut body = response::InvalidDate::build(&url, &invalid, &versions);
+            body.nearest = body
+                .nearest
+                .into_iter()
+                .map(|link| link.mounted(&base_path))
+                .collect();
+            let message = body.message.clone();
+            return api_version.respond_rejected(
+                HttpResponse::BadRequest(),
+                "invalid_date",
+                message,
+                &body,
+            );
+        }
+    };
     // latest date in active publication
-    let current_date = versions
-        .first()
-        .map_or(String::new(), |ver| ver.date.clone());
+    let current_date = versions.dates().next();
     // active version is the version the user is looking at right now
-    let mut active_version =
-        NaiveDate::parse_from_str(params.date.as_deref().unwrap_or_default(), "%Y-%m-%d")
-            .map_or(current_date.clone(), |date| date.clone().to_string());
-    let active_compare_to = params.compare_date.clone().map(|date| {
-        NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_or_else(
-            |_| current_date.clone(),
-            |active_date| active_date.to_string(),
-        )
-    });
-
-    if active_version == current_date {
-        CURRENT_VERSION_DATE.clone_into(&mut active_version);
-    }
+    let active_version = match version_date {
+        Some(found) if Some(found) != current_date => VersionDate::Codified(found),
+        _ => VersionDate::Current,
+    };
 
-    let messages = messages::historical(
+    let mut messages = messages::historical(
         &versions,
         current_publication.name.as_str(),
         &active_publication_name,
-        &params.date,
-        &active_compare_to,
+        version_date,
+        active_compare_to,
+        &url,
+        locale,
     );
+    messages.links = messages.links.mounted(&BasePath::of(&req));
 
     if active_publication_name == current_publication.name.clone() {
         CURRENT_PUBLICATION_NAME.clone_into(&mut active_publication_name);
     }
 
-    response::Version::insert_if_not_present(&mut versions, params.date.clone());
-    response::Version::insert_if_not_present(&mut versions, active_compare_to.clone());
+    for inserted_date in [version_date, active_compare_to].into_iter().flatten() {
+        versions.insert_date(inserted_date);
+    }
+    let mut listed_versions = versions.into_vec();
 
-    let versions_size = versions.len();
-    for (idx, version) in versions.iter_mut().enumerate() {
-        version.display = format_date(&version.date.clone());
+    let versions_size = listed_versions.len();
+    for (idx, version) in listed_versions.iter_mut().enumerate() {
+        if let VersionDate::Codified(codified_date) = version.date {
+            version.display = locale.format_date(codified_date);
+        }
         version.index = versions_size - idx;
     }
-    if let Some(ver) = versions.first_mut() {
+    if let Some(ver) = listed_versions.first_mut() {
         ver.display.push_str(" (last modified)");
     };
 
     let current_version = response::Version::new(
-        CURRENT_VERSION_DATE.to_owned(),
+        VersionDate::Current,
         CURRENT_VERSION_NAME.to_owned(),
-        versions.first().map_or(0, |ver| ver.index),
+        listed_versions.first().map_or(0, |ver| ver.index),
     );
 
-    versions.insert(versions_size - current_version.index, current_version);
+    listed_versions.insert(versions_size - current_version.index, current_version);
 
     let current_publication_name = current_publication.name.clone();
     // duplicate current publication with current label
@@ -135,90 +244,463 @@ pub async fn versions(
         Publication::new(
             current_publication.id.clone(),
             CURRENT_PUBLICATION_NAME.to_owned(),
-            current_publication.date.clone(),
+            current_publication.date,
             current_publication.stele.clone(),
         ),
     );
 
-    HttpResponse::Ok().json(response::Versions::build(
+    let body = response::Versions::build(
         &active_publication_name,
         active_version,
         active_compare_to,
         &url,
         &publications,
         &current_publication_name,
-        &versions,
+        &listed_versions,
         messages,
-    ))
+        locale,
+    );
+    api_version.respond(cacheable(HttpResponse::Ok(), &validators, previews), &body)
 }
 
-/// Get all the versions of a publication.
-async fn publication_versions(
-    db: &DatabaseConnection,
-    publication: &Publication,
-    url: String,
-) -> Vec<response::Version> {
-    tracing::debug!("Fetching publication versions for '{url}'");
-    let mut versions = vec![];
-    let doc_mpath =
-        document_element::Manager::find_doc_mpath_by_url(db, &url, &publication.stele).await;
-    if let Ok(mpath) = doc_mpath {
-        let doc_versions =
-            document_change::Manager::find_all_document_versions_by_mpath_and_publication(
-                db,
-                &mpath,
-                &publication.id,
-            )
+/// Handler for the adjacent versions endpoint, `/_api/versions/{path}/adjacent?date=`.
+///
+/// Returns the codified dates immediately before and after `date`, with their `/_date` urls,
+/// for previous and next version navigation. Like [`versions`], reads in a single transaction,
+/// and is tagged like it. A missing or malformed `date` is rejected with the `invalid_query` error.
+#[tracing::instrument(skip(req, data, access))]
+pub async fn adjacent(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    access: AccessDecision,
+    api_version: ApiVersion,
+) -> impl Responder {
+    let params = match web::Query::<request::Adjacent>::from_query(req.query_string()) {
+        Ok(params) => params,
+        Err(err) => {
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_query", message);
+        }
+    };
+    let stele = match access.stele() {
+        Ok(stele) => stele,
+        Err(err) => {
+            tracing::error!("Error getting stele from request: {err}");
+            let message = format!("Error: {err}");
+            return api_version.respond_error(HttpResponse::BadRequest(), "invalid_stele", message);
+        }
+    };
+    let mut tx = match read_transaction(data.db().for_stele(&stele)).await {
+        Ok(tx) => tx,
+        Err(err) => {
+            tracing::error!("Error beginning a read transaction: {err:?}");
+            return api_version.respond_error(
+                HttpResponse::InternalServerError(),
+                "database_error",
+                "Database error.",
+            );
+        }
+    };
+    let previews = access.previews();
+    let publications = match stele_publications(&mut tx, data.cache(), &stele, previews).await {
+        Ok(publications) => publications,
+        Err(err) => return database_error(tx, api_version, &err).await,
+    };
+    let active_publication = params.publication.as_deref().map_or_else(
+        || publications.first(),
+        |name| publications.iter().find(|pb| pb.name == name),
+    );
+    let Some(publication) = active_publication else {
+        end_read_transaction(tx).await;
+        return api_version.respond_error(
+            HttpResponse::NotFound(),
+            "publication_not_found",
+            "No publication found.",
+        );
+    };
+    let validators = match publications.first() {
+        Some(latest) => Validators::find(&mut tx, latest, &stele, api_version).await,
+        None => Validators::default(),
+    };
+    if is_not_modified(&req, &validators) {
+        end_read_transaction(tx).await;
+        let not_modified = cacheable(HttpResponse::NotModified(), &validators, previews);
+        return api_version.negotiated(not_modified).finish();
+    }
+    let url = clean_url_path(req.match_info().get("path").unwrap_or_default());
+    let versions = match find_versions(&mut tx, data.cache(), publication, url.clone()).await {
+        Ok(versions) => versions,
+        Err(err) => return database_error(tx, api_version, &err).await,
+    };
+    end_read_transaction(tx).await;
+    let base_path = BasePath::of(&req);
+    let mut body =
+        response::Adjacent::build(&url, params.date, publication.name.clone(), &versions);
+    body.previous = body.previous.map(|link| link.mounted(&base_path));
+    body.next = body.next.map(|link| link.mounted(&base_path));
+    api_version.respond(cacheable(HttpResponse::Ok(), &validators, previews), &body)
+}
+
+/// Validators of the versions responses of a stele, which only change when a publication is
+/// ingested.
+#[derive(Debug, Default)]
+struct Validators {
+    /// Tag of the responses, see [`Validators::find`].
+    etag: Option<EntityTag>,
+    /// Time of the latest authentication commit of the latest publication.
+    last_modified: Option<HttpDate>,
+}
+
+impl Validators {
+    /// Validators of the versions responses of the `stele` in the negotiated `api_version`, from
+    /// the `publication` and its latest authentication commit, which the responses last changed
+    /// at.
+    ///
+    /// Keyed by the publication rather than the stele, so that responses built from publications
+    /// cached before an update are not tagged like those built after it, and by the api version,
+    /// so that the tag of a response in one version does not validate the other.
+    async fn find(
+        tx: &mut DatabaseTransaction,
+        publication: &Publication,
+        stele: &str,
+        api_version: ApiVersion,
+    ) -> Self {
+        let found = data_repo_commits::TxManager::find_latest_by_publication(tx, &publication.id)
             .await
-            .unwrap_or_default();
-        versions = doc_versions.into_iter().map(Into::into).collect();
+            .unwrap_or_else(|err| {
+                tracing::warn!(
+                    "Unable to find the latest commit of '{}': {err:?}",
+                    publication.name
+                );
+                None
+            });
+        let Some(commit) = found else {
+            return Self::default();
+        };
+        let tagged = format!(
+            "{}:{}:{stele}:{}",
+            publication.id,
+            commit.auth_commit_hash,
+            api_version.as_str()
+        );
+        Self {
+            etag: Some(EntityTag::new_strong(md5::compute(tagged))),
+            last_modified: commit
+                .auth_commit_time()
+                .map(|time| HttpDate::from(SystemTime::from(time))),
+        }
+    }
+}
+
+/// Whether the response of `req` is unchanged since its `If-None-Match` header, or, without one,
+/// since its `If-Modified-Since` header.
+fn is_not_modified(req: &HttpRequest, validators: &Validators) -> bool {
+    if req.headers().contains_key(IfNoneMatch::name()) {
+        let Some(current) = validators.etag.as_ref() else {
+            return false;
+        };
+        return match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(current)),
+            Err(_) => false,
+        };
+    }
+    let (Some(last_modified), Ok(IfModifiedSince(since))) =
+        (validators.last_modified, IfModifiedSince::parse(req))
+    else {
+        return false;
+    };
+    SystemTime::from(last_modified) <= SystemTime::from(since)
+}
+
+/// Add the `ETag`, `Last-Modified`, `Vary` and `Cache-Control` headers of versions responses to
+/// `response`.
+///
+/// Responses vary by the stele selected by the request headers, see [`AccessDecision::stele`],
+/// and by the `Accept-Version` added by [`ApiVersion::negotiated`]. Responses that include
+/// `previews` are never stored by shared caches.
+fn cacheable(
+    mut response: HttpResponseBuilder,
+    validators: &Validators,
+    previews: bool,
+) -> HttpResponseBuilder {
+    if previews {
+        response.insert_header(CacheControl(vec![
+            CacheDirective::Private,
+            CacheDirective::NoStore,
+        ]));
     } else {
-        let lib_mpath = library::Manager::find_lib_mpath_by_url(db, &url, &publication.stele).await;
-        if let Ok(mpath) = lib_mpath {
-            let coll_versions =
-                library_change::Manager::find_all_collection_versions_by_mpath_and_publication(
-                    db,
-                    &mpath,
-                    &publication.id,
-                )
-                .await
-                .unwrap_or_default();
-            versions = coll_versions.into_iter().map(Into::into).collect();
+        response.insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(VERSIONS_MAX_AGE),
+        ]));
+    }
+    response.append_header((VARY, STELE_HEADER));
+    response.append_header((VARY, STELE_SCOPE_HEADER));
+    if let Some(tag) = validators.etag.as_ref() {
+        response.insert_header(ETag(tag.clone()));
+    }
+    if let Some(time) = validators.last_modified {
+        response.insert_header(LastModified(time));
+    }
+    response
+}
+
+/// Begin a transaction on `db` that all reads of a request go through.
+///
+/// In `SQLite`, the reads of a transaction see the database as of its first read, so the changes
+/// an update commits in the meantime are not mixed into the results.
+///
+/// # Errors
+/// Errors if the transaction cannot be started.
+pub async fn read_transaction(db: &DatabaseConnection) -> anyhow::Result<DatabaseTransaction> {
+    DatabaseTransaction::begin(db.pool.clone()).await
+}
+
+/// End the read transaction `tx`, which has nothing to commit.
+async fn end_read_transaction(tx: DatabaseTransaction) {
+    if let Err(err) = tx.rollback().await {
+        tracing::warn!("Error ending a read transaction: {err:?}");
+    }
+}
+
+/// End the read transaction `tx` of a request the database failed with `err`, and respond
+/// `500 Internal Server Error`.
+async fn database_error(
+    tx: DatabaseTransaction,
+    api_version: ApiVersion,
+    err: &anyhow::Error,
+) -> HttpResponse {
+    tracing::error!("Database error: {err:?}");
+    end_read_transaction(tx).await;
+    api_version.respond_error(
+        HttpResponse::InternalServerError(),
+        "database_error",
+        "Database error.",
+    )
+}
+
+/// Get the non-revoked publications of the `stele`, newest first, from the `cache` if warmed.
+///
+/// Preview and approved publications are included only with `previews`, which are never cached.
+/// Publications awaiting approval are never included.
+///
+/// # Errors
+/// Errors if the publications cannot be read from the database.
+pub async fn stele_publications(
+    tx: &mut DatabaseTransaction,
+    cache: &Cache,
+    stele: &str,
+    previews: bool,
+) -> anyhow::Result<Vec<Publication>> {
+    if previews {
+        let publications =
+            publication::TxManager::find_all_non_revoked_with_previews_by_stele(tx, stele).await?;
+        return Ok(publications
+            .into_iter()
+            .filter(|pb| pb.state != publication::INGESTED)
+            .collect());
+    }
+    if let Some(publications) = cache.publications(stele) {
+        return Ok(publications);
+    }
+    publication::TxManager::find_all_non_revoked_by_stele(tx, stele).await
+}
+
+/// Get all the versions of a publication, from the `cache` if warmed.
+///
+/// # Errors
+/// Errors if the versions cannot be read from the database.
+async fn find_versions(
+    tx: &mut DatabaseTransaction,
+    cache: &Cache,
+    publication: &Publication,
+    url: String,
+) -> anyhow::Result<VersionList> {
+    if let Some(versions) = cache.versions(&publication.id, &url) {
+        return Ok(versions);
+    }
+    publication_versions(tx, cache, publication, url).await
+}
+
+/// Resolve the versions of the document or collection at `url` in the `publication` into the `cache`.
+pub async fn warm(db: &DatabaseConnection, cache: &Cache, publication: &Publication, url: String) {
+    let mut tx = match read_transaction(db).await {
+        Ok(tx) => tx,
+        Err(err) => {
+            tracing::warn!("Unable to warm the versions of '{url}': {err:?}");
+            return;
         }
+    };
+    let found = publication_versions(&mut tx, cache, publication, url.clone()).await;
+    end_read_transaction(tx).await;
+    match found {
+        Ok(versions) => cache.insert_versions(publication.id.clone(), url, versions),
+        Err(err) => tracing::warn!("Unable to warm the versions of '{url}': {err:?}"),
     }
+}
+
+/// Get all the versions of a publication, looking the document or collection up in the `cache`.
+///
+/// # Errors
+/// Errors if the versions cannot be read from the database.
+async fn publication_versions(
+    tx: &mut DatabaseTransaction,
+    cache: &Cache,
+    publication: &Publication,
+    url: String,
+) -> anyhow::Result<VersionList> {
+    tracing::debug!("Fetching publication versions for '{url}'");
+    let Some(mpath) = find_mpath(tx, cache, publication, &url).await? else {
+        return Ok(VersionList::default());
+    };
+    let versions: VersionList =
+        version::TxManager::find_all_by_mpath_and_publication(tx, mpath, &publication.id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
     tracing::debug!("Found {} versions", versions.len());
-    versions
+    Ok(versions)
+}
+
+/// Get the materialized path of the document or collection at `url` in the `publication`, from
+/// the `cache` if resolved before.
+///
+/// A materialized path not found is remembered in the `cache`, so it is not queried again for a
+/// while.
+///
+/// # Errors
+/// Errors if the materialized path cannot be read from the database.
+async fn find_mpath(
+    tx: &mut DatabaseTransaction,
+    cache: &Cache,
+    publication: &Publication,
+    url: &str,
+) -> anyhow::Result<Option<Mpath>> {
+    if cache.is_missing_mpath(&publication.stele, &publication.id, url) {
+        return Ok(None);
+    }
+    if let Some(mpath) = cache.mpath(&publication.stele, &publication.id, url) {
+        return Ok(Some(mpath));
+    }
+    let found = version::TxManager::find_mpath_by_url(tx, url, &publication.stele).await?;
+    let Some(mpath) = found else {
+        cache.insert_missing_mpath(
+            publication.stele.clone(),
+            publication.id.clone(),
+            url.to_owned(),
+        );
+        return Ok(None);
+    };
+    cache.insert_mpath(
+        publication.stele.clone(),
+        publication.id.clone(),
+        url.to_owned(),
+        mpath.clone(),
+    );
+    Ok(Some(mpath))
 }
 
 /// Extracts the stele from the request.
+///
 /// If the `X-Stelae` header is present, it will return the value of the header.
+/// If the `X-Stelae-Scope` header is present, it will return the dependent stele serving the scope,
+/// e.g. `sub/scope/1`.
 /// Otherwise, it will return the root stele.
-fn get_stele_from_request(req: &HttpRequest, archive: &Archive) -> anyhow::Result<String> {
+///
+/// # Errors
+/// Errors if the root stele cannot be found, if a header value is not valid, if both headers are
+/// present, or if no single stele serves the scope.
+pub fn get_stele_from_request(req: &HttpRequest, archive: &Archive) -> Result<String, SteleError> {
     let req_headers = req.headers();
-    let stele = archive.get_root()?.get_qualified_name();
-
-    req_headers.get("X-Stelae").map_or_else(
-        || Ok(stele),
-        |value| {
-            value.to_str().map_or_else(
-                |_| anyhow::bail!("Invalid X-Stelae header value"),
-                |str| Ok(str.to_owned()),
-            )
-        },
-    )
-}
+    let header_value = |name: &'static str| {
+        req_headers
+            .get(name)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_err| SteleError::InvalidHeader { name })
+            })
+            .transpose()
+    };
 
-/// Format a date from %Y-%m-%d to %B %d, %Y.
-fn format_date(date: &str) -> String {
-    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_or(date.to_owned(), |found_date| {
-        found_date.format("%B %d, %Y").to_string()
-    })
+    match (
+        header_value(STELE_HEADER)?,
+        header_value(STELE_SCOPE_HEADER)?,
+    ) {
+        (Some(_), Some(_)) => Err(SteleError::ConflictingHeaders),
+        (Some(qualified_name), None) => Ok(archive.resolve_alias(qualified_name).to_owned()),
+        (None, Some(scope)) => {
+            archive
+                .get_stele_by_scope(scope)
+                .map_err(|err| SteleError::UnservedScope {
+                    reason: err.to_string(),
+                })
+        }
+        (None, None) => archive
+            .get_root()
+            .map(Stele::get_qualified_name)
+            .map_err(|_err| SteleError::NoRoot),
+    }
 }
 
 /// Clean the url path by removing the trailing slash.
-fn clean_url_path(path: &str) -> String {
+#[must_use]
+pub fn clean_url_path(path: &str) -> String {
     let mut url = String::from('/');
     let url_parts = clean_path(path);
     url.push_str(&url_parts);
     url
 }
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::db::models::fixture::{self, STELE};
+    use crate::db::models::publication;
+    use crate::db::{DatabaseConnection, DatabaseTransaction, Tx as _};
+    use crate::server::api::versions::{read_transaction, stele_publications};
+    use crate::server::cache::Cache;
+
+    async fn publish(db: &DatabaseConnection, name: &str) {
+        let mut tx = DatabaseTransaction::begin(db.pool.clone()).await.unwrap();
+        fixture::publish(&mut tx, STELE, &[(name, name)]).await;
+        tx.commit().await.unwrap();
+    }
+
+    fn names(publications: &[publication::Publication]) -> Vec<&str> {
+        publications.iter().map(|pb| pb.name.as_str()).collect()
+    }
+
+    #[actix_web::test]
+    async fn test_stele_publications_when_updated_during_read_transaction_expect_snapshot() {
+        let (_dir, db) = fixture::database().await;
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        publish(&db, "2024-01-01").await;
+        let cache = Cache::default();
+
+        let mut tx = read_transaction(&db).await.unwrap();
+        let before = stele_publications(&mut tx, &cache, STELE, false)
+            .await
+            .unwrap();
+        assert_eq!(names(&before), vec!["2024-01-01"]);
+
+        publish(&db, "2024-02-01").await;
+        let during = stele_publications(&mut tx, &cache, STELE, false)
+            .await
+            .unwrap();
+        assert_eq!(names(&during), vec!["2024-01-01"]);
+        tx.rollback().await.unwrap();
+
+        let mut tx = read_transaction(&db).await.unwrap();
+        let after = stele_publications(&mut tx, &cache, STELE, false)
+            .await
+            .unwrap();
+        assert_eq!(names(&after), vec!["2024-02-01", "2024-01-01"]);
+        tx.rollback().await.unwrap();
+    }
+}
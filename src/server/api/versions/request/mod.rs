@@ -1,4 +1,11 @@
+use chrono::NaiveDate;
 use serde::Deserialize;
+
+use crate::utils::date;
+
+/// Format of the `{date}` segments of a request path, e.g. `_date/{date}`, as shown to users.
+pub const DATE_SEGMENT_FORMAT: &str = "YYYY-MM-DD";
+
 /// Request for the versions endpoint.
 #[derive(Deserialize, Debug)]
 pub struct Version {
@@ -11,3 +18,115 @@ pub struct Version {
     /// Path to document/collection.
     pub path: Option<String>,
 }
+
+impl Version {
+    /// Parse the `date` and `compare_date` segments strictly, see [`parse_date`].
+    ///
+    /// # Errors
+    /// Errors with the first segment that is not a zero-padded `%Y-%m-%d` date.
+    pub fn dates(&self) -> Result<(Option<NaiveDate>, Option<NaiveDate>), InvalidDate> {
+        let version_date = self.date.as_deref().map(parse_date).transpose()?;
+        let compare_date = self.compare_date.as_deref().map(parse_date).transpose()?;
+        Ok((version_date, compare_date))
+    }
+}
+
+/// Query string of the adjacent versions endpoint.
+#[derive(Deserialize, Debug)]
+pub struct Adjacent {
+    /// Date the previous and next versions are relative to.
+    pub date: NaiveDate,
+    /// Name of the publication to read the versions from. Defaults to the latest publication.
+    pub publication: Option<String>,
+}
+
+/// A `{date}` segment of a request path that is not a `%Y-%m-%d` date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidDate {
+    /// The segment as requested.
+    pub segment: String,
+    /// Closest valid date to a segment shaped like a date, with the month and day clamped into
+    /// range, e.g. `2025-12-31` for `2025-13-45`.
+    pub closest: Option<NaiveDate>,
+}
+
+/// Parse the `{date}` segment of a request path strictly, as a zero-padded `%Y-%m-%d` date.
+///
+/// # Errors
+/// Errors if the `segment` is not a zero-padded `%Y-%m-%d` date.
+pub fn parse_date(segment: &str) -> Result<NaiveDate, InvalidDate> {
+    date::parse(segment)
+        .ok()
+        .filter(|found| date::format(*found) == segment)
+        .ok_or_else(|| InvalidDate {
+            segment: segment.to_owned(),
+            closest: closest_date(segment),
+        })
+}
+
+/// The valid date closest to a `segment` of a 4 digit year, and a 1 or 2 digit month and day.
+fn closest_date(segment: &str) -> Option<NaiveDate> {
+    let digits = |part: &str, max_len: usize| {
+        (!part.is_empty()
+            && part.len() <= max_len
+            && part.bytes().all(|byte| byte.is_ascii_digit()))
+        .then(|| part.parse::<u32>().ok())
+        .flatten()
+    };
+    let mut parts = segment.split('-');
+    let (Some(year_part), Some(month_part), Some(day_part), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let year = i32::try_from(digits(year_part, 4).filter(|_| year_part.len() == 4)?).ok()?;
+    let month = digits(month_part, 2)?.clamp(1, 12);
+    let day = digits(day_part, 2)?.clamp(1, 31);
+    (1..=day)
+        .rev()
+        .find_map(|found| NaiveDate::from_ymd_opt(year, month, found))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::api::versions::request::parse_date;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_parse_date_when_zero_padded_date_expect_date() {
+        let actual = parse_date("2023-10-22").unwrap();
+        let expected = NaiveDate::from_ymd_opt(2023, 10, 22).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_date_when_out_of_range_or_not_padded_expect_closest_date() {
+        let cases = [
+            ("2025-13-45", NaiveDate::from_ymd_opt(2025, 12, 31)),
+            ("2023-02-30", NaiveDate::from_ymd_opt(2023, 2, 28)),
+            ("2023-00-00", NaiveDate::from_ymd_opt(2023, 1, 1)),
+            ("2023-1-5", NaiveDate::from_ymd_opt(2023, 1, 5)),
+        ];
+        for (segment, closest) in cases {
+            let actual = parse_date(segment).unwrap_err();
+            assert_eq!(actual.segment, segment);
+            assert_eq!(actual.closest, closest, "{segment}");
+        }
+    }
+
+    #[test]
+    fn test_parse_date_when_not_shaped_like_a_date_expect_no_closest_date() {
+        for segment in [
+            "notadate",
+            "",
+            "23-10-22",
+            "2023-10-22-1",
+            "2023-x-22",
+            "+2023-10-22",
+        ] {
+            let actual = parse_date(segment).unwrap_err();
+            assert_eq!(actual.closest, None, "{segment}");
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, Utc};
 use serde::Serialize;
 
-use super::format_date;
-use crate::server::api::versions::response::Version;
+use crate::server::api::versions::response::VersionList;
+use crate::server::base_path::BasePath;
+use crate::utils::date;
+use crate::utils::locale::Locale;
 
 /// Messages for the versions endpoint.
 #[derive(Serialize, Debug, PartialEq, Eq)]
@@ -14,6 +16,34 @@ pub struct Historical {
     pub version: Option<String>,
     /// Message for a comparison between two versions.
     pub comparison: Option<String>,
+    /// Links to comparisons of the selected version.
+    pub links: Links,
+    /// Whether the selected version is outdated by a later version or publication.
+    pub is_superseded: bool,
+    /// Whether the selected version is dated after today.
+    pub is_future_dated: bool,
+}
+
+/// Links to comparisons of the selected version, for frontends to link to as is.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Links {
+    /// Url comparing the selected version with the latest version of the publication,
+    /// e.g. `/_compare/2023-10-22/2023-12-30/a/b/c`.
+    pub current: Option<String>,
+    /// Url comparing the selected version with the version it is compared to.
+    pub compare: Option<String>,
+}
+
+impl Links {
+    /// The links, with their urls under the `base_path` the archive is served under.
+    #[must_use]
+    pub fn mounted(self, base_path: &BasePath) -> Self {
+        Self {
+            current: self.current.map(|url| base_path.url(&url)),
+            compare: self.compare.map(|url| base_path.url(&url)),
+        }
+    }
 }
 
 /// Returns historical messages for the versions endpoint.
@@ -21,46 +51,80 @@ pub struct Historical {
 /// - A message for an outdated publication.
 /// - A message for an outdated version.
 /// - A message for a comparison between two versions.
+///
+/// Links to comparisons of the selected version of the document at `url` are included, along
+/// with whether the version is superseded or future dated.
 #[must_use]
 pub fn historical(
-    versions: &[Version],
+    versions: &VersionList,
     current_publication_name: &str,
     active_publication_name: &str,
-    version_date: &Option<String>,
-    compare_to_date: &Option<String>,
+    version_date: Option<NaiveDate>,
+    compare_to_date: Option<NaiveDate>,
+    url: &str,
+    locale: Locale,
 ) -> Historical {
-    let current_version: &str = versions
-        .first()
-        .map(|lmv| lmv.date.as_str())
-        .unwrap_or_default();
+    let current_version = versions.dates().next();
 
     let publication = publication_message(
         active_publication_name,
         current_publication_name,
         current_version,
+        locale,
     );
-    let version = version_date.as_ref().and_then(|found_version_date| {
+    let version = version_date.and_then(|found_version_date| {
         version_message(
             current_version,
             found_version_date,
             versions,
-            compare_to_date.as_ref(),
+            compare_to_date,
+            locale,
         )
     });
-    let comparison = compare_to_date.as_ref().and_then(|found_compare_to_date| {
-        version_date.as_ref().map(|found_version_date| {
+    let comparison = compare_to_date.and_then(|found_compare_to_date| {
+        version_date.map(|found_version_date| {
             comparison_message(
                 found_compare_to_date,
                 found_version_date,
                 current_version,
                 versions,
+                locale,
             )
         })
     });
+    let is_superseded = active_publication_name != current_publication_name
+        || version_date
+            .zip(current_version)
+            .is_some_and(|(selected, current)| selected < current);
+    let is_future_dated = version_date.is_some_and(|selected| selected > Utc::now().date_naive());
+    let publication_prefix = if active_publication_name == current_publication_name {
+        String::new()
+    } else {
+        format!("/_publication/{active_publication_name}")
+    };
+    let compare_url = |from: NaiveDate, to: NaiveDate| {
+        format!(
+            "{publication_prefix}/_compare/{}/{}{url}",
+            date::format(from),
+            date::format(to)
+        )
+    };
+    let links = Links {
+        current: version_date
+            .zip(current_version)
+            .filter(|&(selected, current)| selected != current)
+            .map(|(selected, current)| compare_url(selected, current)),
+        compare: version_date
+            .zip(compare_to_date)
+            .map(|(selected, compared)| compare_url(selected, compared)),
+    };
     Historical {
         publication,
         version,
         comparison,
+        links,
+        is_superseded,
+        is_future_dated,
     }
 }
 
@@ -68,108 +132,107 @@ pub fn historical(
 fn publication_message(
     active_publication_name: &str,
     current_publication_name: &str,
-    current_version: &str,
+    current_version: Option<NaiveDate>,
+    locale: Locale,
 ) -> Option<String> {
     if active_publication_name == current_publication_name {
         return None;
     }
-    Some(publication_message_template(current_version))
+    Some(publication_message_template(current_version, locale))
 }
 
 /// Formats the response for an outdated publication.
-fn publication_message_template(date: &str) -> String {
+fn publication_message_template(date: Option<NaiveDate>, locale: Locale) -> String {
     format!(
         "You are viewing a historical publication that was last updated on {current_date} and is no longer being updated.",
-        current_date = format_date(date)
+        current_date = format_date(date, locale)
     )
 }
 
 /// Returns a historical message for an outdated version.
 /// Version is outdated if `version_date` is in the past.
 fn version_message(
-    current_version: &str,
-    version_date: &str,
-    versions: &[Version],
-    compare_to_date: Option<&String>,
+    current_version: Option<NaiveDate>,
+    version_date: NaiveDate,
+    versions: &VersionList,
+    compare_to_date: Option<NaiveDate>,
+    locale: Locale,
 ) -> Option<String> {
-    let is_current_version = {
-        let current_date =
-            NaiveDate::parse_from_str(current_version, "%Y-%m-%d").unwrap_or_default();
-        let Ok(parsed_version_date) = NaiveDate::parse_from_str(version_date, "%Y-%m-%d") else {
-            return None;
-        };
-        current_date <= parsed_version_date
-    };
+    let is_current_version = current_version.unwrap_or_default() <= version_date;
     if compare_to_date.is_some() || is_current_version {
         return None;
     }
-    let version_date_idx = versions
-        .iter()
-        .position(|ver| ver.date.as_str() == version_date);
+    let dates: Vec<NaiveDate> = versions.dates().collect();
+    let version_date_idx = dates.iter().position(|date| *date == version_date);
     let (start_date, end_date) = version_date_idx.map_or_else(
         || {
-            let end_date = versions
-                .iter()
-                .filter(|ver| ver.date.as_str() > version_date)
-                .map(|ver| ver.date.as_str())
-                .min();
-            let found_idx = versions
+            let end_date = dates.iter().filter(|date| **date > version_date).min();
+            let found_idx = dates
                 .iter()
-                .position(|ver| ver.date.as_str() == end_date.unwrap_or_default())
+                .position(|date| Some(date) == end_date)
                 .unwrap_or_default();
-            let start_date = versions
-                .get(found_idx + 1)
-                .map_or_else(|| versions.last(), Some)
-                .map(|ver| ver.date.as_str());
-            (start_date.unwrap_or_default(), end_date.unwrap_or_default())
+            let start_date = dates.get(found_idx + 1).or_else(|| dates.last());
+            (start_date.copied(), end_date.copied())
         },
         |idx| {
-            let start_date = version_date;
-            let end_date = versions
-                .get(idx - 1)
-                .map_or_else(|| versions.first(), Some)
-                .map(|ver| ver.date.as_str())
-                .unwrap_or_default();
-            (start_date, end_date)
+            let end_date = dates.get(idx - 1).or_else(|| dates.first());
+            (Some(version_date), end_date.copied())
         },
     );
-    Some(version_message_template(version_date, start_date, end_date))
+    Some(version_message_template(
+        version_date,
+        start_date,
+        end_date,
+        locale,
+    ))
 }
 
 /// Formats the response for an outdated version.
-fn version_message_template(version_date: &str, start_date: &str, end_date: &str) -> String {
+fn version_message_template(
+    version_date: NaiveDate,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+    locale: Locale,
+) -> String {
     format!(
         "You are viewing this document as it appeared on {version_date}. This version was valid between {start_date} and {end_date}.",
-        version_date = format_date(version_date),
-        start_date = format_date(start_date),
-        end_date = format_date(end_date)
+        version_date = locale.format_date(version_date),
+        start_date = format_date(start_date, locale),
+        end_date = format_date(end_date, locale)
     )
 }
 
 /// Returns a historical message for a comparison between two versions.
 fn comparison_message(
-    compare_to_date: &str,
-    version_date: &str,
-    current_date: &str,
-    versions: &[Version],
+    compare_to_date: NaiveDate,
+    version_date: NaiveDate,
+    current_date: Option<NaiveDate>,
+    versions: &VersionList,
+    locale: Locale,
 ) -> String {
     let (compare_start_date, compare_end_date) = if version_date > compare_to_date {
         (compare_to_date, version_date)
     } else {
         (version_date, compare_to_date)
     };
-    let start_idx = Version::find_index_or_closest(versions, compare_start_date);
-    let end_idx = Version::find_index_or_closest(versions, compare_end_date);
+    let start_idx = versions.find_index_or_closest(compare_start_date);
+    let end_idx = versions.find_index_or_closest(compare_end_date);
     let num_of_changes = start_idx - end_idx;
-    let start_date = format_date(compare_start_date);
-    let end_date = if compare_end_date == current_date {
+    let start_date = locale.format_date(compare_start_date);
+    let end_date = if Some(compare_end_date) == current_date {
         None
     } else {
-        Some(format_date(compare_end_date))
+        Some(locale.format_date(compare_end_date))
     };
     messages_between_template(num_of_changes, &start_date, end_date)
 }
 
+/// Formats the `date` as a long display date in `locale`, or as empty if there is no date.
+fn format_date(date: Option<NaiveDate>, locale: Locale) -> String {
+    date.map(|found| locale.format_date(found))
+        .unwrap_or_default()
+}
+
 /// Formats and returns a message for the number of changes between two dates.
 fn messages_between_template(
     num_of_changes: usize,
@@ -193,6 +256,13 @@ fn messages_between_template(
 }
 
 #[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::get_unwrap,
+    clippy::str_to_string,
+    clippy::default_numeric_fallback,
+    reason = "Tests panic on unexpected values"
+)]
 mod tests {
     use serde_json::json;
 
@@ -202,6 +272,11 @@ mod tests {
     use std::collections::BTreeMap;
 
     use super::super::Publication;
+    use crate::utils::date;
+
+    fn parse(found: &str) -> NaiveDate {
+        date::parse(found).unwrap()
+    }
 
     fn publication_to_versions() -> BTreeMap<Reverse<String>, Publication> {
         let test_data = json!({
@@ -249,26 +324,34 @@ mod tests {
         let active_publication_name = "2023-12-30".to_string();
         let current_publication_name = current_publication_name();
         let publication_to_versions = publication_to_versions();
-        let versions = &publication_to_versions
-            .get(&Reverse(active_publication_name.clone()))
-            .unwrap()
-            .versions;
-        let version_date: Option<String> = None;
-        let compare_to_date: Option<String> = None;
+        let versions = &VersionList::new(
+            publication_to_versions
+                .get(&Reverse(active_publication_name.clone()))
+                .unwrap()
+                .versions
+                .clone(),
+        );
+        let version_date: Option<NaiveDate> = None;
+        let compare_to_date: Option<NaiveDate> = None;
 
         let cut = historical;
 
         let actual = cut(
-            &versions,
+            versions,
             &current_publication_name,
             &active_publication_name,
-            &version_date,
-            &compare_to_date,
+            version_date,
+            compare_to_date,
+            "/a/b",
+            Locale::En,
         );
         let expected = Historical {
             publication: None,
             version: None,
             comparison: None,
+            links: Links::default(),
+            is_superseded: false,
+            is_future_dated: false,
         };
 
         assert_eq!(actual, expected);
@@ -276,35 +359,49 @@ mod tests {
 
     #[test]
     fn test_historical_when_outdated_publication_expect_publication_message_with_last_update() {
-        let test_cases = vec![
-            None,
-            Some("2023-10-22".to_string()),
-            Some("2024-06-06".to_string()),
-        ];
+        let test_cases = vec![None, Some(parse("2023-10-22")), Some(parse("2024-06-06"))];
 
         for version_date in test_cases {
             let active_publication_name = "2023-10-22".to_string();
             let current_publication_name = current_publication_name();
             let publication_to_versions = publication_to_versions();
-            let versions = &publication_to_versions
-                .get(&Reverse(active_publication_name.clone()))
-                .unwrap()
-                .versions;
-            let compare_to_date: Option<String> = None;
+            let versions = &VersionList::new(
+                publication_to_versions
+                    .get(&Reverse(active_publication_name.clone()))
+                    .unwrap()
+                    .versions
+                    .clone(),
+            );
+            let compare_to_date: Option<NaiveDate> = None;
 
             let cut = historical;
 
             let actual = cut(
-                &versions,
+                versions,
                 &current_publication_name,
                 &active_publication_name,
-                &version_date,
-                &compare_to_date,
+                version_date,
+                compare_to_date,
+                "/a/b",
+                Locale::En,
             );
             let expected = Historical {
-                publication: Some(publication_message_template(&versions[0].date)),
+                publication: Some(publication_message_template(
+                    versions.dates().next(),
+                    Locale::En,
+                )),
                 version: None,
                 comparison: None,
+                links: Links {
+                    current: version_date
+                        .filter(|selected| *selected != parse("2023-10-22"))
+                        .map(|selected| {
+                            format!("/_publication/2023-10-22/_compare/{selected}/2023-10-22/a/b")
+                        }),
+                    compare: None,
+                },
+                is_superseded: true,
+                is_future_dated: false,
             };
 
             assert_eq!(actual, expected);
@@ -324,25 +421,46 @@ mod tests {
             let active_publication_name = "2023-10-22".to_string();
             let current_publication_name = current_publication_name();
             let publication_to_versions = publication_to_versions();
-            let versions = &publication_to_versions
-                .get(&Reverse(active_publication_name.clone()))
-                .unwrap()
-                .versions;
-            let compare_to_date: Option<String> = None;
+            let versions = &VersionList::new(
+                publication_to_versions
+                    .get(&Reverse(active_publication_name.clone()))
+                    .unwrap()
+                    .versions
+                    .clone(),
+            );
+            let compare_to_date: Option<NaiveDate> = None;
 
             let cut = historical;
 
             let actual = cut(
-                &versions,
+                versions,
                 &current_publication_name,
                 &active_publication_name,
-                &Some(version_date.to_string()),
-                &compare_to_date,
+                Some(parse(version_date)),
+                compare_to_date,
+                "/a/b",
+                Locale::En,
             );
             let expected = Historical {
-                publication: Some(publication_message_template(&versions[0].date)),
-                version: Some(version_message_template(version_date, start_date, end_date)),
+                publication: Some(publication_message_template(
+                    versions.dates().next(),
+                    Locale::En,
+                )),
+                version: Some(version_message_template(
+                    parse(version_date),
+                    Some(parse(start_date)),
+                    Some(parse(end_date)),
+                    Locale::En,
+                )),
                 comparison: None,
+                links: Links {
+                    current: Some(format!(
+                        "/_publication/2023-10-22/_compare/{version_date}/2023-10-22/a/b"
+                    )),
+                    compare: None,
+                },
+                is_superseded: true,
+                is_future_dated: false,
             };
 
             assert_eq!(actual, expected);
@@ -365,21 +483,26 @@ mod tests {
             let active_publication_name = "2023-10-22".to_string();
             let current_publication_name = current_publication_name();
             let publication_to_versions = publication_to_versions();
-            let versions = &publication_to_versions
-                .get(&Reverse(active_publication_name.clone()))
-                .unwrap()
-                .versions;
-            let compare_to_date = Some("2023-10-22".to_string());
+            let versions = &VersionList::new(
+                publication_to_versions
+                    .get(&Reverse(active_publication_name.clone()))
+                    .unwrap()
+                    .versions
+                    .clone(),
+            );
+            let compare_to_date = Some(parse("2023-10-22"));
             let start_date = version_date;
 
             let cut = historical;
 
             let actual = cut(
-                &versions,
+                versions,
                 &current_publication_name,
                 &active_publication_name,
-                &Some(version_date.to_string()),
-                &compare_to_date,
+                Some(parse(version_date)),
+                compare_to_date,
+                "/a/b",
+                Locale::En,
             );
 
             let expected_comparison_message = messages_between_template(
@@ -392,14 +515,27 @@ mod tests {
                     "5 updates" => 5,
                     _ => 0,
                 },
-                &format_date(start_date),
+                &Locale::En.format_date(parse(start_date)),
                 None,
             );
 
             let expected = Historical {
-                publication: Some(publication_message_template(&versions[0].date)),
+                publication: Some(publication_message_template(
+                    versions.dates().next(),
+                    Locale::En,
+                )),
                 version: None,
                 comparison: Some(expected_comparison_message),
+                links: Links {
+                    current: (version_date != "2023-10-22").then(|| {
+                        format!("/_publication/2023-10-22/_compare/{version_date}/2023-10-22/a/b")
+                    }),
+                    compare: Some(format!(
+                        "/_publication/2023-10-22/_compare/{version_date}/2023-10-22/a/b"
+                    )),
+                },
+                is_superseded: true,
+                is_future_dated: false,
             };
 
             assert_eq!(actual, expected);
@@ -425,19 +561,24 @@ mod tests {
             let active_publication_name = "2023-12-30".to_string();
             let current_publication_name = current_publication_name();
             let publication_to_versions = publication_to_versions();
-            let versions = &publication_to_versions
-                .get(&Reverse(active_publication_name.clone()))
-                .unwrap()
-                .versions;
+            let versions = &VersionList::new(
+                publication_to_versions
+                    .get(&Reverse(active_publication_name.clone()))
+                    .unwrap()
+                    .versions
+                    .clone(),
+            );
 
             let cut = historical;
 
             let actual = cut(
-                &versions,
+                versions,
                 &current_publication_name,
                 &active_publication_name,
-                &Some(version_date.to_string()),
-                &Some(compare_to_date.to_string()),
+                Some(parse(version_date)),
+                Some(parse(compare_to_date)),
+                "/a/b",
+                Locale::En,
             );
 
             let expected_comparison_message = messages_between_template(
@@ -453,17 +594,55 @@ mod tests {
                     "8 updates" => 8,
                     _ => 0,
                 },
-                &format_date(version_date),
-                Some(format_date(compare_to_date)),
+                &Locale::En.format_date(parse(version_date)),
+                Some(Locale::En.format_date(parse(compare_to_date))),
             );
 
             let expected = Historical {
                 publication: None,
                 version: None,
                 comparison: Some(expected_comparison_message),
+                links: Links {
+                    current: Some(format!("/_compare/{version_date}/2023-12-30/a/b")),
+                    compare: Some(format!("/_compare/{version_date}/{compare_to_date}/a/b")),
+                },
+                is_superseded: true,
+                is_future_dated: false,
             };
 
             assert_eq!(actual, expected);
         }
     }
+
+    #[test]
+    fn test_historical_when_date_after_today_expect_future_dated_and_not_superseded() {
+        let current_publication_name = current_publication_name();
+        let publication_to_versions = publication_to_versions();
+        let versions = &VersionList::new(
+            publication_to_versions
+                .get(&Reverse(current_publication_name.clone()))
+                .unwrap()
+                .versions
+                .clone(),
+        );
+
+        let cut = historical;
+
+        let actual = cut(
+            versions,
+            &current_publication_name,
+            &current_publication_name,
+            Some(parse("2999-01-01")),
+            None,
+            "/a/b",
+            Locale::En,
+        );
+
+        assert!(actual.is_future_dated);
+        assert!(!actual.is_superseded);
+        assert_eq!(
+            actual.links.current.as_deref(),
+            Some("/_compare/2999-01-01/2023-12-30/a/b")
+        );
+    }
 }
@@ -1,15 +1,17 @@
-use std::{cmp::Reverse, collections::BTreeMap};
+use std::{cmp::Reverse, collections::BTreeMap, ops::Deref};
 
 use chrono::NaiveDate;
-use serde::Deserialize;
-use serde::Serialize;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::db::models;
+use crate::server::base_path::BasePath;
+use crate::utils::date;
+use crate::utils::locale::Locale;
 
 use self::messages::Historical;
 
-use super::format_date;
-use super::CURRENT_PUBLICATION_NAME;
+use super::request::{self, DATE_SEGMENT_FORMAT};
+use super::{CURRENT_PUBLICATION_NAME, CURRENT_VERSION_DATE};
 
 /// Historical messages for the versions endpoint.
 pub mod messages;
@@ -23,10 +25,11 @@ pub struct Versions {
     pub active_publication: String,
     /// Currently selected version.
     /// Resolves to "current" if the latest version is selected.
-    pub active_version: String,
+    pub active_version: VersionDate,
     /// Currently selected version to compare against.
     /// If `compare_date` is specified, this will be the date to compare against.
-    pub active_compare_to: Option<String>,
+    #[serde(with = "date::ymd_option")]
+    pub active_compare_to: Option<NaiveDate>,
     /// Features for the versions endpoint.
     pub features: Features,
     /// URL path.
@@ -54,7 +57,8 @@ pub struct Publication {
     /// Whether the publication is currently active.
     pub active: bool,
     /// Date of the publication.
-    pub date: String,
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
     /// Display name of the publication.
     pub display: String,
     /// Name of the publication.
@@ -64,11 +68,11 @@ pub struct Publication {
 }
 
 /// Response for a version.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Version {
     /// Codified date of the version.
-    pub date: String,
+    pub date: VersionDate,
     /// Display date of the version.
     pub display: String,
     /// Version number of the version.
@@ -76,11 +80,128 @@ pub struct Version {
     pub index: usize,
 }
 
+/// Response for the adjacent versions endpoint.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Adjacent {
+    /// URL path.
+    pub path: String,
+    /// Date the previous and next versions are relative to.
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
+    /// Name of the publication the versions were read from.
+    pub publication: String,
+    /// Latest version before the date, if any.
+    pub previous: Option<VersionLink>,
+    /// Earliest version after the date, if any.
+    pub next: Option<VersionLink>,
+}
+
+/// A version of a document, with the url it is served under.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionLink {
+    /// Codified date of the version.
+    #[serde(with = "date::ymd")]
+    pub date: NaiveDate,
+    /// Url of the version, e.g. `/_date/2023-10-22/a/b/c`.
+    pub url: String,
+}
+
+impl VersionLink {
+    /// Link to the version of the document at `url` codified on `date`.
+    #[must_use]
+    pub fn new(date: NaiveDate, url: &str) -> Self {
+        Self {
+            date,
+            url: format!("/_date/{}{url}", date::format(date)),
+        }
+    }
+
+    /// The link, with its url under the `base_path` the archive is served under.
+    #[must_use]
+    pub fn mounted(self, base_path: &BasePath) -> Self {
+        Self {
+            url: base_path.url(&self.url),
+            ..self
+        }
+    }
+}
+
+/// Response for a request with a `{date}` segment that is not a date, e.g. `_date/2025-13-45`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidDate {
+    /// URL path.
+    pub path: String,
+    /// The requested date segment.
+    pub date: String,
+    /// Format date segments are expected in.
+    pub expected_format: &'static str,
+    /// Versions nearest to the requested date, latest first.
+    pub nearest: Vec<VersionLink>,
+    /// Explanation of why the date is not valid.
+    pub message: String,
+}
+
+impl InvalidDate {
+    /// Explain that the `invalid` date segment is not a date, and suggest the versions of the
+    /// document at `url` nearest to it.
+    ///
+    /// The nearest versions are the versions immediately before and after the closest valid
+    /// date, or the latest version if the segment is not shaped like a date.
+    #[must_use]
+    pub fn build(url: &str, invalid: &request::InvalidDate, versions: &VersionList) -> Self {
+        let nearest: Vec<NaiveDate> = invalid.closest.map_or_else(
+            || versions.dates().take(1).collect(),
+            |closest| {
+                let after = versions.dates().filter(|ver| *ver > closest).last();
+                let on_or_before = versions.dates().find(|ver| *ver <= closest);
+                after.into_iter().chain(on_or_before).collect()
+            },
+        );
+        let closest_hint = invalid.closest.map_or_else(String::new, |closest| {
+            format!(" The closest valid date is {}.", date::format(closest))
+        });
+        let message = format!(
+            "'{}' is not a valid date. Dates are formatted as {DATE_SEGMENT_FORMAT}, e.g. 2023-10-22.{closest_hint}",
+            invalid.segment
+        );
+        Self {
+            path: url.strip_prefix('/').unwrap_or_default().to_owned(),
+            date: invalid.segment.clone(),
+            expected_format: DATE_SEGMENT_FORMAT,
+            nearest: nearest
+                .into_iter()
+                .map(|ver| VersionLink::new(ver, url))
+                .collect(),
+            message,
+        }
+    }
+}
+
+impl Adjacent {
+    /// Find the versions of the document at `url` immediately before and after `date`.
+    #[must_use]
+    pub fn build(url: &str, date: NaiveDate, publication: String, versions: &[Version]) -> Self {
+        let dates = versions.iter().filter_map(|ver| ver.date.codified());
+        let previous = dates.clone().filter(|ver| *ver < date).max();
+        let next = dates.filter(|ver| *ver > date).min();
+        Self {
+            path: url.strip_prefix('/').unwrap_or_default().to_owned(),
+            previous: previous.map(|ver| VersionLink::new(ver, url)),
+            next: next.map(|ver| VersionLink::new(ver, url)),
+            date,
+            publication,
+        }
+    }
+}
+
 impl From<models::version::Version> for Version {
     fn from(value: models::version::Version) -> Self {
         Self {
-            date: value.codified_date.clone(),
-            display: value.codified_date,
+            date: VersionDate::Codified(value.codified_date),
+            display: date::format(value.codified_date),
             index: 0,
         }
     }
@@ -95,13 +216,14 @@ impl Versions {
     #[must_use]
     pub fn build(
         active_publication_name: &str,
-        active_version: String,
-        active_compare_to: Option<String>,
+        active_version: VersionDate,
+        active_compare_to: Option<NaiveDate>,
         url: &str,
         publications: &[models::publication::Publication],
         current_publication_name: &str,
         versions: &[Version],
         messages: Historical,
+        locale: Locale,
     ) -> Self {
         Self {
             active_publication: active_publication_name.to_owned(),
@@ -119,11 +241,12 @@ impl Versions {
                         Reverse(pb.name.clone()),
                         Publication {
                             active: pb.name == active_publication_name,
-                            date: pb.date.clone(),
+                            date: pb.date,
                             display: Self::format_display_date(
                                 &pb.name,
-                                &pb.date,
+                                pb.date,
                                 current_publication_name,
+                                locale,
                             ),
                             name: pb.name.clone(),
                             versions: {
@@ -144,12 +267,17 @@ impl Versions {
 
     /// Returns a formatted display date.
     /// If the `date` is current, returns the date with `(current)` appended.
-    fn format_display_date(name: &str, date: &str, current_date: &str) -> String {
+    fn format_display_date(
+        name: &str,
+        date: NaiveDate,
+        current_date: &str,
+        locale: Locale,
+    ) -> String {
         if name == CURRENT_PUBLICATION_NAME {
             CURRENT_PUBLICATION_NAME.to_owned()
         } else {
-            let mut formatted_date = format_date(date);
-            if date == current_date {
+            let mut formatted_date = locale.format_date(date);
+            if date::parse(current_date).is_ok_and(|current| current == date) {
                 formatted_date.push_str(" (current)");
             }
             formatted_date
@@ -160,61 +288,292 @@ impl Versions {
 impl Version {
     /// Create a new version.
     #[must_use]
-    pub const fn new(date: String, display: String, index: usize) -> Self {
+    pub const fn new(date: VersionDate, display: String, index: usize) -> Self {
         Self {
             date,
             display,
             index,
         }
     }
+}
+
+/// Date of a version, served as `%Y-%m-%d` or as `current` for the current version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VersionDate {
+    /// Version codified on a date.
+    Codified(NaiveDate),
+    /// Current version, ordered after every codified version.
+    Current,
+}
 
-    /// Insert a new version if it is not present in the list of versions.
-    /// If the date is not in the list of versions, add it
-    /// Do nothing if the date is already in the list of versions.
-    /// This for compatibility purposes with the previous implementation of historical versions
-    pub fn insert_if_not_present(versions: &mut Vec<Self>, date: Option<String>) {
-        let Some(version_date) = date else {
-            return;
-        };
-        if NaiveDate::parse_from_str(&version_date, "%Y-%m-%d").is_err() {
-            return;
+impl VersionDate {
+    /// The codified date, if the version is not the current version.
+    #[must_use]
+    pub const fn codified(self) -> Option<NaiveDate> {
+        match self {
+            Self::Codified(date) => Some(date),
+            Self::Current => None,
         }
-        if versions.iter().all(|ver| ver.date != version_date) {
-            let version = Self::new(version_date.clone(), version_date, 0);
-            Self::insert_version_sorted(versions, version);
+    }
+}
+
+impl From<NaiveDate> for VersionDate {
+    fn from(date: NaiveDate) -> Self {
+        Self::Codified(date)
+    }
+}
+
+impl Serialize for VersionDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Self::Codified(found) => date::ymd::serialize(&found, serializer),
+            Self::Current => serializer.serialize_str(CURRENT_VERSION_DATE),
         }
     }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
+impl<'de> Deserialize<'de> for VersionDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let found = String::deserialize(deserializer)?;
+        if found == CURRENT_VERSION_DATE {
+            return Ok(Self::Current);
+        }
+        date::parse(&found)
+            .map(Self::Codified)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Versions of a document or collection, sorted by codified date in descending order, without
+/// duplicate dates.
+///
+/// The order is kept by construction, so versions are inserted and looked up by position.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionList(Vec<Version>);
+
+impl VersionList {
+    /// Sort the `versions` by date in descending order, keeping the first version of every date.
+    #[must_use]
+    pub fn new(mut versions: Vec<Version>) -> Self {
+        versions.sort_by_key(|version| Reverse(version.date));
+        versions.dedup_by(|next, current| next.date == current.date);
+        Self(versions)
+    }
 
-    /// Insert a new item into an already sorted collection.
-    /// The collection is sorted by date in descending order.
-    pub fn insert_version_sorted(collection: &mut Vec<Self>, item: Self) {
-        let mut idx = 0;
-        for i in collection.iter() {
-            if i.date < item.date {
-                break;
+    /// Insert the `version` at the position of its date.
+    ///
+    /// Returns whether the version was inserted, which it is not if a version of its date is
+    /// already in the list.
+    pub fn insert(&mut self, version: Version) -> bool {
+        match self
+            .0
+            .binary_search_by(|probe| version.date.cmp(&probe.date))
+        {
+            Ok(_) => false,
+            Err(idx) => {
+                self.0.insert(idx, version);
+                true
             }
-            idx += 1;
         }
-        collection.insert(idx, item);
     }
 
-    /// Utility function to find the index of a date in a list of versions.
+    /// Insert a version codified on `date`, if it is not in the list yet.
+    ///
+    /// This for compatibility purposes with the previous implementation of historical versions.
+    pub fn insert_date(&mut self, date: NaiveDate) -> bool {
+        self.insert(Version::new(
+            VersionDate::Codified(date),
+            date::format(date),
+            0,
+        ))
+    }
+
+    /// Index of the version of `date`, or else of the latest version before `date`.
+    ///
+    /// Returns the number of versions if all versions are after `date`.
+    #[must_use]
+    pub fn find_index_or_closest(&self, date: NaiveDate) -> usize {
+        self.0
+            .partition_point(|ver| ver.date > VersionDate::Codified(date))
+    }
+
+    /// The codified dates of the versions, in descending order.
+    pub fn dates(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        self.0.iter().filter_map(|ver| ver.date.codified())
+    }
+
+    /// The versions, sorted by date in descending order.
     #[must_use]
-    pub fn find_index_or_closest(versions: &[Self], date: &str) -> usize {
-        versions
+    pub fn into_vec(self) -> Vec<Version> {
+        self.0
+    }
+}
+
+impl Deref for VersionList {
+    type Target = [Version];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromIterator<Version> for VersionList {
+    fn from_iter<I: IntoIterator<Item = Version>>(versions: I) -> Self {
+        Self::new(versions.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::server::api::versions::request::parse_date;
+    use crate::server::api::versions::response::{
+        Adjacent, InvalidDate, Version, VersionDate, VersionLink, VersionList,
+    };
+    use crate::utils::date;
+    use chrono::{Days, NaiveDate};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    /// Versions codified on days after 2020-01-01, with duplicates likely.
+    fn versions_strategy() -> impl Strategy<Value = Vec<Version>> {
+        vec(date_strategy(), 0..20).prop_map(|dates| {
+            dates
+                .into_iter()
+                .map(|date| Version::new(date.into(), date::format(date), 0))
+                .collect()
+        })
+    }
+
+    /// A `%Y-%m-%d` date on one of the first 60 days after 2020-01-01.
+    fn date_strategy() -> impl Strategy<Value = NaiveDate> {
+        (0_u64..60).prop_map(|days| {
+            let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+            date.checked_add_days(Days::new(days)).unwrap()
+        })
+    }
+
+    /// Whether the dates of the `versions` are in strictly descending order.
+    fn is_strictly_descending(versions: &[Version]) -> bool {
+        versions.windows(2).all(|pair| pair[0].date > pair[1].date)
+    }
+
+    /// The index of the exact or closest earlier date, found by scanning every version.
+    fn find_index_or_closest_by_scan(versions: &[Version], date: NaiveDate) -> usize {
+        let closest = versions
             .iter()
-            .position(|ver| ver.date.as_str() == date)
-            .unwrap_or_else(|| {
-                let closest_date = versions
-                    .iter()
-                    .filter(|ver| ver.date.as_str() < date)
-                    .max_by(|current, next| current.date.cmp(&next.date))
-                    .map_or_else(|| None, |ver| Some(ver.date.as_str()))
-                    .unwrap_or("-1");
-                versions
-                    .iter()
-                    .position(|ver| ver.date.as_str() == closest_date)
-                    .unwrap_or(versions.len())
-            })
+            .map(|ver| ver.date)
+            .filter(|ver| *ver <= VersionDate::Codified(date))
+            .max();
+        closest
+            .and_then(|found| versions.iter().position(|ver| ver.date == found))
+            .unwrap_or(versions.len())
+    }
+
+    proptest! {
+        #[test]
+        fn test_new_when_any_versions_expect_strictly_descending(versions in versions_strategy()) {
+            let cut = VersionList::new(versions);
+            prop_assert!(is_strictly_descending(&cut));
+        }
+
+        #[test]
+        fn test_new_when_any_versions_expect_every_date_kept(versions in versions_strategy()) {
+            let cut = VersionList::new(versions.clone());
+            prop_assert!(versions.iter().all(|ver| cut.iter().any(|kept| kept.date == ver.date)));
+        }
+
+        #[test]
+        fn test_insert_when_any_version_expect_strictly_descending_with_date(
+            versions in versions_strategy(),
+            date in date_strategy(),
+        ) {
+            let mut cut = VersionList::new(versions);
+            let present = cut.dates().any(|found| found == date);
+            let inserted = cut.insert_date(date);
+            prop_assert_eq!(inserted, !present);
+            prop_assert!(is_strictly_descending(&cut));
+            prop_assert!(cut.dates().any(|found| found == date));
+        }
+
+        #[test]
+        fn test_find_index_or_closest_when_any_date_expect_same_as_scan(
+            versions in versions_strategy(),
+            date in date_strategy(),
+        ) {
+            let cut = VersionList::new(versions);
+            let actual = cut.find_index_or_closest(date);
+            let expected = find_index_or_closest_by_scan(&cut, date);
+            prop_assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_version_date_when_serialized_expect_date_or_current() {
+        let codified = VersionDate::Codified(NaiveDate::from_ymd_opt(2023, 10, 22).unwrap());
+        let cut = serde_json::to_string(&[codified, VersionDate::Current]).unwrap();
+        assert_eq!(cut, r#"["2023-10-22","current"]"#);
+        let actual: Vec<VersionDate> = serde_json::from_str(&cut).unwrap();
+        assert_eq!(actual, [codified, VersionDate::Current]);
+        assert!(codified < VersionDate::Current);
+    }
+
+    #[test]
+    fn test_build_when_date_between_versions_expect_previous_and_next() {
+        let versions: Vec<Version> = ["2023-10-22", "2022-01-01", "2021-06-01"]
+            .into_iter()
+            .map(|found| Version::new(date::parse(found).unwrap().into(), found.to_owned(), 0))
+            .collect();
+        let cut = Adjacent::build(
+            "/a/b/c",
+            date::parse("2022-01-01").unwrap(),
+            "p2".to_owned(),
+            &versions,
+        );
+        assert_eq!(cut.path, "a/b/c");
+        assert_eq!(
+            cut.previous,
+            Some(VersionLink::new(
+                date::parse("2021-06-01").unwrap(),
+                "/a/b/c"
+            ))
+        );
+        assert_eq!(
+            cut.next.map(|next| next.url),
+            Some("/_date/2023-10-22/a/b/c".to_owned())
+        );
+        let cut = Adjacent::build(
+            "/a/b/c",
+            date::parse("2024-01-01").unwrap(),
+            "p2".to_owned(),
+            &versions,
+        );
+        assert_eq!(cut.next, None);
+    }
+
+    #[test]
+    fn test_invalid_date_when_out_of_range_date_expect_message_with_closest_date() {
+        let versions: VersionList = ["2023-10-22", "2022-01-01"]
+            .into_iter()
+            .map(|found| Version::new(date::parse(found).unwrap().into(), found.to_owned(), 0))
+            .collect();
+        let invalid = parse_date("2023-13-45").unwrap_err();
+        let cut = InvalidDate::build("/a/b/c", &invalid, &versions);
+        assert_eq!(cut.date, "2023-13-45");
+        assert!(cut
+            .message
+            .ends_with("The closest valid date is 2023-12-31."));
+        assert_eq!(
+            cut.nearest,
+            [VersionLink::new(
+                date::parse("2023-10-22").unwrap(),
+                "/a/b/c"
+            )]
+        );
     }
 }
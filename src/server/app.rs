@@ -3,87 +3,464 @@
     clippy::exit,
     reason = "We exit with 1 error code on any application errors"
 )]
-use crate::db;
+use crate::history::views::ViewCounter;
+use crate::server::access_log::AccessLogger;
+use crate::server::api::identifiers::Identifiers;
 use crate::server::api::state::App as AppState;
+use crate::server::api::takedown::Takedowns;
+use crate::server::auth::Authenticator;
+use crate::server::base_path::BasePath;
+use crate::server::cache::Cache;
 use crate::server::errors::CliError;
+use crate::server::load_shedding::{EndpointClass, LoadShedder};
+use crate::server::proxy::Proxies;
+use crate::server::scheduler::{self, Scheduled, Updates};
+use crate::server::startup::{self, Validated};
+use crate::server::timing::Timings;
+use crate::server::warmup;
 use crate::stelae::archive::Archive;
-use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::{App, Error, HttpServer};
+use crate::utils::daemon;
+use actix_http::{Request, Response};
+use actix_web::dev::{AppConfig, Service, ServiceRequest, ServiceResponse};
+use actix_web::{error, rt, rt::time, web, App, Error, HttpMessage as _, HttpServer};
 use tracing_actix_web::TracingLogger;
 
-use std::{path::PathBuf, process};
+use std::net::{IpAddr, TcpListener};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt as _,
+    io::{FromRawFd as _, OwnedFd, RawFd},
+    net::UnixListener,
+};
+#[cfg(unix)]
+use std::{env, fs, path::Path};
+use std::{fmt, io, path::PathBuf, process, time::Instant};
 
 use actix_http::body::MessageBody;
-use actix_service::ServiceFactory;
+use actix_service::{IntoServiceFactory, ServiceFactory};
 
 use super::api::state::Global;
 use super::tracing::StelaeRootSpanBuilder;
-use crate::server::api::routes;
+use crate::server::api::routes::{self, Routes};
+
+/// Host documents are served on when no address is given.
+pub const DEFAULT_BIND_HOST: &str = "127.0.0.1";
+
+/// Port the management routes are served on when only an admin host is given.
+pub const DEFAULT_ADMIN_PORT: u16 = 9000;
+
+/// Prefix of a `--bind` address of a unix domain socket, e.g. `unix:/run/stelae.sock`.
+const UNIX_BIND_PREFIX: &str = "unix:";
+
+/// First file descriptor passed by systemd socket activation, see `sd_listen_fds(3)`.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Address documents are served on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bind {
+    /// Host and port of a TCP socket.
+    Tcp(String, u16),
+    /// Path of a unix domain socket.
+    Unix(PathBuf),
+}
+
+impl Bind {
+    /// Parse the `address` to serve on, either a host served on `port` or `unix:{path}`.
+    ///
+    /// Defaults to [`DEFAULT_BIND_HOST`] on `port`.
+    #[must_use]
+    pub fn parse(address: Option<&str>, port: u16) -> Self {
+        let host = address.unwrap_or(DEFAULT_BIND_HOST);
+        host.strip_prefix(UNIX_BIND_PREFIX).map_or_else(
+            || Self::Tcp(host.to_owned(), port),
+            |path| Self::Unix(PathBuf::from(path)),
+        )
+    }
+}
+
+impl fmt::Display for Bind {
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "Matching the borrowed address is clearer than matching with `ref` patterns"
+    )]
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Tcp(host, port) => write!(formatter, "http://{host}:{port}"),
+            Self::Unix(path) => write!(formatter, "{UNIX_BIND_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// Addresses the archive is served on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listen {
+    /// Address documents are served on, unless sockets are passed by systemd socket activation.
+    pub bind: Bind,
+    /// Address the management routes, `/_admin/*`, `/_metrics` and `/_health`, are served on
+    /// instead of `bind`, if any.
+    pub admin: Option<Bind>,
+    /// Path the documents and the APIs are served under on `bind`, see [`BasePath`].
+    pub base_path: BasePath,
+}
+
+/// Listening socket passed by systemd socket activation.
+enum Listener {
+    /// A TCP socket.
+    Tcp(TcpListener),
+    /// A unix domain socket.
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
 
 /// Serve documents in a Stelae archive.
+///
+/// The archive is validated first, and all problems found are reported together, see
+/// [`startup::validate`]. Documents are served on the sockets passed by systemd socket
+/// activation if there are any, and on `listen.bind` otherwise. If `listen.admin` is set, the
+/// management routes are only served there, see [`Routes`], so they can be firewalled apart.
+/// If `warm` is set, the cache is warmed before traffic is accepted, see [`warmup::warm`].
+/// If updates are `scheduled`, the archive is updated in the background, see [`scheduler`].
 #[actix_web::main]
-#[tracing::instrument(skip(raw_archive_path, archive_path, port, individual))]
+#[tracing::instrument(skip(raw_archive_path, archive_path, listen, individual, warm, scheduled))]
 pub async fn serve_archive(
     raw_archive_path: &str,
     archive_path: PathBuf,
-    port: u16,
+    listen: Listen,
     individual: bool,
+    warm: bool,
+    scheduled: Option<Scheduled>,
 ) -> Result<(), CliError> {
-    let bind = "127.0.0.1";
+    let listeners = activated_listeners().map_err(|err| {
+        tracing::error!("Unable to take the sockets passed by systemd.");
+        tracing::error!("Error: {err:?}");
+        CliError::GenericError
+    })?;
     let message = "Running Publish Server on a Stelae archive at";
-    tracing::info!("{message} '{raw_archive_path}' on http://{bind}:{port}.",);
+    let Listen {
+        bind,
+        admin,
+        base_path,
+    } = listen;
+    let address = bind.to_string();
+    if listeners.is_empty() {
+        tracing::info!("{message} '{raw_archive_path}' on {address}.");
+    } else {
+        let count = listeners.len();
+        tracing::info!("{message} '{raw_archive_path}' on {count} socket(s) passed by systemd.");
+    }
+
+    let Validated {
+        archive,
+        config,
+        db,
+    } = startup::validate(raw_archive_path, archive_path.clone(), individual)
+        .await
+        .map_err(|report| {
+            tracing::error!("{report}");
+            report.cli_error()
+        })?;
+    let locales = config.locales.unwrap_or_default();
+    let watermarks = config.watermarks.unwrap_or_default();
+    let warmup = config.warmup.unwrap_or_default();
+    let views = config
+        .document_views
+        .as_ref()
+        .map_or_else(ViewCounter::default, |_| ViewCounter::enabled());
+    let views_flush_interval = config.document_views.unwrap_or_default().flush_interval();
+    let load_shedder = LoadShedder::new(&config.concurrency.unwrap_or_default());
 
-    let db = match db::init::connect(&archive_path).await {
-        Ok(db) => db,
+    let takedowns = match Takedowns::load(db.shared()).await {
+        Ok(takedowns) => takedowns,
         Err(err) => {
-            tracing::error!(
-                "error: could not connect to database. Confirm that DATABASE_URL env var is set correctly."
-            );
-            tracing::error!("Error: {:?}", err);
+            tracing::error!("Unable to load takedowns.");
+            tracing::error!("Error: {err:?}");
             return Err(CliError::DatabaseConnectionError);
         }
     };
 
-    let archive = match Archive::parse(archive_path, &PathBuf::from(raw_archive_path), individual) {
-        Ok(archive) => archive,
+    let identifiers = match Identifiers::load(&db).await {
+        Ok(identifiers) => identifiers,
         Err(err) => {
-            tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+            tracing::error!("Unable to load identifiers.");
             tracing::error!("Error: {err:?}");
-            return Err(CliError::ArchiveParseError);
+            return Err(CliError::DatabaseConnectionError);
         }
     };
 
-    let state = AppState { archive, db };
+    let authenticator = init_authenticator(&archive).map_err(|err| {
+        tracing::error!("Unable to initialize authentication.");
+        tracing::error!("Error: {err:?}");
+        CliError::GenericError
+    })?;
+    if admin.is_none() && authenticator.is_none() && is_exposed(&bind, &listeners) {
+        tracing::error!(
+            "Refusing to serve the unauthenticated management routes on {address}, which is not a loopback address."
+        );
+        tracing::error!(
+            "Configure `[auth]` in `.taf/config.toml`, or serve the management routes apart with `--admin-bind`."
+        );
+        return Err(CliError::GenericError);
+    }
 
-    HttpServer::new(move || {
-        init(&state).unwrap_or_else(|err| {
-            tracing::error!("Unable to initialize app.");
-            tracing::error!("Error: {err:?}");
-            // NOTE: We should not need to exit code 1 here (or in any of the closures in `routes.rs`).
-            // We should be able to return an error and let the caller handle it.
-            // However, Actix does not allow us to instantiate the app outside of the closure,
-            // because the opaque type `App` does not implement `Clone`.
-            // Figure out a way to handle this without exiting the process.
-            process::exit(1)
+    let cache = Cache::new(warmup.max_age());
+    warmup::load_current_index(&cache, &archive, &db).await;
+    if warm {
+        let started = Instant::now();
+        match warmup::warm(&cache, &archive, &db, &warmup).await {
+            Ok(warmed) => warmup::log(&warmed, started.elapsed()),
+            Err(err) => {
+                tracing::error!("Unable to warm up, serving with a partially warmed cache.");
+                tracing::error!("Error: {err:?}");
+            }
+        }
+    }
+
+    let state = AppState {
+        archive,
+        db,
+        cache,
+        identifiers,
+        locales,
+        takedowns,
+        watermarks,
+        updates: Updates::default(),
+        base_path,
+        views: views.clone(),
+        authenticator,
+        load_shedder,
+    };
+    views.start_flushing(state.db.shared().clone(), views_flush_interval);
+    if let Some(updates) = scheduled {
+        tracing::info!("Updating the archive at '{}' (UTC)", updates.schedule);
+        if let Err(err) =
+            scheduler::start(updates, raw_archive_path.to_owned(), archive_path, &state)
+        {
+            tracing::error!("Unable to schedule the updates: {err:?}");
+            return Err(CliError::GenericError);
+        }
+    }
+
+    let admin_server = admin
+        .map(|admin_bind| {
+            tracing::info!("Serving the management routes on {admin_bind}.");
+            let admin_state = state.clone();
+            let server = HttpServer::new(move || {
+                init(&admin_state, Routes::Management)
+                    .unwrap_or_else(|err| exit_uninitialized(&err))
+            })
+            .workers(1)
+            .disable_signals();
+            listen_on(server, admin_bind.clone(), vec![]).map_err(|err| {
+                tracing::error!("Unable to listen on {admin_bind}.");
+                tracing::error!("Error: {err:?}");
+                CliError::GenericError
+            })
         })
-    })
-    .bind((bind, port))?
-    .run()
-    .await
-    .map_err(|err| {
+        .transpose()?;
+    let routes = if admin_server.is_some() {
+        Routes::Public
+    } else {
+        Routes::All
+    };
+    let shared_db = state.db.shared().clone();
+    let server = HttpServer::new(move || {
+        init(&state, routes).unwrap_or_else(|err| exit_uninitialized(&err))
+    });
+    let listening = listen_on(server, bind, listeners).map_err(|err| {
+        tracing::error!("Unable to listen on {address}.");
+        tracing::error!("Error: {err:?}");
+        CliError::GenericError
+    })?;
+    // the management routes are served until the documents stop being served.
+    let admin_handle = admin_server.map(|admin_listening| {
+        let running = admin_listening.run();
+        let handle = running.handle();
+        rt::spawn(running);
+        handle
+    });
+    let running = listening.run();
+    daemon::stop_with_service(&running.handle());
+    let served = running.await;
+    if let Some(handle) = admin_handle {
+        handle.stop(true).await;
+    }
+    if let Err(err) = views.flush(&shared_db).await {
+        tracing::error!("Unable to write document views: {err:?}");
+    }
+    served.map_err(|err| {
         tracing::error!("Error running server: {err:?}");
         CliError::GenericError
     })
 }
 
+/// Exit with code 1 if an app cannot be initialized in a worker of the server.
+fn exit_uninitialized<T>(err: &anyhow::Error) -> T {
+    tracing::error!("Unable to initialize app.");
+    tracing::error!("Error: {err:?}");
+    // NOTE: We should not need to exit code 1 here (or in any of the closures in `routes.rs`).
+    // We should be able to return an error and let the caller handle it.
+    // However, Actix does not allow us to instantiate the app outside of the closure,
+    // because the opaque type `App` does not implement `Clone`.
+    // Figure out a way to handle this without exiting the process.
+    process::exit(1)
+}
+
+/// Listen on the sockets passed by systemd socket activation if there are any, and on `bind`
+/// otherwise.
+///
+/// # Errors
+/// Errors if a socket cannot be listened on.
+fn listen_on<F, I, S, B>(
+    server: HttpServer<F, I, S, B>,
+    bind: Bind,
+    listeners: Vec<Listener>,
+) -> io::Result<HttpServer<F, I, S, B>>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S, Request>,
+    S: ServiceFactory<Request, Config = AppConfig> + 'static,
+    S::Error: Into<Error> + 'static,
+    S::InitError: fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service<Request>>::Future: 'static,
+    S::Service: 'static,
+    B: MessageBody + 'static,
+{
+    if !listeners.is_empty() {
+        return listeners
+            .into_iter()
+            .try_fold(server, |listening, listener| match listener {
+                Listener::Tcp(socket) => listening.listen(socket),
+                #[cfg(unix)]
+                Listener::Unix(socket) => listening.listen_uds(socket),
+            });
+    }
+    match bind {
+        Bind::Tcp(host, port) => server.bind((host, port)),
+        #[cfg(unix)]
+        Bind::Unix(path) => {
+            remove_stale_socket(&path)?;
+            server.bind_uds(path)
+        }
+        #[cfg(not(unix))]
+        Bind::Unix(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix domain sockets are not supported on this platform",
+        )),
+    }
+}
+
+/// Whether documents would be served to other hosts on the `listeners` passed by systemd, or on
+/// `bind` if there are none, i.e. on a TCP socket not bound to a loopback address.
+#[expect(
+    clippy::pattern_type_mismatch,
+    reason = "Matching the borrowed addresses is clearer than matching with `ref` patterns"
+)]
+fn is_exposed(bind: &Bind, listeners: &[Listener]) -> bool {
+    if !listeners.is_empty() {
+        return listeners.iter().any(|listener| match listener {
+            Listener::Tcp(socket) => socket
+                .local_addr()
+                .map_or(true, |addr| !addr.ip().is_loopback()),
+            #[cfg(unix)]
+            Listener::Unix(_) => false,
+        });
+    }
+    match bind {
+        Bind::Tcp(host, _) => !is_loopback(host),
+        Bind::Unix(_) => false,
+    }
+}
+
+/// Whether the `host` of a `--bind` address is a loopback address, or `localhost`.
+fn is_loopback(host: &str) -> bool {
+    let address = host.trim_start_matches('[').trim_end_matches(']');
+    address == "localhost" || address.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Take the listening sockets passed by systemd socket activation, see `sd_listen_fds(3)`.
+///
+/// Returns no sockets unless `LISTEN_PID` is the id of this process. The `LISTEN_*` variables
+/// are removed from the environment, so child processes do not take the sockets again.
+///
+/// # Errors
+/// Errors if `LISTEN_FDS` is not a number, or a passed socket is neither a TCP nor a unix socket.
+#[cfg(unix)]
+fn activated_listeners() -> io::Result<Vec<Listener>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").unwrap_or_default();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    if pid.and_then(|value| value.parse().ok()) != Some(process::id()) {
+        return Ok(vec![]);
+    }
+    let count: RawFd = fds
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START.saturating_add(count))
+        .map(|fd| {
+            // SAFETY: systemd passes the listening sockets to the process as the file descriptors
+            // starting at `SD_LISTEN_FDS_START`, and they are only taken once, on start-up.
+            let owned = unsafe { OwnedFd::from_raw_fd(fd) };
+            let tcp = TcpListener::from(owned);
+            if tcp.local_addr().is_ok() {
+                return Ok(Listener::Tcp(tcp));
+            }
+            let unix = UnixListener::from(OwnedFd::from(tcp));
+            unix.local_addr()?;
+            Ok(Listener::Unix(unix))
+        })
+        .collect()
+}
+
+/// Take the listening sockets passed by systemd socket activation, which only exists on unix.
+#[cfg(not(unix))]
+fn activated_listeners() -> io::Result<Vec<Listener>> {
+    Ok(vec![])
+}
+
+/// Remove the unix domain socket at `path` left behind by a previous run, if any.
+///
+/// # Errors
+/// Errors if something other than a socket exists at `path`, or the socket cannot be removed.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
 /// Initialize the application and all possible routing at start-up time.
 ///
+/// Requests with urls longer than the configured limit are rejected with `414 URI Too Long`,
+/// and JSON bodies larger than the configured limit with `413 Payload Too Large`.
+/// Requests over the concurrency limit of their endpoint class, counted across all the apps
+/// sharing the `state`, are rejected with `503 Service Unavailable`, and requests running over their time budget are cancelled with
+/// `504 Gateway Timeout`. If authentication is configured, requests to guarded routes without
+/// a valid token are rejected with `401 Unauthorized`, and requests of users lacking the required
+/// role with `403 Forbidden`. If an access log is configured, every request is written to it
+/// once responded to. If the documents and the APIs are served under a base path, requests
+/// outside of it are answered with `404 Not Found`, see [`BasePath`]. The client and the origin
+/// of every request are resolved once, honoring forwarded headers of trusted proxies only, see
+/// [`Proxies`]. If `[server_timing]` is enabled, responses break the time of the request down by
+/// phase in the `Server-Timing` header, see [`Timings`].
+///
 /// # Arguments
 /// * `state` - The application state
+/// * `routes` - The routes to register, see [`Routes`]
 /// # Errors
 /// Will error if unable to initialize the application
 pub fn init<T: Global + Clone + 'static>(
     state: &T,
+    routes: Routes,
 ) -> anyhow::Result<
     App<
         impl ServiceFactory<
@@ -95,7 +472,158 @@ pub fn init<T: Global + Clone + 'static>(
         >,
     >,
 > {
-    let app = App::new().wrap(TracingLogger::<StelaeRootSpanBuilder>::new());
-    let registered_app = routes::register_app(app, state)?;
+    let config = state.archive().get_config()?;
+    let limits = config.limits.unwrap_or_default();
+    let max_url_length = limits.max_url_length();
+    let load_shedder = state.load_shedder().clone();
+    let timeouts = config.timeouts.unwrap_or_default();
+    let guard_header = config
+        .headers
+        .and_then(|headers| headers.current_documents_guard);
+    let root_stele = state.archive().get_root()?.get_qualified_name();
+    let access_logger = config
+        .access_log
+        .map(|access_log| AccessLogger::new(access_log, root_stele.clone(), guard_header.clone()))
+        .transpose()?;
+    let authenticator = state.authenticator().cloned();
+    let proxies = Proxies::new(&config.proxy.unwrap_or_default())?;
+    let server_timing = config.server_timing.unwrap_or_default().is_enabled();
+    let base_path = if routes.public() {
+        state.base_path().clone()
+    } else {
+        BasePath::default()
+    };
+    let app = App::new()
+        .wrap_fn(move |req, srv| {
+            let path = req.path().to_owned();
+            let budget = timeouts.for_path(&path);
+            let response = srv.call(req);
+            async move {
+                // Dropping the handler cancels it, which rolls back any open transaction.
+                time::timeout(budget, response)
+                    .await
+                    .unwrap_or_else(|_elapsed| {
+                        tracing::warn!("Cancelled request to {path} after {budget:?}");
+                        Err(error::ErrorGatewayTimeout(format!(
+                            "Request took longer than {} seconds",
+                            budget.as_secs()
+                        )))
+                    })
+            }
+        })
+        .wrap_fn(move |req, srv| {
+            let admitted = load_shedder
+                .admit(EndpointClass::of(req.path()))
+                .map(|in_flight| (in_flight, srv.call(req)));
+            async move {
+                let (_in_flight, response) = admitted?;
+                response.await
+            }
+        })
+        .wrap_fn(move |req, srv| {
+            let authorized = authenticator
+                .as_ref()
+                .map_or(Ok(()), |auth| auth.authorize(&req));
+            let pending = authorized.map(|()| srv.call(req));
+            async move { pending?.await }
+        })
+        .wrap_fn(move |req, srv| {
+            let url_length = req
+                .uri()
+                .path_and_query()
+                .map_or(0, |path_and_query| path_and_query.as_str().len());
+            let pending = (url_length <= max_url_length).then(|| srv.call(req));
+            let too_long = move || {
+                error::ErrorUriTooLong(format!("URL is longer than {max_url_length} bytes"))
+            };
+            async move { pending.ok_or_else(too_long)?.await }
+        })
+        .wrap_fn(move |mut req, srv| {
+            let started = Instant::now();
+            let timings = Timings::start(&req, server_timing);
+            req.extensions_mut().insert(proxies.resolve(req.request()));
+            // Requests outside of the base path are refused before they are logged or routed.
+            let pending = base_path.mount(&mut req).then(|| {
+                let logged = access_logger
+                    .as_ref()
+                    .and_then(|logger| Some((logger.clone(), logger.start(&req)?)));
+                (logged, srv.call(req))
+            });
+            async move {
+                let (logged, handling) =
+                    pending.ok_or_else(|| error::ErrorNotFound("Not Found"))?;
+                let result = handling.await;
+                if let Some((logger, entry)) = logged {
+                    logger.finish(entry, &result, started.elapsed());
+                }
+                let mut response = result?;
+                timings.finish(&mut response);
+                Ok(response)
+            }
+        })
+        .wrap(TracingLogger::<StelaeRootSpanBuilder>::new())
+        .app_data(web::JsonConfig::default().limit(limits.max_body_size()));
+    let registered_app = routes::register_app(app, state, routes)?;
     Ok(registered_app)
 }
+
+/// Initialize the authenticator of guarded routes with the `[auth]` config of the `archive`, and
+/// fetch the signing keys of the identity provider.
+///
+/// The authenticator is initialized once, and shared by the workers through the app state, so
+/// the keys are fetched once per process instead of once per worker.
+///
+/// # Errors
+/// Errors if the config or the root stele of the archive cannot be read. Returns `None` if
+/// `[auth]` is not configured.
+pub fn init_authenticator(archive: &Archive) -> anyhow::Result<Option<Authenticator>> {
+    let config = archive.get_config()?;
+    let Some(auth) = config.auth else {
+        return Ok(None);
+    };
+    let guard_header = config
+        .headers
+        .and_then(|headers| headers.current_documents_guard);
+    let root_stele = archive.get_root()?.get_qualified_name();
+    let authenticator = Authenticator::new(auth, root_stele, guard_header, archive.get_scopes())
+        .with_aliases(archive.aliases.clone());
+    if let Err(err) = authenticator.refresh_keys() {
+        tracing::error!("Unable to fetch signing keys: {err:?}");
+    }
+    Ok(Some(authenticator))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::app::{is_exposed, Bind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_is_exposed_when_not_loopback_expect_true() {
+        let cut = is_exposed;
+        assert!(cut(&Bind::Tcp("0.0.0.0".to_owned(), 8080), &[]));
+        assert!(cut(&Bind::Tcp("203.0.113.7".to_owned(), 8080), &[]));
+        assert!(!cut(&Bind::Tcp("127.0.0.1".to_owned(), 8080), &[]));
+        assert!(!cut(&Bind::Tcp("[::1]".to_owned(), 8080), &[]));
+        assert!(!cut(&Bind::Tcp("localhost".to_owned(), 8080), &[]));
+        assert!(!cut(&Bind::Unix(PathBuf::from("/run/stelae.sock")), &[]));
+    }
+
+    #[test]
+    fn test_bind_parse_when_unix_prefix_expect_unix_socket() {
+        let cut = Bind::parse;
+        assert_eq!(
+            cut(Some("unix:/run/stelae.sock"), 8080),
+            Bind::Unix(PathBuf::from("/run/stelae.sock"))
+        );
+        assert_eq!(
+            cut(Some("0.0.0.0"), 8080),
+            Bind::Tcp("0.0.0.0".to_owned(), 8080)
+        );
+        assert_eq!(cut(None, 80), Bind::Tcp("127.0.0.1".to_owned(), 80));
+        assert_eq!(
+            cut(Some("unix:/run/stelae.sock"), 8080).to_string(),
+            "unix:/run/stelae.sock"
+        );
+    }
+}
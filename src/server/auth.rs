@@ -0,0 +1,566 @@
+//! Authenticate requests to guarded routes with an OIDC identity provider.
+//!
+//! Requests carry an ID or access token of the provider as an `Authorization: Bearer` header.
+//! Tokens are verified against the signing keys (JWKS) of the provider, which are fetched at
+//! start-up and fetched again in the background when a token is signed by an unknown key.
+//! Only `RS256` signed tokens are accepted.
+#![expect(
+    clippy::pattern_type_mismatch,
+    reason = "derive_more doesn't respect these lints"
+)]
+#![expect(
+    clippy::min_ident_chars,
+    reason = "JWK members of RSA keys are named `n` and `e` in RFC 7518"
+)]
+use crate::server::api::versions::{STELE_HEADER, STELE_SCOPE_HEADER};
+use crate::stelae::archive::{resolve_alias, resolve_scope, Auth, Role, DEFAULT_GROUPS_CLAIM};
+use crate::utils::http;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::{header, StatusCode};
+use actix_web::{error, rt, HttpMessage as _, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use chrono::Utc;
+use derive_more::{Display, Error};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Seconds of clock skew tolerated when checking the expiry of a token.
+const LEEWAY: i64 = 60;
+
+/// Minimum time between two fetches of the signing keys.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Collection of possible authentication errors
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "Named like the other error collections, e.g. `CliError`"
+)]
+#[derive(Debug, Display, Error)]
+pub enum AuthError {
+    /// No bearer token in the request
+    #[display(fmt = "Authentication required")]
+    MissingToken,
+    /// The token is malformed, expired, or not signed by the provider
+    #[display(fmt = "Invalid token")]
+    InvalidToken,
+    /// The user has no role that grants access to the stele
+    #[display(fmt = "Insufficient role")]
+    Forbidden,
+    /// The endpoint is only served to authenticated admins, and the request was not authenticated
+    /// because `[auth]` is not configured
+    #[display(fmt = "Authentication is not configured, see `[auth]`")]
+    NotConfigured,
+}
+
+#[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
+impl error::ResponseError for AuthError {
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if self.status_code() == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        http::respond_text(response, self.to_string())
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            Self::MissingToken | Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::Forbidden | Self::NotConfigured => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Signing keys of the provider, published at its `jwks_uri`.
+#[derive(Debug, Deserialize)]
+pub struct Jwks {
+    /// The keys, in JWK format.
+    pub keys: Vec<Jwk>,
+}
+
+/// A signing key in JWK format. Only RSA keys are used.
+#[derive(Debug, Deserialize)]
+pub struct Jwk {
+    /// Id of the key, referred to by the `kid` header of tokens.
+    pub kid: Option<String>,
+    /// Key type, e.g. `RSA`.
+    pub kty: String,
+    /// Base64url encoded modulus of an RSA key.
+    pub n: Option<String>,
+    /// Base64url encoded exponent of an RSA key.
+    pub e: Option<String>,
+}
+
+/// Decoded RSA public key.
+#[derive(Debug)]
+struct RsaKey {
+    /// Big-endian modulus.
+    modulus: Vec<u8>,
+    /// Big-endian exponent.
+    exponent: Vec<u8>,
+}
+
+/// Header of a token.
+#[derive(Debug, Deserialize)]
+struct TokenHeader {
+    /// Signing algorithm.
+    alg: String,
+    /// Id of the signing key.
+    kid: Option<String>,
+}
+
+/// Claims of a token checked by stelae.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    /// Issuer.
+    iss: String,
+    /// Audience, a single audience or a list.
+    aud: Audience,
+    /// Expiry, in seconds since the epoch.
+    exp: i64,
+    /// Start of validity, in seconds since the epoch.
+    nbf: Option<i64>,
+    /// Remaining claims, including the groups claim.
+    #[serde(flatten)]
+    other: HashMap<String, Value>,
+}
+
+/// Audience claim of a token.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    /// A single audience.
+    One(String),
+    /// A list of audiences.
+    Many(Vec<String>),
+}
+
+impl Audience {
+    /// Whether `audience` is one of the audiences.
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Self::One(one) => one == audience,
+            Self::Many(many) => many.iter().any(|each| each == audience),
+        }
+    }
+}
+
+/// `OpenID` provider metadata, published at `{issuer}/.well-known/openid-configuration`.
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    /// Url of the signing keys of the provider.
+    jwks_uri: String,
+}
+
+/// A user authorized by [`Authenticator::authorize`], recorded in the extensions of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Role of the user in the stele.
+    pub role: Role,
+    /// Qualified name of the stele the user was authorized in.
+    pub stele: String,
+}
+
+/// Authenticates and authorizes requests to guarded routes.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    /// Authentication configuration.
+    config: Arc<Auth>,
+    /// Qualified name of the root stele, the stele of requests without a stele header.
+    root_stele: String,
+    /// Header selecting the stele of current documents, if the archive guards them by header.
+    guard_header: Option<String>,
+    /// Scopes of the stelae, as pairs of qualified name and scope.
+    scopes: Vec<(String, String)>,
+    /// Former qualified names of stelae, mapped to their current qualified names.
+    aliases: HashMap<String, String>,
+    /// Signing keys of the provider, by key id.
+    keys: Arc<RwLock<HashMap<String, RsaKey>>>,
+    /// When the signing keys were last fetched.
+    last_refresh: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Authenticator {
+    /// Create an authenticator, without signing keys.
+    #[must_use]
+    pub fn new(
+        config: Auth,
+        root_stele: String,
+        guard_header: Option<String>,
+        scopes: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            config: Arc::new(config),
+            root_stele,
+            guard_header,
+            scopes,
+            aliases: HashMap::new(),
+            keys: Arc::new(RwLock::new(HashMap::new())),
+            last_refresh: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The authenticator, resolving the former qualified names of stelae in `aliases`.
+    #[must_use]
+    pub fn with_aliases(self, aliases: HashMap<String, String>) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Replace the signing keys with the RSA keys of `jwks`.
+    pub fn set_keys(&self, jwks: Jwks) {
+        let keys = jwks
+            .keys
+            .into_iter()
+            .filter(|jwk| jwk.kty == "RSA")
+            .filter_map(|jwk| {
+                let modulus = URL_SAFE_NO_PAD.decode(jwk.n?).ok()?;
+                let exponent = URL_SAFE_NO_PAD.decode(jwk.e?).ok()?;
+                Some((jwk.kid.unwrap_or_default(), RsaKey { modulus, exponent }))
+            })
+            .collect();
+        if let Ok(mut current) = self.keys.write() {
+            *current = keys;
+        }
+    }
+
+    /// Fetch the signing keys of the provider.
+    ///
+    /// # Errors
+    /// Errors if the provider metadata or the keys cannot be fetched.
+    pub fn refresh_keys(&self) -> anyhow::Result<()> {
+        if let Ok(mut last_refresh) = self.last_refresh.lock() {
+            *last_refresh = Some(Instant::now());
+        }
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let client = http::client();
+        let metadata: ProviderMetadata = client.get(&discovery_url).call()?.into_json()?;
+        let jwks: Jwks = client.get(&metadata.jwks_uri).call()?.into_json()?;
+        tracing::info!(
+            "Fetched {} signing keys from {}",
+            jwks.keys.len(),
+            metadata.jwks_uri
+        );
+        self.set_keys(jwks);
+        Ok(())
+    }
+
+    /// Fetch the signing keys in the background, unless they were fetched recently.
+    fn refresh_keys_in_background(&self) {
+        let is_due = self.last_refresh.lock().is_ok_and(|mut last_refresh| {
+            let is_stale =
+                last_refresh.is_none_or(|refreshed| refreshed.elapsed() >= REFRESH_INTERVAL);
+            if is_stale {
+                *last_refresh = Some(Instant::now());
+            }
+            is_stale
+        });
+        if !is_due {
+            return;
+        }
+        let authenticator = self.clone();
+        rt::task::spawn_blocking(move || {
+            if let Err(err) = authenticator.refresh_keys() {
+                tracing::error!("Unable to fetch signing keys: {err:?}");
+            }
+        });
+    }
+
+    /// The role required for a request to `path`, or `None` if the route is not guarded.
+    #[must_use]
+    pub fn required_role(&self, path: &str) -> Option<Role> {
+        if path.starts_with("/_admin/") || path == "/_metrics" {
+            return Some(Role::Admin);
+        }
+        let is_current_document = !["/_api/", "/_snapshot/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+        (is_current_document && self.config.guard_current_documents.unwrap_or(false))
+            .then_some(Role::Reader)
+    }
+
+    /// Authorize the request `req`, if its route is guarded.
+    ///
+    /// The stele of `/_admin` requests is taken from the `X-Stelae` header, or resolved from the
+    /// `X-Stelae-Scope` header, and the stele of current documents from the guard header of the
+    /// archive. Both default to the root stele.
+    ///
+    /// # Errors
+    /// Errors if the route is guarded and the request has no valid token, or the user lacks the
+    /// role. Errors as forbidden if the scope of an `/_admin` request is not served by one stele.
+    ///
+    /// The role is decided on the path the request is routed on, in which percent-encoded
+    /// characters are decoded, so e.g. `/%5Fadmin/pin` requires the role of `/_admin/pin`.
+    /// The authorized user is recorded in the request as a [`Principal`].
+    pub fn authorize(&self, req: &ServiceRequest) -> Result<(), AuthError> {
+        let Some(required) = self.required_role(req.match_info().as_str()) else {
+            return Ok(());
+        };
+        let header_value = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::MissingToken)?;
+        let groups = self.verify(token)?;
+        let stele = if required == Role::Admin {
+            match (header_value(STELE_HEADER), header_value(STELE_SCOPE_HEADER)) {
+                (Some(qualified_name), _) => {
+                    resolve_alias(qualified_name, &self.aliases).to_owned()
+                }
+                (None, Some(scope)) => {
+                    resolve_scope(scope, &self.scopes).map_err(|_err| AuthError::Forbidden)?
+                }
+                (None, None) => self.root_stele.clone(),
+            }
+        } else {
+            self.guard_header
+                .as_deref()
+                .and_then(header_value)
+                .unwrap_or(&self.root_stele)
+                .to_owned()
+        };
+        match self.config.role_for(&stele, &groups) {
+            Some(role) if role >= required => {
+                req.extensions_mut().insert(Principal { role, stele });
+                Ok(())
+            }
+            _ => Err(AuthError::Forbidden),
+        }
+    }
+
+    /// Verify the signature and claims of `token`, and return the groups of the user.
+    ///
+    /// # Errors
+    /// Errors if the token is malformed, expired, for another issuer or audience, or not
+    /// signed by a known key of the provider.
+    pub fn verify(&self, token: &str) -> Result<Vec<String>, AuthError> {
+        let mut parts = token.split('.');
+        let (Some(encoded_header), Some(encoded_claims), Some(encoded_signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(AuthError::InvalidToken);
+        };
+        let token_header: TokenHeader = decode_json(encoded_header)?;
+        if token_header.alg != "RS256" {
+            return Err(AuthError::InvalidToken);
+        }
+        let signature = URL_SAFE_NO_PAD
+            .decode(encoded_signature)
+            .map_err(|_err| AuthError::InvalidToken)?;
+        let signed = format!("{encoded_header}.{encoded_claims}");
+        let is_verified = self
+            .keys
+            .read()
+            .map_err(|_err| AuthError::InvalidToken)?
+            .get(&token_header.kid.unwrap_or_default())
+            .map(|found| {
+                RsaPublicKeyComponents {
+                    n: &found.modulus,
+                    e: &found.exponent,
+                }
+                .verify(&RSA_PKCS1_2048_8192_SHA256, signed.as_bytes(), &signature)
+                .is_ok()
+            });
+        match is_verified {
+            Some(true) => {}
+            Some(false) => return Err(AuthError::InvalidToken),
+            None => {
+                self.refresh_keys_in_background();
+                return Err(AuthError::InvalidToken);
+            }
+        }
+        let claims: Claims = decode_json(encoded_claims)?;
+        let now = Utc::now().timestamp();
+        let is_valid = claims.iss == self.config.issuer
+            && claims.aud.contains(&self.config.audience)
+            && claims.exp.saturating_add(LEEWAY) > now
+            && claims
+                .nbf
+                .is_none_or(|nbf| nbf.saturating_sub(LEEWAY) <= now);
+        if !is_valid {
+            return Err(AuthError::InvalidToken);
+        }
+        let groups_claim = self
+            .config
+            .groups_claim
+            .as_deref()
+            .unwrap_or(DEFAULT_GROUPS_CLAIM);
+        let groups = claims
+            .other
+            .get(groups_claim)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(ToOwned::to_owned)
+            .collect();
+        Ok(groups)
+    }
+}
+
+/// Decode a base64url encoded JSON part of a token.
+fn decode_json<T: DeserializeOwned>(encoded: &str) -> Result<T, AuthError> {
+    let decoded = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_err| AuthError::InvalidToken)?;
+    serde_json::from_slice(&decoded).map_err(|_err| AuthError::InvalidToken)
+}
+
+/// The admin the request `req` was authorized for by [`Authenticator::authorize`], for endpoints
+/// only served to authenticated admins.
+///
+/// # Errors
+/// Errors if the request was not authorized as an admin, e.g. because `[auth]` is not configured.
+pub fn require_admin(req: &HttpRequest) -> Result<Principal, AuthError> {
+    req.extensions()
+        .get::<Principal>()
+        .filter(|principal| principal.role == Role::Admin)
+        .cloned()
+        .ok_or(AuthError::NotConfigured)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::auth::{require_admin, AuthError, Authenticator, Jwk, Jwks, Principal};
+    use crate::stelae::archive::{Auth, Role};
+    use actix_web::test::TestRequest;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine as _;
+    use chrono::Utc;
+    use ring::rand::SystemRandom;
+    use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Modulus of the test key at `tests/fixtures/oidc/test_key.pk8`.
+    const MODULUS: &str = "pefS65HhvK4Y3T8G5KF7fLf3cgopkgkAsjIx79f5FonG16jW6pDnEhitD63E7MMro0iVosvi7bzhC2apo0tYs0-SmLDNOWVIUjnkMqYQvsqW1leZl_8NEaISy7XR6tvu1EwaKiN-xA1tQIkR6u6ClEcPwDVSthMOYsBYmiFW6Nikyqv3yC3w5F5AZK7DFX474OlNXmE3hDKfPs2Dxyc1tH0S4IWCe2FxvTDYnoDUYGzKLUt2xC-hMyGLiEnUhbPJuudjAe4bsuDrwl-ZjwAB_2bCDznxPfid1Epsf1z9rXzoH_EAworHz9MRW6y3benXDBlgE6b5SMJoav2ZUSVfJQ";
+
+    fn authenticator() -> Authenticator {
+        let cut = Authenticator::new(
+            Auth {
+                issuer: "https://login.example.com".to_owned(),
+                audience: "stelae".to_owned(),
+                roles: Some(HashMap::from([("law-admins".to_owned(), Role::Admin)])),
+                ..Auth::default()
+            },
+            "test_org/law".to_owned(),
+            None,
+            vec![],
+        );
+        cut.set_keys(Jwks {
+            keys: vec![Jwk {
+                kid: Some("key-1".to_owned()),
+                kty: "RSA".to_owned(),
+                n: Some(MODULUS.to_owned()),
+                e: Some("AQAB".to_owned()),
+            }],
+        });
+        cut
+    }
+
+    fn sign(kid: &str, claims: &serde_json::Value) -> String {
+        let key = include_bytes!("../../tests/fixtures/oidc/test_key.pk8");
+        let key_pair = RsaKeyPair::from_pkcs8(key).unwrap();
+        let header = json!({"alg": "RS256", "typ": "JWT", "kid": kid});
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                signed.as_bytes(),
+                &mut signature,
+            )
+            .unwrap();
+        format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn claims(audience: &str, expires_in: i64) -> serde_json::Value {
+        json!({
+            "iss": "https://login.example.com",
+            "aud": [audience, "other"],
+            "exp": Utc::now().timestamp() + expires_in,
+            "groups": ["law-admins", "staff"],
+        })
+    }
+
+    #[test]
+    fn test_verify_when_valid_token_expect_groups() {
+        let cut = authenticator();
+        let token = sign("key-1", &claims("stelae", 300));
+        let actual = cut.verify(&token).unwrap();
+        assert_eq!(actual, vec!["law-admins".to_owned(), "staff".to_owned()]);
+    }
+
+    #[test]
+    fn test_verify_when_expired_or_wrong_audience_or_tampered_expect_invalid() {
+        let cut = authenticator();
+        let expired = sign("key-1", &claims("stelae", -300));
+        let wrong_audience = sign("key-1", &claims("another-client", 300));
+        let valid = sign("key-1", &claims("stelae", 300));
+        let (signed, _) = valid.rsplit_once('.').unwrap();
+        let tampered = format!("{signed}.{}", URL_SAFE_NO_PAD.encode([0_u8; 256]));
+        for token in [expired, wrong_audience, tampered, "not.a.token".to_owned()] {
+            assert!(matches!(cut.verify(&token), Err(AuthError::InvalidToken)));
+        }
+    }
+
+    #[test]
+    fn test_require_admin_when_authorized_expect_principal_of_stele() {
+        let cut = require_admin;
+        let authenticator = authenticator();
+        let token = sign("key-1", &claims("stelae", 300));
+        let authorized = TestRequest::post()
+            .uri("/%5Fadmin/pin")
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_srv_request();
+        authenticator.authorize(&authorized).unwrap();
+        let expected = Principal {
+            role: Role::Admin,
+            stele: "test_org/law".to_owned(),
+        };
+        assert_eq!(cut(authorized.request()).unwrap(), expected);
+        let unauthenticated = TestRequest::post().uri("/_admin/pin").to_http_request();
+        assert!(matches!(
+            cut(&unauthenticated),
+            Err(AuthError::NotConfigured)
+        ));
+    }
+
+    #[test]
+    fn test_required_role_when_admin_or_guarded_documents_expect_role() {
+        let cut = authenticator();
+        assert_eq!(cut.required_role("/_admin/pin"), Some(Role::Admin));
+        assert_eq!(cut.required_role("/_metrics"), Some(Role::Admin));
+        assert_eq!(cut.required_role("/us/ca/"), None);
+        let guarded = Authenticator::new(
+            Auth {
+                guard_current_documents: Some(true),
+                ..Auth::default()
+            },
+            "test_org/law".to_owned(),
+            None,
+            vec![],
+        );
+        assert_eq!(guarded.required_role("/us/ca/"), Some(Role::Reader));
+        assert_eq!(guarded.required_role("/_api/versions/"), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Serve the archive under a base path, e.g. at `https://example.org/laws/`.
+//!
+//! With `--base-path /laws`, every route is served under `/laws`: the base path is stripped from
+//! requests before they are routed, so handlers see the same paths as when served at the root.
+//! Root-relative urls in responses are prefixed with the base path by the handlers, in headers,
+//! in the urls of json responses and in served html documents.
+use crate::utils::html::{is_root_relative, mount_root_relative_urls};
+use crate::utils::paths::normalize_path;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::Uri;
+use actix_web::{HttpMessage as _, HttpRequest};
+
+/// Path the archive is served under, e.g. `/laws`, empty when served at the root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BasePath(String);
+
+impl BasePath {
+    /// Parse the base `path`, e.g. `/laws` or `laws/`. `/` is the root.
+    ///
+    /// # Errors
+    /// Errors if the path is not a valid url path, see [`normalize_path`].
+    pub fn parse(path: &str) -> anyhow::Result<Self> {
+        let normalized = normalize_path(path)
+            .map_err(|err| anyhow::anyhow!("invalid base path {path}: {err}"))?;
+        if normalized.is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(Self(format!("/{normalized}")))
+    }
+
+    /// The base path, e.g. `/laws`, empty when served at the root.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the archive is served at the root.
+    #[must_use]
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Base path the request `req` was served under, see [`BasePath::mount`].
+    #[must_use]
+    pub fn of(req: &HttpRequest) -> Self {
+        req.extensions().get::<Self>().cloned().unwrap_or_default()
+    }
+
+    /// Prefix the `url` with the base path if it is root-relative, e.g. `/a/b` to `/laws/a/b`.
+    #[must_use]
+    pub fn url(&self, url: &str) -> String {
+        if is_root_relative(url) {
+            return format!("{}{url}", self.0);
+        }
+        url.to_owned()
+    }
+
+    /// Prefix the root-relative urls of the `html` document with the base path.
+    ///
+    /// Returns `html` unchanged if it cannot be rewritten.
+    #[must_use]
+    pub fn html(&self, html: Vec<u8>) -> Vec<u8> {
+        if self.is_root() {
+            return html;
+        }
+        mount_root_relative_urls(&html, &self.0).unwrap_or_else(|err| {
+            tracing::warn!("Unable to prefix urls with base path {}: {err}", self.0);
+            html
+        })
+    }
+
+    /// Strip the base path from the path of the request `req`, before it is routed.
+    ///
+    /// Returns whether the request is under the base path. The base path is recorded in the
+    /// request, see [`BasePath::of`].
+    pub fn mount(&self, req: &mut ServiceRequest) -> bool {
+        if self.is_root() {
+            return true;
+        }
+        let Some(path) = self.strip(req.path()) else {
+            return false;
+        };
+        let path_and_query = match req.query_string() {
+            "" => path.to_owned(),
+            query => format!("{path}?{query}"),
+        };
+        let Ok(uri) = path_and_query.parse::<Uri>() else {
+            return false;
+        };
+        req.match_info_mut().get_mut().update(&uri);
+        req.head_mut().uri = uri;
+        req.extensions_mut().insert(self.clone());
+        true
+    }
+
+    /// Strip the base path from the request `path`, e.g. `/laws/a/b` to `/a/b`.
+    ///
+    /// Returns `None` if the path is not under the base path.
+    fn strip<'path>(&self, path: &'path str) -> Option<&'path str> {
+        match path.strip_prefix(self.0.as_str())? {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::base_path::BasePath;
+
+    #[test]
+    fn test_parse_when_slashes_expect_leading_slash_only() {
+        let cut = BasePath::parse;
+        assert_eq!(cut("laws/").unwrap().as_str(), "/laws");
+        assert_eq!(cut("/a/b").unwrap().as_str(), "/a/b");
+        assert!(cut("/").unwrap().is_root());
+        assert!(cut("/a/../b").is_err());
+    }
+
+    #[test]
+    fn test_strip_when_under_base_path_expect_stripped() {
+        let base_path = BasePath::parse("/laws").unwrap();
+        let cut = |path| base_path.strip(path);
+        assert_eq!(cut("/laws"), Some("/"));
+        assert_eq!(cut("/laws/"), Some("/"));
+        assert_eq!(cut("/laws/_api/versions/a"), Some("/_api/versions/a"));
+        assert_eq!(cut("/lawsuits/a"), None);
+        assert_eq!(cut("/a"), None);
+    }
+
+    #[test]
+    fn test_url_when_root_relative_expect_prefixed() {
+        let base_path = BasePath::parse("/laws").unwrap();
+        let cut = |url| base_path.url(url);
+        assert_eq!(cut("/_date/2023-10-22/a"), "/laws/_date/2023-10-22/a");
+        assert_eq!(cut("//cdn.example.com/a"), "//cdn.example.com/a");
+        assert_eq!(cut("https://example.com/a"), "https://example.com/a");
+    }
+}
@@ -0,0 +1,304 @@
+//! Replay an access log against a local Stelae archive and report request latencies.
+//!
+//! Requests are served in-process by the same app `stelae serve` runs, without a network
+//! in between, so the latencies measure blob lookup, rewriting and database queries only.
+//! The report also counts the lookups of materialized paths the cache saved the database.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db;
+use crate::history::views::ViewCounter;
+use crate::server::api::identifiers::Identifiers;
+use crate::server::api::routes::Routes;
+use crate::server::api::state::App as AppState;
+use crate::server::api::takedown::Takedowns;
+use crate::server::app;
+use crate::server::base_path::BasePath;
+use crate::server::cache::{Cache, Lookups};
+use crate::server::errors::CliError;
+use crate::server::load_shedding::LoadShedder;
+use crate::server::scheduler::Updates;
+use crate::stelae::archive::Archive;
+use actix_http::Request;
+use actix_service::{IntoServiceFactory as _, Service, ServiceFactory as _};
+use actix_web::dev::{AppConfig, ServiceResponse};
+use actix_web::http::{Method, Uri};
+use actix_web::Error;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A request read from an access log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedRequest {
+    /// `GET` or `HEAD`.
+    pub method: Method,
+    /// Path and query of the request, e.g. `/us/ca/cities/san-mateo/`.
+    pub path: String,
+}
+
+/// Latencies of the replayed requests.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Latency of every replayed request, sorted ascending.
+    pub latencies: Vec<Duration>,
+    /// Number of responses per status code.
+    pub statuses: BTreeMap<u16, usize>,
+    /// Lookups of materialized paths served from the cache and queried from the database.
+    pub mpath_lookups: Lookups,
+}
+
+impl Report {
+    /// Mean latency of the requests.
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        let total: Duration = self.latencies.iter().sum();
+        u32::try_from(self.latencies.len())
+            .ok()
+            .and_then(|count| total.checked_div(count))
+            .unwrap_or_default()
+    }
+
+    /// The latency under which `percent` of the requests were served.
+    #[must_use]
+    pub fn percentile(&self, percent: usize) -> Duration {
+        let rank = (self.latencies.len() * percent).div_ceil(100);
+        self.latencies
+            .get(rank.saturating_sub(1))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Replay the `GET` and `HEAD` requests of the access log at `log_file` `repeat` times,
+/// and write a latency report to stdout.
+///
+/// # Errors
+/// Errors if the database or the archive cannot be opened, or if the access log cannot be read.
+pub async fn replay(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    log_file: &Path,
+    repeat: usize,
+) -> Result<(), CliError> {
+    let requests = read_requests(log_file)?;
+    let state = init_state(raw_archive_path, archive_path).await?;
+    let service = match app::init(&state, Routes::All) {
+        Ok(initialized) => initialized
+            .into_factory()
+            .new_service(AppConfig::default())
+            .await
+            .map_err(|()| {
+                tracing::error!("Unable to start app.");
+                CliError::GenericError
+            })?,
+        Err(err) => {
+            tracing::error!("Unable to initialize app.");
+            tracing::error!("Error: {err:?}");
+            return Err(CliError::GenericError);
+        }
+    };
+
+    let mut report = replay_requests(&service, &requests, repeat).await;
+    report.latencies.sort_unstable();
+    report.mpath_lookups = state.cache.mpath_lookups();
+    write_report(&report).map_err(|err| {
+        tracing::error!("Unable to write report: {err}");
+        CliError::GenericError
+    })
+}
+
+/// Replay the `requests` `repeat` times against the `service`, and report their latencies and
+/// statuses.
+async fn replay_requests<S, B>(service: &S, requests: &[LoggedRequest], repeat: usize) -> Report
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+{
+    tracing::info!("Replaying {} requests {repeat} time(s)", requests.len());
+    let mut report = Report::default();
+    for _ in 0..repeat {
+        for request in requests {
+            let Ok(uri) = request.path.parse::<Uri>() else {
+                tracing::warn!("Skipping request of invalid path {}", request.path);
+                continue;
+            };
+            let mut replayed = Request::new();
+            replayed.head_mut().method = request.method.clone();
+            replayed.head_mut().uri = uri;
+            let start = Instant::now();
+            let status = service.call(replayed).await.map_or_else(
+                |err| err.as_response_error().status_code(),
+                |response| response.status(),
+            );
+            report.latencies.push(start.elapsed());
+            *report.statuses.entry(status.as_u16()).or_default() += 1;
+        }
+    }
+    report
+}
+
+/// The `GET` and `HEAD` requests of the access log at `log_file`.
+///
+/// # Errors
+/// Errors if the access log cannot be read, or has no `GET` or `HEAD` requests.
+fn read_requests(log_file: &Path) -> Result<Vec<LoggedRequest>, CliError> {
+    let requests = match fs::read_to_string(log_file) {
+        Ok(log) => log.lines().filter_map(parse_log_line).collect::<Vec<_>>(),
+        Err(err) => {
+            tracing::error!("Unable to read access log '{}': {err}", log_file.display());
+            return Err(CliError::GenericError);
+        }
+    };
+    if requests.is_empty() {
+        tracing::error!("No GET or HEAD requests found in '{}'", log_file.display());
+        return Err(CliError::GenericError);
+    }
+    Ok(requests)
+}
+
+/// The state of the app `stelae serve` runs on the archive at `archive_path`.
+///
+/// # Errors
+/// Errors if the database or the archive cannot be opened.
+async fn init_state(raw_archive_path: &str, archive_path: PathBuf) -> Result<AppState, CliError> {
+    let shared = db::init::connect(&archive_path).await.map_err(|err| {
+        tracing::error!("Error: {err:?}");
+        CliError::DatabaseConnectionError
+    })?;
+    let archive =
+        Archive::parse(archive_path, &PathBuf::from(raw_archive_path), false).map_err(|err| {
+            tracing::error!("Unable to parse archive at '{raw_archive_path}'.");
+            tracing::error!("Error: {err:?}");
+            CliError::ArchiveParseError
+        })?;
+    let config = archive.get_config().map_err(|err| {
+        tracing::error!("Unable to read config of archive at '{raw_archive_path}'.");
+        tracing::error!("Error: {err:?}");
+        CliError::ConfigError
+    })?;
+    let db = db::init::connect_stelae(&archive.path, archive.stelae.keys(), shared)
+        .await
+        .map_err(|err| {
+            tracing::error!("Error: {err:?}");
+            CliError::DatabaseConnectionError
+        })?;
+    let takedowns = Takedowns::load(db.shared()).await.map_err(|err| {
+        tracing::error!("Unable to load takedowns.");
+        tracing::error!("Error: {err:?}");
+        CliError::DatabaseConnectionError
+    })?;
+    let identifiers = Identifiers::load(&db).await.map_err(|err| {
+        tracing::error!("Unable to load identifiers.");
+        tracing::error!("Error: {err:?}");
+        CliError::DatabaseConnectionError
+    })?;
+    let authenticator = app::init_authenticator(&archive).map_err(|err| {
+        tracing::error!("Unable to initialize authentication.");
+        tracing::error!("Error: {err:?}");
+        CliError::GenericError
+    })?;
+    Ok(AppState {
+        archive,
+        db,
+        cache: Cache::default(),
+        identifiers,
+        locales: config.locales.unwrap_or_default(),
+        takedowns,
+        watermarks: config.watermarks.unwrap_or_default(),
+        updates: Updates::default(),
+        base_path: BasePath::default(),
+        views: ViewCounter::default(),
+        authenticator,
+        load_shedder: LoadShedder::new(&config.concurrency.unwrap_or_default()),
+    })
+}
+
+/// Parse a request from a line of an access log.
+///
+/// Lines in the Common or Combined Log Format are parsed for the method and path of their
+/// request line, e.g. `"GET /us/ca/ HTTP/1.1"`. Lines starting with `/` are taken as `GET`
+/// requests of that path. Returns `None` for any other line, and for methods other than
+/// `GET` and `HEAD`.
+#[must_use]
+pub fn parse_log_line(line: &str) -> Option<LoggedRequest> {
+    let trimmed = line.trim();
+    if trimmed.starts_with('/') {
+        return Some(LoggedRequest {
+            method: Method::GET,
+            path: trimmed.to_owned(),
+        });
+    }
+    let (_, request_line) = trimmed.split_once('"')?;
+    let mut parts = request_line.split_whitespace();
+    let method = match parts.next()? {
+        "GET" => Method::GET,
+        "HEAD" => Method::HEAD,
+        _ => return None,
+    };
+    let path = parts.next().filter(|path| path.starts_with('/'))?;
+    Some(LoggedRequest {
+        method,
+        path: path.trim_end_matches('"').to_owned(),
+    })
+}
+
+/// Write the latency report to stdout.
+fn write_report(report: &Report) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "requests: {}", report.latencies.len())?;
+    for (status, count) in &report.statuses {
+        writeln!(stdout, "status {status}: {count}")?;
+    }
+    writeln!(
+        stdout,
+        "mpath lookups: {} cached, {} queried",
+        report.mpath_lookups.hits, report.mpath_lookups.misses
+    )?;
+    writeln!(stdout, "mean: {}us", report.mean().as_micros())?;
+    for percent in [50, 90, 99, 100] {
+        writeln!(
+            stdout,
+            "p{percent}: {}us",
+            report.percentile(percent).as_micros()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::bench::{parse_log_line, Report};
+    use actix_web::http::Method;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_log_line_when_combined_log_format_expect_request() {
+        let cut = parse_log_line;
+        let line = r#"127.0.0.1 - - [10/Oct/2023:13:55:36 +0000] "HEAD /us/ca/codes/1.01/?a=b HTTP/1.1" 200 2326 "-" "curl/8.0""#;
+        let actual = cut(line).unwrap();
+        assert_eq!(actual.method, Method::HEAD);
+        assert_eq!(actual.path, "/us/ca/codes/1.01/?a=b");
+        assert_eq!(cut("/us/ca/").unwrap().path, "/us/ca/");
+        assert_eq!(
+            cut(r#"1.2.3.4 - - [x] "POST /_admin/pin HTTP/1.1" 201 0"#),
+            None
+        );
+        assert_eq!(cut("not a request"), None);
+    }
+
+    #[test]
+    fn test_percentile_when_latencies_expect_nearest_rank() {
+        let cut = Report {
+            latencies: (1..=10).map(Duration::from_millis).collect(),
+            ..Report::default()
+        };
+        assert_eq!(cut.percentile(50), Duration::from_millis(5));
+        assert_eq!(cut.percentile(99), Duration::from_millis(10));
+        assert_eq!(cut.mean(), Duration::from_micros(5500));
+        assert_eq!(Report::default().percentile(50), Duration::ZERO);
+    }
+}
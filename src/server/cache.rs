@@ -0,0 +1,828 @@
+//! Cache of the results resolved ahead of traffic by the warmup.
+//!
+//! `stelae serve --warmup` resolves the current publications, common version queries and hot
+//! document blobs into the cache before accepting traffic, see [`crate::server::warmup`].
+//! Publications and versions are served from the cache until they are older than the maximum age
+//! configured under `[warmup]`, so an update is picked up at the latest once they expire, or as
+//! soon as the cache is [refreshed](Cache::refresh) after a scheduled update. Blobs
+//! are keyed by the `HEAD` commit they were read from, which is remembered for [`HEAD_MAX_AGE`],
+//! so they are served stale for at most that long after an update.
+//!
+//! The ids of the current blobs recorded by `stelae update` when `[current_index]` is enabled are
+//! loaded into the cache at startup, so current documents are read by id instead of walking the
+//! trees of the `HEAD` commit. Like blobs, they are keyed by the `HEAD` commit they were indexed at.
+//!
+//! The materialized paths of the documents and collections the versions are looked up by are
+//! cached as they are resolved by requests, not only by the warmup. They do not change while a
+//! publication is served, so they are keyed by publication, and kept until the cache is full or
+//! is [refreshed](Cache::refresh) after an update.
+//!
+//! The paths of the documents found in the alternate formats of a current document are cached by
+//! repository, keyed by the `HEAD` commit they were looked up at, so alternate links are not
+//! looked up in the trees of every alternate repository on every request.
+//!
+//! The Subresource Integrity manifests of the static assets of the current documents are cached
+//! by stele, keyed by the `HEAD` commits they were computed at, so they are never served stale.
+//!
+//! Lookups of current blobs and materialized paths that found nothing are remembered for
+//! [`NOT_FOUND_MAX_AGE`], so repeated requests for missing documents, e.g. by scrapers, do not
+//! walk the trees of the `HEAD` commit or query the database again.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::db::models::publication::Publication;
+use crate::server::api::versions::response::VersionList;
+use crate::utils::git::BLOB_PATH_POSTFIXES;
+use crate::utils::http::Sha256Digest;
+
+/// Maximum number of materialized paths cached, after which the first inserted is evicted.
+pub const MAX_MPATHS: usize = 10_000;
+
+/// Maximum number of documents whose paths are cached per alternate repository.
+pub const MAX_ALTERNATE_PATHS: usize = 10_000;
+
+/// Time the `HEAD` commit of a repository is remembered for, so current blobs served from the
+/// cache do not open the repository on every request.
+pub const HEAD_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// Time a lookup that found nothing is remembered for.
+pub const NOT_FOUND_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Maximum number of lookups that found nothing remembered, per kind of lookup.
+pub const MAX_NOT_FOUND: usize = 10_000;
+
+/// Commits served instead of withheld `HEAD` commits, keyed by repository.
+///
+/// The `HEAD` commit of a data repository is withheld while it is not served yet, e.g. only
+/// recorded in preview publications or in publications awaiting approval. `None` if no served
+/// commit of the repository is recorded.
+pub type WithheldHeads = HashMap<String, Option<String>>;
+
+/// Results resolved by the warmup, shared by all workers.
+#[derive(Debug, Clone, Default)]
+pub struct Cache(Arc<RwLock<Entries>>);
+
+/// Entries of the cache.
+#[derive(Debug, Default)]
+struct Entries {
+    /// Paths of the current documents in alternate formats, keyed by repository.
+    alternate_paths: HashMap<String, AlternatePaths>,
+    /// Time publications and versions are served for.
+    max_age: Duration,
+    /// Non-revoked publications, newest first, keyed by stele.
+    publications: HashMap<String, Timed<Vec<Publication>>>,
+    /// Versions of documents and collections, keyed by publication id and url.
+    versions: HashMap<(String, String), Timed<VersionList>>,
+    /// Current blobs, keyed by repository and normalized path.
+    blobs: HashMap<(String, String), Blob>,
+    /// Ids of the current blobs, keyed by repository.
+    blob_ids: HashMap<String, BlobIds>,
+    /// Ids of the `HEAD` commits of the repositories, keyed by repository.
+    heads: HashMap<String, Timed<String>>,
+    /// Current layout templates, keyed by repository.
+    layouts: HashMap<String, Layout>,
+    /// Materialized paths of documents and collections, keyed by stele, publication id and url.
+    mpaths: HashMap<(String, String, String), Mpath>,
+    /// Keys of the materialized paths, in the order they were inserted in.
+    mpath_order: VecDeque<(String, String, String)>,
+    /// Number of materialized paths served from the cache.
+    mpath_hits: AtomicU64,
+    /// Number of materialized paths not cached, which are queried from the database.
+    mpath_misses: AtomicU64,
+    /// Subresource Integrity manifests of static assets, with the `HEAD` commits of the served
+    /// data repositories they were computed at, keyed by stele.
+    asset_integrity: HashMap<String, (String, BTreeMap<String, String>)>,
+    /// Current blobs not found, keyed by repository, `HEAD` commit and normalized path.
+    missing_blobs: NotFound,
+    /// Materialized paths not found, keyed by stele, publication id and url.
+    missing_mpaths: NotFound,
+    /// Commits served instead of withheld `HEAD` commits, keyed by stele and whether previews
+    /// are served.
+    withheld_heads: HashMap<(String, bool), Timed<WithheldHeads>>,
+}
+
+/// Materialized path of the document or collection at a url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mpath {
+    /// Materialized path of a document.
+    Document(String),
+    /// Materialized path of a collection.
+    Collection(String),
+}
+
+/// Number of lookups of materialized paths served from the cache and from the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lookups {
+    /// Lookups served from the cache.
+    pub hits: u64,
+    /// Lookups queried from the database.
+    pub misses: u64,
+}
+
+/// Number of lookups that found nothing answered from the cache, by kind of lookup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NotFoundHits {
+    /// Lookups of current blobs.
+    pub blobs: u64,
+    /// Lookups of materialized paths.
+    pub mpaths: u64,
+}
+
+/// Lookups that found nothing, remembered for [`NOT_FOUND_MAX_AGE`].
+#[derive(Debug, Default)]
+struct NotFound {
+    /// Times the lookups found nothing at, keyed by lookup.
+    found_at: HashMap<(String, String, String), Instant>,
+    /// Keys of the lookups, in the order they were inserted in.
+    order: VecDeque<(String, String, String)>,
+    /// Number of lookups answered as not found from the cache.
+    hits: AtomicU64,
+}
+
+/// A cached value, with the time it was resolved at.
+#[derive(Debug)]
+struct Timed<T> {
+    /// The cached value.
+    value: T,
+    /// Time the value was resolved at.
+    resolved_at: Instant,
+}
+
+/// Paths of the documents found at the `HEAD` commit of an alternate repository.
+#[derive(Debug)]
+struct AlternatePaths {
+    /// Id of the `HEAD` commit the documents were looked up at.
+    commit: String,
+    /// Path of the blob of each document, or `None` if the repository lacks it, keyed by stem.
+    paths: HashMap<String, Option<String>>,
+}
+
+/// A cached current blob.
+#[derive(Debug)]
+struct Blob {
+    /// Id of the `HEAD` commit the blob was read from.
+    commit: String,
+    /// Content of the blob.
+    content: Vec<u8>,
+    /// SHA-256 of the content, hashed once when the blob is cached.
+    digest: Sha256Digest,
+}
+
+/// Ids of the blobs of the `HEAD` commit of a repository.
+#[derive(Debug)]
+struct BlobIds {
+    /// Id of the `HEAD` commit the blobs were indexed at.
+    commit: String,
+    /// Ids of the blobs, keyed by path.
+    ids: HashMap<String, String>,
+}
+
+/// The current layout template of a repository.
+#[derive(Debug)]
+struct Layout {
+    /// Id of the `HEAD` commit the template was read from.
+    commit: String,
+    /// The layout template.
+    template: String,
+}
+
+impl<T> Timed<T> {
+    /// Wrap a `value` resolved now.
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            resolved_at: Instant::now(),
+        }
+    }
+}
+
+impl<T: Clone> Timed<T> {
+    /// The value, if it was resolved less than `max_age` ago.
+    fn fresh(&self, max_age: Duration) -> Option<T> {
+        (self.resolved_at.elapsed() < max_age).then(|| self.value.clone())
+    }
+}
+
+impl NotFound {
+    /// Forget every lookup.
+    fn clear(&mut self) {
+        self.found_at.clear();
+        self.order.clear();
+    }
+
+    /// Whether the lookup `key` found nothing less than [`NOT_FOUND_MAX_AGE`] ago, counted as a hit.
+    fn contains(&self, key: &(String, String, String)) -> bool {
+        let remembered = self
+            .found_at
+            .get(key)
+            .is_some_and(|found_at| found_at.elapsed() < NOT_FOUND_MAX_AGE);
+        if remembered {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        remembered
+    }
+
+    /// Remember the lookup `key` found nothing now, forgetting the first inserted lookups while
+    /// they are expired or [`MAX_NOT_FOUND`] are remembered.
+    fn insert(&mut self, key: (String, String, String)) {
+        let now = Instant::now();
+        if let Some(found_at) = self.found_at.get_mut(&key) {
+            *found_at = now;
+            return;
+        }
+        while let Some(first_key) = self.order.front() {
+            let expired = self
+                .found_at
+                .get(first_key)
+                .is_none_or(|found_at| found_at.elapsed() >= NOT_FOUND_MAX_AGE);
+            if !expired && self.found_at.len() < MAX_NOT_FOUND {
+                break;
+            }
+            if let Some(forgotten) = self.order.pop_front() {
+                self.found_at.remove(&forgotten);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.found_at.insert(key, now);
+    }
+}
+
+impl Cache {
+    /// Create an empty cache serving publications and versions for `max_age`.
+    #[must_use]
+    pub fn new(max_age: Duration) -> Self {
+        Self(Arc::new(RwLock::new(Entries {
+            max_age,
+            ..Entries::default()
+        })))
+    }
+
+    /// The non-revoked publications of the `stele`, newest first, if warmed and fresh.
+    #[must_use]
+    pub fn publications(&self, stele: &str) -> Option<Vec<Publication>> {
+        let entries = self.0.read().ok()?;
+        entries.publications.get(stele)?.fresh(entries.max_age)
+    }
+
+    /// Cache the non-revoked `publications` of the `stele`.
+    pub fn insert_publications(&self, stele: String, publications: Vec<Publication>) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.publications.insert(stele, Timed::new(publications));
+        }
+    }
+
+    /// The versions of the document or collection at `url` in the publication, if warmed and fresh.
+    #[must_use]
+    pub fn versions(&self, publication_id: &str, url: &str) -> Option<VersionList> {
+        let entries = self.0.read().ok()?;
+        entries
+            .versions
+            .get(&(publication_id.to_owned(), url.to_owned()))?
+            .fresh(entries.max_age)
+    }
+
+    /// Cache the `versions` of the document or collection at `url` in the publication.
+    pub fn insert_versions(&self, publication_id: String, url: String, versions: VersionList) {
+        if let Ok(mut entries) = self.0.write() {
+            entries
+                .versions
+                .insert((publication_id, url), Timed::new(versions));
+        }
+    }
+
+    /// Whether any blobs were warmed.
+    #[must_use]
+    pub fn has_blobs(&self) -> bool {
+        self.0.read().is_ok_and(|entries| !entries.blobs.is_empty())
+    }
+
+    /// The blob at the normalized `path` of the `repository` and its SHA-256, if warmed from its
+    /// `commit`.
+    #[must_use]
+    pub fn blob(
+        &self,
+        repository: &str,
+        commit: &str,
+        path: &str,
+    ) -> Option<(Vec<u8>, Sha256Digest)> {
+        self.0
+            .read()
+            .ok()?
+            .blobs
+            .get(&(repository.to_owned(), path.to_owned()))
+            .filter(|blob| blob.commit == commit)
+            .map(|blob| (blob.content.clone(), blob.digest))
+    }
+
+    /// Cache the `content` of the blob at the normalized `path` of the `repository` in its `commit`,
+    /// with its SHA-256.
+    pub fn insert_blob(&self, repository: String, commit: String, path: String, content: Vec<u8>) {
+        let digest = Sha256Digest::of(&content);
+        if let Ok(mut entries) = self.0.write() {
+            entries.blobs.insert(
+                (repository, path),
+                Blob {
+                    commit,
+                    content,
+                    digest,
+                },
+            );
+        }
+    }
+
+    /// Whether the ids of current blobs were loaded.
+    #[must_use]
+    pub fn has_blob_ids(&self) -> bool {
+        self.0
+            .read()
+            .is_ok_and(|entries| !entries.blob_ids.is_empty())
+    }
+
+    /// The id of the `HEAD` commit of the `repository`, if resolved less than [`HEAD_MAX_AGE`] ago.
+    #[must_use]
+    pub fn head(&self, repository: &str) -> Option<String> {
+        self.0
+            .read()
+            .ok()?
+            .heads
+            .get(repository)?
+            .fresh(HEAD_MAX_AGE)
+    }
+
+    /// Remember the id of the `HEAD` `commit` of the `repository`.
+    pub fn insert_head(&self, repository: String, commit: String) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.heads.insert(repository, Timed::new(commit));
+        }
+    }
+
+    /// The id of the blob at the normalized `path` of the `repository`, if indexed at its `commit`.
+    ///
+    /// The path is looked up with the postfixes documents are found with in git, e.g. `/index.html`.
+    #[must_use]
+    pub fn blob_id(&self, repository: &str, commit: &str, path: &str) -> Option<String> {
+        self.0
+            .read()
+            .ok()?
+            .blob_ids
+            .get(repository)
+            .filter(|indexed| indexed.commit == commit)
+            .and_then(|indexed| {
+                BLOB_PATH_POSTFIXES
+                    .iter()
+                    .find_map(|postfix| indexed.ids.get(&format!("{path}{postfix}")))
+                    .cloned()
+            })
+    }
+
+    /// Cache the `ids` of the blobs of the `repository`, keyed by path, indexed at its `commit`.
+    pub fn insert_blob_ids(
+        &self,
+        repository: String,
+        commit: String,
+        ids: HashMap<String, String>,
+    ) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.blob_ids.insert(repository, BlobIds { commit, ids });
+        }
+    }
+
+    /// The layout template of the `repository`, if read from its `commit`.
+    #[must_use]
+    pub fn layout(&self, repository: &str, commit: &str) -> Option<String> {
+        self.0
+            .read()
+            .ok()?
+            .layouts
+            .get(repository)
+            .filter(|layout| layout.commit == commit)
+            .map(|layout| layout.template.clone())
+    }
+
+    /// Cache the layout `template` of the `repository` read from its `commit`.
+    pub fn insert_layout(&self, repository: String, commit: String, template: String) {
+        if let Ok(mut entries) = self.0.write() {
+            entries
+                .layouts
+                .insert(repository, Layout { commit, template });
+        }
+    }
+
+    /// The path of the blob of the document `stem` in the alternate `repository`, if looked up at
+    /// its `commit`; `Some(None)` if the repository lacks the document.
+    #[must_use]
+    pub fn alternate_path(
+        &self,
+        repository: &str,
+        commit: &str,
+        stem: &str,
+    ) -> Option<Option<String>> {
+        self.0
+            .read()
+            .ok()?
+            .alternate_paths
+            .get(repository)
+            .filter(|found| found.commit == commit)
+            .and_then(|found| found.paths.get(stem).cloned())
+    }
+
+    /// Cache the path of the blob of the document `stem` in the alternate `repository` looked up
+    /// at its `commit`, or `None` if the repository lacks it.
+    ///
+    /// Paths looked up at an earlier commit are dropped. Once [`MAX_ALTERNATE_PATHS`] documents
+    /// are cached for the commit, further ones are not.
+    pub fn insert_alternate_path(
+        &self,
+        repository: String,
+        commit: String,
+        stem: String,
+        blob_path: Option<String>,
+    ) {
+        let Ok(mut entries) = self.0.write() else {
+            return;
+        };
+        let found = entries
+            .alternate_paths
+            .entry(repository)
+            .or_insert_with(|| AlternatePaths {
+                commit: commit.clone(),
+                paths: HashMap::new(),
+            });
+        if found.commit != commit {
+            found.commit = commit;
+            found.paths.clear();
+        }
+        if found.paths.len() < MAX_ALTERNATE_PATHS {
+            found.paths.insert(stem, blob_path);
+        }
+    }
+
+    /// The materialized path of the document or collection at `url` in the publication of the
+    /// `stele`, if resolved before.
+    #[must_use]
+    pub fn mpath(&self, stele: &str, publication_id: &str, url: &str) -> Option<Mpath> {
+        let entries = self.0.read().ok()?;
+        let mpath = entries
+            .mpaths
+            .get(&(stele.to_owned(), publication_id.to_owned(), url.to_owned()))
+            .cloned();
+        let counter = if mpath.is_some() {
+            &entries.mpath_hits
+        } else {
+            &entries.mpath_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        mpath
+    }
+
+    /// Cache the `mpath` of the document or collection at `url` in the publication of the
+    /// `stele`, evicting the first inserted materialized path if [`MAX_MPATHS`] are cached.
+    pub fn insert_mpath(&self, stele: String, publication_id: String, url: String, mpath: Mpath) {
+        let Ok(mut entries) = self.0.write() else {
+            return;
+        };
+        let key = (stele, publication_id, url);
+        if !entries.mpaths.contains_key(&key) {
+            if entries.mpaths.len() >= MAX_MPATHS {
+                if let Some(first_key) = entries.mpath_order.pop_front() {
+                    entries.mpaths.remove(&first_key);
+                }
+            }
+            entries.mpath_order.push_back(key.clone());
+        }
+        entries.mpaths.insert(key, mpath);
+    }
+
+    /// Number of lookups of materialized paths served from the cache and from the database.
+    #[must_use]
+    pub fn mpath_lookups(&self) -> Lookups {
+        self.0.read().map_or_else(
+            |_err| Lookups::default(),
+            |entries| Lookups {
+                hits: entries.mpath_hits.load(Ordering::Relaxed),
+                misses: entries.mpath_misses.load(Ordering::Relaxed),
+            },
+        )
+    }
+
+    /// The Subresource Integrity manifest of the static assets of the `stele`, if computed at the
+    /// `HEAD` commits `heads` of its served data repositories.
+    #[must_use]
+    pub fn asset_integrity(&self, stele: &str, heads: &str) -> Option<BTreeMap<String, String>> {
+        self.0
+            .read()
+            .ok()?
+            .asset_integrity
+            .get(stele)
+            .filter(|cached| cached.0 == heads)
+            .map(|cached| cached.1.clone())
+    }
+
+    /// Cache the Subresource Integrity `manifest` of the static assets of the `stele`, computed at
+    /// the `HEAD` commits `heads` of its served data repositories.
+    pub fn insert_asset_integrity(
+        &self,
+        stele: String,
+        heads: String,
+        manifest: BTreeMap<String, String>,
+    ) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.asset_integrity.insert(stele, (heads, manifest));
+        }
+    }
+
+    /// Whether the blob at `path` of the `commit` of the `repository` was not found recently.
+    #[must_use]
+    pub fn is_missing_blob(&self, repository: &str, commit: &str, path: &str) -> bool {
+        self.0.read().is_ok_and(|entries| {
+            entries.missing_blobs.contains(&(
+                repository.to_owned(),
+                commit.to_owned(),
+                path.to_owned(),
+            ))
+        })
+    }
+
+    /// Remember the blob at `path` of the `commit` of the `repository` was not found.
+    pub fn insert_missing_blob(&self, repository: String, commit: String, path: String) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.missing_blobs.insert((repository, commit, path));
+        }
+    }
+
+    /// Whether the materialized path of `url` in the publication of the `stele` was not found
+    /// recently.
+    #[must_use]
+    pub fn is_missing_mpath(&self, stele: &str, publication_id: &str, url: &str) -> bool {
+        self.0.read().is_ok_and(|entries| {
+            entries.missing_mpaths.contains(&(
+                stele.to_owned(),
+                publication_id.to_owned(),
+                url.to_owned(),
+            ))
+        })
+    }
+
+    /// Remember the materialized path of `url` in the publication of the `stele` was not found.
+    pub fn insert_missing_mpath(&self, stele: String, publication_id: String, url: String) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.missing_mpaths.insert((stele, publication_id, url));
+        }
+    }
+
+    /// Number of lookups that found nothing answered from the cache.
+    #[must_use]
+    pub fn not_found_hits(&self) -> NotFoundHits {
+        self.0.read().map_or_else(
+            |_err| NotFoundHits::default(),
+            |entries| NotFoundHits {
+                blobs: entries.missing_blobs.hits.load(Ordering::Relaxed),
+                mpaths: entries.missing_mpaths.hits.load(Ordering::Relaxed),
+            },
+        )
+    }
+
+    /// Forget the results an update of the archive may change: the publications, the versions,
+    /// the `HEAD` commits and the materialized paths, found and not found.
+    ///
+    /// Results keyed by the `HEAD` commit they were read at are not forgotten, as they are not
+    /// served once the `HEAD` commit is looked up again.
+    pub fn refresh(&self) {
+        if let Ok(mut entries) = self.0.write() {
+            entries.publications.clear();
+            entries.versions.clear();
+            entries.heads.clear();
+            entries.withheld_heads.clear();
+            entries.mpaths.clear();
+            entries.mpath_order.clear();
+            entries.missing_mpaths.clear();
+        }
+    }
+
+    /// Remember the commits served instead of the `withheld` `HEAD` commits of the data
+    /// repositories of the `stele`, to requests that may see `previews` or not.
+    pub fn insert_withheld_heads(&self, stele: String, previews: bool, withheld: WithheldHeads) {
+        if let Ok(mut entries) = self.0.write() {
+            entries
+                .withheld_heads
+                .insert((stele, previews), Timed::new(withheld));
+        }
+    }
+
+    /// The commits served instead of the withheld `HEAD` commits of the data repositories of the
+    /// `stele` to requests that may see `previews` or not, if resolved less than [`HEAD_MAX_AGE`]
+    /// ago.
+    #[must_use]
+    pub fn withheld_heads(&self, stele: &str, previews: bool) -> Option<WithheldHeads> {
+        self.0
+            .read()
+            .ok()?
+            .withheld_heads
+            .get(&(stele.to_owned(), previews))?
+            .fresh(HEAD_MAX_AGE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::api::versions::response::VersionList;
+    use crate::server::cache::{Cache, Lookups, Mpath, NotFoundHits, MAX_MPATHS, MAX_NOT_FOUND};
+    use crate::utils::http::Sha256Digest;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_versions_when_older_than_max_age_expect_none() {
+        let cut = Cache::new(Duration::ZERO);
+        cut.insert_versions("pb".to_owned(), "a/b".to_owned(), VersionList::default());
+        assert!(cut.versions("pb", "a/b").is_none());
+
+        let cut = Cache::new(Duration::from_secs(60));
+        cut.insert_versions("pb".to_owned(), "a/b".to_owned(), VersionList::default());
+        assert!(cut.versions("pb", "a/b").is_some());
+        assert!(cut.versions("pb", "a/c").is_none());
+    }
+
+    #[test]
+    fn test_blob_when_head_moved_expect_none() {
+        let cut = Cache::default();
+        assert!(!cut.has_blobs());
+        cut.insert_blob(
+            "org/law-html".to_owned(),
+            "abc".to_owned(),
+            "a/b".to_owned(),
+            b"<p>b</p>".to_vec(),
+        );
+        assert!(cut.has_blobs());
+        assert_eq!(
+            cut.blob("org/law-html", "abc", "a/b"),
+            Some((b"<p>b</p>".to_vec(), Sha256Digest::of(b"<p>b</p>")))
+        );
+        assert_eq!(cut.blob("org/law-html", "def", "a/b"), None);
+    }
+
+    #[test]
+    fn test_alternate_path_when_head_moved_expect_none() {
+        let cut = Cache::default();
+        let (repository, stem) = ("org/law-xml", "a/b/c");
+        cut.insert_alternate_path(
+            repository.to_owned(),
+            "abc".to_owned(),
+            stem.to_owned(),
+            Some("a/b/c.xml".to_owned()),
+        );
+        cut.insert_alternate_path(
+            repository.to_owned(),
+            "abc".to_owned(),
+            "a/b/d".to_owned(),
+            None,
+        );
+        assert_eq!(
+            cut.alternate_path(repository, "abc", stem),
+            Some(Some("a/b/c.xml".to_owned()))
+        );
+        assert_eq!(cut.alternate_path(repository, "abc", "a/b/d"), Some(None));
+        assert_eq!(cut.alternate_path(repository, "abc", "a/b/e"), None);
+        cut.insert_alternate_path(
+            repository.to_owned(),
+            "def".to_owned(),
+            "a/b/d".to_owned(),
+            None,
+        );
+        assert_eq!(cut.alternate_path(repository, "def", stem), None);
+        assert_eq!(cut.alternate_path(repository, "abc", stem), None);
+    }
+
+    #[test]
+    fn test_head_when_inserted_expect_commit_of_repository() {
+        let cut = Cache::default();
+        cut.insert_head("org/law-html".to_owned(), "abc".to_owned());
+        assert_eq!(cut.head("org/law-html").as_deref(), Some("abc"));
+        assert!(cut.head("org/law-xml").is_none());
+    }
+
+    #[test]
+    fn test_layout_when_head_moved_expect_none() {
+        let cut = Cache::default();
+        cut.insert_layout(
+            "org/law-html".to_owned(),
+            "abc".to_owned(),
+            "<main>{{ content }}</main>".to_owned(),
+        );
+        assert_eq!(
+            cut.layout("org/law-html", "abc").as_deref(),
+            Some("<main>{{ content }}</main>")
+        );
+        assert_eq!(cut.layout("org/law-html", "def"), None);
+        assert_eq!(cut.layout("org/law-xml", "abc"), None);
+    }
+
+    #[test]
+    fn test_blob_id_when_index_document_expect_id_of_index() {
+        let cut = Cache::default();
+        assert!(!cut.has_blob_ids());
+        cut.insert_blob_ids(
+            "org/law-html".to_owned(),
+            "abc".to_owned(),
+            HashMap::from([("a/b/index.html".to_owned(), "0f2f1ef".to_owned())]),
+        );
+        assert!(cut.has_blob_ids());
+        assert_eq!(
+            cut.blob_id("org/law-html", "abc", "a/b"),
+            Some("0f2f1ef".to_owned())
+        );
+        assert_eq!(cut.blob_id("org/law-html", "abc", "a/c"), None);
+        assert_eq!(cut.blob_id("org/law-html", "def", "a/b"), None);
+    }
+
+    #[test]
+    fn test_mpath_when_full_expect_oldest_evicted_and_lookups_counted() {
+        let cut = Cache::new(Duration::ZERO);
+        for idx in 0..=MAX_MPATHS {
+            cut.insert_mpath(
+                "org/law".to_owned(),
+                "pb".to_owned(),
+                format!("/{idx}"),
+                Mpath::Document(idx.to_string()),
+            );
+        }
+        assert_eq!(cut.mpath("org/law", "pb", "/0"), None);
+        assert_eq!(
+            cut.mpath("org/law", "pb", &format!("/{MAX_MPATHS}")),
+            Some(Mpath::Document(MAX_MPATHS.to_string()))
+        );
+        assert_eq!(cut.mpath("org/law", "other", "/1"), None);
+        assert_eq!(cut.mpath_lookups(), Lookups { hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn test_is_missing_blob_when_remembered_expect_hit_counted() {
+        let cut = Cache::default();
+        assert!(!cut.is_missing_blob("org/law-html", "abc", "a/b"));
+        cut.insert_missing_blob(
+            "org/law-html".to_owned(),
+            "abc".to_owned(),
+            "a/b".to_owned(),
+        );
+        assert!(cut.is_missing_blob("org/law-html", "abc", "a/b"));
+        assert!(!cut.is_missing_blob("org/law-html", "def", "a/b"));
+        assert!(!cut.is_missing_mpath("org/law", "pb", "a/b"));
+        assert_eq!(
+            cut.not_found_hits(),
+            NotFoundHits {
+                blobs: 1,
+                mpaths: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_missing_mpath_when_full_expect_oldest_forgotten() {
+        let cut = Cache::default();
+        for idx in 0..=MAX_NOT_FOUND {
+            cut.insert_missing_mpath("org/law".to_owned(), "pb".to_owned(), format!("/{idx}"));
+        }
+        assert!(!cut.is_missing_mpath("org/law", "pb", "/0"));
+        assert!(cut.is_missing_mpath("org/law", "pb", &format!("/{MAX_NOT_FOUND}")));
+    }
+
+    #[test]
+    fn test_insert_missing_mpath_when_inserted_again_expect_remembered_once() {
+        let cut = Cache::default();
+        for _ in 0..=MAX_NOT_FOUND {
+            cut.insert_missing_mpath("org/law".to_owned(), "pb".to_owned(), "/a".to_owned());
+        }
+        cut.insert_missing_mpath("org/law".to_owned(), "pb".to_owned(), "/b".to_owned());
+        assert!(cut.is_missing_mpath("org/law", "pb", "/a"));
+        assert!(cut.is_missing_mpath("org/law", "pb", "/b"));
+    }
+
+    #[test]
+    fn test_refresh_when_mpaths_cached_expect_mpaths_forgotten() {
+        let cut = Cache::default();
+        cut.insert_mpath(
+            "org/law".to_owned(),
+            "pb".to_owned(),
+            "/a".to_owned(),
+            Mpath::Document("1".to_owned()),
+        );
+        cut.insert_missing_mpath("org/law".to_owned(), "pb".to_owned(), "/b".to_owned());
+        cut.refresh();
+        assert_eq!(cut.mpath("org/law", "pb", "/a"), None);
+        assert!(!cut.is_missing_mpath("org/law", "pb", "/b"));
+    }
+
+    #[test]
+    fn test_refresh_when_publications_and_versions_cached_expect_forgotten() {
+        let cut = Cache::new(Duration::from_secs(60));
+        cut.insert_publications("org/law".to_owned(), vec![]);
+        cut.insert_versions("pb".to_owned(), "a/b".to_owned(), VersionList::default());
+        cut.insert_head("org/law-html".to_owned(), "abc".to_owned());
+        cut.refresh();
+        assert!(cut.publications("org/law").is_none());
+        assert!(cut.versions("pb", "a/b").is_none());
+        assert!(cut.head("org/law-html").is_none());
+    }
+}
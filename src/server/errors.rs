@@ -5,6 +5,7 @@
     reason = "derive_more doesn't respect these lints"
 )]
 
+use crate::utils::http::respond_text;
 use actix_web::{error, http::StatusCode, HttpResponse};
 use derive_more::{Display, Error};
 use std::io;
@@ -21,7 +22,21 @@ pub enum HTTPError {
 }
 
 /// Collection of possible CLI errors
-#[derive(Debug, Display, Error)]
+///
+/// The CLI exits with the [`CliError::exit_code`] of the error, so scripts can branch on the type
+/// of failure:
+///
+/// | Exit code | Error                                  |
+/// |-----------|----------------------------------------|
+/// | 0         | Success                                |
+/// | 1         | [`CliError::GenericError`]             |
+/// | 2         | Invalid command line arguments         |
+/// | 3         | [`CliError::ConfigError`]              |
+/// | 4         | [`CliError::ArchiveParseError`]        |
+/// | 5         | [`CliError::DatabaseConnectionError`]  |
+/// | 6         | [`CliError::VerificationFailure`]      |
+/// | 7         | [`CliError::PartialSuccess`]           |
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq)]
 pub enum CliError {
     /// Database connection error
     #[display(fmt = "Failed to connect to the database")]
@@ -32,6 +47,32 @@ pub enum CliError {
     /// Errors during archive parsing
     #[display(fmt = "Failed to parse the archive ")]
     ArchiveParseError,
+    /// The configuration of the archive, `.taf/config.toml`, cannot be read
+    #[display(fmt = "Failed to read the configuration of the archive")]
+    ConfigError,
+    /// The archive does not match what it is verified against, e.g. a manifest
+    #[display(fmt = "The archive failed verification")]
+    VerificationFailure,
+    /// The command succeeded for some stelae, and failed for others
+    #[display(fmt = "The command succeeded only for some stelae")]
+    PartialSuccess,
+}
+
+impl CliError {
+    /// Exit code of the CLI for the error.
+    ///
+    /// Exit code `2` is left to invalid command line arguments, which `clap` exits with.
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        match *self {
+            Self::GenericError => 1,
+            Self::ConfigError => 3,
+            Self::ArchiveParseError => 4,
+            Self::DatabaseConnectionError => 5,
+            Self::VerificationFailure => 6,
+            Self::PartialSuccess => 7,
+        }
+    }
 }
 
 impl From<io::Error> for CliError {
@@ -51,7 +92,7 @@ pub enum StelaeError {
 #[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
 impl error::ResponseError for StelaeError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        respond_text(HttpResponse::build(self.status_code()), self.to_string())
     }
 
     fn status_code(&self) -> StatusCode {
@@ -60,3 +101,30 @@ impl error::ResponseError for StelaeError {
         }
     }
 }
+
+#[cfg(test)]
+#[expect(
+    clippy::default_numeric_fallback,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::server::errors::CliError;
+
+    #[test]
+    fn test_exit_code_when_cli_errors_expect_distinct_non_zero_codes() {
+        let errors = [
+            CliError::GenericError,
+            CliError::ConfigError,
+            CliError::ArchiveParseError,
+            CliError::DatabaseConnectionError,
+            CliError::VerificationFailure,
+            CliError::PartialSuccess,
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(CliError::exit_code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+        assert!(!codes.contains(&2));
+    }
+}
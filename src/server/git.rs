@@ -1,19 +1,37 @@
 //! Legacy git microserver.
+#![expect(
+    clippy::future_not_send,
+    reason = "Actix handlers taking `HttpRequest` are not `Send`"
+)]
 
-use actix_web::{get, route, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    get, http::header, post, route, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use anyhow::Context as _;
 use git2::{self, ErrorCode};
-use std::path::PathBuf;
+use serde_derive::Deserialize;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
 use tracing_actix_web::TracingLogger;
 
 use super::errors::{CliError, HTTPError, StelaeError};
+use crate::history::mirror;
+use crate::stelae::archive::read_config;
 use crate::utils::git::{Repo, GIT_REQUEST_NOT_FOUND};
-use crate::utils::http::get_contenttype;
-use crate::{server::tracing::StelaeRootSpanBuilder, utils::paths::clean_path};
+use crate::utils::http::{get_contenttype, respond_blob, secrets_match};
+use crate::{
+    server::tracing::StelaeRootSpanBuilder,
+    utils::paths::{is_archive_component, normalize_path},
+};
 
 /// Global, read-only state passed into the actix app
 struct AppState {
     /// path to the Stelae archive
     archive_path: PathBuf,
+    /// Token of the mirrors, see [`crate::stelae::archive::Mirror`]. Mirrors are refused when unset.
+    mirror_token: Option<String>,
 }
 
 /// Root index path
@@ -31,6 +49,166 @@ async fn misc(path: web::Path<String>) -> actix_web::Result<&'static str, Stelae
     }
 }
 
+/// List the repositories of the archive and the commits of their branches, for `stelae mirror`.
+#[get("/_sync")]
+#[tracing::instrument(name = "Listing repositories for mirrors", skip(req, data))]
+async fn sync(req: HttpRequest, data: web::Data<AppState>) -> impl Responder {
+    if let Some(refused) = refuse_mirror(&req, &data) {
+        return refused;
+    }
+    match mirror::build_manifest(&data.archive_path) {
+        Ok(manifest) => HttpResponse::Ok().json(manifest),
+        Err(err) => {
+            tracing::error!("Unable to list repositories: {err:?}");
+            HttpResponse::InternalServerError().body(HTTPError::InternalServerError.to_string())
+        }
+    }
+}
+
+/// Query string of the git smart HTTP reference discovery request.
+#[derive(Deserialize)]
+struct InfoRefsQuery {
+    /// The git service requested by the client.
+    service: String,
+}
+
+/// Git smart HTTP reference discovery of the `{namespace}/{name}` repo.
+///
+/// Only `git-upload-pack` is served, so repositories can be fetched but never pushed to.
+#[get("/{namespace}/{name}/info/refs")]
+#[tracing::instrument(name = "Advertising Git references", skip(req, path, query, data))]
+async fn info_refs(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<InfoRefsQuery>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Some(refused) = refuse_mirror(&req, &data) {
+        return refused;
+    }
+    let (namespace, name) = path.into_inner();
+    if query.service != "git-upload-pack" {
+        return HttpResponse::Forbidden().body("only git-upload-pack is supported");
+    }
+    let Some(repo_path) = find_repo_path(&data.archive_path, &namespace, &name) else {
+        return HttpResponse::NotFound().body(format!("repo {namespace}/{name} does not exist"));
+    };
+    let output = web::block(move || upload_pack(&repo_path, true, &[])).await;
+    match output {
+        Ok(Ok(refs)) => {
+            let mut body = b"001e# service=git-upload-pack\n0000".to_vec();
+            body.extend(refs);
+            HttpResponse::Ok()
+                .content_type("application/x-git-upload-pack-advertisement")
+                .insert_header(("Cache-Control", "no-cache"))
+                .body(body)
+        }
+        Ok(Err(err)) => upload_pack_error_response(&err),
+        Err(err) => upload_pack_error_response(&err.into()),
+    }
+}
+
+/// Git smart HTTP `git-upload-pack` request of the `{namespace}/{name}` repo.
+#[post("/{namespace}/{name}/git-upload-pack")]
+#[tracing::instrument(name = "Uploading Git pack", skip(req, path, body, data))]
+async fn git_upload_pack(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Some(refused) = refuse_mirror(&req, &data) {
+        return refused;
+    }
+    let (namespace, name) = path.into_inner();
+    let Some(repo_path) = find_repo_path(&data.archive_path, &namespace, &name) else {
+        return HttpResponse::NotFound().body(format!("repo {namespace}/{name} does not exist"));
+    };
+    let output = web::block(move || upload_pack(&repo_path, false, &body)).await;
+    match output {
+        Ok(Ok(pack)) => HttpResponse::Ok()
+            .content_type("application/x-git-upload-pack-result")
+            .insert_header(("Cache-Control", "no-cache"))
+            .body(pack),
+        Ok(Err(err)) => upload_pack_error_response(&err),
+        Err(err) => upload_pack_error_response(&err.into()),
+    }
+}
+
+/// Response refusing the mirror request `req`, unless it carries the mirror token of the archive
+/// as an `Authorization: Bearer` header.
+///
+/// Mirroring is refused with `403 Forbidden` if the archive configures no mirror token.
+fn refuse_mirror(req: &HttpRequest, data: &AppState) -> Option<HttpResponse> {
+    let Some(token) = data.mirror_token.as_deref() else {
+        return Some(HttpResponse::Forbidden().body("mirroring is not enabled"));
+    };
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    (!secrets_match(token, given)).then(|| {
+        HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .body("missing or invalid mirror token")
+    })
+}
+
+/// Path of the `{namespace}/{name}` repo in the archive, if it is a git repository.
+fn find_repo_path(archive_path: &Path, namespace: &str, name: &str) -> Option<PathBuf> {
+    if !is_archive_component(namespace) || !is_archive_component(name) {
+        return None;
+    }
+    let repo_path = archive_path.join(namespace).join(name);
+    git2::Repository::open(&repo_path).ok().map(|_| repo_path)
+}
+
+/// Run `git upload-pack` in stateless RPC mode on the repository, with `input` on stdin.
+///
+/// The input is written from a thread of its own while the output is read, so neither side
+/// blocks on a full pipe.
+fn upload_pack(repo_path: &Path, advertise_refs: bool, input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut command = Command::new("git");
+    command.args(["upload-pack", "--stateless-rpc"]);
+    if advertise_refs {
+        command.arg("--advertise-refs");
+    }
+    let mut child = command
+        .arg(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().context("git upload-pack has no stdin")?;
+    let (written, waited) = thread::scope(|scope| {
+        // stdin is closed once written, so git sees the end of the input.
+        let writer = scope.spawn(move || stdin.write_all(input));
+        let waited = child.wait_with_output();
+        (writer.join(), waited)
+    });
+    let output = waited?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git upload-pack failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    written
+        .map_err(|panic| {
+            anyhow::anyhow!("writing the input of git upload-pack panicked: {panic:?}")
+        })?
+        .context("could not write the input of git upload-pack")?;
+    Ok(output.stdout)
+}
+
+/// Log a failed `git upload-pack` and return a safe user-facing error response.
+fn upload_pack_error_response(error: &anyhow::Error) -> HttpResponse {
+    tracing::error!("{error}");
+    HttpResponse::InternalServerError().body(HTTPError::InternalServerError.to_string())
+}
+
 /// Return the content in the stelae archive in the `{namespace}/{name}`
 /// repo at the `commitish` commit at the `remainder` path.
 /// Return 404 if any are not found or there are any errors.
@@ -45,12 +223,15 @@ async fn get_blob(
     data: web::Data<AppState>,
 ) -> impl Responder {
     let (namespace, name, commitish, remainder) = path.into_inner();
+    let blob_path = match normalize_path(&remainder) {
+        Ok(blob_path) => blob_path,
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
     let archive_path = &data.archive_path;
     let blob = Repo::find_blob(archive_path, &namespace, &name, &remainder, &commitish);
-    let blob_path = clean_path(&remainder);
     let contenttype = get_contenttype(&blob_path);
     match blob {
-        Ok(content) => HttpResponse::Ok().insert_header(contenttype).body(content),
+        Ok(content) => respond_blob(HttpResponse::Ok(), contenttype.0, content),
         Err(error) => blob_error_response(&error, &namespace, &name),
     }
 }
@@ -88,6 +269,14 @@ pub async fn serve_git(
     archive_path: PathBuf,
     port: u16,
 ) -> Result<(), CliError> {
+    let mirror_token = read_config(&archive_path)
+        .ok()
+        .and_then(|config| config.mirror)
+        .map(|mirror| mirror.token)
+        .filter(|token| !token.is_empty());
+    if mirror_token.is_none() {
+        tracing::warn!("No [mirror] token is configured, mirrors are refused");
+    }
     let bind = "127.0.0.1";
     let message = "Serving content from the Stelae archive at";
     tracing::info!("{message} '{raw_archive_path}' on http://{bind}:{port}.",);
@@ -96,10 +285,14 @@ pub async fn serve_git(
         App::new()
             .wrap(TracingLogger::<StelaeRootSpanBuilder>::new())
             .service(index)
+            .service(sync)
             .service(misc)
+            .service(info_refs)
+            .service(git_upload_pack)
             .service(get_blob)
             .app_data(web::Data::new(AppState {
                 archive_path: archive_path.clone(),
+                mirror_token: mirror_token.clone(),
             }))
     })
     .bind((bind, port))?
@@ -110,3 +303,68 @@ pub async fn serve_git(
         CliError::GenericError
     })
 }
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::git::{sync, upload_pack, AppState};
+    use actix_web::{http::StatusCode, test, web, App};
+
+    async fn sync_status(mirror_token: Option<&str>, authorization: Option<&str>) -> StatusCode {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(App::new().service(sync).app_data(web::Data::new(AppState {
+            archive_path: archive_dir.path().to_path_buf(),
+            mirror_token: mirror_token.map(ToOwned::to_owned),
+        })))
+        .await;
+        let mut req = test::TestRequest::get().uri("/_sync");
+        if let Some(value) = authorization {
+            req = req.insert_header(("Authorization", value));
+        }
+        test::call_service(&app, req.to_request()).await.status()
+    }
+
+    #[actix_web::test]
+    async fn test_sync_when_token_missing_or_wrong_expect_refused() {
+        assert_eq!(
+            sync_status(None, Some("Bearer secret")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            sync_status(Some("secret"), None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            sync_status(Some("secret"), Some("Bearer other")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_ne!(
+            sync_status(Some("secret"), Some("Bearer secret")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_upload_pack_when_acks_exceed_pipe_buffer_expect_every_ack() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let signature = git2::Signature::now("Stelae", "stelae@example.org").unwrap();
+        let head = repo
+            .commit(Some("HEAD"), &signature, &signature, "Empty", &tree, &[])
+            .unwrap();
+        // every have is acknowledged while the request is read, filling the stdout pipe.
+        let mut input = format!("0045want {head} multi_ack_detailed\n0000");
+        input.push_str(&format!("0032have {head}\n").repeat(4_096));
+        input.push_str("0009done\n");
+
+        let cut = upload_pack;
+        let actual = cut(dir.path(), false, input.as_bytes()).unwrap();
+        let acks = String::from_utf8_lossy(&actual)
+            .matches(&format!("ACK {head} common"))
+            .count();
+        assert_eq!(acks, 4_096);
+    }
+}
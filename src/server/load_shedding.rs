@@ -0,0 +1,132 @@
+//! Shed load by capping the number of concurrent requests per endpoint class.
+//!
+//! Cheap document requests and expensive API requests are counted separately, so a burst
+//! of versions comparisons cannot starve document serving, and vice versa.
+use crate::stelae::archive::Concurrency;
+use crate::utils::http::respond_text;
+use actix_web::http::header;
+use actix_web::{error, Error, HttpResponse};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Class of an endpoint, by the cost of serving a request to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointClass {
+    /// Documents and snapshots, served by a single blob lookup.
+    Static,
+    /// `/_api` and `/_admin` endpoints, which walk git history or query the database.
+    Expensive,
+}
+
+impl EndpointClass {
+    /// Classify a request by its `path`.
+    #[must_use]
+    pub fn of(path: &str) -> Self {
+        if path.starts_with("/_api/") || path.starts_with("/_admin/") {
+            Self::Expensive
+        } else {
+            Self::Static
+        }
+    }
+}
+
+/// Counts in-flight requests per endpoint class, and rejects requests over the limit.
+#[derive(Debug, Clone)]
+pub struct LoadShedder {
+    /// Maximum number of concurrent static requests.
+    max_static: usize,
+    /// Maximum number of concurrent expensive requests.
+    max_expensive: usize,
+    /// Seconds a client is asked to wait before retrying.
+    retry_after: u64,
+    /// Number of in-flight static requests.
+    in_flight_static: Arc<AtomicUsize>,
+    /// Number of in-flight expensive requests.
+    in_flight_expensive: Arc<AtomicUsize>,
+}
+
+impl Default for LoadShedder {
+    fn default() -> Self {
+        Self::new(&Concurrency::default())
+    }
+}
+
+/// An admitted request. The request is counted as in flight until this is dropped.
+#[derive(Debug)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl LoadShedder {
+    /// Create a load shedder with the configured limits.
+    #[must_use]
+    pub fn new(concurrency: &Concurrency) -> Self {
+        Self {
+            max_static: concurrency.max_static_requests(),
+            max_expensive: concurrency.max_expensive_requests(),
+            retry_after: concurrency.retry_after(),
+            in_flight_static: Arc::new(AtomicUsize::new(0)),
+            in_flight_expensive: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Admit a request of endpoint class `class`.
+    ///
+    /// # Errors
+    /// Errors with `503 Service Unavailable` and a `Retry-After` header if the limit of the class is reached.
+    pub fn admit(&self, class: EndpointClass) -> Result<InFlight, Error> {
+        let (in_flight, max) = match class {
+            EndpointClass::Static => (&self.in_flight_static, self.max_static),
+            EndpointClass::Expensive => (&self.in_flight_expensive, self.max_expensive),
+        };
+        in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .map(|_| InFlight(Arc::clone(in_flight)))
+            .map_err(|count| {
+                tracing::warn!("Shedding {class:?} request, {count} requests in flight");
+                let mut builder = HttpResponse::ServiceUnavailable();
+                builder.insert_header((header::RETRY_AFTER, self.retry_after));
+                let response = respond_text(builder, "Server is overloaded, retry later");
+                error::InternalError::from_response("Server is overloaded", response).into()
+            })
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::load_shedding::{EndpointClass, LoadShedder};
+    use crate::stelae::archive::Concurrency;
+
+    #[test]
+    fn test_endpoint_class_of_when_api_or_admin_expect_expensive() {
+        let cut = EndpointClass::of;
+        assert_eq!(cut("/_api/versions/a/b"), EndpointClass::Expensive);
+        assert_eq!(cut("/_admin/broken-links"), EndpointClass::Expensive);
+        assert_eq!(cut("/_snapshot/v1/a/b"), EndpointClass::Static);
+        assert_eq!(cut("/us/ca/_api/"), EndpointClass::Static);
+    }
+
+    #[test]
+    fn test_admit_when_limit_reached_expect_rejected_until_released() {
+        let cut = LoadShedder::new(&Concurrency {
+            max_expensive_requests: Some(1),
+            ..Concurrency::default()
+        });
+        let first = cut.admit(EndpointClass::Expensive).unwrap();
+        let rejected = cut.admit(EndpointClass::Expensive).unwrap_err();
+        assert_eq!(
+            rejected.as_response_error().status_code(),
+            actix_web::http::StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert!(cut.admit(EndpointClass::Static).is_ok());
+        drop(first);
+        assert!(cut.admit(EndpointClass::Expensive).is_ok());
+    }
+}
@@ -2,8 +2,19 @@
 //!
 //! Currently contains only a git microserver.
 
+pub mod access_log;
 pub mod api;
 pub mod app;
+pub mod auth;
+pub mod base_path;
+pub mod bench;
+pub mod cache;
 pub mod errors;
 pub mod git;
+pub mod load_shedding;
+pub mod proxy;
+pub mod scheduler;
+pub mod startup;
+pub mod timing;
 pub mod tracing;
+pub mod warmup;
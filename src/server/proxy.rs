@@ -0,0 +1,326 @@
+//! Derive the client and the origin of requests received through reverse proxies.
+//!
+//! `X-Forwarded-*` headers are only honored on requests from trusted proxies, see
+//! [`Proxy`]. Requests from any other peer are described by their connection alone, so clients
+//! cannot spoof their address in the access log, or the host of canonical links.
+use crate::stelae::archive::Proxy;
+use actix_web::http::{header, uri::Authority, Uri};
+use actix_web::{HttpMessage as _, HttpRequest};
+use std::net::IpAddr;
+
+/// Header listing the client and the proxies a request was forwarded through.
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+/// Header carrying the scheme the client requested.
+const X_FORWARDED_PROTO: &str = "X-Forwarded-Proto";
+/// Header carrying the host the client requested.
+const X_FORWARDED_HOST: &str = "X-Forwarded-Host";
+/// Trusted proxy matching peers connected over a unix domain socket.
+const UNIX_PEER: &str = "unix";
+
+/// Client and origin of a request, see [`Proxies::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forwarded {
+    /// Address of the client, if known.
+    pub client: Option<IpAddr>,
+    /// Scheme the client requested, e.g. `https`.
+    pub scheme: String,
+    /// Host the client requested, e.g. `laws.example.org`.
+    pub host: String,
+    /// Whether the scheme and host are the configured origin, instead of the requested ones.
+    pub is_configured: bool,
+}
+
+impl Forwarded {
+    /// Client and origin of the request `req`, resolved by [`Proxies::resolve`].
+    ///
+    /// Falls back to the connection of the request if it was not resolved.
+    #[must_use]
+    pub fn of(req: &HttpRequest) -> Self {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_else(|| Proxies::default().resolve(req))
+    }
+
+    /// Scheme and host the client requested, e.g. `https://laws.example.org`.
+    #[must_use]
+    pub fn origin(&self) -> String {
+        format!("{}://{}", self.scheme, self.host)
+    }
+
+    /// Url of the root-relative `path` that does not depend on the host the client requested:
+    /// absolute on the configured origin, e.g. `https://laws.example.org/a/b`, and `path`
+    /// itself if no origin is configured.
+    ///
+    /// Used for urls of responses shared between hosts, e.g. immutable snapshots.
+    #[must_use]
+    pub fn stable_url(&self, path: &str) -> String {
+        if self.is_configured {
+            format!("{}{path}", self.origin())
+        } else {
+            path.to_owned()
+        }
+    }
+}
+
+/// Trusted reverse proxies of an archive.
+#[derive(Debug, Clone, Default)]
+pub struct Proxies {
+    /// Networks of the trusted proxies.
+    trusted: Vec<Network>,
+    /// Whether peers connected over a unix domain socket, which have no address, are trusted.
+    trusted_unix: bool,
+    /// Scheme and host of absolute urls, overriding the requested ones.
+    origin: Option<(String, String)>,
+}
+
+impl Proxies {
+    /// Create the trusted proxies of the proxy `config`.
+    ///
+    /// # Errors
+    /// Errors if a trusted proxy is not an address, a CIDR network or `unix`, or the origin is
+    /// not a scheme and a host.
+    pub fn new(config: &Proxy) -> anyhow::Result<Self> {
+        let (unix, networks): (Vec<_>, Vec<_>) = config
+            .trusted
+            .iter()
+            .flatten()
+            .partition(|&network| network == UNIX_PEER);
+        let trusted = networks
+            .into_iter()
+            .map(|network| Network::parse(network))
+            .collect::<anyhow::Result<_>>()?;
+        let origin = config.origin.as_deref().map(parse_origin).transpose()?;
+        Ok(Self {
+            trusted,
+            trusted_unix: !unix.is_empty(),
+            origin,
+        })
+    }
+
+    /// Resolve the client and the origin of the request `req`.
+    ///
+    /// If the peer is a trusted proxy, the client is the last untrusted address of
+    /// `X-Forwarded-For`, and the scheme and host are taken from `X-Forwarded-Proto` and
+    /// `X-Forwarded-Host`. The configured origin, if any, overrides the scheme and host.
+    #[must_use]
+    pub fn resolve(&self, req: &HttpRequest) -> Forwarded {
+        let peer = req.peer_addr().map(|socket| socket.ip().to_canonical());
+        let trusted_peer = peer.map_or(self.trusted_unix, |ip| self.is_trusted(ip));
+        let forwarded = |name| {
+            trusted_peer
+                .then(|| req.headers().get(name)?.to_str().ok())
+                .flatten()
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let client = if trusted_peer {
+            self.forwarded_client(req).or(peer)
+        } else {
+            peer
+        };
+        let is_configured = self.origin.is_some();
+        let (scheme, host) = self.origin.clone().unwrap_or_else(|| {
+            let scheme = forwarded(X_FORWARDED_PROTO)
+                .or_else(|| req.uri().scheme_str())
+                .unwrap_or_else(|| {
+                    if req.app_config().secure() {
+                        "https"
+                    } else {
+                        "http"
+                    }
+                });
+            let host = forwarded(X_FORWARDED_HOST)
+                .or_else(|| req.headers().get(header::HOST)?.to_str().ok())
+                .or_else(|| req.uri().authority().map(Authority::as_str))
+                .unwrap_or_else(|| req.app_config().host());
+            (scheme.to_owned(), host.to_owned())
+        });
+        Forwarded {
+            client,
+            scheme,
+            host,
+            is_configured,
+        }
+    }
+
+    /// The last address of `X-Forwarded-For` of `req` that is not a trusted proxy.
+    ///
+    /// Addresses are walked from the closest hop, stopping at the first that cannot be parsed.
+    fn forwarded_client(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let mut client = None;
+        for value in req.headers().get_all(X_FORWARDED_FOR).rev() {
+            for hop in value.to_str().ok()?.rsplit(',') {
+                let ip = hop.trim().parse::<IpAddr>().ok()?.to_canonical();
+                client = Some(ip);
+                if !self.is_trusted(ip) {
+                    return client;
+                }
+            }
+        }
+        client
+    }
+
+    /// Whether `ip` is a trusted proxy.
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// A CIDR network, e.g. `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    /// Address of the network.
+    address: IpAddr,
+    /// Length of the network prefix, in bits.
+    prefix: u32,
+}
+
+impl Network {
+    /// Parse the `network`, either an address or a CIDR network, e.g. `10.0.0.0/8`.
+    ///
+    /// # Errors
+    /// Errors if the address or the prefix length is invalid.
+    fn parse(network: &str) -> anyhow::Result<Self> {
+        let (raw_address, raw_prefix) = network.split_once('/').unwrap_or((network, ""));
+        let address = raw_address
+            .parse::<IpAddr>()
+            .map_err(|err| anyhow::anyhow!("invalid trusted proxy {network}: {err}"))?
+            .to_canonical();
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = if raw_prefix.is_empty() {
+            bits
+        } else {
+            raw_prefix
+                .parse::<u32>()
+                .ok()
+                .filter(|&length| length <= bits)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid prefix length of trusted proxy {network}")
+                })?
+        };
+        Ok(Self { address, prefix })
+    }
+
+    /// Whether `ip` is in the network.
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(u32::BITS.saturating_sub(self.prefix))
+                    .unwrap_or_default();
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(u128::BITS.saturating_sub(self.prefix))
+                    .unwrap_or_default();
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse the `origin` of absolute urls, e.g. `https://laws.example.org`, into its scheme and host.
+///
+/// # Errors
+/// Errors if the origin has no scheme or host, or has a path.
+fn parse_origin(origin: &str) -> anyhow::Result<(String, String)> {
+    let uri = origin
+        .parse::<Uri>()
+        .map_err(|err| anyhow::anyhow!("invalid proxy origin {origin}: {err}"))?;
+    let has_path = !matches!(uri.path(), "" | "/") || uri.query().is_some();
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) if !has_path => {
+            Ok((scheme.to_owned(), authority.as_str().to_owned()))
+        }
+        _ => anyhow::bail!("invalid proxy origin {origin}: expected a scheme and a host only"),
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::proxy::{Forwarded, Network, Proxies};
+    use crate::stelae::archive::Proxy;
+    use actix_web::test::TestRequest;
+
+    fn proxies(trusted: &[&str], origin: Option<&str>) -> Proxies {
+        Proxies::new(&Proxy {
+            trusted: Some(trusted.iter().map(|&network| network.to_owned()).collect()),
+            origin: origin.map(str::to_owned),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_network_contains_when_cidr_expect_addresses_in_prefix_only() {
+        let cut = |network| Network::parse(network).unwrap();
+        assert!(cut("10.0.0.0/8").contains("10.1.2.3".parse().unwrap()));
+        assert!(!cut("10.0.0.0/8").contains("11.0.0.1".parse().unwrap()));
+        assert!(cut("127.0.0.1").contains("127.0.0.1".parse().unwrap()));
+        assert!(!cut("127.0.0.1").contains("127.0.0.2".parse().unwrap()));
+        assert!(cut("0.0.0.0/0").contains("203.0.113.7".parse().unwrap()));
+        assert!(cut("2001:db8::/32").contains("2001:db8::1".parse().unwrap()));
+        assert!(!cut("2001:db8::/32").contains("10.1.2.3".parse().unwrap()));
+        assert!(Network::parse("10.0.0.0/33").is_err());
+        assert!(Network::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn test_resolve_when_peer_untrusted_expect_forwarded_headers_ignored() {
+        let req = TestRequest::get()
+            .peer_addr("203.0.113.7:4000".parse().unwrap())
+            .insert_header(("Host", "laws.example.org"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .insert_header(("X-Forwarded-Proto", "https"))
+            .insert_header(("X-Forwarded-Host", "spoofed.example.com"))
+            .to_http_request();
+        let actual = proxies(&["10.0.0.0/8"], None).resolve(&req);
+        let expected = Forwarded {
+            client: Some("203.0.113.7".parse().unwrap()),
+            scheme: "http".to_owned(),
+            host: "laws.example.org".to_owned(),
+            is_configured: false,
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_resolve_when_peer_trusted_expect_last_untrusted_forwarded_client() {
+        let req = TestRequest::get()
+            .peer_addr("10.0.0.2:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7, 10.0.0.1"))
+            .insert_header(("X-Forwarded-Proto", "https"))
+            .insert_header(("X-Forwarded-Host", "laws.example.org"))
+            .to_http_request();
+        let actual = proxies(&["10.0.0.0/8"], None).resolve(&req);
+        let expected = Forwarded {
+            client: Some("203.0.113.7".parse().unwrap()),
+            scheme: "https".to_owned(),
+            host: "laws.example.org".to_owned(),
+            is_configured: false,
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_resolve_when_origin_configured_expect_origin_overrides_host() {
+        let req = TestRequest::get()
+            .peer_addr("10.0.0.2:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-Host", "internal.example.com"))
+            .to_http_request();
+        let actual = proxies(&["10.0.0.0/8"], Some("https://laws.example.org")).resolve(&req);
+        assert_eq!(actual.origin(), "https://laws.example.org");
+        assert_eq!(actual.stable_url("/a/b"), "https://laws.example.org/a/b");
+        let unconfigured = proxies(&["10.0.0.0/8"], None).resolve(&req);
+        assert_eq!(unconfigured.stable_url("/a/b"), "/a/b");
+        assert!(Proxies::new(&Proxy {
+            trusted: None,
+            origin: Some("https://laws.example.org/laws".to_owned()),
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,522 @@
+//! Updates of the archive scheduled by `stelae serve --update-schedule`.
+//!
+//! The schedule is a cron expression of five fields, minute, hour, day of month, month and day
+//! of week, in UTC. Every field is `*`, a number, a range `a-b`, or a list of them separated by
+//! `,`, each optionally with a step `/n`. As in cron, a day matches if either the day of month or
+//! the day of week matches when both are restricted.
+//!
+//! At every scheduled time, the repositories are pulled from the upstream given by
+//! `--update-from`, if any, exactly as `stelae mirror` does, and the changes are inserted into the
+//! database, exactly as `stelae update` does. Updates run one after another: a time that passes
+//! while an update is still running is skipped, and an update fails while another command holds
+//! the [lock](crate::utils::lock) of the archive. The status of the updates is served at
+//! `/_admin/status`. The results the server cached that an update may change are
+//! [refreshed](crate::server::cache::Cache::refresh) after every update, and the current blob
+//! ids [reloaded](warmup::load_current_index). The stelae served and their routes are only
+//! read at startup, so a stele an update adds to or removes from the archive is logged, and
+//! served once the server is restarted.
+//!
+//! The updates run on a thread of their own, so the blocking git calls of an update never stall
+//! the workers serving requests.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::history::changes;
+use crate::history::mirror::{self, Upstream};
+use crate::server::api::state::App as AppState;
+use crate::server::warmup;
+use crate::stelae::archive::Archive;
+use crate::utils::lock;
+use crate::utils::output::Output;
+use actix_web::rt::{task, time, System};
+use chrono::{DateTime, Datelike as _, Days, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+/// Number of days searched for the next time of a schedule, which covers the leap days.
+const SEARCHED_DAYS: u64 = 4 * 366;
+
+/// A cron schedule of the updates, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// The cron expression the schedule was parsed from.
+    expression: String,
+    /// Minutes of the hour, one bit per minute.
+    minutes: u64,
+    /// Hours of the day, one bit per hour.
+    hours: u64,
+    /// Days of the month, one bit per day.
+    days: u64,
+    /// Months of the year, one bit per month.
+    months: u64,
+    /// Days of the week, one bit per day, from Sunday.
+    weekdays: u64,
+    /// Whether both the day of month and the day of week are restricted, so either may match.
+    either_day: bool,
+}
+
+impl Schedule {
+    /// The first time of the schedule after `after`, or `None` if there is none in four years.
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let first_day = after.date_naive();
+        (0..SEARCHED_DAYS)
+            .filter_map(|offset| first_day.checked_add_days(Days::new(offset)))
+            .filter(|&day| self.matches_day(day))
+            .flat_map(|day| {
+                bits(self.hours, 23).flat_map(move |hour| {
+                    bits(self.minutes, 59)
+                        .filter_map(move |minute| day.and_hms_opt(hour, minute, 0))
+                })
+            })
+            .map(|time| time.and_utc())
+            .find(|&time| time > after)
+    }
+
+    /// Whether the schedule runs on `day`.
+    fn matches_day(&self, day: NaiveDate) -> bool {
+        let month = has_bit(self.months, day.month());
+        let day_of_month = has_bit(self.days, day.day());
+        let day_of_week = has_bit(self.weekdays, day.weekday().num_days_from_sunday());
+        month
+            && if self.either_day {
+                day_of_month || day_of_week
+            } else {
+                day_of_month && day_of_week
+            }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let &[minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            anyhow::bail!(
+                "expected 5 fields, minute, hour, day of month, month and day of week, in '{expression}'"
+            );
+        };
+        let sundays = parse_field(weekdays, 0, 7)?;
+        let schedule = Self {
+            expression: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            // Both 0 and 7 are Sunday.
+            weekdays: (sundays | (sundays >> 7)) & 0x7f,
+            either_day: days != "*" && weekdays != "*",
+        };
+        if schedule.next_after(DateTime::UNIX_EPOCH).is_none() {
+            anyhow::bail!("'{expression}' never runs");
+        }
+        Ok(schedule)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.expression)
+    }
+}
+
+/// Parse a `field` of a cron expression with values from `min` to `max` into one bit per value.
+///
+/// # Errors
+/// Errors if the field is not `*`, a number, a range or a list of them, each with an optional
+/// step, or a value is out of range.
+fn parse_field(field: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let one: u64 = 1;
+    let mut mask: u64 = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(parse_value(step)?)),
+            None => (item, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+            // A single value with a step, e.g. `5/15`, runs from the value to the maximum.
+            None => {
+                let value = parse_value(range)?;
+                (value, if step.is_some() { max } else { value })
+            }
+        };
+        if start < min || end > max || start > end {
+            anyhow::bail!("'{item}' is not within {min}-{max}");
+        }
+        let step_size = match step {
+            Some(0) => anyhow::bail!("the step of '{item}' is 0"),
+            Some(found) => usize::try_from(found)?,
+            None => 1,
+        };
+        for value in (start..=end).step_by(step_size) {
+            mask |= one.checked_shl(value).unwrap_or_default();
+        }
+    }
+    Ok(mask)
+}
+
+/// Parse a number of a cron expression.
+///
+/// # Errors
+/// Errors if `value` is not a number.
+fn parse_value(value: &str) -> anyhow::Result<u32> {
+    value
+        .parse()
+        .map_err(|_err| anyhow::anyhow!("'{value}' is not a number"))
+}
+
+/// Whether the bit of `value` is set in `mask`.
+fn has_bit(mask: u64, value: u32) -> bool {
+    mask.checked_shr(value)
+        .is_some_and(|shifted| shifted & 1 == 1)
+}
+
+/// The values from 0 to `max` whose bit is set in `mask`, ascending.
+fn bits(mask: u64, max: u32) -> impl Iterator<Item = u32> {
+    (0..=max).filter(move |&value| has_bit(mask, value))
+}
+
+/// Status of the scheduled updates, shared by the scheduler and all workers.
+#[derive(Debug, Clone, Default)]
+pub struct Updates(Arc<RwLock<Status>>);
+
+/// Status of the scheduled updates, served at `/_admin/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// The cron expression of the scheduled updates, if updates are scheduled.
+    pub update_schedule: Option<String>,
+    /// Whether a scheduled update is running.
+    pub update_running: bool,
+    /// Time the next scheduled update starts at.
+    pub next_update_at: Option<DateTime<Utc>>,
+    /// The last scheduled update that finished, if any.
+    pub last_update: Option<UpdateRun>,
+}
+
+/// A finished scheduled update.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRun {
+    /// Time the update started at.
+    pub started_at: DateTime<Utc>,
+    /// Time the update finished at.
+    pub finished_at: DateTime<Utc>,
+    /// Whether the repositories were pulled and every stele was updated without errors.
+    pub ok: bool,
+    /// Error of the update, if it failed.
+    pub error: Option<String>,
+}
+
+impl Updates {
+    /// The current status of the scheduled updates.
+    #[must_use]
+    pub fn status(&self) -> Status {
+        self.0
+            .read()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    /// Change the status of the scheduled updates with `change`.
+    fn change(&self, change: impl FnOnce(&mut Status)) {
+        if let Ok(mut status) = self.0.write() {
+            change(&mut status);
+        }
+    }
+
+    /// Mark an update as started, unless one is running already.
+    ///
+    /// Returns whether the update may start.
+    fn begin(&self) -> bool {
+        self.0.write().is_ok_and(|mut status| {
+            let idle = !status.update_running;
+            status.update_running = true;
+            idle
+        })
+    }
+}
+
+/// Where scheduled updates pull the repositories from, and how often.
+#[derive(Debug, Clone)]
+pub struct Scheduled {
+    /// Times the updates start at.
+    pub schedule: Schedule,
+    /// Upstream Stelae git server to pull the repositories from, if any.
+    pub upstream: Option<Upstream>,
+}
+
+/// Start updating the archive at every time of the schedule, on a thread of its own.
+///
+/// The cache of the server `state` is refreshed after every update, and the current blob ids
+/// reloaded, see [`warmup::load_current_index`].
+///
+/// # Errors
+/// Errors if the thread of the updates cannot be started.
+pub fn start(
+    scheduled: Scheduled,
+    raw_archive_path: String,
+    archive_path: PathBuf,
+    state: &AppState,
+) -> io::Result<()> {
+    let expression = scheduled.schedule.to_string();
+    state
+        .updates
+        .change(|status| status.update_schedule = Some(expression));
+    let served: BTreeSet<String> = state.archive.stelae.keys().cloned().collect();
+    let (archive, db, cache, updates) = (
+        state.archive.clone(),
+        state.db.clone(),
+        state.cache.clone(),
+        state.updates.clone(),
+    );
+    thread::Builder::new()
+        .name("stelae-scheduler".to_owned())
+        .spawn(move || {
+            System::new().block_on(async move {
+                loop {
+                    let Some(next) = scheduled.schedule.next_after(Utc::now()) else {
+                        tracing::warn!("No more scheduled updates for '{}'", scheduled.schedule);
+                        return;
+                    };
+                    updates.change(|status| status.next_update_at = Some(next));
+                    let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                    time::sleep(wait).await;
+                    run(
+                        &updates,
+                        scheduled.upstream.as_ref(),
+                        &raw_archive_path,
+                        &archive_path,
+                    )
+                    .await;
+                    cache.refresh();
+                    warmup::load_current_index(&cache, &archive, &db).await;
+                    log_changed_stelae(&raw_archive_path, &archive_path, &served);
+                }
+            });
+        })?;
+    Ok(())
+}
+
+/// Log the stelae an update added to or removed from the archive, compared to the `served`
+/// stelae, as they are only served once the server is restarted.
+fn log_changed_stelae(raw_archive_path: &str, archive_path: &Path, served: &BTreeSet<String>) {
+    let archive = match Archive::parse(
+        archive_path.to_path_buf(),
+        Path::new(raw_archive_path),
+        false,
+    ) {
+        Ok(archive) => archive,
+        Err(err) => {
+            tracing::error!("Unable to read the updated archive: {err:?}");
+            return;
+        }
+    };
+    let updated: BTreeSet<String> = archive.stelae.into_keys().collect();
+    for added in updated.difference(served) {
+        tracing::warn!("The update added the stele '{added}', restart the server to serve it");
+    }
+    for removed in served.difference(&updated) {
+        tracing::warn!(
+            "The update removed the stele '{removed}', restart the server to stop serving it"
+        );
+    }
+}
+
+/// Pull the repositories from the `upstream`, if any, and insert the changes into the database,
+/// recording the run in `updates`. Skips the run if an update is running already.
+async fn run(
+    updates: &Updates,
+    upstream: Option<&Upstream>,
+    raw_archive_path: &str,
+    archive_path: &Path,
+) {
+    if !updates.begin() {
+        tracing::warn!("Skipping the scheduled update, the previous update is still running");
+        return;
+    }
+    tracing::info!("Starting the scheduled update");
+    let started_at = Utc::now();
+    let error = update(upstream, raw_archive_path, archive_path)
+        .await
+        .err()
+        .map(|err| format!("{err:#}"));
+    if let Some(err) = error.as_deref() {
+        tracing::error!("Scheduled update failed: {err}");
+    }
+    let finished = UpdateRun {
+        started_at,
+        finished_at: Utc::now(),
+        ok: error.is_none(),
+        error,
+    };
+    updates.change(|status| {
+        status.update_running = false;
+        status.last_update = Some(finished);
+    });
+}
+
+/// Pull the repositories from the `upstream`, if any, and insert the changes into the database.
+///
+/// # Errors
+/// Errors if the archive is locked by another update, the repositories cannot be pulled, or the
+/// update of any stele fails.
+async fn update(
+    upstream: Option<&Upstream>,
+    raw_archive_path: &str,
+    archive_path: &Path,
+) -> anyhow::Result<()> {
+    let _locked = lock::Guard::acquire(archive_path, "serve", false)?;
+    if let Some(from) = upstream {
+        let (path, pulled) = (archive_path.to_path_buf(), from.clone());
+        task::spawn_blocking(move || mirror::sync(&path, &pulled)).await??;
+    }
+    changes::insert(
+        raw_archive_path,
+        archive_path.to_path_buf(),
+        false,
+        false,
+        Output::Text,
+    )
+    .await
+    .map_err(|err| anyhow::anyhow!("could not insert the changes: {err:?}"))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::server::scheduler::{run, Schedule, Updates};
+    use chrono::{DateTime, NaiveDate, Utc};
+
+    fn at(date: &str, hour: u32, minute: u32) -> DateTime<Utc> {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_next_after_when_daily_expect_next_day_after_time_passed() {
+        let cut: Schedule = "0 3 * * *".parse().unwrap();
+        assert_eq!(
+            cut.next_after(at("2024-01-01", 2, 59)),
+            Some(at("2024-01-01", 3, 0))
+        );
+        assert_eq!(
+            cut.next_after(at("2024-01-01", 3, 0)),
+            Some(at("2024-01-02", 3, 0))
+        );
+    }
+
+    #[test]
+    fn test_next_after_when_steps_lists_and_ranges_expect_matching_times() {
+        let cut: Schedule = "*/20 9-17 * * 1-5".parse().unwrap();
+        // 2024-01-06 is a Saturday.
+        assert_eq!(
+            cut.next_after(at("2024-01-05", 17, 40)),
+            Some(at("2024-01-08", 9, 0))
+        );
+        let cut: Schedule = "30 0 1,15 * 7".parse().unwrap();
+        // Either the day of month or Sunday matches, 2024-01-07 is a Sunday.
+        assert_eq!(
+            cut.next_after(at("2024-01-02", 0, 0)),
+            Some(at("2024-01-07", 0, 30))
+        );
+        let cut: Schedule = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(
+            cut.next_after(at("2024-03-01", 0, 0)),
+            Some(at("2028-02-29", 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_parse_when_malformed_expect_error() {
+        for expression in [
+            "0 3 * *",
+            "60 3 * * *",
+            "0 3 0 * *",
+            "0 3 * * */0",
+            "0 3 5-1 * *",
+            "0 3 x * *",
+            "0 0 31 2 *",
+        ] {
+            assert!(expression.parse::<Schedule>().is_err(), "{expression}");
+        }
+    }
+
+    /// Generate an archive of one document in one version, without its history in the database.
+    #[cfg(feature = "test-fixtures")]
+    fn generate_archive() -> tempfile::TempDir {
+        use crate::testing::generate;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let size = generate::Size {
+            documents: 1,
+            versions: 1,
+        };
+        generate::generate(archive_dir.path(), size).unwrap();
+        archive_dir
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_run_when_archive_updated_expect_successful_last_update() {
+        let archive_dir = generate_archive();
+        let archive_path = archive_dir.path();
+        let cut = Updates::default();
+
+        run(&cut, None, &archive_path.to_string_lossy(), archive_path).await;
+
+        let actual = cut.status();
+        assert!(!actual.update_running);
+        let last_update = actual.last_update.unwrap();
+        assert!(last_update.ok);
+        assert_eq!(last_update.error, None);
+        assert!(last_update.started_at <= last_update.finished_at);
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_run_when_archive_locked_expect_failed_last_update() {
+        use crate::utils::lock;
+
+        let archive_dir = generate_archive();
+        let archive_path = archive_dir.path();
+        let cut = Updates::default();
+        let _locked = lock::Guard::acquire(archive_path, "update", false).unwrap();
+
+        run(&cut, None, &archive_path.to_string_lossy(), archive_path).await;
+
+        let actual = cut.status();
+        assert!(!actual.update_running);
+        let last_update = actual.last_update.unwrap();
+        assert!(!last_update.ok);
+        assert!(last_update.error.unwrap().contains("update"));
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[actix_web::test]
+    async fn test_run_when_update_running_expect_skipped() {
+        let archive_dir = generate_archive();
+        let archive_path = archive_dir.path();
+        let cut = Updates::default();
+        assert!(cut.begin());
+
+        run(&cut, None, &archive_path.to_string_lossy(), archive_path).await;
+
+        let actual = cut.status();
+        assert!(actual.update_running);
+        assert!(actual.last_update.is_none());
+    }
+}
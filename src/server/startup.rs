@@ -0,0 +1,479 @@
+//! Validate an archive before serving it.
+//!
+//! `stelae serve` checks the configuration, the repositories and the databases of the archive
+//! before it starts. Instead of stopping at the first failure, every problem found is gathered
+//! into a [`Report`], which is logged as a whole before the server exits, so all of them can be
+//! fixed before the next attempt.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{self, Databases};
+use crate::server::errors::CliError;
+use crate::stelae::archive::{read_config, Archive, Config, SecurityHeaders};
+use crate::stelae::stele::Stele;
+use crate::stelae::types::dependencies::Dependencies;
+use crate::utils::archive::get_name_parts;
+use crate::utils::git::Repo;
+use crate::utils::output::{write_json, Output};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hint for problems with the configuration of the archive.
+const CONFIG_HINT: &str =
+    "Check that `.taf/config.toml` exists and is valid TOML, e.g. by running `taf conf init`.";
+/// Hint for invalid security headers.
+const SECURITY_HEADERS_HINT: &str =
+    "Remove newlines and other control characters from the values of `[security_headers]` in `.taf/config.toml`.";
+/// Hint for problems with the authentication repository of a stele.
+const STELE_HINT: &str = "Clone the authentication repository of the stele, e.g. with `taf repo clone`, and check that its `targets/repositories.json` and `targets/dependencies.json` are valid JSON.";
+/// Hint for data repositories missing from the archive.
+const REPOSITORY_HINT: &str = "Clone the data repository into the archive, e.g. with `taf repo update`, or stop serving it in `targets/repositories.json`.";
+/// Hint for problems with a database.
+pub const DATABASE_HINT: &str = "Check that the database is readable and writable, and that the DATABASE_URL env var or the `[database]` config is set correctly.";
+
+/// Part of the archive a problem was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProblemKind {
+    /// The configuration of the archive.
+    Config,
+    /// A stele or data repository of the archive.
+    Archive,
+    /// A database of the archive.
+    Database,
+}
+
+/// A problem preventing the archive from being served.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// Part of the archive the problem was found in.
+    pub kind: ProblemKind,
+    /// What the problem was found in, e.g. a repository.
+    pub subject: String,
+    /// The error, with its causes.
+    pub error: String,
+    /// How the problem can be fixed.
+    pub hint: &'static str,
+}
+
+/// Problems found while validating an archive.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// Whether no problems were found.
+    pub ok: bool,
+    /// Problems found, in the order they were found in.
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Add the problem `err` of `kind` found in `subject`.
+    fn push(
+        &mut self,
+        kind: ProblemKind,
+        subject: String,
+        err: &anyhow::Error,
+        hint: &'static str,
+    ) {
+        self.ok = false;
+        self.problems.push(Problem {
+            kind,
+            subject,
+            error: format!("{err:#}"),
+            hint,
+        });
+    }
+
+    /// The error the CLI exits with for the problems found.
+    ///
+    /// Problems with the configuration take precedence over problems with the archive, which take
+    /// precedence over problems with the databases, as the former often cause the latter.
+    #[must_use]
+    pub fn cli_error(&self) -> CliError {
+        let found = |kind| self.problems.iter().any(|problem| problem.kind == kind);
+        if found(ProblemKind::Config) {
+            CliError::ConfigError
+        } else if found(ProblemKind::Archive) {
+            CliError::ArchiveParseError
+        } else if found(ProblemKind::Database) {
+            CliError::DatabaseConnectionError
+        } else {
+            CliError::GenericError
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "Unable to serve the archive, found {} problem(s):",
+            self.problems.len()
+        )?;
+        for (idx, problem) in self.problems.iter().enumerate() {
+            write!(
+                formatter,
+                "\n  {}. {}: {}\n     {}",
+                idx.saturating_add(1),
+                problem.subject,
+                problem.error,
+                problem.hint
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self {
+            ok: true,
+            problems: vec![],
+        }
+    }
+}
+
+/// An archive that passed validation, ready to be served.
+pub struct Validated {
+    /// The parsed archive.
+    pub archive: Archive,
+    /// Configuration of the archive.
+    pub config: Config,
+    /// Connections to the databases of the archive.
+    pub db: Databases,
+}
+
+/// Validate the archive at `archive_path`, serving only the stele at `raw_archive_path` if
+/// `individual` is set.
+///
+/// Checks that the configuration can be read, that the authentication repository of every stele
+/// can be opened and its `targets/repositories.json` and `targets/dependencies.json` parsed, that
+/// every served data repository is in the archive, and that the databases can be connected to.
+///
+/// # Errors
+/// Errors with the report of all problems found, if any.
+pub async fn validate(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    individual: bool,
+) -> Result<Validated, Report> {
+    let mut report = Report::default();
+    let config = match read_config(&archive_path) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            report.push(
+                ProblemKind::Config,
+                "`.taf/config.toml`".to_owned(),
+                &err,
+                CONFIG_HINT,
+            );
+            None
+        }
+    };
+
+    let security_headers = config
+        .as_ref()
+        .and_then(|conf| conf.security_headers.as_ref());
+    if let Some(Err(err)) = security_headers.map(SecurityHeaders::validate) {
+        report.push(
+            ProblemKind::Config,
+            "`[security_headers]` of `.taf/config.toml`".to_owned(),
+            &err,
+            SECURITY_HEADERS_HINT,
+        );
+    }
+
+    let root = root_stele(
+        &mut report,
+        raw_archive_path,
+        &archive_path,
+        individual,
+        config.as_ref(),
+    );
+    if let Some(root_stele) = root.as_ref() {
+        let mut visited = vec![root_stele.get_qualified_name()];
+        check_stele(&mut report, root_stele, &mut visited);
+    }
+
+    let archive = if report.ok {
+        Archive::parse(
+            archive_path.clone(),
+            &PathBuf::from(raw_archive_path),
+            individual,
+        )
+        .map_err(|err| {
+            report.push(
+                ProblemKind::Archive,
+                format!("archive at '{raw_archive_path}'"),
+                &err,
+                STELE_HINT,
+            );
+        })
+        .ok()
+    } else {
+        None
+    };
+
+    let shared = db::init::connect(&archive_path)
+        .await
+        .map_err(|err| {
+            let url = db::init::database_url(&archive_path);
+            report.push(
+                ProblemKind::Database,
+                format!("database {url}"),
+                &err,
+                DATABASE_HINT,
+            );
+        })
+        .ok();
+    let databases = match (archive.as_ref(), shared) {
+        (Some(parsed), Some(shared_db)) => {
+            db::init::connect_stelae(&parsed.path, parsed.stelae.keys(), shared_db)
+                .await
+                .map_err(|err| {
+                    report.push(
+                        ProblemKind::Database,
+                        "stele databases".to_owned(),
+                        &err,
+                        DATABASE_HINT,
+                    );
+                })
+                .ok()
+        }
+        _ => None,
+    };
+
+    match (archive, config, databases) {
+        (Some(parsed), Some(conf), Some(dbs)) if report.ok => Ok(Validated {
+            archive: parsed,
+            config: conf,
+            db: dbs,
+        }),
+        _ => Err(report),
+    }
+}
+
+/// Validate the archive like `stelae serve` does, and report the problems found.
+///
+/// With `output` JSON, the report is written to stdout, see [`Report`].
+///
+/// # Errors
+/// Errors if problems were found, see [`Report::cli_error`].
+#[tracing::instrument(name = "Stelae validate", skip(raw_archive_path, archive_path))]
+pub async fn run(
+    raw_archive_path: &str,
+    archive_path: PathBuf,
+    individual: bool,
+    output: Output,
+) -> Result<(), CliError> {
+    let report = match validate(raw_archive_path, archive_path, individual).await {
+        Ok(validated) => {
+            tracing::info!(
+                "The archive is valid, serving {} stele(s)",
+                validated.archive.stelae.len()
+            );
+            Report::default()
+        }
+        Err(report) => {
+            tracing::error!("{report}");
+            report
+        }
+    };
+    if output.is_json() {
+        write_json(&report).map_err(|_err| CliError::GenericError)?;
+    }
+    if report.ok {
+        Ok(())
+    } else {
+        Err(report.cli_error())
+    }
+}
+
+/// Build the root stele of the archive, the stele at `raw_archive_path` if `individual` is set,
+/// or the root stele of the `config` otherwise.
+fn root_stele(
+    report: &mut Report,
+    raw_archive_path: &str,
+    archive_path: &Path,
+    individual: bool,
+    config: Option<&Config>,
+) -> Option<Stele> {
+    if individual {
+        return individual_stele(raw_archive_path, archive_path)
+            .map_err(|err| {
+                report.push(
+                    ProblemKind::Archive,
+                    format!("stele at '{raw_archive_path}'"),
+                    &err,
+                    STELE_HINT,
+                );
+            })
+            .ok();
+    }
+    let conf = config?;
+    let (org, name) = (conf.root.org.clone(), conf.root.name.clone());
+    let qualified_name = format!("{org}/{name}");
+    Stele::new(
+        archive_path,
+        Some(name),
+        Some(org.clone()),
+        Some(archive_path.join(org)),
+        true,
+    )
+    .map_err(|err| {
+        report.push(
+            ProblemKind::Archive,
+            format!("stele {qualified_name}"),
+            &err,
+            STELE_HINT,
+        );
+    })
+    .ok()
+}
+
+/// Build the stele at `raw_archive_path`, served on its own.
+///
+/// # Errors
+/// Errors if the path does not exist, or the stele cannot be built.
+fn individual_stele(raw_archive_path: &str, archive_path: &Path) -> anyhow::Result<Stele> {
+    let path = Path::new(raw_archive_path).canonicalize()?;
+    Stele::new(archive_path, None, None, Some(path), true)
+}
+
+/// Check the served data repositories of the `stele`, and the stelae it depends on that have not
+/// been `visited` yet.
+fn check_stele(report: &mut Report, stele: &Stele, visited: &mut Vec<String>) {
+    let qualified_name = stele.get_qualified_name();
+    let served = stele
+        .repositories
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+        .filter(|repository| repository.is_served());
+    for repository in served {
+        if let Err(err) = open_repository(&stele.archive_path, &repository.name) {
+            report.push(
+                ProblemKind::Archive,
+                format!(
+                    "data repository {} of stele {qualified_name}",
+                    repository.name
+                ),
+                &err,
+                REPOSITORY_HINT,
+            );
+        }
+    }
+    let dependencies = match stele.get_dependencies() {
+        Ok(dependencies) => dependencies,
+        Err(err) => {
+            report.push(
+                ProblemKind::Archive,
+                format!("stele {qualified_name}"),
+                &err,
+                STELE_HINT,
+            );
+            return;
+        }
+    };
+    for dependency in dependencies
+        .iter()
+        .flat_map(Dependencies::sorted_dependencies_names)
+    {
+        if visited.contains(&dependency) {
+            continue;
+        }
+        visited.push(dependency.clone());
+        let child = get_name_parts(&dependency).and_then(|(org, name)| {
+            let org_path = stele.archive_path.join(&org);
+            if fs::metadata(org_path.join(&name)).is_err() {
+                // Dependencies missing from the archive are not served, like in `Archive::parse`.
+                return Ok(None);
+            }
+            Stele::new(
+                &stele.archive_path,
+                Some(name),
+                Some(org),
+                Some(org_path),
+                false,
+            )
+            .map(Some)
+        });
+        match child {
+            Ok(Some(child_stele)) => check_stele(report, &child_stele, visited),
+            Ok(None) => {}
+            Err(err) => report.push(
+                ProblemKind::Archive,
+                format!("stele {dependency}"),
+                &err,
+                STELE_HINT,
+            ),
+        }
+    }
+}
+
+/// Open the repository `repository_name` of the archive at `archive_path`, and resolve its `HEAD`.
+///
+/// # Errors
+/// Errors if the repository is not in the archive, or has no `HEAD` commit.
+fn open_repository(archive_path: &Path, repository_name: &str) -> anyhow::Result<()> {
+    let (org, name) = get_name_parts(repository_name)?;
+    Repo::new(archive_path, &org, &name)?.head_commit_id()?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::server::errors::CliError;
+    use crate::server::startup::{ProblemKind, Report};
+
+    #[test]
+    fn test_report_when_problems_pushed_expect_not_ok_and_numbered() {
+        let mut cut = Report::default();
+        assert!(cut.ok);
+        cut.push(
+            ProblemKind::Archive,
+            "stele org/law".to_owned(),
+            &anyhow::anyhow!("could not parse targets/repositories.json"),
+            "Fix it.",
+        );
+        cut.push(
+            ProblemKind::Database,
+            "database".to_owned(),
+            &anyhow::anyhow!("locked"),
+            "Wait.",
+        );
+        assert!(!cut.ok);
+        let actual = cut.to_string();
+        assert!(actual.starts_with("Unable to serve the archive, found 2 problem(s):"));
+        assert!(actual.contains("\n  2. database: locked\n     Wait."));
+        let json = serde_json::to_value(&cut).unwrap();
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["problems"][0]["subject"], "stele org/law");
+        assert_eq!(json["problems"][0]["kind"], "archive");
+    }
+
+    #[test]
+    fn test_cli_error_when_config_and_database_problems_expect_config_error() {
+        let mut cut = Report::default();
+        cut.push(
+            ProblemKind::Database,
+            "database".to_owned(),
+            &anyhow::anyhow!("locked"),
+            "Wait.",
+        );
+        assert_eq!(cut.cli_error(), CliError::DatabaseConnectionError);
+        cut.push(
+            ProblemKind::Config,
+            "`.taf/config.toml`".to_owned(),
+            &anyhow::anyhow!("missing"),
+            "Create it.",
+        );
+        assert_eq!(cut.cli_error(), CliError::ConfigError);
+    }
+}
@@ -0,0 +1,179 @@
+//! Break the time of requests down by phase in the `Server-Timing` header.
+//!
+//! When `[server_timing]` is enabled, every request carries [`Timings`] in its extensions.
+//! Handlers measure their database, git and rewrite phases with [`Timings::measure`] and
+//! [`Timings::measure_async`], which also run each phase in a tracing span named after it. The
+//! phases are summed per request and sent with the total time in the `Server-Timing` header of
+//! the response, e.g. `db;dur=1.2, git;dur=3.4, rewrite;dur=0.1, total;dur=5.0`.
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{HttpMessage as _, HttpRequest};
+use tracing::Instrument as _;
+
+/// Phase of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Querying the database.
+    Db,
+    /// Reading git repositories.
+    Git,
+    /// Rewriting documents, e.g. wrapping them in a layout or mounting their urls.
+    Rewrite,
+}
+
+impl Phase {
+    /// Every phase, in the order they are sent in.
+    pub const ALL: [Self; 3] = [Self::Db, Self::Git, Self::Rewrite];
+
+    /// Name of the phase in the `Server-Timing` header and of its tracing span.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Db => "db",
+            Self::Git => "git",
+            Self::Rewrite => "rewrite",
+        }
+    }
+
+    /// Index of the phase in [`Phase::ALL`].
+    const fn index(self) -> usize {
+        match self {
+            Self::Db => 0,
+            Self::Git => 1,
+            Self::Rewrite => 2,
+        }
+    }
+}
+
+/// Name of the header breaking the time of a request down by phase.
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// Time spent by a request in each phase, not recorded unless `[server_timing]` is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct Timings(Option<Rc<Recorded>>);
+
+/// Time spent by a request in each phase, recorded.
+#[derive(Debug)]
+struct Recorded {
+    /// Time the request started at.
+    started: Instant,
+    /// Time spent in each phase, in the order of [`Phase::ALL`].
+    phases: RefCell<[Duration; 3]>,
+}
+
+impl Timings {
+    /// Timings recording the time spent in each phase from now on.
+    #[must_use]
+    pub fn recorded() -> Self {
+        Self(Some(Rc::new(Recorded {
+            started: Instant::now(),
+            phases: RefCell::default(),
+        })))
+    }
+
+    /// Start the timings of the request `req`, recorded if `enabled`.
+    #[must_use]
+    pub fn start(req: &ServiceRequest, enabled: bool) -> Self {
+        if !enabled {
+            return Self::default();
+        }
+        let timings = Self::recorded();
+        req.extensions_mut().insert(timings.clone());
+        timings
+    }
+
+    /// Send the recorded timings in the `Server-Timing` header of the `response`.
+    pub fn finish<B>(&self, response: &mut ServiceResponse<B>) {
+        let total = self.0.as_ref().map(|recorded| recorded.started.elapsed());
+        let value = total.and_then(|elapsed| self.header_value(elapsed));
+        if let Some(header_value) = value.and_then(|timing| HeaderValue::from_str(&timing).ok()) {
+            response.headers_mut().insert(SERVER_TIMING, header_value);
+        }
+    }
+
+    /// Timings of the request `req`, not recorded if `[server_timing]` is disabled.
+    #[must_use]
+    pub fn of(req: &HttpRequest) -> Self {
+        req.extensions().get::<Self>().cloned().unwrap_or_default()
+    }
+
+    /// Run `work` in the `phase`, adding the time it takes to the phase.
+    pub fn measure<T, F: FnOnce() -> T>(&self, phase: Phase, work: F) -> T {
+        let started = Instant::now();
+        let result = tracing::debug_span!("phase", name = phase.as_str()).in_scope(work);
+        self.add(phase, started.elapsed());
+        result
+    }
+
+    /// Await `work` in the `phase`, adding the time until it completes to the phase.
+    #[expect(
+        clippy::future_not_send,
+        reason = "Timings are shared within the worker serving the request only"
+    )]
+    pub async fn measure_async<T, F: Future<Output = T>>(&self, phase: Phase, work: F) -> T {
+        let started = Instant::now();
+        let result = work
+            .instrument(tracing::debug_span!("phase", name = phase.as_str()))
+            .await;
+        self.add(phase, started.elapsed());
+        result
+    }
+
+    /// Add `elapsed` to the time spent in the `phase`.
+    fn add(&self, phase: Phase, elapsed: Duration) {
+        if let Some(recorded) = self.0.as_ref() {
+            if let Some(duration) = recorded.phases.borrow_mut().get_mut(phase.index()) {
+                *duration += elapsed;
+            }
+        }
+    }
+
+    /// Value of the `Server-Timing` header of the phases and the `total` time of the request, in
+    /// milliseconds, or `None` if not recorded.
+    #[must_use]
+    pub fn header_value(&self, total: Duration) -> Option<String> {
+        let durations = self.0.as_ref()?.phases.borrow();
+        let phases = Phase::ALL
+            .iter()
+            .zip(durations.iter())
+            .map(|(phase, duration)| format!("{};dur={}", phase.as_str(), millis(*duration)));
+        let total_phase = format!("total;dur={}", millis(total));
+        Some(phases.chain([total_phase]).collect::<Vec<_>>().join(", "))
+    }
+}
+
+/// `duration` in milliseconds, with a precision of a microsecond.
+#[expect(
+    clippy::float_arithmetic,
+    reason = "Durations are sent in fractional milliseconds"
+)]
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::default_numeric_fallback,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::server::timing::{Phase, Timings};
+    use std::time::Duration;
+
+    #[test]
+    fn test_header_value_when_recorded_expect_phases_and_total() {
+        let cut = Timings::recorded();
+        let _db = cut.measure(Phase::Db, || 1);
+        let actual = cut.header_value(Duration::from_millis(5)).unwrap();
+        assert!(actual.starts_with("db;dur="));
+        assert!(actual.contains(", git;dur=0.000, rewrite;dur=0.000, "));
+        assert!(actual.ends_with("total;dur=5.000"));
+        assert_eq!(Timings::default().header_value(Duration::ZERO), None);
+    }
+}
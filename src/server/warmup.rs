@@ -0,0 +1,185 @@
+//! Warm the cache of the server before it accepts traffic.
+//!
+//! The current publication of every stele, the versions of its root collection and of the hot
+//! documents listed under `[warmup]` in `.taf/config.toml`, the current blobs of the hot
+//! documents, and the integrity manifests of the static assets of every stele are resolved into
+//! the [`Cache`] of `stelae serve --warmup`.
+#![expect(
+    clippy::future_not_send,
+    reason = "We don't worry about git2-rs not implementing `Send` trait"
+)]
+use crate::db::{
+    models::{current_blob, publication},
+    Databases,
+};
+use crate::server::api::integrity;
+use crate::server::api::versions::{self, clean_url_path};
+use crate::server::cache::Cache;
+use crate::stelae::archive::{Archive, Warmup};
+use crate::stelae::stele::Stele;
+use crate::utils::archive::get_name_parts;
+use crate::utils::git::Repo;
+use crate::utils::paths::normalize_path;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Results resolved into the cache by the warmup.
+#[derive(Debug, Default)]
+pub struct Warmed {
+    /// Number of stelae whose publications were resolved.
+    pub publications: usize,
+    /// Number of version queries resolved.
+    pub versions: usize,
+    /// Number of current blobs read.
+    pub blobs: usize,
+    /// Number of stelae whose asset integrity manifests were computed.
+    pub manifests: usize,
+    /// Hot documents not found in any served data repository.
+    pub missing: Vec<String>,
+}
+
+/// Resolve the current publications, common version queries, hot documents and asset integrity
+/// manifests of every stele in the `archive` into the `cache`.
+///
+/// # Errors
+/// Errors if the publications cannot be read from the database.
+pub async fn warm(
+    cache: &Cache,
+    archive: &Archive,
+    db: &Databases,
+    warmup: &Warmup,
+) -> anyhow::Result<Warmed> {
+    let mut warmed = Warmed::default();
+    let mut stelae: Vec<&Stele> = archive.stelae.values().collect();
+    stelae.sort_by_key(|stele| stele.get_qualified_name());
+    let mut urls = vec![clean_url_path("")];
+    urls.extend(warmup.documents().iter().map(|path| clean_url_path(path)));
+    for stele in &stelae {
+        let name = stele.get_qualified_name();
+        let stele_db = db.for_stele(&name);
+        let publications =
+            publication::Manager::find_all_non_revoked_publications(stele_db, &name).await?;
+        if let Some(current) = publications.first() {
+            for url in &urls {
+                versions::warm(stele_db, cache, current, url.clone()).await;
+                warmed.versions = warmed.versions.saturating_add(1);
+            }
+        }
+        cache.insert_publications(name, publications);
+        warmed.publications = warmed.publications.saturating_add(1);
+    }
+    for document in warmup.documents() {
+        let Ok(path) = normalize_path(document) else {
+            warmed.missing.push(document.clone());
+            continue;
+        };
+        let found = stelae
+            .iter()
+            .map(|stele| warm_blob(cache, stele, &path))
+            .fold(0, usize::saturating_add);
+        if found == 0 {
+            warmed.missing.push(document.clone());
+        }
+        warmed.blobs = warmed.blobs.saturating_add(found);
+    }
+    warmed.manifests = integrity::warm(cache, archive);
+    Ok(warmed)
+}
+
+/// Read the current blob at the normalized `path` of the served data repositories of the `stele`
+/// into the `cache`.
+///
+/// Returns the number of repositories the blob was found in.
+fn warm_blob(cache: &Cache, stele: &Stele, path: &str) -> usize {
+    let served = stele
+        .repositories
+        .iter()
+        .flat_map(|repositories| repositories.get_sorted())
+        .filter(|repository| repository.is_served());
+    let mut found: usize = 0;
+    for repository in served {
+        let Ok((org, name)) = get_name_parts(&repository.name) else {
+            continue;
+        };
+        let Ok(repo) = Repo::new(&stele.archive_path, &org, &name) else {
+            continue;
+        };
+        let Ok(commit) = repo.head_commit_id() else {
+            continue;
+        };
+        if let Ok(content) = repo.get_bytes_at_path(&commit, path) {
+            cache.insert_blob(repository.name.clone(), commit, path.to_owned(), content);
+            found = found.saturating_add(1);
+        }
+    }
+    found
+}
+
+/// Load the ids of the current blobs recorded by `stelae update` for every stele in the `archive`
+/// into the `cache`, see [`crate::stelae::archive::CurrentIndex`].
+///
+/// Returns the number of repositories whose blob ids were loaded.
+///
+/// # Errors
+/// Errors if the current blobs cannot be read from the database.
+#[expect(
+    clippy::iter_over_hash_type,
+    reason = "Blob ids are cached by repository, so the order they are loaded in does not matter"
+)]
+pub async fn load_blob_ids(
+    cache: &Cache,
+    archive: &Archive,
+    db: &Databases,
+) -> anyhow::Result<usize> {
+    let mut loaded: usize = 0;
+    for name in archive.stelae.keys() {
+        let current_blobs =
+            current_blob::Manager::find_all_by_stele(db.for_stele(name), name).await?;
+        let mut repositories: HashMap<String, (String, HashMap<String, String>)> = HashMap::new();
+        for blob in current_blobs {
+            repositories
+                .entry(blob.repository)
+                .or_insert_with(|| (blob.commit_hash, HashMap::new()))
+                .1
+                .insert(blob.path, blob.blob_id);
+        }
+        for (repository, (commit, ids)) in repositories {
+            cache.insert_blob_ids(repository, commit, ids);
+            loaded = loaded.saturating_add(1);
+        }
+    }
+    Ok(loaded)
+}
+
+/// Load the current blob ids into the `cache` with [`load_blob_ids`] if the `[current_index]` of
+/// the `archive` is enabled, logging the outcome. Blobs whose ids are not loaded are looked up in
+/// git.
+pub async fn load_current_index(cache: &Cache, archive: &Archive, db: &Databases) {
+    let enabled = archive
+        .get_config()
+        .is_ok_and(|config| config.current_index.unwrap_or_default().is_enabled());
+    if !enabled {
+        return;
+    }
+    match load_blob_ids(cache, archive, db).await {
+        Ok(loaded) => tracing::info!("Loaded the current blob ids of {loaded} repositories"),
+        Err(err) => {
+            tracing::error!("Unable to load the current blob ids, looking up blobs in git.");
+            tracing::error!("Error: {err:?}");
+        }
+    }
+}
+
+/// Log the results of a warmup that took `elapsed`.
+pub fn log(warmed: &Warmed, elapsed: Duration) {
+    tracing::info!(
+        "Warmed the publications of {} stele(s), {} version queries, {} document blob(s) and {} asset integrity manifest(s) in {elapsed:?}",
+        warmed.publications,
+        warmed.versions,
+        warmed.blobs,
+        warmed.manifests
+    );
+    for document in &warmed.missing {
+        tracing::warn!("Hot document {document} was not found in any served data repository");
+    }
+}
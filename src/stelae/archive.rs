@@ -4,10 +4,17 @@
 use crate::stelae::stele;
 use crate::stelae::stele::Stele;
 use crate::utils::archive::{find_archive_path, get_name_parts};
+use crate::utils::http::secrets_match;
+use crate::utils::locale::Locale;
+use actix_web::http::header::HeaderValue;
+use anyhow::Context as _;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, create_dir_all, read_to_string, write};
+use std::hash::BuildHasher;
+use std::iter;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use toml_edit::ser;
 
 /// The Archive struct is used for interacting with a Stelae Archive.
@@ -17,17 +24,16 @@ pub struct Archive {
     pub path: PathBuf,
     /// map of auth repo name to Stele object
     pub stelae: HashMap<String, Stele>,
+    /// Former qualified names of stelae, mapped to their current qualified names
+    pub aliases: HashMap<String, String>,
 }
 
 impl Archive {
-    /// Get an archive's config object.
+    /// Get an archive's config object, see [`read_config`].
     /// # Errors
     /// Will error if unable to find or parse config file at `.taf/config.toml`
     pub fn get_config(&self) -> anyhow::Result<Config> {
-        let config_path = &self.path.join(PathBuf::from(".taf/config.toml"));
-        let config_str = read_to_string(config_path)?;
-        let conf: Config = toml::from_str(&config_str)?;
-        Ok(conf)
+        read_config(&self.path)
     }
 
     /// Get the Archive's root Stele.
@@ -80,6 +86,31 @@ impl Archive {
         stelae_vec
     }
 
+    /// Return the scopes of the Stelae in the Archive, as pairs of qualified name and scope.
+    #[must_use]
+    pub fn get_scopes(&self) -> Vec<(String, String)> {
+        let mut scopes: Vec<(String, String)> = self
+            .stelae
+            .iter()
+            .flat_map(|(qualified_name, stele)| {
+                stele
+                    .repositories
+                    .iter()
+                    .flat_map(|repositories| repositories.scopes.iter().flatten())
+                    .map(|scope| (qualified_name.clone(), scope.clone()))
+            })
+            .collect();
+        scopes.sort();
+        scopes
+    }
+
+    /// Get the qualified name of the Stele serving `scope`, e.g. `sub/scope/1`.
+    /// # Errors
+    /// Will raise error if no Stele serves the scope, or if the scope is ambiguous.
+    pub fn get_stele_by_scope(&self, scope: &str) -> anyhow::Result<String> {
+        resolve_scope(scope, &self.get_scopes())
+    }
+
     /// Parse an Archive.
     /// # Errors
     /// Will raise error if unable to determine the current root stele or if unable to traverse the child steles.
@@ -91,6 +122,7 @@ impl Archive {
         let mut archive = Self {
             path: archive_path,
             stelae: HashMap::new(),
+            aliases: HashMap::new(),
         };
 
         let path = if individual {
@@ -99,6 +131,11 @@ impl Archive {
             None
         };
         archive.set_root(path)?;
+        archive.aliases = archive
+            .get_config()
+            .ok()
+            .and_then(|config| config.aliases)
+            .unwrap_or_default();
 
         let root = archive.get_root()?;
         let mut visited = vec![root.get_qualified_name()];
@@ -106,6 +143,12 @@ impl Archive {
         Ok(archive)
     }
 
+    /// Current qualified name of the stele `qualified_name`, which may be a former name of it.
+    #[must_use]
+    pub fn resolve_alias<'name>(&'name self, qualified_name: &'name str) -> &'name str {
+        resolve_alias(qualified_name, &self.aliases)
+    }
+
     /// Traverse the child Steles of the current Stele.
     /// # Errors
     /// Will raise error if unable to traverse the child steles.
@@ -149,6 +192,48 @@ impl Archive {
     }
 }
 
+/// Path of the local config of an archive, relative to the archive.
+///
+/// The local config belongs to a single deployment and is not committed. Its values override
+/// the values of `.taf/config.toml`, e.g. guard headers or serve options.
+pub const LOCAL_CONFIG_PATH: &str = ".stelae/config.toml";
+
+/// Read the config of the archive at `archive_path`.
+///
+/// The local config at [`LOCAL_CONFIG_PATH`], if any, is merged into `.taf/config.toml`.
+/// # Errors
+/// Will error if unable to find or parse config file at `.taf/config.toml`, or unable to parse
+/// the local config
+pub fn read_config(archive_path: &Path) -> anyhow::Result<Config> {
+    let config_path = &archive_path.join(PathBuf::from(".taf/config.toml"));
+    let config_str = read_to_string(config_path)?;
+    let mut conf: toml::Table = toml::from_str(&config_str)?;
+    let local_path = archive_path.join(LOCAL_CONFIG_PATH);
+    if local_path.exists() {
+        let local: toml::Table = toml::from_str(&read_to_string(&local_path)?)
+            .with_context(|| format!("Invalid local config {}", local_path.display()))?;
+        merge_config(&mut conf, local);
+    }
+    Ok(toml::Value::Table(conf).try_into()?)
+}
+
+/// Merge the `overrides` into the `config`.
+///
+/// Tables are merged key by key, and any other value is replaced, including arrays.
+fn merge_config(config: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (
+            config.get_mut(&key).and_then(toml::Value::as_table_mut),
+            value,
+        ) {
+            (Some(table), toml::Value::Table(overriding)) => merge_config(table, overriding),
+            (_, overriding) => {
+                config.insert(key, overriding);
+            }
+        }
+    }
+}
+
 /// Check if the `path` is inside an existing archive
 /// # Errors
 /// Return an error if the path is inside an existing archive.
@@ -160,6 +245,64 @@ fn raise_error_if_in_existing_archive(path: &Path) -> anyhow::Result<bool> {
     }
 }
 
+/// Resolve the current qualified name of the Stele `qualified_name` from the `aliases` of former names.
+///
+/// Names that are not former names are current names, and are returned as is.
+#[must_use]
+pub fn resolve_alias<'name, S: BuildHasher>(
+    qualified_name: &'name str,
+    aliases: &'name HashMap<String, String, S>,
+) -> &'name str {
+    aliases
+        .get(qualified_name)
+        .map_or(qualified_name, String::as_str)
+}
+
+/// Resolve the qualified name of the Stele serving `scope` from the `scopes` of the Stelae.
+///
+/// A Stele serves a scope when one of its scopes equals the scope or is a parent of it, e.g.
+/// `sub/scope` serves `sub/scope/1`. The Stele with the most specific scope is selected.
+/// # Errors
+/// Will raise error if no Stele serves the scope, or if several Stelae serve it with equally
+/// specific scopes.
+pub fn resolve_scope(scope: &str, scopes: &[(String, String)]) -> anyhow::Result<String> {
+    let requested = scope.trim_matches('/');
+    if requested.is_empty() {
+        anyhow::bail!("Scope must not be empty");
+    }
+    let serving: Vec<(&str, &str)> = scopes
+        .iter()
+        .map(|serving| (serving.0.as_str(), serving.1.trim_matches('/')))
+        .filter(|&(_, stele_scope)| {
+            !stele_scope.is_empty()
+                && requested
+                    .strip_prefix(stele_scope)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .collect();
+    let Some(most_specific) = serving
+        .iter()
+        .map(|&(_, stele_scope)| stele_scope.len())
+        .max()
+    else {
+        anyhow::bail!("No stele serves scope '{requested}'");
+    };
+    let mut stelae: Vec<&str> = serving
+        .into_iter()
+        .filter(|&(_, stele_scope)| stele_scope.len() == most_specific)
+        .map(|(qualified_name, _)| qualified_name)
+        .collect();
+    stelae.sort_unstable();
+    stelae.dedup();
+    match stelae.as_slice() {
+        &[qualified_name] => Ok(qualified_name.to_owned()),
+        _ => anyhow::bail!(
+            "Scope '{requested}' is ambiguous, it is served by stelae {}",
+            stelae.join(", ")
+        ),
+    }
+}
+
 /// Config object for an Archive
 #[derive(Deserialize, Serialize)]
 pub struct Config {
@@ -169,6 +312,904 @@ pub struct Config {
     pub shallow: bool,
     /// Custom HTTP headers used to interact with the Stele
     pub headers: Option<Headers>,
+    /// Security headers applied to served documents. No security headers are sent when unset.
+    pub security_headers: Option<SecurityHeaders>,
+    /// Limits on the size of requests to the Stele
+    pub limits: Option<Limits>,
+    /// Limits on the number of concurrent requests to the Stele
+    pub concurrency: Option<Concurrency>,
+    /// Time budgets of requests to the Stele
+    pub timeouts: Option<Timeouts>,
+    /// Locales of display dates of the Stele
+    pub locales: Option<Locales>,
+    /// OIDC authentication of guarded routes of the Stele
+    pub auth: Option<Auth>,
+    /// Structured access log of requests to the Stele. No access log is written when unset.
+    pub access_log: Option<AccessLog>,
+    /// Reverse proxies in front of the Stele. Forwarded headers are ignored when unset.
+    pub proxy: Option<Proxy>,
+    /// Counting of the views of current documents of the Stele. No views are counted when unset.
+    pub document_views: Option<DocumentViews>,
+    /// Banners marking historical documents of the Stele. No banner is shown when unset.
+    pub watermarks: Option<Watermarks>,
+    /// Structured data describing served documents. No structured data is emitted when unset.
+    pub structured_data: Option<StructuredData>,
+    /// Plugins run by `stelae update`. Only the built-in `references` plugin runs when unset.
+    pub ingest: Option<Ingest>,
+    /// Webhooks notified of ingested publications. No webhooks are notified when unset.
+    pub webhooks: Option<Webhooks>,
+    /// Digest of the documents changed by `stelae update`. No digest is sent when unset.
+    pub digest: Option<Digest>,
+    /// Thresholds of the disk usage monitoring of `stelae disk-usage`. No warnings are logged when unset.
+    pub disk_usage: Option<DiskUsage>,
+    /// Token of the mirrors fetching from `stelae git`. The archive is not served to mirrors when unset.
+    pub mirror: Option<Mirror>,
+    /// Results resolved into the cache by `stelae warmup` and `stelae serve --warmup`.
+    /// Only the current publications and root collections are warmed when unset.
+    pub warmup: Option<Warmup>,
+    /// Databases of the stelae that store their change data apart from the archive's database.
+    /// All stelae share the archive's database when unset.
+    pub database: Option<Database>,
+    /// Former qualified names of renamed stelae, mapped to their current qualified names, e.g.
+    /// `"city-of-x/law" = "x-city/law"`. Former names keep resolving in the `X-Stelae` header,
+    /// and `stelae update` moves the rows of a former name to the current name.
+    /// A stele with a database of its own has to be moved to the path of its current name.
+    pub aliases: Option<HashMap<String, String>>,
+    /// Token of the requests that are served preview publications. Previews are never served when unset.
+    pub preview: Option<Preview>,
+    /// Approval of ingested publications before they are served. Publications are served as soon
+    /// as they are ingested when unset.
+    pub approval: Option<Approval>,
+    /// Index of the current blobs of the served data repositories. Current documents are looked up
+    /// in the trees of the `HEAD` commits on every request when unset.
+    pub current_index: Option<CurrentIndex>,
+    /// `Server-Timing` header breaking the time of each request down by phase. No header is sent
+    /// when unset.
+    pub server_timing: Option<ServerTiming>,
+}
+
+/// Default maximum length of a request url, in bytes.
+pub const DEFAULT_MAX_URL_LENGTH: usize = 8192;
+
+/// Default maximum size of a JSON request body, in bytes.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Optional request size limits for an Archive
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Limits {
+    /// Maximum length of a request url (path and query), in bytes. Defaults to [`DEFAULT_MAX_URL_LENGTH`].
+    pub max_url_length: Option<usize>,
+    /// Maximum size of a JSON request body, in bytes. Defaults to [`DEFAULT_MAX_BODY_SIZE`].
+    pub max_body_size: Option<usize>,
+}
+
+impl Limits {
+    /// Maximum length of a request url, in bytes.
+    #[must_use]
+    pub fn max_url_length(&self) -> usize {
+        self.max_url_length.unwrap_or(DEFAULT_MAX_URL_LENGTH)
+    }
+
+    /// Maximum size of a JSON request body, in bytes.
+    #[must_use]
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+/// Default maximum number of concurrent document requests, across all workers.
+pub const DEFAULT_MAX_STATIC_REQUESTS: usize = 256;
+
+/// Default maximum number of concurrent `/_api` and `/_admin` requests, across all workers.
+pub const DEFAULT_MAX_EXPENSIVE_REQUESTS: usize = 16;
+
+/// Default value of the `Retry-After` header of shed requests, in seconds.
+pub const DEFAULT_RETRY_AFTER: u64 = 1;
+
+/// Optional concurrency limits for an Archive.
+///
+/// Requests over the limit of their endpoint class are rejected with `503 Service Unavailable`.
+/// Unlike Actix's connection limits, the limits hold across all the workers of the server.
+/// Example:
+/// ```toml
+/// [concurrency]
+/// max_static_requests = 512
+/// max_expensive_requests = 4
+/// retry_after = 5
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Concurrency {
+    /// Maximum number of concurrent requests for documents and snapshots. Defaults to [`DEFAULT_MAX_STATIC_REQUESTS`].
+    pub max_static_requests: Option<usize>,
+    /// Maximum number of concurrent requests to `/_api` and `/_admin` endpoints, e.g. versions comparisons and suggestions.
+    /// Defaults to [`DEFAULT_MAX_EXPENSIVE_REQUESTS`].
+    pub max_expensive_requests: Option<usize>,
+    /// Seconds a client is asked to wait before retrying a rejected request. Defaults to [`DEFAULT_RETRY_AFTER`].
+    pub retry_after: Option<u64>,
+}
+
+impl Concurrency {
+    /// Maximum number of concurrent requests for documents and snapshots.
+    #[must_use]
+    pub fn max_static_requests(&self) -> usize {
+        self.max_static_requests
+            .unwrap_or(DEFAULT_MAX_STATIC_REQUESTS)
+    }
+
+    /// Maximum number of concurrent requests to `/_api` and `/_admin` endpoints.
+    #[must_use]
+    pub fn max_expensive_requests(&self) -> usize {
+        self.max_expensive_requests
+            .unwrap_or(DEFAULT_MAX_EXPENSIVE_REQUESTS)
+    }
+
+    /// Seconds a client is asked to wait before retrying a rejected request.
+    #[must_use]
+    pub fn retry_after(&self) -> u64 {
+        self.retry_after.unwrap_or(DEFAULT_RETRY_AFTER)
+    }
+}
+
+/// Default time budget of a request, in seconds.
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Optional request time budgets for an Archive.
+///
+/// Requests running over their budget are cancelled, rolling back any open database
+/// transaction, and rejected with `504 Gateway Timeout`. Routes are matched by path prefix,
+/// and the longest matching prefix wins.
+/// Example:
+/// ```toml
+/// [timeouts]
+/// default = 10
+///
+/// [timeouts.routes]
+/// "/_api/versions" = 60
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Timeouts {
+    /// Time budget of every request, in seconds. Defaults to [`DEFAULT_TIMEOUT`].
+    pub default: Option<u64>,
+    /// Per-route time budgets in seconds, keyed by path prefix.
+    pub routes: Option<HashMap<String, u64>>,
+}
+
+impl Timeouts {
+    /// Resolve the time budget of a request to `path`.
+    #[must_use]
+    pub fn for_path(&self, path: &str) -> Duration {
+        let seconds = self
+            .routes
+            .iter()
+            .flatten()
+            .filter(|&(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|&(prefix, _)| prefix.len())
+            .map_or_else(
+                || self.default.unwrap_or(DEFAULT_TIMEOUT),
+                |(_, &seconds)| seconds,
+            );
+        Duration::from_secs(seconds)
+    }
+}
+
+/// Optional locale configuration for an Archive.
+///
+/// Locales are BCP 47 language tags, e.g. `es` or `es-MX`. Display dates are formatted in
+/// English when unset, or when the language is not supported.
+/// Example:
+/// ```toml
+/// [locales]
+/// default = "en"
+///
+/// [locales.stelae]
+/// "org-name/law" = "es"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Locales {
+    /// Locale of every stele.
+    pub default: Option<String>,
+    /// Per-stele locales, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, String>>,
+}
+
+impl Locales {
+    /// Resolve the locale of the stele `stele_name`.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> Locale {
+        let tag = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name))
+            .or(self.default.as_ref());
+        tag.map_or_else(Locale::default, |found_tag| {
+            Locale::from_tag(found_tag).unwrap_or_else(|| {
+                tracing::warn!("Unsupported locale {found_tag} for stele {stele_name}");
+                Locale::default()
+            })
+        })
+    }
+}
+
+/// Default claim of an ID token listing the groups of the user.
+pub const DEFAULT_GROUPS_CLAIM: &str = "groups";
+
+/// Optional OIDC authentication configuration for an Archive.
+///
+/// Requests to `/_admin` endpoints, and to current documents if `guard_current_documents` is set,
+/// must carry an `Authorization: Bearer` token issued by `issuer` for `audience`. The groups of
+/// the token are mapped to roles, first by the mapping of the requested stele, then by `roles`.
+/// Example:
+/// ```toml
+/// [auth]
+/// issuer = "https://login.example.com/realms/law"
+/// audience = "stelae"
+/// guard_current_documents = true
+///
+/// [auth.roles]
+/// "law-admins" = "admin"
+///
+/// [auth.stelae."org-name/law"]
+/// "law-readers" = "reader"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Auth {
+    /// Issuer of the tokens. Its keys are discovered at `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    /// Audience the tokens must be issued for, usually the client id of stelae.
+    pub audience: String,
+    /// Claim listing the groups of the user. Defaults to [`DEFAULT_GROUPS_CLAIM`].
+    pub groups_claim: Option<String>,
+    /// Whether reading current documents requires the `reader` role. Defaults to `false`.
+    pub guard_current_documents: Option<bool>,
+    /// Roles of groups in every stele.
+    pub roles: Option<HashMap<String, Role>>,
+    /// Per-stele roles of groups, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, HashMap<String, Role>>>,
+}
+
+/// Role of an authenticated user in a stele.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May read guarded current documents.
+    Reader,
+    /// May read guarded current documents and use `/_admin` endpoints.
+    Admin,
+}
+
+impl Auth {
+    /// Resolve the highest role that `groups` grant in the stele `stele_name`.
+    #[must_use]
+    pub fn role_for(&self, stele_name: &str, groups: &[String]) -> Option<Role> {
+        let stele_roles = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name));
+        groups
+            .iter()
+            .filter_map(|group| {
+                stele_roles
+                    .and_then(|roles| roles.get(group))
+                    .or_else(|| self.roles.as_ref()?.get(group))
+                    .copied()
+            })
+            .max()
+    }
+}
+
+/// Optional access log configuration for an Archive.
+///
+/// Each request is written as a JSON line with its method, path, stele, status, latency and
+/// response size, separately from the tracing diagnostics. Logging can be switched off per route,
+/// keyed by path prefix, and per stele. The client IP address is the address of the peer, or the
+/// `X-Forwarded-For` client if the peer is a trusted proxy of the [`Proxy`] config.
+/// Example:
+/// ```toml
+/// [access_log]
+/// path = "/var/log/stelae/access.log"
+/// ip = "truncate"
+///
+/// [access_log.routes]
+/// "/_admin/" = false
+///
+/// [access_log.stelae]
+/// "org-name/law" = false
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct AccessLog {
+    /// File the access log is appended to. Written to standard output when unset.
+    pub path: Option<PathBuf>,
+    /// How client IP addresses are recorded. Defaults to [`IpPrivacy::Truncate`].
+    pub ip: Option<IpPrivacy>,
+    /// Whether requests are logged, keyed by path prefix. The longest matching prefix wins.
+    pub routes: Option<HashMap<String, bool>>,
+    /// Whether requests are logged, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, bool>>,
+}
+
+/// How client IP addresses are recorded in the access log.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpPrivacy {
+    /// Record the full address.
+    Full,
+    /// Zero the last octet of IPv4 addresses, and all but the first 48 bits of IPv6 addresses.
+    #[default]
+    Truncate,
+    /// Do not record the address.
+    Omit,
+}
+
+impl AccessLog {
+    /// Whether a request to `path` of the stele `stele_name` is logged.
+    ///
+    /// Requests are logged unless switched off for their route or their stele.
+    #[must_use]
+    pub fn is_enabled(&self, path: &str, stele_name: &str) -> bool {
+        let route_enabled = self
+            .routes
+            .iter()
+            .flatten()
+            .filter(|&(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|&(prefix, _)| prefix.len())
+            .is_none_or(|(_, &enabled)| enabled);
+        let stele_enabled = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name))
+            .copied()
+            .unwrap_or(true);
+        route_enabled && stele_enabled
+    }
+}
+
+/// Optional configuration of the reverse proxies in front of an Archive.
+///
+/// The client address, scheme and host of a request are taken from its `X-Forwarded-For`,
+/// `X-Forwarded-Proto` and `X-Forwarded-Host` headers only if the peer it was received from is a
+/// trusted proxy, and from the connection otherwise. Absolute urls of served documents, e.g. their
+/// canonical links, are built from `origin` instead, if set.
+/// Example:
+/// ```toml
+/// [proxy]
+/// trusted = ["127.0.0.1", "10.0.0.0/8", "::1"]
+/// origin = "https://laws.example.org"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Proxy {
+    /// Addresses or CIDR networks of the trusted proxies, or `unix` to trust peers connected
+    /// over a unix domain socket.
+    pub trusted: Option<Vec<String>>,
+    /// Scheme and host of absolute urls, e.g. `https://laws.example.org`. Taken from the request when unset.
+    pub origin: Option<String>,
+}
+
+/// Default interval between writes of the counted document views to the database, in seconds.
+pub const DEFAULT_DOCUMENT_VIEWS_FLUSH_INTERVAL: u64 = 60;
+
+/// Optional counting of the views of current documents of an Archive.
+///
+/// Views are counted per stele, document path and day in memory, and written to the
+/// `document_views` table every `flush_interval` seconds and when the server stops. The most
+/// viewed documents are listed by `/_api/stats/top-documents`.
+/// Example:
+/// ```toml
+/// [document_views]
+/// flush_interval = 300
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct DocumentViews {
+    /// Interval between writes of the counted views, in seconds. Defaults to
+    /// [`DEFAULT_DOCUMENT_VIEWS_FLUSH_INTERVAL`].
+    pub flush_interval: Option<u64>,
+}
+
+impl DocumentViews {
+    /// Interval between writes of the counted views to the database.
+    #[must_use]
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.flush_interval
+                .unwrap_or(DEFAULT_DOCUMENT_VIEWS_FLUSH_INTERVAL),
+        )
+    }
+}
+
+/// Default text of the banner of historical documents. `{{ date }}` is replaced by their date.
+pub const DEFAULT_WATERMARK_TEXT: &str =
+    "This is the version of this document as of {{ date }}. It may not reflect the current law.";
+
+/// Optional configuration of the banner marking historical html documents.
+///
+/// The banner is shown at the top of the document, and is kept when the document is printed.
+/// Example:
+/// ```toml
+/// [watermarks]
+/// text = "Historical version of {{ date }}, see the current version for the law in force."
+///
+/// [watermarks.stelae."org-name/law"]
+/// enabled = false
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Watermarks {
+    /// Banner of the documents of every stele.
+    #[serde(flatten)]
+    pub defaults: Watermark,
+    /// Per-stele overrides, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, Watermark>>,
+}
+
+/// Banner of historical documents.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Watermark {
+    /// Whether the banner is shown. Defaults to `true`.
+    pub enabled: Option<bool>,
+    /// Text of the banner. Defaults to [`DEFAULT_WATERMARK_TEXT`].
+    pub text: Option<String>,
+}
+
+impl Watermarks {
+    /// Resolve the banner text of historical documents of the stele `stele_name`.
+    ///
+    /// Returns `None` if no banner is shown for the stele.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> Option<String> {
+        let overrides = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name));
+        let enabled = overrides
+            .and_then(|watermark| watermark.enabled)
+            .or(self.defaults.enabled)
+            .unwrap_or(true);
+        enabled.then(|| {
+            overrides
+                .and_then(|watermark| watermark.text.clone())
+                .or_else(|| self.defaults.text.clone())
+                .unwrap_or_else(|| DEFAULT_WATERMARK_TEXT.to_owned())
+        })
+    }
+}
+
+/// Optional configuration of the schema.org `Legislation` JSON-LD emitted into html documents.
+/// Example:
+/// ```toml
+/// [structured_data]
+/// jurisdiction = "US-CA"
+///
+/// [structured_data.stelae."org-name/law"]
+/// jurisdiction = "US-CA-SF"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct StructuredData {
+    /// Structured data of the documents of every stele.
+    #[serde(flatten)]
+    pub defaults: StructuredDataValues,
+    /// Per-stele overrides, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, StructuredDataValues>>,
+}
+
+/// Values of the structured data of documents.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StructuredDataValues {
+    /// Whether structured data is emitted. Defaults to `true`.
+    pub enabled: Option<bool>,
+    /// `legislationJurisdiction` of the documents, e.g. an ISO 3166 code such as `US-CA`.
+    pub jurisdiction: Option<String>,
+}
+
+impl StructuredData {
+    /// Resolve the structured data values of documents of the stele `stele_name`.
+    ///
+    /// Returns `None` if no structured data is emitted for the stele.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> Option<StructuredDataValues> {
+        let overrides = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name));
+        let enabled = overrides
+            .and_then(|values| values.enabled)
+            .or(self.defaults.enabled)
+            .unwrap_or(true);
+        enabled.then(|| StructuredDataValues {
+            enabled: Some(true),
+            jurisdiction: overrides
+                .and_then(|values| values.jurisdiction.clone())
+                .or_else(|| self.defaults.jurisdiction.clone()),
+        })
+    }
+}
+
+/// Plugins run by `stelae update` when no plugins are configured.
+pub const DEFAULT_INGEST_PLUGINS: [&str; 1] = ["references"];
+
+/// Optional ingest configuration for an Archive.
+///
+/// Plugins are run in the configured order, by the name they are registered under.
+/// Example:
+/// ```toml
+/// [ingest]
+/// plugins = ["references"]
+///
+/// [ingest.stelae."org-name/law"]
+/// plugins = []
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Ingest {
+    /// Plugins run for every stele.
+    #[serde(flatten)]
+    pub defaults: IngestPlugins,
+    /// Per-stele overrides, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, IngestPlugins>>,
+}
+
+/// Names of the plugins run by `stelae update`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IngestPlugins {
+    /// Names of the plugins. Defaults to [`DEFAULT_INGEST_PLUGINS`].
+    pub plugins: Option<Vec<String>>,
+}
+
+impl Ingest {
+    /// Resolve the names of the plugins run for the stele `stele_name`.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> Vec<String> {
+        self.stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name))
+            .and_then(|overrides| overrides.plugins.clone())
+            .or_else(|| self.defaults.plugins.clone())
+            .unwrap_or_else(|| DEFAULT_INGEST_PLUGINS.map(ToOwned::to_owned).to_vec())
+    }
+}
+
+/// Default number of attempts to deliver a webhook.
+pub const DEFAULT_WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Optional webhooks notified when `stelae update` ingests a publication.
+///
+/// Payloads are signed with HMAC-SHA256 if a secret is configured, see
+/// [`crate::history::webhooks`].
+/// Example:
+/// ```toml
+/// [webhooks]
+/// urls = ["https://example.com/hooks/stelae"]
+/// secret_env = "STELAE_WEBHOOK_SECRET"
+/// max_attempts = 5
+///
+/// [webhooks.stelae]
+/// "org-name/law" = ["https://example.com/hooks/law"]
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Webhooks {
+    /// Endpoints notified of the publications of every stele.
+    pub urls: Option<Vec<String>>,
+    /// Name of the environment variable holding the secret payloads are signed with.
+    pub secret_env: Option<String>,
+    /// Maximum number of attempts to deliver a payload. Defaults to [`DEFAULT_WEBHOOK_ATTEMPTS`].
+    pub max_attempts: Option<u32>,
+    /// Per-stele endpoints, keyed by the qualified name of the stele. Replace `urls`.
+    pub stelae: Option<HashMap<String, Vec<String>>>,
+}
+
+impl Webhooks {
+    /// Resolve the endpoints notified of the publications of the stele `stele_name`.
+    #[must_use]
+    pub fn urls_for_stele(&self, stele_name: &str) -> Vec<String> {
+        self.stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name))
+            .or(self.urls.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Maximum number of attempts to deliver a payload, at least one.
+    #[must_use]
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(DEFAULT_WEBHOOK_ATTEMPTS).max(1)
+    }
+}
+
+/// Optional digest of the documents changed by `stelae update`, sent per stele.
+///
+/// The digest is mailed to `recipients` through the SMTP relay `smtp`, and posted as JSON
+/// to `webhook`, see [`crate::history::digest`]. The connection to the relay is upgraded with
+/// STARTTLS whenever the relay offers it, and the relay is authenticated with if `username` is
+/// configured, which requires STARTTLS.
+/// Example:
+/// ```toml
+/// [digest]
+/// smtp = "smtp.example.com:587"
+/// username = "stelae"
+/// password_env = "STELAE_SMTP_PASSWORD"
+/// from = "stelae@example.com"
+/// recipients = ["editors@example.com"]
+///
+/// [digest.stelae."org-name/law"]
+/// recipients = ["law-editors@example.com"]
+/// webhook = "https://example.com/hooks/digest"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Digest {
+    /// Address of the SMTP relay digests are mailed through, as `host:port`.
+    pub smtp: Option<String>,
+    /// Sender address of mailed digests. Defaults to [`DEFAULT_DIGEST_SENDER`].
+    pub from: Option<String>,
+    /// Destinations of the digests of every stele.
+    #[serde(flatten)]
+    pub defaults: DigestDestinations,
+    /// Name of the environment variable holding the password of `username`.
+    pub password_env: Option<String>,
+    /// Refuse to mail digests if the SMTP relay does not offer STARTTLS. Defaults to `false`.
+    pub require_tls: Option<bool>,
+    /// Per-stele overrides, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, DigestDestinations>>,
+    /// User name to authenticate with the SMTP relay as.
+    pub username: Option<String>,
+}
+
+/// Destinations of the digest of a stele.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DigestDestinations {
+    /// Addresses the digest is mailed to.
+    pub recipients: Option<Vec<String>>,
+    /// Endpoint the digest is posted to.
+    pub webhook: Option<String>,
+}
+
+/// Default sender address of mailed digests.
+pub const DEFAULT_DIGEST_SENDER: &str = "stelae@localhost";
+
+impl Digest {
+    /// Resolve the destinations of the digest of the stele `stele_name`.
+    ///
+    /// Destinations of the stele replace the defaults one by one.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> DigestDestinations {
+        let overrides = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name));
+        DigestDestinations {
+            recipients: overrides
+                .and_then(|destinations| destinations.recipients.clone())
+                .or_else(|| self.defaults.recipients.clone()),
+            webhook: overrides
+                .and_then(|destinations| destinations.webhook.clone())
+                .or_else(|| self.defaults.webhook.clone()),
+        }
+    }
+
+    /// Sender address of mailed digests.
+    #[must_use]
+    pub fn sender(&self) -> &str {
+        self.from.as_deref().unwrap_or(DEFAULT_DIGEST_SENDER)
+    }
+}
+
+/// Optional thresholds of the disk usage monitoring of `stelae disk-usage`.
+///
+/// A warning is logged when a threshold is crossed, see [`crate::history::disk_usage`].
+/// Example:
+/// ```toml
+/// [disk_usage]
+/// max_growth_percent = 20
+/// min_free_bytes = 10737418240
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct DiskUsage {
+    /// Warn when a repository grew by more than this percentage since its previous recorded size.
+    pub max_growth_percent: Option<u64>,
+    /// Warn when fewer bytes than this are free on the disk of the archive.
+    pub min_free_bytes: Option<u64>,
+}
+
+/// Default time warmed publications and versions are served from the cache for, in seconds.
+pub const DEFAULT_WARMUP_MAX_AGE: u64 = 300;
+
+/// Optional warmup of the cache of `stelae serve --warmup`.
+///
+/// The current publication of every stele, the versions of its root collection and of the
+/// listed documents, and the current blobs of the listed documents are resolved into the cache
+/// before traffic is accepted, see [`crate::server::cache`].
+/// Example:
+/// ```toml
+/// [warmup]
+/// documents = ["us/ca/cities/san-mateo", "us/ca/cities/san-mateo/chapter-1"]
+/// max_age = 600
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Warmup {
+    /// Paths of the hot documents, as in their urls.
+    pub documents: Option<Vec<String>>,
+    /// Time warmed publications and versions are served from the cache for, in seconds.
+    /// Defaults to [`DEFAULT_WARMUP_MAX_AGE`].
+    pub max_age: Option<u64>,
+}
+
+impl Warmup {
+    /// Paths of the hot documents, as in their urls.
+    #[must_use]
+    pub fn documents(&self) -> &[String] {
+        self.documents.as_deref().unwrap_or_default()
+    }
+
+    /// Time warmed publications and versions are served from the cache for.
+    #[must_use]
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age.unwrap_or(DEFAULT_WARMUP_MAX_AGE))
+    }
+}
+
+/// Optional index of the current blobs of the served data repositories.
+///
+/// When enabled, `stelae update` records the path and id of every blob of the `HEAD` commit of
+/// the served data repositories of each stele in the `current_blobs` table, and `stelae serve`
+/// loads them at startup to read current documents by id. A repository whose `HEAD` moved since it
+/// was indexed is looked up in git, until the ids are reloaded after the next update scheduled by
+/// `stelae serve --update-schedule`, or the next restart.
+/// Example:
+/// ```toml
+/// [current_index]
+/// enabled = true
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct CurrentIndex {
+    /// Whether current blobs are indexed and served by id. Defaults to `false`.
+    pub enabled: Option<bool>,
+}
+
+impl CurrentIndex {
+    /// Whether current blobs are indexed and served by id.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+}
+
+/// Optional `Server-Timing` header of responses.
+///
+/// When enabled, every response carries a `Server-Timing` header with the time the request spent
+/// querying the database, reading git repositories and rewriting documents, and in total, so the
+/// latency of a request can be broken down without attaching a tracer.
+/// Example:
+/// ```toml
+/// [server_timing]
+/// enabled = true
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ServerTiming {
+    /// Whether responses carry the `Server-Timing` header. Defaults to `false`.
+    pub enabled: Option<bool>,
+}
+
+impl ServerTiming {
+    /// Whether responses carry the `Server-Timing` header.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+}
+
+/// Optional configuration of the mirrors of the archive.
+///
+/// `stelae git` serves the repositories to mirrors, listed at `/_sync` and fetched over the git
+/// smart HTTP protocol, only if they send the token as an `Authorization: Bearer` header, with
+/// `stelae mirror --token-file`.
+/// Example:
+/// ```toml
+/// [mirror]
+/// token = "a-long-random-secret"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Mirror {
+    /// Token that mirrors send in the `Authorization: Bearer` header.
+    pub token: String,
+}
+
+/// Directory of the databases of the stelae isolated with `per_stele`, relative to the archive.
+pub const STELE_DATABASES_DIR: &str = ".taf/stelae";
+
+/// Optional isolation of the change data of stelae in their own databases.
+///
+/// With `per_stele`, the change data of every stele is stored in its own `SQLite` database at
+/// `.taf/stelae/{org}/{name}.sqlite3`, so queries of one stele are not slowed by the history of
+/// another. A stele listed under `[database.stelae]` is stored in the database at its url instead,
+/// e.g. to relocate it to another disk. Takedowns, snapshots and disk usage are kept in the
+/// database of the archive.
+/// Example:
+/// ```toml
+/// [database]
+/// per_stele = true
+///
+/// [database.stelae]
+/// "us-ca/law" = "sqlite:///mnt/large/us-ca-law.sqlite3?mode=rwc"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Database {
+    /// Store the change data of every stele in its own database.
+    pub per_stele: Option<bool>,
+    /// Urls of the databases of individual stelae, keyed by qualified name.
+    pub stelae: Option<HashMap<String, String>>,
+}
+
+impl Database {
+    /// Resolve the url of the database of the stele `stele_name`, if its change data is isolated.
+    #[must_use]
+    pub fn url_for_stele(&self, archive_path: &Path, stele_name: &str) -> Option<String> {
+        self.stelae
+            .as_ref()
+            .and_then(|urls| urls.get(stele_name))
+            .cloned()
+            .or_else(|| {
+                self.per_stele.unwrap_or(false).then(|| {
+                    let path = archive_path
+                        .join(STELE_DATABASES_DIR)
+                        .join(format!("{stele_name}.sqlite3"));
+                    format!("sqlite://{}?mode=rwc", path.to_string_lossy())
+                })
+            })
+    }
+}
+
+/// Optional configuration of the serving of preview publications, which are flagged
+/// `oll:preview "true"` in their RDF and left out of the versions of a stele by default.
+/// Example:
+/// ```toml
+/// [preview]
+/// token = "a-long-random-secret"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Preview {
+    /// Token that requests send in the `X-Stelae-Preview` header to be served previews.
+    pub token: String,
+}
+
+impl Preview {
+    /// Whether the preview token sent by a request, if any, grants access to previews.
+    ///
+    /// The tokens are compared in constant time, see [`secrets_match`]. An empty configured token
+    /// grants no access.
+    #[must_use]
+    pub fn allows(&self, token: Option<&str>) -> bool {
+        token.is_some_and(|given| secrets_match(&self.token, given))
+    }
+}
+
+/// Optional configuration of the approval of publications, with per-stele overrides.
+///
+/// Publications ingested by `stelae update` into a stele that requires approval are not served
+/// until they are approved through `POST /_admin/publications/{name}/state`. Approved publications
+/// are served like previews, only to requests that send the `[preview]` token, until they are
+/// moved to `live` and served to every request.
+/// Example:
+/// ```toml
+/// [approval]
+/// required = true
+///
+/// [approval.stelae]
+/// "org-name/law" = false
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Approval {
+    /// Whether the publications of every stele require approval. Defaults to `false`.
+    pub required: Option<bool>,
+    /// Per-stele overrides of `required`, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, bool>>,
+}
+
+impl Approval {
+    /// Whether the publications of the stele `stele_name` require approval.
+    #[must_use]
+    pub fn is_required(&self, stele_name: &str) -> bool {
+        self.stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name).copied())
+            .or(self.required)
+            .unwrap_or(false)
+    }
 }
 
 /// Optional Header configuration for an Archive
@@ -178,6 +1219,109 @@ pub struct Headers {
     pub current_documents_guard: Option<String>,
 }
 
+/// Security header configuration for an Archive.
+///
+/// Headers left unset fall back to a default, and headers set to an empty string are not sent.
+/// Example:
+/// ```toml
+/// [security_headers]
+/// content_security_policy = "default-src 'self'"
+///
+/// [security_headers.stelae."org-name/law"]
+/// frame_ancestors = "https://example.com"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct SecurityHeaders {
+    /// Headers applied to the documents of every stele.
+    #[serde(flatten)]
+    pub defaults: SecurityHeaderValues,
+    /// Per-stele overrides, keyed by the qualified name of the stele.
+    pub stelae: Option<HashMap<String, SecurityHeaderValues>>,
+}
+
+/// Values of the security headers.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct SecurityHeaderValues {
+    /// `Content-Security-Policy`, without the `frame-ancestors` directive. Not sent by default.
+    pub content_security_policy: Option<String>,
+    /// Sources of the `frame-ancestors` directive of the `Content-Security-Policy`. Defaults to `'self'`.
+    pub frame_ancestors: Option<String>,
+    /// `X-Content-Type-Options`. Defaults to `nosniff`.
+    pub x_content_type_options: Option<String>,
+    /// `Referrer-Policy`. Defaults to `strict-origin-when-cross-origin`.
+    pub referrer_policy: Option<String>,
+}
+
+impl SecurityHeaders {
+    /// Resolve the security headers sent with the documents of the stele `stele_name`.
+    ///
+    /// Returns header names and values.
+    #[must_use]
+    pub fn for_stele(&self, stele_name: &str) -> Vec<(&'static str, String)> {
+        let overrides = self
+            .stelae
+            .as_ref()
+            .and_then(|stelae| stelae.get(stele_name));
+        let resolve = |get: fn(&SecurityHeaderValues) -> Option<&String>, default: &str| {
+            overrides
+                .and_then(get)
+                .or_else(|| get(&self.defaults))
+                .map_or_else(|| default.to_owned(), ToOwned::to_owned)
+        };
+        let frame_ancestors = resolve(|values| values.frame_ancestors.as_ref(), "'self'");
+        let content_security_policy = [
+            resolve(|values| values.content_security_policy.as_ref(), ""),
+            if frame_ancestors.is_empty() {
+                String::new()
+            } else {
+                format!("frame-ancestors {frame_ancestors}")
+            },
+        ]
+        .into_iter()
+        .filter(|directives| !directives.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+        [
+            ("Content-Security-Policy", content_security_policy),
+            (
+                "X-Content-Type-Options",
+                resolve(|values| values.x_content_type_options.as_ref(), "nosniff"),
+            ),
+            (
+                "Referrer-Policy",
+                resolve(
+                    |values| values.referrer_policy.as_ref(),
+                    "strict-origin-when-cross-origin",
+                ),
+            ),
+        ]
+        .into_iter()
+        .filter(|header| !header.1.is_empty())
+        .collect()
+    }
+
+    /// Check that the headers resolved for every stele are valid header values.
+    ///
+    /// # Errors
+    /// Errors with the first header that is not a valid header value, e.g. contains a newline.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let overridden = self.stelae.iter().flat_map(HashMap::keys);
+        for stele_name in iter::once(&String::new()).chain(overridden) {
+            for (name, value) in self.for_stele(stele_name) {
+                HeaderValue::from_str(&value).with_context(|| {
+                    let configured = if stele_name.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" of {stele_name}")
+                    };
+                    format!("Invalid {name} header{configured}: {value:?}")
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Create a new Stelae Archive at path, and return the new archive.
 /// # Errors
 /// Will error if archive is created inside of an existing archive.
@@ -201,12 +1345,381 @@ pub fn init(
         },
         shallow,
         headers,
+        security_headers: None,
+        limits: None,
+        concurrency: None,
+        timeouts: None,
+        locales: None,
+        auth: None,
+        access_log: None,
+        proxy: None,
+        document_views: None,
+        watermarks: None,
+        structured_data: None,
+        ingest: None,
+        webhooks: None,
+        digest: None,
+        disk_usage: None,
+        mirror: None,
+        warmup: None,
+        database: None,
+        aliases: None,
+        preview: None,
+        approval: None,
+        current_index: None,
+        server_timing: None,
     };
     let conf_str = ser::to_string_pretty(&conf)?;
     write(config_path, conf_str)?;
     let archive = Archive {
         path,
         stelae: HashMap::new(),
+        aliases: HashMap::new(),
     };
     Ok(Box::new(archive))
 }
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::stelae::archive::{
+        merge_config, resolve_alias, resolve_scope, AccessLog, Approval, Auth, Database, Digest,
+        DigestDestinations, Ingest, IngestPlugins, Locales, Preview, Role, SecurityHeaderValues,
+        SecurityHeaders, Timeouts, Watermark, Watermarks, Webhooks, DEFAULT_DIGEST_SENDER,
+        DEFAULT_WATERMARK_TEXT,
+    };
+    use crate::utils::locale::Locale;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_merge_config_when_local_overrides_expect_tables_merged_and_values_replaced() {
+        let cut = merge_config;
+        let mut config: toml::Table = toml::from_str(
+            r#"
+            shallow = false
+            [root]
+            org = "org"
+            name = "law"
+            [headers]
+            current_documents_guard = "X-Current-Documents-Guard"
+            [proxy]
+            trusted = ["10.0.0.0/8"]
+            "#,
+        )
+        .unwrap();
+        let local: toml::Table = toml::from_str(
+            r#"
+            shallow = true
+            [headers]
+            current_documents_guard = "X-Local-Guard"
+            [proxy]
+            trusted = ["127.0.0.1"]
+            "#,
+        )
+        .unwrap();
+        cut(&mut config, local);
+        let expected: toml::Table = toml::from_str(
+            r#"
+            shallow = true
+            [root]
+            org = "org"
+            name = "law"
+            [headers]
+            current_documents_guard = "X-Local-Guard"
+            [proxy]
+            trusted = ["127.0.0.1"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn test_for_stele_when_unset_expect_defaults() {
+        let cut = SecurityHeaders::default();
+        let actual = cut.for_stele("test_org/law");
+        let expected = vec![
+            (
+                "Content-Security-Policy",
+                "frame-ancestors 'self'".to_owned(),
+            ),
+            ("X-Content-Type-Options", "nosniff".to_owned()),
+            (
+                "Referrer-Policy",
+                "strict-origin-when-cross-origin".to_owned(),
+            ),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_for_stele_when_overridden_expect_override_and_empty_omitted() {
+        let cut = SecurityHeaders {
+            defaults: SecurityHeaderValues {
+                content_security_policy: Some("default-src 'self'".to_owned()),
+                referrer_policy: Some(String::new()),
+                ..SecurityHeaderValues::default()
+            },
+            stelae: Some(HashMap::from([(
+                "test_org/law".to_owned(),
+                SecurityHeaderValues {
+                    frame_ancestors: Some("https://example.com".to_owned()),
+                    ..SecurityHeaderValues::default()
+                },
+            )])),
+        };
+        let actual = cut.for_stele("test_org/law");
+        let expected = vec![
+            (
+                "Content-Security-Policy",
+                "default-src 'self'; frame-ancestors https://example.com".to_owned(),
+            ),
+            ("X-Content-Type-Options", "nosniff".to_owned()),
+        ];
+        assert_eq!(actual, expected);
+        let other = cut.for_stele("test_org/other");
+        assert_eq!(other[0].1, "default-src 'self'; frame-ancestors 'self'");
+    }
+
+    #[test]
+    fn test_url_for_stele_when_per_stele_or_relocated_expect_isolated_url() {
+        let archive_path = Path::new("/archive");
+        let cut = Database {
+            per_stele: Some(true),
+            stelae: Some(HashMap::from([(
+                "us-ca/law".to_owned(),
+                "sqlite:///mnt/large/us-ca-law.sqlite3?mode=rwc".to_owned(),
+            )])),
+        };
+        assert_eq!(
+            cut.url_for_stele(archive_path, "us-ca/law").as_deref(),
+            Some("sqlite:///mnt/large/us-ca-law.sqlite3?mode=rwc")
+        );
+        assert_eq!(
+            cut.url_for_stele(archive_path, "us-ny/law").as_deref(),
+            Some("sqlite:///archive/.taf/stelae/us-ny/law.sqlite3?mode=rwc")
+        );
+        assert_eq!(
+            Database::default().url_for_stele(archive_path, "us-ny/law"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_scope_when_scopes_nested_or_shared_expect_most_specific_or_error() {
+        let cut = resolve_scope;
+        let scopes = [
+            ("org/county".to_owned(), "sub/scope".to_owned()),
+            ("org/city_1".to_owned(), "sub/scope/1".to_owned()),
+            ("org/city_2".to_owned(), "/sub/scope/2/".to_owned()),
+            ("org/city_3".to_owned(), "sub/scope/2".to_owned()),
+        ];
+        assert_eq!(cut("sub/scope/1", &scopes).unwrap(), "org/city_1");
+        assert_eq!(cut("/sub/scope/1/a/", &scopes).unwrap(), "org/city_1");
+        assert_eq!(cut("sub/scope/10", &scopes).unwrap(), "org/county");
+        assert_eq!(cut("sub/scope", &scopes).unwrap(), "org/county");
+        let ambiguous = cut("sub/scope/2", &scopes).unwrap_err().to_string();
+        assert!(ambiguous.contains("org/city_2, org/city_3"), "{ambiguous}");
+        assert!(cut("sub", &scopes).is_err());
+        assert!(cut("/", &scopes).is_err());
+    }
+
+    #[test]
+    fn test_resolve_alias_when_former_name_expect_current_name() {
+        let cut = resolve_alias;
+        let aliases = HashMap::from([("city-of-x/law".to_owned(), "x-city/law".to_owned())]);
+        assert_eq!(cut("city-of-x/law", &aliases), "x-city/law");
+        assert_eq!(cut("x-city/law", &aliases), "x-city/law");
+        assert_eq!(cut("other/law", &aliases), "other/law");
+    }
+
+    #[test]
+    fn test_for_path_when_routes_configured_expect_longest_prefix() {
+        let cut = Timeouts {
+            default: Some(10),
+            routes: Some(HashMap::from([
+                ("/_api".to_owned(), 20),
+                ("/_api/versions".to_owned(), 60),
+            ])),
+        };
+        assert_eq!(cut.for_path("/_api/versions/a/"), Duration::from_secs(60));
+        assert_eq!(cut.for_path("/_api/suggest"), Duration::from_secs(20));
+        assert_eq!(cut.for_path("/a/b/"), Duration::from_secs(10));
+        assert_eq!(
+            Timeouts::default().for_path("/a/b/"),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_for_stele_when_locales_configured_expect_override_or_default() {
+        let cut = Locales {
+            default: Some("fr".to_owned()),
+            stelae: Some(HashMap::from([
+                ("test_org/law".to_owned(), "es-MX".to_owned()),
+                ("test_org/other".to_owned(), "xx".to_owned()),
+            ])),
+        };
+        assert_eq!(cut.for_stele("test_org/law"), Locale::Es);
+        assert_eq!(cut.for_stele("test_org/another"), Locale::Fr);
+        assert_eq!(cut.for_stele("test_org/other"), Locale::En);
+        assert_eq!(Locales::default().for_stele("test_org/law"), Locale::En);
+    }
+
+    #[test]
+    fn test_role_for_when_groups_mapped_expect_highest_role() {
+        let cut = Auth {
+            roles: Some(HashMap::from([
+                ("admins".to_owned(), Role::Admin),
+                ("staff".to_owned(), Role::Reader),
+            ])),
+            stelae: Some(HashMap::from([(
+                "test_org/law".to_owned(),
+                HashMap::from([("staff".to_owned(), Role::Admin)]),
+            )])),
+            ..Auth::default()
+        };
+        let staff = vec!["staff".to_owned(), "other".to_owned()];
+        assert_eq!(cut.role_for("test_org/law", &staff), Some(Role::Admin));
+        assert_eq!(cut.role_for("test_org/other", &staff), Some(Role::Reader));
+        assert_eq!(cut.role_for("test_org/law", &["other".to_owned()]), None);
+    }
+
+    #[test]
+    fn test_is_enabled_when_route_or_stele_switched_off_expect_not_logged() {
+        let cut = AccessLog {
+            routes: Some(HashMap::from([
+                ("/_admin/".to_owned(), false),
+                ("/_admin/public/".to_owned(), true),
+            ])),
+            stelae: Some(HashMap::from([("test_org/private".to_owned(), false)])),
+            ..AccessLog::default()
+        };
+        assert!(cut.is_enabled("/a/b/c.html", "test_org/law"));
+        assert!(!cut.is_enabled("/_admin/broken-links", "test_org/law"));
+        assert!(cut.is_enabled("/_admin/public/status", "test_org/law"));
+        assert!(!cut.is_enabled("/a/b/c.html", "test_org/private"));
+    }
+
+    #[test]
+    fn test_for_stele_when_watermark_overridden_expect_stele_text_or_none() {
+        let cut = Watermarks {
+            defaults: Watermark::default(),
+            stelae: Some(HashMap::from([
+                (
+                    "test_org/law".to_owned(),
+                    Watermark {
+                        text: Some("As of {{ date }}".to_owned()),
+                        ..Watermark::default()
+                    },
+                ),
+                (
+                    "test_org/other".to_owned(),
+                    Watermark {
+                        enabled: Some(false),
+                        ..Watermark::default()
+                    },
+                ),
+            ])),
+        };
+        assert_eq!(cut.for_stele("test_org/law").unwrap(), "As of {{ date }}");
+        assert_eq!(cut.for_stele("test_org/other"), None);
+        assert_eq!(
+            cut.for_stele("test_org/third").unwrap(),
+            DEFAULT_WATERMARK_TEXT
+        );
+    }
+
+    #[test]
+    fn test_for_stele_when_ingest_plugins_overridden_expect_override_or_default() {
+        let cut = Ingest {
+            defaults: IngestPlugins::default(),
+            stelae: Some(HashMap::from([(
+                "test_org/law".to_owned(),
+                IngestPlugins {
+                    plugins: Some(vec![]),
+                },
+            )])),
+        };
+        assert!(cut.for_stele("test_org/law").is_empty());
+        assert_eq!(cut.for_stele("test_org/other"), vec!["references"]);
+    }
+
+    #[test]
+    fn test_urls_for_stele_when_stele_overridden_expect_override_or_defaults() {
+        let cut = Webhooks {
+            urls: Some(vec!["https://example.com/hooks".to_owned()]),
+            max_attempts: Some(0),
+            stelae: Some(HashMap::from([(
+                "test_org/law".to_owned(),
+                vec!["https://example.com/law".to_owned()],
+            )])),
+            ..Webhooks::default()
+        };
+        assert_eq!(
+            cut.urls_for_stele("test_org/law"),
+            vec!["https://example.com/law"]
+        );
+        assert_eq!(
+            cut.urls_for_stele("test_org/other"),
+            vec!["https://example.com/hooks"]
+        );
+        assert!(Webhooks::default()
+            .urls_for_stele("test_org/law")
+            .is_empty());
+        assert_eq!(cut.max_attempts(), 1);
+    }
+
+    #[test]
+    fn test_for_stele_when_digest_overridden_expect_destinations_merged() {
+        let cut = Digest {
+            defaults: DigestDestinations {
+                recipients: Some(vec!["editors@example.com".to_owned()]),
+                webhook: Some("https://example.com/hooks/digest".to_owned()),
+            },
+            stelae: Some(HashMap::from([(
+                "test_org/law".to_owned(),
+                DigestDestinations {
+                    recipients: Some(vec!["law@example.com".to_owned()]),
+                    webhook: None,
+                },
+            )])),
+            ..Digest::default()
+        };
+        let law = cut.for_stele("test_org/law");
+        assert_eq!(law.recipients.unwrap(), vec!["law@example.com"]);
+        assert_eq!(law.webhook.unwrap(), "https://example.com/hooks/digest");
+        assert_eq!(cut.for_stele("test_org/other"), cut.defaults);
+        assert_eq!(cut.sender(), DEFAULT_DIGEST_SENDER);
+    }
+
+    #[test]
+    fn test_allows_when_token_matches_expect_true() {
+        let cut = Preview {
+            token: "secret".to_owned(),
+        };
+        assert!(cut.allows(Some("secret")));
+        assert!(!cut.allows(Some("guess")));
+        assert!(!cut.allows(Some("secre")));
+        assert!(!cut.allows(Some("secrets")));
+        assert!(!cut.allows(None));
+        assert!(!Preview::default().allows(Some("")));
+    }
+
+    #[test]
+    fn test_is_required_when_stele_overridden_expect_override_or_default() {
+        let cut = Approval {
+            required: Some(true),
+            stelae: Some(HashMap::from([("test_org/law".to_owned(), false)])),
+        };
+        assert!(!cut.is_required("test_org/law"));
+        assert!(cut.is_required("test_org/other"));
+        assert!(!Approval::default().is_required("test_org/law"));
+    }
+}
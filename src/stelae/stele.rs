@@ -5,7 +5,9 @@ use std::path::{Path, PathBuf};
 
 use super::types::{repositories::Repository, targets_metadata::TargetsMetadata};
 use crate::{
-    stelae::types::{dependencies::Dependencies, repositories::Repositories},
+    stelae::types::{
+        dependencies::Dependencies, identifiers::Identifiers, repositories::Repositories,
+    },
     utils::git::Repo,
 };
 use anyhow::Context as _;
@@ -62,7 +64,12 @@ impl Stele {
                 path: path.join(&name),
                 org,
                 name: name.clone(),
-                repo: GitRepository::open(path.join(&name))?,
+                repo: GitRepository::open(path.join(&name)).with_context(|| {
+                    format!(
+                        "could not open the authentication repository at {}",
+                        path.join(&name).display()
+                    )
+                })?,
             },
         };
         stele.get_repositories()?;
@@ -78,24 +85,54 @@ impl Stele {
             .get_bytes_at_path("HEAD", "targets/dependencies.json");
         if let Ok(dependencies_blob) = blob {
             let dependencies_str = String::from_utf8(dependencies_blob)?;
-            let dependencies = serde_json::from_str(&dependencies_str)?;
+            let dependencies = serde_json::from_str(&dependencies_str)
+                .context("could not parse targets/dependencies.json")?;
             return Ok(Some(dependencies));
         }
         Ok(None)
     }
+    /// Get Stele's persistent identifiers.
+    /// # Errors
+    /// Will error if unable to parse identifiers file from `targets/identifiers.json`
+    pub fn get_identifiers(&self) -> anyhow::Result<Option<Identifiers>> {
+        let Ok(blob) = self
+            .auth_repo
+            .get_bytes_at_path("HEAD", "targets/identifiers.json")
+        else {
+            return Ok(None);
+        };
+        let identifiers_str = String::from_utf8(blob)?;
+        let identifiers = serde_json::from_str(&identifiers_str)?;
+        Ok(Some(identifiers))
+    }
     /// Get Stele's repositories.
     /// # Errors
     /// Will error if unable to find or parse repositories file at `targets/repositories.json`
     pub fn get_repositories(&mut self) -> anyhow::Result<Option<Repositories>> {
+        let repositories = self.get_repositories_at_commit("HEAD")?;
+        self.repositories.clone_from(&repositories);
+        Ok(repositories)
+    }
+
+    /// Get Stele's repositories as of the authentication commit `committish`.
+    ///
+    /// Repositories removed from `targets/repositories.json` since are still listed, so the
+    /// content of older publications can be served from them.
+    /// # Errors
+    /// Will error if unable to parse the repositories file at `targets/repositories.json` of the commit
+    pub fn get_repositories_at_commit(
+        &self,
+        committish: &str,
+    ) -> anyhow::Result<Option<Repositories>> {
         let Ok(blob) = self
             .auth_repo
-            .get_bytes_at_path("HEAD", "targets/repositories.json")
+            .get_bytes_at_path(committish, "targets/repositories.json")
         else {
             return Ok(None);
         };
         let repositories_str = String::from_utf8(blob)?;
-        let repositories: Repositories = serde_json::from_str(&repositories_str)?;
-        self.repositories = Some(repositories.clone());
+        let repositories: Repositories = serde_json::from_str(&repositories_str)
+            .context("could not parse targets/repositories.json")?;
         Ok(Some(repositories))
     }
 
@@ -0,0 +1,21 @@
+//! A Stele's persistent identifiers.
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Persistent identifiers of a Stele's documents, as specified in its `identifiers.json` file.
+///
+/// Example:
+/// ```json
+/// {
+///     "identifiers": {
+///         "eli/us/ca/code/title/1/2023-10-22": "us/ca/code/title-1",
+///         "urn:lex:us-ca:code:title.1": "us/ca/code/title-1"
+///     }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Identifiers {
+    /// Map of identifiers, e.g. European Legislation Identifiers (ELI) without a leading `/`, or
+    /// URN:LEX names, to the paths of the documents they identify.
+    pub identifiers: BTreeMap<String, String>,
+}
@@ -1,5 +1,6 @@
 //! The `types` module contains data models for stelae.
 
 pub mod dependencies;
+pub mod identifiers;
 pub mod repositories;
 pub mod targets_metadata;
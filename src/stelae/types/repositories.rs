@@ -1,5 +1,9 @@
 //! A Stele's data repositories.
-use std::{collections::HashMap, fmt, string::String};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, iter,
+    string::String,
+};
 
 use serde::{
     de::{self, MapAccess, Visitor},
@@ -80,10 +84,20 @@ impl Repository {
         self.name.split('/').nth(1).unwrap_or_default().to_owned()
     }
 
-    /// Get the type of the repository.
+    /// Get the type of the repository, as stored in the database.
     #[must_use]
     pub fn get_type(&self) -> Option<String> {
-        self.custom.repository_type.clone()
+        self.custom
+            .repository_type
+            .as_ref()
+            .map(|repository_type| repository_type.as_str().to_owned())
+    }
+
+    /// Whether documents are served from the repository, on its routes, its scope or as fallback.
+    #[must_use]
+    pub fn is_served(&self) -> bool {
+        let custom = &self.custom;
+        custom.routes.is_some() || custom.scope.is_some() || custom.is_fallback == Some(true)
     }
 }
 
@@ -92,9 +106,9 @@ impl Repository {
 pub struct Custom {
     #[serde(rename = "type")]
     /// Type of data repository. e.g. `rdf`, `html`, `pdf`, `xml`, or any other.
-    pub repository_type: Option<String>,
-    /// "latest" or "historical". Currently not used by the framework.
-    pub serve: String,
+    pub repository_type: Option<RepositoryType>,
+    /// Whether the latest or all historical versions of the repository are served.
+    pub serve: ServeType,
     /// Vector of glob patterns used by the Actix framework to resolve url routing.
     /// Routing to use when locating current blobs from the data repository.
     /// Example:
@@ -114,6 +128,327 @@ pub struct Custom {
     ///
     /// When a data repository is a fallback, it is used to serve current blobs when no other data repository matches the request.
     pub is_fallback: Option<bool>,
+    /// Path of a layout template in the data repository, e.g. `_layouts/default.html`.
+    ///
+    /// When set, html fragments served from the data repository are wrapped in the layout,
+    /// read from the same commit as the fragment. See [`crate::utils::template`].
+    pub layout: Option<String>,
+    /// Whether directories of the data repository are served as listings of their entries.
+    ///
+    /// Meant for repositories that are buckets of files, e.g. pdf scans. A request for a directory
+    /// without an index document is answered with a listing, in html or in json.
+    pub directory_listing: Option<bool>,
+    /// Elements injected into historical html documents served from the data repository, e.g.
+    /// markers for frontends. See [`Injection`].
+    pub injections: Option<Vec<Injection>>,
+    /// Languages the documents of the data repository are available in, see [`Languages`].
+    ///
+    /// When set, current documents are served in the language negotiated with the
+    /// `Accept-Language` header or the `?lang=` query parameter.
+    pub languages: Option<Languages>,
+}
+
+/// Type of a data repository, the `type` custom field.
+///
+/// Types unknown to stelae are kept as [`RepositoryType::Other`], and reported as deprecated by
+/// [`Repositories::deprecations`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum RepositoryType {
+    /// Html documents.
+    Html,
+    /// RDF graph of the publications and documents of the stele.
+    Rdf,
+    /// Pdf documents.
+    Pdf,
+    /// Xml documents.
+    Xml,
+    /// Any other type, as declared.
+    Other(String),
+}
+
+impl RepositoryType {
+    /// The type as declared in `repositories.json`, and as stored in the database.
+    #[must_use]
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "Matching the borrowed type is clearer than matching with `ref` patterns"
+    )]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Html => "html",
+            Self::Rdf => "rdf",
+            Self::Pdf => "pdf",
+            Self::Xml => "xml",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl From<String> for RepositoryType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "html" => Self::Html,
+            "rdf" => Self::Rdf,
+            "pdf" => Self::Pdf,
+            "xml" => Self::Xml,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl From<RepositoryType> for String {
+    fn from(value: RepositoryType) -> Self {
+        match value {
+            RepositoryType::Other(other) => other,
+            known @ (RepositoryType::Html
+            | RepositoryType::Rdf
+            | RepositoryType::Pdf
+            | RepositoryType::Xml) => known.as_str().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for RepositoryType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// How a data repository is served, the `serve` custom field.
+///
+/// Values unknown to stelae are kept as [`ServeType::Other`], and reported as deprecated by
+/// [`Repositories::deprecations`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum ServeType {
+    /// Only the latest version of the repository is served.
+    #[default]
+    Latest,
+    /// Every version of the repository is served, at the commit mapped to each date.
+    Historical,
+    /// Any other value, as declared.
+    Other(String),
+}
+
+impl ServeType {
+    /// The serve type as declared in `repositories.json`.
+    #[must_use]
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "Matching the borrowed type is clearer than matching with `ref` patterns"
+    )]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Latest => "latest",
+            Self::Historical => "historical",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl From<String> for ServeType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "latest" => Self::Latest,
+            "historical" => Self::Historical,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl From<ServeType> for String {
+    fn from(value: ServeType) -> Self {
+        match value {
+            ServeType::Other(other) => other,
+            known @ (ServeType::Latest | ServeType::Historical) => known.as_str().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ServeType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// Html elements that are void, and cannot have content.
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Html elements that structure the document, and cannot be injected.
+const DOCUMENT_ELEMENTS: [&str; 3] = ["html", "head", "body"];
+
+/// Element injected into historical html documents.
+///
+/// `{{ date }}` in attribute values and content is replaced by the `%Y-%m-%d` date of the
+/// document. Injections are validated by `stelae update`, see [`Injection::validate`].
+/// Example:
+///
+/// ```json
+/// {"tag": "meta", "attrs": {"name": "stelae:date", "content": "{{ date }}"}, "position": "head-end"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Injection {
+    /// Name of the element, e.g. `meta` or `script`.
+    pub tag: String,
+    /// Attributes of the element. Values are escaped.
+    #[serde(default)]
+    pub attrs: BTreeMap<String, String>,
+    /// Html content of the element, e.g. the configuration of a `script`. Not escaped.
+    pub content: Option<String>,
+    /// Where the element is injected. Defaults to the end of the head.
+    #[serde(default)]
+    pub position: Position,
+}
+
+/// Position of an injected element in the document.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Position {
+    /// Start of the `<head>`.
+    HeadStart,
+    /// End of the `<head>`.
+    #[default]
+    HeadEnd,
+    /// Start of the `<body>`.
+    BodyStart,
+    /// End of the `<body>`.
+    BodyEnd,
+}
+
+impl Injection {
+    /// Whether the injected element is void, and has no content or end tag.
+    #[must_use]
+    pub fn is_void(&self) -> bool {
+        VOID_ELEMENTS.contains(&self.tag.to_ascii_lowercase().as_str())
+    }
+
+    /// Validate that the injection renders a single well-formed element.
+    ///
+    /// # Errors
+    /// Errors if the tag or an attribute name is not a valid html name, if the tag structures
+    /// the document, if a void element has content, or if the content ends the element.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let tag = &self.tag;
+        let is_tag_name = tag
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic())
+            && tag
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-');
+        if !is_tag_name {
+            anyhow::bail!("invalid tag name `{tag}`");
+        }
+        if DOCUMENT_ELEMENTS.contains(&tag.to_ascii_lowercase().as_str()) {
+            anyhow::bail!("`<{tag}>` cannot be injected");
+        }
+        if let Some(name) = self.attrs.keys().find(|name| !is_attribute_name(name)) {
+            anyhow::bail!("invalid attribute name `{name}` of `<{tag}>`");
+        }
+        if let Some(content) = self.content.as_deref() {
+            if self.is_void() {
+                anyhow::bail!("void element `<{tag}>` cannot have content");
+            }
+            if content
+                .to_ascii_lowercase()
+                .contains(&format!("</{}", tag.to_ascii_lowercase()))
+            {
+                anyhow::bail!("content of `<{tag}>` cannot contain its end tag");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Languages the documents of a data repository are available in, and how their variants are named.
+///
+/// Documents without a language in their name are in the `default` language.
+/// Example:
+///
+/// ```json
+/// {"default": "en", "variants": ["es", "fr"], "naming": "suffix"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Languages {
+    /// Language of the documents, e.g. `en`.
+    pub default: String,
+    /// Other languages documents may be available in, e.g. `["es", "fr"]`.
+    #[serde(default)]
+    pub variants: Vec<String>,
+    /// How the variants of a document are named. Defaults to a suffix.
+    #[serde(default)]
+    pub naming: LanguageNaming,
+}
+
+/// Naming convention of the language variants of documents.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LanguageNaming {
+    /// The language precedes the extension, e.g. `a/b/c/index.es.html` or `a/b/c.es.html`.
+    #[default]
+    Suffix,
+    /// The language is the first segment of the path, e.g. `es/a/b/c/index.html`.
+    Prefix,
+}
+
+impl Languages {
+    /// Find the declared language matching the language tag `tag`, e.g. `es` for `es-MX`.
+    ///
+    /// Tags are compared case-insensitively, exact matches first, then by their primary subtag.
+    #[must_use]
+    pub fn find(&self, tag: &str) -> Option<&str> {
+        let primary = |language: &str| {
+            language
+                .split('-')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+        let declared = || iter::once(&self.default).chain(&self.variants);
+        declared()
+            .find(|language| language.eq_ignore_ascii_case(tag))
+            .or_else(|| declared().find(|language| primary(language) == primary(tag)))
+            .map(String::as_str)
+    }
+
+    /// Paths of the variant in `language` of the document at `path`, in order of preference.
+    #[must_use]
+    pub fn variant_paths(&self, path: &str, language: &str) -> Vec<String> {
+        let trimmed = path.trim_matches('/');
+        match self.naming {
+            LanguageNaming::Prefix => vec![format!("{language}/{trimmed}")],
+            LanguageNaming::Suffix => {
+                let (directory, name) = trimmed.rsplit_once('/').unwrap_or(("", trimmed));
+                let parent = if directory.is_empty() {
+                    String::new()
+                } else {
+                    format!("{directory}/")
+                };
+                match name.rsplit_once('.') {
+                    Some((stem, extension)) if !stem.is_empty() => {
+                        vec![format!("{parent}{stem}.{language}.{extension}")]
+                    }
+                    _ if name.is_empty() => vec![format!("index.{language}.html")],
+                    _ => vec![
+                        format!("{trimmed}/index.{language}.html"),
+                        format!("{trimmed}.{language}.html"),
+                    ],
+                }
+            }
+        }
+    }
+}
+
+/// Whether `name` is a valid html attribute name.
+fn is_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.chars().any(|ch| {
+            ch.is_whitespace() || ch.is_control() || matches!(ch, '"' | '\'' | '>' | '/' | '=')
+        })
 }
 
 impl Repositories {
@@ -144,19 +479,19 @@ impl Repositories {
 
     /// Filter and return a `Repository` by it's custom type.
     #[must_use]
-    pub fn get_one_by_custom_type(&self, repository_type: &str) -> Option<&Repository> {
-        self.repositories.values().find(|repository| {
-            repository.custom.repository_type.as_deref() == Some(repository_type)
-        })
+    pub fn get_one_by_custom_type(&self, repository_type: &RepositoryType) -> Option<&Repository> {
+        self.repositories
+            .values()
+            .find(|repository| repository.custom.repository_type.as_ref() == Some(repository_type))
     }
 
     /// Filter and return a `Repository` by it's serve type.
     #[must_use]
-    pub fn get_all_by_custom_type(&self, repository_type: &str) -> Vec<&Repository> {
+    pub fn get_all_by_custom_type(&self, repository_type: &RepositoryType) -> Vec<&Repository> {
         self.repositories
             .values()
             .filter(|repository| {
-                repository.custom.repository_type.as_deref() == Some(repository_type)
+                repository.custom.repository_type.as_ref() == Some(repository_type)
             })
             .collect()
     }
@@ -166,7 +501,7 @@ impl Repositories {
     /// Example:
     /// ```rust
     /// use serde_json::json;
-    /// use stelae::stelae::types::repositories::Repositories;
+    /// use stelae::stelae::types::repositories::{Repositories, ServeType};
     ///
     /// let data = r#"
     /// {
@@ -189,17 +524,61 @@ impl Repositories {
     /// }
     /// "#;
     /// let repositories: Repositories = serde_json::from_str(data).unwrap();
-    /// let serve_type = "latest";
-    /// let repos = repositories.get_all_by_serve_type(serve_type);
+    /// let repos = repositories.get_all_by_serve_type(&ServeType::Latest);
     /// assert_eq!(repos.len(), 2);
     /// ```
     #[must_use]
-    pub fn get_all_by_serve_type(&self, serve_type: &str) -> Vec<&Repository> {
+    pub fn get_all_by_serve_type(&self, serve_type: &ServeType) -> Vec<&Repository> {
         self.repositories
             .values()
-            .filter(|repository| repository.custom.serve == serve_type)
+            .filter(|repository| repository.custom.serve == *serve_type)
             .collect()
     }
+
+    /// Deprecation warnings for the custom fields of every repository with a value unknown to
+    /// stelae, e.g. an unknown `type` or `serve`.
+    #[must_use]
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "Matching the borrowed fields is clearer than matching with `ref` patterns"
+    )]
+    pub fn deprecations(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        for repository in self.get_sorted() {
+            if let Some(RepositoryType::Other(other)) = repository.custom.repository_type.as_ref() {
+                warnings.push(format!(
+                    "repository {} has the unknown type `{other}`, which is deprecated",
+                    repository.name
+                ));
+            }
+            if let ServeType::Other(other) = &repository.custom.serve {
+                warnings.push(format!(
+                    "repository {} has the unknown serve type `{other}`, which is deprecated",
+                    repository.name
+                ));
+            }
+        }
+        warnings.sort();
+        warnings
+    }
+
+    /// Validate the injections of every repository, see [`Injection::validate`].
+    ///
+    /// # Errors
+    /// Errors with the repository and position of the first invalid injection.
+    pub fn validate_injections(&self) -> anyhow::Result<()> {
+        for repository in self.get_sorted() {
+            for (index, injection) in repository.custom.injections.iter().flatten().enumerate() {
+                injection.validate().map_err(|err| {
+                    anyhow::anyhow!(
+                        "invalid injection {index} of repository {}: {err}",
+                        repository.name
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[expect(
@@ -291,3 +670,127 @@ impl<'de> Deserialize<'de> for Repositories {
         deserializer.deserialize_struct("Repositories", FIELDS, RepositoriesVisitor)
     }
 }
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::stelae::types::repositories::{
+        Injection, LanguageNaming, Languages, Position, Repositories, RepositoryType, ServeType,
+    };
+
+    fn languages(naming: LanguageNaming) -> Languages {
+        Languages {
+            default: "en".to_owned(),
+            variants: vec!["es".to_owned(), "pt-BR".to_owned()],
+            naming,
+        }
+    }
+
+    fn injection(tag: &str, attrs: &[(&str, &str)], content: Option<&str>) -> Injection {
+        Injection {
+            tag: tag.to_owned(),
+            attrs: attrs
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+            content: content.map(str::to_owned),
+            position: Position::default(),
+        }
+    }
+
+    #[test]
+    fn test_validate_when_well_formed_element_expect_ok() {
+        let cut = Injection::validate;
+        assert!(cut(&injection(
+            "meta",
+            &[("name", "a"), ("data-x", "{{ date }}")],
+            None
+        ))
+        .is_ok());
+        assert!(cut(&injection(
+            "script",
+            &[("type", "application/json")],
+            Some("{}")
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_validate_when_malformed_element_expect_error() {
+        let cut = |injection: &Injection| message(injection.validate());
+        assert_eq!(cut(&injection("", &[], None)), "invalid tag name ``");
+        assert_eq!(cut(&injection("a b", &[], None)), "invalid tag name `a b`");
+        assert_eq!(
+            cut(&injection("Body", &[], None)),
+            "`<Body>` cannot be injected"
+        );
+        assert_eq!(
+            cut(&injection("div", &[("on\"x", "")], None)),
+            "invalid attribute name `on\"x` of `<div>`"
+        );
+        assert_eq!(
+            cut(&injection("meta", &[], Some("a"))),
+            "void element `<meta>` cannot have content"
+        );
+        assert_eq!(
+            cut(&injection("script", &[], Some("</SCRIPT><p>"))),
+            "content of `<script>` cannot contain its end tag"
+        );
+    }
+
+    fn message(result: anyhow::Result<()>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_find_when_tag_has_subtags_expect_exact_then_primary_match() {
+        let cut = languages(LanguageNaming::Suffix);
+        assert_eq!(cut.find("ES"), Some("es"));
+        assert_eq!(cut.find("es-MX"), Some("es"));
+        assert_eq!(cut.find("pt-br"), Some("pt-BR"));
+        assert_eq!(cut.find("pt"), Some("pt-BR"));
+        assert_eq!(cut.find("en-US"), Some("en"));
+        assert_eq!(cut.find("fr"), None);
+    }
+
+    #[test]
+    fn test_variant_paths_when_naming_expect_language_in_name() {
+        let suffix = languages(LanguageNaming::Suffix);
+        assert_eq!(
+            suffix.variant_paths("a/b/c", "es"),
+            vec!["a/b/c/index.es.html", "a/b/c.es.html"]
+        );
+        assert_eq!(
+            suffix.variant_paths("a/b/c.pdf", "es"),
+            vec!["a/b/c.es.pdf"]
+        );
+        assert_eq!(suffix.variant_paths("", "es"), vec!["index.es.html"]);
+        let prefix = languages(LanguageNaming::Prefix);
+        assert_eq!(prefix.variant_paths("/a/b/c", "es"), vec!["es/a/b/c"]);
+    }
+
+    #[test]
+    fn test_deprecations_when_unknown_type_or_serve_expect_warnings() {
+        let data = r#"{
+            "repositories": {
+                "org/law-html": {"custom": {"type": "html", "serve": "historical"}},
+                "org/law-epub": {"custom": {"type": "epub", "serve": "archived"}}
+            }
+        }"#;
+        let repositories: Repositories = serde_json::from_str(data).unwrap();
+        let cut = Repositories::deprecations;
+        let actual = cut(&repositories);
+        let expected = vec![
+            "repository org/law-epub has the unknown serve type `archived`, which is deprecated",
+            "repository org/law-epub has the unknown type `epub`, which is deprecated",
+        ];
+        assert_eq!(actual, expected);
+        let html = &repositories.repositories["org/law-html"].custom;
+        assert_eq!(html.repository_type, Some(RepositoryType::Html));
+        assert_eq!(html.serve, ServeType::Historical);
+    }
+}
@@ -0,0 +1,344 @@
+//! Data repositories of the synthetic archives.
+use crate::stelae::types::repositories::{Custom, Repository, RepositoryType, ServeType};
+use std::path::PathBuf;
+
+/// Layout of a synthetic archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveType {
+    /// A root stele, with dependent stelae if the jurisdiction is [`Jurisdiction::Multi`].
+    Basic(Jurisdiction),
+    /// A root stele with dependent stelae, which have dependent stelae of their own, each served
+    /// on its own host.
+    Multihost,
+}
+
+/// Whether a basic archive has dependent stelae.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jurisdiction {
+    /// A single stele.
+    Single,
+    /// A root stele with two dependent stelae, served under their scopes.
+    Multi,
+}
+
+/// Kind of a data repository, which decides the fixture files it is filled with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestDataRepositoryType {
+    /// Html documents.
+    Html,
+    /// RDF documents.
+    Rdf,
+    /// Xml documents.
+    Xml,
+    /// Pdf documents.
+    Pdf,
+    /// Documents of mixed types, with the name of one of them.
+    Other(String),
+}
+
+/// Information about a data repository.
+///
+/// This struct is used to initialize a data repository of a synthetic archive.
+#[derive(Debug, Clone)]
+pub struct TestDataRepositoryContext {
+    /// The name of the data repository.
+    pub name: String,
+    /// The paths of the data repository.
+    pub paths: Vec<PathBuf>,
+    /// The kind of data repository.
+    pub kind: TestDataRepositoryType,
+    /// The prefix to use when serving the data repository.
+    ///
+    /// If `None`, the data repository will be served at the root.
+    /// If `Some("prefix")`, the data repository will be served from `/prefix/<data>`.
+    pub serve_prefix: Option<String>,
+    /// The route glob patterns to use when serving the data repository.
+    pub route_glob_patterns: Option<Vec<String>>,
+    /// Whether the data repository is a fallback.
+    pub is_fallback: bool,
+}
+
+impl TestDataRepositoryContext {
+    /// Describe a data repository.
+    ///
+    /// # Errors
+    /// Errors if the repository has neither a serve prefix nor route glob patterns.
+    pub fn new(
+        name: String,
+        paths: Vec<PathBuf>,
+        kind: TestDataRepositoryType,
+        serve_prefix: Option<String>,
+        route_glob_patterns: Option<Vec<String>>,
+        is_fallback: bool,
+    ) -> anyhow::Result<Self> {
+        if serve_prefix.is_none() && route_glob_patterns.is_none() {
+            anyhow::bail!(
+                "A test data repository must have either a serve prefix or route glob patterns."
+            );
+        }
+        Ok(Self {
+            name,
+            paths,
+            kind,
+            serve_prefix,
+            route_glob_patterns,
+            is_fallback,
+        })
+    }
+
+    /// Paths of the html documents of a data repository.
+    #[must_use]
+    pub fn default_html_paths() -> Vec<PathBuf> {
+        to_paths(&[
+            "./index.html",
+            "./a/index.html",
+            "./a/b/index.html",
+            "./a/d/index.html",
+            "./a/b/c.html",
+            "./a/b/c/index.html",
+        ])
+    }
+
+    /// Paths of the RDF documents of a data repository.
+    #[must_use]
+    pub fn default_rdf_paths() -> Vec<PathBuf> {
+        to_paths(&[
+            "./index.rdf",
+            "./a/index.rdf",
+            "./a/b/index.rdf",
+            "./a/d/index.rdf",
+            "./a/b/c.rdf",
+            "./a/b/c/index.rdf",
+        ])
+    }
+
+    /// Paths of the xml documents of a data repository.
+    #[must_use]
+    pub fn default_xml_paths() -> Vec<PathBuf> {
+        to_paths(&[
+            "./index.xml",
+            "./a/index.xml",
+            "./a/b/index.xml",
+            "./a/d/index.xml",
+            "./a/b/c.xml",
+            "./a/b/c/index.xml",
+        ])
+    }
+
+    /// Paths of the pdf documents of a data repository.
+    #[must_use]
+    pub fn default_pdf_paths() -> Vec<PathBuf> {
+        to_paths(&["./example.pdf", "./a/example.pdf", "./a/b/example.pdf"])
+    }
+
+    /// Paths of the json documents of a data repository.
+    #[must_use]
+    pub fn default_json_paths() -> Vec<PathBuf> {
+        to_paths(&["./example.json", "./a/example.json", "./a/b/example.json"])
+    }
+
+    /// Paths of the documents of mixed types of a data repository.
+    #[must_use]
+    pub fn default_other_paths() -> Vec<PathBuf> {
+        to_paths(&[
+            "./index.html",
+            "./example.json",
+            "./a/index.html",
+            "./a/b/index.html",
+            "./a/b/c.html",
+            "./a/d/index.html",
+            "./_prefix/index.html",
+            "./_prefix/a/index.html",
+            "./a/_doc/e/index.html",
+            "./a/e/_doc/f/index.html",
+        ])
+    }
+}
+
+/// Convert the `paths` to path buffers.
+fn to_paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+/// Data repositories of a stele served without scopes.
+///
+/// # Errors
+/// Errors if a data repository cannot be described.
+pub fn get_basic_test_data_repositories() -> anyhow::Result<Vec<TestDataRepositoryContext>> {
+    Ok(vec![
+        TestDataRepositoryContext::new(
+            "law-html".into(),
+            TestDataRepositoryContext::default_html_paths(),
+            TestDataRepositoryType::Html,
+            None,
+            Some(vec![".*".into()]),
+            false,
+        )?,
+        TestDataRepositoryContext::new(
+            "law-rdf".into(),
+            TestDataRepositoryContext::default_rdf_paths(),
+            TestDataRepositoryType::Rdf,
+            Some("_rdf".into()),
+            None,
+            false,
+        )?,
+        TestDataRepositoryContext::new(
+            "law-xml".into(),
+            TestDataRepositoryContext::default_xml_paths(),
+            TestDataRepositoryType::Xml,
+            Some("_xml".into()),
+            None,
+            false,
+        )?,
+        TestDataRepositoryContext::new(
+            "law-xml-codified".into(),
+            to_paths(&[
+                "./index.xml",
+                "./e/index.xml",
+                "./e/f/index.xml",
+                "./e/g/index.xml",
+            ]),
+            TestDataRepositoryType::Xml,
+            Some("_xml_codified".into()),
+            None,
+            false,
+        )?,
+        TestDataRepositoryContext::new(
+            "law-pdf".into(),
+            TestDataRepositoryContext::default_pdf_paths(),
+            TestDataRepositoryType::Pdf,
+            None,
+            Some(vec![".*\\.pdf".into()]),
+            false,
+        )?,
+        TestDataRepositoryContext::new(
+            "law-other".into(),
+            TestDataRepositoryContext::default_other_paths(),
+            TestDataRepositoryType::Other("example.json".to_owned()),
+            None,
+            Some(vec![".*/_doc/.*".into(), "_prefix/.*".into()]),
+            true,
+        )?,
+    ])
+}
+
+/// Data repositories of a dependent stele, with their documents under every one of the `scopes`.
+///
+/// # Errors
+/// Errors if a data repository cannot be described.
+pub fn get_dependent_data_repositories_with_scopes(
+    scopes: &[String],
+) -> anyhow::Result<Vec<TestDataRepositoryContext>> {
+    let kinds = [
+        TestDataRepositoryType::Html,
+        TestDataRepositoryType::Rdf,
+        TestDataRepositoryType::Xml,
+        TestDataRepositoryType::Pdf,
+        TestDataRepositoryType::Other("example.json".to_owned()),
+    ];
+    let mut result = Vec::new();
+    for kind in kinds {
+        let mut context = dependent_data_repository(kind)?;
+        context.paths = scopes
+            .iter()
+            .flat_map(|scope| {
+                context
+                    .paths
+                    .iter()
+                    .map(move |path| PathBuf::from(format!("{scope}/{}", path.display())))
+            })
+            .collect();
+        result.push(context);
+    }
+    Ok(result)
+}
+
+/// Data repository of `kind` of a dependent stele, with its documents at the root.
+///
+/// # Errors
+/// Errors if the data repository cannot be described.
+fn dependent_data_repository(
+    kind: TestDataRepositoryType,
+) -> anyhow::Result<TestDataRepositoryContext> {
+    let unresolved = |extension: &str| {
+        to_paths(&[
+            &format!("./does-not-resolve.{extension}"),
+            &format!("./a/does-not-resolve.{extension}"),
+            &format!("./a/b/does-not-resolve.{extension}"),
+        ])
+    };
+    let (name, paths, serve_prefix, route_glob_patterns, is_fallback) = match kind {
+        TestDataRepositoryType::Html => {
+            let mut paths = TestDataRepositoryContext::default_html_paths();
+            paths.extend(unresolved("html"));
+            ("law-html", paths, None, Some(vec![".*".into()]), false)
+        }
+        TestDataRepositoryType::Rdf => (
+            "law-rdf",
+            TestDataRepositoryContext::default_rdf_paths(),
+            Some("_rdf".into()),
+            None,
+            false,
+        ),
+        TestDataRepositoryType::Xml => (
+            "law-xml",
+            TestDataRepositoryContext::default_xml_paths(),
+            Some("_xml".into()),
+            None,
+            false,
+        ),
+        TestDataRepositoryType::Pdf => (
+            "law-pdf",
+            TestDataRepositoryContext::default_pdf_paths(),
+            None,
+            Some(vec![".*\\.pdf".into()]),
+            false,
+        ),
+        TestDataRepositoryType::Other(_) => {
+            let mut paths = TestDataRepositoryContext::default_other_paths();
+            paths.extend(unresolved("json"));
+            (
+                "law-other",
+                paths,
+                None,
+                Some(vec![".*_doc/.*".into(), "_prefix/.*".into()]),
+                true,
+            )
+        }
+    };
+    TestDataRepositoryContext::new(
+        name.to_owned(),
+        paths,
+        kind,
+        serve_prefix,
+        route_glob_patterns,
+        is_fallback,
+    )
+}
+
+impl From<&TestDataRepositoryContext> for Repository {
+    fn from(context: &TestDataRepositoryContext) -> Self {
+        let repository_type = match context.kind {
+            TestDataRepositoryType::Html => RepositoryType::Html,
+            TestDataRepositoryType::Rdf => RepositoryType::Rdf,
+            TestDataRepositoryType::Xml => RepositoryType::Xml,
+            TestDataRepositoryType::Pdf => RepositoryType::Pdf,
+            TestDataRepositoryType::Other(_) => RepositoryType::Other("other".to_owned()),
+        };
+        let custom = Custom {
+            repository_type: Some(repository_type),
+            serve: ServeType::Latest,
+            scope: context.serve_prefix.clone(),
+            routes: context.route_glob_patterns.clone(),
+            is_fallback: Some(context.is_fallback),
+            layout: None,
+            directory_listing: None,
+            injections: None,
+            languages: None,
+        };
+        Self {
+            name: context.name.clone(),
+            custom,
+        }
+    }
+}
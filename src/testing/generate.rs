@@ -0,0 +1,350 @@
+//! Fabricate an archive of configurable size, to measure the performance of `stelae update` and
+//! `stelae serve` reproducibly without production data.
+//!
+//! The archive has the single stele `generated/law`, with historical html, pdf and xml data
+//! repositories and an RDF repository. Every version is codified in a publication of its own, which references
+//! the publication before it as its last valid publication, and changes every document.
+use super::utils::make_all_git_repos_bare_recursive;
+use super::GitRepository;
+use crate::server::errors::CliError;
+use crate::stelae::archive;
+use crate::stelae::types::repositories::{
+    Custom, Injection, Position, Repositories, Repository, RepositoryType, ServeType,
+};
+use crate::stelae::types::targets_metadata::TargetsMetadata;
+use anyhow::Context as _;
+use chrono::{Days, NaiveDate};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::Path;
+
+/// Organization of the generated stele.
+const ORG: &str = "generated";
+
+/// Codified date of the first version.
+const FIRST_DATE: NaiveDate = match NaiveDate::from_ymd_opt(2020, 1, 1) {
+    Some(date) => date,
+    None => panic!("invalid first date"),
+};
+
+/// Days between the codified dates of consecutive versions.
+const DAYS_BETWEEN_VERSIONS: u64 = 30;
+
+/// Size of a generated archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    /// Number of documents in every version.
+    pub documents: usize,
+    /// Number of versions, each codified in a publication of its own.
+    pub versions: usize,
+}
+
+/// Generate an archive of `size` at `path`, and report what was generated.
+///
+/// # Errors
+/// Errors if `path` is not empty, or the archive cannot be generated.
+pub fn run(path: &Path, size: Size) -> Result<(), CliError> {
+    if let Err(err) = generate(path, size) {
+        tracing::error!(
+            "Unable to generate an archive at '{}': {err:?}",
+            path.display()
+        );
+        return Err(CliError::GenericError);
+    }
+    let mut stdout = io::stdout();
+    writeln!(
+        stdout,
+        "Generated {ORG}/law at {} with {} documents in {} publications",
+        path.display(),
+        size.documents,
+        size.versions
+    )?;
+    writeln!(
+        stdout,
+        "Run `stelae --archive-path {} update` to insert its history",
+        path.display()
+    )?;
+    Ok(())
+}
+
+/// Generate an archive of `size` in the empty or missing directory at `path`.
+///
+/// The repositories are bare, like the repositories of a served archive.
+///
+/// # Errors
+/// Errors if `path` is not empty, or a repository cannot be created.
+pub fn generate(path: &Path, size: Size) -> anyhow::Result<()> {
+    if fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!("{} is not empty", path.display());
+    }
+    fs::create_dir_all(path)?;
+    archive::init(
+        path.to_path_buf(),
+        "law".into(),
+        ORG.into(),
+        None,
+        false,
+        None,
+    )?;
+    let org_path = path.join(ORG);
+    let auth_repo = init_auth_repository(&org_path)?;
+    let html_repo = GitRepository::init(&org_path.join("law-html"))?;
+    let pdf_repo = GitRepository::init(&org_path.join("law-pdf"))?;
+    let xml_repo = GitRepository::init(&org_path.join("law-xml"))?;
+    let rdf_repo = GitRepository::init(&org_path.join("law-rdf"))?;
+    let mut previous: Option<NaiveDate> = None;
+    for version in 0..size.versions {
+        let date = version_date(version)?;
+        tracing::info!(
+            "Generating version {} of {}: {date}",
+            version + 1,
+            size.versions
+        );
+        let commits = [
+            (
+                "law-html",
+                add_html_version(&html_repo, size.documents, version, date)?,
+            ),
+            (
+                "law-pdf",
+                add_typed_version(&pdf_repo, "pdf", size.documents, version, date)?,
+            ),
+            (
+                "law-xml",
+                add_typed_version(&xml_repo, "xml", size.documents, version, date)?,
+            ),
+        ];
+        add_targets_metadata(&auth_repo, &commits, date)?;
+        add_rdf_publication(&rdf_repo, size.documents, date, previous)?;
+        previous = Some(date);
+    }
+    make_all_git_repos_bare_recursive(path)
+}
+
+/// Codified date of the `version`, counted from 0.
+fn version_date(version: usize) -> anyhow::Result<NaiveDate> {
+    let days = u64::try_from(version)? * DAYS_BETWEEN_VERSIONS;
+    FIRST_DATE
+        .checked_add_days(Days::new(days))
+        .context("codified date out of range")
+}
+
+/// Create the authentication repository `law` of the organization at `org_path`, listing the
+/// historical html, pdf and xml and the RDF repositories.
+fn init_auth_repository(org_path: &Path) -> anyhow::Result<GitRepository> {
+    let repo_path = org_path.join("law");
+    let repo = GitRepository::init(&repo_path)?;
+    let data_repository = |name: &str, repository_type: RepositoryType, custom: Custom| {
+        let repository = Repository {
+            name: format!("{ORG}/{name}"),
+            custom: Custom {
+                repository_type: Some(repository_type),
+                ..custom
+            },
+        };
+        (repository.name.clone(), repository)
+    };
+    let repositories = Repositories {
+        scopes: None,
+        repositories: [
+            data_repository(
+                "law-html",
+                RepositoryType::Html,
+                Custom {
+                    serve: ServeType::Historical,
+                    routes: Some(vec![".*".to_owned()]),
+                    injections: Some(vec![Injection {
+                        tag: "meta".to_owned(),
+                        attrs: [("name", "version-date"), ("content", "{{ date }}")]
+                            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                            .into(),
+                        content: None,
+                        position: Position::default(),
+                    }]),
+                    ..Custom::default()
+                },
+            ),
+            data_repository(
+                "law-pdf",
+                RepositoryType::Pdf,
+                Custom {
+                    serve: ServeType::Historical,
+                    routes: Some(vec![".*\\.pdf".to_owned()]),
+                    ..Custom::default()
+                },
+            ),
+            data_repository(
+                "law-xml",
+                RepositoryType::Xml,
+                Custom {
+                    serve: ServeType::Historical,
+                    scope: Some("_xml".to_owned()),
+                    ..Custom::default()
+                },
+            ),
+            data_repository(
+                "law-rdf",
+                RepositoryType::Rdf,
+                Custom {
+                    serve: ServeType::Latest,
+                    scope: Some("_rdf".to_owned()),
+                    ..Custom::default()
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    let content = serde_json::to_string_pretty(&repositories)?;
+    repo.add_file(&repo_path.join("targets"), "repositories.json", &content)?;
+    repo.commit(Some("targets/repositories.json"), "Add repositories.json")?;
+    Ok(repo)
+}
+
+/// Commit the `documents` of the `version` codified on `date` to the html repository, each
+/// linking to the next one.
+fn add_html_version(
+    html_repo: &GitRepository,
+    documents: usize,
+    version: usize,
+    date: NaiveDate,
+) -> anyhow::Result<git2::Oid> {
+    for document in 0..documents {
+        let next = if document + 1 == documents {
+            0
+        } else {
+            document + 1
+        };
+        let content = format!(
+            "<!DOCTYPE html>
+<html>
+<head><title>Document {document}</title></head>
+<body>
+<h1>Document {document}</h1>
+<p>Version {version} of document {document}, codified on {date}.</p>
+<p><a href=\"/doc-{next}\">Document {next}</a></p>
+</body>
+</html>
+"
+        );
+        html_repo.add_file(
+            &html_repo.path.join(format!("doc-{document}")),
+            "index.html",
+            &content,
+        )?;
+    }
+    html_repo.commit(None, &format!("Codify {date}"))
+}
+
+/// Commit the `documents` of the `version` codified on `date` to the data repository of the
+/// `extension`, e.g. `doc-0.pdf` to the pdf repository.
+fn add_typed_version(
+    repo: &GitRepository,
+    extension: &str,
+    documents: usize,
+    version: usize,
+    date: NaiveDate,
+) -> anyhow::Result<git2::Oid> {
+    for document in 0..documents {
+        let content = format!("Version {version} of document {document}, codified on {date}.\n");
+        repo.add_file(&repo.path, &format!("doc-{document}.{extension}"), &content)?;
+    }
+    repo.commit(None, &format!("Codify {date}"))
+}
+
+/// Record the `commits` of the data repositories of the publication codified on `date` in the
+/// authentication repository.
+fn add_targets_metadata(
+    auth_repo: &GitRepository,
+    commits: &[(&str, git2::Oid)],
+    date: NaiveDate,
+) -> anyhow::Result<()> {
+    let publication = date.to_string();
+    for &(repo_name, commit) in commits {
+        let metadata = TargetsMetadata {
+            branch: format!("publication/{publication}"),
+            build_date: Some(publication.clone()),
+            commit: commit.to_string(),
+            codified_date: Some(publication.clone()),
+        };
+        let content = serde_json::to_string_pretty(&metadata)?;
+        auth_repo.add_file(
+            &auth_repo.path.join("targets").join(ORG),
+            repo_name,
+            &content,
+        )?;
+    }
+    auth_repo.commit(None, &format!("Publish {date}"))?;
+    Ok(())
+}
+
+/// Commit the publication codified on `date` to the RDF repository, with a version of each of
+/// the `documents` and referencing the `previous` publication as its last valid publication.
+fn add_rdf_publication(
+    rdf_repo: &GitRepository,
+    documents: usize,
+    date: NaiveDate,
+    previous: Option<NaiveDate>,
+) -> anyhow::Result<()> {
+    let publication_path = rdf_repo.path.join("_publication").join(date.to_string());
+    let mut index = format!(
+        "{PREFIXES}
+<https://example.com/_publication/{date}> rdfs:label \"Publication {date}\" ;
+    dcterms:available \"{date}\" .
+"
+    );
+    if let Some(previous_date) = previous {
+        writeln!(
+            index,
+            "<https://example.com/_publication/{date}> oll:lastValidPublication \"Publication {previous_date}\" ;
+    oll:lastValidCodifiedDate \"{previous_date}\" ."
+        )?;
+    }
+    rdf_repo.add_file(&publication_path, "index.ttl", &index)?;
+    let status = if previous.is_some() {
+        "Element changed"
+    } else {
+        "Element added"
+    };
+    for document in 0..documents {
+        let subject = format!("<https://example.com/_publication/{date}/doc-{document}");
+        let content = format!(
+            "{PREFIXES}
+{subject}> a oll:DocumentVersion ;
+    oll:docId \"doc-{document}\" ;
+    oll:codifiedDate \"{date}\" ;
+    oll:hasChanges {subject}/changes> .
+{subject}/changes> a rdf:Bag ;
+    rdf:_1 {subject}/changes/1> .
+{subject}/changes/1> oll:documentMaterializedPath \"|doc-{document}|\" ;
+    oll:url \"/doc-{document}\" ;
+    oll:status \"{status}\" .
+"
+        );
+        rdf_repo.add_file(&publication_path, &format!("doc-{document}.ttl"), &content)?;
+    }
+    rdf_repo.commit(None, &format!("Add publication {date}"))?;
+    Ok(())
+}
+
+/// Turtle prefixes of the generated RDF files.
+const PREFIXES: &str = "@prefix dcterms: <http://purl.org/dc/terms/> .
+@prefix oll: <https://open.law/us/ngo/oll/_ontology/v0.1/ontology.owl#> .
+@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+";
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::testing::generate::version_date;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_version_date_when_later_version_expect_days_between_versions_apart() {
+        let actual = version_date(2).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2020, 3, 1).unwrap();
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,471 @@
+//! Build synthetic archives and publications for tests.
+//!
+//! Enabled by the `test-fixtures` feature. The archives are the ones the stelae test suite runs
+//! against: every stele has an authentication repository with `targets/repositories.json`, and
+//! optionally `targets/dependencies.json`, and html, RDF, xml, pdf and mixed data repositories
+//! filled with fixture documents. Publications can be added to the RDF repository of a stele, so
+//! `stelae update` can ingest them.
+//!
+//! ```
+//! use stelae::testing::{self, config::{ArchiveType, Jurisdiction}};
+//! use stelae::stelae::archive::Archive;
+//!
+//! let archive_dir = testing::initialize_archive(ArchiveType::Basic(Jurisdiction::Single))?;
+//! let archive = Archive::parse(archive_dir.path().to_path_buf(), archive_dir.path(), false)?;
+//! assert!(archive.stelae.contains_key("test_org/law"));
+//! # anyhow::Ok(())
+//! ```
+pub mod config;
+pub mod generate;
+pub mod utils;
+
+use crate::stelae::archive::{self, Headers};
+use crate::stelae::types::dependencies::{Dependencies, Dependency};
+use crate::stelae::types::repositories::{Custom, Languages, Repositories, Repository};
+use anyhow::Context as _;
+use chrono::NaiveDate;
+use git2::{Commit, Oid};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use self::config::{
+    get_basic_test_data_repositories, get_dependent_data_repositories_with_scopes, ArchiveType,
+    Jurisdiction, TestDataRepositoryContext,
+};
+
+/// A git repository of a synthetic archive, with a working tree to add files to.
+pub struct GitRepository {
+    /// The git repository.
+    pub repo: git2::Repository,
+    /// Path of the working tree of the repository.
+    pub path: PathBuf,
+}
+
+impl GitRepository {
+    /// Initialize a repository at `path`, committing as a fixed test user.
+    ///
+    /// # Errors
+    /// Errors if the repository cannot be initialized.
+    pub fn init(path: &Path) -> anyhow::Result<Self> {
+        let repo = git2::Repository::init(path)?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "name")?;
+        config.set_str("user.email", "email")?;
+        Ok(Self {
+            repo,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Open the repository at `path`.
+    ///
+    /// # Errors
+    /// Errors if there is no repository at `path`.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let repo = git2::Repository::open(path)?;
+        Ok(Self {
+            repo,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Commit the file at `path_str`, relative to the working tree, or all files if `None`, on
+    /// top of `HEAD`.
+    ///
+    /// # Errors
+    /// Errors if the files cannot be added to the index, or the commit cannot be created.
+    pub fn commit(&self, path_str: Option<&str>, commit_msg: &str) -> anyhow::Result<Oid> {
+        let mut index = self.repo.index()?;
+        if let Some(file_path) = path_str {
+            index.add_path(Path::new(file_path))?;
+        } else {
+            index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        }
+        index.write()?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let sig = self.repo.signature()?;
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.target())
+            .and_then(|target_id| self.repo.find_commit(target_id).ok());
+        let parents: Vec<&Commit> = parent.iter().collect();
+        Ok(self
+            .repo
+            .commit(Some("HEAD"), &sig, &sig, commit_msg, &tree, &parents)?)
+    }
+
+    /// Write `content` to the file `file_name` in the directory at `path`, creating the directory.
+    ///
+    /// # Errors
+    /// Errors if the directory or the file cannot be written.
+    pub fn add_file(&self, path: &Path, file_name: &str, content: &str) -> anyhow::Result<()> {
+        fs::create_dir_all(path)?;
+        fs::write(path.join(file_name), content)?;
+        Ok(())
+    }
+}
+
+impl From<GitRepository> for git2::Repository {
+    fn from(repository: GitRepository) -> Self {
+        repository.repo
+    }
+}
+
+impl Deref for GitRepository {
+    type Target = git2::Repository;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
+/// Create a synthetic archive of `archive_type` in a new temporary directory, with bare
+/// repositories like a served archive.
+///
+/// The archive is removed when the returned directory is dropped.
+///
+/// # Errors
+/// Errors if the temporary directory or the archive cannot be created.
+pub fn initialize_archive(archive_type: ArchiveType) -> anyhow::Result<TempDir> {
+    let archive_dir = TempDir::new()?;
+    initialize_archive_inner(archive_type, archive_dir.path())?;
+    utils::make_all_git_repos_bare_recursive(archive_dir.path())?;
+    Ok(archive_dir)
+}
+
+/// Create a synthetic archive of `archive_type` in the empty directory at `path`.
+///
+/// The repositories keep their working trees, so files can still be committed to them, e.g.
+/// with [`add_publication`], before they are converted with
+/// [`utils::make_all_git_repos_bare_recursive`].
+///
+/// # Errors
+/// Errors if the archive cannot be created.
+pub fn initialize_archive_inner(archive_type: ArchiveType, path: &Path) -> anyhow::Result<()> {
+    match archive_type {
+        ArchiveType::Basic(Jurisdiction::Single) => initialize_archive_basic(path),
+        ArchiveType::Basic(Jurisdiction::Multi) => initialize_archive_multijurisdiction(path),
+        ArchiveType::Multihost => initialize_archive_multihost(path),
+    }
+}
+
+/// Create an archive with the single stele `test_org/law`.
+fn initialize_archive_basic(path: &Path) -> anyhow::Result<()> {
+    let org_name = "test_org";
+    archive::init(
+        path.to_path_buf(),
+        "law".into(),
+        org_name.into(),
+        None,
+        false,
+        None,
+    )?;
+    initialize_stele(path, org_name, &get_basic_test_data_repositories()?, None)
+}
+
+/// Create an archive with the root stele `root_test_org/law`, and the stelae
+/// `dependent_stele_1/law` and `dependent_stele_2/law` it depends on, each served under two
+/// scopes.
+fn initialize_archive_multijurisdiction(path: &Path) -> anyhow::Result<()> {
+    let root_org_name = "root_test_org";
+    archive::init(
+        path.to_path_buf(),
+        "law".into(),
+        root_org_name.into(),
+        None,
+        false,
+        None,
+    )?;
+    initialize_stele(
+        path,
+        root_org_name,
+        &get_basic_test_data_repositories()?,
+        None,
+    )?;
+    let dependents = [
+        ("dependent_stele_1", ["sub/scope/1", "sub/scope/2"]),
+        ("dependent_stele_2", ["sub/scope/3", "sub/scope/4"]),
+    ];
+    for (org_name, scope_names) in dependents {
+        let scopes: Vec<String> = scope_names.map(str::to_owned).to_vec();
+        initialize_stele(
+            path,
+            org_name,
+            &get_dependent_data_repositories_with_scopes(&scopes)?,
+            Some(&scopes),
+        )?;
+    }
+    add_dependencies(
+        path,
+        root_org_name,
+        &["dependent_stele_1", "dependent_stele_2"],
+    )
+}
+
+/// Create an archive with the root stele `root_stele/law`, which depends on `stele_1/law` and
+/// `stele_2/law`, and `stele_1/law` on `stele_1_1/law` and `stele_1_2/law`.
+fn initialize_archive_multihost(path: &Path) -> anyhow::Result<()> {
+    let root_org_name = "root_stele";
+    archive::init(
+        path.to_path_buf(),
+        "law".into(),
+        root_org_name.into(),
+        None,
+        false,
+        Some(Headers {
+            current_documents_guard: Some("X-Current-Documents-Guard".into()),
+        }),
+    )?;
+    for org_name in [
+        root_org_name,
+        "stele_1",
+        "stele_2",
+        "stele_1_1",
+        "stele_1_2",
+    ] {
+        initialize_stele(path, org_name, &get_basic_test_data_repositories()?, None)?;
+    }
+    add_dependencies(path, root_org_name, &["stele_1", "stele_2"])?;
+    add_dependencies(path, "stele_1", &["stele_1_1", "stele_1_2"])
+}
+
+/// Create the stele `{org_name}/law` in the archive at `path`, with its `data_repositories`,
+/// served under `scopes` if given.
+///
+/// # Errors
+/// Errors if a repository cannot be created.
+pub fn initialize_stele(
+    path: &Path,
+    org_name: &str,
+    data_repositories: &[TestDataRepositoryContext],
+    scopes: Option<&[String]>,
+) -> anyhow::Result<()> {
+    let org_path = path.join(org_name);
+    init_data_repositories(&org_path, data_repositories)?;
+    init_auth_repository(&org_path, org_name, data_repositories, scopes)?;
+    Ok(())
+}
+
+/// Create the authentication repository `law` of the organization at `path`, listing the
+/// `data_repositories` and `scopes` in its `targets/repositories.json`.
+///
+/// # Errors
+/// Errors if the repository cannot be created.
+pub fn init_auth_repository(
+    path: &Path,
+    org_name: &str,
+    data_repositories: &[TestDataRepositoryContext],
+    scopes: Option<&[String]>,
+) -> anyhow::Result<GitRepository> {
+    let repo_path = path.join("law");
+    fs::create_dir_all(&repo_path)?;
+    let repo = GitRepository::init(&repo_path)?;
+    let repositories = Repositories {
+        scopes: scopes.map(<[String]>::to_vec),
+        repositories: data_repositories
+            .iter()
+            .map(|data_repo| {
+                let mut repository = Repository::from(data_repo);
+                repository.name = format!("{org_name}/{}", repository.name);
+                (repository.name.clone(), repository)
+            })
+            .collect(),
+    };
+    let content = serde_json::to_string_pretty(&repositories)?;
+    repo.add_file(&repo_path.join("targets"), "repositories.json", &content)?;
+    repo.commit(Some("targets/repositories.json"), "Add repositories.json")?;
+    Ok(repo)
+}
+
+/// Create the `data_repositories` of the organization at `path`, filled with fixture documents.
+///
+/// # Errors
+/// Errors if a repository cannot be created.
+pub fn init_data_repositories(
+    path: &Path,
+    data_repositories: &[TestDataRepositoryContext],
+) -> anyhow::Result<Vec<GitRepository>> {
+    let mut git_repositories = Vec::new();
+    for data_repo in data_repositories {
+        let repo_path = path.join(&data_repo.name);
+        fs::create_dir_all(&repo_path)?;
+        let git_repo = GitRepository::init(&repo_path)?;
+        for file_path in &data_repo.paths {
+            let target = git_repo.path.join(file_path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&target, fixture_contents(file_path))?;
+        }
+        git_repo.commit(None, "Add initial data")?;
+        git_repositories.push(git_repo);
+    }
+    Ok(git_repositories)
+}
+
+/// Contents of the fixture document at `path`, by its extension.
+fn fixture_contents(path: &Path) -> &'static [u8] {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("rdf") => include_bytes!("../../tests/fixtures/static_files/index.rdf"),
+        Some("xml") => include_bytes!("../../tests/fixtures/static_files/index.xml"),
+        Some("pdf") => include_bytes!("../../tests/fixtures/static_files/example.pdf"),
+        Some("json") => include_bytes!("../../tests/fixtures/static_files/example.json"),
+        Some("js") => include_bytes!("../../tests/fixtures/static_files/example.js"),
+        _ => include_bytes!("../../tests/fixtures/static_files/index.html"),
+    }
+}
+
+/// Add the stelae of the organizations `dependent_stele_org_names` as dependencies of the stele
+/// of `root_org_name` in the archive at `path`.
+///
+/// # Errors
+/// Errors if the authentication repository cannot be opened, or the dependencies cannot be
+/// committed.
+pub fn add_dependencies(
+    path: &Path,
+    root_org_name: &str,
+    dependent_stele_org_names: &[&str],
+) -> anyhow::Result<()> {
+    let root_repo = get_repository(path, &format!("{root_org_name}/law"))?;
+    let dependencies = Dependencies {
+        dependencies: dependent_stele_org_names
+            .iter()
+            .map(|org_name| {
+                (
+                    format!("{org_name}/law"),
+                    Dependency {
+                        out_of_band_authentication: "sha256".into(),
+                        branch: "main".into(),
+                    },
+                )
+            })
+            .collect(),
+    };
+    let content = serde_json::to_string_pretty(&dependencies)?;
+    root_repo.add_file(
+        &path.join(format!("{root_org_name}/law/targets")),
+        "dependencies.json",
+        &content,
+    )?;
+    root_repo.commit(Some("targets/dependencies.json"), "Add dependencies.json")?;
+    Ok(())
+}
+
+/// Enable the directory listing of the data repository `repository_name` of the stele of
+/// `org_name` in the archive at `path`.
+///
+/// # Errors
+/// Errors if the authentication repository cannot be opened, the repository is not listed in its
+/// `targets/repositories.json`, or the listing cannot be committed.
+pub fn enable_directory_listing(
+    path: &Path,
+    org_name: &str,
+    repository_name: &str,
+) -> anyhow::Result<()> {
+    change_custom(
+        path,
+        org_name,
+        repository_name,
+        "Enable directory listing",
+        |custom| {
+            custom.directory_listing = Some(true);
+        },
+    )
+}
+
+/// Declare the `languages` of the documents of the data repository `repository_name` of the
+/// stele of `org_name` in the archive at `path`.
+///
+/// # Errors
+/// Errors if the authentication repository cannot be opened, the repository is not listed in its
+/// `targets/repositories.json`, or the languages cannot be committed.
+pub fn declare_languages(
+    path: &Path,
+    org_name: &str,
+    repository_name: &str,
+    languages: Languages,
+) -> anyhow::Result<()> {
+    change_custom(
+        path,
+        org_name,
+        repository_name,
+        "Declare languages",
+        |custom| {
+            custom.languages = Some(languages);
+        },
+    )
+}
+
+/// Change the custom fields of the data repository `repository_name` of the stele of `org_name`
+/// in the archive at `path` with `change`, and commit them with the `message`.
+///
+/// # Errors
+/// Errors if the authentication repository cannot be opened, the repository is not listed in its
+/// `targets/repositories.json`, or the change cannot be committed.
+fn change_custom(
+    path: &Path,
+    org_name: &str,
+    repository_name: &str,
+    message: &str,
+    change: impl FnOnce(&mut Custom),
+) -> anyhow::Result<()> {
+    let auth_repo = get_repository(path, &format!("{org_name}/law"))?;
+    let targets = path.join(format!("{org_name}/law/targets"));
+    let mut repositories: Repositories =
+        serde_json::from_str(&fs::read_to_string(targets.join("repositories.json"))?)?;
+    let name = format!("{org_name}/{repository_name}");
+    change(
+        &mut repositories
+            .repositories
+            .get_mut(&name)
+            .with_context(|| format!("Repository {name} is not listed"))?
+            .custom,
+    );
+    let content = serde_json::to_string_pretty(&repositories)?;
+    auth_repo.add_file(&targets, "repositories.json", &content)?;
+    auth_repo.commit(Some("targets/repositories.json"), message)?;
+    Ok(())
+}
+
+/// Add the publication `name` available on `date` to the RDF repository `rdf_repo` of a stele.
+///
+/// The publication is committed as `_publication/{name}/index.ttl`, where `stelae update` reads
+/// publications from. It has no document or collection versions.
+///
+/// # Errors
+/// Errors if the index file cannot be committed.
+pub fn add_publication(
+    rdf_repo: &GitRepository,
+    name: &str,
+    date: NaiveDate,
+) -> anyhow::Result<Oid> {
+    let content = format!(
+        "@prefix dcterms: <http://purl.org/dc/terms/> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<https://example.com/_publication/{name}> rdfs:label \"Publication {name}\" ;
+    dcterms:available \"{}\" .
+",
+        date.format("%Y-%m-%d")
+    );
+    let publication_dir = format!("_publication/{name}");
+    rdf_repo.add_file(&rdf_repo.path.join(&publication_dir), "index.ttl", &content)?;
+    rdf_repo
+        .commit(
+            Some(&format!("{publication_dir}/index.ttl")),
+            &format!("Add publication {name}"),
+        )
+        .with_context(|| format!("could not commit publication {name}"))
+}
+
+/// Open the repository `name`, e.g. `org/law-rdf`, of the archive at `path`.
+///
+/// # Errors
+/// Errors if there is no repository `name` in the archive.
+pub fn get_repository(path: &Path, name: &str) -> anyhow::Result<GitRepository> {
+    GitRepository::open(&path.join(name))
+}
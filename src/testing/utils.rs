@@ -0,0 +1,62 @@
+//! Convert the repositories of a synthetic archive to bare repositories.
+use anyhow::Context as _;
+use std::fs;
+use std::path::Path;
+
+/// Convert every git repository under `path` to a bare repository, like the repositories of a
+/// served archive.
+///
+/// Files cannot be added and committed to a bare repository, because index methods fail on bare
+/// repositories, see [1]. Instead, the repositories are initialized as normal repositories, and
+/// converted once all their files are committed.
+///
+/// [1] - <https://libgit2.org/libgit2/#HEAD/group/index/git_index_add_all>
+///
+/// # Errors
+/// Errors if a directory under `path` cannot be read, or a repository cannot be converted.
+pub fn make_all_git_repos_bare_recursive(path: &Path) -> anyhow::Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+    make_bare(path)?;
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_dir() {
+            make_all_git_repos_bare_recursive(&entry_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert the repository at `dir_path` to a bare repository, if it is a normal repository.
+///
+/// # Errors
+/// Errors if the working tree cannot be removed, or the `.git` directory cannot be moved.
+fn make_bare(dir_path: &Path) -> anyhow::Result<()> {
+    let git_dir = dir_path.join(".git");
+    if !git_dir.is_dir() {
+        return Ok(());
+    }
+    // Remove the working tree, excluding the .git directory
+    for entry in fs::read_dir(dir_path)? {
+        let entry_path = entry?.path();
+        if entry_path == git_dir {
+            continue;
+        }
+        if entry_path.is_dir() {
+            fs::remove_dir_all(&entry_path)?;
+        } else {
+            fs::remove_file(&entry_path)?;
+        }
+    }
+    // Move the contents of the .git directory to the repository directory
+    for entry in fs::read_dir(&git_dir)? {
+        let entry_path = entry?.path();
+        let file_name = entry_path
+            .file_name()
+            .with_context(|| format!("{} has no file name", entry_path.display()))?;
+        fs::rename(&entry_path, dir_path.join(file_name))?;
+    }
+    fs::remove_dir_all(&git_dir)?;
+    Ok(())
+}
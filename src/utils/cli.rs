@@ -4,13 +4,36 @@
     reason = "Allow exits because in this file we ideally handle all errors with known exit codes"
 )]
 
+use crate::history::backup;
 use crate::history::changes;
-use crate::server::app::serve_archive;
+use crate::history::diff;
+use crate::history::disk_usage;
+use crate::history::doctor;
+use crate::history::export::{self, Format};
+use crate::history::manifest;
+use crate::history::mirror::{self, Upstream};
+use crate::history::stats;
+use crate::history::status;
+use crate::server::app::{serve_archive, Bind, Listen, DEFAULT_ADMIN_PORT};
+use crate::server::base_path::BasePath;
+use crate::server::bench;
 use crate::server::errors::CliError;
 use crate::server::git::serve_git;
+use crate::server::scheduler::{Schedule, Scheduled};
+use crate::server::startup;
+#[cfg(feature = "test-fixtures")]
+use crate::testing::generate;
 use crate::utils::archive::find_archive_path;
-use clap::Parser;
+use crate::utils::daemon;
+use crate::utils::lock;
+use crate::utils::output::Output;
+use actix_web::rt::System;
+use chrono::NaiveDate;
+use clap::{ArgGroup, Command, CommandFactory as _, FromArgMatches as _, Parser};
+use clap_complete::Shell;
 use std::env;
+use std::future::Future;
+use std::io::{self, Write as _};
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
@@ -18,21 +41,184 @@ use tracing;
 use tracing::Level;
 use tracing_appender::rolling;
 use tracing_subscriber::fmt;
-use tracing_subscriber::fmt::writer::MakeWriterExt as _;
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriterExt as _};
 use tracing_subscriber::Layer as _;
 use tracing_subscriber::{
     filter::EnvFilter, layer::SubscriberExt as _, util::SubscriberInitExt as _,
 };
 
-/// Stelae is currently just a simple git server.
-/// run from the library directory or pass
-/// path to archive.
+/// Examples and exit codes of the Stelae CLI, shown in the long help.
+const EXAMPLES: &str = "Examples:
+  stelae serve                           Serve the archive in the current directory on port 8080
+  stelae --archive-path /srv/law update  Insert the history of the archive at /srv/law
+  stelae validate --output json          Validate the archive, and write the problems as JSON
+  stelae completions bash                Generate completions for bash
+
+Exit codes:
+  0  Success
+  1  Generic error
+  2  Invalid command line arguments
+  3  The configuration of the archive cannot be read
+  4  The archive cannot be found or parsed
+  5  The database cannot be connected to
+  6  The archive failed verification, e.g. against a manifest
+  7  The command succeeded only for some stelae
+
+Environment:
+  Every option can also be set with a `STELAE_*` environment variable named after it and its
+  subcommand, e.g. `STELAE_ARCHIVE_PATH`, `STELAE_DATABASE_URL`, `STELAE_SERVE_BIND` or
+  `STELAE_MIRROR_FROM`, as shown in the help of each option. Options given on the command line
+  take precedence.";
+
+/// Prefix of the environment variables the options of the Stelae CLI are read from.
+const ENV_PREFIX: &str = "STELAE_";
+
+/// Examples of `stelae git`, shown in its long help.
+const GIT_EXAMPLES: &str = "Examples:
+  stelae git                             Serve the git repositories on port 8080
+  stelae git --port 8081                 Serve the git repositories on port 8081";
+
+/// Examples of `stelae serve`, shown in its long help.
+const SERVE_EXAMPLES: &str = "Examples:
+  stelae serve --port 8000
+  stelae serve --warmup
+  stelae serve --bind unix:/run/stelae.sock
+  stelae serve --bind 0.0.0.0 --admin-port 9000
+  stelae serve --daemon --pid-file /run/stelae.pid --log-file /var/log/stelae.log
+  stelae --archive-path C:\\law serve --service --log-file C:\\law\\stelae.log
+  stelae --archive-path ./org-name serve --individual";
+
+/// Examples of `stelae update`, shown in its long help.
+const UPDATE_EXAMPLES: &str = "Examples:
+  stelae update
+  stelae update --strict --check-links
+  stelae update --output json > update.json";
+
+/// Examples of `stelae manifest`, shown in its long help.
+const MANIFEST_EXAMPLES: &str = "Examples:
+  stelae manifest --date 2023-10-22 --out manifest.json
+  stelae manifest --stele org-name/law --date 2023-10-22
+  stelae manifest --date 2023-10-22 --key-file manifest.key --out manifest.json
+  stelae manifest --verify manifest.json
+  stelae manifest --verify manifest.json --key-file manifest.key --output json";
+
+/// Examples of `stelae mirror`, shown in its long help.
+const MIRROR_EXAMPLES: &str = "Examples:
+  stelae mirror --from https://git.law.example.gov --token-file mirror.token
+  stelae mirror --from https://git.law.example.gov --token-file mirror.token --interval 3600";
+
+/// Examples of `stelae bench`, shown in its long help.
+const BENCH_EXAMPLES: &str = "Examples:
+  stelae bench --log /var/log/nginx/access.log
+  stelae bench --log paths.txt --repeat 5";
+
+/// Examples of `stelae stats`, shown in its long help.
+const STATS_EXAMPLES: &str = "Examples:
+  stelae stats
+  stelae stats --output json";
+
+/// Examples of `stelae diff-publications`, shown in its long help.
+const DIFF_PUBLICATIONS_EXAMPLES: &str = "Examples:
+  stelae diff-publications 2023-10-22
+  stelae diff-publications 2023-10-22 --from 2023-01-01 --stele org-name/repo-name-law
+  stelae diff-publications 2023-10-22 --output json --out diff.json";
+
+/// Examples of `stelae disk-usage`, shown in its long help.
+const DISK_USAGE_EXAMPLES: &str = "Examples:
+  stelae disk-usage
+  stelae disk-usage --interval 86400";
+
+/// Examples of `stelae validate`, shown in its long help.
+const VALIDATE_EXAMPLES: &str = "Examples:
+  stelae validate
+  stelae validate --output json";
+
+/// Examples of `stelae status`, shown in its long help.
+const STATUS_EXAMPLES: &str = "Examples:
+  stelae status
+  stelae status --server http://localhost:8080 --output json
+  stelae status --server http://localhost:9000 --token-file /etc/stelae/admin-token";
+
+/// Examples of `stelae doctor`, shown in its long help.
+const DOCTOR_EXAMPLES: &str = "Examples:
+  stelae doctor
+  stelae doctor --clock-url https://www.example.org --output json";
+
+/// Examples of `stelae backup`, shown in its long help.
+const BACKUP_EXAMPLES: &str = "Examples:
+  stelae backup --out /backups/stelae-2023-10-22.sqlite3";
+
+/// Examples of `stelae restore`, shown in its long help.
+const RESTORE_EXAMPLES: &str = "Examples:
+  stelae restore --from /backups/stelae-2023-10-22.sqlite3
+  stelae restore --from /backups/stelae-2023-10-22.sqlite3 --no-config";
+
+/// Examples of `stelae export`, shown in its long help.
+const EXPORT_EXAMPLES: &str = "Examples:
+  stelae export changes --stele org-name/law --format json
+  stelae export site --date 2023-10-22 --out ./site
+  stelae export warc --date 2023-10-22 --out law.warc --base-url https://law.example.gov";
+
+/// Examples of `stelae export changes`, shown in its long help.
+const EXPORT_CHANGES_EXAMPLES: &str = "Examples:
+  stelae export changes --stele org-name/law > changes.csv
+  stelae export changes --stele org-name/law --format json --from 2020-01-01 --to 2023-12-31";
+
+/// Examples of `stelae export site`, shown in its long help.
+const EXPORT_SITE_EXAMPLES: &str = "Examples:
+  stelae export site --date 2023-10-22 --out ./site
+  stelae export site --stele org-name/law --date 2023-10-22 --out ./site --no-date-urls";
+
+/// Examples of `stelae export warc`, shown in its long help.
+const EXPORT_WARC_EXAMPLES: &str = "Examples:
+  stelae export warc --date 2023-10-22 --out law-2023-10-22.warc --base-url https://law.example.gov";
+
+/// Examples of `stelae generate`, shown in its long help.
+#[cfg(feature = "test-fixtures")]
+const GENERATE_EXAMPLES: &str = "Examples:
+  stelae generate --out /tmp/load --documents 1000 --versions 12
+  stelae --archive-path /tmp/load update && stelae --archive-path /tmp/load serve";
+
+/// Examples of `stelae completions`, shown in its long help.
+const COMPLETIONS_EXAMPLES: &str = "Examples:
+  stelae completions bash > /etc/bash_completion.d/stelae
+  stelae completions zsh > \"${fpath[1]}/_stelae\"
+  stelae completions fish > ~/.config/fish/completions/stelae.fish
+  stelae completions powershell >> $PROFILE";
+
+/// Stelae serves the documents of a Stelae archive, and their history.
+///
+/// Run from the archive directory, or pass the path to the archive with `--archive-path`.
+/// Run `stelae help <command>` for the long help and examples of a command.
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, after_long_help = EXAMPLES)]
 struct Cli {
     /// Path to the Stelae archive. Defaults to cwd.
     #[arg(short, long, default_value_t = String::from(".").to_owned())]
     archive_path: String,
+    /// Format the results of `update`, `manifest --verify`, `diff-publications`, `stats` and
+    /// `validate` are written in.
+    ///
+    /// With `json`, the results are written to stdout as a single JSON document, and the console
+    /// logs to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
+    /// Wait for the lock of the archive held by another `update`, `mirror` or `restore`, instead
+    /// of failing right away.
+    #[arg(long, global = true, default_value_t = false)]
+    wait: bool,
+    /// Url of the `SQLite` database of the archive, with an absolute path, e.g.
+    /// `sqlite:///srv/stelae/db.sqlite3`. Defaults to the `DATABASE_URL` env var, or `db.sqlite3`
+    /// in the `.taf` dir.
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+    /// Path the archive is served under, e.g. `/laws` to serve it at `https://example.org/laws/`.
+    /// Defaults to the root.
+    ///
+    /// `serve` answers requests only under the path, and prefixes the urls in its responses with
+    /// it. `export site` and `export warc` prefix the urls in exported html documents with it.
+    #[arg(long, global = true, value_parser = BasePath::parse)]
+    base_path: Option<BasePath>,
     /// Stelae cli subcommands
     #[command(subcommand)]
     subcommands: Subcommands,
@@ -42,19 +228,64 @@ struct Cli {
 #[derive(Clone, clap::Subcommand)]
 enum Subcommands {
     /// Serve git repositories in the Stelae archive
+    ///
+    /// Repositories are served over the git smart HTTP protocol, read-only, and listed with the
+    /// commit every branch points to at `/_sync`, which `stelae mirror` fetches from. Mirrors
+    /// must send the token of `[mirror]` in `.taf/config.toml`, and are refused when it is unset.
+    #[command(after_long_help = GIT_EXAMPLES)]
     Git {
         /// Port on which to serve the archive.
         #[arg(short, long, default_value_t = 8080)]
         port: u16,
     },
     /// Serve documents in a Stelae archive.
+    ///
+    /// Serves the current documents of the root stele and its dependent stelae, their history and
+    /// the `/_api/` endpoints. The archive is validated first, and all problems found are reported
+    /// together.
+    #[command(
+        after_long_help = SERVE_EXAMPLES,
+        group(ArgGroup::new("background").args(["daemon", "service"]))
+    )]
     Serve {
         /// Port on which to serve the archive.
         #[arg(short, long, default_value_t = 8080)]
         port: u16,
+        /// Address on which to serve the archive: a host, e.g. `0.0.0.0`, or a unix domain socket,
+        /// e.g. `unix:/run/stelae.sock`. Defaults to `127.0.0.1`.
+        ///
+        /// Ignored when sockets are passed by systemd socket activation (`LISTEN_FDS`).
+        /// A non-loopback host is refused unless `[auth]` is configured or the management routes
+        /// are served apart with `--admin-bind`.
+        #[arg(short, long)]
+        bind: Option<String>,
+        /// Address on which to serve the management routes apart from the documents.
+        #[command(flatten)]
+        admin: AdminListener,
         #[arg(short, long, default_value_t = false)]
         /// Serve an individual stele instead of the Stele specified in config.toml.
         individual: bool,
+        /// Warm the cache with the current publications, common version queries and the hot
+        /// documents listed under `[warmup]` in `.taf/config.toml` before accepting traffic.
+        #[arg(long, default_value_t = false)]
+        warmup: bool,
+        /// Serve in the background, detached from the terminal. Only supported on unix.
+        #[arg(long, default_value_t = false)]
+        daemon: bool,
+        /// Serve as a Windows service, started and stopped by the service control manager.
+        /// Only supported on Windows.
+        #[arg(long, default_value_t = false)]
+        service: bool,
+        /// File to write the id of the serving process to. Removed again on shutdown.
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+        /// File the console output of the daemon or service is written to.
+        /// Defaults to `.taf/stelae-serve.log` in the archive.
+        #[arg(long, requires = "background")]
+        log_file: Option<PathBuf>,
+        /// Updates of the archive scheduled in the background.
+        #[command(flatten)]
+        updates: UpdateSchedule,
     },
     /// Update the archive
     ///
@@ -63,7 +294,362 @@ enum Subcommands {
     ///
     ///  - Populates the database with change objects loaded in from RDF repository
     ///  - By default inserts historical information for the root and all referenced stele in the archive
-    Update,
+    ///  - By default records malformed RDF files in the `ingest_errors` table and skips them
+    ///  - Optionally records internal links that do not resolve in the `broken_links` table
+    #[command(after_long_help = UPDATE_EXAMPLES)]
+    Update {
+        /// Fail the update of a stele if any of its RDF files are malformed.
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Check that internal links of the served html documents resolve within their publication,
+        /// and record broken links in the `broken_links` table.
+        #[arg(long, default_value_t = false)]
+        check_links: bool,
+    },
+    /// Create a checksum manifest of a publication, or verify the archive against one.
+    ///
+    /// The manifest lists the path, `sha256` and git object id of every file in the html data repository
+    /// commit mapped to the date, together with the authentication commit that authenticated it.
+    #[command(after_long_help = MANIFEST_EXAMPLES)]
+    Manifest {
+        /// Qualified name of the stele, e.g. `org-name/repo-name-law`. Defaults to the root stele.
+        #[arg(short, long, conflicts_with = "verify")]
+        stele: Option<String>,
+        /// Date of the publication to create the manifest for (YYYY-MM-DD).
+        #[arg(short, long, required_unless_present = "verify")]
+        date: Option<NaiveDate>,
+        /// File to write the manifest to. Defaults to stdout.
+        #[arg(short, long, conflicts_with = "verify")]
+        out: Option<PathBuf>,
+        /// Verify the archive against a previously created manifest file instead.
+        #[arg(long, conflicts_with = "date")]
+        verify: Option<PathBuf>,
+        /// File holding the secret key the manifest is signed, or its signature verified, with.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    /// Mirror the archive served by an upstream Stelae git server (`stelae git`).
+    ///
+    /// Fetches every repository listed by the upstream's `/_sync` endpoint over git smart HTTP,
+    /// only ever fast-forwarding branches, and then inserts historical information like `stelae update`.
+    /// Run it in an empty archive (a directory with a `.taf` folder) to start a new mirror.
+    #[command(after_long_help = MIRROR_EXAMPLES)]
+    Mirror {
+        /// Url of the upstream Stelae git server, e.g. `https://git.law.example.gov`.
+        #[arg(short, long)]
+        from: String,
+        /// File holding the token of the `[mirror]` configuration of the upstream archive.
+        #[arg(long)]
+        token_file: PathBuf,
+        /// Keep the mirror current by mirroring again every this many seconds.
+        #[arg(short, long)]
+        interval: Option<u64>,
+    },
+    /// Replay an access log against the archive and report request latencies.
+    ///
+    /// Only `GET` and `HEAD` requests are replayed. Requests are served in-process, exactly as
+    /// `stelae serve` would serve them, and a latency report is written to stdout.
+    #[command(after_long_help = BENCH_EXAMPLES)]
+    Bench {
+        /// Access log in the Common or Combined Log Format, or a file with one request path per line.
+        #[arg(short, long)]
+        log: PathBuf,
+        /// Replay the access log this many times.
+        #[arg(short, long, default_value_t = 1)]
+        repeat: usize,
+    },
+    /// Compare two publications of a stele.
+    ///
+    /// Lists the documents and collections that are new, changed or removed in a publication
+    /// relative to an earlier publication, by default the publication preceding it, for review
+    /// before the publication is announced.
+    #[command(after_long_help = DIFF_PUBLICATIONS_EXAMPLES)]
+    DiffPublications {
+        /// Publications to compare.
+        #[command(flatten)]
+        compared: ComparedPublications,
+    },
+    /// Report statistics of every stele in the archive.
+    ///
+    /// Reports the number of documents, collections, publications and versions, the earliest and
+    /// latest codified dates, the ingested authentication commits and the repository sizes on disk.
+    #[command(after_long_help = STATS_EXAMPLES)]
+    Stats,
+    /// Record the sizes on disk of the repositories of the archive.
+    ///
+    /// Sizes are recorded in the `repository_sizes` table, and exposed by `/_admin/stats` and
+    /// `/_metrics`. A warning is logged when a threshold configured under `[disk_usage]` in
+    /// `.taf/config.toml` is crossed.
+    #[command(after_long_help = DISK_USAGE_EXAMPLES)]
+    DiskUsage {
+        /// Keep recording every this many seconds.
+        #[arg(short, long)]
+        interval: Option<u64>,
+    },
+    /// Validate the archive like `stelae serve` does, and report all problems found.
+    ///
+    /// Checks that the configuration can be read, that the authentication repository of every
+    /// stele can be opened and its `repositories.json` and `dependencies.json` parsed, that every
+    /// served data repository is in the archive, and that the databases can be connected to.
+    #[command(after_long_help = VALIDATE_EXAMPLES)]
+    Validate {
+        #[arg(short, long, default_value_t = false)]
+        /// Validate an individual stele instead of the Stele specified in config.toml.
+        individual: bool,
+    },
+    /// Report the drift of the database and of a running server from the archive.
+    ///
+    /// For every stele, compares the latest publication in the RDF repository with the latest
+    /// publication ingested into the database, and the `HEAD` of every historical html data
+    /// repository with the commit last recorded in the database. Exits with 6 if any of them
+    /// differ, until `stelae update` is run.
+    #[command(after_long_help = STATUS_EXAMPLES)]
+    Status {
+        /// Url of the management routes of a running `stelae serve` to check for a stale archive,
+        /// which needs a restart. The address of its `--admin-bind` or `--admin-port`, if any.
+        #[arg(short, long)]
+        server: Option<String>,
+        /// File holding the bearer token of an admin, if the management routes are authenticated.
+        #[arg(long, requires = "server")]
+        token_file: Option<PathBuf>,
+    },
+    /// Diagnose the environment of the archive, and report every check as PASS or FAIL.
+    ///
+    /// Checks the built-in git and XML support, the configuration and layout of the archive, the
+    /// database connection and schema version, the permissions of the `.taf` dir and the
+    /// database, and the skew of the clock. Failed checks come with a hint to fix them.
+    #[command(after_long_help = DOCTOR_EXAMPLES)]
+    Doctor {
+        /// Url of a server whose `Date` header the clock is compared with. Defaults to comparing
+        /// with the latest commit of the root stele, which only finds a clock running behind.
+        #[arg(long)]
+        clock_url: Option<String>,
+    },
+    /// Back up the database and the configuration of the archive to a single file.
+    ///
+    /// The snapshot of the database is consistent, and is taken without stopping `stelae serve`.
+    /// The contents of `.taf/config.toml` are kept in the `stelae_backup` table of the file.
+    #[command(after_long_help = BACKUP_EXAMPLES)]
+    Backup {
+        /// File to write the backup to. Must not exist yet.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Restore the database and the configuration of the archive from a `stelae backup` file.
+    ///
+    /// Replaces the current database, so stop `stelae serve` first. Migrations newer than the
+    /// backup are applied after restoring.
+    #[command(after_long_help = RESTORE_EXAMPLES)]
+    Restore {
+        /// Backup file to restore from.
+        #[arg(short, long)]
+        from: PathBuf,
+        /// Keep the current `.taf/config.toml` instead of restoring the one of the backup.
+        #[arg(long, default_value_t = false)]
+        no_config: bool,
+    },
+    /// Export data from the archive database
+    ///
+    /// Exports the change data of a stele, or the documents of a stele as they were on a date.
+    #[command(after_long_help = EXPORT_EXAMPLES)]
+    Export {
+        /// What to export
+        #[command(subcommand)]
+        export: ExportSubcommands,
+    },
+    /// Fabricate an archive of configurable size for load testing.
+    ///
+    /// The archive has a single stele with a historical html data repository and an RDF
+    /// repository. Every version is codified in a publication of its own and changes every
+    /// document, so the performance of `update` and `serve` can be measured reproducibly without
+    /// production data. Does not require an archive.
+    #[cfg(feature = "test-fixtures")]
+    #[command(after_long_help = GENERATE_EXAMPLES)]
+    Generate {
+        /// Directory to generate the archive in. Must be empty or not exist yet.
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Number of documents in every version.
+        #[arg(short, long, default_value_t = 100)]
+        documents: usize,
+        /// Number of versions, each codified in a publication of its own.
+        #[arg(short, long, default_value_t = 10)]
+        versions: usize,
+    },
+    /// Generate shell completions for the Stelae CLI.
+    ///
+    /// The completion script is written to stdout. Does not require an archive.
+    #[command(after_long_help = COMPLETIONS_EXAMPLES)]
+    Completions {
+        /// Shell to generate completions for.
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Address `stelae serve` serves the management routes on, apart from the documents
+#[derive(Clone, clap::Args)]
+struct AdminListener {
+    /// Serve the management routes, `/_admin/*`, `/_metrics` and `/_health`, on this address
+    /// instead, a host or a unix domain socket like `--bind`, so they can be firewalled apart
+    /// from the documents.
+    #[arg(long)]
+    admin_bind: Option<String>,
+    /// Port on which to serve the management routes. Defaults to 9000 on `--admin-bind`, or
+    /// on `127.0.0.1` if no admin address is given.
+    #[arg(long)]
+    admin_port: Option<u16>,
+}
+
+impl AdminListener {
+    /// The address to serve the management routes on, if any is given.
+    fn bind(self) -> Option<Bind> {
+        (self.admin_bind.is_some() || self.admin_port.is_some()).then(|| {
+            Bind::parse(
+                self.admin_bind.as_deref(),
+                self.admin_port.unwrap_or(DEFAULT_ADMIN_PORT),
+            )
+        })
+    }
+}
+
+/// Publications of a stele compared by `stelae diff-publications`.
+#[derive(Clone, clap::Args)]
+struct ComparedPublications {
+    /// Name of the compared publication, e.g. `2023-10-22`.
+    to: String,
+    /// Name of the earlier publication to compare with. Defaults to the preceding publication.
+    #[arg(short, long)]
+    from: Option<String>,
+    /// Qualified name of the stele, e.g. `org-name/repo-name-law`. Defaults to the root stele.
+    #[arg(short, long)]
+    stele: Option<String>,
+    /// File to write the report to. Defaults to stdout.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+impl ComparedPublications {
+    /// Report the differences between the publications.
+    fn report(self, cli: &Cli, archive_path: PathBuf) -> Result<(), CliError> {
+        block_on(diff::report(
+            &cli.archive_path,
+            archive_path,
+            self.stele.as_deref(),
+            self.from.as_deref(),
+            &self.to,
+            cli.output.is_json(),
+            self.out.as_deref(),
+        ))
+    }
+}
+
+/// Updates of the archive scheduled by `stelae serve`, see [`crate::server::scheduler`].
+#[derive(Clone, clap::Args)]
+struct UpdateSchedule {
+    /// Url of the upstream Stelae git server to pull the repositories from before every
+    /// scheduled update, as `stelae mirror` does.
+    #[arg(
+        id = "update_from",
+        long = "update-from",
+        value_name = "UPDATE_FROM",
+        requires_all = ["update_schedule", "update_token_file"]
+    )]
+    from: Option<String>,
+    /// Update the archive in the background on a cron schedule in UTC, e.g. `"0 3 * * *"` for
+    /// every day at 3:00. The status of the updates is served at `/_admin/status`.
+    #[arg(
+        id = "update_schedule",
+        long = "update-schedule",
+        value_name = "UPDATE_SCHEDULE"
+    )]
+    schedule: Option<Schedule>,
+    /// File holding the token of the `[mirror]` configuration of the upstream archive.
+    #[arg(
+        id = "update_token_file",
+        long = "update-token-file",
+        value_name = "UPDATE_TOKEN_FILE",
+        requires = "update_from"
+    )]
+    token_file: Option<PathBuf>,
+}
+
+impl UpdateSchedule {
+    /// The scheduled updates, if a schedule is given.
+    ///
+    /// # Errors
+    /// Errors if the token file of the upstream cannot be read.
+    fn scheduled(self) -> Result<Option<Scheduled>, CliError> {
+        let upstream = match (self.from.as_deref(), self.token_file.as_deref()) {
+            (Some(url), Some(token_file)) => Some(read_upstream(url, token_file)?),
+            _ => None,
+        };
+        Ok(self
+            .schedule
+            .map(|schedule| Scheduled { schedule, upstream }))
+    }
+}
+
+/// Subcommands for `stelae export`
+#[derive(Clone, clap::Subcommand)]
+enum ExportSubcommands {
+    /// Export all document and library changes of a stele.
+    ///
+    /// Changes are written to stdout.
+    #[command(after_long_help = EXPORT_CHANGES_EXAMPLES)]
+    Changes {
+        /// Qualified name of the stele, e.g. `org-name/repo-name-law`.
+        #[arg(short, long)]
+        stele: String,
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Only export changes codified on or after this date (YYYY-MM-DD).
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only export changes codified on or before this date (YYYY-MM-DD).
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Export the html data repository of a stele, as it was on a date, into a static site.
+    ///
+    /// Requires `stelae update` to have inserted the commit hashes of the data repository.
+    #[command(after_long_help = EXPORT_SITE_EXAMPLES)]
+    Site {
+        /// Qualified name of the stele, e.g. `org-name/repo-name-law`. Defaults to the root stele.
+        #[arg(short, long)]
+        stele: Option<String>,
+        /// Date of the site to export (YYYY-MM-DD).
+        #[arg(short, long)]
+        date: NaiveDate,
+        /// Directory to write the site to.
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Write files as they are, without moving them under `_date/<date>/` and rewriting urls.
+        #[arg(long, default_value_t = false)]
+        no_date_urls: bool,
+    },
+    /// Export the html data repository of a stele, as it was on a date, into a WARC file.
+    ///
+    /// Requires `stelae update` to have inserted the commit hashes of the data repository.
+    #[command(after_long_help = EXPORT_WARC_EXAMPLES)]
+    Warc {
+        /// Qualified name of the stele, e.g. `org-name/repo-name-law`. Defaults to the root stele.
+        #[arg(short, long)]
+        stele: Option<String>,
+        /// Date of the documents to export (YYYY-MM-DD).
+        #[arg(short, long)]
+        date: NaiveDate,
+        /// WARC file to write, e.g. `law-2023-10-22.warc`.
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Base url the documents are served at, e.g. `https://law.example.gov`.
+        #[arg(short, long)]
+        base_url: String,
+        /// Record documents under their current urls instead of `/_date/<date>/` urls.
+        #[arg(long, default_value_t = false)]
+        no_date_urls: bool,
+    },
 }
 
 /// Place to initialize tracing
@@ -76,7 +662,7 @@ enum Subcommands {
     clippy::expect_used,
     reason = "Expect that console logging can be initialized"
 )]
-fn init_tracing(archive_path: &Path) {
+fn init_tracing(archive_path: &Path, ansi: bool, output: Output) {
     let taf_dir = archive_path.join(PathBuf::from("./.taf"));
 
     let debug_file_appender =
@@ -91,12 +677,21 @@ fn init_tracing(archive_path: &Path) {
     // this is to avoid color coding in log files and to make it easier to read.
     debug_layer = debug_layer.with_ansi(false);
     error_layer = error_layer.with_ansi(false);
-    // also log to console
-    let console_layer = fmt::layer().with_target(true).with_filter(
-        EnvFilter::try_from_default_env()
-            .or_else(|_| EnvFilter::try_new("info"))
-            .expect("Failed to initialize console logging"),
-    );
+    // also log to console, to stderr when stdout is reserved for the JSON results.
+    let console_writer = if output.is_json() {
+        BoxMakeWriter::new(io::stderr)
+    } else {
+        BoxMakeWriter::new(io::stdout)
+    };
+    let console_layer = fmt::layer()
+        .with_writer(console_writer)
+        .with_target(true)
+        .with_ansi(ansi)
+        .with_filter(
+            EnvFilter::try_from_default_env()
+                .or_else(|_| EnvFilter::try_new("info"))
+                .expect("Failed to initialize console logging"),
+        );
 
     tracing_subscriber::registry()
         .with(debug_layer)
@@ -109,43 +704,464 @@ fn init_tracing(archive_path: &Path) {
     }
 }
 
+/// Serve documents in the archive on `listen`, in the background if requested in `options`.
+///
+/// # Errors
+/// Errors if the server cannot be started in the background, or fails.
+fn serve(
+    cli: &Cli,
+    archive_path: PathBuf,
+    listen: Listen,
+    individual: bool,
+    warmup: bool,
+    scheduled: Option<Scheduled>,
+    options: &daemon::Options,
+) -> Result<(), CliError> {
+    daemon::run(&archive_path.clone(), options, || {
+        serve_archive(
+            &cli.archive_path,
+            archive_path,
+            listen,
+            individual,
+            warmup,
+            scheduled,
+        )
+    })
+}
+
+/// Write the completion script of the Stelae CLI for `shell` to stdout.
+///
+/// # Errors
+/// Errors if the script cannot be written to stdout.
+fn completions(shell: Shell) -> Result<(), CliError> {
+    let mut command = command();
+    let name = command.get_name().to_owned();
+    let mut stdout = io::stdout();
+    clap_complete::generate(shell, &mut command, name, &mut stdout);
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The Stelae CLI, with every option also read from the environment, see [`with_env`].
+fn command() -> Command {
+    with_env(Cli::command(), ENV_PREFIX)
+}
+
+/// Read every option of `command` and its subcommands from the environment variable named after
+/// it and its subcommands, starting with `prefix`, unless it is given on the command line, e.g.
+/// `--archive-path` from `STELAE_ARCHIVE_PATH` and `stelae mirror --from` from
+/// `STELAE_MIRROR_FROM`, so options of the same name in different subcommands do not collide.
+fn with_env(command: Command, prefix: &str) -> Command {
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_owned())
+        .collect();
+    let with_args = command.mut_args(|arg| {
+        let name = format!("{prefix}{}", env_name(arg.get_id().as_str()));
+        arg.env(name)
+    });
+    subcommands.iter().fold(with_args, |parent, name| {
+        let subcommand_prefix = format!("{prefix}{}_", env_name(name));
+        parent.mut_subcommand(name, |subcommand| with_env(subcommand, &subcommand_prefix))
+    })
+}
+
+/// Part of the name of an environment variable for the option or subcommand `name`.
+fn env_name(name: &str) -> String {
+    name.replace('-', "_").to_uppercase()
+}
+
+/// Parse the arguments of the process, and the `STELAE_*` environment variables.
+///
+/// Exits with the usage error if the arguments are invalid.
+fn parse() -> Cli {
+    Cli::from_arg_matches(&command().get_matches()).unwrap_or_else(|err| err.exit())
+}
+
+/// Run the async `command` to completion on a runtime of its own.
+fn block_on<F: Future>(command: F) -> F::Output {
+    System::new().block_on(command)
+}
+
 /// Central place to execute commands
 ///
 /// # Errors
-/// This function returns the generic `CliError`, based on which we exit with a known exit code.
+/// This function returns the `CliError`, based on which we exit with a known exit code.
+#[expect(
+    clippy::too_many_lines,
+    reason = "Every subcommand is dispatched from this single match"
+)]
 fn execute_command(cli: &Cli, archive_path: PathBuf) -> Result<(), CliError> {
-    match cli.subcommands {
+    match cli.subcommands.clone() {
         Subcommands::Git { port } => serve_git(&cli.archive_path, archive_path, port),
-        Subcommands::Serve { port, individual } => {
-            serve_archive(&cli.archive_path, archive_path, port, individual)
+        Subcommands::Serve {
+            port,
+            bind,
+            admin,
+            individual,
+            warmup,
+            daemon,
+            service,
+            pid_file,
+            log_file,
+            updates,
+        } => {
+            let options = daemon::Options {
+                daemon,
+                service,
+                pid_file,
+                log_file,
+            };
+            let listen = Listen {
+                bind: Bind::parse(bind.as_deref(), port),
+                admin: admin.bind(),
+                base_path: cli.base_path.clone().unwrap_or_default(),
+            };
+            serve(
+                cli,
+                archive_path,
+                listen,
+                individual,
+                warmup,
+                updates.scheduled()?,
+                &options,
+            )
         }
-        Subcommands::Update => changes::insert(&cli.archive_path, archive_path),
+        Subcommands::Update {
+            strict,
+            check_links,
+        } => update(cli, archive_path, strict, check_links),
+        Subcommands::Bench { log, repeat } => {
+            block_on(bench::replay(&cli.archive_path, archive_path, &log, repeat))
+        }
+        Subcommands::Mirror {
+            from,
+            token_file,
+            interval,
+        } => mirror(cli, &archive_path, &from, &token_file, interval),
+        Subcommands::Manifest {
+            verify: Some(manifest_file),
+            key_file,
+            ..
+        } => manifest::verify(
+            &archive_path,
+            &manifest_file,
+            key_file.as_deref(),
+            cli.output,
+        ),
+        Subcommands::Manifest {
+            stele,
+            date: Some(date),
+            out,
+            verify: None,
+            key_file,
+        } => block_on(manifest::create(
+            &cli.archive_path,
+            archive_path,
+            stele.as_deref(),
+            date,
+            out.as_deref(),
+            key_file.as_deref(),
+        )),
+        Subcommands::Manifest { date: None, .. } => Err(CliError::GenericError),
+        Subcommands::DiskUsage { interval } => block_on(disk_usage::monitor(
+            &cli.archive_path,
+            &archive_path,
+            interval,
+        )),
+        Subcommands::DiffPublications { compared } => compared.report(cli, archive_path),
+        Subcommands::Stats => block_on(stats::report(&cli.archive_path, archive_path, cli.output)),
+        Subcommands::Validate { individual } => block_on(startup::run(
+            &cli.archive_path,
+            archive_path,
+            individual,
+            cli.output,
+        )),
+        Subcommands::Status { server, token_file } => block_on(status::report(
+            &cli.archive_path,
+            archive_path,
+            server.as_deref(),
+            token_file.as_deref(),
+            cli.output,
+        )),
+        Subcommands::Doctor { clock_url } => diagnose(cli, archive_path, clock_url.as_deref()),
+        // generated before the archive is looked up, see `run_without_archive`.
+        Subcommands::Completions { .. } => Ok(()),
+        #[cfg(feature = "test-fixtures")]
+        Subcommands::Generate {
+            out,
+            documents,
+            versions,
+        } => generate::run(
+            &out,
+            generate::Size {
+                documents,
+                versions,
+            },
+        ),
+        Subcommands::Backup { out } => {
+            block_on(backup::backup(&cli.archive_path, archive_path, &out))
+        }
+        Subcommands::Restore { from, no_config } => restore(cli, archive_path, &from, no_config),
+        Subcommands::Export {
+            export:
+                ExportSubcommands::Changes {
+                    stele,
+                    format,
+                    from,
+                    to,
+                },
+        } => block_on(export::changes(archive_path, &stele, format, from, to)),
+        Subcommands::Export {
+            export:
+                ExportSubcommands::Site {
+                    stele,
+                    date,
+                    out,
+                    no_date_urls,
+                },
+        } => block_on(export::site(
+            &cli.archive_path,
+            archive_path,
+            stele.as_deref(),
+            date,
+            &out,
+            &export::Urls {
+                dated: !no_date_urls,
+                base_path: cli.base_path.clone().unwrap_or_default(),
+            },
+        )),
+        Subcommands::Export {
+            export:
+                ExportSubcommands::Warc {
+                    stele,
+                    date,
+                    out,
+                    base_url,
+                    no_date_urls,
+                },
+        } => block_on(export::warc(
+            &cli.archive_path,
+            archive_path,
+            stele.as_deref(),
+            date,
+            &out,
+            &base_url,
+            &export::Urls {
+                dated: !no_date_urls,
+                base_path: cli.base_path.clone().unwrap_or_default(),
+            },
+        )),
+    }
+}
+
+/// Mirror the archive from the upstream Stelae git server at `from`, authenticated with the token
+/// in `token_file`.
+fn mirror(
+    cli: &Cli,
+    archive_path: &Path,
+    from: &str,
+    token_file: &Path,
+    interval: Option<u64>,
+) -> Result<(), CliError> {
+    let upstream = read_upstream(from, token_file)?;
+    block_on(mirror::mirror(
+        &cli.archive_path,
+        archive_path,
+        &upstream,
+        interval,
+        cli.wait,
+    ))
+}
+
+/// Upstream Stelae git server at `url`, authenticated with the token in `token_file`.
+///
+/// # Errors
+/// Errors with [`CliError::ConfigError`] if the token cannot be read.
+fn read_upstream(url: &str, token_file: &Path) -> Result<Upstream, CliError> {
+    Upstream::new(url, token_file).map_err(|err| {
+        tracing::error!("{err:#}");
+        CliError::ConfigError
+    })
+}
+
+/// Insert the history of the archive into the database, holding the lock of the archive.
+fn update(
+    cli: &Cli,
+    archive_path: PathBuf,
+    strict: bool,
+    check_links: bool,
+) -> Result<(), CliError> {
+    let _locked = lock_archive(&archive_path, "update", cli.wait)?;
+    block_on(changes::insert(
+        &cli.archive_path,
+        archive_path,
+        strict,
+        check_links,
+        cli.output,
+    ))
+}
+
+/// Restore the archive from the backup file `from`, holding the lock of the archive.
+fn restore(cli: &Cli, archive_path: PathBuf, from: &Path, no_config: bool) -> Result<(), CliError> {
+    let _locked = lock_archive(&archive_path, "restore", cli.wait)?;
+    block_on(backup::restore(archive_path, from, no_config))
+}
+
+/// Diagnose the environment of the archive, comparing the clock with `clock_url` if given.
+fn diagnose(cli: &Cli, archive_path: PathBuf, clock_url: Option<&str>) -> Result<(), CliError> {
+    block_on(doctor::run(
+        &cli.archive_path,
+        archive_path,
+        clock_url,
+        cli.output,
+    ))
+}
+
+/// Lock the archive for `command`, see [`lock`].
+///
+/// # Errors
+/// Errors if the archive is locked by another process and `wait` is not set.
+fn lock_archive(archive_path: &Path, command: &str, wait: bool) -> Result<lock::Guard, CliError> {
+    lock::Guard::acquire(archive_path, command, wait).map_err(|err| {
+        tracing::error!("Unable to lock the archive: {err:#}");
+        CliError::GenericError
+    })
+}
+
+/// Exit with 0 on success, or with the exit code of the error.
+fn exit(result: Result<(), CliError>) -> ! {
+    match result {
+        Ok(()) => process::exit(0),
+        Err(err) => process::exit(err.exit_code()),
+    }
+}
+
+/// Run the subcommand and exit, if it does not require an archive.
+fn run_without_archive(cli: &Cli) {
+    // completions are generated without an archive, and without logging to stdout.
+    if let Subcommands::Completions { shell } = cli.subcommands {
+        exit(completions(shell));
+    }
+    // the archive is generated where there is no archive yet.
+    #[cfg(feature = "test-fixtures")]
+    if let Subcommands::Generate {
+        out,
+        documents,
+        versions,
+    } = cli.subcommands.clone()
+    {
+        tracing_subscriber::fmt().with_writer(io::stderr).init();
+        exit(generate::run(
+            &out,
+            generate::Size {
+                documents,
+                versions,
+            },
+        ));
     }
 }
 
 /// Main entrypoint to application
 ///
-/// Exits with 1 if we encounter an error
+/// Exits with the exit code of the [`CliError`] if we encounter an error
 pub fn run() {
     tracing::debug!("Starting application");
-    let cli = Cli::parse();
+    let cli = parse();
+    if let Some(database_url) = cli.database_url.as_deref() {
+        env::set_var("DATABASE_URL", database_url);
+    }
+    run_without_archive(&cli);
     let archive_path_wd = Path::new(&cli.archive_path);
     let Ok(archive_path) = find_archive_path(archive_path_wd) else {
         tracing::error!(
             "error: could not find `.taf` folder in `{}` or any parent directory",
             &cli.archive_path
         );
-        process::exit(1);
+        process::exit(CliError::ArchiveParseError.exit_code());
     };
 
-    init_tracing(&archive_path);
+    // the console output of a daemon or service is written to a log file, so it is not colored
+    // either.
+    let daemonized = matches!(
+        cli.subcommands,
+        Subcommands::Serve { daemon: true, .. } | Subcommands::Serve { service: true, .. }
+    );
+    init_tracing(&archive_path, !daemonized, cli.output);
 
     match execute_command(&cli, archive_path) {
         Ok(()) => process::exit(0),
         Err(err) => {
-            // Exit with 1 if we encounter an error
             tracing::error!("Application error: {err:?}");
-            process::exit(1);
+            process::exit(err.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::utils::cli::{command, Cli};
+    use clap::{Arg, Command, CommandFactory as _};
+    use clap_complete::Shell;
+
+    #[test]
+    fn test_cli_when_built_expect_valid_commands() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn test_command_when_built_expect_every_option_read_from_env() {
+        let cut = command();
+        let env = |arg: &Arg| {
+            arg.get_env()
+                .map(|name| name.to_string_lossy().into_owned())
+        };
+        let archive_path = cut
+            .get_arguments()
+            .find(|arg| arg.get_id() == "archive_path")
+            .unwrap();
+        assert_eq!(env(archive_path).as_deref(), Some("STELAE_ARCHIVE_PATH"));
+        let serve = cut.find_subcommand("serve").unwrap();
+        let actual: Vec<_> = serve.get_arguments().filter_map(env).collect();
+        assert!(
+            actual.contains(&"STELAE_SERVE_BIND".to_owned()),
+            "{actual:?}"
+        );
+        assert!(
+            actual.contains(&"STELAE_SERVE_ADMIN_PORT".to_owned()),
+            "{actual:?}"
+        );
+        let mirror = cut.find_subcommand("mirror").unwrap();
+        let mirror_env: Vec<_> = mirror.get_arguments().filter_map(env).collect();
+        assert!(
+            mirror_env.contains(&"STELAE_MIRROR_FROM".to_owned()),
+            "{mirror_env:?}"
+        );
+        let changes = cut
+            .find_subcommand("export")
+            .and_then(|export| export.find_subcommand("changes"))
+            .unwrap();
+        let changes_env: Vec<_> = changes.get_arguments().filter_map(env).collect();
+        assert!(
+            changes_env.contains(&"STELAE_EXPORT_CHANGES_STELE".to_owned()),
+            "{changes_env:?}"
+        );
+        let export = cut.find_subcommand("export").unwrap();
+        assert!(export
+            .get_subcommands()
+            .flat_map(Command::get_arguments)
+            .all(|arg| env(arg).is_some()));
+    }
+
+    #[test]
+    fn test_completions_when_every_shell_expect_subcommands_completed() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut actual = vec![];
+            clap_complete::generate(shell, &mut Cli::command(), "stelae", &mut actual);
+            let script = String::from_utf8(actual).unwrap();
+            assert!(script.contains("disk-usage"), "{shell} completions");
+            assert!(script.contains("warc"), "{shell} completions");
         }
     }
 }
@@ -0,0 +1,506 @@
+//! Run `stelae serve` in the background, detached from the terminal, or as a Windows service.
+//!
+//! With `--daemon`, the process forks before the server starts, and the parent exits once the
+//! child is detached. The child starts a new session, reads stdin from `/dev/null`, and writes its
+//! console output to a log file, `.taf/stelae-serve.log` unless `--log-file` is given. With
+//! `--pid-file`, the id of the serving process is written to the file, which is removed again once
+//! the server shut down gracefully on `SIGTERM` or `SIGINT`.
+//!
+//! With `--service`, the process is run by the Windows service control manager, e.g. after
+//! `sc.exe create stelae binPath= "stelae.exe --archive-path C:\law serve --service"`. It writes
+//! its console output to the log file, reports the service running once it is connected to the
+//! manager, and shuts the server down gracefully when the service is stopped, or the system shuts
+//! down.
+//!
+//! Forking is only supported on unix, and services only on Windows.
+use crate::server::errors::CliError;
+use actix_web::dev::ServerHandle;
+use anyhow::Context as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+#[cfg(unix)]
+use {
+    nix::sys::signal::kill,
+    nix::unistd::{dup2, fork, setsid, ForkResult, Pid},
+    std::fs::{File, OpenOptions},
+    std::os::unix::io::AsRawFd as _,
+};
+#[cfg(windows)]
+use {
+    std::ffi::c_void,
+    std::fs::OpenOptions,
+    std::os::windows::io::IntoRawHandle as _,
+    std::ptr,
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::sync::{mpsc, Condvar, Mutex, OnceLock},
+    std::thread,
+    windows_sys::Win32::Foundation::{
+        ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_SPECIFIC_ERROR, HANDLE, NO_ERROR,
+    },
+    windows_sys::Win32::System::Console::{SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE},
+    windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+        SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+        SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS,
+        SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+        SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+    },
+};
+
+/// File the console output of a daemon is written to, relative to the archive.
+pub const DEFAULT_LOG_FILE: &str = ".taf/stelae-serve.log";
+
+/// Options of `stelae serve` for running in the background.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Whether to detach from the terminal.
+    pub daemon: bool,
+    /// Whether to run as a Windows service.
+    pub service: bool,
+    /// File to write the id of the serving process to, if any.
+    pub pid_file: Option<PathBuf>,
+    /// File to write the console output of the daemon or service to. Defaults to
+    /// [`DEFAULT_LOG_FILE`].
+    pub log_file: Option<PathBuf>,
+}
+
+/// Side of the fork the process continues on.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fork {
+    /// The original process, which should exit.
+    Parent,
+    /// The detached process, which should serve.
+    Child,
+}
+
+/// Pid file of the serving process, removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    /// Path of the pid file.
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the id of this process to the pid file at `path`.
+    ///
+    /// # Errors
+    /// Errors if the pid file holds the id of a running process, or cannot be written.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        check_pid_file(path)?;
+        fs::write(path, format!("{}\n", process::id()))
+            .with_context(|| format!("could not write pid file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            tracing::warn!("Unable to remove pid file {}: {err}", self.path.display());
+        }
+    }
+}
+
+/// Run `serve`, in the background if `options.daemon` is set, as a Windows service if
+/// `options.service` is set, with a pid file if one is given.
+///
+/// Returns right away in the original process once the server is running in the background.
+///
+/// # Errors
+/// Errors if another server is running with the pid file, if the process cannot be detached or
+/// connected to the service control manager, or if `serve` errors.
+pub fn run<F>(archive_path: &Path, options: &Options, serve: F) -> Result<(), CliError>
+where
+    F: FnOnce() -> Result<(), CliError>,
+{
+    let failed = |err: anyhow::Error| {
+        tracing::error!("Unable to start the server: {err:?}");
+        CliError::GenericError
+    };
+    if let Some(pid_file) = options.pid_file.as_deref() {
+        check_pid_file(pid_file).map_err(failed)?;
+    }
+    let log_file = options
+        .log_file
+        .clone()
+        .unwrap_or_else(|| archive_path.join(DEFAULT_LOG_FILE));
+    if options.daemon && daemonize(&log_file).map_err(failed)? == Fork::Parent {
+        return Ok(());
+    }
+    let with_pid_file = || {
+        let _pid_file = options
+            .pid_file
+            .as_deref()
+            .map(PidFile::create)
+            .transpose()
+            .map_err(failed)?;
+        serve()
+    };
+    if options.service {
+        serve_as_service(&log_file, with_pid_file)
+    } else {
+        with_pid_file()
+    }
+}
+
+/// Error if the pid file at `path` holds the id of a running process.
+///
+/// # Errors
+/// Errors if the process of the pid file is running, or the pid file cannot be read.
+pub fn check_pid_file(path: &Path) -> anyhow::Result<()> {
+    match fs::read_to_string(path) {
+        Ok(contents) => match parse_pid(&contents) {
+            Some(pid) if is_running(pid) => anyhow::bail!(
+                "stelae is already running with pid {pid}, see {}",
+                path.display()
+            ),
+            _ => Ok(()),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("could not read pid file {}", path.display())),
+    }
+}
+
+/// Parse the process id in the `contents` of a pid file.
+#[must_use]
+pub fn parse_pid(contents: &str) -> Option<u32> {
+    contents.trim().parse().ok().filter(|pid| *pid > 0)
+}
+
+/// Whether a process with the id `pid` is running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    i32::try_from(pid).is_ok_and(|raw| kill(Pid::from_raw(raw), None).is_ok())
+}
+
+/// Whether a process with the id `pid` is running, which cannot be checked off unix.
+#[cfg(not(unix))]
+const fn is_running(_pid: u32) -> bool {
+    false
+}
+
+/// Detach the process from the terminal, writing its console output to `log_file`.
+///
+/// Must be called before the server, or any other thread, is started.
+///
+/// # Errors
+/// Errors if the log file cannot be opened, or the process cannot be forked.
+#[cfg(unix)]
+pub fn daemonize(log_file: &Path) -> anyhow::Result<Fork> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("could not open log file {}", log_file.display()))?;
+    let null = File::open("/dev/null")?;
+    // SAFETY: no other threads are running before the server is started, so the child may run
+    // any code after the fork.
+    match unsafe { fork() }? {
+        ForkResult::Parent { child } => {
+            tracing::info!(
+                "Serving in the background with pid {child}, logging to {}",
+                log_file.display()
+            );
+            Ok(Fork::Parent)
+        }
+        ForkResult::Child => {
+            setsid()?;
+            dup2(null.as_raw_fd(), io::stdin().as_raw_fd())?;
+            dup2(log.as_raw_fd(), io::stdout().as_raw_fd())?;
+            dup2(log.as_raw_fd(), io::stderr().as_raw_fd())?;
+            Ok(Fork::Child)
+        }
+    }
+}
+
+/// Detach the process from the terminal, which is only supported on unix.
+///
+/// # Errors
+/// Always errors.
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: &Path) -> anyhow::Result<Fork> {
+    anyhow::bail!(
+        "Serving in the background is only supported on unix, run `stelae serve` under a service wrapper instead"
+    )
+}
+
+/// Name the service is registered under, ignored by the manager for services running in a
+/// process of their own.
+#[cfg(windows)]
+const SERVICE_NAME: &str = "stelae";
+
+/// Handle of the server stopped with the service, see [`stop_with_service`].
+#[cfg(windows)]
+static SERVER: OnceLock<ServerHandle> = OnceLock::new();
+
+/// Whether the service was asked to stop, possibly before the server started.
+#[cfg(windows)]
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle the status of the service is reported through.
+#[cfg(windows)]
+static STATUS_HANDLE: OnceLock<SERVICE_STATUS_HANDLE> = OnceLock::new();
+
+/// Receives whether the service is connected to the service control manager.
+#[cfg(windows)]
+static CONNECTED: Mutex<Option<mpsc::Sender<io::Result<()>>>> = Mutex::new(None);
+
+/// Exit code of the server, set once it stopped, see [`CliError::exit_code`].
+#[cfg(windows)]
+static EXIT_CODE: (Mutex<Option<u32>>, Condvar) = (Mutex::new(None), Condvar::new());
+
+/// Stop the server of `handle` gracefully once the Windows service is stopped.
+///
+/// Signals stop the server anywhere else, see [`actix_web::HttpServer::disable_signals`].
+#[cfg(windows)]
+pub fn stop_with_service(handle: &ServerHandle) {
+    if SERVER.set(handle.clone()).is_ok() && STOP_REQUESTED.load(Ordering::SeqCst) {
+        drop(handle.stop(true));
+    }
+}
+
+/// Stop the server of `handle` gracefully once the Windows service is stopped, which only runs
+/// on Windows.
+#[cfg(not(windows))]
+pub const fn stop_with_service(_handle: &ServerHandle) {}
+
+/// Run `serve` as a Windows service, writing its console output to `log_file`.
+///
+/// The service is reported running once the process is connected to the service control
+/// manager, and stopped with the exit code of `serve`.
+///
+/// # Errors
+/// Errors if the log file cannot be opened, the process was not started by the service control
+/// manager, or `serve` errors.
+#[cfg(windows)]
+pub fn serve_as_service<F>(log_file: &Path, serve: F) -> Result<(), CliError>
+where
+    F: FnOnce() -> Result<(), CliError>,
+{
+    let failed = |err: anyhow::Error| {
+        tracing::error!("Unable to start the service: {err:?}");
+        CliError::GenericError
+    };
+    redirect_output(log_file).map_err(failed)?;
+    let (connected, on_connected) = mpsc::channel();
+    *CONNECTED.lock().unwrap_or_else(|err| err.into_inner()) = Some(connected);
+    let dispatcher = thread::spawn(dispatch);
+    on_connected
+        .recv()
+        .unwrap_or_else(|_| Err(io::Error::other("the service dispatcher exited")))
+        .context("could not connect to the service control manager, is stelae run as a service?")
+        .map_err(failed)?;
+    let served = serve();
+    let (code, stopped) = &EXIT_CODE;
+    let exit_code = served.as_ref().err().map_or(0, CliError::exit_code);
+    *code.lock().unwrap_or_else(|err| err.into_inner()) =
+        Some(u32::try_from(exit_code).unwrap_or(1));
+    stopped.notify_all();
+    if dispatcher.join().is_err() {
+        tracing::warn!("The service dispatcher panicked");
+    }
+    served
+}
+
+/// Run `serve` as a Windows service, which is only supported on Windows.
+///
+/// # Errors
+/// Always errors.
+#[cfg(not(windows))]
+pub fn serve_as_service<F>(_log_file: &Path, _serve: F) -> Result<(), CliError>
+where
+    F: FnOnce() -> Result<(), CliError>,
+{
+    tracing::error!("Serving as a service is only supported on Windows, use `--daemon` instead");
+    Err(CliError::GenericError)
+}
+
+/// Write the console output of the process to `log_file`, as a service has no console.
+///
+/// # Errors
+/// Errors if the log file cannot be opened, or the output cannot be redirected.
+#[cfg(windows)]
+fn redirect_output(log_file: &Path) -> anyhow::Result<()> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("could not open log file {}", log_file.display()))?;
+    // The handle stays open for the lifetime of the process.
+    let handle = log.into_raw_handle() as HANDLE;
+    for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+        // SAFETY: the handle is a valid file handle that is never closed.
+        if unsafe { SetStdHandle(std_handle, handle) } == 0 {
+            return Err(io::Error::last_os_error()).context("could not redirect the output");
+        }
+    }
+    Ok(())
+}
+
+/// Connect the process to the service control manager, until the service stopped.
+#[cfg(windows)]
+fn dispatch() {
+    let mut name: Vec<u16> = SERVICE_NAME.encode_utf16().chain([0]).collect();
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: name.as_mut_ptr(),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: ptr::null_mut(),
+            lpServiceProc: None,
+        },
+    ];
+    // SAFETY: the table is terminated by a null entry, and outlives the dispatcher.
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+        report_connected(Err(io::Error::last_os_error()));
+    }
+}
+
+/// Tell [`serve_as_service`] whether the service is connected to the service control manager.
+#[cfg(windows)]
+fn report_connected(connected: io::Result<()>) {
+    if let Some(sender) = CONNECTED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()
+    {
+        drop(sender.send(connected));
+    }
+}
+
+/// Entry point of the service, called by the dispatcher: report the service running, and then
+/// stopped with the exit code of the server once it stopped.
+#[cfg(windows)]
+extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+    let name: Vec<u16> = SERVICE_NAME.encode_utf16().chain([0]).collect();
+    // SAFETY: the name is null terminated, and the handler takes no context.
+    let handle =
+        unsafe { RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(handle_control), ptr::null()) };
+    if handle == 0 {
+        report_connected(Err(io::Error::last_os_error()));
+        return;
+    }
+    drop(STATUS_HANDLE.set(handle));
+    set_status(SERVICE_RUNNING, 0);
+    report_connected(Ok(()));
+    let (code, stopped) = &EXIT_CODE;
+    let mut exit_code = code.lock().unwrap_or_else(|err| err.into_inner());
+    while exit_code.is_none() {
+        exit_code = stopped
+            .wait(exit_code)
+            .unwrap_or_else(|err| err.into_inner());
+    }
+    set_status(SERVICE_STOPPED, exit_code.unwrap_or(0));
+}
+
+/// Handle the `control` requests of the service control manager, stopping the server gracefully
+/// when the service is stopped or the system shuts down.
+#[cfg(windows)]
+extern "system" fn handle_control(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut c_void,
+    _context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            set_status(SERVICE_STOP_PENDING, 0);
+            STOP_REQUESTED.store(true, Ordering::SeqCst);
+            if let Some(server) = SERVER.get() {
+                drop(server.stop(true));
+            }
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+/// Report the `state` of the service, and the `exit_code` of the server once stopped.
+#[cfg(windows)]
+fn set_status(state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let Some(&handle) = STATUS_HANDLE.get() else {
+        return;
+    };
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: if state == SERVICE_RUNNING {
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+        } else {
+            0
+        },
+        dwWin32ExitCode: if exit_code == 0 {
+            NO_ERROR
+        } else {
+            ERROR_SERVICE_SPECIFIC_ERROR
+        },
+        dwServiceSpecificExitCode: exit_code,
+        dwCheckPoint: 0,
+        dwWaitHint: if state == SERVICE_STOP_PENDING {
+            30_000
+        } else {
+            0
+        },
+    };
+    // SAFETY: the handle was registered for the service, and the status outlives the call.
+    if unsafe { SetServiceStatus(handle, &status) } == 0 {
+        tracing::warn!(
+            "Unable to report the service status: {}",
+            io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::utils::daemon::{check_pid_file, parse_pid, run, Options};
+    use std::{fs, process};
+
+    #[test]
+    fn test_parse_pid_when_trailing_newline_expect_pid() {
+        let cut = parse_pid;
+        assert_eq!(cut("4242\n"), Some(4242));
+        assert_eq!(cut("0"), None);
+        assert_eq!(cut("stelae"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_pid_file_when_process_running_expect_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stelae.pid");
+        let cut = check_pid_file;
+        assert!(cut(&path).is_ok());
+
+        fs::write(&path, format!("{}\n", process::id())).unwrap();
+        assert!(cut(&path).is_err());
+
+        fs::write(&path, "not a pid").unwrap();
+        assert!(cut(&path).is_ok());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_when_service_off_windows_expect_error_without_serving() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = Options {
+            service: true,
+            ..Options::default()
+        };
+        let cut = run;
+        let mut served = false;
+        let actual = cut(dir.path(), &options, || {
+            served = true;
+            Ok(())
+        });
+        assert!(actual.is_err());
+        assert!(!served);
+    }
+}
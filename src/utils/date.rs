@@ -0,0 +1,147 @@
+//! Utility functions for the `%Y-%m-%d` dates of publications and versions.
+//!
+//! Dates are stored in the database and served in responses as `%Y-%m-%d` strings, and kept as
+//! [`NaiveDate`] everywhere in between.
+use chrono::{NaiveDate, ParseResult};
+use sqlx::{any::AnyRow, Row as _};
+
+/// Format of the dates of publications and versions, e.g. `2023-10-22`.
+pub const FORMAT: &str = "%Y-%m-%d";
+
+/// Parse a `%Y-%m-%d` date.
+///
+/// # Errors
+/// Errors if `date` is not a `%Y-%m-%d` date.
+pub fn parse(date: &str) -> ParseResult<NaiveDate> {
+    NaiveDate::parse_from_str(date, FORMAT)
+}
+
+/// Format `date` as a `%Y-%m-%d` date.
+#[must_use]
+pub fn format(date: NaiveDate) -> String {
+    date.format(FORMAT).to_string()
+}
+
+/// Read the `%Y-%m-%d` date in `column` of a database `row`.
+///
+/// # Errors
+/// Errors if the column is missing, or its value is not a `%Y-%m-%d` date.
+pub fn from_row(row: &AnyRow, column: &str) -> Result<NaiveDate, sqlx::Error> {
+    let date: String = row.try_get(column)?;
+    parse(&date).map_err(|err| sqlx::Error::ColumnDecode {
+        index: column.to_owned(),
+        source: Box::new(err),
+    })
+}
+
+/// Serialize and deserialize a [`NaiveDate`] as a `%Y-%m-%d` string, with
+/// `#[serde(with = "crate::utils::date::ymd")]`.
+pub mod ymd {
+    use chrono::NaiveDate;
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+
+    /// Serialize `date` as a `%Y-%m-%d` string.
+    ///
+    /// # Errors
+    /// Errors if the serializer fails.
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format(super::FORMAT))
+    }
+
+    /// Deserialize a `%Y-%m-%d` string.
+    ///
+    /// # Errors
+    /// Errors if the value is not a `%Y-%m-%d` string.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let date = String::deserialize(deserializer)?;
+        super::parse(&date).map_err(D::Error::custom)
+    }
+}
+
+/// Serialize and deserialize an optional [`NaiveDate`] as a `%Y-%m-%d` string or `null`, with
+/// `#[serde(with = "crate::utils::date::ymd_option")]`.
+pub mod ymd_option {
+    use chrono::NaiveDate;
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+
+    /// Serialize `date` as a `%Y-%m-%d` string, or `null` if there is no date.
+    ///
+    /// # Errors
+    /// Errors if the serializer fails.
+    pub fn serialize<S: Serializer>(
+        date: &Option<NaiveDate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match *date {
+            Some(found) => serializer.collect_str(&found.format(super::FORMAT)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserialize a `%Y-%m-%d` string or `null`.
+    ///
+    /// # Errors
+    /// Errors if the value is neither a `%Y-%m-%d` string nor `null`.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|date| super::parse(&date).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Dated {
+        #[serde(with = "ymd")]
+        date: NaiveDate,
+    }
+
+    #[test]
+    fn test_ymd_when_round_tripped_expect_same_date() {
+        let dated = Dated {
+            date: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+        };
+        let json = serde_json::to_string(&dated).unwrap();
+        assert_eq!(json, r#"{"date":"2023-01-02"}"#);
+        let actual: Dated = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual, dated);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct MaybeDated {
+        #[serde(with = "ymd_option")]
+        date: Option<NaiveDate>,
+    }
+
+    #[test]
+    fn test_ymd_option_when_round_tripped_expect_same_date_or_null() {
+        for date in [NaiveDate::from_ymd_opt(2023, 1, 2), None] {
+            let dated = MaybeDated { date };
+            let json = serde_json::to_string(&dated).unwrap();
+            let actual: MaybeDated = serde_json::from_str(&json).unwrap();
+            assert_eq!(actual, dated);
+        }
+        let json = serde_json::to_string(&MaybeDated { date: None }).unwrap();
+        assert_eq!(json, r#"{"date":null}"#);
+    }
+
+    #[test]
+    fn test_ymd_when_not_a_date_expect_error() {
+        let actual = serde_json::from_str::<Dated>(r#"{"date":"2023-13-01"}"#);
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn test_format_when_single_digit_month_and_day_expect_zero_padded() {
+        let date = NaiveDate::from_ymd_opt(987, 1, 2).unwrap();
+        assert_eq!(format(date), "0987-01-02");
+        assert_eq!(parse("0987-01-02").unwrap(), date);
+    }
+}
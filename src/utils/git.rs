@@ -1,16 +1,21 @@
 //! The git module contains structs for interacting with git repositories
 //! in the Stelae Archive.
-use crate::utils::paths::clean_path;
+use crate::utils::path_index::{self, PathIndex};
+use crate::utils::paths::normalize_path;
 use anyhow::Context as _;
-use git2::{Commit, Repository, Sort};
+use git2::{Commit, ObjectType, Oid, Repository, Sort};
 use std::{
     fmt,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 /// This is the first step towards having custom errors
 pub const GIT_REQUEST_NOT_FOUND: &str = "Git object doesn't exist";
 
+/// Postfixes tried, in order, on a requested path to find its blob, see [`Repo::get_bytes_at_path`].
+pub const BLOB_PATH_POSTFIXES: [&str; 4] = ["", "/index.html", ".html", "index.html"];
+
 /// Represents a git repository within an oll archive. includes helpers for
 /// for interacting with the Git Repo.
 /// Expects a path to the archive, as well as the repo's organization and name.
@@ -27,6 +32,15 @@ pub struct Repo {
     pub repo: Repository,
 }
 
+/// An entry of a directory in a git repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// Name of the entry, without its directory.
+    pub name: String,
+    /// Whether the entry is a directory.
+    pub is_directory: bool,
+}
+
 impl fmt::Debug for Repo {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -60,9 +74,14 @@ impl Repo {
     ///
     /// # Errors
     ///
-    /// Will return `Err` if git repository does not exist at `{org}/{name}`
-    /// in archive, or if there is something wrong with the git repository.
+    /// Will return `Err` if `org` or `name` is not a single path segment, if git repository
+    /// does not exist at `{org}/{name}` in archive, or if there is something wrong with the git repository.
     pub fn new(archive_path: &Path, org: &str, name: &str) -> anyhow::Result<Self> {
+        let is_segment =
+            |part: &str| !part.is_empty() && !part.contains('/') && normalize_path(part).is_ok();
+        if !is_segment(org) || !is_segment(name) {
+            anyhow::bail!("Invalid repository name: {org}/{name}");
+        }
         let archive_path_str = archive_path.to_string_lossy();
         tracing::trace!(org, name, "Creating new Repo at {archive_path_str}");
         let repo_path = format!("{archive_path_str}/{org}/{name}");
@@ -88,14 +107,25 @@ impl Repo {
         commitish: &str,
     ) -> anyhow::Result<Vec<u8>> {
         let repo = Self::new(archive_path, namespace, name)?;
-        let blob_path = clean_path(remainder);
+        let blob_path = normalize_path(remainder)?;
         let blob = repo.get_bytes_at_path(commitish, &blob_path)?;
         Ok(blob)
     }
 
+    /// Id of the commit `HEAD` points to.
+    ///
+    /// # Errors
+    /// Will return `Err` if `HEAD` does not point to a commit.
+    pub fn head_commit_id(&self) -> anyhow::Result<String> {
+        Ok(self.repo.head()?.peel_to_commit()?.id().to_string())
+    }
+
     /// Returns bytes of blob found in the commit `commitish` at path `path`
     /// if a blob is not found at path, it will try adding ".html", "index.html,
     /// and "/index.html".
+    /// Paths are looked up in the index of the commit, see [`path_index`], if it is held or
+    /// the commit is `HEAD`, and in the trees of the commit otherwise, so a single lookup in a
+    /// former commit, e.g. of a date, never indexes the whole commit.
     /// Example usage:
     ///
     //// let content: Vec<u8> = repo.get_bytes_at_path(
@@ -108,8 +138,18 @@ impl Repo {
     /// Will return `Err` if `commitish` does not exist in repo, if a blob does
     /// not exist in commit at `path`, or if there is a problem with reading repo.
     pub fn get_bytes_at_path(&self, commitish: &str, path: &str) -> anyhow::Result<Vec<u8>> {
+        if let Some(index) = self.path_index(commitish) {
+            let found = BLOB_PATH_POSTFIXES
+                .iter()
+                .find_map(|postfix| index.get(&format!("{path}{postfix}")));
+            if let Some(&blob_id) = found {
+                return Ok(self.repo.find_blob(blob_id)?.content().to_owned());
+            }
+            tracing::debug!(commitish, path, "Couldn't find requested path in index");
+            anyhow::bail!(GIT_REQUEST_NOT_FOUND)
+        }
         let base_revision = format!("{commitish}:{path}");
-        for postfix in ["", "/index.html", ".html", "index.html"] {
+        for postfix in BLOB_PATH_POSTFIXES {
             let query = &format!("{base_revision}{postfix}");
             let blob = self.find(query);
             if blob.is_ok() {
@@ -121,6 +161,70 @@ impl Repo {
         anyhow::bail!(GIT_REQUEST_NOT_FOUND)
     }
 
+    /// The index of the paths of the blobs of the commit `commitish`, if held, or built on first
+    /// access if `commitish` is the commit `HEAD` points to.
+    ///
+    /// Returns `None` if `commitish` is not a commit, or the commit is not indexed.
+    fn path_index(&self, commitish: &str) -> Option<Arc<PathIndex>> {
+        let commit = self
+            .repo
+            .revparse_single(commitish)
+            .ok()?
+            .peel_to_commit()
+            .ok()?
+            .id();
+        let head = self.repo.head().ok().and_then(|head| head.target());
+        if head == Some(commit) {
+            path_index::index_of(&self.repo, &self.path, commit)
+        } else {
+            path_index::held(&self.path, commit)
+        }
+    }
+
+    /// Returns bytes of the blob with the id `blob_id`.
+    ///
+    /// # Errors
+    /// Will return `Err` if `blob_id` is not a valid id, or no blob with the id exists in repo.
+    pub fn get_bytes_by_id(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        let blob = self.repo.find_blob(Oid::from_str(blob_id)?)?;
+        Ok(blob.content().to_owned())
+    }
+
+    /// Whether a blob exists in the commit `commitish` at exactly `path`, without reading it.
+    #[must_use]
+    pub fn has_blob(&self, commitish: &str, path: &str) -> bool {
+        self.repo
+            .revparse_single(&format!("{commitish}:{path}"))
+            .is_ok_and(|obj| obj.as_blob().is_some())
+    }
+
+    /// List the entries of the directory at `path` in the commit `commitish`.
+    ///
+    /// Directories are listed first, then files, each by name.
+    ///
+    /// # Errors
+    /// Will return `Err` if `commitish` does not exist in repo, or if there is no directory at `path`.
+    pub fn list_tree(&self, commitish: &str, path: &str) -> anyhow::Result<Vec<TreeEntry>> {
+        let obj = self.repo.revparse_single(&format!("{commitish}:{path}"))?;
+        let tree = obj.as_tree().context("Couldn't cast Git object to tree")?;
+        let mut entries: Vec<TreeEntry> = tree
+            .iter()
+            .filter_map(|entry| {
+                Some(TreeEntry {
+                    name: entry.name()?.to_owned(),
+                    is_directory: entry.kind() == Some(ObjectType::Tree),
+                })
+            })
+            .collect();
+        entries.sort_by(|first, second| {
+            second
+                .is_directory
+                .cmp(&first.is_directory)
+                .then_with(|| first.name.cmp(&second.name))
+        });
+        Ok(entries)
+    }
+
     /// Find something like `abc123:/path/to/something.txt` in the Git repo
     fn find(&self, query: &str) -> anyhow::Result<Vec<u8>> {
         tracing::trace!(query, "Git reverse parse search");
@@ -0,0 +1,568 @@
+//! The html module contains helpers for rewriting html documents
+use lol_html::html_content::ContentType;
+use lol_html::{doc_text, element, text, HtmlRewriter, Settings};
+use std::cell::Cell;
+
+use crate::stelae::types::repositories::Position;
+use crate::utils::template::escape;
+
+/// Attributes of html elements that can hold a url.
+const URL_ATTRIBUTES: [&str; 3] = ["href", "src", "action"];
+
+/// Elements whose text is separated from the surrounding text when extracting it.
+const BLOCK_ELEMENTS: &str =
+    "address, article, aside, blockquote, br, dd, div, dl, dt, footer, h1, h2, h3, h4, h5, h6, \
+     header, hr, li, main, nav, ol, p, pre, section, table, td, th, tr, ul";
+
+/// Prefix all root-relative urls in the `html` document with `prefix`.
+///
+/// Only urls starting with a single `/` are rewritten. Absolute and protocol-relative urls,
+/// relative urls and urls to stelae's own endpoints (starting with `/_`) are left untouched.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn prefix_root_relative_urls(html: &[u8], prefix: &str) -> anyhow::Result<Vec<u8>> {
+    rewrite_urls(html, |url| {
+        (is_root_relative(url) && !url.starts_with("/_")).then(|| format!("{prefix}{url}"))
+    })
+}
+
+/// Prefix all root-relative urls in the `html` document with the `base_path` it is served under.
+///
+/// Unlike [`prefix_root_relative_urls`], urls to stelae's own endpoints are prefixed too.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn mount_root_relative_urls(html: &[u8], base_path: &str) -> anyhow::Result<Vec<u8>> {
+    rewrite_urls(html, |url| {
+        is_root_relative(url).then(|| format!("{base_path}{url}"))
+    })
+}
+
+/// Rewrite the urls of the `html` document that `rewrite` returns a new url for.
+fn rewrite_urls(html: &[u8], rewrite: impl Fn(&str) -> Option<String>) -> anyhow::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("[href], [src], [action]", |el| {
+                for attribute in URL_ATTRIBUTES {
+                    let prefixed_url = el.get_attribute(attribute).and_then(|url| rewrite(&url));
+                    if let Some(url) = prefixed_url {
+                        el.set_attribute(attribute, &url)?;
+                    }
+                }
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    Ok(output)
+}
+
+/// Find the `href` of every link (`<a>` element) in the `html` document, in document order.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+pub fn find_link_hrefs(html: &[u8]) -> anyhow::Result<Vec<String>> {
+    let mut hrefs = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("a[href]", |el| {
+                hrefs.extend(el.get_attribute("href"));
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |_: &[u8]| {},
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    Ok(hrefs)
+}
+
+/// Whether the `html` document is a fragment, i.e. has neither an `<html>` nor a `<body>` element.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+pub fn is_fragment(html: &[u8]) -> anyhow::Result<bool> {
+    let mut has_chrome = false;
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("html, body", |_| {
+                has_chrome = true;
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |_: &[u8]| {},
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    Ok(!has_chrome)
+}
+
+/// Find the text of the first `<h1>` element in the `html` document, with its character references
+/// decoded.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+pub fn find_first_heading(html: &[u8]) -> anyhow::Result<Option<String>> {
+    let headings: Cell<usize> = Cell::new(0);
+    let mut heading: Option<String> = None;
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("h1", |_| {
+                    headings.set(headings.get().saturating_add(1));
+                    Ok(())
+                }),
+                text!("h1", |chunk| {
+                    if headings.get() == 1 {
+                        heading
+                            .get_or_insert_with(String::new)
+                            .push_str(chunk.as_str());
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |_: &[u8]| {},
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    Ok(heading.map(|text| decode_entities(text.trim())))
+}
+
+/// Insert `banner` at the start of the body of the `html` document, and `style` at the end of its head.
+///
+/// Both are inserted at the start of fragments without a `<body>` element.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn insert_banner(html: &[u8], banner: &str, style: &str) -> anyhow::Result<Vec<u8>> {
+    let has_body = Cell::new(false);
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("head", |el| {
+                    el.append(style, ContentType::Html);
+                    Ok(())
+                }),
+                element!("body", |el| {
+                    has_body.set(true);
+                    el.prepend(banner, ContentType::Html);
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    if has_body.get() {
+        return Ok(output);
+    }
+    Ok([style.as_bytes(), banner.as_bytes(), &output].concat())
+}
+
+/// Point the canonical link of the `html` document at `href`, replacing any existing one.
+///
+/// The link is appended to the head of the document, or inserted at the start of fragments
+/// without a `<head>` element.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn set_canonical_link(html: &[u8], href: &str) -> anyhow::Result<Vec<u8>> {
+    let link = format!("<link rel=\"canonical\" href=\"{}\">", escape(href));
+    let has_head = Cell::new(false);
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("head", |el| {
+                    has_head.set(true);
+                    el.append(&link, ContentType::Html);
+                    Ok(())
+                }),
+                element!("link[rel=canonical]", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    if has_head.get() {
+        return Ok(output);
+    }
+    Ok([link.as_bytes(), &output].concat())
+}
+
+/// Point the `href` of the existing `<link rel="canonical">` elements of the `html` document at
+/// `href`.
+///
+/// Unlike [`set_canonical_link`], no element is inserted or removed. Every byte of the document
+/// is kept as is, except the start tags of the canonical links, whose attributes are written
+/// back separated by single spaces with the new `href` double quoted.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn set_canonical_href(html: &[u8], href: &str) -> anyhow::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("link[rel=canonical][href]", |el| {
+                el.set_attribute("href", href)?;
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    Ok(output)
+}
+
+/// Append `markup` to the head of the `html` document, or insert it at the start of fragments
+/// without a `<head>` element.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn append_to_head(html: &[u8], markup: &str) -> anyhow::Result<Vec<u8>> {
+    let has_head = Cell::new(false);
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("head", |el| {
+                has_head.set(true);
+                el.append(markup, ContentType::Html);
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    if has_head.get() {
+        return Ok(output);
+    }
+    Ok([markup.as_bytes(), &output].concat())
+}
+
+/// Insert each `markup` at its position in the head or body of the `html` document, keeping the
+/// order of markup at the same position.
+///
+/// In fragments without a `<head>` or `<body>` element, the markup of that element is inserted
+/// at the start of the fragment, or at its end for the end of the body.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn insert_at(html: &[u8], markup: &[(Position, String)]) -> anyhow::Result<Vec<u8>> {
+    let at = |position: Position| {
+        markup
+            .iter()
+            .filter(|&&(at_position, _)| at_position == position)
+            .map(|pair| pair.1.as_str())
+            .collect::<String>()
+    };
+    let (head_start, head_end) = (at(Position::HeadStart), at(Position::HeadEnd));
+    let (body_start, body_end) = (at(Position::BodyStart), at(Position::BodyEnd));
+    let has_head = Cell::new(false);
+    let has_body = Cell::new(false);
+    let mut output = Vec::with_capacity(html.len());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("head", |el| {
+                    has_head.set(true);
+                    el.prepend(&head_start, ContentType::Html);
+                    el.append(&head_end, ContentType::Html);
+                    Ok(())
+                }),
+                element!("body", |el| {
+                    has_body.set(true);
+                    el.prepend(&body_start, ContentType::Html);
+                    el.append(&body_end, ContentType::Html);
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| output.extend_from_slice(chunk),
+    );
+    rewriter.write(html)?;
+    rewriter.end()?;
+    let mut start = String::new();
+    if !has_head.get() {
+        start.push_str(&head_start);
+        start.push_str(&head_end);
+    }
+    if has_body.get() {
+        return Ok([start.as_bytes(), &output].concat());
+    }
+    start.push_str(&body_start);
+    Ok([start.as_bytes(), &output, body_end.as_bytes()].concat())
+}
+
+/// Extract the readable text of the `html` document, with whitespace collapsed.
+///
+/// The contents of the head and of `<script>`, `<style>` and `<template>` elements are skipped.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+pub fn extract_text(html: &[u8]) -> anyhow::Result<String> {
+    let mut readable = Vec::with_capacity(html.len());
+    let mut stripper = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("head, script, style, template", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+                element!(BLOCK_ELEMENTS, |el| {
+                    el.before(" ", ContentType::Text);
+                    el.after(" ", ContentType::Text);
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |chunk: &[u8]| readable.extend_from_slice(chunk),
+    );
+    stripper.write(html)?;
+    stripper.end()?;
+    let mut text = String::new();
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            document_content_handlers: vec![doc_text!(|chunk| {
+                text.push_str(chunk.as_str());
+                Ok(())
+            })],
+            ..Settings::new()
+        },
+        |_: &[u8]| {},
+    );
+    rewriter.write(&readable)?;
+    rewriter.end()?;
+    Ok(decode_entities(
+        &text.split_whitespace().collect::<Vec<_>>().join(" "),
+    ))
+}
+
+/// Decode the numeric character references, and the named ones most commonly found in the text of
+/// documents.
+///
+/// Unknown or malformed references are kept as they are.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(rest.get(..start).unwrap_or_default());
+        rest = rest.get(start..).unwrap_or_default();
+        let reference = rest
+            .find(';')
+            .and_then(|end| Some((rest.get(1..end)?, end)))
+            .and_then(|(name, end)| Some((decode_entity(name)?, end)));
+        if let Some((character, end)) = reference {
+            decoded.push(character);
+            rest = rest.get(end.saturating_add(1)..).unwrap_or_default();
+        } else {
+            decoded.push('&');
+            rest = rest.get(1..).unwrap_or_default();
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Character of the reference `&name;`, if known.
+fn decode_entity(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(decimal) = name.strip_prefix('#') {
+        decimal.parse().ok()?
+    } else {
+        return match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "sect" => Some('\u{a7}'),
+            "para" => Some('\u{b6}'),
+            "ndash" => Some('\u{2013}'),
+            "mdash" => Some('\u{2014}'),
+            _ => None,
+        };
+    };
+    char::from_u32(code)
+}
+
+/// Whether `url` is root-relative, i.e. starts with a single `/`.
+#[must_use]
+pub fn is_root_relative(url: &str) -> bool {
+    url.starts_with('/') && !url.starts_with("//")
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::stelae::types::repositories::Position;
+    use crate::utils::html::{
+        extract_text, find_first_heading, find_link_hrefs, insert_at, insert_banner, is_fragment,
+        prefix_root_relative_urls, set_canonical_href, set_canonical_link,
+    };
+
+    fn rewrite(html: &str) -> String {
+        let actual = prefix_root_relative_urls(html.as_bytes(), "/_date/2023-10-22").unwrap();
+        String::from_utf8(actual).unwrap()
+    }
+
+    #[test]
+    fn test_prefix_root_relative_urls_when_root_relative_href_expect_prefixed() {
+        let cut = rewrite;
+        let actual = cut(r#"<a href="/us/ca/cities/san-mateo/">San Mateo</a>"#);
+        let expected = r#"<a href="/_date/2023-10-22/us/ca/cities/san-mateo/">San Mateo</a>"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_prefix_root_relative_urls_when_src_and_action_expect_prefixed() {
+        let cut = rewrite;
+        let actual = cut(r#"<img src="/static/logo.png"><form action="/search"></form>"#);
+        let expected = r#"<img src="/_date/2023-10-22/static/logo.png"><form action="/_date/2023-10-22/search"></form>"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_prefix_root_relative_urls_when_not_root_relative_expect_unchanged() {
+        let cut = rewrite;
+        let html = r#"<a href="https://example.com/a">a</a><a href="//cdn.example.com/b">b</a><a href="c/d">c</a><a href="/_api/versions/">d</a>"#;
+        let actual = cut(html);
+        assert_eq!(actual, html);
+    }
+
+    #[test]
+    fn test_find_link_hrefs_when_links_expect_hrefs_in_order() {
+        let cut = find_link_hrefs;
+        let html = r#"<a href="/us/ca/">a</a><img src="/logo.png"><a name="x">b</a><a href="../1.02/">c</a>"#;
+        let actual = cut(html.as_bytes()).unwrap();
+        let expected = vec!["/us/ca/".to_owned(), "../1.02/".to_owned()];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_is_fragment_when_no_html_or_body_expect_true() {
+        let cut = is_fragment;
+        assert!(cut(b"<section><h1>1.01</h1></section>").unwrap());
+        assert!(!cut(b"<html><head></head><body><p>a</p></body></html>").unwrap());
+        assert!(!cut(b"<body><p>a</p></body>").unwrap());
+    }
+
+    #[test]
+    fn test_find_first_heading_when_headings_expect_first_text() {
+        let cut = find_first_heading;
+        let html = "<h2>Part</h2><h1> Section <em>1.01</em> </h1><h1>Section 1.02</h1>";
+        let actual = cut(html.as_bytes()).unwrap();
+        assert_eq!(actual, Some("Section 1.01".to_owned()));
+        assert_eq!(cut(b"<p>No heading</p>").unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_first_heading_when_character_references_expect_decoded() {
+        let cut = find_first_heading;
+        let html = "<h1>&sect;&nbsp;1 Fees &amp; Costs &#8212; &#x201C;Rates&#x201D; &bogus;</h1>";
+        let actual = cut(html.as_bytes()).unwrap();
+        let expected = "\u{a7} 1 Fees & Costs \u{2014} \u{201c}Rates\u{201d} &bogus;";
+        assert_eq!(actual, Some(expected.to_owned()));
+    }
+
+    #[test]
+    fn test_insert_banner_when_document_or_fragment_expect_banner_first_in_body() {
+        let cut = |html: &str| {
+            String::from_utf8(
+                insert_banner(html.as_bytes(), "<div>b</div>", "<style></style>").unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            cut("<html><head><title>t</title></head><body><p>a</p></body></html>"),
+            "<html><head><title>t</title><style></style></head><body><div>b</div><p>a</p></body></html>"
+        );
+        assert_eq!(cut("<p>a</p>"), "<style></style><div>b</div><p>a</p>");
+    }
+
+    #[test]
+    fn test_set_canonical_link_when_existing_link_expect_replaced() {
+        let cut = |html: &str| {
+            String::from_utf8(
+                set_canonical_link(html.as_bytes(), "https://example.com/a?b&c").unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            cut(r#"<html><head><link rel="canonical" href="/old"></head><body></body></html>"#),
+            r#"<html><head><link rel="canonical" href="https://example.com/a?b&amp;c"></head><body></body></html>"#
+        );
+        assert_eq!(
+            cut("<p>a</p>"),
+            r#"<link rel="canonical" href="https://example.com/a?b&amp;c"><p>a</p>"#
+        );
+    }
+
+    #[test]
+    fn test_set_canonical_href_when_existing_link_expect_only_href_changed() {
+        let cut = |html: &str| {
+            String::from_utf8(set_canonical_href(html.as_bytes(), "https://example.com/a").unwrap())
+                .unwrap()
+        };
+        assert_eq!(
+            cut("<html>\n <head>\t<LINK  href='/old' rel=canonical data-x>\n</head>\n<body> <p>a</p>  </body></html>"),
+            "<html>\n <head>\t<LINK href=\"https://example.com/a\" rel=canonical data-x>\n</head>\n<body> <p>a</p>  </body></html>"
+        );
+        assert_eq!(cut("<p>a</p>\n"), "<p>a</p>\n");
+    }
+
+    #[test]
+    fn test_insert_at_when_document_or_fragment_expect_markup_at_positions() {
+        let markup = [
+            (Position::BodyEnd, "<i>4</i>".to_owned()),
+            (Position::HeadEnd, "<b>2</b>".to_owned()),
+            (Position::HeadStart, "<b>1</b>".to_owned()),
+            (Position::BodyStart, "<i>3</i>".to_owned()),
+            (Position::HeadEnd, "<b>2b</b>".to_owned()),
+        ];
+        let cut =
+            |html: &str| String::from_utf8(insert_at(html.as_bytes(), &markup).unwrap()).unwrap();
+        assert_eq!(
+            cut("<html><head><title>t</title></head><body><p>a</p></body></html>"),
+            "<html><head><b>1</b><title>t</title><b>2</b><b>2b</b></head><body><i>3</i><p>a</p><i>4</i></body></html>"
+        );
+        assert_eq!(
+            cut("<p>a</p>"),
+            "<b>1</b><b>2</b><b>2b</b><i>3</i><p>a</p><i>4</i>"
+        );
+    }
+
+    #[test]
+    fn test_extract_text_when_document_expect_body_text_only() {
+        let cut = |html: &str| extract_text(html.as_bytes()).unwrap();
+        assert_eq!(
+            cut("<html><head><title>t</title></head><body><h1>A &amp; B</h1><p>one\n two</p><script>x()</script><p>three</p></body></html>"),
+            "A & B one two three"
+        );
+        assert_eq!(cut("<p>a <em>b</em></p><p>c</p>"), "a b c");
+    }
+}
@@ -2,7 +2,53 @@
 use mime_guess::from_ext as file_extension_to_mime;
 
 use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use mime::Mime;
+use ring::hmac;
+use serde::Serialize;
+use sha2::digest::Output;
+use sha2::{Digest as _, Sha256};
 use std::path::Path;
+use std::str;
+use std::time::Duration;
+
+/// Header carrying the SHA-256 of the body of blob responses.
+pub const CONTENT_SHA256_HEADER: &str = "X-Content-SHA256";
+/// Time an outgoing request may take, from connecting to reading the response.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The SHA-256 of a body, hashed once and rendered as the header values carrying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha256Digest(Output<Sha256>);
+
+impl Sha256Digest {
+    /// Lowercase hexadecimal digest, the value of the `X-Content-SHA256` header.
+    #[must_use]
+    pub fn hex(&self) -> String {
+        format!("{:x}", self.0)
+    }
+
+    /// Hash `body`.
+    #[must_use]
+    pub fn of(body: &[u8]) -> Self {
+        Self(Sha256::digest(body))
+    }
+
+    /// Value of the `Repr-Digest` header (RFC 9530), the digest as a `sha-256` byte sequence.
+    #[must_use]
+    pub fn repr_digest(&self) -> String {
+        format!("sha-256=:{}:", STANDARD.encode(self.0))
+    }
+}
+
+/// Client of outgoing requests, e.g. to identity providers and webhooks, which gives up after
+/// [`CLIENT_TIMEOUT`].
+#[must_use]
+pub fn client() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(CLIENT_TIMEOUT).build()
+}
 
 /// `get_contenttype` uses the file extension to return the `ContentType`
 /// for the content at `path`.
@@ -24,9 +70,176 @@ pub fn get_contenttype(path: &str) -> ContentType {
     ContentType(mime)
 }
 
+/// Declare `charset=utf-8` on the textual media type `mime`, unless it declares a charset.
+///
+/// Textual media types are `text/*`, and the json, xml and javascript application types.
+#[must_use]
+pub fn with_charset(mime: Mime) -> Mime {
+    let is_textual = mime.type_() == mime::TEXT
+        || (mime.type_() == mime::APPLICATION
+            && (matches!(mime.subtype().as_str(), "json" | "xml" | "javascript")
+                || matches!(mime.suffix(), Some(mime::JSON | mime::XML))));
+    if !is_textual || mime.get_param(mime::CHARSET).is_some() {
+        return mime;
+    }
+    format!("{mime}; charset=utf-8").parse().unwrap_or(mime)
+}
+
+/// Complete `response` with `body` of the media type `mime`, generated by stelae as UTF-8.
+///
+/// Textual content declares `charset=utf-8`. The body is sized, so its exact `Content-Length`
+/// is sent, also in responses to `HEAD` requests.
+pub fn respond(mut response: HttpResponseBuilder, mime: Mime, body: Vec<u8>) -> HttpResponse {
+    response
+        .insert_header(ContentType(with_charset(mime)))
+        .body(body)
+}
+
+/// Complete `response` with the blob `body` of the media type `mime`, see [`respond`].
+///
+/// Blobs are published in any encoding, so `charset=utf-8` is only declared if `body` is valid
+/// UTF-8. Otherwise the charset is left to the content, e.g. to the declaration of an xml document.
+/// The `X-Content-SHA256` header carries the SHA-256 of the exact bytes served, see
+/// [`content_sha256`], so clients can verify downloads and deduplicate mirrored content.
+pub fn respond_blob(response: HttpResponseBuilder, mime: Mime, body: Vec<u8>) -> HttpResponse {
+    let digest = Sha256Digest::of(&body);
+    respond_digested_blob(response, mime, body, &digest)
+}
+
+/// Complete `response` with the blob `body` whose SHA-256 `digest` is already known, e.g. from
+/// the cache, see [`respond_blob`].
+pub fn respond_digested_blob(
+    mut response: HttpResponseBuilder,
+    mime: Mime,
+    body: Vec<u8>,
+    digest: &Sha256Digest,
+) -> HttpResponse {
+    response.insert_header((CONTENT_SHA256_HEADER, digest.hex()));
+    if str::from_utf8(&body).is_err() {
+        return response.insert_header(ContentType(mime)).body(body);
+    }
+    respond(response, mime, body)
+}
+
+/// Complete `response` with `value` serialized as json, see [`respond`].
+pub fn respond_json<T: Serialize>(response: HttpResponseBuilder, value: &T) -> HttpResponse {
+    match serde_json::to_vec(value) {
+        Ok(body) => respond(response, mime::APPLICATION_JSON, body),
+        Err(err) => {
+            tracing::error!("Unable to serialize response: {err:?}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Complete `response` with the plain `text`, see [`respond`].
+pub fn respond_text<T: Into<String>>(response: HttpResponseBuilder, text: T) -> HttpResponse {
+    respond(response, mime::TEXT_PLAIN, text.into().into_bytes())
+}
+
+/// Value of the `Repr-Digest` header (RFC 9530) of a response with `body`, the `sha-256`
+/// digest of the body as a byte sequence, e.g. `sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:`.
+#[must_use]
+pub fn repr_digest(body: &[u8]) -> String {
+    Sha256Digest::of(body).repr_digest()
+}
+
+/// Lowercase hexadecimal SHA-256 of `body`, the value of the `X-Content-SHA256` header.
+#[must_use]
+pub fn content_sha256(body: &[u8]) -> String {
+    Sha256Digest::of(body).hex()
+}
+
+/// Whether the secret `given` by a request equals the `expected` secret.
+///
+/// Both are compared through their HMAC, whose tags are compared in constant time, so the time
+/// taken does not tell how much of `given` matched. An empty `expected` secret matches nothing.
+#[must_use]
+pub fn secrets_match(expected: &str, given: &str) -> bool {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"stelae-secret-comparison");
+    let tag = hmac::sign(&key, expected.as_bytes());
+    !expected.is_empty() && hmac::verify(&key, given.as_bytes(), tag.as_ref()).is_ok()
+}
+
 #[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
 mod test {
-    use crate::utils::http::get_contenttype;
+    use crate::utils::http::{
+        content_sha256, get_contenttype, repr_digest, respond_blob, respond_digested_blob,
+        secrets_match, with_charset, Sha256Digest, CONTENT_SHA256_HEADER,
+    };
+    use actix_web::HttpResponse;
+
+    #[test]
+    fn test_secrets_match_when_equal_expect_match_unless_empty() {
+        let cut = secrets_match;
+        assert!(cut("a-long-secret", "a-long-secret"));
+        assert!(!cut("a-long-secret", "a-long-secreT"));
+        assert!(!cut("a-long-secret", "a-long"));
+        assert!(!cut("", ""));
+    }
+
+    #[test]
+    fn test_content_sha256_when_empty_body_expect_hex_digest() {
+        let cut = content_sha256;
+        let actual = cut(b"");
+        let expected = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_repr_digest_when_empty_body_expect_sha256_byte_sequence() {
+        let cut = repr_digest;
+        let actual = cut(b"");
+        let expected = "sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_respond_blob_when_not_utf8_expect_no_charset() {
+        let cut = respond_blob;
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>\xa7 1</p>".to_vec();
+        let encoded = cut(HttpResponse::Ok(), mime::TEXT_XML, latin1);
+        assert_eq!(encoded.headers().get("Content-Type").unwrap(), "text/xml");
+        let utf8 = cut(HttpResponse::Ok(), mime::TEXT_XML, b"<p>1</p>".to_vec());
+        assert_eq!(
+            utf8.headers().get("Content-Type").unwrap(),
+            "text/xml; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn test_respond_digested_blob_when_digest_given_expect_digest_header() {
+        let cut = respond_digested_blob;
+        let digest = Sha256Digest::of(b"hashed once");
+        let response = cut(
+            HttpResponse::Ok(),
+            mime::TEXT_HTML,
+            b"<p>1</p>".to_vec(),
+            &digest,
+        );
+        assert_eq!(
+            response.headers().get(CONTENT_SHA256_HEADER).unwrap(),
+            digest.hex().as_str()
+        );
+    }
+
+    #[test]
+    fn test_with_charset_when_textual_expect_utf8_charset() {
+        let cut = with_charset;
+        assert_eq!(cut(mime::TEXT_HTML).to_string(), "text/html; charset=utf-8");
+        assert_eq!(
+            cut(mime::APPLICATION_JSON).to_string(),
+            "application/json; charset=utf-8"
+        );
+        assert_eq!(
+            cut("application/rdf+xml".parse().unwrap()).to_string(),
+            "application/rdf+xml; charset=utf-8"
+        );
+        assert_eq!(cut(mime::TEXT_HTML_UTF_8), mime::TEXT_HTML_UTF_8);
+        assert_eq!(cut(mime::IMAGE_PNG), mime::IMAGE_PNG);
+        assert_eq!(cut(mime::APPLICATION_PDF), mime::APPLICATION_PDF);
+    }
 
     #[test]
     fn test_get_contenttype_when_html_ext_expect_html() {
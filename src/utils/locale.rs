@@ -0,0 +1,180 @@
+//! The locale module formats display dates in the language of a stele.
+use chrono::{Datelike as _, NaiveDate};
+
+/// Languages that display dates can be formatted in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    /// English, e.g. `October 22, 2023`.
+    #[default]
+    En,
+    /// Spanish, e.g. `22 de octubre de 2023`.
+    Es,
+    /// French, e.g. `22 octobre 2023`.
+    Fr,
+    /// German, e.g. `22. Oktober 2023`.
+    De,
+    /// Portuguese, e.g. `22 de outubro de 2023`.
+    Pt,
+    /// Italian, e.g. `22 ottobre 2023`.
+    It,
+}
+
+impl Locale {
+    /// Find the locale of a BCP 47 language `tag`, e.g. `es` or `es-MX`, by its primary language.
+    ///
+    /// Returns `None` if the language is not supported.
+    #[must_use]
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Self::En),
+            "es" => Some(Self::Es),
+            "fr" => Some(Self::Fr),
+            "de" => Some(Self::De),
+            "pt" => Some(Self::Pt),
+            "it" => Some(Self::It),
+            _ => None,
+        }
+    }
+
+    /// Names of the months, January first.
+    #[expect(
+        clippy::non_ascii_literal,
+        reason = "Month names are spelled as in their language"
+    )]
+    const fn month_names(self) -> [&'static str; 12] {
+        match self {
+            Self::En => [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ],
+            Self::Es => [
+                "enero",
+                "febrero",
+                "marzo",
+                "abril",
+                "mayo",
+                "junio",
+                "julio",
+                "agosto",
+                "septiembre",
+                "octubre",
+                "noviembre",
+                "diciembre",
+            ],
+            Self::Fr => [
+                "janvier",
+                "février",
+                "mars",
+                "avril",
+                "mai",
+                "juin",
+                "juillet",
+                "août",
+                "septembre",
+                "octobre",
+                "novembre",
+                "décembre",
+            ],
+            Self::De => [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ],
+            Self::Pt => [
+                "janeiro",
+                "fevereiro",
+                "março",
+                "abril",
+                "maio",
+                "junho",
+                "julho",
+                "agosto",
+                "setembro",
+                "outubro",
+                "novembro",
+                "dezembro",
+            ],
+            Self::It => [
+                "gennaio",
+                "febbraio",
+                "marzo",
+                "aprile",
+                "maggio",
+                "giugno",
+                "luglio",
+                "agosto",
+                "settembre",
+                "ottobre",
+                "novembre",
+                "dicembre",
+            ],
+        }
+    }
+
+    /// Format `date` as a long display date.
+    #[must_use]
+    pub fn format_date(self, date: NaiveDate) -> String {
+        let month = usize::try_from(date.month0())
+            .ok()
+            .and_then(|index| self.month_names().get(index).copied())
+            .unwrap_or_default();
+        let (day, year) = (date.day(), date.year());
+        match self {
+            Self::En => format!("{month} {day:02}, {year}"),
+            Self::Es | Self::Pt => format!("{day} de {month} de {year}"),
+            Self::Fr | Self::It => format!("{day} {month} {year}"),
+            Self::De => format!("{day}. {month} {year}"),
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::unwrap_used,
+    clippy::non_ascii_literal,
+    reason = "Tests panic on unexpected values"
+)]
+mod test {
+    use crate::utils::locale::Locale;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_from_tag_when_region_subtag_expect_primary_language() {
+        let cut = Locale::from_tag;
+        assert_eq!(cut("es-MX"), Some(Locale::Es));
+        assert_eq!(cut("FR"), Some(Locale::Fr));
+        assert_eq!(cut("xx"), None);
+    }
+
+    #[test]
+    fn test_format_date_when_locale_expect_localized_month_and_order() {
+        let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        assert_eq!(Locale::En.format_date(date), "March 01, 2023");
+        assert_eq!(Locale::Es.format_date(date), "1 de marzo de 2023");
+        assert_eq!(Locale::De.format_date(date), "1. März 2023");
+    }
+}
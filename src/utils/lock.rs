@@ -0,0 +1,245 @@
+//! Lock of the archive, held while the archive or its database is changed.
+//!
+//! `stelae update`, `stelae mirror`, `stelae restore` and the updates scheduled by `stelae serve`
+//! hold an exclusive lock of `.taf/stelae.lock` while they run, so two of them never change the
+//! same archive at once. The lock file records which command holds the lock, with its pid, host
+//! and start time, to tell who is holding a busy archive.
+//!
+//! On unix the lock is an advisory `flock(2)` lock, which is released by the operating system
+//! when its process exits, so a crashed update never leaves the archive locked. Elsewhere, the
+//! lock file itself is the lock, and is removed when the lock is released. The `SQLite` database
+//! has no advisory locks, so the lock file is the only lock of the archive.
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process;
+#[cfg(not(unix))]
+use std::{env, io, thread, time::Duration};
+#[cfg(unix)]
+use {
+    nix::errno::Errno,
+    nix::fcntl::{Flock, FlockArg},
+    nix::unistd::gethostname,
+    std::fs::File,
+};
+
+/// Lock file of the archive, relative to the archive.
+pub const LOCK_FILE: &str = ".taf/stelae.lock";
+
+/// Interval the lock file is polled at while waiting for the lock, where it cannot be waited on.
+#[cfg(not(unix))]
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Command holding the lock of the archive, as recorded in the lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    /// Command holding the lock, e.g. `update`.
+    pub command: String,
+    /// Id of the process holding the lock.
+    pub pid: u32,
+    /// Name of the host the process runs on.
+    pub host: String,
+    /// Time the lock was acquired.
+    pub since: DateTime<Utc>,
+}
+
+impl Holder {
+    /// Holder of the lock for `command` in this process.
+    fn current(command: &str) -> Self {
+        Self {
+            command: command.to_owned(),
+            pid: process::id(),
+            host: hostname(),
+            since: Utc::now(),
+        }
+    }
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "`stelae {}` with pid {} on {} since {}",
+            self.command,
+            self.pid,
+            self.host,
+            self.since.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+/// Exclusive lock of the archive, released when dropped.
+#[derive(Debug)]
+pub struct Guard {
+    /// Path of the lock file.
+    path: PathBuf,
+    /// The locked lock file.
+    #[cfg(unix)]
+    file: Flock<File>,
+}
+
+impl Guard {
+    /// Lock the archive at `archive_path` for `command`.
+    ///
+    /// If another process holds the lock, waits for it to be released if `wait` is set, or
+    /// errors right away with the holder of the lock.
+    ///
+    /// # Errors
+    /// Errors if the archive is locked and `wait` is not set, or the lock file cannot be written.
+    pub fn acquire(archive_path: &Path, command: &str, wait: bool) -> anyhow::Result<Self> {
+        let path = archive_path.join(LOCK_FILE);
+        let lock = Self::lock(path, wait)?;
+        lock.record(&Holder::current(command))?;
+        tracing::debug!("Locked the archive for `stelae {command}`");
+        Ok(lock)
+    }
+
+    /// Take the `flock(2)` lock of the lock file at `path`.
+    #[cfg(unix)]
+    fn lock(path: PathBuf, wait: bool) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("could not open lock file {}", path.display()))?;
+        let locked = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(locked) => locked,
+            Err((unlocked, Errno::EWOULDBLOCK)) => {
+                let held_by = held_by(&path);
+                if !wait {
+                    anyhow::bail!(
+                        "the archive is locked by {held_by}, rerun with --wait to wait for it"
+                    );
+                }
+                tracing::info!("Waiting for the lock of the archive held by {held_by}");
+                Flock::lock(unlocked, FlockArg::LockExclusive)
+                    .map_err(|(_file, errno)| errno)
+                    .with_context(|| format!("could not lock {}", path.display()))?
+            }
+            Err((_file, errno)) => {
+                return Err(errno).with_context(|| format!("could not lock {}", path.display()))
+            }
+        };
+        Ok(Self { path, file: locked })
+    }
+
+    /// Create the lock file at `path`, which is the lock where `flock(2)` is not available.
+    #[cfg(not(unix))]
+    fn lock(path: PathBuf, wait: bool) -> anyhow::Result<Self> {
+        let mut waiting = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_file) => return Ok(Self { path }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    let held_by = held_by(&path);
+                    if !wait {
+                        anyhow::bail!(
+                            "the archive is locked by {held_by}, rerun with --wait to wait for it, or remove {} if it is no longer running",
+                            path.display()
+                        );
+                    }
+                    if !waiting {
+                        tracing::info!("Waiting for the lock of the archive held by {held_by}");
+                        waiting = true;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("could not create lock file {}", path.display()))
+                }
+            }
+        }
+    }
+
+    /// Record the `holder` of the lock in the lock file.
+    fn record(&self, holder: &Holder) -> anyhow::Result<()> {
+        let contents = serde_json::to_string(holder)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        writeln!(file, "{contents}")
+            .with_context(|| format!("could not write lock file {}", self.path.display()))
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        // the lock file is only emptied on unix, as removing it would race with another process
+        // locking the file that was just opened.
+        #[cfg(unix)]
+        let released = self.file.set_len(0);
+        #[cfg(not(unix))]
+        let released = fs::remove_file(&self.path);
+        if let Err(err) = released {
+            tracing::warn!("Unable to release lock file {}: {err}", self.path.display());
+        }
+    }
+}
+
+/// Read the holder recorded in the lock file at `path`.
+///
+/// # Errors
+/// Errors if the lock file cannot be read, or does not record a holder.
+pub fn read_holder(path: &Path) -> anyhow::Result<Holder> {
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(contents.trim())?)
+}
+
+/// Describe the holder of the lock file at `path`, or an unknown process if none is recorded.
+fn held_by(path: &Path) -> String {
+    read_holder(path).map_or_else(
+        |_err| "another process".to_owned(),
+        |holder| holder.to_string(),
+    )
+}
+
+/// Name of the host this process runs on.
+#[cfg(unix)]
+fn hostname() -> String {
+    gethostname().map_or_else(
+        |_err| "an unknown host".to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Name of the host this process runs on.
+#[cfg(not(unix))]
+fn hostname() -> String {
+    env::var("COMPUTERNAME").unwrap_or_else(|_err| "an unknown host".to_owned())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::utils::lock::{read_holder, Guard, LOCK_FILE};
+    use std::{fs, process};
+
+    #[test]
+    fn test_acquire_when_archive_locked_expect_error_with_holder() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".taf")).unwrap();
+        let lock = Guard::acquire(dir.path(), "update", false).unwrap();
+        let holder = read_holder(&dir.path().join(LOCK_FILE)).unwrap();
+        assert_eq!(holder.command, "update");
+        assert_eq!(holder.pid, process::id());
+
+        let actual = Guard::acquire(dir.path(), "mirror", false).unwrap_err();
+        let message = format!("{actual:#}");
+        assert!(message.contains("`stelae update`"), "{message}");
+        assert!(
+            message.contains(&format!("pid {}", process::id())),
+            "{message}"
+        );
+
+        drop(lock);
+        assert!(Guard::acquire(dir.path(), "mirror", false).is_ok());
+    }
+}
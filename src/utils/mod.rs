@@ -2,7 +2,16 @@
 
 pub mod archive;
 pub mod cli;
+pub mod daemon;
+pub mod date;
 pub mod git;
+pub mod html;
 pub mod http;
+pub mod locale;
+pub mod lock;
 pub mod md5;
+pub mod output;
+pub mod path_index;
 pub mod paths;
+pub mod structured_data;
+pub mod template;
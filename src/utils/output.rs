@@ -0,0 +1,36 @@
+//! Output of the results of CLI commands.
+//!
+//! With `--output json`, commands write their results to stdout as a single JSON document, and
+//! the console logs are written to stderr, so scripts can parse stdout reliably.
+use serde::Serialize;
+use std::io::{self, Write as _};
+
+/// Format the results of a command are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Output {
+    /// Human readable text, logged to the console.
+    #[default]
+    Text,
+    /// A single JSON document, written to stdout.
+    Json,
+}
+
+impl Output {
+    /// Whether results are written as JSON.
+    #[must_use]
+    pub const fn is_json(self) -> bool {
+        matches!(self, Self::Json)
+    }
+}
+
+/// Write `result` to stdout as a single JSON document.
+///
+/// # Errors
+/// Errors if the result cannot be serialized, or written to stdout.
+pub fn write_json<T: Serialize>(result: &T) -> anyhow::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, result)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+//! Indexes of the paths of the blobs of a commit, for blob lookups without walking git trees.
+//!
+//! The first lookup in an indexed commit of a repository walks the tree of the commit once, and
+//! indexes the path of every blob to its id, while concurrent lookups of the commit wait for the
+//! index instead of building their own. Later lookups in the commit are hash probes. Commits never
+//! change, so an index is never stale; the least recently used indexes are evicted once the
+//! indexes hold more than [`MAX_INDEXED_BYTES`] bytes in total, and a commit whose paths alone
+//! exceed that is not indexed.
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Maximum number of bytes held by all indexes, after which the least recently used is evicted.
+pub const MAX_INDEXED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Ids of the blobs of a commit, keyed by path, e.g. `a/b/c/index.html`.
+pub type PathIndex = HashMap<String, Oid>;
+
+/// Repository path and commit of an index.
+type Key = (PathBuf, Oid);
+
+/// Index of a commit, built once by its first lookup. `None` if the commit cannot be indexed.
+type Slot = Arc<OnceLock<Option<Arc<PathIndex>>>>;
+
+/// Indexes of the commits looked up in, shared by all workers.
+static INDEXES: OnceLock<Mutex<PathIndexes>> = OnceLock::new();
+
+/// Indexes of commits, evicted least recently used first.
+#[derive(Debug, Default)]
+pub struct PathIndexes {
+    /// Number of bytes held by all built indexes.
+    bytes: usize,
+    /// Indexes keyed by repository path and commit.
+    indexes: HashMap<Key, Held>,
+    /// Keys of the indexes by the order they were last used in, least recently used first.
+    order: BTreeMap<u64, Key>,
+    /// Number of uses of the indexes, the order of the next use.
+    uses: u64,
+}
+
+/// Index of a commit held by [`PathIndexes`].
+#[derive(Debug)]
+struct Held {
+    /// Number of bytes of the index, once built.
+    bytes: Option<usize>,
+    /// Order the index was last used in.
+    order: u64,
+    /// The index, built on first access.
+    slot: Slot,
+}
+
+impl PathIndexes {
+    /// Account for the bytes of the built index `slot` of `key`, evicting the least recently used
+    /// indexes until at most [`MAX_INDEXED_BYTES`] bytes are held.
+    ///
+    /// Does nothing if the index is accounted for already, or was evicted while it was built.
+    fn account(&mut self, key: &Key, slot: &Slot) {
+        let Some(built) = slot.get() else {
+            return;
+        };
+        let Some(held) = self
+            .indexes
+            .get_mut(key)
+            .filter(|held| held.bytes.is_none() && Arc::ptr_eq(&held.slot, slot))
+        else {
+            return;
+        };
+        let bytes = mem::size_of::<(Key, Held)>() + built.as_deref().map_or(0, size_of_index);
+        held.bytes = Some(bytes);
+        self.bytes += bytes;
+        while self.bytes > MAX_INDEXED_BYTES {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.indexes.remove(&oldest) {
+                self.bytes -= evicted.bytes.unwrap_or_default();
+            }
+        }
+    }
+
+    /// Number of bytes held by all indexes.
+    #[must_use]
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The index of the `commit` of the repository at `repo_path`, if built and held.
+    pub fn get(&mut self, repo_path: &Path, commit: Oid) -> Option<Arc<PathIndex>> {
+        let key = (repo_path.to_path_buf(), commit);
+        self.indexes.get(&key)?;
+        self.slot(key).get().cloned().flatten()
+    }
+
+    /// The slot of the index of `key`, marked as the most recently used, added if not held.
+    fn slot(&mut self, key: Key) -> Slot {
+        let order = self.uses;
+        self.uses += 1;
+        self.order.insert(order, key.clone());
+        let held = self.indexes.entry(key).or_insert_with(|| Held {
+            bytes: None,
+            order,
+            slot: Slot::default(),
+        });
+        let used = mem::replace(&mut held.order, order);
+        if used != order {
+            self.order.remove(&used);
+        }
+        Arc::clone(&held.slot)
+    }
+}
+
+/// The index of the `commit` of `repo`, found at `repo_path`, built on first access.
+///
+/// Returns `None` if the commit cannot be indexed, e.g. its paths alone exceed
+/// [`MAX_INDEXED_BYTES`], so the lookup walks the trees of the commit instead.
+pub fn index_of(repo: &Repository, repo_path: &Path, commit: Oid) -> Option<Arc<PathIndex>> {
+    let indexes = INDEXES.get_or_init(Mutex::default);
+    let key = (repo_path.to_path_buf(), commit);
+    let slot = indexes.lock().ok()?.slot(key.clone());
+    let index = slot
+        .get_or_init(|| build(repo, commit, MAX_INDEXED_BYTES).map(Arc::new))
+        .clone();
+    if let Ok(mut held) = indexes.lock() {
+        held.account(&key, &slot);
+    }
+    index
+}
+
+/// The index of the `commit` of the repository at `repo_path`, if built and held, without
+/// building it.
+pub fn held(repo_path: &Path, commit: Oid) -> Option<Arc<PathIndex>> {
+    INDEXES.get()?.lock().ok()?.get(repo_path, commit)
+}
+
+/// Index the path of every blob of the tree of the `commit` of `repo`.
+///
+/// Returns `None` if the commit is not found, its tree cannot be walked, or the index would hold
+/// more than `max_bytes` bytes.
+fn build(repo: &Repository, commit: Oid, max_bytes: usize) -> Option<PathIndex> {
+    let tree = repo.find_commit(commit).ok()?.tree().ok()?;
+    let mut index = PathIndex::new();
+    let mut bytes: usize = 0;
+    let walked = tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+        let path = format!("{root}{}", entry.name().unwrap_or_default());
+        bytes = bytes.saturating_add(size_of_path(&path));
+        if bytes > max_bytes {
+            return TreeWalkResult::Abort;
+        }
+        index.insert(path, entry.id());
+        TreeWalkResult::Ok
+    });
+    if let Err(err) = walked {
+        tracing::debug!("Not indexing the paths of commit {commit}: {err}");
+        return None;
+    }
+    Some(index)
+}
+
+/// Number of bytes held by the `index`.
+fn size_of_index(index: &PathIndex) -> usize {
+    index.keys().map(|path| size_of_path(path)).sum()
+}
+
+/// Number of bytes held by an index for the `path` of a blob and its id.
+const fn size_of_path(path: &str) -> usize {
+    path.len() + mem::size_of::<(String, Oid)>()
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::utils::path_index::{Key, PathIndex, PathIndexes, MAX_INDEXED_BYTES};
+    use git2::Oid;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    /// Hold an index of a path of `bytes` bytes for the `commit` of `repo_path`.
+    fn hold(indexes: &mut PathIndexes, repo_path: &str, commit: Oid, bytes: usize) {
+        let index = PathIndex::from([("x".repeat(bytes), Oid::zero())]);
+        let key: Key = (PathBuf::from(repo_path), commit);
+        let slot = indexes.slot(key.clone());
+        slot.set(Some(Arc::new(index))).unwrap();
+        indexes.account(&key, &slot);
+    }
+
+    #[test]
+    fn test_account_when_full_expect_least_recently_used_evicted() {
+        let mut cut = PathIndexes::default();
+        let (first, second, third) = (
+            Oid::from_bytes(&[1; 20]).unwrap(),
+            Oid::from_bytes(&[2; 20]).unwrap(),
+            Oid::from_bytes(&[3; 20]).unwrap(),
+        );
+        let half = MAX_INDEXED_BYTES / 2 - 256;
+        hold(&mut cut, "org/law-html", first, half);
+        hold(&mut cut, "org/law-html", second, half);
+        assert!(cut.get(Path::new("org/law-html"), first).is_some());
+        let held = cut.bytes();
+
+        hold(&mut cut, "org/law-pdf", third, 1024);
+        assert!(cut.get(Path::new("org/law-html"), second).is_none());
+        assert!(cut.get(Path::new("org/law-html"), first).is_some());
+        assert!(cut.get(Path::new("org/law-pdf"), third).is_some());
+        assert!(cut.bytes() < held);
+        assert_eq!(cut.order.len(), cut.indexes.len());
+    }
+
+    #[test]
+    fn test_get_when_slot_not_built_expect_none() {
+        let mut cut = PathIndexes::default();
+        let key: Key = (PathBuf::from("org/law-html"), Oid::zero());
+        let slot = cut.slot(key.clone());
+        assert!(cut.get(Path::new("org/law-html"), Oid::zero()).is_none());
+
+        slot.set(None).unwrap();
+        cut.account(&key, &slot);
+        assert!(cut.get(Path::new("org/law-html"), Oid::zero()).is_none());
+        assert!(cut.bytes() > 0);
+    }
+}
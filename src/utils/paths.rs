@@ -1,7 +1,10 @@
 //! Utility functions for working with paths
 use lazy_static::lazy_static;
+use percent_encoding::percent_decode_str;
 use regex::Regex;
-use std::path::{Path, PathBuf};
+use std::error::Error;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
 /// On Windows removes the `\\?\\` prefix to UNC paths.
 /// For other OS'es just turns the `Path` into a `PathBuf`
 #[must_use]
@@ -24,3 +27,148 @@ pub fn clean_path(path: &str) -> String {
     }
     RE.replace_all(path, "").to_string()
 }
+
+/// Maximum length of a path accepted by [`normalize_path`], in bytes.
+pub const MAX_PATH_LENGTH: usize = 1024;
+
+/// Maximum number of times a path is percent-decoded by [`normalize_path`].
+///
+/// Request paths are percent-decoded once by the router, so traversal left encoded in a path was
+/// encoded more than once, to smuggle it past filters that decode it again.
+const MAX_DECODINGS: usize = 3;
+
+/// Reasons a path is rejected by [`normalize_path`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvalidPath {
+    /// The path is longer than [`MAX_PATH_LENGTH`].
+    TooLong,
+    /// The path contains a null byte.
+    NullByte,
+    /// The path contains a `.` or `..` segment, or a `\` separator.
+    Traversal,
+    /// The path decodes to a path with a `.` or `..` segment, a `\` separator or a null byte.
+    EncodedTraversal,
+}
+
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::TooLong => write!(formatter, "Path is longer than {MAX_PATH_LENGTH} bytes"),
+            Self::NullByte => write!(formatter, "Path contains a null byte"),
+            Self::Traversal => write!(formatter, "Path contains a relative segment"),
+            Self::EncodedTraversal => {
+                write!(formatter, "Path contains an encoded relative segment")
+            }
+        }
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "Use implicit implementation")]
+impl Error for InvalidPath {}
+
+/// Validate a request `path` and remove its leading and trailing `/`s.
+///
+/// Use this instead of [`clean_path`] for paths that come from a request.
+///
+/// Percent-encodings are kept, so literal `%`s in paths are served, but the path is decoded a few
+/// more times and rejected if it then contains traversal.
+///
+/// # Errors
+/// Errors if the path is overlong, or contains a null byte, a `.` or `..` segment or a `\`
+/// separator, also once percent-decoded.
+pub fn normalize_path(path: &str) -> Result<String, InvalidPath> {
+    if path.len() > MAX_PATH_LENGTH {
+        return Err(InvalidPath::TooLong);
+    }
+    if path.contains('\0') {
+        return Err(InvalidPath::NullByte);
+    }
+    if is_traversal(path) {
+        return Err(InvalidPath::Traversal);
+    }
+    let mut decoded = path.to_owned();
+    for _ in 0..MAX_DECODINGS {
+        let next = percent_decode_str(&decoded)
+            .decode_utf8_lossy()
+            .into_owned();
+        if next == decoded {
+            break;
+        }
+        if next.contains('\0') || is_traversal(&next) {
+            return Err(InvalidPath::EncodedTraversal);
+        }
+        decoded = next;
+    }
+    Ok(clean_path(path))
+}
+
+/// Whether `part` is a single normal path component that is not hidden, such as the organization
+/// or the name of a repository in an archive.
+///
+/// Joining such a part to the archive path never leaves the archive, nor reaches its `.taf` dir.
+#[must_use]
+pub fn is_archive_component(part: &str) -> bool {
+    let mut components = Path::new(part).components();
+    !part.starts_with('.')
+        && !part.contains('/')
+        && matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+}
+
+/// Whether `path` contains a `.` or `..` segment, or a `\` separator.
+fn is_traversal(path: &str) -> bool {
+    path.contains('\\')
+        || path
+            .split('/')
+            .any(|segment| segment == "." || segment == "..")
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::utils::paths::{is_archive_component, normalize_path, InvalidPath, MAX_PATH_LENGTH};
+
+    #[test]
+    fn test_is_archive_component_when_traversal_or_hidden_expect_false() {
+        let cut = is_archive_component;
+        assert!(cut("test_org"));
+        assert!(cut("law-html"));
+        for part in ["", ".", "..", "../law", "org/law", "/etc", ".taf", "org/"] {
+            assert!(!cut(part), "{part}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_path_when_valid_expect_cleaned() {
+        let cut = normalize_path;
+        assert_eq!(cut("/us/ca/codes/1.01/").unwrap(), "us/ca/codes/1.01");
+        assert_eq!(cut("/").unwrap(), "");
+        assert_eq!(cut("/a/..b/c..html").unwrap(), "a/..b/c..html");
+        assert_eq!(cut("/a/100%25.html").unwrap(), "a/100%25.html");
+        assert_eq!(cut("/a/50%off.html").unwrap(), "a/50%off.html");
+    }
+
+    #[test]
+    fn test_normalize_path_when_malicious_expect_rejected() {
+        let cut = normalize_path;
+        let overlong = "a/".repeat(MAX_PATH_LENGTH);
+        let cases = [
+            ("/../../etc/passwd", InvalidPath::Traversal),
+            ("/a/./b", InvalidPath::Traversal),
+            ("/a/..", InvalidPath::Traversal),
+            ("..\\..\\windows", InvalidPath::Traversal),
+            ("/a/%2e%2e/b", InvalidPath::EncodedTraversal),
+            ("/a/%2E%2e%2Fb", InvalidPath::EncodedTraversal),
+            ("/a/..%5cb", InvalidPath::EncodedTraversal),
+            ("/a/..%252f..%252fb", InvalidPath::EncodedTraversal),
+            ("/a%00.html", InvalidPath::EncodedTraversal),
+            ("/a\0.html", InvalidPath::NullByte),
+            (overlong.as_str(), InvalidPath::TooLong),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(cut(path).unwrap_err(), expected, "{path}");
+        }
+    }
+}
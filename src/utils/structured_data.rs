@@ -0,0 +1,132 @@
+//! The structured data module describes served documents as schema.org `Legislation` JSON-LD.
+//!
+//! The `Legislation` is the work, identified by the url of its current version. The served
+//! representation is its `LegislationObject` work example.
+use crate::stelae::archive::StructuredDataValues;
+use crate::utils::html::{append_to_head, find_first_heading};
+use serde_json::{json, Map, Value};
+
+/// Metadata of a served document.
+#[derive(Debug, Clone, Copy)]
+pub struct Document<'doc> {
+    /// Path of the document, without leading or trailing `/`.
+    pub path: &'doc str,
+    /// Absolute url of the current version of the document.
+    pub url: &'doc str,
+    /// Absolute url the document was served at.
+    pub served_url: &'doc str,
+    /// Date of the served version, in `YYYY-MM-DD` format, if it is a historical version.
+    pub version_date: Option<&'doc str>,
+}
+
+/// Build the `Legislation` JSON-LD of the `document`, named `name`.
+#[must_use]
+pub fn legislation(document: &Document, name: &str, values: &StructuredDataValues) -> Value {
+    let mut legislation = Map::new();
+    legislation.insert("@context".to_owned(), json!("https://schema.org"));
+    legislation.insert("@type".to_owned(), json!("Legislation"));
+    legislation.insert("name".to_owned(), json!(name));
+    legislation.insert("url".to_owned(), json!(document.url));
+    legislation.insert("legislationIdentifier".to_owned(), json!(document.path));
+    if let Some(jurisdiction) = values.jurisdiction.as_deref() {
+        legislation.insert("legislationJurisdiction".to_owned(), json!(jurisdiction));
+    }
+    if let Some(date) = document.version_date {
+        legislation.insert("legislationDateVersion".to_owned(), json!(date));
+    }
+    legislation.insert(
+        "workExample".to_owned(),
+        json!({
+            "@type": "LegislationObject",
+            "encodingFormat": "text/html",
+            "url": document.served_url,
+        }),
+    );
+    Value::Object(legislation)
+}
+
+/// Append the `Legislation` JSON-LD of the `html` `document` to its head.
+///
+/// The document is named by its first `<h1>`, or the last segment of its path.
+///
+/// # Errors
+/// Errors if the document cannot be parsed or rewritten.
+pub fn insert_legislation(
+    html: &[u8],
+    document: &Document,
+    values: &StructuredDataValues,
+) -> anyhow::Result<Vec<u8>> {
+    let name = find_first_heading(html)?.unwrap_or_else(|| {
+        document
+            .path
+            .rsplit('/')
+            .find(|segment| !segment.is_empty())
+            .unwrap_or_default()
+            .to_owned()
+    });
+    // `</` must not end the script element early, JSON allows `/` to be escaped.
+    let json_ld = legislation(document, &name, values)
+        .to_string()
+        .replace("</", "<\\/");
+    append_to_head(
+        html,
+        &format!("<script type=\"application/ld+json\">{json_ld}</script>"),
+    )
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::stelae::archive::StructuredDataValues;
+    use crate::utils::structured_data::{insert_legislation, legislation, Document};
+    use serde_json::json;
+
+    #[test]
+    fn test_legislation_when_historical_version_expect_version_date_and_work_example() {
+        let cut = legislation;
+        let document = Document {
+            path: "us/ca/1.01",
+            url: "https://example.com/us/ca/1.01",
+            served_url: "https://example.com/_snapshot/v1/us/ca/1.01",
+            version_date: Some("2023-10-22"),
+        };
+        let values = StructuredDataValues {
+            jurisdiction: Some("US-CA".to_owned()),
+            ..StructuredDataValues::default()
+        };
+        let actual = cut(&document, "Section 1.01", &values);
+        let expected = json!({
+            "@context": "https://schema.org",
+            "@type": "Legislation",
+            "name": "Section 1.01",
+            "url": "https://example.com/us/ca/1.01",
+            "legislationIdentifier": "us/ca/1.01",
+            "legislationJurisdiction": "US-CA",
+            "legislationDateVersion": "2023-10-22",
+            "workExample": {
+                "@type": "LegislationObject",
+                "encodingFormat": "text/html",
+                "url": "https://example.com/_snapshot/v1/us/ca/1.01",
+            },
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_insert_legislation_when_document_expect_script_in_head() {
+        let cut = insert_legislation;
+        let document = Document {
+            path: "a/b",
+            url: "https://example.com/a/b",
+            served_url: "https://example.com/a/b",
+            version_date: None,
+        };
+        let html = b"<html><head></head><body><h1>A &amp; B</h1></body></html>";
+        let actual = cut(html, &document, &StructuredDataValues::default()).unwrap();
+        let actual_html = String::from_utf8(actual).unwrap();
+        assert!(actual_html.starts_with(
+            r#"<html><head><script type="application/ld+json">{"@context":"https://schema.org","#
+        ));
+        assert!(actual_html.contains(r#""name":"A & B""#));
+    }
+}
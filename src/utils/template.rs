@@ -0,0 +1,280 @@
+//! The template module wraps html fragments in a stele-provided layout template.
+//!
+//! Layouts are html documents with `{{ variable }}` placeholders:
+//! - `title`: text of the first `<h1>` of the fragment, or the last segment of its path
+//! - `date`: version date of the document, empty for current documents
+//! - `navigation`: a `<nav>` of links to the ancestors of the document
+//! - `content`: the fragment itself
+//!
+//! Unknown placeholders are rendered empty.
+//!
+//! Historical documents can also be marked with a banner stating their date, and be injected
+//! with the elements their data repository declares.
+use crate::stelae::types::repositories::Injection;
+use crate::utils::html::{find_first_heading, insert_at, insert_banner, is_fragment};
+use std::fmt::Write as _;
+
+/// Style of the banner of historical documents, which is kept, in black and white, when printed.
+const WATERMARK_STYLE: &str = concat!(
+    "<style>.stelae-watermark{margin:0 0 1em;padding:.5em 1em;border:1px solid #856404;",
+    "background:#fff3cd;color:#856404}",
+    "@media print{.stelae-watermark{display:block!important;border-color:#000;",
+    "background:none;color:#000}",
+    ".stelae-watermark::after{content:\" Printed from a historical version.\"}}</style>"
+);
+
+/// Render the `template`, substituting `{{ name }}` placeholders with their value in `variables`.
+///
+/// Values are inserted verbatim, escape them with [`escape`] if they are text.
+#[must_use]
+pub fn render(template: &str, variables: &[(&str, &str)]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((before, opened)) = rest.split_once("{{") {
+        let Some((name, after)) = opened.split_once("}}") else {
+            break;
+        };
+        output.push_str(before);
+        if let Some(&(_, value)) = variables.iter().find(|&&(key, _)| key == name.trim()) {
+            output.push_str(value);
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Escape `text` for use in html text and attribute values.
+#[must_use]
+pub fn escape(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, ch| {
+            match ch {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(ch),
+            }
+            escaped
+        })
+}
+
+/// Build a `<nav>` of links to the ancestors of the document at the cleaned `path`.
+///
+/// Links are prefixed with `url_prefix`, e.g. `/_snapshot/{name}`.
+#[must_use]
+pub fn breadcrumbs(path: &str, url_prefix: &str) -> String {
+    let mut href = escape(url_prefix);
+    let mut items = vec![format!("<li><a href=\"{href}/\">Home</a></li>")];
+    let mut segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .peekable();
+    while let Some(segment) = segments.next() {
+        if segments.peek().is_none() {
+            break;
+        }
+        let text = escape(segment);
+        href = format!("{href}/{text}");
+        items.push(format!("<li><a href=\"{href}/\">{text}</a></li>"));
+    }
+    format!(
+        "<nav class=\"breadcrumbs\"><ol>{}</ol></nav>",
+        items.concat()
+    )
+}
+
+/// Wrap the `html` document at `path` in the `layout` template if it is a fragment.
+///
+/// Complete documents, with an `<html>` or `<body>` element, are returned unchanged.
+///
+/// # Errors
+/// Errors if the document cannot be parsed.
+pub fn wrap_fragment(
+    layout: &str,
+    html: &[u8],
+    path: &str,
+    url_prefix: &str,
+    date: &str,
+) -> anyhow::Result<Vec<u8>> {
+    if !is_fragment(html)? {
+        return Ok(html.to_vec());
+    }
+    let title = escape(&find_first_heading(html)?.unwrap_or_else(|| {
+        path.rsplit('/')
+            .find(|segment| !segment.is_empty())
+            .unwrap_or_default()
+            .to_owned()
+    }));
+    let content = String::from_utf8_lossy(html);
+    let wrapped = render(
+        layout,
+        &[
+            ("title", &title),
+            ("date", &escape(date)),
+            ("navigation", &breadcrumbs(path, url_prefix)),
+            ("content", &content),
+        ],
+    );
+    Ok(wrapped.into_bytes())
+}
+
+/// Mark the historical `html` document of `date` with a banner of `text`.
+///
+/// `{{ date }}` in `text` is replaced by `date`.
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn watermark(html: &[u8], text: &str, date: &str) -> anyhow::Result<Vec<u8>> {
+    let banner = format!(
+        "<div class=\"stelae-watermark\" role=\"note\">{}</div>",
+        render(&escape(text), &[("date", &escape(date))])
+    );
+    insert_banner(html, &banner, WATERMARK_STYLE)
+}
+
+/// Render the element of the `injection`, substituting `{{ date }}` with the `%Y-%m-%d` `date`.
+fn render_injection(injection: &Injection, date: &str) -> String {
+    let variables = [("date", date)];
+    let attributes = injection
+        .attrs
+        .iter()
+        .fold(String::new(), |mut attributes, (name, value)| {
+            let _infallible = write!(
+                attributes,
+                " {name}=\"{}\"",
+                escape(&render(value, &variables))
+            );
+            attributes
+        });
+    let start_tag = format!("<{}{attributes}>", injection.tag);
+    if injection.is_void() {
+        return start_tag;
+    }
+    let content = injection.content.as_deref().unwrap_or_default();
+    format!(
+        "{start_tag}{}</{}>",
+        render(content, &variables),
+        injection.tag
+    )
+}
+
+/// Inject the elements of the `injections` into the historical `html` document of `date`.
+///
+/// Invalid injections are skipped, see [`Injection::validate`].
+///
+/// # Errors
+/// Errors if the document cannot be rewritten.
+pub fn inject(html: &[u8], injections: &[Injection], date: &str) -> anyhow::Result<Vec<u8>> {
+    let markup: Vec<_> = injections
+        .iter()
+        .filter(|injection| match injection.validate() {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!("Skipping injection of `<{}>`: {err}", injection.tag);
+                false
+            }
+        })
+        .map(|injection| (injection.position, render_injection(injection, date)))
+        .collect();
+    if markup.is_empty() {
+        return Ok(html.to_vec());
+    }
+    insert_at(html, &markup)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Tests panic on unexpected values")]
+mod test {
+    use crate::stelae::types::repositories::{Injection, Position};
+    use crate::utils::template::{breadcrumbs, inject, render, watermark, wrap_fragment};
+
+    #[test]
+    fn test_render_when_placeholders_expect_substituted() {
+        let cut = render;
+        let actual = cut(
+            "<title>{{title}}</title>{{ unknown }}<p>{{ date }}</p>{{",
+            &[("title", "1.01"), ("date", "2023-10-22")],
+        );
+        let expected = "<title>1.01</title><p>2023-10-22</p>{{";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_breadcrumbs_when_nested_path_expect_ancestor_links() {
+        let cut = breadcrumbs;
+        let actual = cut("us/ca/1.01", "/_snapshot/v1");
+        let expected = concat!(
+            r#"<nav class="breadcrumbs"><ol><li><a href="/_snapshot/v1/">Home</a></li>"#,
+            r#"<li><a href="/_snapshot/v1/us/">us</a></li>"#,
+            r#"<li><a href="/_snapshot/v1/us/ca/">ca</a></li></ol></nav>"#
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_wrap_fragment_when_fragment_expect_wrapped_and_document_unchanged() {
+        let cut = wrap_fragment;
+        let layout = "<html><title>{{ title }}</title><body>{{ content }}</body></html>";
+        let actual = cut(layout, b"<h1>A &amp; B</h1>", "a/b", "", "").unwrap();
+        let expected = "<html><title>A &amp; B</title><body><h1>A &amp; B</h1></body></html>";
+        assert_eq!(String::from_utf8(actual).unwrap(), expected);
+        let document = b"<html><body><p>a</p></body></html>";
+        assert_eq!(cut(layout, document, "a/b", "", "").unwrap(), document);
+    }
+
+    #[test]
+    fn test_watermark_when_date_placeholder_expect_escaped_banner() {
+        let cut = watermark;
+        let actual = cut(
+            b"<body><p>a</p></body>",
+            "As of {{ date }} & before",
+            "1 mars 2023",
+        )
+        .unwrap();
+        let actual_html = String::from_utf8(actual).unwrap();
+        assert!(actual_html.contains(
+            r#"<body><div class="stelae-watermark" role="note">As of 1 mars 2023 &amp; before</div><p>a</p>"#
+        ));
+    }
+
+    #[test]
+    fn test_inject_when_injections_expect_rendered_elements_and_invalid_skipped() {
+        let cut = inject;
+        let injections = [
+            Injection {
+                tag: "meta".to_owned(),
+                attrs: [("content", "{{ date }}"), ("name", "a\"b")]
+                    .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                    .into(),
+                content: None,
+                position: Position::HeadEnd,
+            },
+            Injection {
+                tag: "script".to_owned(),
+                attrs: [("type".to_owned(), "application/json".to_owned())].into(),
+                content: Some(r#"{"date":"{{ date }}"}"#.to_owned()),
+                position: Position::BodyEnd,
+            },
+            Injection {
+                tag: "body".to_owned(),
+                attrs: [].into(),
+                content: None,
+                position: Position::BodyStart,
+            },
+        ];
+        let actual = cut(
+            b"<html><head></head><body><p>a</p></body></html>",
+            &injections,
+            "2023-03-01",
+        )
+        .unwrap();
+        let expected = concat!(
+            r#"<html><head><meta content="2023-03-01" name="a&quot;b"></head>"#,
+            r#"<body><p>a</p><script type="application/json">{"date":"2023-03-01"}</script></body></html>"#
+        );
+        assert_eq!(String::from_utf8(actual).unwrap(), expected);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::common;
+use actix_web::{http::StatusCode, test};
+use stelae::testing::generate;
+
+/// Request for the document at `uri` with the preview token `token`, if any.
+fn get(uri: &str, token: Option<&str>) -> actix_http::Request {
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(value) = token {
+        req = req.insert_header(("X-Stelae-Preview", value));
+    }
+    req.to_request()
+}
+
+#[actix_web::test]
+async fn test_serve_when_publication_moves_through_approval_expect_served_once_approved_or_live() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[preview]\ntoken = \"secret\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET state = 'ingested' WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app_as_admin(archive_path.path()).await;
+
+    for token in [None, Some("secret")] {
+        for uri in ["/doc-0", "/_date/2020-01-31/_repo/html/doc-0"] {
+            let body = String::from_utf8(
+                test::call_and_read_body(&app, get(uri, token))
+                    .await
+                    .to_vec(),
+            )
+            .unwrap();
+            assert!(
+                body.contains("Version 0 of document 0"),
+                "{uri} {token:?}: {body}"
+            );
+        }
+    }
+
+    for (state, published, previewed) in [
+        ("approved", "Version 0", "Version 1"),
+        ("live", "Version 1", "Version 1"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/_admin/publications/2020-01-31/state")
+            .insert_header(common::admin_authorization())
+            .set_json(serde_json::json!({ "state": state }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{state}");
+        let body = String::from_utf8(
+            test::call_and_read_body(&app, get("/doc-0", None))
+                .await
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(body.contains(published), "{state}: {body}");
+        let body = String::from_utf8(
+            test::call_and_read_body(&app, get("/doc-0", Some("secret")))
+                .await
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(body.contains(previewed), "{state} with token: {body}");
+    }
+}
+
+#[actix_web::test]
+async fn test_transition_when_auth_not_configured_expect_forbidden() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET state = 'ingested' WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/_admin/publications/2020-01-31/state")
+        .set_json(serde_json::json!({ "state": "live" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body = String::from_utf8(
+        test::call_and_read_body(&app, get("/doc-0", None))
+            .await
+            .to_vec(),
+    )
+    .unwrap();
+    assert!(body.contains("Version 0 of document 0"), "{body}");
+}
+
+#[actix_web::test]
+async fn test_versions_when_awaiting_approval_expect_listed_with_token_once_approved() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[preview]\ntoken = \"secret\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET state = 'ingested' WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app_as_admin(archive_path.path()).await;
+
+    for (approved, token, expected) in [
+        (false, None, vec!["2020-01-01"]),
+        (false, Some("secret"), vec!["2020-01-01"]),
+        (true, None, vec!["2020-01-01"]),
+        (true, Some("secret"), vec!["2020-01-31", "2020-01-01"]),
+    ] {
+        if approved && token.is_none() {
+            let req = test::TestRequest::post()
+                .uri("/_admin/publications/2020-01-31/state")
+                .insert_header(common::admin_authorization())
+                .set_json(serde_json::json!({ "state": "approved" }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = test::call_service(&app, get("/_api/versions/doc-0", token)).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{approved} {token:?}");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            common::publication_names(&body),
+            expected,
+            "{approved} {token:?}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn test_transition_when_skipping_approval_expect_conflict() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET state = 'ingested' WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app_as_admin(archive_path.path()).await;
+
+    for (name, state, status, expected) in [
+        (
+            "2020-01-31",
+            "live",
+            StatusCode::CONFLICT,
+            "Publication 2020-01-31 is ingested, and cannot move to live.",
+        ),
+        (
+            "2020-02-01",
+            "approved",
+            StatusCode::NOT_FOUND,
+            "Publication 2020-02-01 not found.",
+        ),
+        (
+            "2020-01-31",
+            "revoked",
+            StatusCode::BAD_REQUEST,
+            "Field `state` must be one of `approved` or `live`.",
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/_admin/publications/{name}/state"))
+            .insert_header(common::admin_authorization())
+            .set_json(serde_json::json!({ "state": state }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{name} {state}");
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert_eq!(body, expected, "{name} {state}");
+    }
+}
@@ -1,6 +1,19 @@
-use crate::archive_testtools::config::{ArchiveType, Jurisdiction};
+use crate::archive_testtools::{
+    self,
+    config::{ArchiveType, Jurisdiction},
+};
 use crate::common;
-use actix_web::test;
+use actix_web::{
+    http::{Method, StatusCode},
+    test,
+};
+use stelae::server::api::routes::Routes;
+use stelae::server::api::state::App as AppState;
+use stelae::server::base_path::BasePath;
+use stelae::server::load_shedding::{EndpointClass, LoadShedder};
+use stelae::server::startup::validate;
+use stelae::stelae::archive::Concurrency;
+use stelae::stelae::types::repositories::{LanguageNaming, Languages};
 
 #[actix_web::test]
 async fn test_resolve_law_html_request_with_full_path_expect_success() {
@@ -189,3 +202,795 @@ async fn get_law_pdf_request_with_incorrect_path_expect_not_found() {
     let expected = true;
     assert_eq!(actual, expected);
 }
+
+#[actix_web::test]
+async fn test_resolve_request_with_traversal_path_expect_bad_request() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for request_uri in &[
+        "/a/../../../../etc/passwd",
+        "/a/%2e%2e/%2e%2e/etc/passwd",
+        "/a/b/..%252f..%252fetc/passwd",
+        "/a/b/c.html%00.png",
+        "/a/.%5c..%5cetc",
+    ] {
+        let req = test::TestRequest::get().uri(request_uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        let actual = resp.status();
+        let expected = actix_web::http::StatusCode::BAD_REQUEST;
+        assert_eq!(actual, expected, "{request_uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_resolve_request_with_overlong_url_expect_uri_too_long() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+    let request_uri = format!("/a/{}", "b".repeat(10_000));
+    let req = test::TestRequest::get().uri(&request_uri).to_request();
+    let resp = test::try_call_service(&app, req).await;
+    let actual = resp.map_or_else(
+        |err| err.as_response_error().status_code(),
+        |resp| resp.status(),
+    );
+    let expected = actix_web::http::StatusCode::URI_TOO_LONG;
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_resolve_api_request_when_over_concurrency_limit_expect_service_unavailable() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[concurrency]\nmax_expensive_requests = 0\nretry_after = 7\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/suggest?q=a")
+        .to_request();
+    let resp = test::try_call_service(&app, req).await;
+    let err = resp.err().unwrap();
+    let actual = err.error_response();
+    assert_eq!(
+        actual.status(),
+        actix_web::http::StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert_eq!(actual.headers().get("Retry-After").unwrap(), "7");
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_resolve_api_request_when_limit_reached_in_other_worker_expect_service_unavailable() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let load_shedder = LoadShedder::new(&Concurrency {
+        max_expensive_requests: Some(1),
+        ..Concurrency::default()
+    });
+    let shared = load_shedder.clone();
+    let worker = common::initialize_app_with(archive_path.path(), |state| AppState {
+        load_shedder: shared,
+        ..state
+    })
+    .await;
+    let shared = load_shedder.clone();
+    let other_worker = common::initialize_app_with(archive_path.path(), |state| AppState {
+        load_shedder: shared,
+        ..state
+    })
+    .await;
+
+    let in_flight = load_shedder.admit(EndpointClass::Expensive).unwrap();
+    let req = test::TestRequest::get()
+        .uri("/_api/suggest?q=a")
+        .to_request();
+    let err = test::try_call_service(&worker, req).await.err().unwrap();
+    assert_eq!(
+        err.error_response().status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    let req = test::TestRequest::get()
+        .uri("/_api/suggest?q=a")
+        .to_request();
+    let err = test::try_call_service(&other_worker, req)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.error_response().status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    drop(in_flight);
+    let req = test::TestRequest::get()
+        .uri("/_api/suggest?q=a")
+        .to_request();
+    let resp = test::call_service(&other_worker, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_resolve_request_when_git_lookup_exceeds_timeout_expect_gateway_timeout() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[timeouts.routes]\n\"/large\" = 0\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+    // Reading the large document takes longer than the first tick of the timer.
+    let repo = git2::Repository::open(archive_path.path().join("test_org/law-html")).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let blob = repo.blob(&b"<p>Large</p>".repeat(4_000_000)).unwrap();
+    let mut tree = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+    tree.insert("large.html", blob, 0o100_644).unwrap();
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let signature = head.author();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "Add large document",
+        &tree,
+        &[&head],
+    )
+    .unwrap();
+
+    let req = test::TestRequest::get().uri("/large.html").to_request();
+    let resp = test::try_call_service(&app, req).await;
+    let actual = resp.err().unwrap().error_response();
+    assert_eq!(
+        actual.status(),
+        actix_web::http::StatusCode::GATEWAY_TIMEOUT
+    );
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_resolve_admin_request_when_auth_configured_without_token_expect_unauthorized() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[auth]\nissuer = \"http://127.0.0.1:9\"\naudience = \"stelae\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_admin/broken-links")
+        .insert_header(("Authorization", "Bearer not.a.token"))
+        .to_request();
+    let resp = test::try_call_service(&app, req).await;
+    let actual = resp.err().unwrap().error_response();
+    assert_eq!(actual.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+    assert_eq!(actual.headers().get("WWW-Authenticate").unwrap(), "Bearer");
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_resolve_admin_request_when_path_percent_encoded_expect_unauthorized() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[auth]\nissuer = \"http://127.0.0.1:9\"\naudience = \"stelae\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (method, uri) in [
+        (Method::GET, "/%5Fadmin/takedowns"),
+        (Method::POST, "/%5Fadmin/pin"),
+        (Method::GET, "/%5fadmin/broken-links"),
+        (Method::GET, "/%5Fmetrics"),
+    ] {
+        let req = test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .to_request();
+        let resp = test::try_call_service(&app, req).await;
+        let actual = resp.err().unwrap().error_response();
+        assert_eq!(
+            actual.status(),
+            actix_web::http::StatusCode::UNAUTHORIZED,
+            "{uri}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn test_resolve_request_when_access_log_configured_expect_anonymized_entry() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let log_path = archive_path.path().join("access.log");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str(&format!(
+        "\n[access_log]\npath = {:?}\n\n[access_log.routes]\n\"/_api/\" = false\n",
+        log_path.to_string_lossy()
+    ));
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for uri in ["/a/b/c.html", "/_api/versions/a/b/c.html"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .peer_addr("203.0.113.7:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.23"))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let entries: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry["path"], "/a/b/c.html");
+    assert_eq!(entry["method"], "GET");
+    assert_eq!(entry["status"], 200);
+    assert_eq!(entry["ip"], "203.0.113.0");
+    assert_eq!(entry["stele"], "test_org/law");
+}
+
+#[actix_web::test]
+async fn test_resolve_request_when_forwarded_by_trusted_proxy_expect_forwarded_client_logged() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let log_path = archive_path.path().join("access.log");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str(&format!(
+        "\n[access_log]\npath = {:?}\nip = \"full\"\n\n[proxy]\ntrusted = [\"10.0.0.0/8\"]\n",
+        log_path.to_string_lossy()
+    ));
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for peer in ["10.0.0.2:1234", "203.0.113.7:1234"] {
+        let req = test::TestRequest::get()
+            .uri("/a/b/c.html")
+            .peer_addr(peer.parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.23, 10.0.0.3"))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let actual: Vec<String> = log
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["ip"].to_string())
+        .collect();
+    assert_eq!(actual, ["\"198.51.100.23\"", "\"203.0.113.7\""]);
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_taken_down_expect_unavailable_for_legal_reasons() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let takedowns = stelae::server::api::takedown::Takedowns::default();
+    takedowns.insert(
+        "test_org/law".to_owned(),
+        "a/b/c.html",
+        "Removed by court order".to_owned(),
+    );
+    takedowns.insert(
+        "test_org/other".to_owned(),
+        "a/b/",
+        "Removed in another stele".to_owned(),
+    );
+    let app =
+        common::initialize_app_with(archive_path.path(), |state| AppState { takedowns, ..state })
+            .await;
+
+    for uri in [
+        "/a/b/c.html",
+        "/a/b/c/index.html",
+        "/a/b/c",
+        "/_xml/a/b/c.xml",
+        "/_date/2020-01-01/a/b/c/index.html",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            "{uri}"
+        );
+        let actual = test::read_body(resp).await;
+        assert!(common::blob_to_string(actual.to_vec()).contains("Removed by court order"));
+    }
+
+    let req = test::TestRequest::get().uri("/a/b/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_check_links_when_taken_down_expect_unavailable_for_legal_reasons() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let takedowns = stelae::server::api::takedown::Takedowns::default();
+    takedowns.insert(
+        "test_org/law".to_owned(),
+        "a/b/c.html",
+        "Removed by court order".to_owned(),
+    );
+    let app =
+        common::initialize_app_with(archive_path.path(), |state| AppState { takedowns, ..state })
+            .await;
+
+    let req = test::TestRequest::post()
+        .uri("/_api/check-links")
+        .set_json(serde_json::json!({"paths": ["/a/b/c.html", "/a/b/c/index.html", "/a/d/index.html", "/a/b/missing.html"]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let actual: Vec<(bool, u64)> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|status| {
+            (
+                status["exists"].as_bool().unwrap(),
+                status["status"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        actual,
+        [(false, 451), (false, 451), (true, 200), (false, 404)]
+    );
+}
+
+#[actix_web::test]
+async fn test_check_links_when_too_many_paths_expect_bad_request() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let paths = vec!["/a/b/c.html"; 1_001];
+    let req = test::TestRequest::post()
+        .uri("/_api/check-links")
+        .set_json(serde_json::json!({ "paths": paths }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/_api/check-links")
+        .insert_header(("Accept-Version", "2"))
+        .set_json(serde_json::json!({ "paths": paths }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["code"], "too_many_paths");
+}
+
+#[actix_web::test]
+async fn test_take_down_when_auth_not_configured_expect_forbidden() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/_admin/takedowns")
+        .set_json(serde_json::json!({"path": "/a/b/c.html", "reason": "Court order 123"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+    let req = test::TestRequest::delete()
+        .uri("/_admin/takedowns/a/b/c.html")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_structured_data_configured_expect_json_ld() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[structured_data]\njurisdiction = \"US-CA\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let actual = common::blob_to_string(test::call_and_read_body(&app, req).await.to_vec());
+    assert!(actual.contains(r#"<script type="application/ld+json">"#));
+    assert!(actual.contains(r#""legislationJurisdiction":"US-CA""#));
+    assert!(actual.contains(r#""legislationIdentifier":"a/b/c.html""#));
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_other_formats_exist_expect_alternate_links() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    let actual: Vec<&str> = resp
+        .headers()
+        .get_all("Link")
+        .map(|value| value.to_str().unwrap())
+        .collect();
+    assert!(actual.contains(&r#"</_xml/a/b/c.xml>; rel="alternate"; type="application/xml""#));
+    assert!(actual.contains(&r#"</_rdf/a/b/c.rdf>; rel="alternate"; type="application/rdf+xml""#));
+    assert!(!actual.iter().any(|link| link.contains("text/html")));
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_xml_accepted_expect_xml_document() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/c.html")
+        .insert_header(("Accept", "application/xml, text/html;q=0.9"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(
+        resp.headers().get("Content-Location").unwrap(),
+        "/_xml/a/b/c.xml"
+    );
+    assert_eq!(resp.headers().get("Vary").unwrap(), "Accept");
+    let actual = test::read_body(resp).await;
+    assert!(String::from_utf8_lossy(&actual).contains("<?xml"));
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_json_format_expect_metadata_and_text() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/c.html?format=json")
+        .insert_header(("Accept", "text/html"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(actual["path"], "a/b/c.html");
+    assert!(actual["text"].is_string());
+    assert!(actual["formats"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|format| format["repoType"] == "xml"));
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_unknown_format_expect_client_error() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/c.html?format=docx")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_client_error());
+}
+
+#[actix_web::test]
+async fn test_resolve_eli_when_identified_expect_redirect_and_cite_as_link() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let identifiers = stelae::server::api::identifiers::Identifiers::from(vec![
+        stelae::db::models::identifier::Identifier {
+            identifier: "eli/us/act/2023/1".to_owned(),
+            stele: "test_org/law".to_owned(),
+            path: "a/b/c.html".to_owned(),
+        },
+    ]);
+    let app = common::initialize_app_with(archive_path.path(), |state| AppState {
+        identifiers,
+        ..state
+    })
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/eli/us/act/2023/1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 303);
+    assert_eq!(resp.headers().get("Location").unwrap(), "/a/b/c.html");
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    let actual: Vec<&str> = resp
+        .headers()
+        .get_all("Link")
+        .map(|value| value.to_str().unwrap())
+        .collect();
+    assert!(actual.contains(&r#"</eli/us/act/2023/1>; rel="cite-as""#));
+
+    let req = test::TestRequest::get()
+        .uri("/eli/us/act/2023/2")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_client_error());
+}
+
+#[actix_web::test]
+async fn test_resolve_law_xml_directory_when_listing_enabled_expect_html_and_json_listing() {
+    let archive_path =
+        common::initialize_archive_without_bare(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    archive_testtools::enable_directory_listing(archive_path.path(), "test_org", "law-xml")
+        .unwrap();
+    archive_testtools::utils::make_all_git_repos_bare_recursive(archive_path.path()).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/_xml/a/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let actual = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(actual.contains(r#"<a href="/_xml/a/b/">b/</a>"#));
+    assert!(actual.contains(r#"<a href="/_xml/a/index.xml">index.xml</a>"#));
+
+    let req = test::TestRequest::get()
+        .uri("/_xml/a/b?format=json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(actual["path"], "a/b");
+    assert_eq!(actual["entries"][0]["url"], "/_xml/a/b/c/");
+    assert_eq!(actual["entries"][0]["isDirectory"], true);
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_language_variant_expect_variant_at_head() {
+    let archive_path =
+        common::initialize_archive_without_bare(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let languages = Languages {
+        default: "en".to_owned(),
+        variants: vec!["es".to_owned()],
+        naming: LanguageNaming::Suffix,
+    };
+    archive_testtools::declare_languages(archive_path.path(), "test_org", "law-html", languages)
+        .unwrap();
+    let html_repo =
+        archive_testtools::get_repository(archive_path.path(), "test_org/law-html").unwrap();
+    let variant = "<html><body>Documento en español</body></html>";
+    html_repo
+        .add_file(&html_repo.path.join("a/b"), "c.es.html", variant)
+        .unwrap();
+    html_repo
+        .commit(Some("a/b/c.es.html"), "Add the Spanish variant")
+        .unwrap();
+    archive_testtools::utils::make_all_git_repos_bare_recursive(archive_path.path()).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/c.html?lang=es")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Language").unwrap(), "es");
+    let actual = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(actual.contains("Documento en español"));
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/c.html")
+        .insert_header(("Accept-Language", "fr, en;q=0.5"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Language").unwrap(), "en");
+    let actual = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(!actual.contains("Documento en español"));
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_head_expect_charset_and_sized_body() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let expected = test::read_body(resp).await.len();
+
+    let req = test::TestRequest::default()
+        .method(actix_web::http::Method::HEAD)
+        .uri("/a/b/c.html")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let actual = actix_web::body::MessageBody::size(resp.response().body());
+    assert_eq!(actual, actix_web::body::BodySize::Sized(expected as u64));
+
+    let req = test::TestRequest::get()
+        .uri("/a/b/missing.html")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+}
+
+#[actix_web::test]
+async fn test_validate_when_repositories_missing_and_database_unreadable_expect_all_problems_reported(
+) {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let path = archive_path.path();
+    let raw_path = path.to_string_lossy();
+    assert!(validate(&raw_path, path.to_path_buf(), false).await.is_ok());
+
+    std::fs::remove_dir_all(path.join("test_org/law-html")).unwrap();
+    std::fs::remove_dir_all(path.join("test_org/law-pdf")).unwrap();
+    std::fs::write(path.join(".taf/db.sqlite3"), "not a database").unwrap();
+    let report = validate(&raw_path, path.to_path_buf(), false)
+        .await
+        .err()
+        .unwrap();
+    assert!(!report.ok);
+    let actual: Vec<&str> = report
+        .problems
+        .iter()
+        .map(|problem| problem.subject.as_str())
+        .collect();
+    assert_eq!(actual.len(), 3, "{report}");
+    assert_eq!(
+        &actual[..2],
+        [
+            "data repository test_org/law-pdf of stele test_org/law",
+            "data repository test_org/law-html of stele test_org/law"
+        ]
+    );
+    assert!(actual[2].starts_with("database "), "{report}");
+}
+
+#[actix_web::test]
+async fn test_validate_when_security_header_has_newline_expect_config_problem() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let path = archive_path.path();
+    let config_path = path.join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str(
+        "\n[security_headers.stelae.\"test_org/law\"]\nreferrer_policy = \"no-referrer\\nX-Injected: 1\"\n",
+    );
+    std::fs::write(&config_path, config).unwrap();
+
+    let report = validate(&path.to_string_lossy(), path.to_path_buf(), false)
+        .await
+        .err()
+        .unwrap();
+    let actual: Vec<&str> = report
+        .problems
+        .iter()
+        .map(|problem| problem.subject.as_str())
+        .collect();
+    assert_eq!(actual, ["`[security_headers]` of `.taf/config.toml`"]);
+    assert!(report.problems[0]
+        .error
+        .contains("Referrer-Policy header of test_org/law"));
+}
+
+#[actix_web::test]
+async fn test_routes_when_management_apart_expect_management_routes_only_on_admin_app() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let public = common::initialize_app_with_routes(archive_path.path(), Routes::Public).await;
+    let admin = common::initialize_app_with_routes(archive_path.path(), Routes::Management).await;
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    assert!(test::call_service(&public, req).await.status().is_success());
+    for request_uri in &[
+        "/_health",
+        "/_metrics",
+        "/_admin/takedowns",
+        "/_admin/stats",
+    ] {
+        let req = test::TestRequest::get().uri(request_uri).to_request();
+        let actual = test::call_service(&public, req).await.status();
+        assert_eq!(actual, StatusCode::NOT_FOUND, "{request_uri}");
+    }
+
+    let req = test::TestRequest::get().uri("/_health").to_request();
+    assert!(test::call_service(&admin, req).await.status().is_success());
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    assert_eq!(
+        test::call_service(&admin, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn test_resolve_law_html_request_when_base_path_expect_served_under_base_path_only() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let app = common::initialize_app_with(archive_path.path(), |state| AppState {
+        base_path: BasePath::parse("/laws").unwrap(),
+        ..state
+    })
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/laws/a/b/c.html")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let actual: Vec<&str> = resp
+        .headers()
+        .get_all("Link")
+        .map(|value| value.to_str().unwrap())
+        .collect();
+    assert!(actual.contains(&r#"</laws/_xml/a/b/c.xml>; rel="alternate"; type="application/xml""#));
+
+    let req = test::TestRequest::get()
+        .uri("/laws/a/b/c.html")
+        .insert_header(("Accept", "application/xml"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("Content-Location").unwrap(),
+        "/laws/_xml/a/b/c.xml"
+    );
+
+    for request_uri in &["/a/b/c.html", "/lawsuits/a/b/c.html", "/_xml/a/b/c.xml"] {
+        let req = test::TestRequest::get().uri(request_uri).to_request();
+        let resp = test::try_call_service(&app, req).await;
+        let actual = resp.err().unwrap().error_response().status();
+        assert_eq!(actual, StatusCode::NOT_FOUND, "{request_uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_resolve_document_when_server_timing_enabled_expect_phases_in_header() {
+    let archive_path =
+        common::initialize_archive(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[server_timing]\nenabled = true\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/a/b/c.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let actual = resp
+        .headers()
+        .get("Server-Timing")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(actual.starts_with("db;dur="), "{actual}");
+    assert!(actual.contains(", git;dur="), "{actual}");
+    assert!(actual.contains(", total;dur="), "{actual}");
+}
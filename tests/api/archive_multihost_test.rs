@@ -123,8 +123,8 @@ async fn test_law_other_data_request_content_with_cycle_expect_other_document_re
     // Add a cycle
     // stele_1 -> stele_1_1 -> stele_1
     // Expect that the cycle is resolved
-    archive_testtools::add_dependencies(archive_path.path(), "stele_1_1", vec!["stele_1"]).unwrap();
-    utils::make_all_git_repos_bare_recursive(&archive_path).unwrap();
+    archive_testtools::add_dependencies(archive_path.path(), "stele_1_1", &["stele_1"]).unwrap();
+    utils::make_all_git_repos_bare_recursive(archive_path.path()).unwrap();
     let app = common::initialize_app(archive_path.path()).await;
     for guard_value in [
         "stele_1/law",
@@ -1,6 +1,8 @@
 use crate::archive_testtools::config::{ArchiveType, Jurisdiction};
 use crate::common;
 use actix_web::test;
+use stelae::server::api::versions::{get_stele_from_request, SteleError};
+use stelae::stelae::archive::Archive;
 
 #[actix_web::test]
 async fn test_resolve_root_stele_law_html_request_with_full_path_expect_success() {
@@ -161,3 +163,94 @@ async fn test_dependent_stele_law_other_with_full_path_when_underscore_routing_i
         assert_eq!(actual, expected);
     }
 }
+
+#[actix_web::test]
+async fn test_get_stele_from_request_with_scope_header_expect_dependent_stele() {
+    let archive_path = common::initialize_archive(ArchiveType::Basic(Jurisdiction::Multi)).unwrap();
+    let archive = Archive::parse(
+        archive_path.path().to_path_buf(),
+        archive_path.path(),
+        false,
+    )
+    .unwrap();
+    for (scope, expected) in [
+        ("sub/scope/1", "dependent_stele_1/law"),
+        ("/sub/scope/2/", "dependent_stele_1/law"),
+        ("sub/scope/4/a/b", "dependent_stele_2/law"),
+    ] {
+        let req = test::TestRequest::default()
+            .insert_header(("X-Stelae-Scope", scope))
+            .to_http_request();
+        let actual = get_stele_from_request(&req, &archive).unwrap();
+        assert_eq!(actual, expected);
+    }
+    let req = test::TestRequest::default()
+        .insert_header(("X-Stelae-Scope", "sub/scope"))
+        .to_http_request();
+    assert!(matches!(
+        get_stele_from_request(&req, &archive),
+        Err(SteleError::UnservedScope { .. })
+    ));
+    let req = test::TestRequest::default()
+        .insert_header(("X-Stelae", "dependent_stele_1/law"))
+        .insert_header(("X-Stelae-Scope", "sub/scope/3"))
+        .to_http_request();
+    assert_eq!(
+        get_stele_from_request(&req, &archive),
+        Err(SteleError::ConflictingHeaders)
+    );
+}
+
+#[actix_web::test]
+async fn test_archive_summary_request_expect_stelae_of_archive() {
+    let archive_path = common::initialize_archive(ArchiveType::Basic(Jurisdiction::Multi)).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+    let req = test::TestRequest::get().uri("/_admin/archive").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success(), "{}", resp.status());
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    assert!(actual["stelae"]
+        .as_object()
+        .unwrap()
+        .contains_key("root_test_org/law"));
+}
+
+#[actix_web::test]
+async fn test_take_down_when_other_stele_serves_path_expect_taken_down_in_stele_only() {
+    let archive_path = common::initialize_archive(ArchiveType::Basic(Jurisdiction::Multi)).unwrap();
+    let app = common::initialize_app_as_admin(archive_path.path()).await;
+    let uri = "/sub/scope/1/a/b/c.html";
+
+    let req = test::TestRequest::post()
+        .uri("/_admin/takedowns")
+        .insert_header(common::admin_authorization())
+        .insert_header(("X-Stelae", "root_test_org/law"))
+        .set_json(serde_json::json!({"path": uri, "reason": "Court order 123"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+    let req = test::TestRequest::get().uri(uri).to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::post()
+        .uri("/_admin/takedowns")
+        .insert_header(common::admin_authorization())
+        .insert_header(("X-Stelae", "dependent_stele_1/law"))
+        .set_json(serde_json::json!({"path": uri, "reason": "Court order 123"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+    let req = test::TestRequest::get().uri(uri).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 451);
+
+    let req = test::TestRequest::get()
+        .uri("/_admin/takedowns")
+        .insert_header(common::admin_authorization())
+        .insert_header(("X-Stelae", "root_test_org/law"))
+        .to_request();
+    let actual: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let expected = serde_json::json!([{
+        "path": "sub/scope/1/a/b/c.html",
+        "reason": "Court order 123",
+        "stele": "root_test_org/law",
+    }]);
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,78 @@
+use crate::common;
+use actix_web::{http::StatusCode, test};
+use stelae::db;
+use stelae::testing::generate;
+
+#[actix_web::test]
+async fn test_compare_collection_when_dates_given_expect_net_changes_of_members() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    // The generated archive has no collections, so the document is made a member of `cb`, next
+    // to the empty `c_`, whose materialized path matches `cb|` unless the wildcard is escaped.
+    let conn = db::init::connect(archive_path.path()).await.unwrap();
+    for statement in [
+        "INSERT INTO library (mpath, url, stele) VALUES ('|c_|', '/c_', 'generated/law'), ('|cb|', '/cb', 'generated/law')",
+        "INSERT INTO changed_library_document (library_mpath, document_change_id) SELECT '|cb|', id FROM document_change",
+    ] {
+        sqlx::query(statement).execute(&conn.pool).await.unwrap();
+    }
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, expected) in [
+        (
+            "/_api/compare-collection?path=cb&from=2019-12-31&to=2020-01-31",
+            serde_json::json!({ "new": 1, "changed": 0, "removed": 0 }),
+        ),
+        (
+            "/_api/compare-collection?path=cb&from=2020-01-01&to=2020-01-31",
+            serde_json::json!({ "new": 0, "changed": 1, "removed": 0 }),
+        ),
+        (
+            "/_api/compare-collection?path=c_&from=2019-12-31&to=2020-01-31",
+            serde_json::json!({ "new": 0, "changed": 0, "removed": 0 }),
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actual: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(actual["counts"], expected, "{uri}");
+    }
+
+    for (uri, expected) in [
+        (
+            "/_api/compare-collection?path=missing&from=2020-01-01&to=2020-01-31",
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "/_api/compare-collection?path=cb&from=2020-01-31&to=2020-01-01",
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actual = test::call_service(&app, req).await.status();
+        assert_eq!(actual, expected, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_compare_collection_when_accept_version_2_expect_enveloped_error() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/compare-collection?path=missing&from=2020-01-01&to=2020-01-31")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0]["code"], "collection_not_found");
+}
@@ -0,0 +1,59 @@
+use crate::common;
+use actix_web::test;
+use sqlx::Row as _;
+use stelae::history::changes;
+use stelae::testing::generate;
+use stelae::utils::output::Output;
+
+#[actix_web::test]
+async fn test_update_when_current_index_enabled_expect_current_blobs_served_by_id() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[current_index]\nenabled = true\n");
+    std::fs::write(&config_path, config).unwrap();
+    changes::insert(
+        &archive_path.path().to_string_lossy(),
+        archive_path.path().to_path_buf(),
+        false,
+        false,
+        Output::Text,
+    )
+    .await
+    .unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+
+    let paths: Vec<String> = sqlx::query(
+        "SELECT path FROM current_blobs WHERE repository = 'generated/law-html' ORDER BY path",
+    )
+    .fetch_all(&conn.pool)
+    .await
+    .unwrap()
+    .iter()
+    .map(|row| row.get("path"))
+    .collect();
+    assert_eq!(paths, vec!["doc-0/index.html", "doc-1/index.html"]);
+
+    // Point the blob of the first document at the blob of the second, which is only served if
+    // the current blob is read by its id.
+    sqlx::query(
+        "UPDATE current_blobs SET blob_id = (
+            SELECT blob_id FROM current_blobs WHERE path = 'doc-1/index.html'
+        ) WHERE path = 'doc-0/index.html'",
+    )
+    .execute(&conn.pool)
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/doc-0").to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("Version 1 of document 1"), "{body}");
+}
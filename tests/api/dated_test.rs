@@ -0,0 +1,290 @@
+use crate::common;
+use actix_web::{
+    http::{header, StatusCode},
+    test,
+};
+use stelae::server::api::state::App as AppState;
+use stelae::server::base_path::BasePath;
+use stelae::testing::generate;
+
+#[actix_web::test]
+async fn test_serve_on_date_when_html_repo_expect_version_of_date() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (date, expected) in [
+        ("2020-01-01", "Version 0 of document 0"),
+        ("2020-01-15", "Version 0 of document 0"),
+        ("2020-01-31", "Version 1 of document 0"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/_date/{date}/_repo/html/doc-0"))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains(expected), "{date}: {actual}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_path_without_repo_expect_repo_serving_path() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, expected) in [
+        ("/_date/2020-01-15/doc-0", "Version 0 of document 0"),
+        ("/_date/2020-01-31/doc-1/", "Version 1 of document 1"),
+        ("/_date/2020-01-15/doc-0.pdf", "Version 0 of document 0"),
+        (
+            "/_date/2020-01-31/_xml/doc-1.xml",
+            "Version 1 of document 1",
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains(expected), "{uri}: {actual}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_html_expect_banner_injections_and_dated_urls() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for uri in [
+        "/_date/2020-01-15/doc-0",
+        "/_date/2020-01-15/_repo/html/doc-0",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains("stelae-watermark"), "{uri}: {actual}");
+        assert!(
+            actual.contains(r#"<meta content="2020-01-15" name="version-date">"#),
+            "{uri}: {actual}"
+        );
+        assert!(
+            actual.contains(r#"href="/_date/2020-01-15/doc-1""#),
+            "{uri}: {actual}"
+        );
+    }
+
+    let req = test::TestRequest::get().uri("/doc-0").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let actual = String::from_utf8_lossy(&body);
+    assert!(!actual.contains("stelae-watermark"), "{actual}");
+    assert!(actual.contains(r#"href="/doc-1""#), "{actual}");
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_base_path_expect_dated_urls_under_base_path() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app_with(archive_path.path(), |state| AppState {
+        base_path: BasePath::parse("/laws").unwrap(),
+        ..state
+    })
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/laws/_date/2020-01-01/doc-0")
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let actual = String::from_utf8_lossy(&body);
+    assert!(
+        actual.contains(r#"href="/laws/_date/2020-01-01/doc-1""#),
+        "{actual}"
+    );
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_pdf_or_xml_repo_expect_version_of_date() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, expected) in [
+        (
+            "/_date/2020-01-15/_repo/pdf/doc-0.pdf",
+            "Version 0 of document 0",
+        ),
+        (
+            "/_date/2020-01-31/_repo/pdf/doc-0.pdf",
+            "Version 1 of document 0",
+        ),
+        (
+            "/_date/2020-01-15/_repo/xml/doc-0.xml",
+            "Version 0 of document 0",
+        ),
+        (
+            "/_date/2020-01-31/_repo/xml/doc-0.xml",
+            "Version 1 of document 0",
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains(expected), "{uri}: {actual}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_no_repo_or_version_expect_not_found() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, expected) in [
+        ("/_date/2020-01-01/_repo/rdf/doc-0", StatusCode::NOT_FOUND),
+        (
+            "/_date/2020-01-01/_repo/pdf/doc-9.pdf",
+            StatusCode::NOT_FOUND,
+        ),
+        ("/_date/2019-12-31/_repo/html/doc-0", StatusCode::NOT_FOUND),
+        ("/_date/2020-01-01/_repo/html/doc-9", StatusCode::NOT_FOUND),
+        (
+            "/_date/2020-13-01/_repo/html/doc-0",
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actual = test::call_service(&app, req).await.status();
+        assert_eq!(actual, expected, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_formats_on_date_when_effective_periods_unavailable_expect_server_error() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/formats/doc-0?date=2019-12-31")
+        .to_request();
+    let actual = test::call_service(&app, req).await.status();
+    assert_eq!(actual, StatusCode::NOT_FOUND);
+
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("DROP TABLE document_change")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::get()
+        .uri("/_api/formats/doc-0?date=2019-12-31")
+        .to_request();
+    let actual = test::call_service(&app, req).await.status();
+    assert_eq!(actual, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[actix_web::test]
+async fn test_in_force_when_redirected_expect_document_of_date_served() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (on, expected) in [
+        ("2020-01-15", "Version 0 of document 0"),
+        ("2020-02-15", "Version 1 of document 0"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/_api/in-force/doc-0?on={on}"))
+            .to_request();
+        let redirect = test::call_service(&app, req).await;
+        assert_eq!(redirect.status(), StatusCode::FOUND, "{on}");
+        let location = redirect.headers().get(header::LOCATION).unwrap();
+        let location = location.to_str().unwrap().to_owned();
+
+        let req = test::TestRequest::get().uri(&location).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK, "{location}");
+        let body = test::read_body(response).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains(expected), "{location}: {actual}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_html_expect_canonical_link_to_current_document() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[structured_data]\njurisdiction = \"US-CA\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_date/2020-01-15/doc-0")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let link = resp
+        .headers()
+        .get("Link")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body = test::read_body(resp).await;
+    let actual = String::from_utf8_lossy(&body);
+    assert!(link.ends_with(r#"/doc-0>; rel="canonical""#), "{link}");
+    let canonical = link.trim_start_matches('<').split('>').next().unwrap();
+    assert!(
+        actual.contains(&format!(r#"<link rel="canonical" href="{canonical}">"#)),
+        "{actual}"
+    );
+    assert!(
+        actual.contains(r#"<script type="application/ld+json">"#),
+        "{actual}"
+    );
+    assert!(
+        actual.contains(r#""legislationJurisdiction":"US-CA""#),
+        "{actual}"
+    );
+    assert!(
+        actual.contains(r#""legislationDateVersion":"2020-01-15""#),
+        "{actual}"
+    );
+}
@@ -0,0 +1,114 @@
+use crate::common;
+use actix_web::{http::StatusCode, test};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest as _, Sha384};
+use stelae::server::api::state::App as AppState;
+use stelae::server::base_path::BasePath;
+use stelae::testing::generate;
+
+/// Script committed to the html repository of the generated archive.
+const SCRIPT: &[u8] = b"alert('Hello, world.');";
+/// Stylesheet committed to the html repository of the generated archive.
+const STYLESHEET: &[u8] = b"body { color: black; }";
+
+/// Commit the `SCRIPT` at `app.js` and the `STYLESHEET` at `_static/site.css` to the html
+/// repository of the generated archive at `archive_path`.
+fn commit_assets(archive_path: &std::path::Path) {
+    let repo = git2::Repository::open(archive_path.join("generated/law-html")).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let mut statics = repo.treebuilder(None).unwrap();
+    statics
+        .insert("site.css", repo.blob(STYLESHEET).unwrap(), 0o100_644)
+        .unwrap();
+    let mut tree = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+    tree.insert("app.js", repo.blob(SCRIPT).unwrap(), 0o100_644)
+        .unwrap();
+    tree.insert("_static", statics.write().unwrap(), 0o040_000)
+        .unwrap();
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let signature = head.author();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "Add assets",
+        &tree,
+        &[&head],
+    )
+    .unwrap();
+}
+
+#[actix_web::test]
+async fn test_asset_integrity_when_no_assets_expect_empty_manifest() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/asset-integrity.json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(actual, serde_json::json!({}));
+}
+
+#[actix_web::test]
+async fn test_asset_integrity_when_scripts_and_stylesheets_expect_sha384_by_url() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    commit_assets(archive_path.path());
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/asset-integrity.json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let vary = resp
+        .headers()
+        .get_all("Vary")
+        .map(|value| value.to_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(vary, ["X-Stelae", "X-Stelae-Scope"]);
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    let stylesheet = format!("sha384-{}", STANDARD.encode(Sha384::digest(STYLESHEET)));
+    let expected = serde_json::json!({
+        "/app.js": "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO",
+        "/_static/site.css": stylesheet,
+    });
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_asset_integrity_when_base_path_expect_urls_under_base_path() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    commit_assets(archive_path.path());
+    let app = common::initialize_app_with(archive_path.path(), |state| AppState {
+        base_path: BasePath::parse("/laws").unwrap(),
+        ..state
+    })
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/laws/_api/asset-integrity.json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let actual: serde_json::Value = test::read_body_json(resp).await;
+    let urls = actual.as_object().unwrap().keys().collect::<Vec<_>>();
+    assert_eq!(urls, ["/laws/_static/site.css", "/laws/app.js"]);
+}
@@ -1,3 +1,12 @@
+mod approval_test;
 mod archive_basic_test;
 mod archive_multihost_test;
 mod archive_multijursidiction_test;
+mod compare_test;
+mod current_index_test;
+mod dated_test;
+mod integrity_test;
+mod pinned_test;
+mod preview_test;
+mod stats_test;
+mod versions_test;
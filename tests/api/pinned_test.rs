@@ -0,0 +1,221 @@
+use crate::common;
+use actix_web::{
+    http::{header, StatusCode},
+    test,
+};
+use stelae::server::api::dated::find_historical_repository;
+use stelae::stelae::archive::Archive;
+use stelae::testing::generate;
+use stelae::utils::git::Repo;
+use stelae::utils::http::content_sha256;
+
+#[actix_web::test]
+async fn test_serve_at_commit_when_head_commit_expect_latest_version() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let sha = Repo::new(archive_path.path(), "generated", "law-html")
+        .unwrap()
+        .head_commit_id()
+        .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/_commit/{sha}/doc-0"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cache_control = resp.headers().get("Cache-Control").unwrap().to_owned();
+    let checksum = resp.headers().get("X-Content-SHA256").unwrap().to_owned();
+    let body = test::read_body(resp).await;
+    let actual = String::from_utf8_lossy(&body);
+    assert!(actual.contains("Version 1 of document 0"), "{actual}");
+    assert!(cache_control.to_str().unwrap().contains("immutable"));
+    assert_eq!(checksum.to_str().unwrap(), content_sha256(&body));
+}
+
+#[actix_web::test]
+async fn test_serve_at_commit_when_repository_removed_since_expect_document_of_commit() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let sha = Repo::new(archive_path.path(), "generated", "law-html")
+        .unwrap()
+        .head_commit_id()
+        .unwrap();
+    let auth_commit = Repo::new(archive_path.path(), "generated", "law")
+        .unwrap()
+        .head_commit_id()
+        .unwrap();
+    common::remove_repository(archive_path.path(), "generated/law-html").unwrap();
+    let archive = Archive::parse(
+        archive_path.path().to_path_buf(),
+        archive_path.path(),
+        false,
+    )
+    .unwrap();
+    let stele = archive.stelae.get("generated/law").unwrap();
+
+    let listed = |committish: &str| {
+        stele
+            .get_repositories_at_commit(committish)
+            .unwrap()
+            .unwrap()
+            .repositories
+            .contains_key("generated/law-html")
+    };
+    assert!(!listed("HEAD"));
+    assert!(listed(&auth_commit));
+    let found = find_historical_repository(&archive, "generated/law", &auth_commit, "html");
+    assert_eq!(found.unwrap().name, "generated/law-html");
+    assert!(find_historical_repository(&archive, "generated/law", "HEAD", "html").is_none());
+
+    let app = common::initialize_app(archive_path.path()).await;
+    for (uri, expected) in [
+        (format!("/_commit/{sha}/doc-0"), "Version 1 of document 0"),
+        (
+            "/_date/2020-01-01/_repo/html/doc-0".to_owned(),
+            "Version 0 of document 0",
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        let body = test::read_body(resp).await;
+        let actual = String::from_utf8_lossy(&body);
+        assert!(actual.contains(expected), "{uri}: {actual}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_at_commit_when_unknown_or_abbreviated_sha_expect_error() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, expected) in [
+        (
+            "/_commit/0000000000000000000000000000000000000000/doc-0",
+            StatusCode::NOT_FOUND,
+        ),
+        ("/_commit/0f2f1ef/doc-0", StatusCode::BAD_REQUEST),
+        ("/_commit/HEAD/doc-0", StatusCode::BAD_REQUEST),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actual = test::call_service(&app, req).await.status();
+        assert_eq!(actual, expected, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_at_commit_when_uppercase_sha_expect_redirect_to_lowercase() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let sha = Repo::new(archive_path.path(), "generated", "law-html")
+        .unwrap()
+        .head_commit_id()
+        .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/_commit/{}/doc-0?format=json",
+            sha.to_uppercase()
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+    let location = resp.headers().get(header::LOCATION).unwrap();
+    assert_eq!(
+        location.to_str().unwrap(),
+        format!("/_commit/{sha}/doc-0?format=json")
+    );
+}
+
+#[actix_web::test]
+async fn test_serve_at_commit_when_unrecorded_or_preview_commit_expect_not_found_without_token() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[preview]\ntoken = \"secret\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let repo = Repo::new(archive_path.path(), "generated", "law-html").unwrap();
+    let latest = repo.head_commit_id().unwrap();
+    let first = repo
+        .repo
+        .revparse_single("HEAD~1")
+        .unwrap()
+        .id()
+        .to_string();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM data_repo_commits WHERE commit_hash = $1")
+        .bind(&first)
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET preview = 1 WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (sha, token, expected) in [
+        (&first, Some("secret"), StatusCode::NOT_FOUND),
+        (&latest, None, StatusCode::NOT_FOUND),
+        (&latest, Some("guess"), StatusCode::NOT_FOUND),
+        (&latest, Some("secret"), StatusCode::OK),
+    ] {
+        let mut req = test::TestRequest::get().uri(&format!("/_commit/{sha}/doc-0"));
+        if let Some(value) = token {
+            req = req.insert_header(("X-Stelae-Preview", value));
+        }
+        let actual = test::call_service(&app, req.to_request()).await.status();
+        assert_eq!(actual, expected, "{sha} {token:?}");
+    }
+}
+
+#[actix_web::test]
+async fn test_pin_when_auth_not_configured_expect_forbidden() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/_admin/pin")
+        .set_json(serde_json::json!({"name": "unauthenticated"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/_snapshot/unauthenticated/doc-0")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,89 @@
+use crate::common;
+use actix_web::{http::StatusCode, test};
+use stelae::testing::generate;
+
+/// Generate an archive of two versions whose latest publication is a preview, served with the
+/// preview token `secret`.
+async fn initialize_archive_with_preview() -> tempfile::TempDir {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let config_path = archive_path.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[preview]\ntoken = \"secret\"\n");
+    std::fs::write(&config_path, config).unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE publication SET preview = 1 WHERE name = '2020-01-31'")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    archive_path
+}
+
+#[actix_web::test]
+async fn test_serve_when_head_only_in_preview_expect_published_version_without_token() {
+    let archive_path = initialize_archive_with_preview().await;
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (uri, token, expected) in [
+        ("/doc-0", None, "Version 0 of document 0"),
+        ("/doc-0", Some("guess"), "Version 0 of document 0"),
+        ("/doc-0", Some("secret"), "Version 1 of document 0"),
+        ("/doc-0.pdf", None, "Version 0 of document 0"),
+        ("/doc-0.pdf", Some("secret"), "Version 1 of document 0"),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(value) = token {
+            req = req.insert_header(("X-Stelae-Preview", value));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri} with {token:?}");
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains(expected), "{uri} with {token:?}: {body}");
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_date_when_commit_of_preview_expect_published_version_without_token() {
+    let archive_path = initialize_archive_with_preview().await;
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (token, expected) in [
+        (None, "Version 0 of document 0"),
+        (Some("secret"), "Version 1 of document 0"),
+    ] {
+        let mut req = test::TestRequest::get().uri("/_date/2020-01-31/_repo/html/doc-0");
+        if let Some(value) = token {
+            req = req.insert_header(("X-Stelae-Preview", value));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{token:?}");
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains(expected), "{token:?}: {body}");
+    }
+}
+
+#[actix_web::test]
+async fn test_versions_when_preview_expect_preview_publication_listed_with_token_only() {
+    let archive_path = initialize_archive_with_preview().await;
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (token, expected) in [
+        (None, vec!["2020-01-01"]),
+        (Some("secret"), vec!["2020-01-31", "2020-01-01"]),
+    ] {
+        let mut req = test::TestRequest::get().uri("/_api/versions/doc-0");
+        if let Some(value) = token {
+            req = req.insert_header(("X-Stelae-Preview", value));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{token:?}");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(common::publication_names(&body), expected, "{token:?}");
+    }
+}
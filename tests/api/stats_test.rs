@@ -0,0 +1,102 @@
+use crate::common;
+use actix_web::test;
+use stelae::db;
+use stelae::history::views::ViewCounter;
+use stelae::server::api::state::App as AppState;
+use stelae::stelae::archive::Archive;
+use stelae::testing::generate;
+
+#[actix_web::test]
+async fn test_top_documents_when_views_flushed_expect_most_viewed_first() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let archive = Archive::parse(
+        archive_path.path().to_path_buf(),
+        archive_path.path(),
+        false,
+    )
+    .unwrap();
+    let root = archive.get_root().unwrap().get_qualified_name();
+    let views = ViewCounter::enabled();
+    views.record(&root, "doc-1");
+    views.record(&root, "doc-2");
+    views.record(&root, "doc-2");
+    let conn = db::init::connect(archive_path.path()).await.unwrap();
+    views.flush(&conn).await.unwrap();
+    views.record(&root, "doc-1");
+    views.flush(&conn).await.unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/stats/top-documents?since=2020-01-01")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let expected = serde_json::json!([
+        { "path": "doc-1", "views": 2 },
+        { "path": "doc-2", "views": 2 },
+    ]);
+    assert_eq!(body["documents"], expected);
+    assert_eq!(body["since"], "2020-01-01");
+}
+
+#[actix_web::test]
+async fn test_stats_when_management_route_expect_counts_and_measured_sizes() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/_admin/stats").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body[0]["stele"], "generated/law");
+    assert_eq!(body[0]["documents"], 2);
+    let repositories = body[0]["repositories"].as_array().unwrap();
+    assert!(repositories
+        .iter()
+        .all(|repository| repository["bytes"].as_u64().unwrap() > 0));
+}
+
+#[actix_web::test]
+async fn test_serve_when_views_counted_expect_listed_in_top_documents() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let views = ViewCounter::enabled();
+    let app = common::initialize_app_with(archive_path.path(), |state| AppState {
+        views: views.clone(),
+        ..state
+    })
+    .await;
+    for uri in ["/doc-0", "/doc-0", "/doc-1"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "{uri}");
+    }
+    let req = test::TestRequest::default()
+        .method(actix_web::http::Method::HEAD)
+        .uri("/doc-1")
+        .to_request();
+    test::call_service(&app, req).await;
+    let conn = db::init::connect(archive_path.path()).await.unwrap();
+    assert_eq!(views.flush(&conn).await.unwrap(), 2);
+
+    let req = test::TestRequest::get()
+        .uri("/_api/stats/top-documents")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let expected = serde_json::json!([
+        { "path": "doc-0", "views": 2 },
+        { "path": "doc-1", "views": 1 },
+    ]);
+    assert_eq!(body["documents"], expected);
+}
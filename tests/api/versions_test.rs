@@ -0,0 +1,404 @@
+use crate::common;
+use actix_web::{
+    http::{header, StatusCode},
+    test,
+};
+use stelae::testing::generate;
+
+/// Dates of the versions of the active publication in a versions response.
+fn version_dates(body: &serde_json::Value, publication: &str) -> Vec<String> {
+    body["publications"][publication]["versions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|version| version["date"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_versions_when_history_inserted_expect_every_version_of_current_publication() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let actual = version_dates(&body, "Current");
+    let expected = vec!["current", "2020-03-01", "2020-01-31", "2020-01-01"];
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_versions_when_past_publication_expect_versions_up_to_publication() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/_publication/2020-01-31/doc-1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let actual = version_dates(&body, "2020-01-31");
+    let expected = vec!["current", "2020-01-31", "2020-01-01"];
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_versions_when_date_expect_active_version_of_date() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/_date/2020-01-31/doc-1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let actual = &body["activeVersion"];
+    let expected = "2020-01-31";
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_adjacent_when_middle_version_expect_previous_and_next_versions() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1/adjacent?date=2020-01-31")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let actual = (
+        body["previous"]["date"].as_str().unwrap(),
+        body["next"]["date"].as_str().unwrap(),
+    );
+    let expected = ("2020-01-01", "2020-03-01");
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_adjacent_when_malformed_date_expect_bad_request_with_api_error() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1/adjacent?date=2020-13-01")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let actual = &body["errors"][0]["code"];
+    let expected = "invalid_query";
+    assert_eq!(actual, expected);
+}
+
+#[actix_web::test]
+async fn test_versions_when_malformed_date_expect_bad_request_with_nearest_versions() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for (segment, expected) in [
+        (
+            "2020-02-30",
+            vec!["/_date/2020-03-01/doc-1", "/_date/2020-01-31/doc-1"],
+        ),
+        ("notadate", vec!["/_date/2020-03-01/doc-1"]),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/_api/versions/_date/{segment}/doc-1"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["expectedFormat"], "YYYY-MM-DD");
+        let actual: Vec<&str> = body["nearest"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|version| version["url"].as_str().unwrap())
+            .collect();
+        assert_eq!(actual, expected, "{segment}");
+    }
+}
+
+#[actix_web::test]
+async fn test_versions_when_if_none_match_etag_expect_not_modified() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CACHE_CONTROL).unwrap(),
+        "public, max-age=60"
+    );
+    let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+    assert!(vary.iter().any(|value| *value == "X-Stelae"));
+    assert!(vary.iter().any(|value| *value == "X-Stelae-Scope"));
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header((header::IF_NONE_MATCH, etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header((header::IF_NONE_MATCH, "\"stale\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_versions_when_if_modified_since_last_modified_expect_not_modified() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let last_modified = resp.headers().get(header::LAST_MODIFIED).unwrap().clone();
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(
+        resp.headers().get(header::LAST_MODIFIED),
+        Some(&last_modified)
+    );
+
+    for (name, value) in [
+        (header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT"),
+        (header::IF_NONE_MATCH, "\"stale\""),
+    ] {
+        let req = test::TestRequest::get()
+            .uri("/_api/versions/doc-1")
+            .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+            .insert_header((name.clone(), value))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{name}: {value}");
+    }
+}
+
+#[actix_web::test]
+async fn test_versions_when_other_accept_version_expect_other_etag() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let legacy = resp.headers().get(header::ETAG).unwrap().clone();
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let enveloped = resp.headers().get(header::ETAG).unwrap().clone();
+    assert_ne!(legacy, enveloped);
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .insert_header((header::IF_NONE_MATCH, legacy))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+    assert!(vary.iter().any(|value| *value == "Accept-Version"));
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .insert_header((header::IF_NONE_MATCH, enveloped))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+    assert!(vary.iter().any(|value| *value == "Accept-Version"));
+}
+
+#[actix_web::test]
+async fn test_status_when_auth_not_configured_expect_forbidden() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get().uri("/_admin/status").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_versions_when_accept_version_2_expect_enveloped_response() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/_date/2020-01-31/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+    assert!(vary.iter().any(|value| *value == "Accept-Version"));
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["meta"]["apiVersion"], "2");
+    assert_eq!(body["errors"], serde_json::json!([]));
+    let actual = version_dates(&body["data"], "Current");
+    let expected = vec!["current", "2020-03-01", "2020-01-31", "2020-01-01"];
+    assert_eq!(actual, expected);
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/_date/2020-13-45/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0]["code"], "invalid_date");
+    assert_eq!(body["data"]["date"], "2020-13-45");
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header(("Accept-Version", "3"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[actix_web::test]
+async fn test_versions_when_database_fails_expect_internal_server_error_with_api_error() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 3,
+    })
+    .await
+    .unwrap();
+    let conn = stelae::db::init::connect(archive_path.path())
+        .await
+        .unwrap();
+    sqlx::query("ALTER TABLE publication RENAME TO broken_publication")
+        .execute(&conn.pool)
+        .await
+        .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/_api/versions/doc-1")
+        .insert_header(("Accept-Version", "2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0]["code"], "database_error");
+}
+
+#[actix_web::test]
+async fn test_api_when_accept_version_2_expect_enveloped_responses() {
+    let archive_path = common::initialize_archive_with_history(generate::Size {
+        documents: 1,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let app = common::initialize_app(archive_path.path()).await;
+
+    for uri in ["/_api/publications/2020-01-31/delta", "/_api/suggest?q=doc"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let legacy: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Accept-Version", "2"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+        assert!(vary.iter().any(|value| *value == "Accept-Version"), "{uri}");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"], legacy, "{uri}");
+        assert_eq!(body["meta"]["apiVersion"], "2", "{uri}");
+    }
+
+    for (uri, expected) in [
+        ("/_api/timeline/doc-missing", "document_not_found"),
+        (
+            "/_api/publications/1999-01-01/delta",
+            "publication_not_found",
+        ),
+        ("/_api/suggest?q=%20", "invalid_query"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Accept-Version", "2"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["errors"][0]["code"], expected, "{uri}");
+    }
+}
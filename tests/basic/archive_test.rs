@@ -1,4 +1,9 @@
+use chrono::NaiveDate;
+use stelae::history::changes::find_latest_rdf_publication;
+use stelae::stelae::archive::Archive;
+use stelae::testing::generate;
 use stelae::utils::archive::find_archive_path;
+use stelae::utils::git::Repo;
 
 use crate::common::{self, BASIC_MODULE_NAME};
 
@@ -43,3 +48,25 @@ fn test_find_archive_path_when_not_in_archive_expect_error() {
         "\"{actual}\" doesn't contain {expected}"
     );
 }
+
+#[test]
+fn test_generate_when_several_versions_expect_latest_publication_last_version() {
+    let archive_path = tempfile::tempdir().unwrap();
+    let size = generate::Size {
+        documents: 3,
+        versions: 2,
+    };
+    generate::generate(archive_path.path(), size).unwrap();
+    let archive = Archive::parse(
+        archive_path.path().to_path_buf(),
+        archive_path.path(),
+        false,
+    )
+    .unwrap();
+    assert!(archive.stelae.contains_key("generated/law"));
+    let repo = Repo::new(archive_path.path(), "generated", "law-rdf").unwrap();
+    let actual = find_latest_rdf_publication(&repo, "generated/law").unwrap();
+    let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+    let expected = Some(("2020-01-31".to_owned(), date));
+    assert_eq!(actual, expected);
+}
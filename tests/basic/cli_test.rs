@@ -0,0 +1,183 @@
+use crate::common;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use stelae::testing::generate;
+use tempfile::Builder;
+
+/// Run the `stelae` binary on the archive at `archive_path` with `args`.
+pub fn stelae(archive_path: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stelae"))
+        .arg("--archive-path")
+        .arg(archive_path)
+        .args(args)
+        .env_remove("DATABASE_URL")
+        .output()
+        .unwrap()
+}
+
+/// The JSON document the command wrote to stdout.
+pub fn json_results(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|err| {
+        panic!(
+            "stdout is not a single JSON document: {err}\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })
+}
+
+#[test]
+fn test_update_when_output_json_expect_results_of_every_stele() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/");
+    let archive_dir = Builder::new().tempdir_in(&path).unwrap();
+    generate::generate(
+        archive_dir.path(),
+        generate::Size {
+            documents: 2,
+            versions: 1,
+        },
+    )
+    .unwrap();
+
+    let output = stelae(archive_dir.path(), &["update", "--output", "json"]);
+
+    assert!(output.status.success());
+    let actual = json_results(&output);
+    assert_eq!(actual["ok"], Value::Bool(true));
+    assert_eq!(actual["error"], Value::Null);
+    assert_eq!(actual["stelae"][0]["stele"], "generated/law");
+    assert_eq!(actual["stelae"][0]["ok"], Value::Bool(true));
+    assert_eq!(actual["stelae"][0]["errors"], Value::Array(vec![]));
+}
+
+#[actix_web::test]
+async fn test_manifest_verify_when_output_json_expect_outcome_and_problems() {
+    let archive_dir = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 1,
+    })
+    .await
+    .unwrap();
+    let manifest_file = archive_dir.path().join("manifest.json");
+    let created = stelae(
+        archive_dir.path(),
+        &[
+            "manifest",
+            "--date",
+            "2020-01-31",
+            "--out",
+            &manifest_file.to_string_lossy(),
+        ],
+    );
+    assert!(created.status.success());
+
+    let manifest_arg = manifest_file.to_string_lossy();
+    let verify = ["manifest", "--verify", &manifest_arg, "--output", "json"];
+    let output = stelae(archive_dir.path(), &verify);
+
+    assert!(output.status.success());
+    let actual = json_results(&output);
+    assert_eq!(actual["ok"], Value::Bool(true));
+    assert!(actual["matched"].as_u64().unwrap() > 0);
+    assert_eq!(actual["problems"], Value::Array(vec![]));
+
+    let mut manifest: Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_file).unwrap()).unwrap();
+    manifest["repositories"][0]["files"][0]["sha256"] = Value::String("0".repeat(64));
+    std::fs::write(&manifest_file, manifest.to_string()).unwrap();
+
+    let output = stelae(archive_dir.path(), &verify);
+
+    assert!(!output.status.success());
+    let actual = json_results(&output);
+    assert_eq!(actual["ok"], Value::Bool(false));
+    assert!(!actual["problems"].as_array().unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_diff_publications_when_from_given_expect_json_comparison() {
+    let archive_dir = common::initialize_archive_with_history(generate::Size {
+        documents: 2,
+        versions: 2,
+    })
+    .await
+    .unwrap();
+    let diff_file = archive_dir.path().join("diff.json");
+    let diff_arg = diff_file.to_string_lossy();
+
+    let output = stelae(
+        archive_dir.path(),
+        &[
+            "diff-publications",
+            "2020-01-31",
+            "--from",
+            "2020-01-01",
+            "--output",
+            "json",
+            "--out",
+            &diff_arg,
+        ],
+    );
+
+    assert!(output.status.success());
+    let actual: Value =
+        serde_json::from_str(&std::fs::read_to_string(&diff_file).unwrap()).unwrap();
+    assert_eq!(actual["stele"], "generated/law");
+    assert_eq!(actual["from"], "2020-01-01");
+    assert_eq!(actual["to"], "2020-01-31");
+    assert!(actual["documents"].is_array());
+
+    let output = stelae(
+        archive_dir.path(),
+        &["diff-publications", "2020-01-01", "--from", "2020-01-31"],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_update_when_unregistered_plugin_expect_config_error_exit_code() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/");
+    let archive_dir = Builder::new().tempdir_in(&path).unwrap();
+    generate::generate(
+        archive_dir.path(),
+        generate::Size {
+            documents: 1,
+            versions: 1,
+        },
+    )
+    .unwrap();
+    let config_path = archive_dir.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[ingest]\nplugins = [\"unregistered\"]\n");
+    std::fs::write(&config_path, config).unwrap();
+
+    let output = stelae(archive_dir.path(), &["update", "--output", "json"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let actual = json_results(&output);
+    assert_eq!(actual["ok"], Value::Bool(false));
+    assert!(actual["error"].as_str().unwrap().contains("unregistered"));
+}
+
+#[test]
+fn test_update_when_archive_without_stelae_expect_archive_parse_error_exit_code() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/");
+    let archive_dir = Builder::new().tempdir_in(&path).unwrap();
+    generate::generate(
+        archive_dir.path(),
+        generate::Size {
+            documents: 1,
+            versions: 1,
+        },
+    )
+    .unwrap();
+    std::fs::remove_dir_all(archive_dir.path().join("generated")).unwrap();
+
+    let output = stelae(archive_dir.path(), &["update"]);
+
+    assert_eq!(output.status.code(), Some(4));
+}
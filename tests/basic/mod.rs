@@ -1,2 +1,4 @@
 mod archive_test;
+mod cli_test;
 mod gitrepo_test;
+mod status_test;
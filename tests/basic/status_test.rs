@@ -0,0 +1,65 @@
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::path::PathBuf;
+use stelae::history::changes::find_latest_rdf_publication;
+use stelae::testing::generate;
+use stelae::utils::git::Repo;
+use tempfile::Builder;
+
+use super::cli_test::{json_results, stelae};
+use crate::archive_testtools::{
+    self,
+    config::{ArchiveType, Jurisdiction},
+};
+use crate::common;
+
+#[test]
+fn test_find_latest_rdf_publication_when_publications_added_expect_latest() {
+    let archive_path =
+        common::initialize_archive_without_bare(ArchiveType::Basic(Jurisdiction::Single)).unwrap();
+    let rdf_repo =
+        archive_testtools::get_repository(archive_path.path(), "test_org/law-rdf").unwrap();
+    let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let second = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    archive_testtools::add_publication(&rdf_repo, "2024-01-01", first).unwrap();
+    archive_testtools::add_publication(&rdf_repo, "2024-06-01", second).unwrap();
+    archive_testtools::utils::make_all_git_repos_bare_recursive(archive_path.path()).unwrap();
+    let repo = Repo::new(archive_path.path(), "test_org", "law-rdf").unwrap();
+    let actual = find_latest_rdf_publication(&repo, "test_org/law").unwrap();
+    let expected = Some(("2024-06-01".to_owned(), second));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_status_when_updated_publication_awaits_approval_expect_up_to_date() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/");
+    let archive_dir = Builder::new().tempdir_in(&path).unwrap();
+    generate::generate(
+        archive_dir.path(),
+        generate::Size {
+            documents: 1,
+            versions: 1,
+        },
+    )
+    .unwrap();
+    let config_path = archive_dir.path().join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str("\n[approval]\nrequired = true\n");
+    std::fs::write(&config_path, config).unwrap();
+
+    let behind = stelae(archive_dir.path(), &["status", "--output", "json"]);
+    assert_eq!(behind.status.code(), Some(6));
+
+    let updated = stelae(archive_dir.path(), &["update"]);
+    assert!(updated.status.success());
+    let output = stelae(archive_dir.path(), &["status", "--output", "json"]);
+
+    assert!(output.status.success());
+    let actual = json_results(&output);
+    assert_eq!(actual["ok"], Value::Bool(true));
+    assert_eq!(
+        actual["stelae"][0]["dbPublication"],
+        actual["stelae"][0]["rdfPublication"]
+    );
+}
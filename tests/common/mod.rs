@@ -7,17 +7,33 @@ use actix_web::{
     Error,
 };
 use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
 use std::path::{Path, PathBuf};
 use std::sync::Once;
 use stelae::db;
-use stelae::server::api::state::Global;
+use stelae::history::changes;
+use stelae::server::api::identifiers::Identifiers;
+use stelae::server::api::routes::Routes;
+use stelae::server::api::state::App as AppState;
+use stelae::server::api::takedown::Takedowns;
 use tempfile::Builder;
 static INIT: Once = Once::new();
 
 use actix_http::body::MessageBody;
 
+use stelae::history::views::ViewCounter;
 use stelae::server::app;
+use stelae::server::auth::{Jwk, Jwks};
+use stelae::server::base_path::BasePath;
+use stelae::server::cache::Cache;
+use stelae::server::load_shedding::LoadShedder;
+use stelae::server::scheduler::Updates;
+use stelae::server::warmup;
 use stelae::stelae::archive::Archive;
+use stelae::testing::generate;
+use stelae::utils::output::Output;
 
 pub const BASIC_MODULE_NAME: &str = "basic";
 
@@ -25,6 +41,20 @@ pub fn blob_to_string(blob: Vec<u8>) -> String {
     core::str::from_utf8(blob.as_slice()).unwrap().into()
 }
 
+/// Names of the publications listed in a versions response, newest first, without the
+/// `Current` publication.
+pub fn publication_names(body: &serde_json::Value) -> Vec<String> {
+    let mut names: Vec<String> = body["publications"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .filter(|name| *name != "Current")
+        .cloned()
+        .collect();
+    names.sort_by(|left, right| right.cmp(left));
+    names
+}
+
 // TODO: consider adding abort! test macro,
 // which aborts the current test.
 // then we can manually inspect the state of the test environment
@@ -32,33 +62,186 @@ pub fn blob_to_string(blob: Vec<u8>) -> String {
 // to manually inspect state of test environment at present,
 // we use anyhow::bail!() which aborts the entire test suite.
 
-#[derive(Debug, Clone)]
-pub struct TestAppState {
-    archive: Archive,
+/// Initialize the app on the archive at `archive_path`, connected to the database of the archive.
+pub async fn initialize_app(
+    archive_path: &Path,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    initialize_app_with_routes(archive_path, Routes::All).await
 }
 
-impl Global for TestAppState {
-    fn archive(&self) -> &Archive {
-        &self.archive
-    }
-    fn db(&self) -> &db::DatabaseConnection {
-        unimplemented!()
-    }
+/// Initialize the app on the archive at `archive_path` with only the `routes` registered.
+pub async fn initialize_app_with_routes(
+    archive_path: &Path,
+    routes: Routes,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    let state = app_state(archive_path).await;
+    let app = app::init(&state, routes).unwrap();
+    test::init_service(app).await
 }
 
-pub async fn initialize_app(
+/// Initialize the app on the archive at `archive_path` with the state returned by `configure`,
+/// e.g. `|state| AppState { base_path, ..state }`, given the real state of the archive.
+pub async fn initialize_app_with(
     archive_path: &Path,
+    configure: impl FnOnce(AppState) -> AppState,
 ) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
-    let archive = Archive::parse(archive_path.to_path_buf(), archive_path, false).unwrap();
-    let state = TestAppState { archive };
-    let app = app::init(&state).unwrap();
+    let state = configure(app_state(archive_path).await);
+    let app = app::init(&state, Routes::All).unwrap();
     test::init_service(app).await
 }
 
+/// Issuer of the tokens of [`admin_authorization`]. Nothing listens on it, so fetching its
+/// signing keys fails fast.
+const ISSUER: &str = "http://127.0.0.1:9";
+
+/// Modulus of the test key at `tests/fixtures/oidc/test_key.pk8`.
+const TEST_KEY_MODULUS: &str = "pefS65HhvK4Y3T8G5KF7fLf3cgopkgkAsjIx79f5FonG16jW6pDnEhitD63E7MMro0iVosvi7bzhC2apo0tYs0-SmLDNOWVIUjnkMqYQvsqW1leZl_8NEaISy7XR6tvu1EwaKiN-xA1tQIkR6u6ClEcPwDVSthMOYsBYmiFW6Nikyqv3yC3w5F5AZK7DFX474OlNXmE3hDKfPs2Dxyc1tH0S4IWCe2FxvTDYnoDUYGzKLUt2xC-hMyGLiEnUhbPJuudjAe4bsuDrwl-ZjwAB_2bCDznxPfid1Epsf1z9rXzoH_EAworHz9MRW6y3benXDBlgE6b5SMJoav2ZUSVfJQ";
+
+/// Initialize the app on the archive at `archive_path` with `[auth]` configured, authorizing
+/// the requests with [`admin_authorization`] as admins of every stele.
+pub async fn initialize_app_as_admin(
+    archive_path: &Path,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    let config_path = archive_path.join(".taf/config.toml");
+    let mut config = std::fs::read_to_string(&config_path).unwrap_or_default();
+    config.push_str(&format!(
+        "\n[auth]\nissuer = \"{ISSUER}\"\naudience = \"stelae\"\n\n[auth.roles]\nlaw-admins = \"admin\"\n"
+    ));
+    std::fs::write(&config_path, config).unwrap();
+    initialize_app_with(archive_path, |state| {
+        if let Some(authenticator) = state.authenticator.as_ref() {
+            authenticator.set_keys(Jwks {
+                keys: vec![Jwk {
+                    kid: Some("key-1".to_owned()),
+                    kty: "RSA".to_owned(),
+                    n: Some(TEST_KEY_MODULUS.to_owned()),
+                    e: Some("AQAB".to_owned()),
+                }],
+            });
+        }
+        state
+    })
+    .await
+}
+
+/// `Authorization` header of a request by an admin, for the app of [`initialize_app_as_admin`].
+pub fn admin_authorization() -> (&'static str, String) {
+    let key_pair = RsaKeyPair::from_pkcs8(include_bytes!("../fixtures/oidc/test_key.pk8")).unwrap();
+    let header = serde_json::json!({"alg": "RS256", "typ": "JWT", "kid": "key-1"});
+    let claims = serde_json::json!({
+        "iss": ISSUER,
+        "aud": "stelae",
+        "exp": chrono::Utc::now().timestamp() + 300,
+        "groups": ["law-admins"],
+    });
+    let signed = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let mut signature = vec![0; key_pair.public().modulus_len()];
+    key_pair
+        .sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            signed.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+    (
+        "Authorization",
+        format!("Bearer {signed}.{}", URL_SAFE_NO_PAD.encode(signature)),
+    )
+}
+
+/// The real application state of the archive at `archive_path`, connected to its database.
+async fn app_state(archive_path: &Path) -> AppState {
+    let archive = Archive::parse(archive_path.to_path_buf(), archive_path, false).unwrap();
+    let config = archive.get_config().unwrap();
+    let shared = db::init::connect(archive_path).await.unwrap();
+    let db = db::init::connect_stelae(archive_path, archive.stelae.keys(), shared)
+        .await
+        .unwrap();
+    let identifiers = Identifiers::load(&db).await.unwrap();
+    let cache = Cache::default();
+    warmup::load_current_index(&cache, &archive, &db).await;
+    AppState {
+        authenticator: app::init_authenticator(&archive).unwrap(),
+        archive,
+        takedowns: Takedowns::load(db.shared()).await.unwrap(),
+        identifiers,
+        db,
+        cache,
+        locales: config.locales.unwrap_or_default(),
+        watermarks: config.watermarks.unwrap_or_default(),
+        updates: Updates::default(),
+        base_path: BasePath::default(),
+        views: ViewCounter::default(),
+        load_shedder: LoadShedder::new(&config.concurrency.unwrap_or_default()),
+    }
+}
+
+/// Generate an archive of `size`, and insert its history with the real `stelae update`.
+///
+/// Every archive has its own `SQLite` database in its `.taf` dir, so every test has its own
+/// database, which is removed together with the archive. Only `SQLite` is supported, so
+/// `DATABASE_URL` must not be set.
+pub async fn initialize_archive_with_history(size: generate::Size) -> Result<tempfile::TempDir> {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/fixtures/");
+    let td = Builder::new().tempdir_in(&path)?;
+    generate::generate(td.path(), size)?;
+    changes::insert(
+        &td.path().to_string_lossy(),
+        td.path().to_path_buf(),
+        true,
+        false,
+        Output::Text,
+    )
+    .await
+    .map_err(|err| anyhow::anyhow!("`stelae update` failed: {err:?}"))?;
+    Ok(td)
+}
+
+/// Remove the data repository `repository` from `targets/repositories.json` of the generated
+/// archive at `archive_path`, in a new commit of its authentication repository.
+pub fn remove_repository(archive_path: &Path, repository: &str) -> Result<()> {
+    let repo = git2::Repository::open(archive_path.join("generated/law"))?;
+    let head = repo.head()?.peel_to_commit()?;
+    let tree = head.tree()?;
+    let listed = repo.find_blob(tree.get_path(Path::new("targets/repositories.json"))?.id())?;
+    let mut repositories: serde_json::Value = serde_json::from_slice(listed.content())?;
+    repositories["repositories"]
+        .as_object_mut()
+        .and_then(|listed_repositories| listed_repositories.remove(repository))
+        .ok_or_else(|| anyhow::anyhow!("{repository} is not listed"))?;
+    let content = serde_json::to_string_pretty(&repositories)?;
+    let targets = repo.find_tree(tree.get_path(Path::new("targets"))?.id())?;
+    let mut targets_builder = repo.treebuilder(Some(&targets))?;
+    targets_builder.insert(
+        "repositories.json",
+        repo.blob(content.as_bytes())?,
+        0o100_644,
+    )?;
+    let mut root_builder = repo.treebuilder(Some(&tree))?;
+    root_builder.insert("targets", targets_builder.write()?, 0o040_000)?;
+    let removed = repo.find_tree(root_builder.write()?)?;
+    let signature = git2::Signature::now("Stelae", "stelae@example.com")?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &format!("Remove {repository}"),
+        &removed,
+        &[&head],
+    )?;
+    Ok(())
+}
+
 pub fn initialize_archive(archive_type: ArchiveType) -> Result<tempfile::TempDir> {
     match initialize_archive_without_bare(archive_type) {
         Ok(td) => {
-            if let Err(err) = utils::make_all_git_repos_bare_recursive(&td) {
+            if let Err(err) = utils::make_all_git_repos_bare_recursive(td.path()) {
                 return Err(err);
             }
             Ok(td)
@@ -73,7 +256,7 @@ pub fn initialize_archive_without_bare(archive_type: ArchiveType) -> Result<temp
 
     let td = Builder::new().tempdir_in(&path).unwrap();
 
-    if let Err(err) = archive_testtools::initialize_archive_inner(archive_type, &td) {
+    if let Err(err) = archive_testtools::initialize_archive_inner(archive_type, td.path()) {
         dbg!(&err);
         use std::mem::ManuallyDrop;
         let td = ManuallyDrop::new(td);
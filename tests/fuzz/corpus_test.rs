@@ -0,0 +1,17 @@
+use super::targets::TARGETS;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn test_fuzz_corpus_when_replayed_expect_no_panics() {
+    let corpus_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+    for (target, run) in TARGETS {
+        let mut inputs = 0;
+        for entry in fs::read_dir(corpus_path.join(target)).unwrap() {
+            let input = fs::read(entry.unwrap().path()).unwrap();
+            run(&input);
+            inputs += 1;
+        }
+        assert!(inputs > 0, "corpus of {target} is empty");
+    }
+}
@@ -0,0 +1,4 @@
+#[path = "../../fuzz/src/lib.rs"]
+mod targets;
+
+mod corpus_test;
@@ -5,3 +5,4 @@ mod api;
 mod archive_testtools;
 mod basic;
 mod common;
+mod fuzz;